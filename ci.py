@@ -96,7 +96,11 @@ def run_rustfmt_check():
 
 
 def run_rust_tests():
-    sp.run(["cargo", "test", "--package", "sealvault_core"], check=True)
+    # CI runs on macOS where SQLCipher uses CommonCrypto, so test database encryption as well
+    sp.run(
+        ["cargo", "test", "--package", "sealvault_core", "--features", "sqlcipher"],
+        check=True,
+    )
 
 
 def run_ios_ui_tests():
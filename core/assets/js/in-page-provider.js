@@ -27,6 +27,10 @@
   const SEALVAULT_DEFAULT_CHAIN_ID = "<SEALVAULT_DEFAULT_CHAIN_ID>"
   // Decimal integer string
   const SEALVAULT_DEFAULT_NETWORK_VERSION = "<SEALVAULT_DEFAULT_NETWORK_VERSION>"
+  // Hex encoded JSON of the EIP-6963 provider info
+  const SEALVAULT_WALLET_INFO = "<SEALVAULT_WALLET_INFO>"
+  // Hex encoded URL of an HTTP backend for requests. Empty if the app handles requests.
+  const SEALVAULT_BACKEND_URL = "<SEALVAULT_BACKEND_URL>"
 
   const ETHEREUM_PROVIDER = "ethereum"
   const REQUEST_TIMEOUT_MS = 60 * 1000
@@ -81,6 +85,15 @@
     return new TextDecoder().decode(bytes)
   }
 
+  /**
+   * Verbose logging for development. The body is removed from production scripts.
+   */
+  function debugLog(...args) {
+    // <debug-only>
+    console.debug(...args)
+    // </debug-only>
+  }
+
   /**
    * Generate a cryptographically random v4 UUID.
    *
@@ -209,7 +222,7 @@
    */
   ;(function EthereumProvider() {
 
-    console.debug(`Initializing ${EthereumProvider.name}`)
+    debugLog(`Initializing ${EthereumProvider.name}`)
 
     /**
      * EIP-1193 Provider error object: https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1193.md#rpc-errors
@@ -253,7 +266,7 @@
          * @returns {Promise}
          */
         request(jsonRpcRequest) {
-          console.debug('sv request', jsonRpcRequest)
+          debugLog('sv request', jsonRpcRequest)
           const requestPromise = new Promise((resolve, reject) => {
             const { parent, value: requestHandler } = getPropByString(
               window,
@@ -292,7 +305,7 @@
             // Prevent reflected XSS by passing the result as hexadecimal utf-8 bytes to JS.
             // See the security model in the developer docs for more.
             response = JSON.parse(hexBytesToString(responseHex))
-            console.debug('sv response', response)
+            debugLog('sv response', response)
           } catch (error) {
             // We don't know which request to respond to
             console.error(
@@ -355,6 +368,23 @@
         selectedAddress: null,
       }
 
+      /**
+       * Report the page's name, icon and description to the wallet. The wallet treats it as
+       * untrusted.
+       */
+      function sendDomainMetadata() {
+        const iconLink = document.querySelector('link[rel~="icon"]')
+        const descriptionMeta = document.querySelector('meta[name="description"]')
+        const params = {
+          name: document.title || null,
+          icon: iconLink ? iconLink.href : null,
+          description: descriptionMeta ? descriptionMeta.content : null,
+        }
+        request({ method: "sealvault_sendDomainMetadata", params }).catch((error) =>
+          console.error(error)
+        )
+      }
+
       /**
        * Alias for ethereum.request({ method: 'eth_requestAccounts' }).
        */
@@ -508,6 +538,8 @@
           // Don't trigger "accountsChanged" event as that causes infinite reload
           // on https://fi.woo.org/
           state.selectedAddress = selectedAddress
+
+          sendDomainMetadata()
         }
       )
 
@@ -568,5 +600,50 @@
       // MetaMask defines it as enumerable
       enumerable: true,
     })
+
+    // EIP-6963 multi-wallet discovery lets dapps find SealVault even if an other wallet
+    // defines `window.ethereum`.
+    // https://eips.ethereum.org/EIPS/eip-6963
+    const providerDetail = Object.freeze({
+      info: Object.freeze(JSON.parse(hexBytesToString(SEALVAULT_WALLET_INFO))),
+      provider: EthereumProvider.modules.ethereum,
+    })
+
+    function announceProvider() {
+      window.dispatchEvent(
+        new CustomEvent("eip6963:announceProvider", { detail: providerDetail })
+      )
+    }
+
+    // Dapps that load after the script request the announcement again.
+    window.addEventListener("eip6963:requestProvider", announceProvider)
+    announceProvider()
+  })()
+
+  /**
+   * Post requests to an HTTP backend instead of the app's request handler.
+   * Only used when the script is served by the dev server.
+   */
+  ;(function HttpBridge() {
+    if (!SEALVAULT_BACKEND_URL) {
+      return
+    }
+    const backendUrl = hexBytesToString(SEALVAULT_BACKEND_URL)
+    debugLog(`${HttpBridge.name}: posting requests to ${backendUrl}`)
+
+    async function postMessage(message) {
+      const body = typeof message === "string" ? message : JSON.stringify(message)
+      const response = await fetch(backendUrl, {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+        },
+        body,
+      })
+      const text = await response.text()
+      window[SEALVAULT_RPC_PROVIDER].respond(text)
+    }
+
+    window[SEALVAULT_REQUEST_HANDLER] = postMessage
   })()
 })()
@@ -0,0 +1,22 @@
+CREATE TABLE transactions
+(
+    deterministic_id TEXT PRIMARY KEY NOT NULL,
+
+    chain_id         TEXT            NOT NULL,
+    -- Hex encoded with 0x prefix
+    tx_hash          TEXT            NOT NULL,
+    from_address_id  TEXT            NOT NULL,
+    -- Checksum address. Null for contract deployments.
+    to_address       TEXT,
+
+    -- RFC 3339 timestamps
+    created_at       TEXT             NOT NULL,
+    updated_at       TEXT,
+
+    FOREIGN KEY (chain_id) REFERENCES chains (deterministic_id),
+    FOREIGN KEY (from_address_id) REFERENCES addresses (deterministic_id),
+
+    UNIQUE (chain_id, tx_hash)
+);
+
+CREATE INDEX IF NOT EXISTS transactions_from_address_id_idx on transactions (from_address_id);
@@ -0,0 +1,2 @@
+-- JSON serialized profile settings.
+ALTER TABLE profiles ADD COLUMN user_settings TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1 @@
+ALTER TABLE local_dapp_sessions DROP COLUMN trust_level;
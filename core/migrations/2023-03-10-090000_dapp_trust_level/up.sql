@@ -0,0 +1,3 @@
+-- Trusted dapps skip the transaction approval prompt for low value, unflagged transactions.
+ALTER TABLE local_dapp_sessions ADD COLUMN trust_level TEXT NOT NULL DEFAULT 'standard'
+    CHECK (trust_level IN ('standard', 'trusted'));
@@ -0,0 +1 @@
+DROP TABLE dapp_metadata;
@@ -0,0 +1,16 @@
+-- Metadata reported by the dapp itself. It's untrusted and must never be used to identify
+-- the dapp, that's what `dapps.identifier` is for.
+CREATE TABLE dapp_metadata
+(
+    dapp_id     TEXT PRIMARY KEY NOT NULL,
+
+    name        TEXT,
+    icon_url    TEXT,
+    description TEXT,
+
+    -- RFC 3339 timestamps
+    created_at  TEXT             NOT NULL,
+    updated_at  TEXT,
+
+    FOREIGN KEY (dapp_id) REFERENCES dapps (deterministic_id)
+);
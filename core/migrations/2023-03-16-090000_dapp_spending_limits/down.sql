@@ -0,0 +1,2 @@
+DROP TABLE local_dapp_spends;
+ALTER TABLE local_dapp_sessions DROP COLUMN spending_limit;
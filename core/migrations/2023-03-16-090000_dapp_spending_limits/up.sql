@@ -0,0 +1,24 @@
+-- JSON serialized spending limit of the dapp. Null means no limit.
+ALTER TABLE local_dapp_sessions ADD COLUMN spending_limit TEXT;
+
+-- Amounts spent by dapps to enforce spending limits over a rolling window.
+CREATE TABLE local_dapp_spends
+(
+    uuid          TEXT PRIMARY KEY NOT NULL,
+
+    session_id    TEXT             NOT NULL,
+    chain_id      TEXT             NOT NULL,
+    -- Checksum address of the fungible token contract. Null for the native token.
+    token_address TEXT,
+    -- Decimal amount in the smallest denomination of the token.
+    amount        TEXT             NOT NULL,
+
+    -- RFC 3339 timestamp
+    created_at    TEXT             NOT NULL,
+
+    FOREIGN KEY (session_id) REFERENCES local_dapp_sessions (uuid),
+    FOREIGN KEY (chain_id) REFERENCES chains (deterministic_id)
+);
+
+CREATE INDEX IF NOT EXISTS local_dapp_spends_session_id_created_at_idx
+    on local_dapp_spends (session_id, created_at);
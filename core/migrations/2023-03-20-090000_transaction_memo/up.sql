@@ -0,0 +1,2 @@
+-- Note attached to a transfer by the user for their own records. Never broadcast.
+ALTER TABLE transactions ADD COLUMN memo TEXT;
@@ -0,0 +1 @@
+ALTER TABLE local_settings DROP COLUMN last_restored_backup_timestamp;
@@ -0,0 +1,3 @@
+-- Unix timestamp of the most recent backup restored on this device. Restoring older backups is
+-- rejected to prevent rolling back state with a superseded backup.
+ALTER TABLE local_settings ADD COLUMN last_restored_backup_timestamp BIGINT;
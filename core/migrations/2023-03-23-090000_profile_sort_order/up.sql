@@ -0,0 +1,8 @@
+-- User chosen position of the profile in listings. Ascending.
+ALTER TABLE profiles ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0;
+-- Keep the creation order for existing profiles.
+UPDATE profiles SET sort_order = (
+    SELECT COUNT(*) FROM profiles AS p
+    WHERE p.created_at < profiles.created_at
+        OR (p.created_at = profiles.created_at AND p.deterministic_id < profiles.deterministic_id)
+);
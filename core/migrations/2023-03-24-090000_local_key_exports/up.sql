@@ -0,0 +1,15 @@
+-- Private key exports on this device for auditing and rate limiting. Never contains key material.
+CREATE TABLE local_key_exports
+(
+    uuid       TEXT PRIMARY KEY NOT NULL,
+
+    address_id TEXT             NOT NULL,
+
+    -- RFC 3339 timestamp
+    created_at TEXT             NOT NULL,
+
+    FOREIGN KEY (address_id) REFERENCES addresses (deterministic_id)
+);
+
+CREATE INDEX IF NOT EXISTS local_key_exports_created_at_idx
+    on local_key_exports (created_at);
@@ -0,0 +1,2 @@
+ALTER TABLE asymmetric_keys DROP COLUMN pin_order;
+ALTER TABLE asymmetric_keys DROP COLUMN pinned;
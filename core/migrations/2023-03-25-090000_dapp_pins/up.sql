@@ -0,0 +1,4 @@
+-- Dapps pinned by the user are listed first in the profile. Ascending pin order, null if the
+-- dapp isn't pinned.
+ALTER TABLE asymmetric_keys ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE asymmetric_keys ADD COLUMN pin_order INTEGER;
@@ -0,0 +1,21 @@
+-- User assigned tags of the dapps in a profile, eg. "DeFi" or "NFT".
+CREATE TABLE dapp_tags
+(
+    deterministic_id TEXT PRIMARY KEY NOT NULL,
+
+    profile_id       TEXT             NOT NULL,
+    dapp_id          TEXT             NOT NULL,
+    -- Tags are compared case-insensitively.
+    tag              TEXT             NOT NULL COLLATE NOCASE,
+
+    -- RFC 3339 timestamps
+    created_at       TEXT             NOT NULL,
+    updated_at       TEXT,
+
+    FOREIGN KEY (profile_id) REFERENCES profiles (deterministic_id),
+    FOREIGN KEY (dapp_id) REFERENCES dapps (deterministic_id),
+
+    UNIQUE (profile_id, dapp_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS dapp_tags_profile_id_tag_idx on dapp_tags (profile_id, tag);
@@ -0,0 +1,14 @@
+ALTER TABLE local_settings ADD COLUMN backup_version INTEGER NOT NULL default 0;
+ALTER TABLE local_settings ADD COLUMN backup_completed_at TEXT;
+
+UPDATE local_settings
+SET backup_version = coalesce(
+    (SELECT json_extract(value, '$') FROM local_settings_values WHERE key = 'backup_version'),
+    0
+);
+UPDATE local_settings
+SET backup_completed_at = (
+    SELECT json_extract(value, '$') FROM local_settings_values WHERE key = 'backup_completed_at'
+);
+
+DROP TABLE local_settings_values;
@@ -0,0 +1,23 @@
+-- Typed local settings stored as JSON values by key. Not part of backups.
+CREATE TABLE local_settings_values
+(
+    key        TEXT PRIMARY KEY NOT NULL,
+    -- JSON
+    value      TEXT             NOT NULL,
+
+    -- RFC 3339 timestamp
+    updated_at TEXT             NOT NULL
+);
+
+INSERT INTO local_settings_values (key, value, updated_at)
+SELECT 'backup_version', json_quote(backup_version), strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+FROM local_settings;
+
+INSERT INTO local_settings_values (key, value, updated_at)
+SELECT 'backup_completed_at', json_quote(backup_completed_at),
+       strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+FROM local_settings
+WHERE backup_completed_at IS NOT NULL;
+
+ALTER TABLE local_settings DROP COLUMN backup_version;
+ALTER TABLE local_settings DROP COLUMN backup_completed_at;
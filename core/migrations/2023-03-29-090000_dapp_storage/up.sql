@@ -0,0 +1,25 @@
+-- Encrypted key-value storage of a dapp in a profile, eg. session tokens or API keys.
+CREATE TABLE dapp_storage
+(
+    deterministic_id TEXT PRIMARY KEY NOT NULL,
+
+    profile_id       TEXT             NOT NULL,
+    dapp_id          TEXT             NOT NULL,
+    key              TEXT             NOT NULL,
+    -- The value is encrypted with a key derived from this DEK for the profile. The associated
+    -- data binds the value to the profile, the dapp and the key.
+    dek_id           TEXT             NOT NULL,
+    encrypted_value  BLOB             NOT NULL,
+    -- Plaintext size in bytes for the storage quota of the dapp.
+    value_size       INTEGER          NOT NULL,
+
+    -- RFC 3339 timestamps
+    created_at       TEXT             NOT NULL,
+    updated_at       TEXT,
+
+    FOREIGN KEY (profile_id) REFERENCES profiles (deterministic_id),
+    FOREIGN KEY (dapp_id) REFERENCES dapps (deterministic_id),
+    FOREIGN KEY (dek_id) REFERENCES data_encryption_keys (deterministic_id),
+
+    UNIQUE (profile_id, dapp_id, key)
+);
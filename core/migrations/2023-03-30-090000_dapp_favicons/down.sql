@@ -0,0 +1,2 @@
+ALTER TABLE dapps DROP COLUMN favicon_id;
+DROP TABLE dapp_favicons;
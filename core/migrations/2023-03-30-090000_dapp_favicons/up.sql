@@ -0,0 +1,14 @@
+-- Favicons of dapps. Identical images are only stored once.
+CREATE TABLE dapp_favicons
+(
+    deterministic_id TEXT PRIMARY KEY NOT NULL,
+
+    image_hash       BLOB UNIQUE      NOT NULL,
+    image            BLOB             NOT NULL,
+
+    -- RFC 3339 timestamps
+    created_at       TEXT             NOT NULL,
+    updated_at       TEXT
+);
+
+ALTER TABLE dapps ADD COLUMN favicon_id TEXT REFERENCES dapp_favicons (deterministic_id);
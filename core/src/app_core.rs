@@ -2,24 +2,42 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::{collections::HashSet, fmt::Debug, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
+use diesel::SqliteConnection;
+use ethers::types::U256;
 use rand::seq::IteratorRandom;
 use typed_builder::TypedBuilder;
+use url::Url;
 
 use crate::{
-    assets::{list_profile_pics, load_profile_pic},
+    assets::ProfilePicRegistry,
     async_runtime as rt, backup,
     backup::{BackupError, BackupStorageI},
+    config, dapp_manifest,
+    dapp_manifest::DappImportResult,
     db::{
         data_migrations, models as m, schema_migrations::run_migrations, ConnectionPool,
-        DeterministicId,
+        DbEncryptionKey, DeferredTxConnection, DeterministicId, IntegrityCheck,
     },
+    demo,
     device::{DeviceIdentifier, DeviceName},
     dto,
     encryption::Keychain,
     error::Error,
+    fiat,
+    fiat::{CorePriceFeedI, FiatCurrency, HostPriceFeed},
     http_client::HttpClient,
+    ipfs::IpfsGatewayHealth,
+    key_export,
+    key_export::PrivateKeyHex,
+    metrics::MetricsSnapshot,
     protocols::{
         eth,
         eth::{
@@ -29,8 +47,14 @@ use crate::{
     },
     public_suffix_list::PublicSuffixList,
     resources::{CoreResources, CoreResourcesI},
+    storage_usage,
+    storage_usage::StorageBreakdown,
     ui_callback::TokenTransferResult,
-    CoreError, CoreUICallbackI, DappApprovalParams,
+    utils::{is_dir_writable, is_https_or_loopback, rfc3339_timestamp_from_unix},
+    version_info,
+    version_info::VersionInfo,
+    CoreError, CoreUICallbackI, DappApprovalParams, DappSignBatchApprovalParams,
+    DappTransactionApprovalParams,
 };
 
 /// Provides cross-platform key and transaction management.
@@ -44,40 +68,89 @@ pub struct AppCore {
 }
 
 impl AppCore {
-    // UI callbacks cannot be part of the args struct, because Uniffi expects it to be hashable
-    // then.
+    // Callback interfaces cannot be part of the args struct, because Uniffi expects it to be
+    // hashable then.
     pub fn new(
         args: CoreArgs,
         backup_storage: Box<dyn BackupStorageI>,
         ui_callbacks: Box<dyn CoreUICallbackI>,
+        transaction_inspector: Box<dyn eth::CoreTransactionInspectorI>,
+        price_feed: Box<dyn CorePriceFeedI>,
     ) -> Result<Self, CoreError> {
         // No-op if logger has been already initialized.
         let _ = env_logger::try_init();
 
-        let rpc_manager = Box::new(eth::RpcManager::new());
-        let connection_pool = ConnectionPool::new(&args.db_file_path)?;
-        let keychain = Keychain::new();
+        let rpc_manager = Box::new(eth::RpcManager::new_with_logging(args.log_rpc));
+        let db_encryption_key: Option<DbEncryptionKey> = args
+            .db_encryption_key
+            .as_deref()
+            .map(|key| key.parse())
+            .transpose()?;
+        let connection_pool =
+            ConnectionPool::new_with_key(&args.db_file_path, db_encryption_key)?;
+        let keychain = new_keychain(&args.db_file_path)?;
         let public_suffix_list = PublicSuffixList::new()?;
-        let http_client = HttpClient::new(args.cache_dir);
+        // Caching is optional, so the core works without a writable cache dir.
+        let cache_dir = PathBuf::from(&args.cache_dir);
+        let cache_dir_writable = is_dir_writable(&cache_dir);
+        let backup_spool = backup::BackupSpool::new(&args.cache_dir, cache_dir_writable);
+        let http_client = if cache_dir_writable {
+            HttpClient::new(args.cache_dir)
+        } else {
+            log::warn!("The cache dir is not writable. HTTP responses won't be cached.");
+            HttpClient::new_without_cache()
+        };
 
         let CoreArgs {
             device_name,
             device_id,
+            default_fiat_currency,
+            disabled_methods,
+            approval_timeout_seconds,
+            max_decoded_calldata_bytes,
+            max_backup_size_bytes,
+            app_version,
+            demo,
             ..
         } = args;
         let device_id: DeviceIdentifier = device_id.try_into()?;
         let device_name: DeviceName = device_name.try_into()?;
+        let default_fiat_currency: FiatCurrency = default_fiat_currency.parse()?;
 
         let resources = CoreResources::builder()
             .ui_callbacks(ui_callbacks)
+            .transaction_inspector(Box::new(eth::HostTransactionInspector::new(
+                transaction_inspector,
+            )))
+            .price_feed(Box::new(HostPriceFeed::new(price_feed)))
             .rpc_manager(rpc_manager)
             .connection_pool(connection_pool)
             .keychain(keychain)
             .http_client(http_client)
             .public_suffix_list(public_suffix_list)
             .backup_storage(backup_storage)
+            .backup_spool(backup_spool)
             .device_id(device_id)
             .device_name(device_name)
+            .default_fiat_currency(default_fiat_currency)
+            .disabled_methods(disabled_methods.unwrap_or_else(default_disabled_methods))
+            .approval_timeout(
+                approval_timeout_seconds
+                    .map(Duration::from_secs)
+                    .unwrap_or(config::DEFAULT_APPROVAL_TIMEOUT),
+            )
+            .max_decoded_calldata_bytes(
+                max_decoded_calldata_bytes
+                    .map(|max_bytes| max_bytes as usize)
+                    .unwrap_or(config::DEFAULT_MAX_DECODED_CALLDATA_BYTES),
+            )
+            .max_backup_size_bytes(
+                max_backup_size_bytes.unwrap_or(config::DEFAULT_MAX_BACKUP_SIZE_BYTES),
+            )
+            .app_version(app_version)
+            .demo(demo)
+            .cache_dir_writable(cache_dir_writable)
+            .cache_dir(cache_dir)
             .build();
 
         Self::new_with_overrides(Arc::new(resources))
@@ -95,6 +168,10 @@ impl AppCore {
         self.resources.keychain()
     }
 
+    fn profile_pics(&self) -> &ProfilePicRegistry {
+        self.resources.profile_pics()
+    }
+
     fn rpc_manager(&self) -> &dyn eth::RpcManagerI {
         self.resources.rpc_manager()
     }
@@ -107,6 +184,14 @@ impl AppCore {
     pub fn new_with_overrides(
         resources: Arc<dyn CoreResourcesI>,
     ) -> Result<Self, CoreError> {
+        // Fail with a distinct error on a corrupt DB file, so that the host can offer restoring
+        // from a backup instead of running migrations on damaged data. The full check is too
+        // slow for large DBs on every launch, the host can run it with
+        // `check_database_integrity`.
+        resources
+            .connection_pool()
+            .check_integrity(IntegrityCheck::Quick)?;
+
         // Run DB schema migrations and data migrations that haven't been applied yet.
         resources
             .connection_pool()
@@ -118,6 +203,9 @@ impl AppCore {
                     resources.public_suffix_list(),
                 )
             })?;
+        if resources.is_demo() {
+            demo::seed_demo_data(resources.as_ref())?;
+        }
 
         Ok(AppCore { resources })
     }
@@ -135,6 +223,8 @@ impl AppCore {
         // Check if we can create backups after enabling them.
         match backup::create_backup(self.resources.as_ref()) {
             Ok(_) => Ok(()),
+            // Backups work, but the first one is only uploaded when storage is reachable.
+            Err(BackupError::BackupSpooled) => Err(BackupError::BackupSpooled),
             Err(error) => {
                 // If we can't create backups, disable.
                 self.disable_backup()?;
@@ -143,6 +233,18 @@ impl AppCore {
         }
     }
 
+    /// Upload backups that failed to upload when they were created, eg. because the network
+    /// was down. Should be called when connectivity is restored.
+    pub fn flush_pending_backups(&self) -> Result<(), BackupError> {
+        backup::flush_pending_backups(self.resources.as_ref())
+    }
+
+    /// Manually retry uploading the last backup if it isn't uploaded. Creates a new backup if
+    /// the file of the last backup is gone. No-op if the last backup is already uploaded.
+    pub fn resend_backup(&self) -> Result<(), BackupError> {
+        backup::resend_backup(self.resources.as_ref())
+    }
+
     pub fn disable_backup(&self) -> Result<(), CoreError> {
         backup::disable_backup(self.resources.as_ref())?;
         Ok(())
@@ -155,6 +257,121 @@ impl AppCore {
         Ok(result)
     }
 
+    /// Whether the host should remind the user to back up. Call on app launch.
+    pub fn backup_reminder_status(&self) -> Result<backup::BackupReminder, CoreError> {
+        let result = backup::backup_reminder_status(self.resources.as_ref())?;
+        Ok(result)
+    }
+
+    /// Estimate the size of the next backup in bytes without creating it. The backup may be
+    /// smaller, because it's compressed when that saves space.
+    pub fn estimate_backup_size(&self) -> Result<u64, CoreError> {
+        let result = backup::estimate_backup_size(self.resources.as_ref())?;
+        Ok(result)
+    }
+
+    /// Storage usage by category, eg. for a storage management screen. The DB categories add
+    /// up to the size of the DB which is what goes into a backup.
+    pub fn storage_breakdown(&self) -> Result<StorageBreakdown, CoreError> {
+        let result = storage_usage::storage_breakdown(self.resources.as_ref())?;
+        Ok(result)
+    }
+
+    /// Check that the database file isn't corrupt. Fails with `CoreError::DatabaseCorrupt` if it
+    /// is, in which case the host should offer restoring from a backup.
+    /// Runs the full integrity check which takes a while for large DBs, so it should be called
+    /// on idle.
+    pub fn check_database_integrity(&self) -> Result<(), CoreError> {
+        self.connection_pool()
+            .check_integrity(IntegrityCheck::Full)?;
+        Ok(())
+    }
+
+    /// List backups in the backup storage grouped by the device that created them.
+    pub fn list_backups_grouped(&self) -> Vec<backup::DeviceBackups> {
+        backup::list_backups_grouped(self.resources.backup_storage())
+    }
+
+    /// Compare two backups in the backup storage, eg. to find out why a restore is missing
+    /// something. Both backups are decrypted with the password. The app state is unchanged.
+    pub fn diff_backups(
+        &self,
+        first_backup_file_name: String,
+        second_backup_file_name: String,
+        password: String,
+    ) -> Result<backup::BackupDiff, BackupError> {
+        backup::diff_backups(
+            self.resources.backup_storage(),
+            self.resources.keychain(),
+            self.resources.max_backup_size_bytes(),
+            self.resources.app_version(),
+            first_backup_file_name,
+            second_backup_file_name,
+            &password,
+        )
+    }
+
+    /// List backups in the backup storage that have the same device id and backup version,
+    /// so that the user can resolve them.
+    pub fn detect_backup_conflicts(&self) -> Vec<backup::BackupConflict> {
+        backup::detect_backup_conflicts(self.resources.backup_storage())
+    }
+
+    /// Request exporting the private key of an address of the profile. The user must
+    /// authenticate through the UI callbacks first. Returns a single use token that must be
+    /// passed to `export_private_key` after the user confirmed the export.
+    pub fn request_private_key_export(
+        &self,
+        profile_id: String,
+        address_id: String,
+    ) -> Result<String, CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let address_id: m::AddressId = address_id.parse()?;
+        let token = key_export::request_private_key_export(
+            self.resources.as_ref(),
+            &profile_id,
+            &address_id,
+        )?;
+        Ok(token)
+    }
+
+    /// Export the hex encoded private key of an address of the profile. The confirmation token
+    /// is from `request_private_key_export`. Exports are rate limited and recorded locally.
+    pub fn export_private_key(
+        &self,
+        profile_id: String,
+        address_id: String,
+        confirmation_token: String,
+    ) -> Result<PrivateKeyHex, CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let address_id: m::AddressId = address_id.parse()?;
+        let private_key = key_export::export_private_key(
+            self.resources.as_ref(),
+            &profile_id,
+            &address_id,
+            &confirmation_token,
+        )?;
+        Ok(private_key)
+    }
+
+    /// Set the gateways to fetch `ipfs://` content from. They're tried in order on failure.
+    /// At least one gateway is required. The gateways are not persisted.
+    pub fn set_ipfs_gateways(&self, urls: Vec<String>) -> Result<(), CoreError> {
+        self.resources.ipfs_gateways().set_gateways(&urls)?;
+        Ok(())
+    }
+
+    /// The health of the IPFS gateways in the configured order.
+    pub fn ipfs_gateway_health(&self) -> Result<Vec<IpfsGatewayHealth>, CoreError> {
+        let res = self.resources.ipfs_gateways().health()?;
+        Ok(res)
+    }
+
+    /// Aggregate usage counts since the core was started for product metrics.
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        self.resources.metrics().snapshot()
+    }
+
     pub fn display_backup_password(&self) -> Result<String, CoreError> {
         let res = backup::display_backup_password(self.keychain())?;
         Ok(res)
@@ -165,6 +382,34 @@ impl AppCore {
         Ok(res)
     }
 
+    /// Reclaim the space of deleted rows and update the query planner statistics, eg. when the
+    /// app is idle. Returns the number of bytes reclaimed. Fails with a retriable error while a
+    /// backup is created or restored.
+    pub fn optimize_database(&self) -> Result<u64, CoreError> {
+        if backup::is_backup_in_progress(self.resources.as_ref())
+            || self.resources.restore_status().status()?
+                == backup::RestoreStatus::InProgress
+        {
+            return Err(CoreError::Retriable {
+                error: "Can't optimize the database while a backup is in progress".into(),
+            });
+        }
+        let reclaimed = self.connection_pool().optimize()?;
+        Ok(reclaimed)
+    }
+
+    /// Status of the most recent backup restore of this core.
+    pub fn restore_status(&self) -> Result<backup::RestoreStatus, CoreError> {
+        let res = self.resources.restore_status().status()?;
+        Ok(res)
+    }
+
+    /// Versions of the app, the database schema and the backup for diagnostics.
+    pub fn version_info(&self) -> Result<VersionInfo, CoreError> {
+        let res = version_info::version_info(self.resources.as_ref())?;
+        Ok(res)
+    }
+
     pub fn list_profiles(&self) -> Result<Vec<dto::CoreProfile>, CoreError> {
         let res = self.assembler().assemble_profiles()?;
         Ok(res)
@@ -183,24 +428,99 @@ impl AppCore {
         Ok(())
     }
 
+    /// List the ids of the Ethereum chains that a profile has submitted transactions on, ordered
+    /// by most recent activity first.
+    pub fn active_chains_for_profile(
+        &self,
+        profile_id: String,
+    ) -> Result<Vec<u64>, CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let mut conn = self.connection_pool().connection()?;
+        let chain_ids =
+            m::Transaction::list_active_eth_chains_for_profile(&mut conn, &profile_id)?;
+        Ok(chain_ids.into_iter().map(Into::into).collect())
+    }
+
     pub fn create_profile(
         &self,
         name: String,
         bundled_picture_name: String,
     ) -> Result<(), CoreError> {
         let name: m::ProfileName = name.try_into()?;
+        let bundled_picture = self.profile_pics().load(&bundled_picture_name)?;
         self.connection_pool().deferred_transaction(|mut tx_conn| {
             m::Profile::create_eth_profile(
                 &mut tx_conn,
                 self.keychain(),
                 &name,
                 &bundled_picture_name,
+                &bundled_picture,
             )?;
             Ok(())
         })?;
         Ok(())
     }
 
+    /// Download a PNG image and set it as the picture of the profile, eg. to use a web avatar.
+    pub fn set_profile_picture_from_url(
+        &self,
+        profile_id: String,
+        url: String,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let url = Url::parse(&url).map_err(|_| Error::User {
+            explanation: format!("Invalid picture URL: '{url}'"),
+        })?;
+        // Download outside of the transaction.
+        let picture_id = {
+            let mut conn = self.connection_pool().connection()?;
+            m::ProfilePicture::insert_from_url(
+                &mut conn,
+                self.resources.http_client(),
+                url,
+            )?
+        };
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            let profile =
+                m::Profile::fetch(tx_conn.as_mut(), &profile_id)?.ok_or_else(|| {
+                    Error::User {
+                        explanation: format!("Profile not found: '{profile_id}'"),
+                    }
+                })?;
+            profile.set_picture_id(tx_conn.as_mut(), &picture_id)
+        })?;
+        Ok(())
+    }
+
+    /// Persist a user chosen order of the profiles that `list_profiles` respects.
+    /// The ids must contain each profile exactly once. New profiles are appended.
+    pub fn reorder_profiles(&self, ordered_ids: Vec<String>) -> Result<(), CoreError> {
+        let ordered_ids = ordered_ids
+            .into_iter()
+            .map(|profile_id| profile_id.parse())
+            .collect::<Result<Vec<DeterministicId>, _>>()?;
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::Profile::reorder(&mut tx_conn, &ordered_ids)
+        })?;
+        Ok(())
+    }
+
+    /// Register additional profile pictures by name that can be used as bundled profile pictures.
+    /// The pictures must be PNG images.
+    pub fn register_profile_pictures(
+        &self,
+        pictures: HashMap<String, Vec<u8>>,
+    ) -> Result<(), CoreError> {
+        self.profile_pics().register(pictures)?;
+        Ok(())
+    }
+
+    /// List the names of the bundled and registered profile pictures.
+    pub fn list_available_profile_pictures(&self) -> Result<Vec<String>, CoreError> {
+        let names = self.profile_pics().list_available()?;
+        Ok(names)
+    }
+
     /// Return the name of a random bundled profile picture that can be used for a new profile.
     /// Returns none if there are no unused.
     pub fn random_bundled_profile_picture(&self) -> Result<Option<String>, CoreError> {
@@ -208,7 +528,8 @@ impl AppCore {
         let taken_names: HashSet<String> = m::ProfilePicture::list_names(&mut conn)?
             .into_iter()
             .collect();
-        let bundled_names: HashSet<String> = list_profile_pics().into_iter().collect();
+        let bundled_names: HashSet<String> =
+            self.profile_pics().list_available()?.into_iter().collect();
 
         let res = bundled_names
             .difference(&taken_names)
@@ -221,19 +542,76 @@ impl AppCore {
         &self,
         picture_name: String,
     ) -> Result<Vec<u8>, CoreError> {
-        let picture = load_profile_pic(&picture_name)?;
+        let picture = self.profile_pics().load(&picture_name)?;
         Ok(picture)
     }
 
+    /// Fetch the tokens of an address. If token auto-discovery is enabled for the profile,
+    /// new tokens of profile wallets are discovered in the background and they are returned
+    /// on a later fetch.
     pub fn tokens_for_address_id(
         &self,
         address_id: String,
     ) -> Result<dto::CoreTokens, CoreError> {
         let address_id: m::AddressId = address_id.try_into()?;
+        if let Err(err) = self.auto_discover_tokens(&address_id) {
+            log::warn!("Failed to discover tokens for address id {address_id}: {err:?}");
+        }
         let res = self.assembler().tokens_for_address_id(address_id)?;
         Ok(res)
     }
 
+    /// Delete the local activity history of a profile matching the filter and bump the backup
+    /// version. On-chain data is unaffected.
+    pub fn clear_history(
+        &self,
+        profile_id: String,
+        filter: HistoryFilter,
+    ) -> Result<ClearedHistory, CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let (dapp_id, created_before) = match filter {
+            HistoryFilter::All => (None, None),
+            HistoryFilter::Dapp { dapp_id } => (Some(dapp_id.parse()?), None),
+            HistoryFilter::OlderThan { timestamp } => {
+                (None, Some(rfc3339_timestamp_from_unix(timestamp)?))
+            }
+        };
+        let res = self
+            .connection_pool()
+            .exclusive_transaction(|mut tx_conn| {
+                let transactions = m::Transaction::delete_for_profile(
+                    tx_conn.as_mut(),
+                    &profile_id,
+                    dapp_id.as_ref(),
+                    created_before.as_deref(),
+                )?;
+                m::LocalSettings::increment_backup_version(&mut tx_conn)?;
+                Ok(ClearedHistory {
+                    transactions: transactions as u64,
+                })
+            })?;
+        Ok(res)
+    }
+
+    /// List a page of the NFTs of an address. NFTs can be filtered by contract address and
+    /// token standard.
+    pub fn list_nfts(&self, args: ListNftsArgs) -> Result<dto::CoreNFTPage, CoreError> {
+        let address_id: m::AddressId = args.address_id.parse()?;
+        let contract_address: Option<eth::ChecksumAddress> = args
+            .contract_address
+            .map(|address| address.parse())
+            .transpose()?;
+        let query = eth::NftQuery::builder()
+            .contract_address(contract_address)
+            .standard(args.standard)
+            .sort(args.sort)
+            .cursor(args.cursor)
+            .limit(args.limit)
+            .build();
+        let res = self.assembler().list_nfts(&address_id, &query)?;
+        Ok(res)
+    }
+
     pub fn fetch_address(&self, address_id: String) -> Result<dto::CoreAddress, Error> {
         let address_id: m::AddressId = address_id.parse()?;
         let result = self.assembler().assemble_address(&address_id)?;
@@ -253,22 +631,58 @@ impl AppCore {
         &self,
         rpc_provider_name: String,
         request_handler_name: String,
+        wallet_name: String,
+        wallet_icon: String,
+        wallet_rdns: String,
+        options: in_page_provider::ScriptOptions,
     ) -> Result<String, CoreError> {
         let res = in_page_provider::load_in_page_provider_script(
             &rpc_provider_name,
             &request_handler_name,
+            &wallet_name,
+            &wallet_icon,
+            &wallet_rdns,
+            &options,
         )?;
         Ok(res)
     }
 
+    /// Process an in-page request. Pass a request id to be able to cancel the request with
+    /// `cancel_request`.
     pub fn in_page_request(
         &self,
         context: Box<dyn InPageRequestContextI>,
         raw_request: String,
+        request_id: Option<String>,
+    ) -> Result<(), CoreError> {
+        let resources = self.resources.clone();
+        let provider = DappKeyProvider::new(resources, context)?;
+        match request_id {
+            Some(request_id) => {
+                let pending_requests = self.resources.pending_requests();
+                pending_requests.register(&request_id, &raw_request)?;
+                let provider = provider.with_request_id(Some(request_id.clone()));
+                let task = provider.in_page_request(raw_request);
+                pending_requests.set_task(&request_id, task)?;
+            }
+            None => {
+                provider.in_page_request(raw_request);
+            }
+        }
+        Ok(())
+    }
+
+    /// Cancel an in-page request by the id that was passed to `in_page_request`, eg. when the
+    /// user navigates away. The dapp receives a cancellation error and later approval
+    /// responses from the UI for the request are ignored. No-op if the request was completed.
+    pub fn cancel_request(
+        &self,
+        context: Box<dyn InPageRequestContextI>,
+        request_id: String,
     ) -> Result<(), CoreError> {
         let resources = self.resources.clone();
         let provider = DappKeyProvider::new(resources, context)?;
-        provider.in_page_request(raw_request);
+        provider.cancel_request(request_id);
         Ok(())
     }
 
@@ -294,14 +708,63 @@ impl AppCore {
         Ok(())
     }
 
+    pub fn user_approved_dapp_transaction(
+        &self,
+        context: Box<dyn InPageRequestContextI>,
+        tx_approval: DappTransactionApprovalParams,
+    ) -> Result<(), CoreError> {
+        let resources = self.resources.clone();
+        let provider = DappKeyProvider::new(resources, context)?;
+        provider.user_approved_dapp_transaction(tx_approval);
+        Ok(())
+    }
+
+    pub fn user_rejected_dapp_transaction(
+        &self,
+        context: Box<dyn InPageRequestContextI>,
+        tx_approval: DappTransactionApprovalParams,
+    ) -> Result<(), CoreError> {
+        let resources = self.resources.clone();
+        let provider = DappKeyProvider::new(resources, context)?;
+        provider.user_rejected_dapp_transaction(tx_approval);
+        Ok(())
+    }
+
+    /// Sign the items of the batch that are approved. Rejects the batch if none are approved.
+    pub fn user_approved_dapp_sign_batch(
+        &self,
+        context: Box<dyn InPageRequestContextI>,
+        batch_approval: DappSignBatchApprovalParams,
+    ) -> Result<(), CoreError> {
+        let resources = self.resources.clone();
+        let provider = DappKeyProvider::new(resources, context)?;
+        provider.user_approved_dapp_sign_batch(batch_approval);
+        Ok(())
+    }
+
+    pub fn user_rejected_dapp_sign_batch(
+        &self,
+        context: Box<dyn InPageRequestContextI>,
+        batch_approval: DappSignBatchApprovalParams,
+    ) -> Result<(), CoreError> {
+        let resources = self.resources.clone();
+        let provider = DappKeyProvider::new(resources, context)?;
+        provider.user_rejected_dapp_sign_batch(batch_approval);
+        Ok(())
+    }
+
     /// Transfer native token on an Ethereum protocol network.
     pub fn eth_transfer_native_token(
         &self,
         args: EthTransferNativeTokenArgs,
     ) -> Result<(), CoreError> {
+        validate_transfer_memo(args.memo.as_deref())?;
         let from_address_id: m::AddressId = args.from_address_id.clone().try_into()?;
-        let to_address: eth::ChecksumAddress =
-            args.to_checksum_address.clone().try_into()?;
+        let to_address = parse_transfer_address(
+            &*self.resources,
+            &from_address_id,
+            &args.to_checksum_address,
+        )?;
         let signing_key = fetch_eth_signing_key_for_transfer(
             &*self.resources,
             &from_address_id,
@@ -312,13 +775,44 @@ impl AppCore {
             signing_key.chain_id,
             &args.amount_decimal,
         )?;
-        let rpc_provider = self.rpc_manager().eth_api_provider(signing_key.chain_id);
-        let tx_hash_res =
-            rpc_provider.transfer_native_token(&signing_key, to_address, &amount);
+        if !args.confirmed_high_value {
+            let settings = self.fetch_profile_settings_for_address(&from_address_id)?;
+            if settings.is_eth_high_value(&amount) {
+                let threshold = settings.eth_high_value_threshold(amount.chain_id);
+                return Err(CoreError::User {
+                    explanation: format!(
+                        "Transfers above {} {} must be confirmed",
+                        threshold.display_amount(),
+                        amount.chain_id.native_token().symbol()
+                    ),
+                });
+            }
+        }
+        let (tx_hash_res, queue_entry) = if self.resources.is_demo() {
+            (demo::synthetic_tx_hash(self.resources.rng()), None)
+        } else {
+            let rpc_provider = self.rpc_manager().eth_api_provider(signing_key.chain_id);
+            let res = rpc_provider
+                .native_token_transfer_tx(&signing_key, to_address, &amount)
+                .and_then(|tx| {
+                    send_queued_transfer(
+                        &*self.resources,
+                        &from_address_id,
+                        &signing_key,
+                        tx,
+                    )
+                });
+            split_queued_transfer_result(res)
+        };
 
         let resources = self.resources.clone();
         rt::spawn_blocking(move || {
-            let res = token_transfer_callbacks(resources, args.into(), tx_hash_res);
+            let res = token_transfer_callbacks(
+                resources,
+                args.into(),
+                tx_hash_res,
+                queue_entry,
+            );
             if let Some(err) = res.err() {
                 log::error!(
                     "Failed to call native token transfer callbacks due to error: {err:?}"
@@ -329,34 +823,149 @@ impl AppCore {
         Ok(())
     }
 
+    /// Preview a native token transfer on an Ethereum protocol network.
+    /// Transfers flagged as high value must be confirmed with `confirmed_high_value`.
+    pub fn eth_native_token_transfer_preview(
+        &self,
+        args: EthTransferNativeTokenArgs,
+    ) -> Result<dto::CoreTransferPreview, CoreError> {
+        let from_address_id: m::AddressId = args.from_address_id.try_into()?;
+        let chain_id = m::Address::fetch_eth_chain_id(
+            &mut self.connection_pool().connection()?,
+            &from_address_id,
+        )?;
+        let settings = self.fetch_profile_settings_for_address(&from_address_id)?;
+
+        let amount =
+            eth::NativeTokenAmount::new_from_decimal(chain_id, &args.amount_decimal)?;
+        let threshold = settings.eth_high_value_threshold(chain_id);
+        let currency = settings.fiat_currency_or(self.resources.default_fiat_currency());
+        let to_fiat = |amount: &eth::NativeTokenAmount| {
+            fiat::native_token_fiat_value(self.resources.price_feed(), &currency, amount)
+                .map(Into::into)
+        };
+        let preview = dto::CoreTransferPreview::builder()
+            .high_value(settings.is_eth_high_value(&amount))
+            .amount(amount.display_amount())
+            .high_value_threshold(Some(threshold.display_amount()))
+            .fiat_amount(to_fiat(&amount))
+            .fiat_high_value_threshold(to_fiat(&threshold))
+            .build();
+        Ok(preview)
+    }
+
+    /// Preview a fungible token transfer on an Ethereum protocol network.
+    /// We don't have prices for fungible tokens, so these transfers are never flagged as high
+    /// value.
+    pub fn eth_fungible_token_transfer_preview(
+        &self,
+        args: EthTransferFungibleTokenArgs,
+    ) -> Result<dto::CoreTransferPreview, CoreError> {
+        let _: m::AddressId = args.from_address_id.try_into()?;
+        let _: eth::ChecksumAddress = args.token_id.try_into()?;
+        let preview = dto::CoreTransferPreview::builder()
+            .high_value(false)
+            .amount(args.amount_decimal)
+            .high_value_threshold(None)
+            .build();
+        Ok(preview)
+    }
+
+    /// Set the amount above which transfers and transactions on a chain are considered high
+    /// value for a profile.
+    pub fn set_eth_high_value_threshold(
+        &self,
+        profile_id: String,
+        chain_id: u64,
+        amount_decimal: String,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let chain_id: eth::ChainId = chain_id.try_into()?;
+        let threshold =
+            eth::NativeTokenAmount::new_from_decimal(chain_id, &amount_decimal)?;
+        self.connection_pool()
+            .deferred_transaction(move |mut tx_conn| {
+                let mut settings =
+                    m::Profile::fetch_user_settings(tx_conn.as_mut(), &profile_id)?;
+                settings.set_eth_high_value_threshold(threshold);
+                m::Profile::set_user_settings(tx_conn.as_mut(), &profile_id, &settings)
+            })?;
+        Ok(())
+    }
+
+    /// Set the fiat currency that values are displayed in for a profile.
+    /// The currency code must be an ISO 4217 code, eg. "EUR".
+    pub fn set_fiat_currency(
+        &self,
+        profile_id: String,
+        currency_code: String,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let currency: FiatCurrency = currency_code.parse()?;
+        self.connection_pool()
+            .deferred_transaction(move |mut tx_conn| {
+                let mut settings =
+                    m::Profile::fetch_user_settings(tx_conn.as_mut(), &profile_id)?;
+                settings.set_fiat_currency(currency);
+                m::Profile::set_user_settings(tx_conn.as_mut(), &profile_id, &settings)
+            })?;
+        Ok(())
+    }
+
+    fn fetch_profile_settings_for_address(
+        &self,
+        address_id: &m::AddressId,
+    ) -> Result<m::ProfileSettings, Error> {
+        let mut conn = self.connection_pool().connection()?;
+        let profile_id = m::Address::fetch_profile_id(&mut conn, address_id)?;
+        m::Profile::fetch_user_settings(&mut conn, &profile_id)
+    }
+
     /// Transfer fungible native token on an Ethereum protocol network.
     /// Returns the tx hash that can be used to poll for the result.
     pub fn eth_transfer_fungible_token(
         &self,
         args: EthTransferFungibleTokenArgs,
     ) -> Result<(), CoreError> {
+        validate_transfer_memo(args.memo.as_deref())?;
         // TODO we use contract address as token id for now, but it should be chain specific
         let contract_address: eth::ChecksumAddress = args.token_id.clone().try_into()?;
         let from_address_id: m::AddressId = args.from_address_id.clone().try_into()?;
-        let to_address: eth::ChecksumAddress =
-            args.to_checksum_address.clone().try_into()?;
+        let to_address = parse_transfer_address(
+            &*self.resources,
+            &from_address_id,
+            &args.to_checksum_address,
+        )?;
         let signing_key = fetch_eth_signing_key_for_transfer(
             &*self.resources,
             &from_address_id,
             to_address,
         )?;
 
-        let rpc_provider = self.rpc_manager().eth_api_provider(signing_key.chain_id);
-        let tx_hash_res = rpc_provider.transfer_fungible_token(
-            &signing_key,
-            to_address,
-            &args.amount_decimal,
-            contract_address,
-        );
+        let (tx_hash_res, queue_entry) = if self.resources.is_demo() {
+            (demo::synthetic_tx_hash(self.resources.rng()), None)
+        } else {
+            let rpc_provider = self.rpc_manager().eth_api_provider(signing_key.chain_id);
+            let res = rt::block_on(rpc_provider.fungible_token_transfer_tx_async(
+                &signing_key,
+                to_address,
+                &args.amount_decimal,
+                contract_address,
+            ))
+            .and_then(|tx| {
+                send_queued_transfer(&*self.resources, &from_address_id, &signing_key, tx)
+            });
+            split_queued_transfer_result(res)
+        };
 
         let resources = self.resources.clone();
         rt::spawn_blocking(move || {
-            let res = token_transfer_callbacks(resources, args.into(), tx_hash_res);
+            let res = token_transfer_callbacks(
+                resources,
+                args.into(),
+                tx_hash_res,
+                queue_entry,
+            );
             if let Some(err) = res.err() {
                 log::error!(
                     "Failed to call native token transfer callbacks due to error: {err:?}"
@@ -367,60 +976,775 @@ impl AppCore {
         Ok(())
     }
 
-    /// List supported Ethereum chains.
-    pub fn list_eth_chains(&self) -> Vec<dto::CoreEthChain> {
-        self.assembler().list_eth_chains()
-    }
-
-    /// Add a supported Ethereum chain to an address. The operation is idempotent.
-    pub fn add_eth_chain(
+    /// Fetch the native token balance of an address at a block on an Ethereum chain.
+    /// The block can be a tag (`latest`, `pending`, `safe`, `finalized`, `earliest`) or a block
+    /// number. Returns the balance formatted for display.
+    pub fn balance_at(
         &self,
         chain_id: u64,
-        address_id: String,
-    ) -> Result<(), CoreError> {
+        checksum_address: String,
+        block: String,
+    ) -> Result<String, CoreError> {
         let chain_id: eth::ChainId = chain_id.try_into()?;
-        let address_id: m::AddressId = address_id.try_into()?;
-        let _ = self
-            .connection_pool()
-            .deferred_transaction(move |mut tx_conn| {
-                m::Address::fetch_or_create_for_eth_chain(
-                    &mut tx_conn,
-                    &address_id,
-                    chain_id,
-                )
-            })?;
-        Ok(())
+        let address: eth::ChecksumAddress = checksum_address.try_into()?;
+        let block_id = eth::parse_block_id(&block)?;
+        let rpc_provider = self.rpc_manager().eth_api_provider(chain_id);
+        let balance = rpc_provider.native_token_balance_at(address, block_id)?;
+        Ok(balance.display_amount())
     }
 
-    /// Change the address to connect with to a dapp.
-    /// Assumes there is already a key for the dapp in the profile.
-    pub fn eth_change_dapp_chain(
+    /// Fetch the native token balance of an address on an Ethereum chain at the latest block.
+    /// If `include_pending` is set, the pending balance subtracts the value and the estimated
+    /// fee of the transactions in the queue that haven't been confirmed yet, so that sent
+    /// amounts don't show as available until they're confirmed.
+    pub fn native_token_balance(
         &self,
-        args: EthChangeDappChainArgs,
-    ) -> Result<(), CoreError> {
-        let new_chain_id: eth::ChainId = args.new_chain_id.try_into()?;
-        let profile_id: DeterministicId = args.profile_id.try_into()?;
-        let dapp_id: DeterministicId = args.dapp_id.try_into()?;
-        self.connection_pool()
-            .deferred_transaction(move |mut tx_conn| {
-                let params = m::NewDappSessionParams::builder()
-                    .profile_id(&profile_id)
-                    .dapp_id(&dapp_id)
-                    .chain_id(new_chain_id)
-                    .build();
-                let session = m::LocalDappSession::create_eth_session_if_not_exists(
-                    &mut tx_conn,
-                    &params,
-                )?;
-                session.change_eth_chain(&mut tx_conn, new_chain_id)?;
-                Ok(())
-            })?;
-        Ok(())
+        profile_id: String,
+        chain_id: u64,
+        checksum_address: String,
+        include_pending: bool,
+    ) -> Result<dto::CoreNativeBalance, CoreError> {
+        let key = transaction_queue_key(profile_id, chain_id, checksum_address)?;
+        let rpc_provider = self.rpc_manager().eth_api_provider(key.chain_id);
+        let confirmed = rpc_provider.native_token_balance(key.address.into())?;
+        let pending = if include_pending {
+            let outflow = self.resources.transaction_queue().pending_outflow(&key)?;
+            let pending = eth::NativeTokenAmount::new(
+                key.chain_id,
+                confirmed.amount.saturating_sub(outflow),
+            );
+            Some((&pending).into())
+        } else {
+            None
+        };
+        Ok(dto::CoreNativeBalance {
+            confirmed: (&confirmed).into(),
+            pending,
+        })
     }
 
-    /// List the ids of the top dapps used by the user.
-    pub fn top_dapps(&self, limit: u32) -> Result<Vec<String>, CoreError> {
-        let res = self.connection_pool().deferred_transaction(|mut tx_conn| {
+    /// The latest block number on the chain. Cached for a short time, so it may lag behind
+    /// by a block.
+    pub fn current_block_number(&self, chain_id: u64) -> Result<u64, CoreError> {
+        let chain_id: eth::ChainId = chain_id.try_into()?;
+        let rpc_provider = self.rpc_manager().eth_api_provider(chain_id);
+        let block_number = rt::block_on(
+            self.resources
+                .block_number_cache()
+                .get_or_fetch(chain_id, || async move {
+                    rpc_provider.block_number_async().await.map_err(|err| {
+                        log::error!("Failed to fetch block number: {err:?}");
+                        Error::Retriable {
+                            error: format!(
+                                "No RPC endpoint is reachable for chain '{}'",
+                                chain_id.display_name()
+                            ),
+                        }
+                    })
+                }),
+        )?;
+        Ok(block_number)
+    }
+
+    /// Decode the function call in the data of a transaction to a contract for display.
+    /// The data is hex encoded with 0x prefix. Returns the selector if the function is not known.
+    /// Selectors are the same on all chains and contracts, so only the data is needed.
+    pub fn decode_calldata(&self, data: String) -> Result<eth::DecodedCall, CoreError> {
+        let data = data.strip_prefix("0x").unwrap_or(&data);
+        let data = hex::decode(data).map_err(|_| Error::User {
+            explanation: "Calldata must be hex encoded.".into(),
+        })?;
+        let decoded_call = eth::SELECTOR_REGISTRY.decode(&data)?;
+        Ok(decoded_call)
+    }
+
+    /// Verify that an off-chain signature was produced by the key of the expected address.
+    /// Returns false if the signature recovers to a different address, eg. because the message
+    /// was tampered with, and an error if the signature is malformed.
+    pub fn verify_signature(
+        &self,
+        message: String,
+        signature: String,
+        expected_address: String,
+        kind: eth::SignatureKind,
+    ) -> Result<bool, CoreError> {
+        let res = eth::verify_signature(kind, &message, &signature, &expected_address)?;
+        Ok(res)
+    }
+
+    /// Build a transaction on an online device to sign it on another device with
+    /// `sign_request`. The gas limit and gas price are fetched from the chain and the nonce is
+    /// reserved in the transaction queue of the sender.
+    /// Only profile wallets can sign requests, because dapp accounts have spending limits.
+    pub fn build_signing_request(
+        &self,
+        profile_id: String,
+        chain_id: u64,
+        tx: EthSigningRequestArgs,
+    ) -> Result<eth::SigningRequest, CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let chain_id: eth::ChainId = chain_id.try_into()?;
+        let from_address: eth::ChecksumAddress = tx.from_checksum_address.try_into()?;
+        let to_address: Option<eth::ChecksumAddress> =
+            tx.to_checksum_address.map(TryInto::try_into).transpose()?;
+        let value = U256::from_dec_str(&tx.value_wei).map_err(|_| Error::User {
+            explanation: format!("Invalid amount: '{}'", tx.value_wei),
+        })?;
+        let data = tx.data.as_deref().unwrap_or_default();
+        let data =
+            hex::decode(data.strip_prefix("0x").unwrap_or(data)).map_err(|_| {
+                Error::User {
+                    explanation: "Calldata must be hex encoded.".into(),
+                }
+            })?;
+
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            fetch_signing_request_address_id(
+                &mut tx_conn,
+                &profile_id,
+                from_address,
+                chain_id,
+            )
+        })?;
+        let rpc_provider = self.rpc_manager().eth_api_provider(chain_id);
+        let request = rt::block_on(eth::SigningRequest::build(
+            &rpc_provider,
+            self.resources.transaction_queue(),
+            profile_id,
+            from_address,
+            to_address,
+            value,
+            data.into(),
+        ))?;
+        Ok(request)
+    }
+
+    /// Sign a request built with `build_signing_request` without network access.
+    /// The sender account of the request must be a wallet of the profile of the request.
+    pub fn sign_request(
+        &self,
+        request: eth::SigningRequest,
+    ) -> Result<eth::SignedTransaction, CoreError> {
+        let profile_id: DeterministicId = request.profile_id.parse()?;
+        let chain_id = request.eth_chain_id()?;
+        let from_address = request.from_address()?;
+        let signing_key =
+            self.connection_pool().deferred_transaction(|mut tx_conn| {
+                let address_id = fetch_signing_request_address_id(
+                    &mut tx_conn,
+                    &profile_id,
+                    from_address,
+                    chain_id,
+                )?;
+                m::Address::fetch_eth_signing_key(
+                    &mut tx_conn,
+                    self.resources.keychain(),
+                    &address_id,
+                )
+            })?;
+        let signed = request.sign(&signing_key)?;
+        Ok(signed)
+    }
+
+    /// Serialize a signing request to transfer it to another device, e.g. through a QR code.
+    pub fn encode_signing_request(
+        &self,
+        request: eth::SigningRequest,
+    ) -> Result<String, CoreError> {
+        Ok(request.encode()?)
+    }
+
+    /// Deserialize a signing request from another device.
+    pub fn decode_signing_request(
+        &self,
+        payload: String,
+    ) -> Result<eth::SigningRequest, CoreError> {
+        Ok(eth::SigningRequest::decode(&payload)?)
+    }
+
+    /// Broadcast a transaction signed with `sign_request`. Returns the tx hash.
+    pub fn broadcast_signed_transaction(
+        &self,
+        chain_id: u64,
+        raw_transaction: String,
+    ) -> Result<String, CoreError> {
+        let chain_id: eth::ChainId = chain_id.try_into()?;
+        let raw_transaction: ethers::types::Bytes =
+            raw_transaction.parse().map_err(|_| Error::User {
+                explanation: "The signed transaction must be hex encoded.".into(),
+            })?;
+        let rpc_provider = self.rpc_manager().eth_api_provider(chain_id);
+        let tx_hash =
+            rt::block_on(rpc_provider.send_raw_transaction_async(raw_transaction))?;
+        Ok(format!("{tx_hash:#x}"))
+    }
+
+    /// List supported Ethereum chains.
+    pub fn list_eth_chains(&self) -> Vec<dto::CoreEthChain> {
+        self.assembler().list_eth_chains()
+    }
+
+    /// Add a supported Ethereum chain to an address. The operation is idempotent.
+    pub fn add_eth_chain(
+        &self,
+        chain_id: u64,
+        address_id: String,
+    ) -> Result<(), CoreError> {
+        let chain_id: eth::ChainId = chain_id.try_into()?;
+        let address_id: m::AddressId = address_id.try_into()?;
+        let _ = self
+            .connection_pool()
+            .deferred_transaction(move |mut tx_conn| {
+                m::Address::fetch_or_create_for_eth_chain(
+                    &mut tx_conn,
+                    &address_id,
+                    chain_id,
+                )
+            })?;
+        Ok(())
+    }
+
+    /// List the transactions sent from an address ordered by most recent first.
+    pub fn list_transactions(
+        &self,
+        address_id: String,
+    ) -> Result<Vec<dto::CoreTransaction>, CoreError> {
+        let address_id: m::AddressId = address_id.parse()?;
+        let mut conn = self.connection_pool().connection()?;
+        let chain_id = m::Address::fetch_eth_chain_id(&mut conn, &address_id)?;
+        let results = m::Transaction::list_for_address(&mut conn, &address_id)?
+            .into_iter()
+            .map(|tx| dto::CoreTransaction {
+                id: tx.deterministic_id.into(),
+                chain_id: chain_id.into(),
+                tx_hash: tx.tx_hash,
+                to_checksum_address: tx.to_address.map(Into::into),
+                memo: tx.memo,
+                created_at: tx.created_at,
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Add the dapps from a JSON manifest, eg. to seed the app with a curated list.
+    /// The dapps are added to a profile when the user approves them on first connect.
+    /// Returns the outcome for each entry. Malformed entries are skipped.
+    pub fn import_dapps(
+        &self,
+        manifest_json: String,
+    ) -> Result<Vec<DappImportResult>, CoreError> {
+        let res = dapp_manifest::import_dapps(self.resources.as_ref(), &manifest_json)?;
+        Ok(res)
+    }
+
+    /// Scan the fungible tokens of a profile on a chain for spenders that can move them.
+    /// Returns the allowances sorted by risk, highest first.
+    /// Spenders are discovered from the token contracts' `Approval` event logs. Fails if the RPC
+    /// can't return the logs, instead of returning an incomplete list.
+    pub fn scan_allowances(
+        &self,
+        profile_id: String,
+        chain_id: u64,
+    ) -> Result<Vec<eth::Allowance>, CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let chain_id: eth::ChainId = chain_id.try_into()?;
+        // Demo addresses have no on-chain history.
+        if self.resources.is_demo() {
+            return Ok(Default::default());
+        }
+        let mut conn = self.connection_pool().connection()?;
+        let tokens = m::Token::list_profile_fungible_tokens_for_eth_chain(
+            &mut conn,
+            &profile_id,
+            chain_id,
+        )?;
+        let known_spenders = m::Transaction::list_eth_recipients_for_profile(
+            &mut conn,
+            &profile_id,
+            chain_id,
+        )?;
+
+        let rpc_provider = self.rpc_manager().eth_api_provider(chain_id);
+        let mut allowances: Vec<eth::TokenAllowance> = Default::default();
+        for (owner, token) in tokens {
+            let token_allowances =
+                rt::block_on(eth::fetch_token_allowances(&rpc_provider, owner, token))?;
+            allowances.extend(token_allowances);
+        }
+
+        Ok(eth::rank_allowances(allowances, &known_spenders))
+    }
+
+    /// Discover new fungible tokens of the profile's wallet on a chain from its recent
+    /// transfers and track them. Spam tokens are skipped.
+    /// Returns the checksum addresses of the newly discovered token contracts.
+    pub fn discover_tokens(
+        &self,
+        profile_id: String,
+        chain_id: u64,
+    ) -> Result<Vec<String>, CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        let chain_id: eth::ChainId = chain_id.try_into()?;
+        let address_id = self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::Address::fetch_eth_wallet_id(&mut tx_conn, &profile_id, chain_id)
+        })?;
+        let discovered =
+            discover_tokens_for_address(&*self.resources, &address_id, chain_id)?;
+        Ok(discovered.into_iter().map(Into::into).collect())
+    }
+
+    /// Enable or disable discovering new tokens when the tokens of a profile wallet are
+    /// fetched.
+    pub fn set_auto_discover_tokens(
+        &self,
+        profile_id: String,
+        enabled: bool,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.parse()?;
+        self.connection_pool()
+            .deferred_transaction(move |mut tx_conn| {
+                let mut settings =
+                    m::Profile::fetch_user_settings(tx_conn.as_mut(), &profile_id)?;
+                settings.set_auto_discover_tokens(enabled);
+                m::Profile::set_user_settings(tx_conn.as_mut(), &profile_id, &settings)
+            })?;
+        Ok(())
+    }
+
+    /// Start discovering tokens for the address in the background if it's a profile wallet,
+    /// auto-discovery is enabled for its profile and it wasn't started recently.
+    fn auto_discover_tokens(&self, address_id: &m::AddressId) -> Result<(), Error> {
+        // Demo addresses have no on-chain history.
+        if self.resources.is_demo() {
+            return Ok(());
+        }
+        let mut conn = self.connection_pool().connection()?;
+        if !m::Address::is_profile_wallet(&mut conn, address_id)? {
+            return Ok(());
+        }
+        let profile_id = m::Address::fetch_profile_id(&mut conn, address_id)?;
+        let settings = m::Profile::fetch_user_settings(&mut conn, &profile_id)?;
+        if !settings.auto_discover_tokens {
+            return Ok(());
+        }
+        let chain_id = m::Address::fetch_eth_chain_id(&mut conn, address_id)?;
+        let address = m::Address::fetch_address(&mut conn, address_id)?;
+        if !eth::start_auto_discovery(chain_id, address) {
+            return Ok(());
+        }
+
+        let resources = self.resources.clone();
+        let address_id = address_id.clone();
+        rt::spawn_blocking(move || {
+            if let Err(err) =
+                discover_tokens_for_address(&*resources, &address_id, chain_id)
+            {
+                log::warn!(
+                    "Failed to discover tokens for address id {address_id}: {err:?}"
+                );
+            }
+        });
+        Ok(())
+    }
+
+    /// Check that an RPC URL works before adding it as a custom network.
+    /// Returns the chain id and latest block reported by the endpoint, the latency and whether
+    /// it's an archive node.
+    pub fn test_rpc_url(&self, url: String) -> Result<eth::RpcTestResult, CoreError> {
+        let res = rt::block_on(eth::test_rpc_url(&url, config::HTTP_FETCH_TIMEOUT))?;
+        Ok(res)
+    }
+
+    /// List the queued and recent transactions of an address on a chain ordered by nonce.
+    pub fn transaction_queue(
+        &self,
+        profile_id: String,
+        chain_id: u64,
+        checksum_address: String,
+    ) -> Result<Vec<dto::CoreQueuedTransaction>, CoreError> {
+        let key = transaction_queue_key(profile_id, chain_id, checksum_address)?;
+        let results = self
+            .resources
+            .transaction_queue()
+            .list(&key)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(results)
+    }
+
+    /// Cancel a transaction that failed to submit to unblock the transaction queue.
+    pub fn cancel_queued_transaction(
+        &self,
+        profile_id: String,
+        chain_id: u64,
+        checksum_address: String,
+        nonce: u64,
+    ) -> Result<(), CoreError> {
+        let key = transaction_queue_key(profile_id, chain_id, checksum_address)?;
+        self.resources
+            .transaction_queue()
+            .cancel(&key, nonce.into())?;
+        Ok(())
+    }
+
+    /// Resync the nonce of an address on a chain with the pending transaction count on the
+    /// chain. Transactions in the queue that haven't been confirmed from that nonce are
+    /// cleared and need to be submitted again. Returns the nonce of the next transaction.
+    pub fn reset_nonce(
+        &self,
+        profile_id: String,
+        chain_id: u64,
+        checksum_address: String,
+    ) -> Result<u64, CoreError> {
+        let key = transaction_queue_key(profile_id, chain_id, checksum_address)?;
+        let rpc_provider = self.rpc_manager().eth_api_provider(key.chain_id);
+        let pending_nonce =
+            rt::block_on(rpc_provider.pending_nonce_async(key.address.into()))?;
+        let cleared = self
+            .resources
+            .transaction_queue()
+            .reset(&key, pending_nonce)?;
+        log::info!(
+            "Reset nonce to {pending_nonce} on chain {} and cleared {cleared} transactions",
+            key.chain_id
+        );
+        Ok(pending_nonce.low_u64())
+    }
+
+    /// Change the address to connect with to a dapp.
+    /// Assumes there is already a key for the dapp in the profile.
+    pub fn eth_change_dapp_chain(
+        &self,
+        args: EthChangeDappChainArgs,
+    ) -> Result<(), CoreError> {
+        let new_chain_id: eth::ChainId = args.new_chain_id.try_into()?;
+        let profile_id: DeterministicId = args.profile_id.try_into()?;
+        let dapp_id: DeterministicId = args.dapp_id.try_into()?;
+        self.connection_pool()
+            .deferred_transaction(move |mut tx_conn| {
+                let params = m::NewDappSessionParams::builder()
+                    .profile_id(&profile_id)
+                    .dapp_id(&dapp_id)
+                    .chain_id(new_chain_id)
+                    .build();
+                let session = m::LocalDappSession::create_eth_session_if_not_exists(
+                    &mut tx_conn,
+                    &params,
+                )?;
+                session.change_eth_chain(&mut tx_conn, new_chain_id)?;
+                Ok(())
+            })?;
+        Ok(())
+    }
+
+    /// Set how much the user trusts a dapp on this device.
+    /// Trusted dapps can send transactions without approval unless the transaction is high value
+    /// or flagged as risky. Must only be called on explicit user action.
+    pub fn set_dapp_trust(
+        &self,
+        profile_id: String,
+        dapp_id: String,
+        level: m::DappTrustLevel,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        let dapp_identifier =
+            self.connection_pool()
+                .deferred_transaction(move |mut tx_conn| {
+                    let params = m::NewDappSessionParams::builder()
+                        .profile_id(&profile_id)
+                        .dapp_id(&dapp_id)
+                        .build();
+                    let session = m::LocalDappSession::create_eth_session_if_not_exists(
+                        &mut tx_conn,
+                        &params,
+                    )?;
+                    let session = session.set_trust_level(&mut tx_conn, level)?;
+                    Ok(session.dapp_human_identifier)
+                })?;
+        log::info!("User set trust level of dapp '{dapp_identifier}' to '{level}'");
+        Ok(())
+    }
+
+    /// Resolve a deep link to a dapp. The link can be a dapp id, a `sealvault://dapp/<dapp id>`
+    /// uri or the url of the dapp. Returns none if the dapp doesn't exist and an error if the
+    /// link is malformed.
+    pub fn resolve_dapp_deeplink(
+        &self,
+        id_or_url: String,
+    ) -> Result<Option<dto::CoreDappDetail>, CoreError> {
+        let dapp_id = m::Dapp::deterministic_id_for_deep_link(
+            &id_or_url,
+            self.resources.public_suffix_list(),
+        )?;
+        let detail = self.connection_pool().deferred_transaction(|mut tx_conn| {
+            let dapp = match m::Dapp::fetch_optional(tx_conn.as_mut(), &dapp_id)? {
+                Some(dapp) => dapp,
+                None => return Ok(None),
+            };
+            let profile_ids = m::Dapp::list_profile_ids(tx_conn.as_mut(), &dapp_id)?;
+            Ok(Some(dto::CoreDappDetail {
+                id: dapp.deterministic_id.into(),
+                human_identifier: dapp.identifier,
+                url: (&dapp.url).into(),
+                profile_ids: profile_ids.into_iter().map(Into::into).collect(),
+            }))
+        })?;
+        Ok(detail)
+    }
+
+    /// The accounts that could be offered to the dapp at the url, one per profile that the dapp
+    /// has been added to, eg. for a profile and account picker when connecting. The address of
+    /// the dapp's session on this device is preferred. Only secure origins are accepted.
+    pub fn accounts_for_origin(
+        &self,
+        url: String,
+    ) -> Result<Vec<OriginAccount>, CoreError> {
+        let url = Url::parse(&url)
+            .ok()
+            .filter(is_https_or_loopback)
+            .ok_or_else(|| Error::User {
+                explanation: format!("Invalid or insecure dapp url: '{url}'"),
+            })?;
+        let dapp_id =
+            m::Dapp::deterministic_id_for_url(url, self.resources.public_suffix_list())?;
+        let accounts = self.connection_pool().deferred_transaction(|mut tx_conn| {
+            let profile_ids = m::Dapp::list_profile_ids(tx_conn.as_mut(), &dapp_id)?;
+            let mut accounts: Vec<OriginAccount> = Default::default();
+            for profile_id in profile_ids {
+                let params = m::FetchDappSessionParams::builder()
+                    .dapp_id(&dapp_id)
+                    .profile_id(&profile_id)
+                    .build();
+                let address = match m::LocalDappSession::fetch_eth_session(
+                    &mut tx_conn,
+                    &params,
+                )? {
+                    Some(session) => Some(session.address),
+                    // The dapp may have been added on an other device.
+                    None => {
+                        let params = m::ListAddressesForDappParams::builder()
+                            .profile_id(&profile_id)
+                            .dapp_id(&dapp_id)
+                            .build();
+                        m::Address::list_for_dapp(tx_conn.as_mut(), &params)?
+                            .into_iter()
+                            .next()
+                            .map(|address| address.address)
+                    }
+                };
+                if let Some(address) = address {
+                    accounts.push(OriginAccount {
+                        profile_id: profile_id.into(),
+                        checksum_address: address.to_string(),
+                    });
+                }
+            }
+            Ok(accounts)
+        })?;
+        Ok(accounts)
+    }
+
+    /// Replace the user assigned tags of a dapp in a profile, eg. "DeFi" or "NFT". Tags are
+    /// free-form, but they're trimmed, truncated and deduplicated case-insensitively.
+    pub fn set_dapp_tags(
+        &self,
+        profile_id: String,
+        dapp_id: String,
+        tags: Vec<String>,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::Dapp::set_tags(&mut tx_conn, &profile_id, &dapp_id, &tags)
+        })?;
+        Ok(())
+    }
+
+    /// The distinct dapp tags in use in a profile in alphabetical order, eg. to filter the
+    /// dapps of the profile.
+    pub fn list_dapp_tags(&self, profile_id: String) -> Result<Vec<String>, CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let mut conn = self.connection_pool().connection()?;
+        let tags = m::Dapp::list_tags(&mut conn, &profile_id)?;
+        Ok(tags)
+    }
+
+    /// Store a value for a dapp in a profile, eg. a session token. Values are encrypted at rest
+    /// and a dapp can only read the values that it stored in the profile. Fails if the dapp
+    /// would exceed its storage quota.
+    pub fn dapp_storage_set(
+        &self,
+        profile_id: String,
+        dapp_id: String,
+        key: String,
+        value: String,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::DappStorage::set(
+                &mut tx_conn,
+                self.keychain(),
+                &profile_id,
+                &dapp_id,
+                &key,
+                &value,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Fetch a value that a dapp stored in a profile. Returns none if the key isn't set.
+    pub fn dapp_storage_get(
+        &self,
+        profile_id: String,
+        dapp_id: String,
+        key: String,
+    ) -> Result<Option<String>, CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        let value = self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::DappStorage::get(
+                &mut tx_conn,
+                self.keychain(),
+                &profile_id,
+                &dapp_id,
+                &key,
+            )
+        })?;
+        Ok(value)
+    }
+
+    /// Delete a value that a dapp stored in a profile.
+    pub fn dapp_storage_delete(
+        &self,
+        profile_id: String,
+        dapp_id: String,
+        key: String,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::DappStorage::delete(&mut tx_conn, &profile_id, &dapp_id, &key)
+        })?;
+        Ok(())
+    }
+
+    /// Delete all values that a dapp stored in a profile, eg. when the dapp is removed from the
+    /// profile.
+    pub fn dapp_storage_clear(
+        &self,
+        profile_id: String,
+        dapp_id: String,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::DappStorage::delete_for_dapp(&mut tx_conn, &profile_id, &dapp_id)
+        })?;
+        Ok(())
+    }
+
+    /// Pin a dapp to the top of the profile's dapps or unpin it.
+    pub fn set_dapp_pinned(
+        &self,
+        profile_id: String,
+        dapp_id: String,
+        pinned: bool,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::Dapp::set_pinned(&mut tx_conn, &profile_id, &dapp_id, pinned)
+        })?;
+        Ok(())
+    }
+
+    /// Persist a user chosen order of the pinned dapps in a profile.
+    /// The ids must contain each pinned dapp of the profile exactly once.
+    pub fn reorder_pinned_dapps(
+        &self,
+        profile_id: String,
+        ordered_dapp_ids: Vec<String>,
+    ) -> Result<(), CoreError> {
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let ordered_dapp_ids = ordered_dapp_ids
+            .into_iter()
+            .map(|dapp_id| dapp_id.parse())
+            .collect::<Result<Vec<DeterministicId>, _>>()?;
+        self.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::Dapp::reorder_pinned(&mut tx_conn, &profile_id, &ordered_dapp_ids)
+        })?;
+        Ok(())
+    }
+
+    /// Set a cumulative spending limit for a dapp over a rolling window on this device.
+    /// Transactions that would exceed the limit require approval even if the dapp is trusted.
+    /// A limit without native or token amounts removes the limit.
+    pub fn set_dapp_spending_limit(
+        &self,
+        args: DappSpendingLimitArgs,
+    ) -> Result<(), CoreError> {
+        let DappSpendingLimitArgs {
+            profile_id,
+            dapp_id,
+            window_seconds,
+            native_limits,
+            token_limits,
+        } = args;
+        let profile_id: DeterministicId = profile_id.try_into()?;
+        let dapp_id: DeterministicId = dapp_id.try_into()?;
+        let native = native_limits
+            .into_iter()
+            .map(|limit| {
+                let chain_id: eth::ChainId = limit.chain_id.try_into()?;
+                eth::NativeTokenAmount::new_from_decimal(chain_id, &limit.amount_decimal)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let tokens = token_limits
+            .into_iter()
+            .map(|limit| {
+                let amount =
+                    U256::from_dec_str(&limit.amount).map_err(|_| Error::User {
+                        explanation: format!("Invalid token amount: '{}'", limit.amount),
+                    })?;
+                Ok(m::TokenSpendingLimit {
+                    chain_id: limit.chain_id.try_into()?,
+                    contract_address: limit.token_id.try_into()?,
+                    amount,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let spending_limit = m::DappSpendingLimit::builder()
+            .window_seconds(window_seconds)
+            .native(native)
+            .tokens(tokens)
+            .build();
+
+        let dapp_identifier =
+            self.connection_pool()
+                .deferred_transaction(move |mut tx_conn| {
+                    let params = m::NewDappSessionParams::builder()
+                        .profile_id(&profile_id)
+                        .dapp_id(&dapp_id)
+                        .build();
+                    let session = m::LocalDappSession::create_eth_session_if_not_exists(
+                        &mut tx_conn,
+                        &params,
+                    )?;
+                    let session = session
+                        .set_spending_limit(&mut tx_conn, Some(&spending_limit))?;
+                    Ok(session.dapp_human_identifier)
+                })?;
+        log::info!("User set spending limit of dapp '{dapp_identifier}'");
+        Ok(())
+    }
+
+    /// List the ids of the top dapps used by the user.
+    pub fn top_dapps(&self, limit: u32) -> Result<Vec<String>, CoreError> {
+        let res = self.connection_pool().deferred_transaction(|mut tx_conn| {
             // Prefer dapps that have sessions on this device.
             let mut session_dapp_ids =
                 m::LocalDappSession::list_dapp_ids_desc(tx_conn.as_mut(), limit)?;
@@ -437,12 +1761,102 @@ impl AppCore {
     }
 }
 
+fn transaction_queue_key(
+    profile_id: String,
+    chain_id: u64,
+    checksum_address: String,
+) -> Result<eth::TransactionQueueKey, CoreError> {
+    let address: eth::ChecksumAddress = checksum_address.try_into()?;
+    Ok(eth::TransactionQueueKey {
+        profile_id: profile_id.parse()?,
+        chain_id: chain_id.try_into()?,
+        address: address.to_address(),
+    })
+}
+
+fn default_disabled_methods() -> Vec<String> {
+    config::DEFAULT_DISABLED_JSONRPC_METHODS
+        .iter()
+        .map(|method| method.to_string())
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct CoreArgs {
     pub device_id: String,
     pub device_name: String,
     pub cache_dir: String,
     pub db_file_path: String,
+    /// ISO 4217 code of the fiat currency for profiles that haven't set a preference.
+    pub default_fiat_currency: String,
+    /// Log RPC requests at debug level with secrets redacted.
+    pub log_rpc: bool,
+    /// In-page JSON-RPC methods to reject even if they're supported.
+    /// Defaults to `config::DEFAULT_DISABLED_JSONRPC_METHODS` if none.
+    pub disabled_methods: Option<Vec<String>>,
+    /// How long to wait for the user to respond to an approval prompt before rejecting the
+    /// request. Defaults to `config::DEFAULT_APPROVAL_TIMEOUT` if none.
+    pub approval_timeout_seconds: Option<u64>,
+    /// Calldata larger than this is only summarized by its selector and size in transaction
+    /// approvals. Defaults to `config::DEFAULT_MAX_DECODED_CALLDATA_BYTES` if none.
+    pub max_decoded_calldata_bytes: Option<u64>,
+    /// Backups of DBs larger than this are neither created nor restored.
+    /// Defaults to `config::DEFAULT_MAX_BACKUP_SIZE_BYTES` if none.
+    pub max_backup_size_bytes: Option<u64>,
+    /// The `major.minor.patch` version of the host app. Backups that require a newer app
+    /// version aren't restored.
+    pub app_version: String,
+    /// Hex encoded 256-bit raw key to encrypt the DB with SQLCipher.
+    /// None means the DB is not encrypted.
+    pub db_encryption_key: Option<String>,
+    /// Seed sample profiles and dapps and don't submit transfers and transactions to the
+    /// network. For screenshots and onboarding.
+    pub demo: bool,
+}
+
+/// Which local history records to delete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryFilter {
+    All,
+    /// The history of a dapp by its id.
+    Dapp {
+        dapp_id: String,
+    },
+    /// Records created before the unix timestamp.
+    OlderThan {
+        timestamp: i64,
+    },
+}
+
+/// An account that can be offered to a dapp from a profile that the dapp has been added to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginAccount {
+    pub profile_id: String,
+    pub checksum_address: String,
+}
+
+/// The number of deleted history records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClearedHistory {
+    pub transactions: u64,
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct ListNftsArgs {
+    pub address_id: String,
+    /// The `next_cursor` of the previous page. None for the first page.
+    #[builder(default)]
+    pub cursor: Option<String>,
+    #[builder(default = config::MAX_NFT_PAGE_SIZE)]
+    pub limit: u32,
+    /// Only list NFTs of this contract.
+    #[builder(default)]
+    pub contract_address: Option<String>,
+    /// Only list NFTs of this token standard, eg. `ERC721`.
+    #[builder(default)]
+    pub standard: Option<String>,
+    #[builder(default)]
+    pub sort: eth::NftSort,
 }
 
 #[derive(Debug, Clone, TypedBuilder)]
@@ -450,6 +1864,12 @@ pub struct EthTransferNativeTokenArgs {
     pub from_address_id: String,
     pub to_checksum_address: String,
     pub amount_decimal: String,
+    /// Must be set to transfer amounts above the high value threshold of the profile.
+    #[builder(default)]
+    pub confirmed_high_value: bool,
+    /// Local note stored with the transaction. Never broadcast.
+    #[builder(default)]
+    pub memo: Option<String>,
 }
 
 #[derive(Debug, Clone, TypedBuilder)]
@@ -458,6 +1878,24 @@ pub struct EthTransferFungibleTokenArgs {
     pub to_checksum_address: String,
     pub amount_decimal: String,
     pub token_id: String,
+    /// Local note stored with the transaction. Never broadcast.
+    #[builder(default)]
+    pub memo: Option<String>,
+}
+
+/// The transaction to build a signing request for.
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct EthSigningRequestArgs {
+    pub from_checksum_address: String,
+    /// None for contract deployments.
+    #[builder(default)]
+    pub to_checksum_address: Option<String>,
+    /// Decimal amount in wei.
+    #[builder(default = "0".into())]
+    pub value_wei: String,
+    /// Hex encoded calldata with 0x prefix.
+    #[builder(default)]
+    pub data: Option<String>,
 }
 
 #[derive(Debug, Clone, TypedBuilder)]
@@ -467,12 +1905,40 @@ pub struct EthChangeDappChainArgs {
     pub new_chain_id: u64,
 }
 
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct DappSpendingLimitArgs {
+    pub profile_id: String,
+    pub dapp_id: String,
+    /// The length of the rolling window in seconds.
+    pub window_seconds: u64,
+    #[builder(default)]
+    pub native_limits: Vec<NativeSpendingLimitArgs>,
+    #[builder(default)]
+    pub token_limits: Vec<TokenSpendingLimitArgs>,
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct NativeSpendingLimitArgs {
+    pub chain_id: u64,
+    pub amount_decimal: String,
+}
+
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct TokenSpendingLimitArgs {
+    pub chain_id: u64,
+    /// The contract address of the token.
+    pub token_id: String,
+    /// Decimal integer amount in the lowest denomination of the token.
+    pub amount: String,
+}
+
 #[derive(Debug, Clone)]
 struct EthTokenTransferCallbackArgs {
     pub from_address_id: String,
     pub to_checksum_address: String,
     pub amount_decimal: String,
     pub token_id: Option<String>,
+    pub memo: Option<String>,
 }
 
 impl From<EthTransferNativeTokenArgs> for EthTokenTransferCallbackArgs {
@@ -481,12 +1947,15 @@ impl From<EthTransferNativeTokenArgs> for EthTokenTransferCallbackArgs {
             from_address_id,
             to_checksum_address,
             amount_decimal,
+            memo,
+            ..
         } = value;
         EthTokenTransferCallbackArgs {
             from_address_id,
             to_checksum_address,
             amount_decimal,
             token_id: None,
+            memo,
         }
     }
 }
@@ -498,16 +1967,84 @@ impl From<EthTransferFungibleTokenArgs> for EthTokenTransferCallbackArgs {
             to_checksum_address,
             amount_decimal,
             token_id,
+            memo,
         } = value;
         EthTokenTransferCallbackArgs {
             from_address_id,
             to_checksum_address,
             amount_decimal,
             token_id: Some(token_id),
+            memo,
         }
     }
 }
 
+/// Discover new tokens of the address from its recent transfers and track them.
+fn discover_tokens_for_address(
+    resources: &dyn CoreResourcesI,
+    address_id: &m::AddressId,
+    chain_id: eth::ChainId,
+) -> Result<Vec<eth::ChecksumAddress>, Error> {
+    // Demo addresses have no on-chain history.
+    if resources.is_demo() {
+        return Ok(Default::default());
+    }
+    let mut conn = resources.connection_pool().connection()?;
+    let address = m::Address::fetch_address(&mut conn, address_id)?;
+    let profile_id = m::Address::fetch_profile_id(&mut conn, address_id)?;
+    let known_tokens: HashSet<eth::ChecksumAddress> =
+        m::Token::list_profile_fungible_tokens_for_eth_chain(
+            &mut conn,
+            &profile_id,
+            chain_id,
+        )?
+        .into_iter()
+        .filter(|(owner, _)| *owner == address)
+        .map(|(_, token)| token)
+        .collect();
+    drop(conn);
+
+    let rpc_provider = resources.rpc_manager().eth_api_provider(chain_id);
+    let discovered =
+        rt::block_on(eth::discover_tokens(&rpc_provider, address, &known_tokens))?;
+    resources
+        .connection_pool()
+        .deferred_transaction(|mut tx_conn| {
+            m::Token::upsert_fungible_tokens_for_eth_address(
+                &mut tx_conn,
+                chain_id,
+                &discovered,
+                address_id,
+            )
+        })?;
+    Ok(discovered)
+}
+
+fn validate_transfer_memo(memo: Option<&str>) -> Result<(), Error> {
+    match memo {
+        Some(memo) if memo.chars().count() > config::MAX_TRANSFER_MEMO_LENGTH => {
+            Err(Error::User {
+                explanation: format!(
+                    "The memo must be at most {} characters",
+                    config::MAX_TRANSFER_MEMO_LENGTH
+                ),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Parse the recipient address in the address format of the chain of the sender.
+fn parse_transfer_address(
+    resources: &dyn CoreResourcesI,
+    from_address_id: &m::AddressId,
+    to_address: &str,
+) -> Result<eth::ChecksumAddress, Error> {
+    let mut conn = resources.connection_pool().connection()?;
+    let chain_id = m::Address::fetch_eth_chain_id(&mut conn, from_address_id)?;
+    chain_id.parse_address(to_address)?.evm_address()
+}
+
 fn fetch_eth_signing_key_for_transfer(
     resources: &dyn CoreResourcesI,
     from_address_id: &m::AddressId,
@@ -543,20 +2080,159 @@ fn fetch_eth_signing_key_for_transfer(
     Ok(signing_key)
 }
 
+fn verify_eth_address_in_profile(
+    conn: &mut SqliteConnection,
+    profile_id: &DeterministicId,
+    address: eth::ChecksumAddress,
+) -> Result<(), Error> {
+    let address_profile_id = m::Address::fetch_profile_id_for_eth_address(conn, address)?;
+    if address_profile_id.as_ref() != Some(profile_id) {
+        return Err(Error::User {
+            explanation: format!("The account {address} doesn't belong to the profile."),
+        });
+    }
+    Ok(())
+}
+
+/// The system keychain on iOS.
+#[cfg(target_os = "ios")]
+fn new_keychain(_db_file_path: &str) -> Result<Keychain, Error> {
+    Ok(Keychain::new())
+}
+
+/// Other platforms are only used for development. The keychain is saved next to a persistent
+/// DB, because the DB can't be opened without the keys.
+#[cfg(not(target_os = "ios"))]
+fn new_keychain(db_file_path: &str) -> Result<Keychain, Error> {
+    if db_file_path == config::IN_MEMORY_DB_FILE_PATH {
+        Ok(Keychain::new())
+    } else {
+        let file_path =
+            PathBuf::from(format!("{db_file_path}{}", config::KEYCHAIN_FILE_SUFFIX));
+        Keychain::new_persistent(&file_path)
+    }
+}
+
+/// The address id of the sender of a signing request. The sender must be a wallet of the
+/// profile, because signing requests for dapp accounts would bypass their spending limits.
+fn fetch_signing_request_address_id(
+    tx_conn: &mut DeferredTxConnection,
+    profile_id: &DeterministicId,
+    address: eth::ChecksumAddress,
+    chain_id: eth::ChainId,
+) -> Result<m::AddressId, Error> {
+    verify_eth_address_in_profile(tx_conn.as_mut(), profile_id, address)?;
+    let address_id =
+        m::Address::fetch_or_create_id_by_address_on_chain(tx_conn, address, chain_id)?
+            .ok_or_else(|| Error::Fatal {
+            error: "Address of signing request not found".into(),
+        })?;
+    if !m::Address::is_profile_wallet(tx_conn.as_mut(), &address_id)? {
+        return Err(Error::User {
+            explanation:
+                "Only wallet accounts can sign requests. Dapp accounts are limited \
+                to the dapp."
+                    .into(),
+        });
+    }
+    Ok(address_id)
+}
+
+/// The queue entry of a wallet transfer, used to mark it confirmed or failed.
+type TransferQueueEntry = (eth::TransactionQueueKey, U256);
+
+/// Send a wallet transfer through the transaction queue of the sender, so that it's ordered
+/// with the dapp transactions of the address and it counts towards the pending balance.
+fn send_queued_transfer(
+    resources: &dyn CoreResourcesI,
+    from_address_id: &m::AddressId,
+    signing_key: &eth::SigningKey,
+    tx: ethers::types::TransactionRequest,
+) -> Result<(ethers::types::H256, TransferQueueEntry), Error> {
+    let mut conn = resources.connection_pool().connection()?;
+    let profile_id = m::Address::fetch_profile_id(&mut conn, from_address_id)?;
+    drop(conn);
+    let key = eth::TransactionQueueKey {
+        profile_id,
+        chain_id: signing_key.chain_id,
+        address: signing_key.address.to_address(),
+    };
+    let rpc_provider = resources
+        .rpc_manager()
+        .eth_api_provider(signing_key.chain_id);
+    let queue = resources.transaction_queue();
+    let (nonce, tx_hash) = rt::block_on(rpc_provider.send_queued_transaction_async(
+        queue,
+        &key,
+        signing_key,
+        tx,
+    ))?;
+    Ok((tx_hash, (key, nonce)))
+}
+
+/// Split the result for the token transfer callbacks.
+fn split_queued_transfer_result(
+    res: Result<(ethers::types::H256, TransferQueueEntry), Error>,
+) -> (
+    Result<ethers::types::H256, Error>,
+    Option<TransferQueueEntry>,
+) {
+    match res {
+        Ok((tx_hash, queue_entry)) => (Ok(tx_hash), Some(queue_entry)),
+        Err(err) => (Err(err), None),
+    }
+}
+
 fn token_transfer_callbacks(
     resources: Arc<dyn CoreResourcesI>,
     args: EthTokenTransferCallbackArgs,
     tx_hash_res: Result<ethers::types::H256, Error>,
+    queue_entry: Option<TransferQueueEntry>,
 ) -> Result<(), CoreError> {
+    let from_address_id: m::AddressId = args.from_address_id.parse()?;
+    // The transaction is sent to the contract for fungible token transfers.
+    let to_address: eth::ChecksumAddress = match &args.token_id {
+        Some(contract_address) => contract_address.parse()?,
+        None => args.to_checksum_address.parse()?,
+    };
+    let memo = args.memo.clone();
     let (chain_id, mut transfer_res) =
         build_partial_token_transfer_result(resources.clone(), args)?;
     match tx_hash_res {
+        // Nothing was submitted, so there is nothing to record or wait for.
+        Ok(_) if resources.is_demo() => {
+            resources.metrics().record_transfer_sent();
+            resources
+                .ui_callbacks()
+                .sent_token_transfer(transfer_res.clone());
+            resources.ui_callbacks().token_transfer_result(transfer_res);
+        }
         Ok(tx_hash) => {
+            resources.metrics().record_transfer_sent();
+            rt::block_on(m::Transaction::record_submitted_eth(
+                resources.connection_pool(),
+                from_address_id,
+                chain_id,
+                tx_hash,
+                Some(to_address),
+                memo,
+            ));
+
             let sent_res = transfer_res.clone();
             resources.ui_callbacks().sent_token_transfer(sent_res);
 
             let rpc_provider = resources.rpc_manager().eth_api_provider(chain_id);
             let confirmation = rpc_provider.wait_for_confirmation(tx_hash);
+            if let Some((key, nonce)) = queue_entry {
+                let queue = resources.transaction_queue();
+                let queue_res = match confirmation.as_ref() {
+                    Ok(_) => queue.mark_confirmed(&key, nonce),
+                    Err(err) => queue.mark_failed(&key, nonce, err.to_string()),
+                };
+                if let Err(err) = queue_res {
+                    log::error!("Failed to update transaction queue due to error: {err}");
+                }
+            }
             match confirmation {
                 Ok(tx_hash_str) => {
                     let explorer_url = eth::explorer::tx_url(chain_id, &tx_hash_str)?;
@@ -580,6 +2256,7 @@ fn build_partial_token_transfer_result(
         from_address_id,
         to_checksum_address,
         token_id,
+        ..
     } = args;
     let from_address_id: m::AddressId = from_address_id.parse()?;
     let to_address: eth::ChecksumAddress = to_checksum_address.parse()?;
@@ -617,17 +2294,22 @@ fn build_partial_token_transfer_result(
             })?;
     let res = if let Some(contract_address) = token_id {
         let rpc = resources.rpc_manager().eth_api_provider(chain_id);
-        let token_symbol = rpc.fungible_token_symbol(contract_address.try_into()?)?;
+        let contract_address: eth::ChecksumAddress = contract_address.try_into()?;
+        let token_symbol = rpc.fungible_token_symbol(contract_address)?;
+        let decimals = rpc.fungible_token_decimals(contract_address)?;
+        let token = eth::FungibleToken::new(chain_id, contract_address, decimals);
+        let amount = eth::FungibleTokenAmount::new_from_decimal(token, &amount_decimal)?;
+        let amount =
+            eth::CurrencyAmount::new(chain_id, amount.amount, decimals, token_symbol);
         TokenTransferResult::builder()
-            .amount(amount_decimal)
-            .token_symbol(token_symbol)
+            .amount(amount)
             .chain_display_name(chain_id.display_name())
             .to_display_name(to_display_name)
             .build()
     } else {
+        let amount = eth::NativeTokenAmount::new_from_decimal(chain_id, &amount_decimal)?;
         TokenTransferResult::builder()
-            .amount(amount_decimal)
-            .token_symbol(chain_id.native_token().symbol())
+            .amount(&amount)
             .chain_display_name(chain_id.display_name())
             .to_display_name(to_display_name)
             .build()
@@ -658,9 +2340,25 @@ pub mod tests {
 
     use super::*;
     use crate::{
-        backup::{BackupStorageI, TmpBackupStorage},
+        assets::load_profile_pic,
+        backup::{
+            BackupSpool, BackupStorageI, BackupsInProgress, RestoreStatusTracker,
+            TmpBackupStorage,
+        },
+        fiat::{
+            tests::{CorePriceFeedMock, PriceFeedMock},
+            PriceFeedI,
+        },
+        ipfs::IpfsGateways,
+        key_export::KeyExportConfirmations,
+        metrics::Metrics,
+        protocols::eth::in_page_provider::{
+            to_rpc_error, DappRequestError, JsonRpcError,
+        },
+        rng::{RngI, SystemRng},
         CoreInPageCallbackI, DappAllotmentTransferResult, DappApprovalParams,
         DappSignatureResult, DappTransactionApproved, DappTransactionResult,
+        TransactionWarning,
     };
 
     #[derive(Debug)]
@@ -709,11 +2407,31 @@ pub mod tests {
         connection_pool: ConnectionPool,
         keychain: Keychain,
         http_client: HttpClient,
+        ipfs_gateways: IpfsGateways,
         rpc_manager: Box<eth::AnvilRpcManager>,
         public_suffix_list: PublicSuffixList,
         backup_storage: Box<TmpBackupStorage>,
+        backup_spool: BackupSpool,
+        backups_in_progress: BackupsInProgress,
+        restore_status: RestoreStatusTracker,
         device_id: DeviceIdentifier,
         device_name: DeviceName,
+        transaction_inspector: eth::HostTransactionInspector,
+        price_feed: Box<PriceFeedMock>,
+        default_fiat_currency: FiatCurrency,
+        transaction_queue: eth::TransactionQueue,
+        block_number_cache: eth::BlockNumberCache,
+        pending_requests: eth::in_page_provider::PendingRequests,
+        key_export_confirmations: KeyExportConfirmations,
+        profile_pics: ProfilePicRegistry,
+        metrics: Metrics,
+        rng: Box<dyn RngI>,
+        disabled_methods: Vec<String>,
+        approval_timeout: Duration,
+        max_decoded_calldata_bytes: usize,
+        max_backup_size_bytes: u64,
+        app_version: String,
+        demo: bool,
     }
 
     impl CoreResourcesMock {
@@ -733,70 +2451,230 @@ pub mod tests {
                 .expect("path ok");
             let http_client = HttpClient::new(cache_dir);
 
-            let public_suffix_list = PublicSuffixList::new()?;
+            let public_suffix_list = PublicSuffixList::new()?;
+
+            let backup_storage = Box::new(TmpBackupStorage::new(!disable_backups)?);
+            let backup_spool = BackupSpool::new(&tmp_dir.cache_dir, true);
+            let device_id = "test-device-id".parse()?;
+            let device_name = "test-device-name".parse()?;
+            let transaction_inspector =
+                eth::HostTransactionInspector::new(Box::new(TransactionInspectorMock {}));
+            let price_feed = Box::new(PriceFeedMock::new());
+            let default_fiat_currency = FiatCurrency::default_currency();
+
+            Ok(Self {
+                tmp_dir,
+                ui_callbacks,
+                rpc_manager,
+                connection_pool,
+                keychain,
+                http_client,
+                ipfs_gateways: Default::default(),
+                public_suffix_list,
+                backup_storage,
+                backup_spool,
+                backups_in_progress: Default::default(),
+                restore_status: Default::default(),
+                device_id,
+                device_name,
+                transaction_inspector,
+                price_feed,
+                default_fiat_currency,
+                transaction_queue: Default::default(),
+                block_number_cache: Default::default(),
+                pending_requests: Default::default(),
+                key_export_confirmations: Default::default(),
+                profile_pics: Default::default(),
+                metrics: Default::default(),
+                rng: Box::new(SystemRng),
+                disabled_methods: default_disabled_methods(),
+                approval_timeout: config::DEFAULT_APPROVAL_TIMEOUT,
+                max_decoded_calldata_bytes: config::DEFAULT_MAX_DECODED_CALLDATA_BYTES,
+                max_backup_size_bytes: config::DEFAULT_MAX_BACKUP_SIZE_BYTES,
+                app_version: config::BACKUP_MIN_APP_VERSION.into(),
+                demo: false,
+            })
+        }
+
+        pub fn set_keychain(&mut self, keychain: Keychain) {
+            self.keychain = keychain
+        }
+
+        pub fn set_device_id(&mut self, device_id: DeviceIdentifier) {
+            self.device_id = device_id
+        }
+
+        pub fn set_rng(&mut self, rng: Box<dyn RngI>) {
+            self.rng = rng
+        }
+
+        pub fn set_disabled_methods(&mut self, disabled_methods: Vec<String>) {
+            self.disabled_methods = disabled_methods
+        }
+
+        pub fn set_approval_timeout(&mut self, approval_timeout: Duration) {
+            self.approval_timeout = approval_timeout
+        }
+
+        pub fn set_max_decoded_calldata_bytes(&mut self, max_bytes: usize) {
+            self.max_decoded_calldata_bytes = max_bytes
+        }
+
+        pub fn set_max_backup_size_bytes(&mut self, max_bytes: u64) {
+            self.max_backup_size_bytes = max_bytes
+        }
+
+        pub fn set_demo(&mut self, demo: bool) {
+            self.demo = demo
+        }
+
+        pub fn tmp_backup_storage(&self) -> &TmpBackupStorage {
+            &self.backup_storage
+        }
+    }
+
+    impl CoreResourcesI for CoreResourcesMock {
+        fn ui_callbacks(&self) -> &dyn CoreUICallbackI {
+            &*self.ui_callbacks
+        }
+
+        fn connection_pool(&self) -> &ConnectionPool {
+            &self.connection_pool
+        }
+
+        fn keychain(&self) -> &Keychain {
+            &self.keychain
+        }
+
+        fn http_client(&self) -> &HttpClient {
+            &self.http_client
+        }
+
+        fn ipfs_gateways(&self) -> &IpfsGateways {
+            &self.ipfs_gateways
+        }
+
+        fn rpc_manager(&self) -> &dyn eth::RpcManagerI {
+            &*self.rpc_manager
+        }
+
+        fn public_suffix_list(&self) -> &PublicSuffixList {
+            &self.public_suffix_list
+        }
+
+        fn backup_storage(&self) -> &dyn BackupStorageI {
+            &*self.backup_storage
+        }
+
+        fn backup_spool(&self) -> &BackupSpool {
+            &self.backup_spool
+        }
+
+        fn backups_in_progress(&self) -> &BackupsInProgress {
+            &self.backups_in_progress
+        }
+
+        fn restore_status(&self) -> &RestoreStatusTracker {
+            &self.restore_status
+        }
+
+        fn device_id(&self) -> &DeviceIdentifier {
+            &self.device_id
+        }
+
+        fn device_name(&self) -> &DeviceName {
+            &self.device_name
+        }
+
+        fn transaction_inspector(&self) -> &dyn eth::TransactionInspectorI {
+            &self.transaction_inspector
+        }
+
+        fn price_feed(&self) -> &dyn PriceFeedI {
+            &*self.price_feed
+        }
+
+        fn default_fiat_currency(&self) -> &FiatCurrency {
+            &self.default_fiat_currency
+        }
+
+        fn transaction_queue(&self) -> &eth::TransactionQueue {
+            &self.transaction_queue
+        }
+
+        fn block_number_cache(&self) -> &eth::BlockNumberCache {
+            &self.block_number_cache
+        }
 
-            let backup_storage = Box::new(TmpBackupStorage::new(!disable_backups)?);
-            let device_id = "test-device-id".parse()?;
-            let device_name = "test-device-name".parse()?;
+        fn pending_requests(&self) -> &eth::in_page_provider::PendingRequests {
+            &self.pending_requests
+        }
 
-            Ok(Self {
-                tmp_dir,
-                ui_callbacks,
-                rpc_manager,
-                connection_pool,
-                keychain,
-                http_client,
-                public_suffix_list,
-                backup_storage,
-                device_id,
-                device_name,
-            })
+        fn key_export_confirmations(&self) -> &KeyExportConfirmations {
+            &self.key_export_confirmations
         }
 
-        pub fn set_keychain(&mut self, keychain: Keychain) {
-            self.keychain = keychain
+        fn profile_pics(&self) -> &ProfilePicRegistry {
+            &self.profile_pics
         }
 
-        pub fn set_device_id(&mut self, device_id: DeviceIdentifier) {
-            self.device_id = device_id
+        fn metrics(&self) -> &Metrics {
+            &self.metrics
         }
-    }
 
-    impl CoreResourcesI for CoreResourcesMock {
-        fn ui_callbacks(&self) -> &dyn CoreUICallbackI {
-            &*self.ui_callbacks
+        fn rng(&self) -> &dyn RngI {
+            &*self.rng
         }
 
-        fn connection_pool(&self) -> &ConnectionPool {
-            &self.connection_pool
+        fn disabled_methods(&self) -> &[String] {
+            &self.disabled_methods
         }
 
-        fn keychain(&self) -> &Keychain {
-            &self.keychain
+        fn approval_timeout(&self) -> Duration {
+            self.approval_timeout
         }
 
-        fn http_client(&self) -> &HttpClient {
-            &self.http_client
+        fn max_decoded_calldata_bytes(&self) -> usize {
+            self.max_decoded_calldata_bytes
         }
 
-        fn rpc_manager(&self) -> &dyn eth::RpcManagerI {
-            &*self.rpc_manager
+        fn max_backup_size_bytes(&self) -> u64 {
+            self.max_backup_size_bytes
         }
 
-        fn public_suffix_list(&self) -> &PublicSuffixList {
-            &self.public_suffix_list
+        fn app_version(&self) -> &str {
+            &self.app_version
         }
 
-        fn backup_storage(&self) -> &dyn BackupStorageI {
-            &*self.backup_storage
+        fn is_demo(&self) -> bool {
+            self.demo
         }
 
-        fn device_id(&self) -> &DeviceIdentifier {
-            &self.device_id
+        fn is_cache_dir_writable(&self) -> bool {
+            true
         }
 
-        fn device_name(&self) -> &DeviceName {
-            &self.device_name
+        fn cache_dir(&self) -> &Path {
+            &self.tmp_dir.cache_dir
+        }
+    }
+
+    /// Transactions to this address are flagged by the mock transaction inspector.
+    pub const FLAGGED_RECIPIENT: &str = "0x000000000000000000000000000000000000dEaD";
+
+    #[derive(Debug)]
+    pub struct TransactionInspectorMock {}
+
+    impl eth::CoreTransactionInspectorI for TransactionInspectorMock {
+        fn inspect(&self, tx: eth::InspectedTransaction) -> Vec<TransactionWarning> {
+            match tx.to_checksum_address.as_deref() {
+                Some(to) if to == FLAGGED_RECIPIENT => {
+                    vec![TransactionWarning::builder()
+                        .message("Recipient is flagged as malicious")
+                        .build()]
+                }
+                _ => Default::default(),
+            }
         }
     }
 
@@ -824,8 +2702,12 @@ pub mod tests {
             // Important not to use in-memory DB as Sqlite has subtle differences in in memory
             // mode.
             let tmp_dir = TmpCoreDir::new()?;
+            let resources = CoreResourcesMock::new(tmp_dir, disable_backups)?;
+            Self::with_resources(resources)
+        }
 
-            let resources = Arc::new(CoreResourcesMock::new(tmp_dir, disable_backups)?);
+        pub fn with_resources(resources: CoreResourcesMock) -> Result<Self, CoreError> {
+            let resources = Arc::new(resources);
             let core = Arc::new(AppCore::new_with_overrides(resources.clone())?);
             let ui_callback_state = resources.ui_callbacks.state.clone();
 
@@ -869,6 +2751,11 @@ pub mod tests {
                 .expect("there is an profile wallet")
         }
 
+        /// Whether an RPC provider has been requested from the RPC manager.
+        pub fn is_rpc_started(&self) -> bool {
+            self.resources.rpc_manager.is_started()
+        }
+
         pub fn fund_first_profile_wallet(
             &self,
             chain_id: eth::ChainId,
@@ -905,6 +2792,29 @@ pub mod tests {
             DappKeyProvider::new(self.resources.clone(), context).expect("url valid")
         }
 
+        pub fn in_page_provider_for_url(
+            &self,
+            args: InPageRequestContextMockArgs,
+            page_url: &str,
+        ) -> DappKeyProvider {
+            let mut context =
+                InPageRequestContextMock::new(args, self.in_page_callback_state.clone());
+            context.page_url = page_url.into();
+
+            DappKeyProvider::new(self.resources.clone(), Box::new(context))
+                .expect("url valid")
+        }
+
+        pub fn wait_for_in_page_responses(&self, count: usize) {
+            for _ in 0..SLEEP_TIMES {
+                thread::sleep(Duration::from_millis(SLEEP_DURATION_MS));
+                // Don't hold the lock while sleeping.
+                if self.responses().len() == count {
+                    break;
+                }
+            }
+        }
+
         pub fn wait_for_first_in_page_response(&self) {
             for _ in 0..SLEEP_TIMES {
                 thread::sleep(Duration::from_millis(SLEEP_DURATION_MS));
@@ -934,6 +2844,22 @@ pub mod tests {
                 .clone()
         }
 
+        pub fn dapp_sign_batch_approval(&self) -> Option<DappSignBatchApprovalParams> {
+            self.in_page_callback_state
+                .dapp_sign_batch_approval
+                .read()
+                .unwrap()
+                .clone()
+        }
+
+        pub fn dapp_tx_approval_requests(&self) -> Vec<DappTransactionApprovalParams> {
+            self.in_page_callback_state
+                .dapp_tx_approval_requests
+                .read()
+                .unwrap()
+                .clone()
+        }
+
         pub fn responses(&self) -> Vec<String> {
             self.in_page_callback_state
                 .responses
@@ -950,6 +2876,23 @@ pub mod tests {
                 .clone()
         }
 
+        /// Make the user fail authentication from now on.
+        pub fn deny_user_authentication(&self) {
+            *self
+                .ui_callback_state
+                .is_authentication_denied
+                .write()
+                .unwrap() = true;
+        }
+
+        pub fn authentication_requests(&self) -> Vec<String> {
+            self.ui_callback_state
+                .authentication_requests
+                .read()
+                .unwrap()
+                .clone()
+        }
+
         pub fn sent_token_transfers(&self) -> Vec<TokenTransferResult> {
             self.ui_callback_state
                 .sent_token_transfers
@@ -1000,6 +2943,14 @@ pub mod tests {
                 .clone()
         }
 
+        pub fn approval_timeouts(&self) -> Vec<String> {
+            self.ui_callback_state
+                .approval_timeouts
+                .read()
+                .unwrap()
+                .clone()
+        }
+
         pub fn dapp_url(&self) -> &Url {
             &self.in_page_callback_state.page_url
         }
@@ -1013,6 +2964,9 @@ pub mod tests {
         dapp_signature_results: Arc<RwLock<Vec<DappSignatureResult>>>,
         dapp_transaction_approved: Arc<RwLock<Vec<DappTransactionApproved>>>,
         dapp_transaction_results: Arc<RwLock<Vec<DappTransactionResult>>>,
+        approval_timeouts: Arc<RwLock<Vec<String>>>,
+        authentication_requests: Arc<RwLock<Vec<String>>>,
+        is_authentication_denied: Arc<RwLock<bool>>,
     }
 
     impl UICallbackState {
@@ -1024,6 +2978,9 @@ pub mod tests {
                 dapp_transaction_approved: Arc::new(Default::default()),
                 dapp_signature_results: Arc::new(Default::default()),
                 dapp_transaction_results: Arc::new(Default::default()),
+                approval_timeouts: Arc::new(Default::default()),
+                authentication_requests: Arc::new(Default::default()),
+                is_authentication_denied: Arc::new(Default::default()),
             }
         }
 
@@ -1034,6 +2991,7 @@ pub mod tests {
                 + self.dapp_signature_results.read().unwrap().len()
                 + self.dapp_transaction_approved.read().unwrap().len()
                 + self.dapp_transaction_results.read().unwrap().len()
+                + self.approval_timeouts.read().unwrap().len()
         }
 
         fn add_token_transfer_sent(&self, result: TokenTransferResult) {
@@ -1085,6 +3043,22 @@ pub mod tests {
                 results.push(result)
             }
         }
+
+        fn add_approval_timeout(&self, json_rpc_request: String) {
+            {
+                let mut results = self.approval_timeouts.write().expect("no poison");
+                results.push(json_rpc_request)
+            }
+        }
+
+        fn authenticate(&self, reason: String) -> bool {
+            {
+                let mut requests =
+                    self.authentication_requests.write().expect("no poison");
+                requests.push(reason)
+            }
+            !*self.is_authentication_denied.read().expect("no poison")
+        }
     }
 
     #[derive(Debug, Clone)]
@@ -1122,12 +3096,22 @@ pub mod tests {
         fn dapp_transaction_result(&self, result: DappTransactionResult) {
             self.state.add_dapp_transaction_result(result)
         }
+
+        fn approval_timed_out(&self, json_rpc_request: String) {
+            self.state.add_approval_timeout(json_rpc_request)
+        }
+
+        fn authenticate_user(&self, reason: String) -> bool {
+            self.state.authenticate(reason)
+        }
     }
 
     #[derive(Debug)]
     pub struct InPageCallbackStateMock {
         core: Arc<AppCore>,
         dapp_approval: Arc<RwLock<Option<DappApprovalParams>>>,
+        dapp_tx_approval_requests: Arc<RwLock<Vec<DappTransactionApprovalParams>>>,
+        dapp_sign_batch_approval: Arc<RwLock<Option<DappSignBatchApprovalParams>>>,
         responses: Arc<RwLock<Vec<String>>>,
         notifications: Arc<RwLock<Vec<String>>>,
         page_url: Url,
@@ -1138,6 +3122,8 @@ pub mod tests {
             Self {
                 core,
                 dapp_approval: Arc::new(Default::default()),
+                dapp_tx_approval_requests: Arc::new(Default::default()),
+                dapp_sign_batch_approval: Arc::new(Default::default()),
                 responses: Arc::new(Default::default()),
                 notifications: Arc::new(Default::default()),
                 page_url,
@@ -1159,6 +3145,30 @@ pub mod tests {
             }
         }
 
+        fn add_dapp_tx_approval_request(
+            &self,
+            tx_approval: DappTransactionApprovalParams,
+        ) {
+            {
+                let mut requests =
+                    self.dapp_tx_approval_requests.write().expect("no poison");
+                requests.push(tx_approval)
+            }
+        }
+
+        fn update_dapp_sign_batch_approval(
+            &self,
+            batch_approval: DappSignBatchApprovalParams,
+        ) {
+            {
+                let _ = self
+                    .dapp_sign_batch_approval
+                    .write()
+                    .expect("no poison")
+                    .insert(batch_approval);
+            }
+        }
+
         fn add_response(&self, response_hex: String) {
             {
                 let mut responses = self.responses.write().expect("no poison");
@@ -1176,10 +3186,16 @@ pub mod tests {
 
     #[derive(Debug, Clone, TypedBuilder)]
     pub struct InPageRequestContextMockArgs {
+        /// If false, approval requests are left pending.
+        #[builder(default = true)]
+        pub user_responds: bool,
         #[builder(default = true)]
         pub user_approves: bool,
         #[builder(default = true)]
         pub transfer_allotment: bool,
+        /// Indices of the sign batch items that the user declines.
+        #[builder(default)]
+        pub declined_sign_batch_items: Vec<usize>,
     }
 
     impl Default for InPageRequestContextMockArgs {
@@ -1240,6 +3256,9 @@ pub mod tests {
                 self.state.clone(),
             ));
             dapp_approval.transfer_allotment = self.args.transfer_allotment;
+            if !self.args.user_responds {
+                return;
+            }
             if self.args.user_approves {
                 self.state
                     .core
@@ -1253,6 +3272,60 @@ pub mod tests {
             }
         }
 
+        fn request_dapp_transaction_approval(
+            &self,
+            tx_approval: DappTransactionApprovalParams,
+        ) {
+            self.state.add_dapp_tx_approval_request(tx_approval.clone());
+            let context = Box::new(InPageRequestContextMock::new(
+                self.args.clone(),
+                self.state.clone(),
+            ));
+            if !self.args.user_responds {
+                return;
+            }
+            if self.args.user_approves {
+                self.state
+                    .core
+                    .user_approved_dapp_transaction(context, tx_approval)
+                    .expect("user_approved_dapp_transaction ok")
+            } else {
+                self.state
+                    .core
+                    .user_rejected_dapp_transaction(context, tx_approval)
+                    .expect("user_rejected_dapp_transaction ok")
+            }
+        }
+
+        fn request_dapp_sign_batch_approval(
+            &self,
+            mut batch_approval: DappSignBatchApprovalParams,
+        ) {
+            self.state
+                .update_dapp_sign_batch_approval(batch_approval.clone());
+            let context = Box::new(InPageRequestContextMock::new(
+                self.args.clone(),
+                self.state.clone(),
+            ));
+            for index in self.args.declined_sign_batch_items.iter() {
+                batch_approval.items[*index].approved = false;
+            }
+            if !self.args.user_responds {
+                return;
+            }
+            if self.args.user_approves {
+                self.state
+                    .core
+                    .user_approved_dapp_sign_batch(context, batch_approval)
+                    .expect("user_approved_dapp_sign_batch ok")
+            } else {
+                self.state
+                    .core
+                    .user_rejected_dapp_sign_batch(context, batch_approval)
+                    .expect("user_rejected_dapp_sign_batch ok")
+            }
+        }
+
         fn respond(&self, response_hex: String) {
             self.state.add_response(response_hex)
         }
@@ -1271,38 +3344,364 @@ pub mod tests {
         ));
 
         tmp.core
-            .in_page_request(context, "invalid-jsonrpc-payload".to_string())?;
+            .in_page_request(context, "invalid-jsonrpc-payload".to_string(), None)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn cancelled_request_stops_waiting_for_approval() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let args = InPageRequestContextMockArgs::builder()
+            .user_responds(false)
+            .build();
+        let context = || {
+            Box::new(InPageRequestContextMock::new(
+                args.clone(),
+                tmp.in_page_callback_state.clone(),
+            ))
+        };
+        let raw_request =
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_requestAccounts","params":[]}"#;
+
+        tmp.core.in_page_request(
+            context(),
+            raw_request.into(),
+            Some("request-1".into()),
+        )?;
+        for _ in 0..SLEEP_TIMES {
+            if tmp.dapp_approval().is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(SLEEP_DURATION_MS));
+        }
+        let dapp_approval = tmp.dapp_approval().expect("approval requested");
+        assert!(tmp.responses().is_empty());
+
+        tmp.core.cancel_request(context(), "request-1".into())?;
+        tmp.wait_for_first_in_page_response();
+        let responses = tmp.responses();
+        assert_eq!(responses.len(), 1);
+        let JsonRpcError { code, message } =
+            to_rpc_error(&DappRequestError::RequestCancelled);
+        assert!(responses[0].contains(&code.to_string()));
+        assert!(responses[0].contains(&message));
+
+        // A late approval from the UI is ignored.
+        tmp.core.user_approved_dapp(context(), dapp_approval)?;
+        tmp.wait_for_in_page_responses(2);
+        assert_eq!(tmp.responses().len(), 1);
+        let accounts = tmp.core.accounts_for_origin(tmp.dapp_url().to_string())?;
+        assert!(accounts.is_empty());
+
+        // The same request is processed again after the cancellation.
+        let context = Box::new(InPageRequestContextMock::new(
+            Default::default(),
+            tmp.in_page_callback_state.clone(),
+        ));
+        tmp.core.in_page_request(
+            context,
+            raw_request.into(),
+            Some("request-2".into()),
+        )?;
+        tmp.wait_for_in_page_responses(2);
+        assert_eq!(tmp.responses().len(), 2);
+        let accounts = tmp.core.accounts_for_origin(tmp.dapp_url().to_string())?;
+        assert_eq!(accounts.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unanswered_approval_times_out() -> Result<()> {
+        let mut resources = CoreResourcesMock::new(TmpCoreDir::new()?, false)?;
+        // Long enough to approve the second prompt before it times out.
+        resources.set_approval_timeout(Duration::from_millis(5 * SLEEP_DURATION_MS));
+        let tmp = TmpCore::with_resources(resources)?;
+        let args = InPageRequestContextMockArgs::builder()
+            .user_responds(false)
+            .build();
+        let context = || {
+            Box::new(InPageRequestContextMock::new(
+                args.clone(),
+                tmp.in_page_callback_state.clone(),
+            ))
+        };
+        let raw_request =
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_requestAccounts","params":[]}"#;
+
+        tmp.core
+            .in_page_request(context(), raw_request.into(), None)?;
+        tmp.wait_for_first_in_page_response();
+        let responses = tmp.responses();
+        assert_eq!(responses.len(), 1);
+        let JsonRpcError { code, message } =
+            to_rpc_error(&DappRequestError::ApprovalTimedOut);
+        assert!(responses[0].contains(&code.to_string()));
+        assert!(responses[0].contains(&message));
+        assert_eq!(tmp.approval_timeouts(), vec![raw_request.to_string()]);
+
+        // A late approval from the UI is ignored.
+        let dapp_approval = tmp.dapp_approval().expect("approval requested");
+        tmp.core
+            .user_approved_dapp(context(), dapp_approval.clone())?;
+        tmp.wait_for_in_page_responses(2);
+        assert_eq!(tmp.responses().len(), 1);
+        let accounts = tmp.core.accounts_for_origin(tmp.dapp_url().to_string())?;
+        assert!(accounts.is_empty());
+
+        // Resending the same request prompts again and the new prompt can be approved.
+        tmp.core
+            .in_page_request(context(), raw_request.into(), None)?;
+        for _ in 0..SLEEP_TIMES {
+            if tmp.dapp_approval().map(|approval| approval.approval_id)
+                != Some(dapp_approval.approval_id)
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(SLEEP_DURATION_MS));
+        }
+        let second_approval = tmp.dapp_approval().expect("approval requested");
+        assert_ne!(second_approval.approval_id, dapp_approval.approval_id);
+        tmp.core.user_approved_dapp(context(), second_approval)?;
+        tmp.wait_for_in_page_responses(2);
+        assert_eq!(tmp.responses().len(), 2);
+        assert!(!tmp.responses()[1].contains(&message));
+        let accounts = tmp.core.accounts_for_origin(tmp.dapp_url().to_string())?;
+        assert_eq!(accounts.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reopens_persistent_db() -> Result<()> {
+        let tmp_dir = TmpCoreDir::new()?;
+        let new_core = || {
+            let args = CoreArgs {
+                device_id: "test-device-id".into(),
+                device_name: "test-device-name".into(),
+                cache_dir: tmp_dir.cache_dir.to_str().expect("path ok").into(),
+                db_file_path: tmp_dir.db_file_path.clone(),
+                default_fiat_currency: config::DEFAULT_FIAT_CURRENCY.into(),
+                log_rpc: false,
+                disabled_methods: None,
+                approval_timeout_seconds: None,
+                max_decoded_calldata_bytes: None,
+                max_backup_size_bytes: None,
+                app_version: config::BACKUP_MIN_APP_VERSION.into(),
+                db_encryption_key: None,
+                demo: false,
+            };
+            let ui_callbacks =
+                Box::new(CoreUICallbackMock::new(Arc::new(UICallbackState::new())));
+            AppCore::new(
+                args,
+                Box::new(TmpBackupStorage::default()),
+                ui_callbacks,
+                Box::new(TransactionInspectorMock {}),
+                Box::new(CorePriceFeedMock {}),
+            )
+        };
+
+        let core = new_core()?;
+        core.enable_backup()?;
+        let password = core.display_backup_password()?;
+        let profile_ids: Vec<_> =
+            core.list_profiles()?.into_iter().map(|p| p.id).collect();
+        drop(core);
+
+        // The keys are loaded from the keychain file.
+        let core = new_core()?;
+        assert_eq!(core.display_backup_password()?, password);
+        let reopened_ids: Vec<_> =
+            core.list_profiles()?.into_iter().map(|p| p.id).collect();
+        assert_eq!(reopened_ids, profile_ids);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unwritable_cache_dir() -> Result<()> {
+        let tmp_dir = TmpCoreDir::new()?;
+        // A file can't be used as a dir even with root privileges.
+        let cache_dir = tmp_dir.tmp_dir.path().join("cache-file");
+        fs::write(&cache_dir, b"")?;
+        let args = CoreArgs {
+            device_id: "test-device-id".into(),
+            device_name: "test-device-name".into(),
+            cache_dir: cache_dir.to_str().expect("path ok").into(),
+            db_file_path: tmp_dir.db_file_path.clone(),
+            default_fiat_currency: config::DEFAULT_FIAT_CURRENCY.into(),
+            log_rpc: false,
+            disabled_methods: None,
+            approval_timeout_seconds: None,
+            max_decoded_calldata_bytes: None,
+            max_backup_size_bytes: None,
+            app_version: config::BACKUP_MIN_APP_VERSION.into(),
+            db_encryption_key: None,
+            demo: false,
+        };
+        let ui_callbacks =
+            Box::new(CoreUICallbackMock::new(Arc::new(UICallbackState::new())));
+
+        let core = AppCore::new(
+            args,
+            Box::new(TmpBackupStorage::default()),
+            ui_callbacks,
+            Box::new(TransactionInspectorMock {}),
+            Box::new(CorePriceFeedMock {}),
+        )?;
+
+        assert!(!core.version_info()?.cache_dir_writable);
+        assert_eq!(core.list_profiles()?.len(), 1);
+        let backup_file = tempfile::NamedTempFile::new()?;
+        let res = core
+            .resources()
+            .backup_spool()
+            .spool("backup.zip", backup_file.path());
+        assert!(matches!(res, Err(Error::Fatal { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_profile() -> Result<()> {
+        let tmp = TmpCore::new()?;
+
+        let first_profile = tmp.first_profile();
+        let initial_count = tmp.core.list_profiles()?.len();
+
+        let name = "foo".to_string();
+        tmp.core.create_profile(name.clone(), "seal-1".into())?;
+        let profiles = tmp.core.list_profiles()?;
+        assert_eq!(profiles.len(), initial_count + 1);
+        assert_eq!(profiles[initial_count].name, name);
+
+        let name = "bar".to_string();
+        tmp.core.create_profile(name.clone(), "seal-2".into())?;
+        let profiles = tmp.core.list_profiles()?;
+        assert_eq!(profiles.len(), initial_count + 2);
+        assert_eq!(profiles[initial_count + 1].name, name);
+
+        let last_profile = profiles.last().unwrap();
+
+        let active_profile_id = tmp.core.active_profile_id()?;
+        assert_eq!(active_profile_id.as_str(), &first_profile.id);
+
+        tmp.core.set_active_profile_id(last_profile.id.clone())?;
+        let active_profile_id = tmp.core.active_profile_id()?;
+        assert_eq!(active_profile_id.as_str(), &last_profile.id);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reorder_profiles() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        tmp.core.create_profile("foo".into(), "seal-1".into())?;
+        tmp.core.create_profile("bar".into(), "seal-2".into())?;
+        let ids: Vec<String> = tmp
+            .core
+            .list_profiles()?
+            .into_iter()
+            .map(|profile| profile.id)
+            .collect();
+
+        let reversed: Vec<String> = ids.iter().rev().cloned().collect();
+        tmp.core.reorder_profiles(reversed.clone())?;
+        let listed: Vec<String> = tmp
+            .core
+            .list_profiles()?
+            .into_iter()
+            .map(|profile| profile.id)
+            .collect();
+        assert_eq!(listed, reversed);
+
+        // New profile is appended
+        tmp.core.create_profile("baz".into(), "seal-3".into())?;
+        let profiles = tmp.core.list_profiles()?;
+        assert_eq!(profiles.len(), ids.len() + 1);
+        assert_eq!(profiles.last().unwrap().name, "baz");
+        let listed: Vec<String> = profiles[..ids.len()]
+            .iter()
+            .map(|profile| profile.id.clone())
+            .collect();
+        assert_eq!(listed, reversed);
+
+        // Missing and duplicate ids are rejected
+        let all_ids: Vec<String> =
+            profiles.into_iter().map(|profile| profile.id).collect();
+        let res = tmp.core.reorder_profiles(reversed);
+        assert!(matches!(res, Err(CoreError::User { .. })));
+        let mut duplicates = all_ids.clone();
+        duplicates[0] = all_ids[1].clone();
+        let res = tmp.core.reorder_profiles(duplicates);
+        assert!(matches!(res, Err(CoreError::User { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn backup_reminder_without_backup_is_urgent() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let reminder = tmp.core.backup_reminder_status()?;
+        assert_eq!(reminder.severity, backup::BackupReminderSeverity::Urgent);
+        assert!(reminder.has_unsaved_key_material);
+        assert!(reminder.seconds_since_last_upload.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn estimate_backup_size_scales_with_pictures() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let initial_estimate = tmp.core.estimate_backup_size()?;
+        assert!(initial_estimate > 0);
+
+        let padding_bytes = 200_000;
+        let mut picture = load_profile_pic("seal-2")?;
+        picture.extend(vec![1u8; padding_bytes]);
+        let picture_name = "backup-size-test".to_string();
+        tmp.core.register_profile_pictures(HashMap::from([(
+            picture_name.clone(),
+            picture,
+        )]))?;
+        tmp.core.create_profile("foo".into(), picture_name)?;
+
+        let estimate = tmp.core.estimate_backup_size()?;
+        assert!(estimate >= initial_estimate + padding_bytes as u64);
+        // Within a reasonable margin
+        assert!(estimate < initial_estimate + 2 * padding_bytes as u64);
 
         Ok(())
     }
 
     #[test]
-    fn create_profile() -> Result<()> {
+    fn create_profile_with_registered_picture() -> Result<()> {
         let tmp = TmpCore::new()?;
 
-        let first_profile = tmp.first_profile();
-        let initial_count = tmp.core.list_profiles()?.len();
+        // Change the hash so that it doesn't collide with the bundled picture.
+        let mut picture = load_profile_pic("seal-1")?;
+        picture.push(0);
+        let picture_name = "registered-picture-test".to_string();
+        tmp.core.register_profile_pictures(HashMap::from([(
+            picture_name.clone(),
+            picture.clone(),
+        )]))?;
+        assert!(tmp
+            .core
+            .list_available_profile_pictures()?
+            .contains(&picture_name));
 
-        let name = "foo".to_string();
-        tmp.core.create_profile(name.clone(), "seal-1".into())?;
-        let profiles = tmp.core.list_profiles()?;
-        assert_eq!(profiles.len(), initial_count + 1);
-        assert_eq!(profiles[initial_count].name, name);
+        tmp.core
+            .create_profile("foo".into(), picture_name.clone())?;
 
-        let name = "bar".to_string();
-        tmp.core.create_profile(name.clone(), "seal-2".into())?;
         let profiles = tmp.core.list_profiles()?;
-        assert_eq!(profiles.len(), initial_count + 2);
-        assert_eq!(profiles[initial_count + 1].name, name);
-
-        let last_profile = profiles.last().unwrap();
-
-        let active_profile_id = tmp.core.active_profile_id()?;
-        assert_eq!(active_profile_id.as_str(), &first_profile.id);
-
-        tmp.core.set_active_profile_id(last_profile.id.clone())?;
-        let active_profile_id = tmp.core.active_profile_id()?;
-        assert_eq!(active_profile_id.as_str(), &last_profile.id);
+        let profile = profiles.last().unwrap();
+        assert_eq!(profile.picture, picture);
+        assert_eq!(
+            tmp.core.fetch_bundled_profile_picture(picture_name)?,
+            picture
+        );
 
         Ok(())
     }
@@ -1398,6 +3797,116 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn native_token_transfer_preview_high_value() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        tmp.core.set_eth_high_value_threshold(
+            tmp.first_profile().id,
+            chain_id.into(),
+            "1".into(),
+        )?;
+
+        let below = transfer_native_token_args(&tmp, 1);
+        let preview = tmp.core.eth_native_token_transfer_preview(below)?;
+        assert!(!preview.high_value);
+        assert_eq!(preview.amount, "1");
+        assert_eq!(preview.high_value_threshold, Some("1".into()));
+
+        let above = transfer_native_token_args(&tmp, 2);
+        let preview = tmp.core.eth_native_token_transfer_preview(above)?;
+        assert!(preview.high_value);
+        assert_eq!(preview.amount, "2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn fiat_currency_changes_preview_fiat_amount() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile_id = tmp.first_profile().id;
+
+        let preview = tmp
+            .core
+            .eth_native_token_transfer_preview(transfer_native_token_args(&tmp, 1))?;
+        let usd = preview.fiat_amount.expect("has price");
+        assert_eq!(usd.currency, "USD");
+        assert_eq!(usd.amount, "2000");
+        assert!(!usd.fallback);
+
+        tmp.core
+            .set_fiat_currency(profile_id.clone(), "eur".into())?;
+        let preview = tmp
+            .core
+            .eth_native_token_transfer_preview(transfer_native_token_args(&tmp, 1))?;
+        let eur = preview.fiat_amount.expect("has price");
+        assert_eq!(eur.currency, "EUR");
+        assert_eq!(eur.amount, "1800");
+        assert!(!eur.fallback);
+
+        // The mock price feed doesn't support JPY
+        tmp.core
+            .set_fiat_currency(profile_id.clone(), "JPY".into())?;
+        let preview = tmp
+            .core
+            .eth_native_token_transfer_preview(transfer_native_token_args(&tmp, 1))?;
+        let fallback = preview.fiat_amount.expect("has price");
+        assert_eq!(fallback.currency, "USD");
+        assert!(fallback.fallback);
+
+        let result = tmp.core.set_fiat_currency(profile_id, "XYZ".into());
+        assert!(matches!(result, Err(CoreError::User { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn high_value_transfer_requires_confirmation() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        tmp.fund_first_profile_wallet(chain_id, 10)?;
+        tmp.core.set_eth_high_value_threshold(
+            tmp.first_profile().id,
+            chain_id.into(),
+            "1".into(),
+        )?;
+
+        let mut args = transfer_native_token_args(&tmp, 2);
+        let result = tmp.core.eth_transfer_native_token(args.clone());
+        assert!(matches!(result, Err(CoreError::User { .. })));
+
+        args.confirmed_high_value = true;
+        tmp.core.eth_transfer_native_token(args)?;
+        tmp.wait_for_ui_callbacks(2);
+        let transfer_results = tmp.token_transfer_results();
+        assert_eq!(transfer_results.len(), 1);
+        assert!(transfer_results[0].error_message.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fungible_token_transfer_preview_without_price() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let wallet = tmp.first_profile_wallet();
+        let to_address = ethers::types::Address::random();
+        let args = EthTransferFungibleTokenArgs::builder()
+            .from_address_id(wallet.id)
+            .to_checksum_address(ethers::utils::to_checksum(&to_address, None))
+            .amount_decimal("1000000".into())
+            .token_id(ethers::utils::to_checksum(
+                &ethers::types::Address::random(),
+                None,
+            ))
+            .build();
+
+        let preview = tmp.core.eth_fungible_token_transfer_preview(args)?;
+        assert!(!preview.high_value);
+        assert!(preview.high_value_threshold.is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn cannot_transfer_fungible_token_between_profiles() -> Result<()> {
         let tmp = TmpCore::new()?;
@@ -1466,6 +3975,34 @@ pub mod tests {
             .build()
     }
 
+    #[test]
+    fn transfer_memo_round_trips() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        tmp.fund_first_profile_wallet(chain_id, 10)?;
+
+        let mut args = transfer_native_token_args(&tmp, 1);
+        args.memo = Some("x".repeat(config::MAX_TRANSFER_MEMO_LENGTH + 1));
+        let result = tmp.core.eth_transfer_native_token(args.clone());
+        assert!(matches!(result, Err(CoreError::User { .. })));
+
+        let memo = "Invoice #42";
+        args.memo = Some(memo.into());
+        tmp.core.eth_transfer_native_token(args.clone())?;
+        tmp.wait_for_ui_callbacks(2);
+
+        let transactions = tmp.core.list_transactions(args.from_address_id)?;
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].memo.as_deref(), Some(memo));
+        assert_eq!(transactions[0].chain_id, u64::from(chain_id));
+        assert_eq!(
+            transactions[0].to_checksum_address,
+            Some(args.to_checksum_address)
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn eth_transfer_native_token_success_callbacks() -> Result<()> {
         let tmp = TmpCore::new()?;
@@ -1488,6 +4025,66 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn demo_transfer_returns_synthetic_result_without_rpc() -> Result<()> {
+        let mut resources = CoreResourcesMock::new(TmpCoreDir::new()?, false)?;
+        resources.set_demo(true);
+        let tmp = TmpCore::with_resources(resources)?;
+
+        let profiles = tmp.core.list_profiles()?;
+        assert_eq!(profiles.len(), 3);
+        for profile in profiles.iter() {
+            let dapps: HashSet<&str> = profile
+                .dapps
+                .iter()
+                .map(|dapp| dapp.human_identifier.as_str())
+                .collect();
+            for demo_dapp in ["uniswap.org", "opensea.io", "ens.domains"] {
+                assert!(dapps.contains(demo_dapp), "{demo_dapp} in {dapps:?}");
+            }
+        }
+        // The default profile has the default dapps from the data migrations too.
+        let default_profile = tmp.core.active_profile_id()?;
+        let dapp_counts: Vec<usize> =
+            profiles.iter().map(|profile| profile.dapps.len()).collect();
+        for profile in profiles.iter() {
+            if profile.id == default_profile {
+                assert!(profile.dapps.len() > 3, "{dapp_counts:?}");
+            } else {
+                assert_eq!(profile.dapps.len(), 3, "{dapp_counts:?}");
+            }
+        }
+        assert!(tmp.core.version_info()?.demo);
+
+        // Nothing is fetched from the network.
+        let wallet_id = profiles[0].wallets[0].id.clone();
+        tmp.core.tokens_for_address_id(wallet_id.clone())?;
+        let nfts = tmp
+            .core
+            .list_nfts(ListNftsArgs::builder().address_id(wallet_id).build())?;
+        assert!(nfts.nfts.is_empty());
+        let chain_id = eth::ChainId::default_wallet_chain();
+        let allowances = tmp.core.scan_allowances(default_profile, chain_id.into())?;
+        assert!(allowances.is_empty());
+        assert!(!tmp.is_rpc_started());
+
+        // Not funded
+        let args = transfer_native_token_args(&tmp, 1);
+        tmp.core.eth_transfer_native_token(args.clone())?;
+        tmp.wait_for_ui_callbacks(2);
+
+        let sent_results = tmp.sent_token_transfers();
+        assert_eq!(sent_results.len(), 1);
+        assert!(sent_results[0].error_message.is_none());
+        let transfer_results = tmp.token_transfer_results();
+        assert_eq!(transfer_results.len(), 1);
+        assert!(transfer_results[0].error_message.is_none());
+        assert!(tmp.core.list_transactions(args.from_address_id)?.is_empty());
+        assert!(!tmp.is_rpc_started());
+
+        Ok(())
+    }
+
     #[test]
     fn eth_transfer_native_token_full_amount() -> Result<()> {
         let tmp = TmpCore::new()?;
@@ -1527,6 +4124,441 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn current_block_number_is_cached() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+
+        let block_number = tmp.core.current_block_number(chain_id.into())?;
+        // Mines a new block
+        tmp.fund_first_profile_wallet(chain_id, 1)?;
+        let cached_block_number = tmp.core.current_block_number(chain_id.into())?;
+        assert_eq!(cached_block_number, block_number);
+
+        let rpc_provider = tmp.resources.rpc_manager().eth_api_provider(chain_id);
+        assert!(rpc_provider.block_number()? > block_number);
+
+        Ok(())
+    }
+
+    #[test]
+    fn balance_at() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        let wallet = tmp.first_profile_wallet();
+        tmp.fund_first_profile_wallet(chain_id, 2)?;
+
+        let latest = tmp.core.balance_at(
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+            "latest".into(),
+        )?;
+        assert_eq!(latest, "2");
+        let genesis = tmp.core.balance_at(
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+            "0".into(),
+        )?;
+        assert_eq!(genesis, "0");
+
+        let result =
+            tmp.core
+                .balance_at(chain_id.into(), wallet.checksum_address, "foo".into());
+        assert!(matches!(result, Err(CoreError::User { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_dapp_deeplink() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile = tmp.first_profile();
+        let dapp = profile.dapps.first().expect("there is a dapp");
+
+        let links = [
+            dapp.id.clone(),
+            format!("{}{}", config::DAPP_DEEP_LINK_PREFIX, dapp.id),
+            dapp.url.clone(),
+        ];
+        for link in links {
+            let detail = tmp.core.resolve_dapp_deeplink(link)?.expect("dapp exists");
+            assert_eq!(detail.id, dapp.id);
+            assert_eq!(detail.human_identifier, dapp.human_identifier);
+            assert!(detail.profile_ids.contains(&profile.id));
+        }
+
+        let unknown_id = "A".repeat(52);
+        assert_eq!(tmp.core.resolve_dapp_deeplink(unknown_id)?, None);
+        let unknown_url = "https://unknown-dapp.org".to_string();
+        assert_eq!(tmp.core.resolve_dapp_deeplink(unknown_url)?, None);
+
+        for malformed in ["foo", "sealvault://dapp/foo", "file:///etc/passwd"] {
+            let res = tmp.core.resolve_dapp_deeplink(malformed.into());
+            assert!(matches!(res, Err(CoreError::User { .. })));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn signs_signing_request_offline() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        let profile = tmp.first_profile();
+        let wallet = tmp.first_profile_wallet();
+        tmp.fund_first_profile_wallet(chain_id, 1)?;
+        let to_address: eth::ChecksumAddress = ethers::types::Address::random().into();
+
+        let args = EthSigningRequestArgs::builder()
+            .from_checksum_address(wallet.checksum_address.clone())
+            .to_checksum_address(Some(to_address.to_string()))
+            .value_wei(ethers::utils::parse_ether("0.1")?.to_string())
+            .build();
+        let request =
+            tmp.core
+                .build_signing_request(profile.id.clone(), chain_id.into(), args)?;
+        assert_eq!(request.nonce, 0);
+
+        let payload = tmp.core.encode_signing_request(request.clone())?;
+        let decoded = tmp.core.decode_signing_request(payload)?;
+        assert_eq!(decoded, request);
+
+        // The sender account must belong to the profile.
+        let mut tampered = decoded.clone();
+        tampered.from_checksum_address = to_address.to_string();
+        let res = tmp.core.sign_request(tampered);
+        assert!(matches!(res, Err(CoreError::User { .. })));
+
+        // Dapp accounts can't sign requests, because they have spending limits.
+        let dapp_address = profile
+            .dapps
+            .first()
+            .and_then(|dapp| dapp.addresses.first())
+            .expect("there is a dapp address");
+        let mut dapp_request = decoded.clone();
+        dapp_request.from_checksum_address = dapp_address.checksum_address.clone();
+        let res = tmp.core.sign_request(dapp_request);
+        assert!(matches!(res, Err(CoreError::User { .. })));
+
+        // The nonce is reserved in the queue, so the next transaction doesn't reuse it.
+        let key = transaction_queue_key(
+            profile.id.clone(),
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+        )?;
+        let queue = tmp.resources.transaction_queue();
+        assert_eq!(queue.enqueue(&key, 0.into(), None, U256::zero())?, 1.into());
+        queue.mark_failed(&key, 1.into(), "not sent".into())?;
+        queue.cancel(&key, 1.into())?;
+
+        let signed = tmp.core.sign_request(decoded)?;
+        let tx_hash = tmp
+            .core
+            .broadcast_signed_transaction(chain_id.into(), signed.raw_transaction)?;
+        assert_eq!(tx_hash, signed.tx_hash);
+        let rpc_provider = tmp.core.rpc_manager().eth_api_provider(chain_id);
+        rpc_provider.wait_for_confirmation(tx_hash.parse()?)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn reset_nonce_unblocks_queue() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        let profile = tmp.first_profile();
+        let wallet = tmp.first_profile_wallet();
+        tmp.fund_first_profile_wallet(chain_id, 1)?;
+
+        let key = transaction_queue_key(
+            profile.id.clone(),
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+        )?;
+        // Wedge the queue with nonces that were never used on chain.
+        let queue = tmp.resources.transaction_queue();
+        queue.enqueue(&key, 5.into(), None, U256::zero())?;
+        let failed = queue.enqueue(&key, 5.into(), None, U256::zero())?;
+        queue.mark_failed(&key, failed, "nonce too high".into())?;
+        let res = queue.enqueue(&key, 0.into(), None, U256::zero());
+        assert!(matches!(res, Err(Error::User { .. })));
+
+        let nonce = tmp.core.reset_nonce(
+            profile.id.clone(),
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+        )?;
+        assert_eq!(nonce, 0);
+        assert_eq!(queue.enqueue(&key, 0.into(), None, U256::zero())?, 0.into());
+
+        let statuses: Vec<eth::QueuedTransactionStatus> = tmp
+            .core
+            .transaction_queue(profile.id, chain_id.into(), wallet.checksum_address)?
+            .into_iter()
+            .map(|tx| tx.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                eth::QueuedTransactionStatus::Queued,
+                eth::QueuedTransactionStatus::Cleared,
+                eth::QueuedTransactionStatus::Cleared,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn pending_balance_subtracts_queued_transactions() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        let profile = tmp.first_profile();
+        let wallet = tmp.first_profile_wallet();
+        tmp.fund_first_profile_wallet(chain_id, 2)?;
+
+        let key = transaction_queue_key(
+            profile.id.clone(),
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+        )?;
+        let outflow = ethers::utils::parse_ether("0.5")?;
+        tmp.resources
+            .transaction_queue()
+            .enqueue(&key, 0.into(), None, outflow)?;
+
+        let balance = tmp.core.native_token_balance(
+            profile.id.clone(),
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+            true,
+        )?;
+        assert_eq!(balance.confirmed.display(), "2");
+        assert_eq!(
+            balance.pending.map(|pending| pending.display()),
+            Some("1.5".into())
+        );
+
+        let balance = tmp.core.native_token_balance(
+            profile.id,
+            chain_id.into(),
+            wallet.checksum_address,
+            false,
+        )?;
+        assert_eq!(balance.confirmed.display(), "2");
+        assert_eq!(balance.pending, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wallet_transfers_are_queued() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        let profile = tmp.first_profile();
+        let wallet = tmp.first_profile_wallet();
+        tmp.fund_first_profile_wallet(chain_id, 10)?;
+
+        let args = transfer_native_token_args(&tmp, 1);
+        tmp.core.eth_transfer_native_token(args)?;
+        tmp.wait_for_ui_callbacks(2);
+
+        let queue = tmp.core.transaction_queue(
+            profile.id.clone(),
+            chain_id.into(),
+            wallet.checksum_address.clone(),
+        )?;
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].status, eth::QueuedTransactionStatus::Confirmed);
+        assert!(queue[0].tx_hash.is_some());
+        let balance = tmp.core.native_token_balance(
+            profile.id,
+            chain_id.into(),
+            wallet.checksum_address,
+            true,
+        )?;
+        assert_eq!(balance.pending, Some(balance.confirmed));
+
+        Ok(())
+    }
+
+    #[test]
+    fn discovers_no_tokens_without_token_transfers() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile_id = tmp.first_profile().id;
+        let chain_id = eth::ChainId::default_wallet_chain();
+        // Native token transfers don't emit logs.
+        tmp.fund_first_profile_wallet(chain_id, 1)?;
+
+        let discovered = tmp
+            .core
+            .discover_tokens(profile_id.clone(), chain_id.into())?;
+        assert!(discovered.is_empty());
+
+        tmp.core
+            .set_auto_discover_tokens(profile_id.clone(), true)?;
+        let mut conn = tmp.connection_pool().connection()?;
+        let settings = m::Profile::fetch_user_settings(&mut conn, &profile_id.parse()?)?;
+        assert!(settings.auto_discover_tokens);
+
+        Ok(())
+    }
+
+    #[test]
+    fn active_chains_for_profile() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile = tmp.first_profile();
+        let wallet = tmp.first_profile_wallet();
+        let from_address_id: m::AddressId = wallet.id.parse()?;
+
+        let record = |chain_id: eth::ChainId, tx_hash: u64| {
+            tmp.connection_pool().deferred_transaction(|mut tx_conn| {
+                let params = m::NewEthTransactionParams::builder()
+                    .from_address_id(&from_address_id)
+                    .chain_id(chain_id)
+                    .tx_hash(ethers::types::H256::from_low_u64_be(tx_hash))
+                    .build();
+                m::Transaction::create_eth(&mut tx_conn, &params)
+            })
+        };
+
+        let active_chains = tmp.core.active_chains_for_profile(profile.id.clone())?;
+        assert!(active_chains.is_empty());
+
+        record(eth::ChainId::EthMainnet, 1)?;
+        // Timestamps have millisecond resolution.
+        thread::sleep(Duration::from_millis(2));
+        record(eth::ChainId::PolygonMainnet, 2)?;
+
+        let active_chains = tmp.core.active_chains_for_profile(profile.id.clone())?;
+        assert_eq!(
+            active_chains,
+            vec![
+                u64::from(eth::ChainId::PolygonMainnet),
+                u64::from(eth::ChainId::EthMainnet)
+            ]
+        );
+
+        thread::sleep(Duration::from_millis(2));
+        record(eth::ChainId::EthMainnet, 3)?;
+
+        let active_chains = tmp.core.active_chains_for_profile(profile.id)?;
+        assert_eq!(
+            active_chains,
+            vec![
+                u64::from(eth::ChainId::EthMainnet),
+                u64::from(eth::ChainId::PolygonMainnet)
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn accounts_for_origin_across_profiles() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile_id = tmp.core.active_profile_id()?;
+        tmp.core.create_profile("other".into(), "seal-2".into())?;
+        let other_profile_id = tmp.core.list_profiles()?.pop().unwrap().id;
+        let manifest = r#"[{"url": "https://app.multi-profile.org"}]"#;
+        let results = tmp.core.import_dapps(manifest.into())?;
+        let dapp_id: DeterministicId = results[0].dapp_id.clone().unwrap().parse()?;
+        // Imported dapps aren't added to profiles until the user connects.
+        let accounts = tmp
+            .core
+            .accounts_for_origin("https://app.multi-profile.org".into())?;
+        assert!(accounts.is_empty());
+        for profile_id in [&profile_id, &other_profile_id] {
+            let profile_id: DeterministicId = profile_id.parse()?;
+            tmp.connection_pool().deferred_transaction(|mut tx_conn| {
+                let params = m::CreateEthAddressParams::builder()
+                    .profile_id(&profile_id)
+                    .chain_id(eth::ChainId::default_dapp_chain())
+                    .dapp_id(Some(&dapp_id))
+                    .build();
+                m::Address::create_eth_key_and_address(
+                    &mut tx_conn,
+                    tmp.resources.keychain(),
+                    &params,
+                )
+            })?;
+        }
+
+        let accounts = tmp
+            .core
+            .accounts_for_origin("https://app.multi-profile.org/swap".into())?;
+
+        let profile_ids: Vec<&str> = accounts
+            .iter()
+            .map(|account| account.profile_id.as_str())
+            .collect();
+        assert_eq!(
+            profile_ids,
+            vec![profile_id.as_str(), other_profile_id.as_str()]
+        );
+        assert_ne!(accounts[0].checksum_address, accounts[1].checksum_address);
+        let unknown = tmp
+            .core
+            .accounts_for_origin("https://unknown.example.org".into())?;
+        assert!(unknown.is_empty());
+        let res = tmp
+            .core
+            .accounts_for_origin("http://app.multi-profile.org".into());
+        assert!(matches!(res, Err(CoreError::User { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn clear_history_for_profile() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile = tmp.first_profile();
+        tmp.core.create_profile("other".into(), "seal-2".into())?;
+        let other_profile = tmp.core.list_profiles()?.pop().unwrap();
+        let from_address_id: m::AddressId = tmp.first_profile_wallet().id.parse()?;
+        let other_address_id: m::AddressId = other_profile.wallets[0].id.parse()?;
+
+        let record = |address_id: &m::AddressId, tx_hash: u64| {
+            tmp.connection_pool().deferred_transaction(|mut tx_conn| {
+                let params = m::NewEthTransactionParams::builder()
+                    .from_address_id(address_id)
+                    .chain_id(eth::ChainId::EthMainnet)
+                    .tx_hash(ethers::types::H256::from_low_u64_be(tx_hash))
+                    .build();
+                m::Transaction::create_eth(&mut tx_conn, &params)
+            })
+        };
+        record(&from_address_id, 1)?;
+        record(&from_address_id, 2)?;
+        record(&other_address_id, 3)?;
+        let list = |address_id: &m::AddressId| -> Result<Vec<m::Transaction>> {
+            let mut conn = tmp.connection_pool().connection()?;
+            Ok(m::Transaction::list_for_address(&mut conn, address_id)?)
+        };
+        let backup_version = || -> Result<backup::BackupVersion> {
+            let mut conn = tmp.connection_pool().connection()?;
+            Ok(m::LocalSettings::fetch_backup_version(&mut conn)?)
+        };
+        let initial_backup_version = backup_version()?;
+
+        // Nothing is older than the epoch
+        let cleared = tmp.core.clear_history(
+            profile.id.clone(),
+            HistoryFilter::OlderThan { timestamp: 0 },
+        )?;
+        assert_eq!(cleared.transactions, 0);
+        assert_eq!(list(&from_address_id)?.len(), 2);
+
+        let cleared = tmp.core.clear_history(profile.id, HistoryFilter::All)?;
+
+        assert_eq!(cleared.transactions, 2);
+        assert!(list(&from_address_id)?.is_empty());
+        assert_eq!(list(&other_address_id)?.len(), 1);
+        assert!(backup_version()? > initial_backup_version);
+
+        Ok(())
+    }
+
     #[test]
     fn tokens_for_address_id() -> Result<()> {
         let tmp = TmpCore::new()?;
@@ -2,13 +2,33 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::Read,
+    sync::RwLock,
+};
+
+use flate2::{read::ZlibDecoder, Crc};
+use lazy_static::lazy_static;
+use regex::Regex;
 use rust_embed::{EmbeddedFile, RustEmbed};
 
 use crate::{
-    config::{PROFILE_PIC_EXTENSION, PROFILE_PIC_PREFIX},
+    config::{
+        MAX_PROFILE_PIC_DIMENSION_PX, MAX_PROFILE_PIC_SIZE_BYTES, PROFILE_PIC_EXTENSION,
+        PROFILE_PIC_PREFIX,
+    },
     error::Error,
+    utils::blake3_hash,
 };
 
+lazy_static! {
+    static ref PROFILE_PIC_NAME_REGEX: Regex =
+        Regex::new(r"^[a-zA-Z0-9_-]{1,64}$").expect("static is ok");
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
 #[derive(RustEmbed)]
 #[folder = "assets/"]
 struct Asset;
@@ -33,11 +53,16 @@ pub(super) fn load_asset_as_string(asset_path: &str) -> Result<String, Error> {
     Ok(str.to_string())
 }
 
-/// Load a profile picture image as bytes by its name (file name without extension).
+/// Load a bundled profile picture image as bytes by its name (file name without extension).
 pub fn load_profile_pic(name: &str) -> Result<Vec<u8>, Error> {
+    load_bundled_profile_pic(name).ok_or_else(|| Error::Fatal {
+        error: format!("Profile picture not found with name: '{}'", name),
+    })
+}
+
+fn load_bundled_profile_pic(name: &str) -> Option<Vec<u8>> {
     let asset_path = format!("{}/{}{}", PROFILE_PIC_PREFIX, name, PROFILE_PIC_EXTENSION);
-    let embedded_file = load_asset(&asset_path)?;
-    Ok(Vec::from(embedded_file.data))
+    Asset::get(&asset_path).map(|embedded_file| Vec::from(embedded_file.data))
 }
 
 /// List available profile picture names.
@@ -52,6 +77,229 @@ pub fn list_profile_pics() -> Vec<String> {
         .collect()
 }
 
+/// The bundled profile picture name that is assigned to a profile by default, eg. when its
+/// picture is missing. The same seed always maps to the same picture.
+pub fn default_profile_pic_name(seed: &str) -> String {
+    let mut names = list_profile_pics();
+    names.sort();
+    let hash = blake3_hash(seed.as_bytes());
+    let index =
+        u64::from_le_bytes(hash.as_bytes()[..8].try_into().expect("hash is 32 bytes"));
+    names.swap_remove(index as usize % names.len())
+}
+
+/// Profile pictures registered by the host at runtime in addition to the bundled ones.
+#[derive(Debug, Default)]
+pub struct ProfilePicRegistry {
+    pics: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl ProfilePicRegistry {
+    /// Register additional profile pictures so that hosts can add pictures without
+    /// rebuilding the core. Names may not collide with bundled pictures. Registering a name
+    /// again replaces the picture. Nothing is registered if any of the pictures is invalid.
+    pub fn register(&self, pics: HashMap<String, Vec<u8>>) -> Result<(), Error> {
+        let bundled: BTreeSet<String> = list_profile_pics().into_iter().collect();
+        for (name, image) in pics.iter() {
+            if !PROFILE_PIC_NAME_REGEX.is_match(name) {
+                return Err(Error::User {
+                    explanation: format!("Invalid profile picture name: '{}'", name),
+                });
+            }
+            if bundled.contains(name) {
+                return Err(Error::User {
+                    explanation: format!(
+                        "Profile picture name '{}' is taken by a bundled one",
+                        name
+                    ),
+                });
+            }
+            validate_profile_pic(image)?;
+        }
+        let mut registered = self.pics.write()?;
+        registered.extend(pics);
+        Ok(())
+    }
+
+    /// Load a bundled or registered profile picture by its name.
+    pub fn load(&self, name: &str) -> Result<Vec<u8>, Error> {
+        if let Some(image) = load_bundled_profile_pic(name) {
+            return Ok(image);
+        }
+        let registered = self.pics.read()?;
+        registered.get(name).cloned().ok_or_else(|| Error::User {
+            explanation: format!("Profile picture not found with name: '{}'", name),
+        })
+    }
+
+    /// List the names of bundled and registered profile pictures in sorted order.
+    pub fn list_available(&self) -> Result<Vec<String>, Error> {
+        let mut names: BTreeSet<String> = list_profile_pics().into_iter().collect();
+        let registered = self.pics.read()?;
+        names.extend(registered.keys().cloned());
+        Ok(names.into_iter().collect())
+    }
+}
+
+/// Make sure the image is a PNG within the size limits by decoding it.
+pub fn validate_profile_pic(image: &[u8]) -> Result<(), Error> {
+    let invalid = |reason: &str| Error::User {
+        explanation: format!("Invalid profile picture: {}", reason),
+    };
+    if image.len() > MAX_PROFILE_PIC_SIZE_BYTES {
+        return Err(invalid("too large"));
+    }
+    decode_png(image).map_err(invalid)
+}
+
+/// The image header of a PNG.
+/// See https://www.w3.org/TR/png/#11IHDR
+struct PngHeader {
+    width: u32,
+    height: u32,
+    color_type: u8,
+    bits_per_pixel: usize,
+    interlaced: bool,
+}
+
+impl PngHeader {
+    fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        if data.len() != 13 {
+            return Err("invalid image header");
+        }
+        let width = u32::from_be_bytes(data[0..4].try_into().expect("checked length"));
+        let height = u32::from_be_bytes(data[4..8].try_into().expect("checked length"));
+        let valid_dimension = |d: u32| d > 0 && d <= MAX_PROFILE_PIC_DIMENSION_PX;
+        if !valid_dimension(width) || !valid_dimension(height) {
+            return Err("invalid dimensions");
+        }
+        let bit_depth = data[8];
+        let color_type = data[9];
+        let channels = match (color_type, bit_depth) {
+            // Grayscale
+            (0, 1 | 2 | 4 | 8 | 16) => 1,
+            // RGB
+            (2, 8 | 16) => 3,
+            // Palette
+            (3, 1 | 2 | 4 | 8) => 1,
+            // Grayscale with alpha
+            (4, 8 | 16) => 2,
+            // RGBA
+            (6, 8 | 16) => 4,
+            _ => return Err("invalid color type or bit depth"),
+        };
+        // Compression and filter methods must be 0, interlace method is 0 or 1 (Adam7).
+        if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+            return Err("unsupported encoding");
+        }
+        Ok(Self {
+            width,
+            height,
+            color_type,
+            bits_per_pixel: channels * bit_depth as usize,
+            interlaced: data[12] == 1,
+        })
+    }
+
+    /// The number of scanlines and the bytes per scanline including the filter type byte for
+    /// each pass of the image. Non-interlaced images have a single pass.
+    fn passes(&self) -> Vec<(usize, usize)> {
+        // (x offset, y offset, x step, y step)
+        let passes: &[(u32, u32, u32, u32)] = if self.interlaced {
+            &[
+                (0, 0, 8, 8),
+                (4, 0, 8, 8),
+                (0, 4, 4, 8),
+                (2, 0, 4, 4),
+                (0, 2, 2, 4),
+                (1, 0, 2, 2),
+                (0, 1, 1, 2),
+            ]
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+        let pass_len = |len: u32, offset: u32, step: u32| {
+            len.saturating_sub(offset).saturating_add(step - 1) / step
+        };
+        passes
+            .iter()
+            .map(|(x, y, dx, dy)| {
+                let width = pass_len(self.width, *x, *dx) as usize;
+                let height = pass_len(self.height, *y, *dy) as usize;
+                (width, height)
+            })
+            .filter(|(width, height)| *width > 0 && *height > 0)
+            .map(|(width, height)| (height, 1 + (width * self.bits_per_pixel + 7) / 8))
+            .collect()
+    }
+}
+
+/// Decode a PNG to make sure that it's a valid image: the chunk checksums match, the image
+/// data inflates to exactly the size that the header implies and each scanline has a valid
+/// filter type. Pixels aren't unfiltered, because that can't fail. Data after the end chunk
+/// is ignored like decoders do.
+/// See https://www.w3.org/TR/png/
+fn decode_png(image: &[u8]) -> Result<(), &'static str> {
+    let mut rest = image.strip_prefix(PNG_SIGNATURE).ok_or("not a PNG")?;
+    let mut header: Option<PngHeader> = None;
+    let mut has_palette = false;
+    let mut image_data: Vec<u8> = Vec::new();
+    loop {
+        let length = rest.get(0..4).ok_or("truncated")?;
+        let length =
+            u32::from_be_bytes(length.try_into().expect("checked length")) as usize;
+        let data_end = length.checked_add(8).ok_or("truncated")?;
+        let chunk = rest.get(4..data_end).ok_or("truncated")?;
+        let crc = rest.get(data_end..data_end + 4).ok_or("truncated")?;
+        let mut expected_crc = Crc::new();
+        expected_crc.update(chunk);
+        if expected_crc.sum().to_be_bytes() != crc {
+            return Err("corrupt chunk");
+        }
+        rest = &rest[data_end + 4..];
+
+        let (chunk_type, data) = chunk.split_at(4);
+        match (chunk_type, header.is_some()) {
+            (b"IHDR", false) => header = Some(PngHeader::parse(data)?),
+            (_, false) => return Err("missing image header"),
+            (b"IHDR", true) => return Err("duplicate image header"),
+            (b"PLTE", true) => has_palette = true,
+            (b"IDAT", true) => image_data.extend_from_slice(data),
+            (b"IEND", true) => break,
+            _ => (),
+        }
+    }
+    let header = header.ok_or("missing image header")?;
+    if header.color_type == 3 && !has_palette {
+        return Err("missing palette");
+    }
+
+    let passes = header.passes();
+    let expected_len: usize = passes.iter().map(|(rows, row_len)| rows * row_len).sum();
+    let mut decoded: Vec<u8> = Vec::with_capacity(expected_len);
+    // Read at most one byte more than expected to detect excess data without inflating all of
+    // it.
+    ZlibDecoder::new(image_data.as_slice())
+        .take(expected_len as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|_| "corrupt image data")?;
+    if decoded.len() != expected_len {
+        return Err("image data doesn't match the header");
+    }
+
+    // Each scanline starts with a filter type that's at most 4.
+    let mut offset = 0;
+    for (rows, row_len) in passes {
+        for _ in 0..rows {
+            if decoded[offset] > 4 {
+                return Err("invalid filter type");
+            }
+            offset += row_len;
+        }
+    }
+    Ok(())
+}
+
 type Replacement<'a> = (&'a str, &'a str);
 
 /// Load asset replacing the first occurrence each replacement.
@@ -90,4 +338,128 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn default_profile_pic_is_deterministic() -> Result<()> {
+        let name = default_profile_pic_name("profile-id");
+
+        assert_eq!(name, default_profile_pic_name("profile-id"));
+        assert!(list_profile_pics().contains(&name));
+
+        Ok(())
+    }
+
+    #[test]
+    fn registers_profile_pic() -> Result<()> {
+        let registry = ProfilePicRegistry::default();
+        let image = load_profile_pic("seal-1")?;
+        let name = "registers-profile-pic-test".to_string();
+        registry.register(HashMap::from([(name.clone(), image.clone())]))?;
+
+        assert_eq!(registry.load(&name)?, image);
+        assert_eq!(registry.load("seal-2")?, load_profile_pic("seal-2")?);
+        assert!(registry.list_available()?.contains(&name));
+        assert!(!list_profile_pics().contains(&name));
+        assert!(load_profile_pic(&name).is_err());
+        assert!(ProfilePicRegistry::default().load(&name).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_profile_pics() -> Result<()> {
+        let registry = ProfilePicRegistry::default();
+        let image = load_profile_pic("seal-1")?;
+        let register = |name: &str, image: Vec<u8>| {
+            registry.register(HashMap::from([(name.to_string(), image)]))
+        };
+
+        assert!(matches!(
+            register("seal-1", image.clone()),
+            Err(Error::User { .. })
+        ));
+        assert!(matches!(
+            register("../foo", image.clone()),
+            Err(Error::User { .. })
+        ));
+        assert!(matches!(
+            register("not-png", vec![0u8; 100]),
+            Err(Error::User { .. })
+        ));
+
+        let mut too_large = image.clone();
+        too_large.resize(MAX_PROFILE_PIC_SIZE_BYTES + 1, 0);
+        assert!(register("too-large", too_large).is_err());
+
+        let mut truncated = image.clone();
+        truncated.truncate(image.len() / 2);
+        assert!(register("truncated", truncated).is_err());
+
+        let mut corrupt = image;
+        let last = corrupt.len() - 20;
+        corrupt[last] ^= 0xff;
+        assert!(register("corrupt", corrupt).is_err());
+
+        assert_eq!(registry.list_available()?, list_profile_pics());
+
+        Ok(())
+    }
+
+    /// Encode raw scanlines of an 8-bit grayscale image as a PNG.
+    fn encode_png(width: u32, height: u32, scanlines: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+
+        use flate2::{write::ZlibEncoder, Compression};
+
+        fn push_chunk(png: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let mut crc = Crc::new();
+            crc.update(chunk_type);
+            crc.update(data);
+            png.extend_from_slice(chunk_type);
+            png.extend_from_slice(data);
+            png.extend_from_slice(&crc.sum().to_be_bytes());
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // Bit depth, color type, compression, filter and interlace method.
+        header.extend_from_slice(&[8, 0, 0, 0, 0]);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(scanlines).unwrap();
+        let image_data = encoder.finish().unwrap();
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        push_chunk(&mut png, b"IHDR", &header);
+        push_chunk(&mut png, b"IDAT", &image_data);
+        push_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn decodes_profile_pic() -> Result<()> {
+        for name in list_profile_pics() {
+            validate_profile_pic(&load_profile_pic(&name)?)?;
+        }
+
+        // Two rows of two pixels, each starting with the filter type.
+        let valid = encode_png(2, 2, &[0, 1, 2, 1, 3, 4]);
+        validate_profile_pic(&valid)?;
+
+        let mut trailing = valid;
+        trailing.push(0);
+        validate_profile_pic(&trailing)?;
+
+        let short = encode_png(2, 2, &[0, 1, 2, 1, 3]);
+        assert!(validate_profile_pic(&short).is_err());
+
+        let long = encode_png(2, 2, &[0, 1, 2, 1, 3, 4, 5]);
+        assert!(validate_profile_pic(&long).is_err());
+
+        let invalid_filter = encode_png(2, 2, &[0, 1, 2, 5, 3, 4]);
+        assert!(validate_profile_pic(&invalid_filter).is_err());
+
+        Ok(())
+    }
 }
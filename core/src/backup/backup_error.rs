@@ -12,6 +12,12 @@ pub enum BackupError {
     #[error("Failed to put backup file into backup storage.")]
     FailedToStoreBackup,
 
+    #[error(
+        "Failed to put backup file into backup storage. It's kept on the device to be \
+        uploaded later."
+    )]
+    BackupSpooled,
+
     #[error("Failed to fetch the backup file from backup storage.")]
     FailedToFetchBackup,
 
@@ -24,6 +30,17 @@ pub enum BackupError {
     #[error("The KDF secret is not available from the keychain.")]
     KDFSecretNotAvailable,
 
+    #[error(
+        "The backup from {backup_timestamp} is older than the backup from \
+        {last_restored_timestamp} that was already restored on this device."
+    )]
+    RollbackDetected {
+        /// Unix timestamp
+        backup_timestamp: i64,
+        /// Unix timestamp
+        last_restored_timestamp: i64,
+    },
+
     /// See crate::error;
     #[error("{error}")]
     Error { error: CoreError },
@@ -4,6 +4,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::backup::compression::BackupCompression;
+
 /// The backup scheme version.
 #[derive(
     Copy,
@@ -21,5 +23,24 @@ use serde::{Deserialize, Serialize};
 )]
 #[strum(serialize_all = "lowercase")]
 pub enum BackupScheme {
+    /// Zip archive with the metadata and the encrypted DB. The zip entries are stored
+    /// uncompressed.
     V1,
+    /// Same as V1, but the DB may be compressed before encryption which is recorded in the
+    /// `compression` field of the metadata. Apps that only know V1 fail to parse the metadata
+    /// instead of restoring the compressed DB.
+    V2,
+}
+
+impl BackupScheme {
+    /// The scheme of new backups.
+    pub const CURRENT: Self = Self::V2;
+
+    /// Whether a backup with this scheme may use the compression.
+    pub fn supports(&self, compression: BackupCompression) -> bool {
+        match self {
+            Self::V1 => compression.is_none(),
+            Self::V2 => true,
+        }
+    }
 }
@@ -39,7 +39,12 @@ pub trait BackupStorageI: Send + Sync + Debug {
 /// Used for testing.
 #[cfg(test)]
 pub(crate) mod tmp_backup_storage {
-    use std::{fmt::Debug, fs, path::PathBuf};
+    use std::{
+        fmt::Debug,
+        fs,
+        path::PathBuf,
+        sync::atomic::{AtomicBool, Ordering},
+    };
 
     use tempfile::TempDir;
 
@@ -49,6 +54,7 @@ pub(crate) mod tmp_backup_storage {
     pub struct TmpBackupStorage {
         pub tmp_dir: TempDir,
         can_backup: bool,
+        fail_uploads: AtomicBool,
     }
 
     impl TmpBackupStorage {
@@ -59,9 +65,15 @@ pub(crate) mod tmp_backup_storage {
             Ok(Self {
                 tmp_dir,
                 can_backup,
+                fail_uploads: AtomicBool::new(false),
             })
         }
 
+        /// Simulate upload failures, eg. when the network is down.
+        pub fn set_fail_uploads(&self, fail_uploads: bool) {
+            self.fail_uploads.store(fail_uploads, Ordering::SeqCst)
+        }
+
         fn backup_file_path(&self, backup_file_name: &str) -> PathBuf {
             let backup_file_name: PathBuf = backup_file_name.into();
             self.tmp_dir.path().join(backup_file_name)
@@ -116,6 +128,9 @@ pub(crate) mod tmp_backup_storage {
             backup_file_name: String,
             tmp_file_path: String,
         ) -> bool {
+            if self.fail_uploads.load(Ordering::SeqCst) {
+                return false;
+            }
             let to_path = self.backup_file_path(&backup_file_name);
             let tmp_file_path: PathBuf = tmp_file_path.into();
 
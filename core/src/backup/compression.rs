@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Only store the compressed payload if it's at least this much smaller than the original.
+const MIN_COMPRESSION_SAVINGS_PERCENT: usize = 10;
+
+/// The compression applied to the backup payload before encryption.
+/// Compression must happen before encryption as cipher text doesn't compress.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    strum_macros::AsRefStr,
+    strum_macros::Display,
+    strum_macros::EnumString,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum BackupCompression {
+    /// Stored as is. Backups created before compression was supported are uncompressed.
+    #[default]
+    None,
+    /// Raw deflate stream (RFC 1951).
+    Deflate,
+}
+
+impl BackupCompression {
+    pub fn is_none(&self) -> bool {
+        *self == Self::None
+    }
+
+    /// Compress the payload if it reduces the size meaningfully. Returns the compression that
+    /// was applied and the payload to encrypt.
+    pub fn compress_if_smaller(payload: Vec<u8>) -> Result<(Self, Vec<u8>), Error> {
+        let compressed = deflate(&payload)?;
+        let max_size =
+            payload.len() - payload.len() * MIN_COMPRESSION_SAVINGS_PERCENT / 100;
+        if compressed.len() < max_size {
+            Ok((Self::Deflate, compressed))
+        } else {
+            Ok((Self::None, payload))
+        }
+    }
+
+    /// Decompress the decrypted payload. Fails if the decompressed payload would be larger
+    /// than `max_size` bytes.
+    pub fn decompress(&self, payload: Vec<u8>, max_size: u64) -> Result<Vec<u8>, Error> {
+        let result = match self {
+            Self::None => payload,
+            Self::Deflate => inflate(&payload, max_size)?,
+        };
+        if result.len() as u64 > max_size {
+            return Err(too_large_error(max_size));
+        }
+        Ok(result)
+    }
+}
+
+fn deflate(payload: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(payload)
+        .and_then(|_| encoder.finish())
+        .map_err(|err| Error::Fatal {
+            error: format!("Failed to compress backup with error: '{err}'"),
+        })
+}
+
+/// Stops after `max_size + 1` bytes, so the caller can detect payloads that are too large
+/// without decompressing them fully.
+fn inflate(payload: &[u8], max_size: u64) -> Result<Vec<u8>, Error> {
+    let decoder = DeflateDecoder::new(payload);
+    let mut result = Vec::new();
+    decoder
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut result)
+        .map_err(|err| Error::Fatal {
+            error: format!("Failed to decompress backup with error: '{err}'"),
+        })?;
+    Ok(result)
+}
+
+fn too_large_error(max_size: u64) -> Error {
+    Error::User {
+        explanation: format!(
+            "The backup is too large to restore. The maximum size is {max_size} bytes."
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rand::{thread_rng, RngCore};
+
+    use super::*;
+
+    const MAX_SIZE: u64 = 1024 * 1024;
+
+    #[test]
+    fn compresses_compressible_payload() -> Result<()> {
+        let payload = b"sealvault".repeat(10_000);
+
+        let (compression, compressed) =
+            BackupCompression::compress_if_smaller(payload.clone())?;
+
+        assert_eq!(compression, BackupCompression::Deflate);
+        assert!(compressed.len() < payload.len());
+        assert_eq!(compression.decompress(compressed, MAX_SIZE)?, payload);
+        Ok(())
+    }
+
+    #[test]
+    fn stores_incompressible_payload() -> Result<()> {
+        let mut payload = vec![0u8; 10_000];
+        thread_rng().fill_bytes(&mut payload);
+
+        let (compression, stored) =
+            BackupCompression::compress_if_smaller(payload.clone())?;
+
+        assert_eq!(compression, BackupCompression::None);
+        assert_eq!(compression.decompress(stored, MAX_SIZE)?, payload);
+        Ok(())
+    }
+
+    #[test]
+    fn empty_payload_is_stored() -> Result<()> {
+        let (compression, stored) = BackupCompression::compress_if_smaller(Vec::new())?;
+        assert_eq!(compression, BackupCompression::None);
+        assert!(stored.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_payload_that_decompresses_above_max_size() -> Result<()> {
+        // Compresses to ~10 KiB
+        let payload = vec![0u8; 10 * MAX_SIZE as usize];
+        let (compression, compressed) = BackupCompression::compress_if_smaller(payload)?;
+        assert_eq!(compression, BackupCompression::Deflate);
+        assert!((compressed.len() as u64) < MAX_SIZE);
+
+        let res = compression.decompress(compressed, MAX_SIZE);
+
+        assert!(matches!(res, Err(Error::User { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn max_size_is_inclusive() -> Result<()> {
+        let payload = b"sealvault".repeat(1000);
+        let max_size = payload.len() as u64;
+        let (compression, compressed) =
+            BackupCompression::compress_if_smaller(payload.clone())?;
+
+        assert_eq!(
+            compression.decompress(compressed.clone(), max_size)?,
+            payload
+        );
+        assert!(compression.decompress(compressed, max_size - 1).is_err());
+        assert!(BackupCompression::None
+            .decompress(payload, max_size - 1)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_deflate_stream() {
+        let res = BackupCompression::Deflate.decompress(vec![0xff; 16], MAX_SIZE);
+        assert!(res.is_err());
+    }
+}
@@ -7,25 +7,78 @@ use std::{
     io::{Read, Seek, Write},
     path::Path,
     str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use diesel::connection::SimpleConnection;
 
 use crate::{
     backup::{
-        backup_error::BackupError, backup_scheme::BackupScheme,
-        metadata::MetadataFromFileName, restore::verify_backup, BackupMetadata,
-        BackupStorageI, BackupVersion,
+        backup_error::BackupError,
+        backup_scheme::BackupScheme,
+        compression::BackupCompression,
+        metadata::{is_backup_uploaded, last_backup_file_name, MetadataFromFileName},
+        restore::verify_backup,
+        BackupMetadata, BackupSpool, BackupStorageI, BackupVersion,
     },
-    db::models as m,
+    db::{db_size_bytes, export_database, models as m, DbEncryptionKey},
+    device::DeviceIdentifier,
     encryption::{DataEncryptionKey, EncryptionOutput},
     resources::CoreResourcesI,
     utils::{path_to_string, tmp_file},
     Error,
 };
 
+/// The number of DB backups of a core being created.
+#[derive(Debug, Default)]
+pub struct BackupsInProgress {
+    count: AtomicUsize,
+}
+
+/// Whether a DB backup is being created, eg. to avoid vacuuming the DB meanwhile.
+pub fn is_backup_in_progress(resources: &dyn CoreResourcesI) -> bool {
+    resources.backups_in_progress().count.load(Ordering::SeqCst) > 0
+}
+
+/// Marks a backup in progress until dropped.
+struct BackupInProgress<'a> {
+    backups_in_progress: &'a BackupsInProgress,
+}
+
+impl<'a> BackupInProgress<'a> {
+    fn start(resources: &'a dyn CoreResourcesI) -> Self {
+        let backups_in_progress = resources.backups_in_progress();
+        backups_in_progress.count.fetch_add(1, Ordering::SeqCst);
+        Self {
+            backups_in_progress,
+        }
+    }
+}
+
+impl<'a> Drop for BackupInProgress<'a> {
+    fn drop(&mut self) {
+        self.backups_in_progress
+            .count
+            .fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Approximate size of the encryption, metadata and zip container overhead in a backup file.
+const BACKUP_OVERHEAD_BYTES: u64 = 1024;
+
+/// Estimate the size of a backup file in bytes without creating a backup.
+/// The backup contains the encrypted DB file, so the estimate is the size of the DB plus the
+/// overhead of the backup file format. It's an upper bound, because the DB is only compressed
+/// if that makes it smaller and the compressed size isn't known without compressing it.
+pub fn estimate_backup_size(resources: &dyn CoreResourcesI) -> Result<u64, Error> {
+    let mut conn = resources.connection_pool().connection()?;
+    let db_size = db_size_bytes(&mut conn)?;
+    Ok(db_size + BACKUP_OVERHEAD_BYTES)
+}
+
 /// Create backup to the desired directory if needed. The directory is assumed to exist.
-/// Returns the backup metadata if a backup was created.
+/// Returns the backup metadata if a backup was created. Returns `BackupError::BackupSpooled`
+/// if the backup was created, but it failed to upload.
 /// A backup is needed if the pending backup version matches the completed backup version in the
 /// database.
 /// The backup is a zip file that contains an encrypted database backup and the metadata. Returns
@@ -38,21 +91,79 @@ pub fn create_backup(
         return Err(BackupError::BackupDisabled);
     }
     let metadata = db_backup(resources)?;
-    remove_outdated_backups(resources.backup_storage(), &metadata)?;
+    resources.metrics().record_backup_created();
+    // Keep the outdated backups in storage until the spooled backup is uploaded.
+    if resources
+        .backup_spool()
+        .is_spooled(&metadata.backup_file_name())
+    {
+        return Err(BackupError::BackupSpooled);
+    }
+    remove_outdated_backups(
+        resources.backup_storage(),
+        &metadata.device_id,
+        metadata.backup_version,
+    )?;
     Ok(metadata)
 }
 
+/// Upload backups that were spooled locally, because uploading them failed when they were
+/// created. Removes the outdated backups from storage after a successful upload.
+pub fn flush_pending_backups(resources: &dyn CoreResourcesI) -> Result<(), BackupError> {
+    if !resources.backup_storage().can_backup() {
+        return Err(BackupError::BackupDisabled);
+    }
+    let uploaded = resources.backup_spool().flush(resources.backup_storage())?;
+    for backup_file_name in uploaded {
+        let meta_from_file_name = MetadataFromFileName::from_str(&backup_file_name)?;
+        remove_outdated_backups(
+            resources.backup_storage(),
+            &meta_from_file_name.device_id,
+            meta_from_file_name.backup_version,
+        )?;
+    }
+    if resources.backup_spool().list()?.is_empty() {
+        Ok(())
+    } else {
+        Err(BackupError::BackupSpooled)
+    }
+}
+
+/// Upload the last backup again if it isn't uploaded, eg. because uploading it failed. The
+/// spooled backup file is uploaded if there is one, otherwise a new backup is created from the
+/// current state. No-op if the last backup is already uploaded.
+pub fn resend_backup(resources: &dyn CoreResourcesI) -> Result<(), BackupError> {
+    if !resources.backup_storage().can_backup() {
+        return Err(BackupError::BackupDisabled);
+    }
+    if let Some((_, backup_file_name)) = last_backup_file_name(resources)? {
+        if is_backup_uploaded(resources, &backup_file_name) {
+            return Ok(());
+        }
+    }
+    if !resources.backup_spool().list()?.is_empty() {
+        return flush_pending_backups(resources);
+    }
+
+    let _ = create_backup(resources)?;
+    Ok(())
+}
+
 pub(in crate::backup) fn db_backup(
     resources: &dyn CoreResourcesI,
 ) -> Result<BackupMetadata, Error> {
+    let _in_progress = BackupInProgress::start(resources);
     let connection_pool = resources.connection_pool();
 
+    // Increment here to make sure it's part of backup. If there is an error later, it'll cause
+    // gaps in the backup versions, but that's ok.
+    connection_pool.exclusive_transaction(|mut tx_conn| {
+        m::LocalSettings::increment_backup_version(&mut tx_conn)
+    })?;
+
     // Flush WAL to the DB file. Can't be inside exclusive transaction, because it acquires its own
     // lock.
     let mut conn = connection_pool.connection()?;
-    // Increment here to make sure it's part of backup. If there is an error later, it'll cause
-    // gaps in the backup versions, but that's ok.
-    m::LocalSettings::increment_backup_version(&mut conn)?;
     conn.batch_execute("PRAGMA wal_checkpoint(FULL);")?;
 
     // Exclusive transaction here for copy
@@ -74,21 +185,44 @@ pub(in crate::backup) fn db_backup(
         let db_backup_dek = DataEncryptionKey::db_backup_dek(resources.keychain())?;
 
         // Copies DB file
-        let backup_contents =
-            create_verified_backup(connection_pool.db_path(), backup_version)?;
+        let backup_contents = create_verified_backup(
+            connection_pool.db_path(),
+            connection_pool.encryption_key(),
+            backup_version,
+        )?;
+        // Restore rejects backups above the limit, so don't create one that can't be restored.
+        let max_backup_size = resources.max_backup_size_bytes();
+        if backup_contents.len() as u64 + BACKUP_OVERHEAD_BYTES > max_backup_size {
+            return Err(Error::User {
+                explanation: format!(
+                    "The wallet is too large to back up. The maximum backup size is \
+                    {max_backup_size} bytes."
+                ),
+            });
+        }
+        let (compression, backup_contents) =
+            BackupCompression::compress_if_smaller(backup_contents)?;
 
+        // Fail loudly instead of creating a backup that other app versions can't decrypt.
+        BackupMetadata::verify_canonical_json_stable()?;
         let metadata = BackupMetadata::builder()
-            .backup_scheme(BackupScheme::V1)
+            .backup_scheme(BackupScheme::CURRENT)
             .backup_version(backup_version)
             .device_id(resources.device_id().clone())
             .device_name(resources.device_name().clone())
             .kdf_nonce(&kdf_nonce)
+            .compression(compression)
             .build();
 
         let encryption_output =
             db_backup_dek.encrypt_backup(&backup_contents, &metadata)?;
 
-        store_backup_zip(resources.backup_storage(), &metadata, &encryption_output)?;
+        store_backup_zip(
+            resources.backup_storage(),
+            resources.backup_spool(),
+            &metadata,
+            &encryption_output,
+        )?;
 
         m::LocalSettings::update_backup_timestamp(tx_conn.as_mut())?;
 
@@ -97,21 +231,31 @@ pub(in crate::backup) fn db_backup(
 }
 
 /// Create a verified backup of the DB and return it as bytes.
+/// An encrypted DB is exported to plaintext, because the backup is encrypted with the backup
+/// key and the DB encryption key is not available on the device that restores it.
 fn create_verified_backup(
     db_path: &Path,
+    encryption_key: Option<&DbEncryptionKey>,
     backup_version: BackupVersion,
 ) -> Result<Vec<u8>, Error> {
-    let mut db_file = File::open(db_path).map_err(|err| Error::Retriable {
-        error: format!("Failed to open DB file: {err}"),
-    })?;
-
     let mut backup_file = tmp_file()?;
 
-    // Sqlite C backup api would be preferable to copying, but it's not supported by Diesel.
-    // Copy while holding lock to make sure DB doesn't change.
-    std::io::copy(&mut db_file, &mut backup_file).map_err(|err| Error::Retriable {
-        error: format!("Failed to copy DB file to backup file: {err}"),
-    })?;
+    if let Some(encryption_key) = encryption_key {
+        // Export while holding lock to make sure DB doesn't change. Readers aren't blocked by
+        // the exclusive transaction in WAL mode.
+        export_database(db_path, Some(encryption_key), backup_file.path(), None)?;
+    } else {
+        let mut db_file = File::open(db_path).map_err(|err| Error::Retriable {
+            error: format!("Failed to open DB file: {err}"),
+        })?;
+        // Sqlite C backup api would be preferable to copying, but it's not supported by Diesel.
+        // Copy while holding lock to make sure DB doesn't change.
+        std::io::copy(&mut db_file, &mut backup_file).map_err(|err| {
+            Error::Retriable {
+                error: format!("Failed to copy DB file to backup file: {err}"),
+            }
+        })?;
+    }
 
     verify_backup(backup_file.path(), backup_version)?;
 
@@ -128,8 +272,12 @@ fn create_verified_backup(
     Ok(backup_contents)
 }
 
-fn store_backup_zip(
+/// Upload the backup zip to backup storage. If the upload fails, the zip is spooled locally to
+/// be uploaded by `flush_pending_backups`. Spooled backups are outdated after a successful
+/// upload, so they're removed then.
+pub(in crate::backup) fn store_backup_zip(
     backup_storage: &dyn BackupStorageI,
+    backup_spool: &BackupSpool,
     metadata: &BackupMetadata,
     encryption_output: &EncryptionOutput,
 ) -> Result<(), BackupError> {
@@ -144,20 +292,30 @@ fn store_backup_zip(
     let is_ok =
         backup_storage.copy_to_storage(metadata.backup_file_name(), tmp_file_path);
     if is_ok {
-        Ok(())
-    } else {
-        Err(BackupError::FailedToStoreBackup)
+        if let Err(err) = backup_spool.clear() {
+            log::error!("Failed to clear outdated spooled backups: {err}");
+        }
+        return Ok(());
     }
+
+    log::warn!("Failed to upload backup. Spooling it for later upload.");
+    backup_spool
+        .spool(&metadata.backup_file_name(), tmp_file.path())
+        .map_err(|err| {
+            log::error!("Failed to spool backup: {err}");
+            BackupError::FailedToStoreBackup
+        })
 }
 
 /// Removes the outdated backups that were created on this device.
 fn remove_outdated_backups(
     backup_storage: &dyn BackupStorageI,
-    current_metadata: &BackupMetadata,
+    device_id: &DeviceIdentifier,
+    backup_version: BackupVersion,
 ) -> Result<(), BackupError> {
     let backup_file_names = backup_storage.list_backup_file_names();
     for file_name in backup_file_names {
-        if should_delete(&file_name, current_metadata)? {
+        if should_delete(&file_name, device_id, backup_version)? {
             let is_ok = backup_storage.delete_backup(file_name);
             if !is_ok {
                 log::error!("Failed to delete backup file.")
@@ -169,7 +327,8 @@ fn remove_outdated_backups(
 
 fn should_delete(
     backup_file_name: &str,
-    current_metadata: &BackupMetadata,
+    device_id: &DeviceIdentifier,
+    backup_version: BackupVersion,
 ) -> Result<bool, Error> {
     let meta_from_file_name = match MetadataFromFileName::from_str(backup_file_name) {
         Ok(meta) => meta,
@@ -181,10 +340,9 @@ fn should_delete(
     };
 
     // There may be other devices saving backups in the same directory.
-    let same_device = meta_from_file_name.device_id == current_metadata.device_id;
+    let same_device = &meta_from_file_name.device_id == device_id;
     // Important to only delete earlier versions as a newer backup may have been created inbetween.
-    let earlier_version =
-        meta_from_file_name.backup_version < current_metadata.backup_version;
+    let earlier_version = meta_from_file_name.backup_version < backup_version;
     Ok(same_device && earlier_version)
 }
 
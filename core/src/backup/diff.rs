@@ -0,0 +1,147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    backup::{
+        restore::{decrypt_backup, verify_backup_version, DecryptedBackup},
+        BackupError, BackupStorageI,
+    },
+    config,
+    db::{
+        exclusive_transaction, models as m, open_in_memory,
+        schema_migrations::run_migrations,
+    },
+    encryption::Keychain,
+    Error,
+};
+
+/// The entities of a kind that differ between two backups by deterministic id.
+/// Exposed through FFI to UI.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BackupEntityDiff {
+    /// In the second backup, but not in the first one.
+    pub added: Vec<String>,
+    /// In the first backup, but not in the second one.
+    pub removed: Vec<String>,
+    /// In both backups with different data.
+    pub changed: Vec<String>,
+}
+
+/// The differences between two backups.
+/// Exposed through FFI to UI.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BackupDiff {
+    pub profiles: BackupEntityDiff,
+    pub dapps: BackupEntityDiff,
+    pub keys: BackupEntityDiff,
+    /// The backup version of the second backup minus the first one. Backup versions are per
+    /// device, so this is only meaningful for backups from the same device.
+    pub backup_version_delta: i64,
+    /// The timestamp of the second backup minus the first one in seconds.
+    pub timestamp_delta_seconds: i64,
+}
+
+/// Compare two backups in backup storage that were created with the same password.
+/// The backups are decrypted in memory, so no decrypted data is written to disk. The DB of the
+/// app is not touched.
+pub fn diff_backups(
+    backup_storage: &dyn BackupStorageI,
+    keychain: &Keychain,
+    max_backup_size: u64,
+    app_version: &str,
+    first_backup_file_name: String,
+    second_backup_file_name: String,
+    password: &str,
+) -> Result<BackupDiff, BackupError> {
+    let first = decrypt_backup(
+        backup_storage,
+        first_backup_file_name,
+        keychain,
+        password,
+        max_backup_size,
+        app_version,
+    )?;
+    let second = decrypt_backup(
+        backup_storage,
+        second_backup_file_name,
+        keychain,
+        password,
+        max_backup_size,
+        app_version,
+    )?;
+
+    let backup_version_delta = i64::from(second.metadata.backup_version)
+        - i64::from(first.metadata.backup_version);
+    let timestamp_delta_seconds = second.metadata.timestamp - first.metadata.timestamp;
+
+    let first = BackupSnapshot::load(&first)?;
+    let second = BackupSnapshot::load(&second)?;
+
+    Ok(BackupDiff {
+        profiles: diff_entities(&first.profiles, &second.profiles),
+        dapps: diff_entities(&first.dapps, &second.dapps),
+        keys: diff_entities(&first.keys, &second.keys),
+        backup_version_delta,
+        timestamp_delta_seconds,
+    })
+}
+
+/// The entities of a backup by deterministic id.
+struct BackupSnapshot {
+    profiles: BTreeMap<String, m::Profile>,
+    dapps: BTreeMap<String, m::Dapp>,
+    keys: BTreeMap<String, m::AsymmetricKey>,
+}
+
+impl BackupSnapshot {
+    fn load(backup: &DecryptedBackup) -> Result<Self, Error> {
+        let mut conn = open_in_memory(&backup.db_bytes)?;
+        verify_backup_version(&mut conn, backup.metadata.backup_version)?;
+        // Backups from older app versions have older schemas. The migrations only change the
+        // in-memory copy.
+        exclusive_transaction(&mut conn, |mut tx_conn| run_migrations(&mut tx_conn))?;
+
+        let profiles = m::Profile::list_all(&mut conn)?
+            .into_iter()
+            .map(|profile| (profile.deterministic_id.to_string(), profile))
+            .collect();
+        let dapps = m::Dapp::list_all(&mut conn, 0, config::UNPAGINATED_DAPP_LIST_LIMIT)?
+            .into_iter()
+            .map(|dapp| (dapp.deterministic_id.to_string(), dapp))
+            .collect();
+        let keys = m::AsymmetricKey::list_all(&mut conn)?
+            .into_iter()
+            .map(|key| (key.deterministic_id.to_string(), key))
+            .collect();
+
+        Ok(Self {
+            profiles,
+            dapps,
+            keys,
+        })
+    }
+}
+
+/// The results are sorted by id.
+fn diff_entities<T: PartialEq>(
+    first: &BTreeMap<String, T>,
+    second: &BTreeMap<String, T>,
+) -> BackupEntityDiff {
+    let mut diff = BackupEntityDiff::default();
+    for (id, entity) in second {
+        match first.get(id) {
+            None => diff.added.push(id.clone()),
+            Some(first_entity) if first_entity != entity => diff.changed.push(id.clone()),
+            Some(_) => (),
+        }
+    }
+    diff.removed = first
+        .keys()
+        .filter(|id| !second.contains_key(*id))
+        .cloned()
+        .collect();
+    diff
+}
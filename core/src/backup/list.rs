@@ -0,0 +1,260 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::backup::{metadata::MetadataFromFileName, BackupStorageI};
+
+/// A backup file in the storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupFileInfo {
+    pub backup_file_name: String,
+    /// Unix timestamp from the file name. None if the file name couldn't be parsed.
+    pub timestamp: Option<i64>,
+    /// None if the file name couldn't be parsed.
+    pub backup_version: Option<i64>,
+}
+
+/// Backups created on the same device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceBackups {
+    /// None for the bucket of file names that couldn't be parsed.
+    pub device_id: Option<String>,
+    /// The operating system from the file name of the latest backup.
+    pub operating_system: Option<String>,
+    /// Latest first.
+    pub backups: Vec<BackupFileInfo>,
+}
+
+/// Multiple backups in the storage from the same device with the same backup version.
+/// Backup versions are monotonically increasing within a device, so this means that the
+/// storage had a race or the device id was reused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupConflict {
+    pub device_id: String,
+    pub backup_version: i64,
+    /// Latest first.
+    pub backups: Vec<BackupFileInfo>,
+}
+
+/// List backups in the storage grouped by the device that created them.
+/// Groups are ordered by device id and the unknown bucket is last.
+/// File names that can't be parsed are collected into the unknown bucket instead of failing,
+/// since the storage may contain files from other app versions.
+pub fn list_backups_grouped(backup_storage: &dyn BackupStorageI) -> Vec<DeviceBackups> {
+    let mut by_device: BTreeMap<_, Vec<(MetadataFromFileName, String)>> =
+        Default::default();
+    let mut unknown: Vec<BackupFileInfo> = Default::default();
+
+    for backup_file_name in backup_storage.list_backup_file_names() {
+        match MetadataFromFileName::from_str(&backup_file_name) {
+            Ok(meta) => by_device
+                .entry(meta.device_id.clone())
+                .or_default()
+                .push((meta, backup_file_name)),
+            Err(err) => {
+                log::warn!("Error parsing backup file name: '{err}'");
+                unknown.push(BackupFileInfo {
+                    backup_file_name,
+                    timestamp: None,
+                    backup_version: None,
+                })
+            }
+        }
+    }
+
+    let mut results: Vec<DeviceBackups> = by_device
+        .into_iter()
+        .map(|(device_id, mut metas)| {
+            metas.sort_by(|(a, _), (b, _)| {
+                b.backup_version
+                    .cmp(&a.backup_version)
+                    .then(b.timestamp.cmp(&a.timestamp))
+            });
+            let operating_system = metas.first().map(|(meta, _)| meta.os.to_string());
+            let backups = metas
+                .into_iter()
+                .map(|(meta, backup_file_name)| BackupFileInfo {
+                    backup_file_name,
+                    timestamp: Some(meta.timestamp),
+                    backup_version: Some(meta.backup_version.into()),
+                })
+                .collect();
+            DeviceBackups {
+                device_id: Some(device_id.into()),
+                operating_system,
+                backups,
+            }
+        })
+        .collect();
+
+    if !unknown.is_empty() {
+        unknown.sort_by(|a, b| a.backup_file_name.cmp(&b.backup_file_name));
+        results.push(DeviceBackups {
+            device_id: None,
+            operating_system: None,
+            backups: unknown,
+        });
+    }
+
+    results
+}
+
+/// Find backups in the storage that have the same device id and backup version.
+/// Conflicts are ordered by device id and then by backup version, latest first.
+pub fn detect_backup_conflicts(
+    backup_storage: &dyn BackupStorageI,
+) -> Vec<BackupConflict> {
+    let mut conflicts: Vec<BackupConflict> = Default::default();
+    for device_backups in list_backups_grouped(backup_storage) {
+        let device_id = match device_backups.device_id {
+            Some(device_id) => device_id,
+            None => continue,
+        };
+        // Backups of a device are sorted latest first, which is kept within a version.
+        let mut by_version: BTreeMap<i64, Vec<BackupFileInfo>> = Default::default();
+        for backup in device_backups.backups {
+            if let Some(backup_version) = backup.backup_version {
+                by_version.entry(backup_version).or_default().push(backup);
+            }
+        }
+        conflicts.extend(
+            by_version
+                .into_iter()
+                .rev()
+                .filter(|(_, backups)| backups.len() > 1)
+                .map(|(backup_version, backups)| BackupConflict {
+                    device_id: device_id.clone(),
+                    backup_version,
+                    backups,
+                }),
+        );
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::{
+        backup::{
+            metadata::get_backup_file_name, BackupScheme, BackupVersion, TmpBackupStorage,
+        },
+        device::{DeviceIdentifier, OperatingSystem},
+        utils::path_to_string,
+    };
+
+    fn add_file(storage: &TmpBackupStorage, file_name: String) -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let tmp_path = path_to_string(tmp_file.path())?;
+        assert!(storage.copy_to_storage(file_name, tmp_path));
+        Ok(())
+    }
+
+    fn add_backup(
+        storage: &TmpBackupStorage,
+        device_id: &DeviceIdentifier,
+        timestamp: i64,
+        version: i64,
+    ) -> Result<String> {
+        let os: OperatingSystem = Default::default();
+        let version: BackupVersion = version.try_into()?;
+        let file_name =
+            get_backup_file_name(BackupScheme::V1, &os, timestamp, device_id, version);
+        add_file(storage, file_name.clone())?;
+        Ok(file_name)
+    }
+
+    #[test]
+    fn groups_backups_by_device() -> Result<()> {
+        let storage = TmpBackupStorage::default();
+        let device_one: DeviceIdentifier =
+            "1b4f0e98-5197-4b64-bd1d-0c1d1e6e0a01".parse()?;
+        let device_two: DeviceIdentifier =
+            "9a3c2f10-6a7d-4f52-8c5e-2b9d7e4f1a02".parse()?;
+
+        let one_first = add_backup(&storage, &device_one, 100, 1)?;
+        let one_second = add_backup(&storage, &device_one, 200, 2)?;
+        let two_first = add_backup(&storage, &device_two, 150, 1)?;
+        let junk = "notes.txt".to_string();
+        add_file(&storage, junk.clone())?;
+
+        let groups = list_backups_grouped(&storage);
+
+        assert_eq!(groups.len(), 3);
+
+        assert_eq!(
+            groups[0].device_id.as_deref(),
+            Some(device_one.to_string().as_str())
+        );
+        let names: Vec<&str> = groups[0]
+            .backups
+            .iter()
+            .map(|b| b.backup_file_name.as_str())
+            .collect();
+        assert_eq!(names, vec![one_second.as_str(), one_first.as_str()]);
+        assert_eq!(groups[0].backups[0].backup_version, Some(2));
+        assert_eq!(groups[0].backups[0].timestamp, Some(200));
+
+        assert_eq!(
+            groups[1].device_id.as_deref(),
+            Some(device_two.to_string().as_str())
+        );
+        assert_eq!(groups[1].backups.len(), 1);
+        assert_eq!(groups[1].backups[0].backup_file_name, two_first);
+
+        assert_eq!(groups[2].device_id, None);
+        assert_eq!(
+            groups[2].backups,
+            vec![BackupFileInfo {
+                backup_file_name: junk,
+                timestamp: None,
+                backup_version: None,
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_same_version_backups() -> Result<()> {
+        let storage = TmpBackupStorage::default();
+        let device_one: DeviceIdentifier =
+            "1b4f0e98-5197-4b64-bd1d-0c1d1e6e0a01".parse()?;
+        let device_two: DeviceIdentifier =
+            "9a3c2f10-6a7d-4f52-8c5e-2b9d7e4f1a02".parse()?;
+
+        add_backup(&storage, &device_one, 100, 1)?;
+        let first = add_backup(&storage, &device_one, 200, 2)?;
+        let second = add_backup(&storage, &device_one, 300, 2)?;
+        // Same version on another device is not a conflict.
+        add_backup(&storage, &device_two, 150, 1)?;
+
+        let conflicts = detect_backup_conflicts(&storage);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].device_id, device_one.to_string());
+        assert_eq!(conflicts[0].backup_version, 2);
+        let backups: Vec<(&str, Option<i64>)> = conflicts[0]
+            .backups
+            .iter()
+            .map(|b| (b.backup_file_name.as_str(), b.timestamp))
+            .collect();
+        assert_eq!(
+            backups,
+            vec![(second.as_str(), Some(300)), (first.as_str(), Some(200))]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_storage_has_no_groups() {
+        let storage = TmpBackupStorage::default();
+        assert!(list_backups_grouped(&storage).is_empty());
+    }
+}
@@ -9,10 +9,12 @@ use lazy_static::lazy_static;
 use olpc_cjson::CanonicalFormatter;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use typed_builder::TypedBuilder;
 
 use crate::{
-    backup::backup_scheme::BackupScheme,
+    backup::{backup_scheme::BackupScheme, compression::BackupCompression},
+    config,
     db::models as m,
     device::{DeviceIdentifier, DeviceName, OperatingSystem},
     resources::CoreResourcesI,
@@ -20,6 +22,15 @@ use crate::{
     Error,
 };
 
+/// The version of the metadata format. Must be incremented on any change to the canonical JSON
+/// of the metadata, as it's the associated data of the backup encryption.
+pub(in crate::backup) const METADATA_FORMAT_VERSION: u8 = 2;
+
+/// The canonical JSON of `canonical_json_fixture` with metadata format version 1. Decrypting
+/// existing backups fails if this changes, so it must never be updated without incrementing
+/// `METADATA_FORMAT_VERSION` and keeping support for the old format.
+const CANONICAL_JSON_GOLDEN: &[u8] = br#"{"backup_scheme":"V1","backup_version":1,"compression":"deflate","device_id":"475dda83-9447-4626-9cf1-ecc4ddbe5bbd","device_name":"foo","kdf_nonce":"bm9uY2U=","operating_system":"ios","timestamp":1679000000}"#;
+
 lazy_static! {
     static ref BACKUP_FILE_NAME_REGEX: Regex =
         Regex::new(r"^sealvault_backup_(?P<scheme>[A-Za-z0-9-]+)_(?P<os>[A-Za-z0-9-]+)_(?P<timestamp>\d+)_(?P<device_id>[A-Za-z0-9-]+)_(?P<version>\d+)\.zip$").expect("static is ok");
@@ -61,6 +72,17 @@ impl TryFrom<i64> for BackupVersion {
     }
 }
 
+impl BackupVersion {
+    /// The version after this one. Fails instead of wrapping on overflow to keep versions
+    /// monotonically increasing.
+    pub fn next(self) -> Result<Self, Error> {
+        let next = self.0.checked_add(1).ok_or_else(|| Error::Fatal {
+            error: "Backup version overflow".into(),
+        })?;
+        next.try_into()
+    }
+}
+
 impl FromStr for BackupVersion {
     type Err = Error;
 
@@ -89,6 +111,33 @@ pub struct BackupMetadata {
     /// Base-64 encoded KDF nonce
     #[builder(setter(into))]
     pub kdf_nonce: String,
+    /// The compression applied to the backup before encryption.
+    /// Omitted if uncompressed to keep the associated data of older backups unchanged.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "BackupCompression::is_none")]
+    pub compression: BackupCompression,
+    /// The version of the metadata format.
+    /// Omitted for the first version to keep the associated data of older backups unchanged.
+    #[builder(default = METADATA_FORMAT_VERSION)]
+    #[serde(
+        default = "initial_metadata_format_version",
+        skip_serializing_if = "is_initial_metadata_format_version"
+    )]
+    pub metadata_version: u8,
+    /// The minimum app version required to restore the backup.
+    /// Missing from backups created before metadata format version 2 which any version can
+    /// restore.
+    #[builder(default = Some(config::BACKUP_MIN_APP_VERSION.into()))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_app_version: Option<String>,
+}
+
+fn initial_metadata_format_version() -> u8 {
+    1
+}
+
+fn is_initial_metadata_format_version(version: &u8) -> bool {
+    *version == initial_metadata_format_version()
 }
 
 impl BackupMetadata {
@@ -102,6 +151,36 @@ impl BackupMetadata {
         )
     }
 
+    /// Refuse to restore the backup if the app version is older than the minimum app version
+    /// of the backup, so that old apps don't mangle backups of newer ones.
+    pub(in crate::backup) fn verify_min_app_version(
+        &self,
+        app_version: &str,
+    ) -> Result<(), Error> {
+        let min_app_version = match self.min_app_version.as_deref() {
+            Some(min_app_version) => min_app_version,
+            None => return Ok(()),
+        };
+        // The minimum app version comes from the backup, so it's untrusted input.
+        let min_version =
+            parse_app_version(min_app_version).ok_or_else(|| Error::User {
+                explanation: format!(
+                    "The backup requires an unknown app version '{min_app_version}'. Please update the app to restore it."
+                ),
+            })?;
+        let version = parse_app_version(app_version).ok_or_else(|| Error::Fatal {
+            error: format!("Invalid app version: '{app_version}'"),
+        })?;
+        if version < min_version {
+            return Err(Error::User {
+                explanation: format!(
+                    "The backup requires app version {min_app_version} or newer. Please update the app to restore it."
+                ),
+            });
+        }
+        Ok(())
+    }
+
     /// Use this for a canonical serialization of the backup metadata to make sure that the
     /// associated data in the AEAD matches.
     pub fn canonical_json(&self) -> Result<Vec<u8>, Error> {
@@ -113,6 +192,51 @@ impl BackupMetadata {
         })?;
         Ok(buf)
     }
+
+    /// Verify that the canonical JSON of a fixed metadata matches the golden bytes to detect if
+    /// a dependency upgrade changed the serialization. Backups created after such a change
+    /// couldn't be decrypted by other app versions.
+    pub(in crate::backup) fn verify_canonical_json_stable() -> Result<(), Error> {
+        let canonical_json = Self::canonical_json_fixture()?.canonical_json()?;
+        if canonical_json != CANONICAL_JSON_GOLDEN {
+            return Err(Error::Fatal {
+                error: format!(
+                    "Backup metadata canonical JSON changed: '{}'",
+                    String::from_utf8_lossy(&canonical_json)
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn canonical_json_fixture() -> Result<Self, Error> {
+        let metadata = Self::builder()
+            .backup_scheme(BackupScheme::V1)
+            .backup_version(1.try_into()?)
+            .timestamp(1679000000)
+            .device_id("475dda83-9447-4626-9cf1-ecc4ddbe5bbd".parse()?)
+            .device_name("foo".parse()?)
+            .operating_system("ios".parse()?)
+            .kdf_nonce("bm9uY2U=")
+            .compression(BackupCompression::Deflate)
+            .metadata_version(1)
+            .min_app_version(None)
+            .build();
+        Ok(metadata)
+    }
+
+    /// Diagnostics only. Human-readable dump of the associated data that decryption expects
+    /// for this metadata to compare it against the metadata file in the backup.
+    /// The output contains no secrets, but it shouldn't be shown to users.
+    pub fn associated_data_debug(&self) -> Result<String, Error> {
+        let associated_data = self.canonical_json()?;
+        let json = String::from_utf8_lossy(&associated_data);
+        Ok(format!(
+            "Backup associated data ({} bytes)\njson: {json}\nhex: {}",
+            associated_data.len(),
+            hex::encode(&associated_data)
+        ))
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -148,6 +272,20 @@ impl FromStr for MetadataFromFileName {
     }
 }
 
+/// Parse a `major.minor.patch` version for comparison. Pre-release and build suffixes are
+/// ignored. Returns None if the version is invalid.
+fn parse_app_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core_version = version.split(['-', '+']).next().unwrap_or_default();
+    let parts = core_version
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match parts.as_slice() {
+        [major, minor, patch] => Some((*major, *minor, *patch)),
+        _ => None,
+    }
+}
+
 pub(in crate::backup) fn get_backup_file_name(
     backup_scheme: BackupScheme,
     os: &OperatingSystem,
@@ -181,6 +319,33 @@ where
 pub fn last_uploaded_backup(
     resources: &dyn CoreResourcesI,
 ) -> Result<Option<i64>, Error> {
+    let last_backup = last_backup_file_name(resources)?;
+    match last_backup {
+        Some((timestamp, backup_file_name))
+            if is_backup_uploaded(resources, &backup_file_name) =>
+        {
+            Ok(Some(timestamp))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Whether the backup file is in storage and it's not waiting to be uploaded.
+pub(in crate::backup) fn is_backup_uploaded(
+    resources: &dyn CoreResourcesI,
+    backup_file_name: &str,
+) -> bool {
+    // A spooled backup is waiting to be uploaded even if storage has a partial copy.
+    !resources.backup_spool().is_spooled(backup_file_name)
+        && resources
+            .backup_storage()
+            .is_uploaded(backup_file_name.to_string())
+}
+
+/// The unix timestamp and file name of the last backup created on this device if any.
+pub(in crate::backup) fn last_backup_file_name(
+    resources: &dyn CoreResourcesI,
+) -> Result<Option<(i64, String)>, Error> {
     let (backup_version, datestamp) =
         resources
             .connection_pool()
@@ -197,21 +362,27 @@ pub fn last_uploaded_backup(
             let datetime = parse_rfc3339_timestamp(&datestamp)?;
             let timestamp = datetime.timestamp();
             let os: OperatingSystem = Default::default();
-            let backup_file_name = get_backup_file_name(
-                BackupScheme::V1,
-                &os,
-                timestamp,
-                resources.device_id(),
-                backup_version,
-            );
-
-            let is_uploaded = resources.backup_storage().is_uploaded(backup_file_name);
-
-            if is_uploaded {
-                Ok(Some(timestamp))
-            } else {
-                Ok(None)
-            }
+            let file_name = |scheme| {
+                get_backup_file_name(
+                    scheme,
+                    &os,
+                    timestamp,
+                    resources.device_id(),
+                    backup_version,
+                )
+            };
+            // The last backup may have been created with an older scheme before an app update.
+            let backup_file_name = BackupScheme::iter()
+                .rev()
+                .map(file_name)
+                .find(|backup_file_name| {
+                    resources.backup_spool().is_spooled(backup_file_name)
+                        || resources
+                            .backup_storage()
+                            .is_uploaded(backup_file_name.clone())
+                })
+                .unwrap_or_else(|| file_name(BackupScheme::CURRENT));
+            Ok(Some((timestamp, backup_file_name)))
         }
     }
 }
@@ -5,10 +5,15 @@
 mod backup_error;
 mod backup_scheme;
 mod backup_storage;
+mod compression;
 mod create;
+mod diff;
+mod list;
 mod metadata;
+mod reminder;
 mod restore;
 mod setup;
+mod spool;
 
 // File names inside the backup zip
 pub(in crate::backup) const ENCRYPTED_BACKUP_FILE_NAME: &str = "backup.sqlite3.encrypted";
@@ -20,45 +25,85 @@ pub use backup_scheme::BackupScheme;
 #[cfg(test)]
 pub use backup_storage::tmp_backup_storage::TmpBackupStorage;
 pub use backup_storage::BackupStorageI;
-pub use create::create_backup;
+pub use compression::BackupCompression;
+pub use create::{
+    create_backup, estimate_backup_size, flush_pending_backups, is_backup_in_progress,
+    resend_backup, BackupsInProgress,
+};
+pub use diff::{diff_backups, BackupDiff, BackupEntityDiff};
+pub use list::{
+    detect_backup_conflicts, list_backups_grouped, BackupConflict, BackupFileInfo,
+    DeviceBackups,
+};
 pub use metadata::{last_uploaded_backup, BackupMetadata, BackupVersion};
-pub use restore::{find_latest_backup, restore_backup, BackupRestoreData};
+pub use reminder::{
+    backup_reminder_status, BackupReminder, BackupReminderSeverity,
+    BackupReminderThresholds,
+};
+pub use restore::{
+    find_latest_backup, restore_backup, BackupContents, BackupRestoreData, RestoreStatus,
+    RestoreStatusTracker,
+};
 pub use setup::{
     disable_backup, display_backup_password, is_backup_enabled, set_up_or_rotate_backup,
 };
+pub use spool::BackupSpool;
 
 #[cfg(test)]
 mod tests {
-    use std::{io::Write, sync::Arc};
+    use std::{fs, io::Write, path::Path, sync::Arc, thread, time::Duration};
 
-    use anyhow::Result;
+    use anyhow::{anyhow, Result};
+    use diesel::connection::SimpleConnection;
+    use diesel_migrations::MigrationHarness;
     use tempfile::NamedTempFile;
 
     use super::*;
     use crate::{
         app_core::tests::{CoreResourcesMock, TmpCoreDir},
+        assets::load_profile_pic,
         backup::{
             backup_scheme::BackupScheme,
-            create::db_backup,
+            create::{db_backup, store_backup_zip},
             metadata::{get_backup_file_name, BackupVersion, MetadataFromFileName},
+            reminder::PendingBackupChanges,
             restore::{
-                backup_metadata_from_zip, find_latest_backup_inner, restore_backup_inner,
+                backup_metadata_from_zip, find_latest_backup_inner,
+                replace_db_and_sk_kek, restore_backup_inner, restore_backup_with_status,
                 RestoreWorkDir,
             },
             setup::rollback_enable_backup,
         },
+        config,
         db::{
-            data_migrations, models as m, schema_migrations::run_migrations,
+            data_migrations, models as m,
+            schema_migrations::{latest_applied_version, run_migrations, MIGRATIONS},
             ConnectionPool,
         },
         device::{DeviceIdentifier, DeviceName, OperatingSystem},
-        encryption::{KdfSecret, Keychain},
+        encryption::{DataEncryptionKey, KdfSecret, KeyEncryptionKey, KeyName, Keychain},
         protocols::eth,
         resources::CoreResourcesI,
         utils::{path_to_string, tmp_file, unix_timestamp},
-        CoreArgs,
+        CoreArgs, CoreError, Error,
     };
 
+    /// The latest schema migration of app versions before backup settings were moved to
+    /// `local_settings_values`.
+    const OLD_SCHEMA_VERSION: &str = "20230216084100";
+
+    /// Revert the schema migrations of the DB at the path that are newer than the version to
+    /// simulate a DB created by an older app version.
+    fn revert_schema_migrations_after(db_path: &Path, version: &str) -> Result<()> {
+        let connection_pool = ConnectionPool::new(&path_to_string(db_path)?)?;
+        let mut conn = connection_pool.connection()?;
+        while latest_applied_version(&mut conn)?.as_deref() > Some(version) {
+            conn.revert_last_migration(MIGRATIONS)
+                .map_err(|err| anyhow!(err))?;
+        }
+        Ok(())
+    }
+
     struct BackupTest {
         pub resources: Arc<CoreResourcesMock>,
     }
@@ -106,6 +151,43 @@ mod tests {
             Ok(metadata)
         }
 
+        /// Create a backup of the DB with its schema reverted to the version to simulate a
+        /// backup made by an older app version.
+        fn create_backup_with_schema(&self, version: &str) -> Result<BackupMetadata> {
+            let current = self.create_backup()?;
+
+            let connection_pool = self.resources.connection_pool();
+            connection_pool
+                .connection()?
+                .batch_execute("PRAGMA wal_checkpoint(FULL);")?;
+            let old_db = tmp_file()?;
+            fs::copy(connection_pool.db_path(), old_db.path())?;
+            revert_schema_migrations_after(old_db.path(), version)?;
+
+            let (compression, backup_contents) =
+                BackupCompression::compress_if_smaller(fs::read(old_db.path())?)?;
+            let metadata = BackupMetadata::builder()
+                .backup_scheme(current.backup_scheme)
+                .backup_version(current.backup_version)
+                .device_id(current.device_id.clone())
+                .device_name(current.device_name.clone())
+                .kdf_nonce(current.kdf_nonce.as_str())
+                .compression(compression)
+                .build();
+            let db_backup_dek =
+                DataEncryptionKey::db_backup_dek(self.resources.keychain())?;
+            let encryption_output =
+                db_backup_dek.encrypt_backup(&backup_contents, &metadata)?;
+            store_backup_zip(
+                self.backup_storage(),
+                self.resources.backup_spool(),
+                &metadata,
+                &encryption_output,
+            )?;
+
+            Ok(metadata)
+        }
+
         fn backup_versions_in_dir(&self) -> Result<Vec<BackupVersion>> {
             let mut res: Vec<BackupVersion> = Default::default();
             let backup_storage = self.backup_storage();
@@ -143,29 +225,41 @@ mod tests {
             })
         }
 
-        fn restore(
-            &self,
-            password: &str,
-            metadata: &BackupMetadata,
-        ) -> Result<BackupMetadata> {
+        fn core_args(&self) -> CoreArgs {
             let db_file_path = self.restore_to.path().to_str().unwrap().to_string();
-            let core_args = CoreArgs {
+            CoreArgs {
                 device_id: self.resources.device_id().to_string(),
                 device_name: self.resources.device_name().to_string(),
                 // This is not used for restore
                 cache_dir: "".into(),
                 db_file_path,
-            };
+                default_fiat_currency: self.resources.default_fiat_currency().to_string(),
+                log_rpc: false,
+                disabled_methods: None,
+                approval_timeout_seconds: None,
+                max_decoded_calldata_bytes: None,
+                max_backup_size_bytes: None,
+                app_version: self.resources.app_version().into(),
+                db_encryption_key: None,
+                demo: false,
+            }
+        }
 
-            let backup_metadata = restore_backup_inner(
-                core_args,
+        fn restore(
+            &self,
+            password: &str,
+            metadata: &BackupMetadata,
+        ) -> Result<(BackupMetadata, BackupContents)> {
+            let res = restore_backup_with_status(
+                self.core_args(),
                 self.resources.backup_storage(),
                 metadata.backup_file_name(),
                 self.resources.keychain(),
                 password,
+                self.resources.restore_status(),
             )?;
 
-            Ok(backup_metadata)
+            Ok(res)
         }
 
         fn verify_can_decrypt_key(&self) -> Result<()> {
@@ -193,10 +287,23 @@ mod tests {
             Ok(())
         }
 
+        fn restored_backup_version(&self) -> Result<BackupVersion> {
+            let connection_pool = ConnectionPool::new(
+                self.restore_to
+                    .path()
+                    .to_str()
+                    .expect("path converts to str"),
+            )?;
+            let mut conn = connection_pool.connection()?;
+            let backup_version = m::LocalSettings::fetch_backup_version(&mut conn)?;
+            Ok(backup_version)
+        }
+
         fn verify(&self, password: &str, backup_metadata: &BackupMetadata) -> Result<()> {
-            let restore_metadata = self.restore(password, backup_metadata)?;
+            let (restore_metadata, contents) = self.restore(password, backup_metadata)?;
             self.verify_can_decrypt_key()?;
             assert_eq!(backup_metadata, &restore_metadata);
+            assert_eq!(contents, Default::default());
             Ok(())
         }
     }
@@ -220,6 +327,148 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn can_restore_compressed_db_backup() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let backup_metadata = backup.create_backup()?;
+
+        // SQLite files have lots of empty space in pages.
+        assert_eq!(backup_metadata.compression, BackupCompression::Deflate);
+        // Apps that only support V1 must reject compressed backups.
+        assert_eq!(backup_metadata.backup_scheme, BackupScheme::V2);
+
+        let restore = RestoreTest::new(backup)?;
+        restore.verify(&password, &backup_metadata)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn v1_scheme_must_be_uncompressed() {
+        assert!(BackupScheme::V1.supports(BackupCompression::None));
+        assert!(!BackupScheme::V1.supports(BackupCompression::Deflate));
+        assert!(BackupScheme::V2.supports(BackupCompression::Deflate));
+    }
+
+    #[test]
+    fn metadata_without_compression_is_uncompressed() -> Result<()> {
+        let metadata = BackupMetadata::builder()
+            .backup_scheme(BackupScheme::V1)
+            .backup_version(1.try_into()?)
+            .device_id("475dda83-9447-4626-9cf1-ecc4ddbe5bbd".parse()?)
+            .device_name("foo".parse()?)
+            .kdf_nonce("nonce")
+            .build();
+
+        let json = String::from_utf8(metadata.canonical_json()?)?;
+        // Associated data of backups created before compression was supported must not change.
+        assert!(!json.contains("compression"));
+        let parsed: BackupMetadata = serde_json::from_str(&json)?;
+        assert_eq!(parsed.compression, BackupCompression::None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_json_matches_golden() -> Result<()> {
+        BackupMetadata::verify_canonical_json_stable()?;
+
+        let metadata = BackupMetadata::builder()
+            .backup_scheme(BackupScheme::V1)
+            .backup_version(1.try_into()?)
+            .device_id("475dda83-9447-4626-9cf1-ecc4ddbe5bbd".parse()?)
+            .device_name("foo".parse()?)
+            .kdf_nonce("nonce")
+            .metadata_version(1)
+            .build();
+        let json = String::from_utf8(metadata.canonical_json()?)?;
+        assert!(!json.contains("metadata_version"));
+
+        let metadata = BackupMetadata {
+            metadata_version: 2,
+            ..metadata
+        };
+        let json = String::from_utf8(metadata.canonical_json()?)?;
+        assert!(json.contains(r#""metadata_version":2"#));
+        let parsed: BackupMetadata = serde_json::from_str(&json)?;
+        assert_eq!(parsed.metadata_version, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn restore_requires_min_app_version() -> Result<()> {
+        let metadata = BackupMetadata::builder()
+            .backup_scheme(BackupScheme::V1)
+            .backup_version(1.try_into()?)
+            .device_id("475dda83-9447-4626-9cf1-ecc4ddbe5bbd".parse()?)
+            .device_name("foo".parse()?)
+            .kdf_nonce("nonce")
+            .build();
+        assert_eq!(
+            metadata.min_app_version.as_deref(),
+            Some(config::BACKUP_MIN_APP_VERSION)
+        );
+        let json = String::from_utf8(metadata.canonical_json()?)?;
+        let parsed: BackupMetadata = serde_json::from_str(&json)?;
+        assert_eq!(parsed.min_app_version, metadata.min_app_version);
+
+        let metadata = BackupMetadata {
+            min_app_version: Some("1.2.3".into()),
+            ..metadata
+        };
+        let res = metadata.verify_min_app_version("1.2.2");
+        assert!(matches!(res, Err(Error::User { .. })));
+        let res = metadata.verify_min_app_version("0.9.10");
+        assert!(matches!(res, Err(Error::User { .. })));
+        metadata.verify_min_app_version("1.2.3")?;
+        metadata.verify_min_app_version("1.10.0")?;
+        metadata.verify_min_app_version("2.0.0-beta.1")?;
+
+        // Backups created before the minimum app version was recorded can be restored by any
+        // version.
+        let metadata = BackupMetadata {
+            min_app_version: None,
+            ..metadata
+        };
+        metadata.verify_min_app_version("0.0.1")?;
+
+        // The minimum app version of the backup is untrusted input.
+        let metadata = BackupMetadata {
+            min_app_version: Some("not-a-version".into()),
+            ..metadata
+        };
+        let res = metadata.verify_min_app_version("1.2.3");
+        assert!(matches!(res, Err(Error::User { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn associated_data_debug_matches_canonical_json() -> Result<()> {
+        let metadata = BackupMetadata::builder()
+            .backup_scheme(BackupScheme::V1)
+            .backup_version(1.try_into()?)
+            .device_id("475dda83-9447-4626-9cf1-ecc4ddbe5bbd".parse()?)
+            .device_name("foo".parse()?)
+            .kdf_nonce("nonce")
+            .build();
+        let associated_data = metadata.canonical_json()?;
+
+        let debug = metadata.associated_data_debug()?;
+
+        assert!(debug.contains(&format!("({} bytes)", associated_data.len())));
+        assert!(debug.contains(&format!(
+            "json: {}",
+            String::from_utf8(associated_data.clone())?
+        )));
+        assert!(debug.contains(&format!("hex: {}", hex::encode(&associated_data))));
+
+        Ok(())
+    }
+
     #[test]
     fn can_restore_after_multiple_backup() -> Result<()> {
         let backup = BackupTest::new()?;
@@ -249,6 +498,454 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn restores_default_picture_for_corrupted_picture() -> Result<()> {
+        use diesel::prelude::*;
+
+        use crate::{assets::default_profile_pic_name, db::schema::profile_pictures};
+
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let mut conn = backup.resources.connection_pool().connection()?;
+        diesel::update(profile_pictures::table)
+            .set(profile_pictures::image.eq(b"corrupted".to_vec()))
+            .execute(&mut conn)?;
+        drop(conn);
+        let backup_metadata = backup.create_backup()?;
+
+        let restore = RestoreTest::new(backup)?;
+        let (restore_metadata, contents) =
+            restore.restore(&password, &backup_metadata)?;
+
+        assert_eq!(restore_metadata, backup_metadata);
+        assert_eq!(contents.missing_profile_pictures, 1);
+        // Non-picture data is restored.
+        restore.verify_can_decrypt_key()?;
+        let connection_pool =
+            ConnectionPool::new(&path_to_string(restore.restore_to.path())?)?;
+        let mut conn = connection_pool.connection()?;
+        let profiles = m::Profile::list_all(&mut conn)?;
+        assert_eq!(profiles.len(), 1);
+        let profile = profiles.first().expect("there is a profile");
+        let picture = m::ProfilePicture::fetch(&mut conn, &profile.picture_id)?
+            .expect("picture exists");
+        assert!(picture.is_valid());
+        assert_eq!(
+            picture.image_name,
+            Some(default_profile_pic_name(
+                &profile.deterministic_id.to_string()
+            ))
+        );
+        // The corrupted picture is removed.
+        assert_eq!(m::ProfilePicture::list_all(&mut conn)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_db_replace_keeps_old_sk_kek() -> Result<()> {
+        let keychain = Keychain::new();
+        let old_sk_kek = KeyEncryptionKey::random(KeyName::SkKeyEncryptionKey)?;
+        // The SK-DEK of the old DB is encrypted with the old SK-KEK.
+        let sk_dek = DataEncryptionKey::random(KeyName::SkDataEncryptionKey)?;
+        let encrypted_sk_dek = sk_dek.to_encrypted(&old_sk_kek)?;
+        old_sk_kek.upsert_to_local_keychain(&keychain)?;
+
+        let new_sk_kek = KeyEncryptionKey::random(KeyName::SkKeyEncryptionKey)?;
+        // Fail between the keychain rotation and the DB replacement.
+        let res = replace_db_and_sk_kek(&keychain, new_sk_kek, || {
+            Err(Error::Retriable {
+                error: "Injected failure".into(),
+            })
+        });
+        assert!(res.is_err());
+
+        let sk_kek = KeyEncryptionKey::sk_kek(&keychain)?;
+        let decrypted = DataEncryptionKey::from_encrypted(
+            KeyName::SkDataEncryptionKey,
+            &encrypted_sk_dek,
+            &sk_kek,
+        );
+        assert!(decrypted.is_ok());
+
+        // Without an old SK-KEK, the new one is removed.
+        let keychain = Keychain::new();
+        let new_sk_kek = KeyEncryptionKey::random(KeyName::SkKeyEncryptionKey)?;
+        let res = replace_db_and_sk_kek(&keychain, new_sk_kek, || {
+            Err(Error::Retriable {
+                error: "Injected failure".into(),
+            })
+        });
+        assert!(res.is_err());
+        assert!(KeyEncryptionKey::sk_kek_if_exists(&keychain)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn restore_from_other_device_resets_backup_version() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let _ = backup.create_backup()?;
+        let backup_metadata = backup.create_backup()?;
+        let zero_backup_version: BackupVersion = 0.try_into()?;
+        assert!(backup_metadata.backup_version > zero_backup_version);
+
+        let restore = RestoreTest::new(backup)?;
+        assert_ne!(restore.resources.device_id(), &backup_metadata.device_id);
+        restore.verify(&password, &backup_metadata)?;
+
+        // The restore device starts its own version chain.
+        assert_eq!(restore.restored_backup_version()?, zero_backup_version);
+
+        Ok(())
+    }
+
+    #[test]
+    fn restores_backup_with_old_schema() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let backup_metadata = backup.create_backup_with_schema(OLD_SCHEMA_VERSION)?;
+        let latest_version = latest_applied_version(
+            &mut backup.resources.connection_pool().connection()?,
+        )?;
+
+        let restore = RestoreTest::new(backup)?;
+        restore.verify(&password, &backup_metadata)?;
+
+        let connection_pool =
+            ConnectionPool::new(&path_to_string(restore.restore_to.path())?)?;
+        let mut conn = connection_pool.connection()?;
+        assert_eq!(latest_applied_version(&mut conn)?, latest_version);
+        assert_eq!(
+            m::LocalSettings::fetch_last_restored_backup_timestamp(&mut conn)?,
+            Some(backup_metadata.timestamp)
+        );
+        assert!(!is_backup_enabled(&connection_pool)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn restores_over_db_with_old_schema() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let backup_metadata = backup.create_backup_with_schema(OLD_SCHEMA_VERSION)?;
+
+        // Simulate restoring onto a DB that hasn't been migrated since an app update.
+        let restore = RestoreTest::new(backup)?;
+        let connection_pool = restore.resources.connection_pool();
+        connection_pool
+            .connection()?
+            .batch_execute("PRAGMA wal_checkpoint(FULL);")?;
+        fs::copy(connection_pool.db_path(), restore.restore_to.path())?;
+        revert_schema_migrations_after(restore.restore_to.path(), OLD_SCHEMA_VERSION)?;
+
+        restore.verify(&password, &backup_metadata)?;
+
+        let connection_pool =
+            ConnectionPool::new(&path_to_string(restore.restore_to.path())?)?;
+        let mut conn = connection_pool.connection()?;
+        assert_eq!(
+            m::LocalSettings::fetch_last_restored_backup_timestamp(&mut conn)?,
+            Some(backup_metadata.timestamp)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn diffs_backups_with_added_dapp() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let first = backup.create_backup_without_deleting_outdated()?;
+
+        let url = "https://unknown-dapp.org".parse()?;
+        let dapp_id =
+            backup
+                .resources
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    m::Dapp::create_if_not_exists(
+                        &mut tx_conn,
+                        url,
+                        backup.resources.public_suffix_list(),
+                    )
+                })?;
+        let second = backup.create_backup_without_deleting_outdated()?;
+
+        let diff = diff_backups(
+            backup.backup_storage(),
+            backup.resources.keychain(),
+            backup.resources.max_backup_size_bytes(),
+            backup.resources.app_version(),
+            first.backup_file_name(),
+            second.backup_file_name(),
+            &password,
+        )?;
+        assert_eq!(
+            diff.dapps,
+            BackupEntityDiff {
+                added: vec![dapp_id.to_string()],
+                ..Default::default()
+            }
+        );
+        assert_eq!(diff.profiles, Default::default());
+        assert_eq!(diff.keys, Default::default());
+        assert_eq!(diff.backup_version_delta, 1);
+        assert!(diff.timestamp_delta_seconds >= 0);
+
+        // The live DB is unchanged.
+        let mut conn = backup.resources.connection_pool().connection()?;
+        assert_eq!(
+            m::LocalSettings::fetch_backup_version(&mut conn)?,
+            second.backup_version
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_file_name_with_mismatched_device_id() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let backup_metadata = backup.create_backup()?;
+
+        // Store the backup under a file name that claims a different device.
+        let work_dir = RestoreWorkDir::new(&backup_metadata.backup_file_name())?;
+        let backup_storage = backup.backup_storage();
+        assert!(backup_storage.copy_from_storage(
+            backup_metadata.backup_file_name(),
+            work_dir.zip_path_string()?
+        ));
+        let other_device_id: DeviceIdentifier = "other-device-id".parse()?;
+        let renamed_file_name = get_backup_file_name(
+            backup_metadata.backup_scheme,
+            &backup_metadata.operating_system,
+            backup_metadata.timestamp,
+            &other_device_id,
+            backup_metadata.backup_version,
+        );
+        assert!(backup_storage
+            .copy_to_storage(renamed_file_name.clone(), work_dir.zip_path_string()?));
+
+        let restore = RestoreTest::new(backup)?;
+        let db_file_path = restore.restore_to.path().to_str().unwrap().to_string();
+        let core_args = CoreArgs {
+            device_id: restore.resources.device_id().to_string(),
+            device_name: restore.resources.device_name().to_string(),
+            cache_dir: "".into(),
+            db_file_path,
+            default_fiat_currency: restore.resources.default_fiat_currency().to_string(),
+            log_rpc: false,
+            disabled_methods: None,
+            approval_timeout_seconds: None,
+            max_decoded_calldata_bytes: None,
+            max_backup_size_bytes: None,
+            app_version: restore.resources.app_version().into(),
+            db_encryption_key: None,
+            demo: false,
+        };
+        let result = restore_backup_inner(
+            core_args,
+            restore.resources.backup_storage(),
+            renamed_file_name,
+            restore.resources.keychain(),
+            &password,
+        );
+        assert!(matches!(
+            result,
+            Err(BackupError::Error {
+                error: CoreError::Fatal { .. }
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_restoring_backup_above_max_size() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let backup_metadata = backup.create_backup()?;
+
+        let restore = RestoreTest::new(backup)?;
+        let core_args = CoreArgs {
+            max_backup_size_bytes: Some(1024),
+            ..restore.core_args()
+        };
+        let result = restore_backup_inner(
+            core_args,
+            restore.resources.backup_storage(),
+            backup_metadata.backup_file_name(),
+            restore.resources.keychain(),
+            &password,
+        );
+        assert!(matches!(
+            result,
+            Err(BackupError::Error {
+                error: CoreError::User { .. }
+            })
+        ));
+        // The DB is left untouched.
+        assert_eq!(std::fs::metadata(restore.restore_to.path())?.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_restoring_backup_for_newer_app() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let backup_metadata = backup.create_backup()?;
+
+        let restore = RestoreTest::new(backup)?;
+        let core_args = CoreArgs {
+            app_version: "0.0.1".into(),
+            ..restore.core_args()
+        };
+        let result = restore_backup_inner(
+            core_args,
+            restore.resources.backup_storage(),
+            backup_metadata.backup_file_name(),
+            restore.resources.keychain(),
+            &password,
+        );
+        assert!(matches!(
+            result,
+            Err(BackupError::Error {
+                error: CoreError::User { .. }
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn does_not_create_backup_above_max_size() -> Result<()> {
+        let mut backup = BackupTest::new()?;
+        Arc::get_mut(&mut backup.resources)
+            .expect("resources aren't shared")
+            .set_max_backup_size_bytes(1024);
+        backup.setup_or_rotate_backup()?;
+
+        let result = create_backup(backup.resources.as_ref());
+        assert!(matches!(
+            result,
+            Err(BackupError::Error {
+                error: CoreError::User { .. }
+            })
+        ));
+        assert!(backup.backup_versions_in_dir()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_restoring_older_backup_after_newer() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let older_metadata = backup.create_backup_without_deleting_outdated()?;
+        // Timestamps have second resolution.
+        thread::sleep(Duration::from_millis(1100));
+        let newer_metadata = backup.create_backup_without_deleting_outdated()?;
+        assert!(older_metadata.timestamp < newer_metadata.timestamp);
+
+        let restore = RestoreTest::new(backup)?;
+        restore.verify(&password, &newer_metadata)?;
+
+        let result = restore.restore(&password, &older_metadata);
+        let error = result
+            .expect_err("older backup is rejected")
+            .downcast::<BackupError>()?;
+        assert_eq!(
+            error,
+            BackupError::RollbackDetected {
+                backup_timestamp: older_metadata.timestamp,
+                last_restored_timestamp: newer_metadata.timestamp,
+            }
+        );
+
+        // Restoring the same backup again is fine.
+        restore.verify(&password, &newer_metadata)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_restoring_if_last_restored_timestamp_is_unreadable() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let backup_metadata = backup.create_backup()?;
+
+        let restore = RestoreTest::new(backup)?;
+        std::fs::write(restore.restore_to.path(), b"not a database")?;
+
+        let result = restore.restore(&password, &backup_metadata);
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(restore.restore_to.path())?, b"not a database");
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_restore_leaves_db_unchanged() -> Result<()> {
+        use diesel::prelude::*;
+
+        use crate::{db::schema::local_encrypted_deks, encryption::KeyName};
+
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let password = backup.backup_password()?;
+        let valid_metadata = backup.create_backup_without_deleting_outdated()?;
+        // Timestamps have second resolution.
+        thread::sleep(Duration::from_millis(1100));
+        // Make the restore fail after the backup was written by removing the SK-DEK that
+        // is re-encrypted with the SK-KEK of the restore device.
+        let mut conn = backup.resources.connection_pool().connection()?;
+        diesel::delete(local_encrypted_deks::table.filter(
+            local_encrypted_deks::kek_name.eq(KeyName::SkKeyEncryptionKey.as_ref()),
+        ))
+        .execute(&mut conn)?;
+        drop(conn);
+        let failing_metadata = backup.create_backup_without_deleting_outdated()?;
+
+        let restore = RestoreTest::new(backup)?;
+        assert_eq!(
+            restore.resources.restore_status().status()?,
+            RestoreStatus::NotStarted
+        );
+        restore.verify(&password, &valid_metadata)?;
+        assert_eq!(
+            restore.resources.restore_status().status()?,
+            RestoreStatus::Completed
+        );
+        let db_before = std::fs::read(restore.restore_to.path())?;
+
+        let result = restore.restore(&password, &failing_metadata);
+        assert!(result.is_err());
+        assert_eq!(
+            restore.resources.restore_status().status()?,
+            RestoreStatus::Failed
+        );
+
+        // Full rollback: the previously restored DB is intact.
+        assert_eq!(std::fs::read(restore.restore_to.path())?, db_before);
+        restore.verify_can_decrypt_key()?;
+
+        Ok(())
+    }
+
     #[test]
     fn can_rotate_password() -> Result<()> {
         let backup = BackupTest::new()?;
@@ -407,6 +1104,52 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn detects_pending_backup_changes() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let _ = backup.create_backup()?;
+
+        let pending =
+            backup
+                .resources
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    let backup_completed_at =
+                        m::LocalSettings::fetch_backup_timestamp(tx_conn.as_mut())?;
+                    PendingBackupChanges::fetch(
+                        tx_conn.as_mut(),
+                        backup_completed_at.as_deref(),
+                    )
+                })?;
+        assert_eq!(pending, Default::default());
+
+        let pending =
+            backup
+                .resources
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    let backup_completed_at =
+                        m::LocalSettings::fetch_backup_timestamp(tx_conn.as_mut())?;
+                    let name: m::ProfileName = "foo".parse()?;
+                    m::Profile::create_eth_profile(
+                        &mut tx_conn,
+                        backup.resources.keychain(),
+                        &name,
+                        "seal-1",
+                        &load_profile_pic("seal-1")?,
+                    )?;
+                    PendingBackupChanges::fetch(
+                        tx_conn.as_mut(),
+                        backup_completed_at.as_deref(),
+                    )
+                })?;
+        assert!(pending.has_changes);
+        assert!(pending.has_unsaved_key_material);
+
+        Ok(())
+    }
+
     #[test]
     fn device_id_from_filename_ok() -> Result<()> {
         let os: OperatingSystem = Default::default();
@@ -431,4 +1174,184 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn unknown_os_in_filename_is_preserved() -> Result<()> {
+        let file_name = "sealvault_backup_V1_futureos_1679000000_\
+            475dda83-9447-4626-9cf1-ecc4ddbe5bbd_3.zip";
+
+        let meta_from_file_name: MetadataFromFileName = file_name.parse()?;
+
+        assert_eq!(
+            meta_from_file_name.os,
+            OperatingSystem::Unknown("futureos".into())
+        );
+        assert!(!meta_from_file_name.os.is_known());
+        assert_eq!(meta_from_file_name.os.to_string(), "futureos");
+        // Known operating systems parse exactly.
+        assert_eq!("ios".parse::<OperatingSystem>()?, OperatingSystem::Ios);
+        assert_eq!(
+            "iOS".parse::<OperatingSystem>()?,
+            OperatingSystem::Unknown("iOS".into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn backup_version_next_increments() -> Result<()> {
+        let backup_version: BackupVersion = 16.try_into()?;
+        assert_eq!(backup_version.next()?, 17.try_into()?);
+        Ok(())
+    }
+
+    #[test]
+    fn backup_version_next_overflow_errors() -> Result<()> {
+        let backup_version: BackupVersion = i64::MAX.try_into()?;
+        assert!(backup_version.next().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn increment_backup_version_returns_new_version() -> Result<()> {
+        let backup = BackupTest::new()?;
+        let connection_pool = backup.resources.connection_pool();
+        let before = connection_pool.deferred_transaction(|mut tx_conn| {
+            m::LocalSettings::fetch_backup_version(tx_conn.as_mut())
+        })?;
+
+        let incremented = connection_pool.exclusive_transaction(|mut tx_conn| {
+            m::LocalSettings::increment_backup_version(&mut tx_conn)
+        })?;
+
+        assert_eq!(incremented, before.next()?);
+        let after = connection_pool.deferred_transaction(|mut tx_conn| {
+            m::LocalSettings::fetch_backup_version(tx_conn.as_mut())
+        })?;
+        assert_eq!(after, incremented);
+        Ok(())
+    }
+
+    #[test]
+    fn failed_upload_is_spooled_and_flushed() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let resources = backup.resources.as_ref();
+        let storage = resources.tmp_backup_storage();
+
+        let uploaded_metadata = backup.create_backup()?;
+
+        storage.set_fail_uploads(true);
+        let res = create_backup(resources);
+        assert!(matches!(res, Err(BackupError::BackupSpooled)));
+        let spooled_file_name = resources.backup_spool().list()?.pop().expect("spooled");
+        let spooled_metadata: MetadataFromFileName = spooled_file_name.parse()?;
+
+        assert!(resources.backup_spool().is_spooled(&spooled_file_name));
+        assert!(!storage.is_uploaded(spooled_file_name.clone()));
+        assert_eq!(last_uploaded_backup(resources)?, None);
+        // The previous backup is kept until the new one is uploaded.
+        assert_eq!(
+            backup.backup_versions_in_dir()?,
+            vec![uploaded_metadata.backup_version]
+        );
+
+        // Still failing
+        let res = flush_pending_backups(resources);
+        assert!(matches!(res, Err(BackupError::BackupSpooled)));
+        assert!(resources.backup_spool().is_spooled(&spooled_file_name));
+
+        storage.set_fail_uploads(false);
+        flush_pending_backups(resources)?;
+
+        assert!(!resources.backup_spool().is_spooled(&spooled_file_name));
+        assert!(storage.is_uploaded(spooled_file_name));
+        assert!(last_uploaded_backup(resources)?.is_some());
+        assert_eq!(
+            backup.backup_versions_in_dir()?,
+            vec![spooled_metadata.backup_version]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn successful_backup_clears_outdated_spooled_backups() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let resources = backup.resources.as_ref();
+        let storage = resources.tmp_backup_storage();
+
+        storage.set_fail_uploads(true);
+        let res = create_backup(resources);
+        assert!(matches!(res, Err(BackupError::BackupSpooled)));
+        assert_eq!(resources.backup_spool().list()?.len(), 1);
+
+        storage.set_fail_uploads(false);
+        let metadata = backup.create_backup()?;
+        assert!(resources.backup_spool().list()?.is_empty());
+
+        // Nothing stale to upload.
+        flush_pending_backups(resources)?;
+        assert_eq!(
+            backup.backup_versions_in_dir()?,
+            vec![metadata.backup_version]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resends_backup_that_failed_to_upload() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let resources = backup.resources.as_ref();
+        let storage = resources.tmp_backup_storage();
+
+        storage.set_fail_uploads(true);
+        let res = create_backup(resources);
+        assert!(matches!(res, Err(BackupError::BackupSpooled)));
+        let spooled_file_name = resources.backup_spool().list()?.pop().expect("spooled");
+        let spooled_metadata: MetadataFromFileName = spooled_file_name.parse()?;
+        assert!(!storage.is_uploaded(spooled_file_name.clone()));
+
+        let res = resend_backup(resources);
+        assert!(matches!(res, Err(BackupError::BackupSpooled)));
+
+        storage.set_fail_uploads(false);
+        resend_backup(resources)?;
+
+        assert!(storage.is_uploaded(spooled_file_name));
+        assert!(last_uploaded_backup(resources)?.is_some());
+        // Idempotent if the backup is already uploaded.
+        resend_backup(resources)?;
+        assert_eq!(
+            backup.backup_versions_in_dir()?,
+            vec![spooled_metadata.backup_version]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resend_creates_new_backup_if_spooled_backup_is_gone() -> Result<()> {
+        let backup = BackupTest::new()?;
+        backup.setup_or_rotate_backup()?;
+        let resources = backup.resources.as_ref();
+        let storage = resources.tmp_backup_storage();
+
+        storage.set_fail_uploads(true);
+        let spooled_metadata = backup.create_backup_without_deleting_outdated()?;
+        resources.backup_spool().clear()?;
+        storage.set_fail_uploads(false);
+
+        resend_backup(resources)?;
+
+        assert!(last_uploaded_backup(resources)?.is_some());
+        let versions = backup.backup_versions_in_dir()?;
+        assert_eq!(versions.len(), 1);
+        assert!(versions[0] > spooled_metadata.backup_version);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,283 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use diesel::{prelude::*, SqliteConnection};
+use typed_builder::TypedBuilder;
+
+use crate::{
+    backup::last_uploaded_backup,
+    config,
+    db::{models as m, schema::asymmetric_keys},
+    resources::CoreResourcesI,
+    utils::unix_timestamp,
+    Error,
+};
+
+/// Tables that are part of the backup and track modifications with timestamps.
+const BACKED_UP_TABLES: [&str; 13] = [
+    "profiles",
+    "profile_pictures",
+    "asymmetric_keys",
+    "addresses",
+    "chains",
+    "dapp_favicons",
+    "dapp_storage",
+    "dapp_tags",
+    "dapps",
+    "data_encryption_keys",
+    "tokens",
+    "tokens_to_addresses",
+    "transactions",
+];
+
+/// How urgently the user should be reminded to back up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BackupReminderSeverity {
+    Fine,
+    Reminder,
+    Urgent,
+}
+
+impl BackupReminderSeverity {
+    fn escalate(self) -> Self {
+        match self {
+            Self::Fine => Self::Reminder,
+            Self::Reminder | Self::Urgent => Self::Urgent,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupReminder {
+    pub severity: BackupReminderSeverity,
+    /// Whether there are changes that aren't part of the last uploaded backup.
+    pub has_pending_changes: bool,
+    /// Whether there are keys that aren't part of the last uploaded backup.
+    pub has_unsaved_key_material: bool,
+    /// None if no backup has been uploaded yet.
+    pub seconds_since_last_upload: Option<i64>,
+}
+
+/// The time since the last uploaded backup after which the user should be reminded if there are
+/// pending changes.
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct BackupReminderThresholds {
+    #[builder(default = config::BACKUP_REMINDER_AFTER)]
+    pub reminder_after: Duration,
+    #[builder(default = config::BACKUP_URGENT_AFTER)]
+    pub urgent_after: Duration,
+}
+
+impl Default for BackupReminderThresholds {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(in crate::backup) struct PendingBackupChanges {
+    pub has_changes: bool,
+    pub has_unsaved_key_material: bool,
+}
+
+impl PendingBackupChanges {
+    /// Changes that happened after the backup completed at the RFC3339 timestamp. Everything is
+    /// pending if there is no backup.
+    pub fn fetch(
+        conn: &mut SqliteConnection,
+        backup_completed_at: Option<&str>,
+    ) -> Result<Self, Error> {
+        use asymmetric_keys::dsl as ak;
+
+        let backup_completed_at = match backup_completed_at {
+            Some(timestamp) => timestamp,
+            None => {
+                return Ok(Self {
+                    has_changes: true,
+                    has_unsaved_key_material: true,
+                })
+            }
+        };
+
+        let has_unsaved_key_material = diesel::select(diesel::dsl::exists(
+            asymmetric_keys::table.filter(ak::created_at.gt(backup_completed_at)),
+        ))
+        .get_result(conn)?;
+
+        // Timestamps are RFC3339 strings in UTC with the same precision, so they sort
+        // lexicographically.
+        let subqueries: Vec<String> = BACKED_UP_TABLES
+            .iter()
+            .map(|table| {
+                format!("SELECT 1 FROM {table} WHERE created_at > ?1 OR updated_at > ?1")
+            })
+            .collect();
+        let query = format!(
+            "SELECT EXISTS({}) AS has_changes",
+            subqueries.join(" UNION ALL ")
+        );
+        let result: HasChanges = diesel::sql_query(query)
+            .bind::<diesel::sql_types::Text, _>(backup_completed_at)
+            .get_result(conn)?;
+
+        Ok(Self {
+            has_changes: result.has_changes,
+            has_unsaved_key_material,
+        })
+    }
+}
+
+#[derive(QueryableByName)]
+struct HasChanges {
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    has_changes: bool,
+}
+
+/// Whether the user should be reminded to back up based on the time since the last uploaded
+/// backup and the changes since then.
+pub fn backup_reminder_status(
+    resources: &dyn CoreResourcesI,
+) -> Result<BackupReminder, Error> {
+    let last_upload = last_uploaded_backup(resources)?;
+    let pending_changes =
+        resources
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                let backup_completed_at = match last_upload {
+                    Some(_) => {
+                        m::LocalSettings::fetch_backup_timestamp(tx_conn.as_mut())?
+                    }
+                    None => None,
+                };
+                PendingBackupChanges::fetch(
+                    tx_conn.as_mut(),
+                    backup_completed_at.as_deref(),
+                )
+            })?;
+    Ok(backup_reminder(
+        last_upload,
+        pending_changes,
+        unix_timestamp(),
+        &Default::default(),
+    ))
+}
+
+/// Compute the reminder with the current time as unix timestamp injected.
+pub(in crate::backup) fn backup_reminder(
+    last_upload: Option<i64>,
+    pending_changes: PendingBackupChanges,
+    now: i64,
+    thresholds: &BackupReminderThresholds,
+) -> BackupReminder {
+    let seconds_since_last_upload = last_upload.map(|timestamp| (now - timestamp).max(0));
+
+    let severity = if pending_changes.has_changes {
+        let reminder_after = duration_to_secs(thresholds.reminder_after);
+        let urgent_after = duration_to_secs(thresholds.urgent_after);
+        let severity = match seconds_since_last_upload {
+            None => BackupReminderSeverity::Urgent,
+            Some(secs) if secs >= urgent_after => BackupReminderSeverity::Urgent,
+            Some(secs) if secs >= reminder_after => BackupReminderSeverity::Reminder,
+            Some(_) => BackupReminderSeverity::Fine,
+        };
+        // Losing keys means losing funds, so remind sooner.
+        if pending_changes.has_unsaved_key_material {
+            severity.escalate()
+        } else {
+            severity
+        }
+    } else {
+        BackupReminderSeverity::Fine
+    };
+
+    BackupReminder {
+        severity,
+        has_pending_changes: pending_changes.has_changes,
+        has_unsaved_key_material: pending_changes.has_unsaved_key_material,
+        seconds_since_last_upload,
+    }
+}
+
+fn duration_to_secs(duration: Duration) -> i64 {
+    i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAST_UPLOAD: i64 = 1_678_000_000;
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+
+    fn thresholds() -> BackupReminderThresholds {
+        BackupReminderThresholds::builder()
+            .reminder_after(Duration::from_secs(7 * DAY as u64))
+            .urgent_after(Duration::from_secs(30 * DAY as u64))
+            .build()
+    }
+
+    fn changes(has_unsaved_key_material: bool) -> PendingBackupChanges {
+        PendingBackupChanges {
+            has_changes: true,
+            has_unsaved_key_material,
+        }
+    }
+
+    fn severity_at(
+        now: i64,
+        pending_changes: PendingBackupChanges,
+    ) -> BackupReminderSeverity {
+        backup_reminder(Some(LAST_UPLOAD), pending_changes, now, &thresholds()).severity
+    }
+
+    #[test]
+    fn fine_without_pending_changes() {
+        let now = LAST_UPLOAD + 365 * DAY;
+        let reminder =
+            backup_reminder(Some(LAST_UPLOAD), Default::default(), now, &thresholds());
+        assert_eq!(reminder.severity, BackupReminderSeverity::Fine);
+        assert!(!reminder.has_pending_changes);
+        assert_eq!(reminder.seconds_since_last_upload, Some(365 * DAY));
+    }
+
+    #[test]
+    fn fine_with_recent_backup() {
+        let severity = severity_at(LAST_UPLOAD + HOUR, changes(false));
+        assert_eq!(severity, BackupReminderSeverity::Fine);
+    }
+
+    #[test]
+    fn reminder_after_threshold() {
+        let severity = severity_at(LAST_UPLOAD + 7 * DAY, changes(false));
+        assert_eq!(severity, BackupReminderSeverity::Reminder);
+    }
+
+    #[test]
+    fn reminder_for_recent_unsaved_key_material() {
+        let severity = severity_at(LAST_UPLOAD + HOUR, changes(true));
+        assert_eq!(severity, BackupReminderSeverity::Reminder);
+    }
+
+    #[test]
+    fn urgent_after_threshold() {
+        let severity = severity_at(LAST_UPLOAD + 30 * DAY, changes(false));
+        assert_eq!(severity, BackupReminderSeverity::Urgent);
+    }
+
+    #[test]
+    fn urgent_for_old_unsaved_key_material() {
+        let severity = severity_at(LAST_UPLOAD + 7 * DAY, changes(true));
+        assert_eq!(severity, BackupReminderSeverity::Urgent);
+    }
+
+    #[test]
+    fn urgent_without_uploaded_backup() {
+        let reminder = backup_reminder(None, changes(true), LAST_UPLOAD, &thresholds());
+        assert_eq!(reminder.severity, BackupReminderSeverity::Urgent);
+        assert!(reminder.seconds_since_last_upload.is_none());
+    }
+}
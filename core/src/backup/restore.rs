@@ -3,28 +3,37 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::{
+    collections::HashSet,
+    fs,
     fs::File,
     io::{Read, Write},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Mutex,
 };
 
-use tempfile::TempDir;
+use diesel::SqliteConnection;
+use tempfile::{NamedTempFile, TempDir};
 
 use crate::{
+    assets::default_profile_pic_name,
     backup::{
-        metadata::{BackupMetadata, MetadataFromFileName},
-        setup::{
-            backup_connection_pool, rollback_enable_backup, set_up_or_rotate_sk_kek,
-        },
+        metadata::{BackupMetadata, MetadataFromFileName, METADATA_FORMAT_VERSION},
+        setup::{backup_connection_pool, remove_keys_for_backup, rotate_sk_kek},
         BackupError, BackupStorageI, BackupVersion, ENCRYPTED_BACKUP_FILE_NAME,
         METADATA_FILE_NAME,
     },
-    db::models as m,
+    config,
+    db::{
+        data_migrations, export_database, models as m, schema_migrations::run_migrations,
+        ConnectionPool, DbEncryptionKey, DeterministicId,
+    },
     device::{DeviceIdentifier, OperatingSystem},
     encryption::{
-        BackupPassword, EncryptionOutput, KdfNonce, KdfSecret, Keychain, RootBackupKey,
+        BackupPassword, EncryptionOutput, KdfNonce, KdfSecret, KeyEncryptionKey, KeyName,
+        Keychain, RootBackupKey,
     },
+    public_suffix_list::PublicSuffixList,
     utils::path_to_string,
     CoreArgs, CoreError, Error,
 };
@@ -55,6 +64,45 @@ impl BackupRestoreData {
     }
 }
 
+/// Exposed through FFI to UI.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BackupContents {
+    /// The number of profiles whose pictures were missing or invalid in the backup and were
+    /// replaced with a default picture.
+    pub missing_profile_pictures: u32,
+}
+
+/// Status of the most recent restore of a core.
+/// Exposed through FFI to UI.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreStatus {
+    #[default]
+    NotStarted,
+    InProgress,
+    Completed,
+    /// The restore failed and the DB was left unchanged.
+    Failed,
+}
+
+/// Tracks the status of the restores of a core.
+#[derive(Debug, Default)]
+pub struct RestoreStatusTracker {
+    status: Mutex<RestoreStatus>,
+}
+
+impl RestoreStatusTracker {
+    pub fn status(&self) -> Result<RestoreStatus, Error> {
+        let status = self.status.lock()?;
+        Ok(*status)
+    }
+
+    fn set_status(&self, status: RestoreStatus) -> Result<(), Error> {
+        let mut current = self.status.lock()?;
+        *current = status;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub(in crate::backup) struct RestoreWorkDir {
     // The `TempDir` is not accessed, but we want to retain for the life time of this struct,
@@ -87,31 +135,78 @@ pub fn restore_backup(
     backup_storage: Box<dyn BackupStorageI>,
     backup_file_name: String,
     password: String,
-) -> Result<(), BackupError> {
+) -> Result<BackupContents, BackupError> {
     let keychain = Keychain::new();
-    let _ = restore_backup_inner(
+    // The restore runs before the core is created, so its status is only reported through
+    // the result.
+    let restore_status = RestoreStatusTracker::default();
+    let (_, contents) = restore_backup_with_status(
         core_args,
         &*backup_storage,
         backup_file_name,
         &keychain,
         &password,
+        &restore_status,
     )?;
-    Ok(())
+    Ok(contents)
 }
 
-pub(in crate::backup) fn restore_backup_inner(
+/// Restore the backup and track the status of the restore.
+pub(in crate::backup) fn restore_backup_with_status(
     core_args: CoreArgs,
     backup_storage: &dyn BackupStorageI,
     backup_file_name: String,
     keychain: &Keychain,
     password: &str,
-) -> Result<BackupMetadata, BackupError> {
+    restore_status: &RestoreStatusTracker,
+) -> Result<(BackupMetadata, BackupContents), BackupError> {
+    restore_status.set_status(RestoreStatus::InProgress)?;
+    let res = restore_backup_inner(
+        core_args,
+        backup_storage,
+        backup_file_name,
+        keychain,
+        password,
+    );
+    let status = match res {
+        Ok(_) => RestoreStatus::Completed,
+        Err(_) => RestoreStatus::Failed,
+    };
+    restore_status.set_status(status)?;
+    res
+}
+
+/// A backup that was fetched from storage, authenticated and decrypted in memory.
+pub(in crate::backup) struct DecryptedBackup {
+    pub metadata: BackupMetadata,
+    pub meta_from_file_name: MetadataFromFileName,
+    pub sk_backup_kek: KeyEncryptionKey,
+    /// The decompressed DB file.
+    pub db_bytes: Vec<u8>,
+}
+
+/// Fetch a backup from storage and decrypt it. Nothing is written to the DB.
+pub(in crate::backup) fn decrypt_backup(
+    backup_storage: &dyn BackupStorageI,
+    backup_file_name: String,
+    keychain: &Keychain,
+    password: &str,
+    max_backup_size: u64,
+    app_version: &str,
+) -> Result<DecryptedBackup, BackupError> {
     let password: BackupPassword = password.parse().map_err(|err| {
         log::debug!("Error parsing backup password: {err}");
         BackupError::InvalidPassword
     })?;
 
-    let device_id: DeviceIdentifier = core_args.device_id.parse()?;
+    // The backup may have been created on a different device than the one we're restoring on.
+    let meta_from_file_name: MetadataFromFileName = backup_file_name.parse()?;
+    if !meta_from_file_name.os.is_known() {
+        log::warn!(
+            "Restoring backup from unknown OS '{}' that may be from a newer device",
+            meta_from_file_name.os
+        );
+    }
     let work_dir = RestoreWorkDir::new(&backup_file_name)?;
 
     if !backup_storage.copy_from_storage(backup_file_name, work_dir.zip_path_string()?) {
@@ -119,43 +214,344 @@ pub(in crate::backup) fn restore_backup_inner(
     }
 
     let metadata = backup_metadata_from_zip(work_dir.zip_path())?;
+    metadata.verify_min_app_version(app_version)?;
+    // The file name is not authenticated, but the metadata is as associated data.
+    if metadata.device_id != meta_from_file_name.device_id {
+        return Err(Error::Fatal {
+            error: format!(
+                "Backup file name device id '{}' doesn't match metadata device id '{}'",
+                meta_from_file_name.device_id, metadata.device_id
+            ),
+        }
+        .into());
+    }
 
-    let kdf_secret =
-        KdfSecret::from_keychain(keychain, &metadata.device_id).map_err(|err| {
-            log::debug!("Error fetching KDF secret from keychain: {err}");
-            BackupError::KDFSecretNotAvailable
-        })?;
+    let kdf_secret = KdfSecret::from_keychain(keychain, &meta_from_file_name.device_id)
+        .map_err(|err| {
+        log::debug!("Error fetching KDF secret from keychain: {err}");
+        BackupError::KDFSecretNotAvailable
+    })?;
 
     let kdf_nonce: KdfNonce = metadata.kdf_nonce.parse()?;
     let root_backup_key = RootBackupKey::derive_from(&password, &kdf_secret, &kdf_nonce)?;
     let db_backup_dek = root_backup_key.derive_db_backup_dek()?;
     let sk_backup_kek = root_backup_key.derive_sk_backup_kek()?;
 
-    let encrypted_backup_bytes =
-        extract_from_zip(work_dir.zip_path(), ENCRYPTED_BACKUP_FILE_NAME)
-            .map_err(map_zip_error)?;
+    let encrypted_backup_bytes = extract_from_zip(
+        work_dir.zip_path(),
+        ENCRYPTED_BACKUP_FILE_NAME,
+        max_backup_size,
+    )?;
     let encryption_output: EncryptionOutput = encrypted_backup_bytes.try_into()?;
 
     let decrypted_backup = db_backup_dek
         .decrypt_backup(&encryption_output, &metadata)
         .map_err(|err| {
             log::debug!("Error decrypting backup: {err}");
+            if let Ok(associated_data) = metadata.associated_data_debug() {
+                log::debug!("{associated_data}");
+            }
             // It might be possible that the KDF secret is invalid if there is a logic error in the
             // application or the keychain provides the wrong secret, but in the absence of bugs, the
             // error is due to the user providing the wrong password.
             BackupError::InvalidPassword
         })?;
+    let db_bytes = metadata
+        .compression
+        .decompress(decrypted_backup, max_backup_size)?;
+
+    Ok(DecryptedBackup {
+        metadata,
+        meta_from_file_name,
+        sk_backup_kek,
+        db_bytes,
+    })
+}
+
+pub(in crate::backup) fn restore_backup_inner(
+    core_args: CoreArgs,
+    backup_storage: &dyn BackupStorageI,
+    backup_file_name: String,
+    keychain: &Keychain,
+    password: &str,
+) -> Result<(BackupMetadata, BackupContents), BackupError> {
+    let device_id: DeviceIdentifier = core_args.device_id.parse()?;
+    let db_encryption_key: Option<DbEncryptionKey> = core_args
+        .db_encryption_key
+        .as_deref()
+        .map(|key| key.parse())
+        .transpose()?;
+    let max_backup_size = core_args
+        .max_backup_size_bytes
+        .unwrap_or(config::DEFAULT_MAX_BACKUP_SIZE_BYTES);
+
+    let DecryptedBackup {
+        metadata,
+        meta_from_file_name,
+        sk_backup_kek,
+        db_bytes,
+    } = decrypt_backup(
+        backup_storage,
+        backup_file_name,
+        keychain,
+        password,
+        max_backup_size,
+        &core_args.app_version,
+    )?;
     let restore_path = Path::new(&core_args.db_file_path);
-    restore_decrypted_backup(&metadata, &decrypted_backup, restore_path)?;
 
-    let restored_connection_pool =
-        set_up_or_rotate_sk_kek(keychain, &sk_backup_kek, restore_path)?;
+    // The metadata is authenticated at this point, so the timestamp can be trusted. Backups
+    // older than what was already restored are rejected to prevent replaying superseded
+    // backups to roll back state.
+    let last_restored_timestamp =
+        fetch_last_restored_backup_timestamp(restore_path, db_encryption_key.clone())?;
+    if let Some(last_restored_timestamp) = last_restored_timestamp {
+        if metadata.timestamp < last_restored_timestamp {
+            return Err(BackupError::RollbackDetected {
+                backup_timestamp: metadata.timestamp,
+                last_restored_timestamp,
+            });
+        }
+    }
+
+    // The backup is restored into a staging file that replaces the DB only once it's fully
+    // processed, so that a failed or interrupted restore leaves the DB unchanged.
+    let staged = staging_file_for(restore_path)?;
+    let staged_path = staged.path();
+    restore_decrypted_backup(&metadata, &db_bytes, staged_path)?;
 
-    // Disable backup in restored DB and delete keys on device keychain as user will need to
-    // generate new backup password for this device.
-    rollback_enable_backup(&restored_connection_pool, keychain, &device_id)?;
+    // The keychain is only changed once the restored DB replaces the DB, so that the DB is
+    // left with an SK-KEK it can decrypt if the restore fails.
+    let (restored_connection_pool, sk_kek) = rotate_sk_kek(&sk_backup_kek, staged_path)?;
 
-    Ok(metadata)
+    // Disable backup in restored DB as user will need to generate new backup password for this
+    // device.
+    restored_connection_pool.deferred_transaction(|mut tx_conn| {
+        m::LocalSettings::disable_backups(&mut tx_conn)
+    })?;
+
+    let missing_profile_pictures =
+        restore_missing_profile_pictures(&restored_connection_pool)?;
+    if missing_profile_pictures > 0 {
+        log::warn!(
+            "Replaced {missing_profile_pictures} missing or invalid restored profile pictures"
+        );
+    }
+    let contents = BackupContents {
+        missing_profile_pictures,
+    };
+
+    restored_connection_pool.deferred_transaction(|mut tx_conn| {
+        m::LocalSettings::set_last_restored_backup_timestamp(
+            tx_conn.as_mut(),
+            metadata.timestamp,
+        )
+    })?;
+
+    // Backup versions are monotonically increasing per device, so the restored version only
+    // makes sense on the device that created the backup. Continue from the latest backup of
+    // the current device in storage if any, otherwise start a new chain.
+    if meta_from_file_name.device_id != device_id {
+        let backup_version =
+            match latest_backup_version_for_device(backup_storage, &device_id) {
+                Some(backup_version) => backup_version,
+                None => 0.try_into()?,
+            };
+        restored_connection_pool.deferred_transaction(|mut tx_conn| {
+            m::LocalSettings::set_backup_version(&mut tx_conn, backup_version)
+        })?;
+    }
+
+    // Connections must be closed before the file is replaced.
+    drop(restored_connection_pool);
+    replace_db_and_sk_kek(keychain, sk_kek, || match db_encryption_key.as_ref() {
+        Some(db_encryption_key) => {
+            encrypt_restored_db(staged.path(), restore_path, db_encryption_key)
+        }
+        None => persist_restored_db(staged, restore_path),
+    })?;
+
+    // Data migrations may need the SK-KEK of the restored DB, so they can only run once it's
+    // on the keychain. The core runs pending migrations on launch too, so a failure here
+    // doesn't fail the restore.
+    if let Err(err) = run_data_migrations(restore_path, db_encryption_key, keychain) {
+        log::error!("Failed to run data migrations on restored DB: '{err}'");
+    }
+
+    // Delete the backup keys on the device keychain as backups are disabled in the restored DB.
+    remove_keys_for_backup(keychain, &device_id);
+
+    Ok((metadata, contents))
+}
+
+/// Save the SK-KEK of the restored DB to the keychain and replace the DB with `persist`.
+/// The old SK-KEK is put back if the DB can't be replaced, because the SK-DEK in the old DB is
+/// encrypted with it.
+pub(in crate::backup) fn replace_db_and_sk_kek(
+    keychain: &Keychain,
+    sk_kek: KeyEncryptionKey,
+    persist: impl FnOnce() -> Result<(), Error>,
+) -> Result<(), Error> {
+    let old_sk_kek = KeyEncryptionKey::sk_kek_if_exists(keychain)?;
+    sk_kek.upsert_to_local_keychain(keychain)?;
+
+    if let Err(err) = persist() {
+        let rollback = match old_sk_kek {
+            Some(old_sk_kek) => old_sk_kek.upsert_to_local_keychain(keychain),
+            None => KeyEncryptionKey::delete_from_keychain_if_exists(
+                keychain,
+                KeyName::SkKeyEncryptionKey,
+            ),
+        };
+        if let Err(rollback_err) = rollback {
+            log::error!(
+                "Failed to roll back SK-KEK after failed restore: '{rollback_err}'"
+            );
+        }
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// A temporary file in the same directory as the restore path to make sure the rename that
+/// replaces the DB is atomic.
+fn staging_file_for(restore_path: &Path) -> Result<NamedTempFile, Error> {
+    let restore_dir = restore_path.parent().ok_or_else(|| Error::Fatal {
+        error: "Restore path has no parent directory".into(),
+    })?;
+    NamedTempFile::new_in(restore_dir).map_err(|err| Error::Retriable {
+        error: format!("Failed to create temporary file with error: '{err}'"),
+    })
+}
+
+fn persist_restored_db(
+    restored: NamedTempFile,
+    restore_path: &Path,
+) -> Result<(), Error> {
+    restored
+        .persist(restore_path)
+        .map_err(|err| Error::Retriable {
+            error: format!("Failed to replace DB with restored DB with error: '{err}'"),
+        })?;
+    Ok(())
+}
+
+/// Backups made by older app versions have the schema of that version, so the DB must be
+/// migrated before it's accessed with the current models.
+fn migrate_restored_db_schema(db_path: &Path) -> Result<(), Error> {
+    let connection_pool = backup_connection_pool(db_path)?;
+    connection_pool.exclusive_transaction(|mut tx_conn| run_migrations(&mut tx_conn))
+}
+
+fn run_data_migrations(
+    db_path: &Path,
+    db_encryption_key: Option<DbEncryptionKey>,
+    keychain: &Keychain,
+) -> Result<(), Error> {
+    let db_path = path_to_string(db_path)?;
+    let connection_pool = ConnectionPool::new_with_key(&db_path, db_encryption_key)?;
+    let psl = PublicSuffixList::new()?;
+    connection_pool.exclusive_transaction(|tx_conn| {
+        data_migrations::run_all(tx_conn, keychain, &psl)
+    })
+}
+
+/// Partial or old backups may miss profile picture blobs or have corrupted ones. This
+/// shouldn't fail the restore, so assign the default picture to the affected profiles.
+/// Returns the number of affected profiles.
+fn restore_missing_profile_pictures(
+    connection_pool: &ConnectionPool,
+) -> Result<u32, Error> {
+    connection_pool.deferred_transaction(|mut tx_conn| {
+        let mut invalid_picture_ids: HashSet<DeterministicId> = Default::default();
+        let mut restored_picture_ids: HashSet<DeterministicId> = Default::default();
+        let mut count: u32 = 0;
+
+        for profile in m::Profile::list_all(tx_conn.as_mut())? {
+            let picture =
+                m::ProfilePicture::fetch(tx_conn.as_mut(), &profile.picture_id)?;
+            if picture
+                .map(|picture| picture.is_valid())
+                .unwrap_or_default()
+            {
+                continue;
+            }
+            invalid_picture_ids.insert(profile.picture_id.clone());
+
+            let picture_name =
+                default_profile_pic_name(&profile.deterministic_id.to_string());
+            let picture_id =
+                m::ProfilePicture::upsert_bundled(tx_conn.as_mut(), &picture_name)?;
+            profile.set_picture_id(tx_conn.as_mut(), &picture_id)?;
+            restored_picture_ids.insert(picture_id);
+            count += 1;
+        }
+
+        for picture_id in invalid_picture_ids.difference(&restored_picture_ids) {
+            m::ProfilePicture::delete(tx_conn.as_mut(), picture_id)?;
+        }
+
+        Ok(count)
+    })
+}
+
+/// The timestamp of the last backup restored into the DB at the path if any.
+/// Returns None if there is no DB yet. Errors reading an existing DB are returned, because
+/// the timestamp protects against rollbacks.
+fn fetch_last_restored_backup_timestamp(
+    db_path: &Path,
+    db_encryption_key: Option<DbEncryptionKey>,
+) -> Result<Option<i64>, Error> {
+    let is_empty = match fs::metadata(db_path) {
+        Ok(meta) => meta.len() == 0,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => true,
+        Err(err) => {
+            return Err(Error::Retriable {
+                error: format!("Failed to read DB file metadata with error: '{err}'"),
+            })
+        }
+    };
+    if is_empty {
+        return Ok(None);
+    }
+    let db_path = path_to_string(db_path)?;
+    let connection_pool = ConnectionPool::new_with_key(&db_path, db_encryption_key)?;
+    // The restore may run before the core has migrated the DB after an app update.
+    connection_pool.exclusive_transaction(|mut tx_conn| {
+        run_migrations(&mut tx_conn)?;
+        m::LocalSettings::fetch_last_restored_backup_timestamp(tx_conn.as_mut())
+    })
+}
+
+/// Backups contain the plaintext DB, so encrypt the restored DB with the DB encryption key of
+/// this device before it replaces the DB at the restore path.
+fn encrypt_restored_db(
+    restored_path: &Path,
+    restore_path: &Path,
+    db_encryption_key: &DbEncryptionKey,
+) -> Result<(), Error> {
+    let encrypted = staging_file_for(restore_path)?;
+    export_database(
+        restored_path,
+        None,
+        encrypted.path(),
+        Some(db_encryption_key),
+    )?;
+    persist_restored_db(encrypted, restore_path)
+}
+
+fn latest_backup_version_for_device(
+    backup_storage: &dyn BackupStorageI,
+    device_id: &DeviceIdentifier,
+) -> Option<BackupVersion> {
+    backup_storage
+        .list_backup_file_names()
+        .into_iter()
+        .filter_map(|file_name| MetadataFromFileName::from_str(&file_name).ok())
+        .filter(|meta| &meta.device_id == device_id)
+        .map(|meta| meta.backup_version)
+        .max()
 }
 
 pub fn find_latest_backup(
@@ -219,16 +615,32 @@ pub(in crate::backup) fn find_latest_backup_inner(
 pub(in crate::backup) fn backup_metadata_from_zip(
     zip_path: &Path,
 ) -> Result<BackupMetadata, Error> {
-    let backup_metadata_bytes =
-        extract_from_zip(zip_path, METADATA_FILE_NAME).map_err(map_zip_error)?;
+    let backup_metadata_bytes = extract_from_zip(
+        zip_path,
+        METADATA_FILE_NAME,
+        config::MAX_BACKUP_METADATA_SIZE_BYTES,
+    )?;
     let metadata: BackupMetadata = serde_json::from_slice(&backup_metadata_bytes)
         .map_err(|_err| Error::Retriable {
             error: "Failed to deserialize backup metadata".into(),
         })?;
+    if metadata.metadata_version > METADATA_FORMAT_VERSION {
+        return Err(Error::User {
+            explanation: "The backup was created by a newer version of the app. Please update the app to restore it.".into(),
+        });
+    }
+    if !metadata.backup_scheme.supports(metadata.compression) {
+        return Err(Error::Fatal {
+            error: format!(
+                "Backup scheme '{}' doesn't support compression '{}'",
+                metadata.backup_scheme, metadata.compression
+            ),
+        });
+    }
     Ok(metadata)
 }
 
-fn restore_decrypted_backup(
+pub(in crate::backup) fn restore_decrypted_backup(
     metadata: &BackupMetadata,
     decrypted_backup: &[u8],
     to_path: &Path,
@@ -241,20 +653,33 @@ fn restore_decrypted_backup(
         .map_err(|err| Error::Retriable {
             error: format!("Failed to write to restored backup file with error: '{err}'"),
         })?;
+    migrate_restored_db_schema(to_path)?;
     verify_backup(to_path, metadata.backup_version)?;
     Ok(())
 }
 
+/// Zip entries are compressed too, so the size is capped while reading.
 fn extract_from_zip(
     path: &Path,
     file_name: &str,
-) -> Result<Vec<u8>, zip::result::ZipError> {
-    let file = File::open(path)?;
-    let mut archive = zip::ZipArchive::new(file)?;
+    max_size: u64,
+) -> Result<Vec<u8>, Error> {
+    let file = File::open(path).map_err(|err| map_zip_error(err.into()))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(map_zip_error)?;
 
     let mut file_bytes: Vec<u8> = Default::default();
-    let mut backup_file = archive.by_name(file_name)?;
-    backup_file.read_to_end(&mut file_bytes)?;
+    let backup_file = archive.by_name(file_name).map_err(map_zip_error)?;
+    backup_file
+        .take(max_size + 1)
+        .read_to_end(&mut file_bytes)
+        .map_err(|err| map_zip_error(err.into()))?;
+    if file_bytes.len() as u64 > max_size {
+        return Err(Error::User {
+            explanation: format!(
+                "The backup is too large to restore. The maximum size is {max_size} bytes."
+            ),
+        });
+    }
 
     Ok(file_bytes)
 }
@@ -270,9 +695,16 @@ pub(in crate::backup) fn verify_backup(
     expected_backup_version: BackupVersion,
 ) -> Result<(), Error> {
     let backup_cp = backup_connection_pool(backup_path)?;
-
     let mut conn = backup_cp.connection()?;
-    let backup_version = m::LocalSettings::fetch_backup_version(&mut conn)?;
+    verify_backup_version(&mut conn, expected_backup_version)
+}
+
+/// Make sure that the DB contains the expected backup version.
+pub(in crate::backup) fn verify_backup_version(
+    conn: &mut SqliteConnection,
+    expected_backup_version: BackupVersion,
+) -> Result<(), Error> {
+    let backup_version = m::LocalSettings::fetch_backup_version(conn)?;
 
     if backup_version == expected_backup_version {
         Ok(())
@@ -13,6 +13,7 @@ use crate::{
         KeyName, Keychain, RootBackupKey,
     },
     resources::CoreResourcesI,
+    rng::RngI,
     Error,
 };
 
@@ -27,6 +28,7 @@ pub fn set_up_or_rotate_backup(resources: &dyn CoreResourcesI) -> Result<(), Err
             let kdf_nonce = setup_or_rotate_keys_for_backup(
                 resources.keychain(),
                 resources.device_id(),
+                resources.rng(),
                 &mut tx_conn,
             )?;
 
@@ -56,6 +58,7 @@ pub fn is_backup_enabled(connection_pool: &ConnectionPool) -> Result<bool, Error
 fn setup_or_rotate_keys_for_backup(
     keychain: &Keychain,
     device_identifier: &DeviceIdentifier,
+    rng: &dyn RngI,
     tx_conn: &mut ExclusiveTxConnection,
 ) -> Result<KdfNonce, Error> {
     // Create new backup password
@@ -63,7 +66,7 @@ fn setup_or_rotate_keys_for_backup(
     // most likely failure scenario.
     let kdf_secret = KdfSecret::setup_or_rotate(keychain, device_identifier)?;
     let backup_password = BackupPassword::setup_or_rotate(keychain)?;
-    let kdf_nonce: KdfNonce = KdfNonce::random()?;
+    let kdf_nonce: KdfNonce = KdfNonce::random_with(rng)?;
 
     let root_backup_key =
         RootBackupKey::derive_from(&backup_password, &kdf_secret, &kdf_nonce)?;
@@ -132,6 +135,9 @@ pub fn disable_backup(resources: &dyn CoreResourcesI) -> Result<(), Error> {
         resources.device_id(),
     )?;
 
+    // Spooled backups were encrypted with the deleted keys.
+    resources.backup_spool().clear()?;
+
     // Delete backups that were created on this device.
     delete_backups_for_device(resources)?;
 
@@ -194,17 +200,19 @@ fn delete_backups_for_device(resources: &dyn CoreResourcesI) -> Result<(), Backu
     }
 }
 
-/// Set up SK-KEK if we're on new device as it's not on the local keychain. Rotate if the app is
-/// reinstalled on same device for hygiene.
-/// It's possible that SK-KEK already exists when reinstalling on same device since iOS Keychain
-/// items are not deleted when the app is deleted.
-pub(in crate::backup) fn set_up_or_rotate_sk_kek(
-    keychain: &Keychain,
+/// Encrypt the SK-DEK in the restored DB at the path with a new SK-KEK and return the new
+/// SK-KEK. The SK-KEK is set up if we're on new device as it's not on the local keychain and
+/// rotated if the app is reinstalled on same device for hygiene. It's possible that SK-KEK
+/// already exists when reinstalling on same device since iOS Keychain items are not deleted
+/// when the app is deleted.
+/// The caller must save the SK-KEK to the keychain when the restored DB replaces the DB of
+/// the app.
+pub(in crate::backup) fn rotate_sk_kek(
     sk_backup_kek: &KeyEncryptionKey,
     db_path: &Path,
-) -> Result<ConnectionPool, Error> {
+) -> Result<(ConnectionPool, KeyEncryptionKey), Error> {
     let backup_cp = backup_connection_pool(db_path)?;
-    backup_cp.exclusive_transaction(|mut tx_conn| {
+    let sk_kek = backup_cp.exclusive_transaction(|mut tx_conn| {
         // Fetch the secret key data encryption key (SK-DEK) from the database and decrypt it with the backup key encryption key
         let (dek_id, sk_dek) = m::DataEncryptionKey::fetch_dek(tx_conn.as_mut(), KeyName::SkDataEncryptionKey, sk_backup_kek)?;
         // Create a new secret key encryption key (SK-KEK)
@@ -218,10 +226,9 @@ pub(in crate::backup) fn set_up_or_rotate_sk_kek(
             error: "Local encrypted SK-DEK with SK-KEK is assumed to exist when calling `rotate_sk_kek`".into()
         })?;
         m::LocalEncryptedDek::set_encrypted_dek(tx_conn.as_mut(), sk_dek_id, &encrypted_sk_dek)?;
-        // Save the new SK-KEK to the keychain.
-        sk_kek.upsert_to_local_keychain(keychain)
+        Ok(sk_kek)
     })?;
-    Ok(backup_cp)
+    Ok((backup_cp, sk_kek))
 }
 
 pub(in crate::backup) fn backup_connection_pool(
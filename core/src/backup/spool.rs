@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{backup::BackupStorageI, utils::path_to_string, Error};
+
+const BACKUP_SPOOL_DIR_NAME: &str = "backup_spool";
+
+/// Local directory for encrypted backup files that failed to upload to backup storage.
+/// Spooled backups are uploaded on the next flush instead of being lost.
+#[derive(Debug, Clone)]
+pub struct BackupSpool {
+    dir: PathBuf,
+    is_writable: bool,
+}
+
+impl BackupSpool {
+    /// Spooling fails if the cache dir is not writable.
+    pub fn new(cache_dir: impl AsRef<Path>, is_writable: bool) -> Self {
+        Self {
+            dir: cache_dir.as_ref().join(BACKUP_SPOOL_DIR_NAME),
+            is_writable,
+        }
+    }
+
+    fn file_path(&self, backup_file_name: &str) -> PathBuf {
+        self.dir.join(backup_file_name)
+    }
+
+    /// Copy the backup file into the spool. Previously spooled backups are removed, because
+    /// they're outdated by the new one.
+    pub fn spool(&self, backup_file_name: &str, file_path: &Path) -> Result<(), Error> {
+        if !self.is_writable {
+            // Not a bug in the app, so it shouldn't be reported.
+            return Err(Error::Retriable {
+                error: "Cannot spool backup, because the cache dir is not writable."
+                    .into(),
+            });
+        }
+        fs::create_dir_all(&self.dir).map_err(|err| Error::Retriable {
+            error: format!("Failed to create backup spool dir with error: '{err}'"),
+        })?;
+        fs::copy(file_path, self.file_path(backup_file_name)).map_err(|err| {
+            Error::Retriable {
+                error: format!("Failed to spool backup file with error: '{err}'"),
+            }
+        })?;
+        for spooled in self.list()? {
+            if spooled != backup_file_name {
+                self.remove(&spooled)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the backup file is waiting to be uploaded.
+    pub fn is_spooled(&self, backup_file_name: &str) -> bool {
+        self.file_path(backup_file_name).exists()
+    }
+
+    /// File names of the spooled backups.
+    pub fn list(&self) -> Result<Vec<String>, Error> {
+        if !self.dir.exists() {
+            return Ok(Default::default());
+        }
+        let entries = fs::read_dir(&self.dir).map_err(|err| Error::Retriable {
+            error: format!("Failed to list backup spool dir with error: '{err}'"),
+        })?;
+        let mut results: Vec<String> = Default::default();
+        for entry in entries.flatten() {
+            if let Ok(file_name) = entry.file_name().into_string() {
+                results.push(file_name);
+            }
+        }
+        results.sort();
+        Ok(results)
+    }
+
+    pub fn remove(&self, backup_file_name: &str) -> Result<(), Error> {
+        fs::remove_file(self.file_path(backup_file_name)).map_err(|err| {
+            Error::Retriable {
+                error: format!("Failed to remove spooled backup with error: '{err}'"),
+            }
+        })
+    }
+
+    /// Remove all spooled backups, eg. when backups are disabled.
+    pub fn clear(&self) -> Result<(), Error> {
+        for backup_file_name in self.list()? {
+            self.remove(&backup_file_name)?;
+        }
+        Ok(())
+    }
+
+    /// Attempt to upload the spooled backups and remove them on success.
+    /// Returns the file names of the uploaded backups.
+    pub fn flush(
+        &self,
+        backup_storage: &dyn BackupStorageI,
+    ) -> Result<Vec<String>, Error> {
+        let mut uploaded: Vec<String> = Default::default();
+        for backup_file_name in self.list()? {
+            let file_path = path_to_string(&self.file_path(&backup_file_name))?;
+            if backup_storage.copy_to_storage(backup_file_name.clone(), file_path) {
+                self.remove(&backup_file_name)?;
+                uploaded.push(backup_file_name);
+            } else {
+                log::warn!("Failed to upload spooled backup '{backup_file_name}'");
+            }
+        }
+        Ok(uploaded)
+    }
+}
@@ -8,18 +8,115 @@ use std::time::Duration;
 // We're just one app among many on the user's device, keep the footprint low.
 pub const DB_CONNECTION_POOL_SIZE: u32 = 4;
 pub const DB_BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Free pages reclaimed in one step when optimizing the DB. 1000 pages is 4 MiB with the
+/// default page size.
+pub const DB_INCREMENTAL_VACUUM_PAGES: u64 = 1000;
+/// SQLite opens a new in-memory DB for this path.
+pub const IN_MEMORY_DB_FILE_PATH: &str = ":memory:";
+/// Appended to the DB file path for the keychain file on platforms without a system keychain.
+pub const KEYCHAIN_FILE_SUFFIX: &str = ".keychain.json";
 pub const TOKIO_WORKER_THREADS: usize = 1;
 pub const TOKIO_MAX_BLOCKING_THREADS: usize = 8;
 pub const MAX_ASYNC_CONCURRENT_REQUESTS: usize = 8;
 
+// RPC
+pub const RPC_MAX_RETRIES: u32 = 3;
+pub const RPC_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+pub const RPC_MAX_BACKOFF: Duration = Duration::from_secs(4);
+
+pub const BLOCK_NUMBER_CACHE_TTL: Duration = Duration::from_secs(2);
+
+// Event logs
+/// `eth_getLogs` ranges that the RPC provider rejects aren't split below this many blocks.
+pub const GET_LOGS_MIN_BLOCK_RANGE: u64 = 2_000;
+/// Fetching the logs of a filter fails after this many `eth_getLogs` requests.
+pub const GET_LOGS_MAX_REQUESTS: usize = 64;
+
+// Token discovery
+/// Number of recent blocks scanned for token transfers of an address.
+pub const TOKEN_DISCOVERY_BLOCK_RANGE: u64 = 10_000;
+/// Many public RPC endpoints reject `eth_getLogs` requests for larger block ranges.
+pub const TOKEN_DISCOVERY_BLOCKS_PER_REQUEST: u64 = 2_000;
+/// The scan stops once this many transfer logs have been fetched.
+pub const TOKEN_DISCOVERY_MAX_LOGS: usize = 1_000;
+/// At most this many new tokens are added in one scan.
+pub const TOKEN_DISCOVERY_MAX_TOKENS: usize = 20;
+/// Automatic discovery is started at most this often for an address when its tokens are
+/// fetched.
+pub const TOKEN_AUTO_DISCOVERY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Fungible tokens with longer symbols are treated as spam.
+pub const MAX_TOKEN_SYMBOL_LENGTH: usize = 12;
+
+// Transaction queue
+pub const TX_QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Number of confirmed, replaced or failed transactions to keep per queue.
+pub const TX_QUEUE_MAX_FINISHED: usize = 20;
+
 // User
 pub const DEFAULT_PROFILE_NAME: &str = "Default";
 pub const DEFAULT_PROFILE_PICTURE_NAME: &str = "seal-7";
 pub const MAX_PROFILE_NAME_LENGTH: usize = 30;
+pub const MAX_PROFILE_PIC_SIZE_BYTES: usize = 512 * 1024;
+pub const MAX_PROFILE_PIC_DIMENSION_PX: u32 = 1024;
+/// Transfer memos are local notes, longer ones are rejected.
+pub const MAX_TRANSFER_MEMO_LENGTH: usize = 200;
+/// Larger NFT page sizes are clamped to this.
+pub const MAX_NFT_PAGE_SIZE: u32 = 50;
+/// Private key exports must be confirmed within this time after they're requested.
+pub const KEY_EXPORT_CONFIRMATION_TTL: Duration = Duration::from_secs(2 * 60);
+/// At most `MAX_KEY_EXPORTS_PER_WINDOW` private keys can be exported within this window.
+pub const KEY_EXPORT_WINDOW: Duration = Duration::from_secs(60 * 60);
+pub const MAX_KEY_EXPORTS_PER_WINDOW: i64 = 3;
+
+// Fiat
+pub const DEFAULT_FIAT_CURRENCY: &str = "USD";
+pub const FALLBACK_FIAT_CURRENCY: &str = "USD";
+
+// Backup
+pub const BACKUP_REMINDER_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+pub const BACKUP_URGENT_AFTER: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// Backups come from untrusted storage, so restore aborts if a file in the backup or the
+/// decompressed payload exceeds this size instead of allocating unboundedly. Backups of
+/// larger DBs aren't created, because they couldn't be restored.
+pub const DEFAULT_MAX_BACKUP_SIZE_BYTES: u64 = 256 * 1024 * 1024;
+/// The backup metadata file is small, so it has its own lower cap.
+pub const MAX_BACKUP_METADATA_SIZE_BYTES: u64 = 64 * 1024;
+/// The minimum app version that can restore backups created by this version. Must be raised
+/// when the backup contents change in a way that older versions would mangle.
+pub const BACKUP_MIN_APP_VERSION: &str = "0.1.0";
 
 // In-page provider
 pub const MAX_JSONRPC_REQUEST_SIZE_BYTES: usize = 1000000;
 pub const MAX_JSONRPC_RESPONSE_SIZE_BYTES: usize = 1000000;
+/// The maximum number of typed data messages in a batch signature request.
+pub const MAX_SIGN_BATCH_SIZE: usize = 20;
+/// Methods rejected unless the host overrides the list. `eth_sign` signs arbitrary hashes.
+pub const DEFAULT_DISABLED_JSONRPC_METHODS: [&str; 1] = ["eth_sign"];
+/// Requests are rejected if the user doesn't respond to the approval prompt in this time
+/// unless the host overrides it. Leaves the user time to review the request.
+pub const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Larger calldata is only summarized in transaction approvals unless the host overrides it.
+/// Decoding pathological calldata could make the approval prompt unresponsive.
+pub const DEFAULT_MAX_DECODED_CALLDATA_BYTES: usize = 32 * 1024;
+/// Limit for listing dapps where pagination isn't needed.
+pub const UNPAGINATED_DAPP_LIST_LIMIT: u32 = u32::MAX;
+/// Self-reported dapp name and description are truncated to this many characters.
+pub const MAX_DAPP_METADATA_FIELD_LENGTH: usize = 200;
+/// User assigned dapp tags are truncated to this many characters.
+pub const MAX_DAPP_TAG_LENGTH: usize = 30;
+/// The maximum number of tags of a dapp in a profile.
+pub const MAX_DAPP_TAGS: usize = 10;
+/// The maximum number of bytes of keys and values a dapp can store in a profile.
+pub const MAX_DAPP_STORAGE_BYTES: usize = 64 * 1024;
+/// The maximum length of a dapp storage key in bytes.
+pub const MAX_DAPP_STORAGE_KEY_LENGTH: usize = 256;
+/// Longer self-reported dapp icon urls are ignored.
+pub const MAX_DAPP_METADATA_URL_LENGTH: usize = 2000;
+/// The last use of a dapp session is only updated if it's older than this, so that polling
+/// requests don't write to the DB.
+pub const DAPP_LAST_USED_RESOLUTION_SECONDS: u64 = 60;
+/// Response to `web3_clientVersion`.
+pub const WEB3_CLIENT_VERSION: &str = concat!("SealVault/", env!("CARGO_PKG_VERSION"));
 
 // Assets
 pub const IN_PAGE_PROVIDER_FILE_NAME: &str = "in-page-provider.js";
@@ -31,6 +128,11 @@ pub const REQUEST_HANDLER_PLACEHOLDER: &str = "<SEALVAULT_REQUEST_HANDLER>";
 pub const DEFAULT_CHAIN_ID_PLACEHOLDER: &str = "<SEALVAULT_DEFAULT_CHAIN_ID>";
 pub const DEFAULT_NETWORK_VERSION_PLACEHOLDER: &str =
     "<SEALVAULT_DEFAULT_NETWORK_VERSION>";
+pub const WALLET_INFO_PLACEHOLDER: &str = "<SEALVAULT_WALLET_INFO>";
+pub const BACKEND_URL_PLACEHOLDER: &str = "<SEALVAULT_BACKEND_URL>";
+/// Lines between these markers are removed from the in-page script unless in debug mode.
+pub const DEBUG_ONLY_START_MARKER: &str = "// <debug-only>";
+pub const DEBUG_ONLY_END_MARKER: &str = "// </debug-only>";
 pub const ETH_NATIVE_TOKEN_PREFIX: &str = "protocols/eth/native-tokens";
 pub const NATIVE_TOKEN_EXTENSION: &str = ".png";
 pub const FALLBACK_FAVICON_ASSET: &str = "fallback-favicon.png";
@@ -40,6 +142,25 @@ pub const PUBLIC_SUFFIX_LIST_ASSET: &str = "public-suffix-list.dat.txt";
 
 // Favicons
 pub const FAVICON_API: &str = "https://icons.duckduckgo.com/ip3/";
+pub const MAX_FAVICON_SIZE_BYTES: usize = 64 * 1024;
+/// ICO images can't be larger than this, so it's used for all formats.
+pub const MAX_FAVICON_DIMENSION_PX: u32 = 256;
+
+// HTTP
+pub const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Total time for a fetch including reading the body.
+pub const HTTP_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+pub const HTTP_MAX_REDIRECTS: usize = 5;
+/// Favicons, token logos and chain icons are small, larger responses are aborted.
+pub const MAX_HTTP_ICON_SIZE_BYTES: usize = 1024 * 1024;
+
+// IPFS
+/// Gateways to fetch `ipfs://` content from, tried in order.
+pub const DEFAULT_IPFS_GATEWAYS: &[&str] = &["https://ipfs.io", "https://dweb.link"];
+/// A failed gateway is tried after the healthy ones for this long.
+pub const IPFS_GATEWAY_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
 
 // iOS
 pub const IOS_SERVICE: &str = "org.sealvault";
+/// Deep links to a dapp are `sealvault://dapp/<dapp id>`.
+pub const DAPP_DEEP_LINK_PREFIX: &str = "sealvault://dapp/";
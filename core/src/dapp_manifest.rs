@@ -0,0 +1,253 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    config,
+    db::{models as m, DeferredTxConnection, DeterministicId},
+    resources::CoreResourcesI,
+    Error,
+};
+
+/// An entry of a dapp manifest, eg. `{"url": "https://app.uniswap.org", "name": "Uniswap"}`.
+#[derive(Debug, Clone, Deserialize)]
+struct DappManifestEntry {
+    url: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+}
+
+impl DappManifestEntry {
+    fn dapp_url(&self) -> Result<Url, Error> {
+        let url = Url::parse(self.url.trim()).map_err(|err| Error::User {
+            explanation: format!("Invalid dapp URL '{}': {err}", self.url),
+        })?;
+        if url.scheme() != "https" || url.host_str().is_none() {
+            return Err(Error::User {
+                explanation: format!("Dapp URL must be an https URL: '{}'", self.url),
+            });
+        }
+        Ok(url)
+    }
+
+    /// Only https icon urls are accepted.
+    fn icon_url(&self) -> Result<Option<Url>, Error> {
+        let icon = match self.icon.as_deref().map(str::trim) {
+            Some(icon) if !icon.is_empty() => icon,
+            _ => return Ok(None),
+        };
+        let invalid_icon = || Error::User {
+            explanation: format!("Dapp icon must be an https URL: '{icon}'"),
+        };
+        if icon.len() > config::MAX_DAPP_METADATA_URL_LENGTH {
+            return Err(invalid_icon());
+        }
+        let url = Url::parse(icon).map_err(|_| invalid_icon())?;
+        if url.scheme() != "https" || url.host_str().is_none() {
+            return Err(invalid_icon());
+        }
+        Ok(Some(url))
+    }
+}
+
+/// The outcome of importing an entry of a dapp manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DappImportResult {
+    /// The position of the entry in the manifest.
+    pub index: u32,
+    /// None if the entry doesn't have an url.
+    pub url: Option<String>,
+    /// Set if the dapp was added or it already existed.
+    pub dapp_id: Option<String>,
+    /// Set if the entry was skipped.
+    pub error: Option<String>,
+}
+
+/// Add the dapps from a JSON manifest.
+/// The manifest is an array of `{"url": "https://...", "name": "...", "icon": "https://..."}`
+/// objects where the name and icon are optional.
+/// Only the dapps are added, no keys or sessions are created, so the user is still asked to
+/// approve the dapp in a profile on first connect.
+/// Malformed entries are skipped and reported in the results, the other entries are still
+/// imported. Dapps that already exist are reported as successes and left unchanged.
+pub fn import_dapps(
+    resources: &dyn CoreResourcesI,
+    manifest_json: &str,
+) -> Result<Vec<DappImportResult>, Error> {
+    let entries: Vec<serde_json::Value> =
+        serde_json::from_str(manifest_json).map_err(|err| Error::User {
+            explanation: format!("The dapp manifest must be a JSON array: {err}"),
+        })?;
+
+    let results = entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let url = entry
+                .get("url")
+                .and_then(|url| url.as_str())
+                .map(Into::into);
+            let res = serde_json::from_value::<DappManifestEntry>(entry)
+                .map_err(|err| Error::User {
+                    explanation: format!("Invalid dapp manifest entry: {err}"),
+                })
+                .and_then(|entry| {
+                    resources
+                        .connection_pool()
+                        .deferred_transaction(|mut tx_conn| {
+                            import_dapp(&mut tx_conn, resources, &entry)
+                        })
+                });
+            let (dapp_id, error) = match res {
+                Ok(dapp_id) => (Some(dapp_id.into()), None),
+                Err(err) => {
+                    log::debug!("Skipping dapp manifest entry {index}: {err:?}");
+                    (None, Some(error_message(err)))
+                }
+            };
+            DappImportResult {
+                index: index as u32,
+                url,
+                dapp_id,
+                error,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+fn import_dapp(
+    tx_conn: &mut DeferredTxConnection,
+    resources: &dyn CoreResourcesI,
+    entry: &DappManifestEntry,
+) -> Result<DeterministicId, Error> {
+    let url = entry.dapp_url()?;
+    let icon_url = entry.icon_url()?;
+    let psl = resources.public_suffix_list();
+    let dapp_id = m::Dapp::deterministic_id_for_url(url.clone(), psl)?;
+    if m::Dapp::fetch_optional(tx_conn.as_mut(), &dapp_id)?.is_some() {
+        return Ok(dapp_id);
+    }
+
+    let dapp_id = m::Dapp::create_if_not_exists(tx_conn, url, psl)?;
+    if entry.name.is_some() || icon_url.is_some() {
+        let params = m::DappMetadataParams::builder()
+            .name(entry.name.clone())
+            .icon_url(icon_url.map(Into::into))
+            .build();
+        m::DappMetadata::upsert(tx_conn, &dapp_id, &params)?;
+    }
+
+    Ok(dapp_id)
+}
+
+/// Only user errors are shown verbatim, the rest might leak internals.
+fn error_message(err: Error) -> String {
+    match err {
+        Error::User { explanation } => explanation,
+        _ => "Failed to add the dapp".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{app_core::tests::TmpCore, CoreError};
+
+    #[test]
+    fn imports_valid_entries_and_reports_invalid_ones() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let manifest = r#"[
+            {"url": "https://app.uniswap.org", "name": "Uniswap"},
+            {"url": "not a url"},
+            {"url": "http://insecure.example.com"},
+            {"name": "Missing url"},
+            {"url": "https://opensea.io/", "icon": "https://opensea.io/icon.png"},
+            {"url": "https://app.uniswap.org/swap"},
+            {"url": "https://example.org", "icon": "javascript:alert(1)"}
+        ]"#;
+
+        let results = tmp.core.import_dapps(manifest.into())?;
+
+        let succeeded: Vec<u32> = results
+            .iter()
+            .filter(|res| res.error.is_none())
+            .map(|res| res.index)
+            .collect();
+        assert_eq!(succeeded, vec![0, 4, 5]);
+        for res in results.iter().filter(|res| res.error.is_some()) {
+            assert!(res.dapp_id.is_none());
+        }
+        assert_eq!(results[1].url.as_deref(), Some("not a url"));
+        assert!(results[3].url.is_none());
+        assert!(results[6].error.as_ref().unwrap().contains("icon"));
+        // Same dapp
+        assert_eq!(results[0].dapp_id, results[5].dapp_id);
+
+        let mut conn = tmp.connection_pool().connection()?;
+        let dapp_id: DeterministicId = results[0].dapp_id.clone().unwrap().parse()?;
+        let metadata = m::DappMetadata::fetch(&mut conn, &dapp_id)?.unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("Uniswap"));
+        let dapp_id: DeterministicId = results[4].dapp_id.clone().unwrap().parse()?;
+        let dapp = m::Dapp::fetch_optional(&mut conn, &dapp_id)?.unwrap();
+        assert_eq!(dapp.identifier, "opensea.io");
+        let metadata = m::DappMetadata::fetch(&mut conn, &dapp_id)?.unwrap();
+        assert_eq!(
+            metadata.icon_url.as_deref(),
+            Some("https://opensea.io/icon.png")
+        );
+        let dapp_id = m::Dapp::deterministic_id_for_url(
+            Url::parse("https://example.org")?,
+            tmp.resources.public_suffix_list(),
+        )?;
+        assert!(m::Dapp::fetch_optional(&mut conn, &dapp_id)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_does_not_connect_dapps() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let manifest = r#"[{"url": "https://app.imported.org"}]"#;
+
+        let results = tmp.core.import_dapps(manifest.into())?;
+
+        let dapp_id: DeterministicId = results[0].dapp_id.clone().unwrap().parse()?;
+        let mut conn = tmp.connection_pool().connection()?;
+        assert!(m::Dapp::list_profile_ids(&mut conn, &dapp_id)?.is_empty());
+        let profile = tmp.first_profile();
+        assert!(!profile
+            .dapps
+            .iter()
+            .any(|dapp| dapp.human_identifier == "imported.org"));
+        let profile_id: DeterministicId = profile.id.parse()?;
+        let params = m::FetchDappSessionParams::builder()
+            .dapp_id(&dapp_id)
+            .profile_id(&profile_id)
+            .build();
+        let session = tmp.connection_pool().deferred_transaction(|mut tx_conn| {
+            m::LocalDappSession::fetch_eth_session(&mut tx_conn, &params)
+        })?;
+        assert!(session.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_non_array_manifest() -> Result<()> {
+        let tmp = TmpCore::new()?;
+
+        let res = tmp.core.import_dapps(r#"{"url": "x"}"#.into());
+
+        assert!(matches!(res, Err(CoreError::User { .. })));
+        Ok(())
+    }
+}
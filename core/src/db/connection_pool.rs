@@ -14,30 +14,55 @@ use diesel::{
     Connection, SqliteConnection,
 };
 
-use crate::{async_runtime as rt, config, Error};
+use crate::{
+    async_runtime as rt, config,
+    db::{
+        db_encryption_key::{key_pragmas, open_and_verify, DbEncryptionKey},
+        integrity::{check_integrity, IntegrityCheck},
+        maintenance::optimize,
+    },
+    Error,
+};
 
 /// A Sqlite connection pool.
 #[derive(Debug)]
 pub struct ConnectionPool {
     pool: Pool<ConnectionManager<SqliteConnection>>,
     db_path: PathBuf,
+    encryption_key: Option<DbEncryptionKey>,
 }
 
 type PooledSqliteConnection = PooledConnection<ConnectionManager<SqliteConnection>>;
 
 impl ConnectionPool {
+    /// Connection pool for an unencrypted DB.
     pub fn new(db_path: &str) -> Result<Self, Error> {
+        Self::new_with_key(db_path, None)
+    }
+
+    /// Connection pool for a DB that is encrypted with SQLCipher if there is a key.
+    /// Fails if the key doesn't match the DB.
+    pub fn new_with_key(
+        db_path: &str,
+        encryption_key: Option<DbEncryptionKey>,
+    ) -> Result<Self, Error> {
+        // The pool retries failing connections until a timeout, so check the key up front for
+        // a fast and clear error.
+        let _ = open_and_verify(db_path, encryption_key.as_ref())?;
+
         let manager = ConnectionManager::new(db_path);
         let pool = Pool::builder()
             .max_size(config::DB_CONNECTION_POOL_SIZE)
             .connection_customizer(Box::new(ConnectionOptions {
                 // Needed to allow concurrent transactions
                 busy_timeout: config::DB_BUSY_TIMEOUT,
+                encryption_key: encryption_key.clone(),
             }))
             .build(manager)?;
         Ok(Self {
             pool,
             db_path: db_path.into(),
+            encryption_key,
         })
     }
 
@@ -45,6 +70,26 @@ impl ConnectionPool {
         self.db_path.as_path()
     }
 
+    /// The SQLCipher key if the DB is encrypted.
+    pub fn encryption_key(&self) -> Option<&DbEncryptionKey> {
+        self.encryption_key.as_ref()
+    }
+
+    /// Run the SQLite integrity check. Fails with `Error::DatabaseCorrupt` if the DB file is
+    /// corrupt.
+    pub fn check_integrity(&self, check: IntegrityCheck) -> Result<(), Error> {
+        let mut conn = self.connection()?;
+        check_integrity(&mut conn, check)
+    }
+
+    /// Reclaim free pages and update the query planner statistics. Returns the number of bytes
+    /// reclaimed. `VACUUM` needs exclusive access, so it fails if other connections are in a
+    /// transaction until the busy timeout.
+    pub fn optimize(&self) -> Result<u64, Error> {
+        let mut conn = self.connection()?;
+        optimize(&mut conn)
+    }
+
     /// Get a Sqlite connection.
     pub fn connection(&self) -> Result<PooledSqliteConnection, Error> {
         let conn = self.pool.get()?;
@@ -87,13 +132,40 @@ impl ConnectionPool {
         F: FnOnce(ExclusiveTxConnection) -> Result<T, Error>,
     {
         let mut connection = self.connection()?;
-        connection.exclusive_transaction::<T, Error, _>(|conn| {
-            let tx_conn = ExclusiveTxConnection(conn);
-            callback(tx_conn)
+        exclusive_transaction(&mut connection, callback)
+    }
+
+    pub async fn exclusive_transaction_async<T, F>(&self, callback: F) -> Result<T, Error>
+    where
+        F: FnOnce(ExclusiveTxConnection) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.pool.clone();
+        rt::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            conn.exclusive_transaction::<T, Error, _>(|conn| {
+                let tx_conn = ExclusiveTxConnection(conn);
+                callback(tx_conn)
+            })
         })
+        .await?
     }
 }
 
+/// Start an exclusive transaction on a connection that isn't pooled, eg. an in-memory DB.
+pub fn exclusive_transaction<T, F>(
+    conn: &mut SqliteConnection,
+    callback: F,
+) -> Result<T, Error>
+where
+    F: FnOnce(ExclusiveTxConnection) -> Result<T, Error>,
+{
+    conn.exclusive_transaction::<T, Error, _>(|conn| {
+        let tx_conn = ExclusiveTxConnection(conn);
+        callback(tx_conn)
+    })
+}
+
 /// A deferred Sqlite transaction. Functions that execute queries should take this as argument
 /// instead of `SqliteConnection` if they should be executed in a deferred transaction.
 pub struct DeferredTxConnection<'a>(&'a mut SqliteConnection);
@@ -138,16 +210,25 @@ impl<'a> Debug for ExclusiveTxConnection<'a> {
 #[derive(Debug)]
 pub struct ConnectionOptions {
     pub busy_timeout: Duration,
+    pub encryption_key: Option<DbEncryptionKey>,
 }
 
 impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
     for ConnectionOptions
 {
     fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        // The key must be set before anything else is executed on the connection.
+        // The key is validated to be hex, so there is no SQLite injection.
+        let key_pragmas = self
+            .encryption_key
+            .as_ref()
+            .map(key_pragmas)
+            .unwrap_or_default();
         // No SQLite injection with u128.
         let timeout: u128 = self.busy_timeout.as_millis();
         let query = &format!(
             "
+            {key_pragmas}
             PRAGMA busy_timeout = {timeout};
             PRAGMA journal_mode = WAL;
             PRAGMA synchronous = NORMAL;
@@ -12,6 +12,7 @@ use url::Url;
 use crate::db::{deterministic_id::DeriveDeterministicId, DeterministicId};
 #[allow(deprecated)]
 use crate::{
+    assets::load_profile_pic,
     config,
     db::{models as m, DeferredTxConnection, ExclusiveTxConnection},
     encryption::{DataEncryptionKey, KeyEncryptionKey, KeyName, Keychain},
@@ -82,6 +83,7 @@ impl Migration for MigrationV0 {
             keychain,
             &name,
             config::DEFAULT_PROFILE_PICTURE_NAME,
+            &load_profile_pic(config::DEFAULT_PROFILE_PICTURE_NAME)?,
         )?;
 
         m::LocalSettings::create(tx_conn.as_mut(), &profile_id)?;
@@ -159,7 +161,8 @@ impl Migration for MigrationV1 {
         keychain: &Keychain,
         psl: &PublicSuffixList,
     ) -> Result<(), Error> {
-        let dapps = m::Dapp::list_all(tx_conn.as_mut())?;
+        let dapps =
+            m::Dapp::list_all(tx_conn.as_mut(), 0, config::UNPAGINATED_DAPP_LIST_LIMIT)?;
         if dapps.is_empty() {
             let active_profile_id =
                 m::LocalSettings::fetch_active_profile_id(tx_conn.as_mut())?;
@@ -243,19 +246,14 @@ impl MigrationV2 {
         // Update profile pictures
         let profile_pictures = m::ProfilePicture::list_all(tx_conn.as_mut())?;
         for mut pp in profile_pictures.into_iter() {
-            #[allow(deprecated)]
-            let deprecated_pp_entity = m::AccountPictureEntity {
-                image_hash: &pp.image_hash,
-            };
-            let deprecated_det_id = deprecated_pp_entity.deterministic_id()?;
-            if pp.deterministic_id == deprecated_det_id {
+            if has_deprecated_picture_id(&pp)? {
+                let deprecated_det_id = pp.deterministic_id.clone();
                 let temp_img_hash = try_random_bytes::<U32>()?.to_vec();
                 let original_img_hash = mem::replace(&mut pp.image_hash, temp_img_hash);
                 let pp_entity = m::ProfilePictureEntity {
                     image_hash: &original_img_hash,
                 };
-                let pp_det_id = pp_entity.deterministic_id()?;
-                let deprecated_det_id = mem::replace(&mut pp.deterministic_id, pp_det_id);
+                pp.deterministic_id = pp_entity.deterministic_id()?;
                 pp.insert(tx_conn.as_mut())?;
 
                 let profiles = m::Profile::list_all(tx_conn.as_mut())?;
@@ -302,11 +300,51 @@ impl Migration for MigrationV2 {
     }
 }
 
+/// Whether the picture was inserted with the deprecated account picture deterministic id.
+fn has_deprecated_picture_id(pp: &m::ProfilePicture) -> Result<bool, Error> {
+    #[allow(deprecated)]
+    let deprecated_pp_entity = m::AccountPictureEntity {
+        image_hash: &pp.image_hash,
+    };
+    Ok(pp.deterministic_id == deprecated_pp_entity.deterministic_id()?)
+}
+
+#[derive(Debug, Clone)]
+struct MigrationV3 {}
+
+impl Migration for MigrationV3 {
+    fn version(&self) -> &'static str {
+        "v3"
+    }
+
+    fn description(&self) -> &'static str {
+        // The deprecated model has no columns of its own, only its deterministic ids differ, so
+        // there is nothing to drop from the schema.
+        "Repair profile pictures that still have deprecated account picture deterministic ids."
+    }
+
+    fn run(
+        &self,
+        tx_conn: &mut DeferredTxConnection,
+        _: &Keychain,
+        _: &PublicSuffixList,
+    ) -> Result<(), Error> {
+        // Pictures with deprecated ids can remain if they were added after v2 ran, eg. from an
+        // old backup. The v2 picture migration only touches those, so it's safe to run it again.
+        MigrationV2::migrate_profile_pictures(tx_conn)
+    }
+
+    fn rollback(&self, _keychain: &Keychain) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
 lazy_static! {
     static ref MIGRATIONS: Vec<Box<dyn Migration>> = vec![
         Box::new(MigrationV0 {}),
         Box::new(MigrationV1 {}),
-        Box::new(MigrationV2 {})
+        Box::new(MigrationV2 {}),
+        Box::new(MigrationV3 {})
     ];
 }
 
@@ -539,4 +577,43 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn v3_repairs_profile_pictures() -> Result<()> {
+        let tmp = TmpCoreDir::new()?;
+        let connection_pool = ConnectionPool::new(&tmp.db_file_path)?;
+        let keychain = Keychain::new();
+        let psl = PublicSuffixList::new()?;
+
+        // Old-style rows created before profiles replaced accounts.
+        connection_pool.exclusive_transaction(|mut tx_conn| {
+            run_migrations(&mut tx_conn)?;
+            let mut tx_conn: DeferredTxConnection = tx_conn.into();
+            run_v0_migration_for_accounts(&mut tx_conn, &keychain)
+        })?;
+
+        let deprecated_picture = {
+            let mut conn = connection_pool.connection()?;
+            let pictures = m::ProfilePicture::list_all(&mut conn)?;
+            assert_eq!(pictures.len(), 1);
+            pictures.into_iter().next().unwrap()
+        };
+        assert!(has_deprecated_picture_id(&deprecated_picture)?);
+
+        let v3 = MigrationV3 {};
+        connection_pool
+            .deferred_transaction(|mut tx_conn| v3.run(&mut tx_conn, &keychain, &psl))?;
+
+        let mut conn = connection_pool.connection()?;
+        let pictures = m::ProfilePicture::list_all(&mut conn)?;
+        assert_eq!(pictures.len(), 1);
+        let picture = pictures.into_iter().next().unwrap();
+        assert!(!has_deprecated_picture_id(&picture)?);
+        assert_eq!(picture.image_hash, deprecated_picture.image_hash);
+        for profile in m::Profile::list_all(&mut conn)? {
+            assert_eq!(profile.picture_id, picture.deterministic_id);
+        }
+
+        Ok(())
+    }
 }
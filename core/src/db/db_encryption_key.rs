@@ -0,0 +1,344 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    fmt::{Debug, Formatter},
+    fs::File,
+    io::Read,
+    path::Path,
+    str::FromStr,
+};
+
+use diesel::{connection::SimpleConnection, Connection, SqliteConnection};
+use zeroize::Zeroizing;
+
+use crate::{
+    db::integrity::map_corruption_error, utils::path_to_string, CoreError, Error,
+};
+
+/// Raw SQLCipher keys are 256 bits.
+const DB_ENCRYPTION_KEY_HEX_LEN: usize = 64;
+
+/// Every plaintext SQLite database file starts with this header. SQLCipher encrypts the header
+/// too, so an encrypted database file starts with random bytes.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// SQLite error for a file that doesn't look like a database. SQLCipher returns it both for a
+/// corrupt database and for a wrong key.
+const NOT_A_DATABASE_MESSAGE: &str = "file is not a database";
+
+/// Raw key for SQLCipher database encryption at rest. Hex encoded as that's what the SQLCipher
+/// raw key pragma expects. Validated to be hex, so it's safe to interpolate into pragmas.
+#[derive(Clone, PartialEq, Eq)]
+pub struct DbEncryptionKey(Zeroizing<String>);
+
+impl DbEncryptionKey {
+    /// The value for the `key` and `rekey` pragmas and the `KEY` clause of `ATTACH`.
+    fn sql_value(&self) -> String {
+        format!("\"x'{}'\"", self.0.as_str())
+    }
+}
+
+impl FromStr for DbEncryptionKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == DB_ENCRYPTION_KEY_HEX_LEN
+            && s.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            Ok(Self(Zeroizing::new(s.to_string())))
+        } else {
+            Err(Error::Fatal {
+                error: format!(
+                    "DB encryption key must be {DB_ENCRYPTION_KEY_HEX_LEN} hex characters"
+                ),
+            })
+        }
+    }
+}
+
+impl Debug for DbEncryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DbEncryptionKey")
+            .field(&"[REDACTED]")
+            .finish()
+    }
+}
+
+/// Pragmas that must be executed first on a new connection to an encrypted DB.
+/// The compatibility setting pins the cipher parameters, so that upgrading SQLCipher doesn't
+/// make existing databases unreadable.
+pub(in crate::db) fn key_pragmas(key: &DbEncryptionKey) -> String {
+    format!(
+        "
+        PRAGMA key = {};
+        PRAGMA cipher_compatibility = 4;
+        ",
+        key.sql_value()
+    )
+}
+
+/// Open a connection to the DB and make sure the key matches.
+/// Reading an encrypted DB without the right key fails with "file is not a database", so the
+/// file header is used to tell a key problem from a corrupt file.
+pub(in crate::db) fn open_and_verify(
+    db_path: &str,
+    key: Option<&DbEncryptionKey>,
+) -> Result<SqliteConnection, Error> {
+    ensure_sqlcipher(key)?;
+    let mut conn = SqliteConnection::establish(db_path)?;
+    if let Some(key) = key {
+        conn.batch_execute(&key_pragmas(key))?;
+    }
+    conn.batch_execute("SELECT count(*) FROM sqlite_master;")
+        .map_err(|err| match &err {
+            diesel::result::Error::DatabaseError(_, info)
+                if info.message().contains(NOT_A_DATABASE_MESSAGE) =>
+            {
+                map_not_a_database_error(db_path, key.is_some(), info.message())
+            }
+            _ => map_corruption_error(err),
+        })?;
+    Ok(conn)
+}
+
+/// Keys can only be used if SQLCipher is bundled.
+fn ensure_sqlcipher(key: Option<&DbEncryptionKey>) -> Result<(), Error> {
+    if key.is_some() && !cfg!(feature = "sqlcipher") {
+        Err(Error::Fatal {
+            error: "Database encryption key provided, but the `sqlcipher` feature is \
+                disabled."
+                .into(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn map_not_a_database_error(db_path: &str, has_key: bool, message: &str) -> Error {
+    log::debug!("Failed to open DB with error: {message}");
+    let corrupt = || Error::DatabaseCorrupt {
+        error: message.into(),
+    };
+    let is_plaintext = match read_header(db_path) {
+        Ok(Some(header)) => header == SQLITE_HEADER.as_slice(),
+        // Too short to be either a plaintext or an encrypted database.
+        Ok(None) => return corrupt(),
+        Err(err) => return err,
+    };
+    match (is_plaintext, has_key) {
+        (true, false) => corrupt(),
+        (true, true) => Error::Fatal {
+            error: "Failed to open the database. The database isn't encrypted, but an \
+                encryption key was provided."
+                .into(),
+        },
+        (false, false) => Error::Fatal {
+            error: "Failed to open the database. The database is encrypted, but the \
+                encryption key is missing."
+                .into(),
+        },
+        (false, true) => Error::Fatal {
+            error: "Failed to open the database. The database encryption key doesn't \
+                match."
+                .into(),
+        },
+    }
+}
+
+/// Read the first bytes of the database file. Returns None if the file is shorter than the
+/// header.
+fn read_header(db_path: &str) -> Result<Option<Vec<u8>>, Error> {
+    let mut header = vec![0; SQLITE_HEADER.len()];
+    let mut file = File::open(db_path).map_err(|err| Error::Fatal {
+        error: format!("Failed to open database file with error: {err}"),
+    })?;
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(Some(header)),
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(Error::Fatal {
+            error: format!("Failed to read database file header with error: {err}"),
+        }),
+    }
+}
+
+/// Change the encryption key of an encrypted database. Keys are hex encoded 256-bit raw keys.
+/// The database must not be open, so this should be called before `AppCore` is created.
+pub fn rekey_database(
+    db_file_path: String,
+    old_key: String,
+    new_key: String,
+) -> Result<(), CoreError> {
+    let old_key: DbEncryptionKey = old_key.parse()?;
+    let new_key: DbEncryptionKey = new_key.parse()?;
+    rekey_database_inner(&db_file_path, &old_key, &new_key)?;
+    Ok(())
+}
+
+fn rekey_database_inner(
+    db_path: &str,
+    old_key: &DbEncryptionKey,
+    new_key: &DbEncryptionKey,
+) -> Result<(), Error> {
+    let mut conn = open_and_verify(db_path, Some(old_key))?;
+    // Rekeying rewrites every page which is only reliable in rollback journal mode.
+    conn.batch_execute(&format!(
+        "
+        PRAGMA journal_mode = DELETE;
+        PRAGMA rekey = {};
+        PRAGMA journal_mode = WAL;
+        ",
+        new_key.sql_value()
+    ))?;
+    Ok(())
+}
+
+/// Copy the database to a new file with a different key. None means plaintext.
+/// The target file must not exist.
+pub(crate) fn export_database(
+    from_path: &Path,
+    from_key: Option<&DbEncryptionKey>,
+    to_path: &Path,
+    to_key: Option<&DbEncryptionKey>,
+) -> Result<(), Error> {
+    let from_path = path_to_string(from_path)?;
+    let to_path = path_to_string(to_path)?;
+    ensure_sqlcipher(to_key)?;
+    let mut conn = open_and_verify(&from_path, from_key)?;
+    let to_key = to_key
+        .map(|key| key.sql_value())
+        .unwrap_or_else(|| "''".into());
+    // Single quotes are the only character that needs escaping in SQL string literals.
+    let to_path = to_path.replace('\'', "''");
+    conn.batch_execute(&format!(
+        "
+        ATTACH DATABASE '{to_path}' AS exported KEY {to_key};
+        SELECT sqlcipher_export('exported');
+        DETACH DATABASE exported;
+        "
+    ))?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlcipher"))]
+mod tests {
+    use std::fs;
+
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::db::ConnectionPool;
+
+    fn key(byte: &str) -> Result<DbEncryptionKey> {
+        Ok(byte.repeat(32).parse()?)
+    }
+
+    fn create_db(db_path: &str, key: Option<DbEncryptionKey>) -> Result<()> {
+        let pool = ConnectionPool::new_with_key(db_path, key)?;
+        pool.connection()?
+            .batch_execute("CREATE TABLE foo (id INTEGER PRIMARY KEY);")?;
+        Ok(())
+    }
+
+    fn can_open(db_path: &str, key: Option<DbEncryptionKey>) -> bool {
+        ConnectionPool::new_with_key(db_path, key).is_ok()
+    }
+
+    #[test]
+    fn encrypted_db_requires_key() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = path_to_string(&tmp_dir.path().join("encrypted.sqlite3"))?;
+        create_db(&db_path, Some(key("ab")?))?;
+
+        assert!(can_open(&db_path, Some(key("ab")?)));
+        assert!(!can_open(&db_path, None));
+        assert!(!can_open(&db_path, Some(key("cd")?)));
+        Ok(())
+    }
+
+    #[test]
+    fn unencrypted_db_opens_without_key() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = path_to_string(&tmp_dir.path().join("plain.sqlite3"))?;
+        create_db(&db_path, None)?;
+
+        assert!(can_open(&db_path, None));
+        assert!(!can_open(&db_path, Some(key("ab")?)));
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_key_is_not_corruption() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = path_to_string(&tmp_dir.path().join("encrypted.sqlite3"))?;
+        create_db(&db_path, Some(key("ab")?))?;
+
+        let res = open_and_verify(&db_path, Some(&key("cd")?));
+
+        assert!(matches!(res, Err(Error::Fatal { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn garbled_plaintext_db_is_corrupt() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = path_to_string(&tmp_dir.path().join("plain.sqlite3"))?;
+        create_db(&db_path, None)?;
+        let mut bytes = fs::read(&db_path)?;
+        let header_len = SQLITE_HEADER.len();
+        bytes[header_len..].iter_mut().for_each(|byte| *byte = 0xff);
+        fs::write(&db_path, bytes)?;
+
+        let res = open_and_verify(&db_path, None);
+
+        assert!(matches!(res, Err(Error::DatabaseCorrupt { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn can_rekey() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = path_to_string(&tmp_dir.path().join("encrypted.sqlite3"))?;
+        create_db(&db_path, Some(key("ab")?))?;
+
+        rekey_database(db_path.clone(), "ab".repeat(32), "cd".repeat(32))?;
+
+        assert!(can_open(&db_path, Some(key("cd")?)));
+        assert!(!can_open(&db_path, Some(key("ab")?)));
+        Ok(())
+    }
+
+    #[test]
+    fn export_round_trips() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let encrypted = tmp_dir.path().join("encrypted.sqlite3");
+        let plain = tmp_dir.path().join("plain.sqlite3");
+        create_db(&path_to_string(&encrypted)?, Some(key("ab")?))?;
+
+        export_database(&encrypted, Some(&key("ab")?), &plain, None)?;
+
+        let plain = path_to_string(&plain)?;
+        assert!(can_open(&plain, None));
+        let pool = ConnectionPool::new(&plain)?;
+        pool.connection()?.batch_execute("SELECT id FROM foo;")?;
+        Ok(())
+    }
+
+    #[test]
+    fn accepts_hex_keys_only() {
+        assert!("ab".repeat(32).parse::<DbEncryptionKey>().is_ok());
+        assert!("ab".repeat(31).parse::<DbEncryptionKey>().is_err());
+        assert!(format!("{}'", "a".repeat(63))
+            .parse::<DbEncryptionKey>()
+            .is_err());
+    }
+
+    #[test]
+    fn debug_is_redacted() -> Result<()> {
+        let key: DbEncryptionKey = "ab".repeat(32).parse()?;
+        assert!(!format!("{key:?}").contains("abab"));
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use diesel::{prelude::*, SqliteConnection};
+
+use crate::Error;
+
+#[derive(QueryableByName)]
+struct DbSize {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    size: i64,
+}
+
+#[derive(QueryableByName)]
+struct TableSize {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    table_name: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    size: i64,
+}
+
+/// The size of the DB as seen by the connection. Includes changes in the WAL that haven't been
+/// checkpointed yet and free pages which are copied to the backup as well.
+pub fn db_size_bytes(conn: &mut SqliteConnection) -> Result<u64, Error> {
+    let db_size: DbSize = diesel::sql_query(
+        "SELECT page_count * page_size AS size FROM pragma_page_count(), pragma_page_size()",
+    )
+    .get_result(conn)?;
+    to_size(db_size.size)
+}
+
+/// The size of the pages used by each table including its indices by table name.
+/// Free pages aren't included.
+pub fn table_sizes_bytes(
+    conn: &mut SqliteConnection,
+) -> Result<HashMap<String, u64>, Error> {
+    // The `dbstat` virtual table lists b-trees by name, so indices are mapped to their table.
+    let table_sizes: Vec<TableSize> = diesel::sql_query(
+        "SELECT s.tbl_name AS table_name, SUM(d.pgsize) AS size \
+        FROM dbstat AS d INNER JOIN sqlite_master AS s ON s.name = d.name \
+        GROUP BY s.tbl_name",
+    )
+    .load(conn)?;
+    table_sizes
+        .into_iter()
+        .map(|table_size| Ok((table_size.table_name, to_size(table_size.size)?)))
+        .collect()
+}
+
+fn to_size(size: i64) -> Result<u64, Error> {
+    u64::try_from(size).map_err(|_| Error::Fatal {
+        error: "DB size is negative".into(),
+    })
+}
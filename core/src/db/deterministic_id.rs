@@ -166,12 +166,16 @@ mod entity_name {
         AsymmetricKey,
         Chain,
         Dapp,
+        DappFavicon,
+        DappStorage,
+        DappTag,
         DataEncryptionKey,
         DataMigration,
         Profile,
         ProfilePicture,
         Token,
         TokenToAddress,
+        Transaction,
         #[cfg(test)]
         Mock,
     }
@@ -0,0 +1,203 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_int,
+    ptr,
+};
+
+use diesel::{connection::SimpleConnection, Connection, SqliteConnection};
+use libsqlite3_sys as ffi;
+
+use crate::{utils::new_uuid, Error};
+
+const MAIN_SCHEMA: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") };
+
+/// Offset of the page size in the SQLite file header.
+const PAGE_SIZE_OFFSET: usize = 16;
+
+/// Offsets of the file format write and read versions in the SQLite file header.
+const FILE_FORMAT_OFFSETS: [usize; 2] = [18, 19];
+
+/// File format version for rollback journal mode. WAL mode databases must be switched to it,
+/// because the in-memory VFS can't open the WAL file.
+const LEGACY_FILE_FORMAT: u8 = 1;
+
+/// Open a copy of a serialized plaintext SQLite DB, eg. a decrypted backup, in memory.
+/// Nothing is written to disk, and changes are discarded when the connection is closed.
+///
+/// Diesel can't deserialize a DB, so the bytes are deserialized on a raw SQLite connection
+/// and copied with the backup API to a named in-memory DB that the Diesel connection opens.
+pub fn open_in_memory(db_bytes: &[u8]) -> Result<SqliteConnection, Error> {
+    let mut db_bytes = db_bytes.to_vec();
+    let page_size = page_size(&db_bytes)?;
+    for offset in FILE_FORMAT_OFFSETS {
+        db_bytes[offset] = LEGACY_FILE_FORMAT;
+    }
+
+    // A memdb name starting with a slash is shared by the connections of the process until
+    // the last one is closed. The Diesel connection is opened first to keep it alive.
+    let uri = format!("file:/sealvault-{}?vfs=memdb", new_uuid());
+    let mut conn = SqliteConnection::establish(&uri)?;
+    // The backup API can't change the page size of an in-memory DB. No SQL injection with u32.
+    conn.batch_execute(&format!(
+        "
+        PRAGMA page_size = {page_size};
+        PRAGMA foreign_keys = ON;
+        "
+    ))?;
+
+    let source = RawConnection::open(":memory:")?;
+    source.deserialize(db_bytes)?;
+    let target = RawConnection::open(&uri)?;
+    source.copy_to(&target)?;
+
+    Ok(conn)
+}
+
+fn page_size(db_bytes: &[u8]) -> Result<u32, Error> {
+    let page_size = db_bytes
+        .get(PAGE_SIZE_OFFSET..PAGE_SIZE_OFFSET + 2)
+        .ok_or_else(|| Error::Fatal {
+            error: "DB is too short to have a header".into(),
+        })?;
+    // 1 means 65536 as it doesn't fit into two bytes.
+    match u16::from_be_bytes([page_size[0], page_size[1]]) {
+        1 => Ok(65536),
+        page_size => Ok(page_size.into()),
+    }
+}
+
+/// SQLite connection handle for the APIs that Diesel doesn't expose. Closed on drop.
+struct RawConnection(*mut ffi::sqlite3);
+
+impl RawConnection {
+    fn open(uri: &str) -> Result<Self, Error> {
+        let uri = CString::new(uri).map_err(|err| Error::Fatal {
+            error: err.to_string(),
+        })?;
+        let mut handle = ptr::null_mut();
+        let flags =
+            ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE | ffi::SQLITE_OPEN_URI;
+        let rc = unsafe {
+            ffi::sqlite3_open_v2(uri.as_ptr(), &mut handle, flags, ptr::null())
+        };
+        // The handle must be closed even if opening failed.
+        let conn = Self(handle);
+        conn.check(rc)?;
+        Ok(conn)
+    }
+
+    /// Replace the main DB of the connection with the bytes.
+    fn deserialize(&self, db_bytes: Vec<u8>) -> Result<(), Error> {
+        let size: i64 = db_bytes.len().try_into().map_err(|_| Error::Fatal {
+            error: "DB is too large to deserialize".into(),
+        })?;
+        // SQLite takes ownership of the buffer, so it must be allocated by SQLite.
+        let buffer = unsafe { ffi::sqlite3_malloc64(size as u64) } as *mut u8;
+        if buffer.is_null() {
+            return Err(Error::Fatal {
+                error: "Failed to allocate memory to deserialize DB".into(),
+            });
+        }
+        unsafe { ptr::copy_nonoverlapping(db_bytes.as_ptr(), buffer, db_bytes.len()) };
+        // SQLite frees the buffer with `FREEONCLOSE` even if deserializing fails.
+        let rc = unsafe {
+            ffi::sqlite3_deserialize(
+                self.0,
+                MAIN_SCHEMA.as_ptr(),
+                buffer,
+                size,
+                size,
+                (ffi::SQLITE_DESERIALIZE_FREEONCLOSE | ffi::SQLITE_DESERIALIZE_READONLY)
+                    as _,
+            )
+        };
+        self.check(rc)
+    }
+
+    /// Copy the main DB of the connection to the main DB of the target connection.
+    fn copy_to(&self, target: &RawConnection) -> Result<(), Error> {
+        let backup = unsafe {
+            ffi::sqlite3_backup_init(
+                target.0,
+                MAIN_SCHEMA.as_ptr(),
+                self.0,
+                MAIN_SCHEMA.as_ptr(),
+            )
+        };
+        if backup.is_null() {
+            return Err(target.error("Failed to start copying DB"));
+        }
+        let step_rc = unsafe { ffi::sqlite3_backup_step(backup, -1) };
+        let finish_rc = unsafe { ffi::sqlite3_backup_finish(backup) };
+        if step_rc != ffi::SQLITE_DONE {
+            return Err(target.error("Failed to copy DB"));
+        }
+        target.check(finish_rc)
+    }
+
+    fn check(&self, rc: c_int) -> Result<(), Error> {
+        if rc == ffi::SQLITE_OK {
+            Ok(())
+        } else {
+            Err(self.error("SQLite call failed"))
+        }
+    }
+
+    fn error(&self, context: &str) -> Error {
+        let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.0)) };
+        Error::Fatal {
+            error: format!("{context} with error: '{}'", message.to_string_lossy()),
+        }
+    }
+}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_close_v2(self.0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use anyhow::Result;
+    use diesel::{sql_types::Integer, QueryableByName, RunQueryDsl};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{db::ConnectionPool, utils::path_to_string};
+
+    #[derive(QueryableByName)]
+    struct Count {
+        #[diesel(sql_type = Integer)]
+        count: i32,
+    }
+
+    #[test]
+    fn opens_copy_in_memory() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = tmp_dir.path().join("db.sqlite3");
+        {
+            let pool = ConnectionPool::new(&path_to_string(&db_path)?)?;
+            pool.connection()?.batch_execute(
+                "CREATE TABLE foo (id INTEGER PRIMARY KEY); INSERT INTO foo VALUES (1);",
+            )?;
+        }
+        let db_bytes = fs::read(&db_path)?;
+
+        let mut conn = open_in_memory(&db_bytes)?;
+        conn.batch_execute("INSERT INTO foo VALUES (2);")?;
+
+        let count: Count = diesel::sql_query("SELECT count(*) AS count FROM foo")
+            .get_result(&mut conn)?;
+        assert_eq!(count.count, 2);
+        // Writes go to memory.
+        assert_eq!(fs::read(&db_path)?, db_bytes);
+        Ok(())
+    }
+}
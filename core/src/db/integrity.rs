@@ -0,0 +1,128 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use diesel::{prelude::*, SqliteConnection};
+
+use crate::Error;
+
+/// The number of problems to report from the integrity check.
+const MAX_REPORTED_PROBLEMS: usize = 5;
+
+/// SQLite error messages that indicate a corrupt database file. "file is not a database" is
+/// left out, because it's also the error for an encrypted database with the wrong key.
+const CORRUPTION_MESSAGES: &[&str] = &["database disk image is malformed"];
+
+/// How thorough the integrity check is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityCheck {
+    /// `PRAGMA quick_check` is O(N) in the size of the database, because it skips verifying
+    /// that indices match their tables. Fast enough to run on startup.
+    Quick,
+    /// `PRAGMA integrity_check` verifies indices as well, which is O(N log N). For running on
+    /// demand.
+    Full,
+}
+
+impl IntegrityCheck {
+    fn query(&self) -> &'static str {
+        // The pragmas name their result column after themselves, so it's aliased.
+        match self {
+            Self::Quick => "SELECT quick_check AS result FROM pragma_quick_check;",
+            Self::Full => "SELECT integrity_check AS result FROM pragma_integrity_check;",
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct IntegrityCheckRow {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    result: String,
+}
+
+/// Run the SQLite integrity check. Returns `Error::DatabaseCorrupt` with the first problems if
+/// the database file is corrupt.
+pub fn check_integrity(
+    conn: &mut SqliteConnection,
+    check: IntegrityCheck,
+) -> Result<(), Error> {
+    let rows: Vec<IntegrityCheckRow> = diesel::sql_query(check.query())
+        .load(conn)
+        .map_err(map_corruption_error)?;
+    let problems: Vec<String> = rows
+        .into_iter()
+        .map(|row| row.result)
+        .filter(|result| result != "ok")
+        .take(MAX_REPORTED_PROBLEMS)
+        .collect();
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::DatabaseCorrupt {
+            error: problems.join("\n"),
+        })
+    }
+}
+
+/// Map errors from reading a corrupt database file to `Error::DatabaseCorrupt`.
+pub fn map_corruption_error(err: diesel::result::Error) -> Error {
+    match &err {
+        diesel::result::Error::DatabaseError(_, info)
+            if is_corruption_message(info.message()) =>
+        {
+            Error::DatabaseCorrupt {
+                error: info.message().into(),
+            }
+        }
+        _ => err.into(),
+    }
+}
+
+fn is_corruption_message(message: &str) -> bool {
+    CORRUPTION_MESSAGES
+        .iter()
+        .any(|corruption_message| message.contains(corruption_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+
+    use anyhow::Result;
+    use diesel::connection::SimpleConnection;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{db::ConnectionPool, utils::path_to_string};
+
+    #[test]
+    fn detects_truncated_db_file() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = tmp_dir.path().join("truncated.sqlite3");
+        let db_path_str = path_to_string(&db_path)?;
+        {
+            let pool = ConnectionPool::new(&db_path_str)?;
+            let mut conn = pool.connection()?;
+            conn.batch_execute(
+                "
+                CREATE TABLE foo (id INTEGER PRIMARY KEY, data BLOB NOT NULL);
+                WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 500)
+                INSERT INTO foo (data) SELECT randomblob(1000) FROM seq;
+                PRAGMA wal_checkpoint(TRUNCATE);
+                ",
+            )?;
+            check_integrity(&mut conn, IntegrityCheck::Full)?;
+        }
+
+        let file = OpenOptions::new().write(true).open(&db_path)?;
+        let len = file.metadata()?.len();
+        file.set_len(len / 2)?;
+
+        for check in [IntegrityCheck::Quick, IntegrityCheck::Full] {
+            let res = ConnectionPool::new(&db_path_str)
+                .and_then(|pool| pool.check_integrity(check));
+            assert!(matches!(res, Err(Error::DatabaseCorrupt { .. })));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use diesel::{
+    connection::{SimpleConnection, TransactionManager},
+    prelude::*,
+    SqliteConnection,
+};
+
+use crate::{config, db::db_size_bytes, Error};
+
+/// The `auto_vacuum` pragma value for incremental vacuum.
+const AUTO_VACUUM_INCREMENTAL: i32 = 2;
+
+#[derive(QueryableByName)]
+struct AutoVacuum {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    auto_vacuum: i32,
+}
+
+#[derive(QueryableByName)]
+struct FreelistCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    freelist_count: i64,
+}
+
+/// Reclaim free pages and update the query planner statistics. Returns the number of bytes
+/// reclaimed.
+/// The first run rebuilds the DB with a full `VACUUM` to enable incremental vacuum. Later runs
+/// reclaim free pages in small steps, so that the write lock isn't held for long.
+/// Fails if the connection is in a transaction, because `VACUUM` can't run in a transaction.
+pub fn optimize(conn: &mut SqliteConnection) -> Result<u64, Error> {
+    let transaction_depth =
+        <SqliteConnection as Connection>::TransactionManager::transaction_manager_status_mut(
+            conn,
+        )
+        .transaction_depth()?;
+    if transaction_depth.is_some() {
+        return Err(Error::Fatal {
+            error: "Can't optimize the database inside a transaction".into(),
+        });
+    }
+
+    let size_before = db_size_bytes(conn)?;
+
+    let auto_vacuum: AutoVacuum =
+        diesel::sql_query("PRAGMA auto_vacuum;").get_result(conn)?;
+    if auto_vacuum.auto_vacuum == AUTO_VACUUM_INCREMENTAL {
+        let step = config::DB_INCREMENTAL_VACUUM_PAGES;
+        let free_pages = freelist_count(conn)?;
+        for _ in 0..(free_pages + step - 1) / step {
+            conn.batch_execute(&format!("PRAGMA incremental_vacuum({step});"))?;
+        }
+    } else {
+        // Changing the auto vacuum mode of an existing DB only takes effect after a vacuum.
+        conn.batch_execute(
+            "
+            PRAGMA auto_vacuum = INCREMENTAL;
+            VACUUM;
+            ",
+        )?;
+    }
+
+    // Shrink the WAL file as well.
+    conn.batch_execute(
+        "
+        ANALYZE;
+        PRAGMA optimize;
+        PRAGMA wal_checkpoint(TRUNCATE);
+        ",
+    )?;
+
+    let size_after = db_size_bytes(conn)?;
+    Ok(size_before.saturating_sub(size_after))
+}
+
+fn freelist_count(conn: &mut SqliteConnection) -> Result<u64, Error> {
+    let count: FreelistCount =
+        diesel::sql_query("PRAGMA freelist_count;").get_result(conn)?;
+    u64::try_from(count.freelist_count).map_err(|_| Error::Fatal {
+        error: "Freelist count is negative".into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{db::ConnectionPool, utils::path_to_string};
+
+    fn insert_rows(conn: &mut SqliteConnection) -> Result<()> {
+        conn.batch_execute(
+            "
+            WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 500)
+            INSERT INTO foo (data) SELECT randomblob(1000) FROM seq;
+            PRAGMA wal_checkpoint(TRUNCATE);
+            ",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn reclaims_deleted_rows() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = tmp_dir.path().join("optimize.sqlite3");
+        let pool = ConnectionPool::new(&path_to_string(&db_path)?)?;
+        let mut conn = pool.connection()?;
+        conn.batch_execute(
+            "CREATE TABLE foo (id INTEGER PRIMARY KEY, data BLOB NOT NULL);",
+        )?;
+
+        // The first run switches to incremental vacuum with a full vacuum.
+        for _ in 0..2 {
+            insert_rows(&mut conn)?;
+            conn.batch_execute("DELETE FROM foo; PRAGMA wal_checkpoint(TRUNCATE);")?;
+            let file_size_before = fs::metadata(&db_path)?.len();
+
+            let reclaimed = optimize(&mut conn)?;
+
+            let file_size_after = fs::metadata(&db_path)?.len();
+            assert!(reclaimed > 0);
+            assert!(file_size_after < file_size_before);
+        }
+
+        let auto_vacuum: AutoVacuum =
+            diesel::sql_query("PRAGMA auto_vacuum;").get_result(&mut conn)?;
+        assert_eq!(auto_vacuum.auto_vacuum, AUTO_VACUUM_INCREMENTAL);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_inside_transaction() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let db_path = tmp_dir.path().join("optimize.sqlite3");
+        let pool = ConnectionPool::new(&path_to_string(&db_path)?)?;
+
+        let res = pool.deferred_transaction(|mut tx_conn| optimize(tx_conn.as_mut()));
+
+        assert!(matches!(res, Err(Error::Fatal { .. })));
+        Ok(())
+    }
+}
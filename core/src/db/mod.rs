@@ -4,13 +4,25 @@
 
 mod connection_pool;
 pub mod data_migrations;
+mod db_encryption_key;
+mod db_size;
 mod deterministic_id;
+mod in_memory;
+mod integrity;
 mod json_value;
+mod maintenance;
 pub mod models;
 mod schema;
 pub mod schema_migrations;
 mod url_value;
 
-pub use connection_pool::{ConnectionPool, DeferredTxConnection, ExclusiveTxConnection};
+pub use connection_pool::{
+    exclusive_transaction, ConnectionPool, DeferredTxConnection, ExclusiveTxConnection,
+};
+pub(crate) use db_encryption_key::export_database;
+pub use db_encryption_key::{rekey_database, DbEncryptionKey};
+pub use db_size::{db_size_bytes, table_sizes_bytes};
 pub use deterministic_id::DeterministicId;
+pub use in_memory::open_in_memory;
+pub use integrity::IntegrityCheck;
 pub use json_value::JsonValue;
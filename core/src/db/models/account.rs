@@ -47,7 +47,7 @@ impl Account {
         keychain: &Keychain,
         params: &AccountParams,
     ) -> Result<DeterministicId, Error> {
-        let picture_id = m::AccountPicture::insert_bundled(
+        let picture_id = m::AccountPictureEntity::insert_bundled(
             tx_conn.as_mut(),
             params.bundled_picture_name,
         )?;
@@ -2,8 +2,6 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::fmt::Debug;
-
 use diesel::{prelude::*, SqliteConnection};
 use generic_array::{typenum::U1, GenericArray};
 
@@ -17,33 +15,18 @@ use crate::{
     Error,
 };
 
-/// Deprecated in favor of ProfilePicture
+/// Deprecated in favor of ProfilePicture.
+/// Only kept to derive the deterministic ids of pictures created before the rename in data
+/// migrations, as old backups may still contain them.
 #[deprecated]
-#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
-#[diesel(primary_key(deterministic_id))]
+#[derive(Insertable)]
 #[diesel(table_name = profile_pictures)]
-pub struct AccountPicture {
-    pub deterministic_id: DeterministicId,
-    pub image_name: Option<String>,
-    pub image_hash: Vec<u8>,
-    pub image: Vec<u8>,
-    pub created_at: String,
-    pub updated_at: Option<String>,
+pub struct AccountPictureEntity<'a> {
+    pub(crate) image_hash: &'a [u8],
 }
 
-impl AccountPicture {
-    pub fn fetch_image(conn: &mut SqliteConnection, id: &str) -> Result<Vec<u8>, Error> {
-        use profile_pictures::dsl as ap;
-
-        let image = profile_pictures::table
-            .filter(ap::deterministic_id.eq(id))
-            .select(ap::image)
-            .first(conn)?;
-
-        Ok(image)
-    }
-
-    /// Insert a bundled account picture into the database and return its deterministic id.
+impl<'a> AccountPictureEntity<'a> {
+    /// Insert a bundled picture with the deprecated deterministic id and return the id.
     pub fn insert_bundled(
         conn: &mut SqliteConnection,
         image_name: &str,
@@ -55,17 +38,7 @@ impl AccountPicture {
         };
         entity.create(conn, &image, Some(image_name))
     }
-}
 
-/// Deprecated in favor of ProfilePicture
-#[deprecated]
-#[derive(Insertable)]
-#[diesel(table_name = profile_pictures)]
-pub struct AccountPictureEntity<'a> {
-    pub(crate) image_hash: &'a [u8],
-}
-
-impl<'a> AccountPictureEntity<'a> {
     /// Insert an account picture and return its deterministic id.
     fn create(
         &self,
@@ -29,6 +29,7 @@ use crate::{
     },
     encryption::{KeyEncryptionKey, KeyName, Keychain},
     protocols::{eth, BlockchainProtocol},
+    rng::{RngI, SystemRng},
     utils::rfc3339_timestamp,
     Error,
 };
@@ -147,7 +148,7 @@ impl Address {
             &sk_kek,
         )?;
 
-        let signing_key = eth::EthereumAsymmetricKey::random()?;
+        let signing_key = eth::EthereumAsymmetricKey::random_with(params.rng)?;
         let encrypted_signing_key = signing_key.to_encrypted_der(&sk_dek)?;
         let public_key = signing_key.public_key_der()?;
 
@@ -478,6 +479,8 @@ impl Address {
             .inner_join(chains::table.on(a::chain_id.eq(c::deterministic_id)))
             .filter(a::deterministic_id.eq(address_id))
             .filter(c::protocol.eq(BlockchainProtocol::Ethereum))
+            // Keys of other curves can't sign for the chain.
+            .filter(ak::elliptic_curve.eq(BlockchainProtocol::Ethereum.elliptic_curve()))
             .select((dek::name, ak::encrypted_der, c::protocol_data))
             .first::<(String, EncryptionOutput, JsonValue)>(tx_conn.as_mut())?;
 
@@ -609,6 +612,9 @@ pub struct CreateEthAddressParams<'a> {
     pub dapp_id: Option<&'a DeterministicId>,
     #[builder(default = false)]
     pub is_profile_wallet: bool,
+    /// The random generator for the signing key.
+    #[builder(default = &SystemRng)]
+    pub rng: &'a dyn RngI,
 }
 
 #[derive(
@@ -713,4 +719,28 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn signing_key_has_protocol_curve() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let wallet = tmp_core.first_profile_wallet();
+        let wallet_id: AddressId = wallet.id.parse()?;
+
+        let signing_key =
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    Address::fetch_eth_signing_key(
+                        &mut tx_conn,
+                        tmp_core.keychain(),
+                        &wallet_id,
+                    )
+                })?;
+        assert_eq!(
+            signing_key.key.curve,
+            BlockchainProtocol::Ethereum.elliptic_curve()
+        );
+
+        Ok(())
+    }
 }
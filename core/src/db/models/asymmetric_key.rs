@@ -33,6 +33,10 @@ pub struct AsymmetricKey {
     pub dapp_id: Option<DeterministicId>,
     pub created_at: String,
     pub updated_at: Option<String>,
+    /// Whether the user pinned the dapp of the key in the profile.
+    pub pinned: bool,
+    /// Position of a pinned dapp in the profile. Ascending.
+    pub pin_order: Option<i32>,
 }
 
 impl AsymmetricKey {
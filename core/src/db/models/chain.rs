@@ -124,7 +124,7 @@ impl Chain {
     }
 
     /// Fetch an Ethereum chain and return its deterministic id if it exists.
-    fn fetch_eth_chain_deterministic_id(
+    pub fn fetch_eth_chain_deterministic_id(
         conn: &mut SqliteConnection,
         chain_id: eth::ChainId,
     ) -> Result<Option<DeterministicId>, Error> {
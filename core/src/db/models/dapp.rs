@@ -2,18 +2,29 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use diesel::{prelude::*, SqliteConnection};
-use generic_array::{typenum::U1, GenericArray};
+use std::collections::{HashMap, HashSet};
+
+use diesel::{dsl::max, prelude::*, sql_types::Text, sqlite::Sqlite, SqliteConnection};
+use generic_array::{
+    typenum::{U1, U3},
+    GenericArray,
+};
 use url::Url;
 
 use crate::{
+    config,
     db::{
         deterministic_id::{DeriveDeterministicId, DeterministicId, EntityName},
-        schema::{asymmetric_keys, dapps, profiles},
+        models as m,
+        schema::{
+            addresses, asymmetric_keys, dapp_favicons, dapp_metadata, dapp_storage,
+            dapp_tags, dapps, local_dapp_sessions, local_dapp_spends, profiles,
+            transactions,
+        },
         url_value::UrlValue,
         DeferredTxConnection,
     },
-    public_suffix_list::PublicSuffixList,
+    public_suffix_list::{host_identifier, PublicSuffixList},
     utils::rfc3339_timestamp,
     Error,
 };
@@ -26,6 +37,7 @@ pub struct Dapp {
     pub url: UrlValue,
     pub created_at: String,
     pub updated_at: Option<String>,
+    pub favicon_id: Option<DeterministicId>,
 }
 
 type AllColumns = (
@@ -34,6 +46,7 @@ type AllColumns = (
     dapps::url,
     dapps::created_at,
     dapps::updated_at,
+    dapps::favicon_id,
 );
 
 const ALL_COLUMNS: AllColumns = (
@@ -42,6 +55,7 @@ const ALL_COLUMNS: AllColumns = (
     dapps::url,
     dapps::created_at,
     dapps::updated_at,
+    dapps::favicon_id,
 );
 
 impl Dapp {
@@ -49,27 +63,315 @@ impl Dapp {
         ALL_COLUMNS
     }
 
-    pub fn list_all(conn: &mut SqliteConnection) -> Result<Vec<Self>, Error> {
-        Ok(dapps::table.load::<Self>(conn)?)
+    /// List a page of all dapps in the order they were created. Ties are ordered by id so
+    /// pages are stable.
+    pub fn list_all(
+        conn: &mut SqliteConnection,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<Self>, Error> {
+        use dapps::dsl as d;
+
+        Ok(dapps::table
+            .order((d::created_at.asc(), d::deterministic_id.asc()))
+            .offset(offset as i64)
+            .limit(limit as i64)
+            .load::<Self>(conn)?)
     }
 
-    /// List all dapps that have been added to an profile.
+    /// List a page of the dapps that have been added to an profile. Pinned dapps come first in
+    /// pin order, then the rest by recency. If there is a tag, only the dapps with the tag are
+    /// listed.
     pub fn list_for_profile(
         conn: &mut SqliteConnection,
         profile_id: &DeterministicId,
+        tag: Option<&str>,
+        offset: u32,
+        limit: u32,
     ) -> Result<Vec<Self>, Error> {
         use asymmetric_keys::dsl as ak;
         use dapps::dsl as d;
 
-        let dapps: Vec<Self> = asymmetric_keys::table
+        let mut query = asymmetric_keys::table
             .inner_join(dapps::table.on(ak::dapp_id.eq(d::deterministic_id.nullable())))
             .filter(ak::profile_id.eq(profile_id))
             .select(Self::all_columns())
-            .load(conn)?;
+            .order((
+                ak::pinned.desc(),
+                ak::pin_order.asc(),
+                d::updated_at.desc(),
+                d::created_at.desc(),
+                d::deterministic_id.asc(),
+            ))
+            .into_boxed();
+        if let Some(tag) = tag {
+            query = query
+                .filter(d::deterministic_id.eq_any(Self::tagged_ids(profile_id, tag)));
+        }
+        let dapps: Vec<Self> =
+            query.offset(offset as i64).limit(limit as i64).load(conn)?;
 
         Ok(dapps)
     }
 
+    /// The number of dapps that `list_for_profile` lists with the same tag filter.
+    pub fn count_for_profile(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+        tag: Option<&str>,
+    ) -> Result<i64, Error> {
+        use asymmetric_keys::dsl as ak;
+        use dapps::dsl as d;
+
+        let mut query = asymmetric_keys::table
+            .inner_join(dapps::table.on(ak::dapp_id.eq(d::deterministic_id.nullable())))
+            .filter(ak::profile_id.eq(profile_id))
+            .count()
+            .into_boxed();
+        if let Some(tag) = tag {
+            query = query
+                .filter(d::deterministic_id.eq_any(Self::tagged_ids(profile_id, tag)));
+        }
+        let count: i64 = query.get_result(conn)?;
+
+        Ok(count)
+    }
+
+    /// Subquery for the ids of the dapps with the tag in the profile.
+    fn tagged_ids<'a>(
+        profile_id: &'a DeterministicId,
+        tag: &'a str,
+    ) -> dapp_tags::BoxedQuery<'a, Sqlite, Text> {
+        use dapp_tags::dsl as dt;
+
+        // The tag column is case-insensitive.
+        dapp_tags::table
+            .filter(dt::profile_id.eq(profile_id))
+            .filter(dt::tag.eq(tag.trim()))
+            .select(dt::dapp_id)
+            .into_boxed()
+    }
+
+    /// Replace the user assigned tags of a dapp in a profile. Tags are trimmed, truncated and
+    /// deduplicated case-insensitively. Empty tags are ignored.
+    pub fn set_tags(
+        tx_conn: &mut DeferredTxConnection,
+        profile_id: &DeterministicId,
+        dapp_id: &DeterministicId,
+        tags: &[String],
+    ) -> Result<(), Error> {
+        use asymmetric_keys::dsl as ak;
+        use dapp_tags::dsl as dt;
+
+        let tags = sanitize_tags(tags)?;
+        let key_id: Option<DeterministicId> = asymmetric_keys::table
+            .filter(ak::profile_id.eq(profile_id))
+            .filter(ak::dapp_id.eq(Some(dapp_id)))
+            .select(ak::deterministic_id)
+            .first(tx_conn.as_mut())
+            .optional()?;
+        if key_id.is_none() {
+            return Err(Error::User {
+                explanation: "The dapp hasn't been added to the profile.".into(),
+            });
+        }
+
+        diesel::delete(
+            dapp_tags::table
+                .filter(dt::profile_id.eq(profile_id))
+                .filter(dt::dapp_id.eq(dapp_id)),
+        )
+        .execute(tx_conn.as_mut())?;
+
+        let created_at = rfc3339_timestamp();
+        for tag in tags {
+            let entity = DappTagEntity {
+                profile_id,
+                dapp_id,
+                tag: tag.to_lowercase(),
+            };
+            diesel::insert_into(dapp_tags::table)
+                .values((
+                    dt::deterministic_id.eq(entity.deterministic_id()?),
+                    dt::profile_id.eq(profile_id),
+                    dt::dapp_id.eq(dapp_id),
+                    dt::tag.eq(&tag),
+                    dt::created_at.eq(&created_at),
+                ))
+                .execute(tx_conn.as_mut())?;
+        }
+
+        Ok(())
+    }
+
+    /// The distinct tags in use in a profile in alphabetical order.
+    pub fn list_tags(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+    ) -> Result<Vec<String>, Error> {
+        use dapp_tags::dsl as dt;
+
+        let tags: Vec<String> = dapp_tags::table
+            .filter(dt::profile_id.eq(profile_id))
+            .select(dt::tag)
+            .distinct()
+            .order(dt::tag.asc())
+            .load(conn)?;
+
+        Ok(tags)
+    }
+
+    /// The tags of a dapp in a profile in alphabetical order.
+    pub fn list_tags_for_dapp(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+        dapp_id: &DeterministicId,
+    ) -> Result<Vec<String>, Error> {
+        use dapp_tags::dsl as dt;
+
+        let tags: Vec<String> = dapp_tags::table
+            .filter(dt::profile_id.eq(profile_id))
+            .filter(dt::dapp_id.eq(dapp_id))
+            .select(dt::tag)
+            .order(dt::tag.asc())
+            .load(conn)?;
+
+        Ok(tags)
+    }
+
+    /// The ids of the dapps pinned in a profile in pin order.
+    pub fn list_pinned_ids_for_profile(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+    ) -> Result<Vec<DeterministicId>, Error> {
+        use asymmetric_keys::dsl as ak;
+
+        let dapp_ids: Vec<Option<DeterministicId>> = asymmetric_keys::table
+            .filter(ak::profile_id.eq(profile_id))
+            .filter(ak::pinned.eq(true))
+            .order(ak::pin_order.asc())
+            .select(ak::dapp_id)
+            .load(conn)?;
+
+        Ok(dapp_ids.into_iter().flatten().collect())
+    }
+
+    /// Pin a dapp to the top of the profile's dapps or unpin it. Newly pinned dapps are
+    /// appended to the pinned dapps. Unpinned dapps return to recency order.
+    pub fn set_pinned(
+        tx_conn: &mut DeferredTxConnection,
+        profile_id: &DeterministicId,
+        dapp_id: &DeterministicId,
+        pinned: bool,
+    ) -> Result<(), Error> {
+        use asymmetric_keys::dsl as ak;
+
+        let is_pinned: Option<bool> = asymmetric_keys::table
+            .filter(ak::profile_id.eq(profile_id))
+            .filter(ak::dapp_id.eq(Some(dapp_id)))
+            .select(ak::pinned)
+            .first(tx_conn.as_mut())
+            .optional()?;
+        let is_pinned = is_pinned.ok_or_else(|| Error::User {
+            explanation: "The dapp hasn't been added to the profile.".into(),
+        })?;
+        if is_pinned == pinned {
+            return Ok(());
+        }
+
+        let pin_order = if pinned {
+            let max_pin_order: Option<i32> = asymmetric_keys::table
+                .filter(ak::profile_id.eq(profile_id))
+                .select(max(ak::pin_order))
+                .first(tx_conn.as_mut())?;
+            Some(max_pin_order.map(|max| max + 1).unwrap_or_default())
+        } else {
+            None
+        };
+
+        diesel::update(
+            asymmetric_keys::table
+                .filter(ak::profile_id.eq(profile_id))
+                .filter(ak::dapp_id.eq(Some(dapp_id))),
+        )
+        .set((
+            ak::pinned.eq(pinned),
+            ak::pin_order.eq(pin_order),
+            ak::updated_at.eq(rfc3339_timestamp()),
+        ))
+        .execute(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+
+    /// Persist a user chosen order of the pinned dapps in a profile.
+    /// The ids must contain each pinned dapp of the profile exactly once.
+    pub fn reorder_pinned(
+        tx_conn: &mut DeferredTxConnection,
+        profile_id: &DeterministicId,
+        ordered_ids: &[DeterministicId],
+    ) -> Result<(), Error> {
+        use asymmetric_keys::dsl as ak;
+
+        let mut pinned_ids =
+            Self::list_pinned_ids_for_profile(tx_conn.as_mut(), profile_id)?;
+        let mut requested_ids = ordered_ids.to_vec();
+        pinned_ids.sort();
+        requested_ids.sort();
+        if pinned_ids != requested_ids {
+            return Err(Error::User {
+                explanation: "The dapp ids must contain each pinned dapp exactly once."
+                    .into(),
+            });
+        }
+
+        for (pin_order, dapp_id) in ordered_ids.iter().enumerate() {
+            let pin_order: i32 = pin_order.try_into().map_err(|_| Error::Fatal {
+                error: "Too many dapps to reorder".into(),
+            })?;
+            diesel::update(
+                asymmetric_keys::table
+                    .filter(ak::profile_id.eq(profile_id))
+                    .filter(ak::dapp_id.eq(Some(dapp_id))),
+            )
+            .set((
+                ak::pin_order.eq(pin_order),
+                ak::updated_at.eq(rfc3339_timestamp()),
+            ))
+            .execute(tx_conn.as_mut())?;
+        }
+
+        Ok(())
+    }
+
+    /// The creation timestamp of the most recent transaction by dapp for the dapps added to a
+    /// profile. Dapps without transactions are omitted.
+    pub fn list_last_transacted_at_for_profile(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+    ) -> Result<HashMap<DeterministicId, String>, Error> {
+        use addresses::dsl as a;
+        use asymmetric_keys::dsl as ak;
+        use transactions::dsl as t;
+
+        let rows: Vec<(Option<DeterministicId>, Option<String>)> = transactions::table
+            .inner_join(addresses::table.on(a::deterministic_id.eq(t::from_address_id)))
+            .inner_join(
+                asymmetric_keys::table.on(ak::deterministic_id.eq(a::asymmetric_key_id)),
+            )
+            .filter(ak::profile_id.eq(profile_id))
+            .filter(ak::dapp_id.is_not_null())
+            .group_by(ak::dapp_id)
+            .select((ak::dapp_id, max(t::created_at)))
+            .load(conn)?;
+
+        let results = rows
+            .into_iter()
+            .filter_map(|(dapp_id, created_at)| Some((dapp_id?, created_at?)))
+            .collect();
+        Ok(results)
+    }
+
     /// List dapp ids in descending order by last updated at.
     pub fn list_dapp_ids_desc(
         conn: &mut SqliteConnection,
@@ -86,6 +388,103 @@ impl Dapp {
         Ok(dapp_ids)
     }
 
+    /// List dapp ids in descending order by the last use of their local sessions on this
+    /// device. Dapps without a local session are omitted. Usage is device-local, so it's not
+    /// stored in the synced dapps table.
+    pub fn list_recently_used(
+        conn: &mut SqliteConnection,
+        limit: u32,
+    ) -> Result<Vec<DeterministicId>, Error> {
+        use local_dapp_sessions::dsl as lds;
+
+        let rows: Vec<(DeterministicId, Option<String>)> = local_dapp_sessions::table
+            .group_by(lds::dapp_id)
+            .select((lds::dapp_id, max(lds::last_used_at)))
+            .order((max(lds::last_used_at).desc(), lds::dapp_id.asc()))
+            .limit(limit as i64)
+            .load(conn)?;
+
+        Ok(rows.into_iter().map(|(dapp_id, _)| dapp_id).collect())
+    }
+
+    /// Search the dapps added to a profile by identifier or url host, case-insensitively.
+    /// Results are ordered by relevance (exact, prefix then substring match) then recency.
+    /// An empty query returns the recently used dapps of the profile.
+    pub fn search(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+        query: &str,
+        limit: u32,
+    ) -> Result<Vec<Self>, Error> {
+        use asymmetric_keys::dsl as ak;
+        use dapps::dsl as d;
+
+        let query = query.trim().to_lowercase();
+        let base_query = asymmetric_keys::table
+            .inner_join(dapps::table.on(ak::dapp_id.eq(d::deterministic_id.nullable())))
+            .filter(ak::profile_id.eq(profile_id))
+            .select(Self::all_columns())
+            .distinct()
+            .order((d::updated_at.desc(), d::created_at.desc()));
+
+        if query.is_empty() {
+            let dapps: Vec<Self> = base_query.limit(limit as i64).load(conn)?;
+            return Ok(dapps);
+        }
+
+        // Sqlite `LIKE` is case-insensitive for ASCII. The url is only a pre-filter, because
+        // the path might match as well, relevance is decided based on the host below.
+        let pattern = format!("%{}%", escape_like_pattern(&query));
+        let candidates: Vec<Self> = base_query
+            .filter(
+                d::identifier
+                    .like(&pattern)
+                    .escape('\\')
+                    .or(d::url.like(&pattern).escape('\\')),
+            )
+            .load(conn)?;
+
+        // Sort is stable so recency order is preserved within the same relevance.
+        let mut ranked: Vec<(SearchRelevance, Self)> = candidates
+            .into_iter()
+            .filter_map(|dapp| {
+                let url: Url = dapp.url.clone().into();
+                let host = url.host_str().map(|host| host.to_lowercase());
+                let relevance =
+                    SearchRelevance::new(&dapp.identifier.to_lowercase(), &query)
+                        .max(host.and_then(|host| SearchRelevance::new(&host, &query)));
+                relevance.map(|relevance| (relevance, dapp))
+            })
+            .collect();
+        ranked.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        Ok(ranked
+            .into_iter()
+            .map(|(_, dapp)| dapp)
+            .take(limit as usize)
+            .collect())
+    }
+
+    /// Search all dapps by identifier substring, case-insensitively. Results are ordered by
+    /// recency.
+    pub fn search_by_identifier(
+        conn: &mut SqliteConnection,
+        query: &str,
+        limit: u32,
+    ) -> Result<Vec<Self>, Error> {
+        use dapps::dsl as d;
+
+        // Sqlite `LIKE` is case-insensitive for ASCII and identifiers are lowercase ASCII.
+        let pattern = format!("%{}%", escape_like_pattern(&query.trim().to_lowercase()));
+        let dapps: Vec<Self> = dapps::table
+            .filter(d::identifier.like(&pattern).escape('\\'))
+            .order((d::updated_at.desc(), d::created_at.desc()))
+            .limit(limit as i64)
+            .load(conn)?;
+
+        Ok(dapps)
+    }
+
     /// Get the human-readable dapp identifier from an url.
     pub fn dapp_identifier(
         url: Url,
@@ -95,6 +494,87 @@ impl Dapp {
         Ok(dapp_entity.identifier)
     }
 
+    /// The dapp identifiers of the urls in the order of the input. An invalid url only fails
+    /// its own result.
+    pub fn dapp_identifiers<S: AsRef<str>>(
+        urls: &[S],
+        public_suffix_list: &PublicSuffixList,
+    ) -> Vec<Result<String, Error>> {
+        urls.iter()
+            .map(|url| {
+                let url = Url::parse(url.as_ref())?;
+                Self::dapp_identifier(url, public_suffix_list)
+            })
+            .collect()
+    }
+
+    /// The deterministic id of the dapp at an url. The dapp may not exist.
+    pub fn deterministic_id_for_url(
+        url: Url,
+        public_suffix_list: &PublicSuffixList,
+    ) -> Result<DeterministicId, Error> {
+        let dapp_entity = DappEntity::new(url, public_suffix_list)?;
+        dapp_entity.deterministic_id()
+    }
+
+    /// The dapp id from a deep link. The link can be a dapp id, a `sealvault://dapp/<dapp id>`
+    /// uri or the url of the dapp. The dapp may not exist.
+    pub fn deterministic_id_for_deep_link(
+        link: &str,
+        public_suffix_list: &PublicSuffixList,
+    ) -> Result<DeterministicId, Error> {
+        let link = link.trim();
+        let invalid_link = || Error::User {
+            explanation: format!("Invalid dapp link: '{link}'"),
+        };
+        if let Some(dapp_id) = link.strip_prefix(config::DAPP_DEEP_LINK_PREFIX) {
+            return dapp_id.parse().map_err(|_| invalid_link());
+        }
+        if let Ok(dapp_id) = link.parse() {
+            return Ok(dapp_id);
+        }
+        let url = Url::parse(link)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(invalid_link)?;
+        Self::deterministic_id_for_url(url, public_suffix_list)
+    }
+
+    /// Fetch a dapp by its id. Returns none if the dapp doesn't exist.
+    pub fn fetch_optional(
+        conn: &mut SqliteConnection,
+        dapp_id: &DeterministicId,
+    ) -> Result<Option<Self>, Error> {
+        use dapps::dsl as d;
+
+        let dapp = dapps::table
+            .filter(d::deterministic_id.eq(dapp_id))
+            .select(Self::all_columns())
+            .first(conn)
+            .optional()?;
+
+        Ok(dapp)
+    }
+
+    /// The ids of the profiles that a dapp has been added to in the profile order.
+    pub fn list_profile_ids(
+        conn: &mut SqliteConnection,
+        dapp_id: &DeterministicId,
+    ) -> Result<Vec<DeterministicId>, Error> {
+        use asymmetric_keys::dsl as ak;
+        use profiles::dsl as p;
+
+        let profile_ids: Vec<DeterministicId> = asymmetric_keys::table
+            .inner_join(profiles::table.on(ak::profile_id.eq(p::deterministic_id)))
+            .filter(ak::dapp_id.eq(Some(dapp_id)))
+            .select(p::deterministic_id)
+            .distinct()
+            .order((p::sort_order.asc(), p::created_at.asc()))
+            .load(conn)?;
+
+        Ok(profile_ids)
+    }
+
     /// Get the human-readable dapp identifier for a dapp id.
     pub fn fetch_dapp_identifier(
         conn: &mut SqliteConnection,
@@ -122,6 +602,98 @@ impl Dapp {
         Ok(dapp_id)
     }
 
+    /// Delete a dapp with its metadata, tags, sessions and storage.
+    /// Dapps that still have keys in a profile can't be deleted, because the keys may hold
+    /// assets. A user error is returned in that case and nothing is deleted.
+    /// The operation is idempotent. Returns false if the dapp didn't exist.
+    pub fn delete(
+        tx_conn: &mut DeferredTxConnection,
+        dapp_id: &DeterministicId,
+    ) -> Result<bool, Error> {
+        use asymmetric_keys::dsl as ak;
+        use dapp_metadata::dsl as dm;
+        use dapp_storage::dsl as ds;
+        use dapp_tags::dsl as dt;
+        use dapps::dsl as d;
+        use local_dapp_sessions::dsl as lds;
+        use local_dapp_spends::dsl as ldsp;
+
+        let has_keys: bool = diesel::select(diesel::dsl::exists(
+            asymmetric_keys::table.filter(ak::dapp_id.eq(Some(dapp_id))),
+        ))
+        .get_result(tx_conn.as_mut())?;
+        if has_keys {
+            return Err(Error::User {
+                explanation:
+                    "The dapp can't be removed while it has accounts in a profile.".into(),
+            });
+        }
+
+        let session_ids = local_dapp_sessions::table
+            .filter(lds::dapp_id.eq(dapp_id))
+            .select(lds::uuid);
+        diesel::delete(
+            local_dapp_spends::table.filter(ldsp::session_id.eq_any(session_ids)),
+        )
+        .execute(tx_conn.as_mut())?;
+        diesel::delete(local_dapp_sessions::table.filter(lds::dapp_id.eq(dapp_id)))
+            .execute(tx_conn.as_mut())?;
+        diesel::delete(dapp_storage::table.filter(ds::dapp_id.eq(dapp_id)))
+            .execute(tx_conn.as_mut())?;
+        diesel::delete(dapp_tags::table.filter(dt::dapp_id.eq(dapp_id)))
+            .execute(tx_conn.as_mut())?;
+        diesel::delete(dapp_metadata::table.filter(dm::dapp_id.eq(dapp_id)))
+            .execute(tx_conn.as_mut())?;
+        let deleted =
+            diesel::delete(dapps::table.filter(d::deterministic_id.eq(dapp_id)))
+                .execute(tx_conn.as_mut())?;
+        m::DappFavicon::delete_orphaned(tx_conn.as_mut())?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Set the favicon of a dapp. Identical favicons of different dapps are stored once and
+    /// a replaced favicon is deleted if no other dapp uses it.
+    /// Returns a user error if the image is not a supported favicon.
+    pub fn set_favicon(
+        tx_conn: &mut DeferredTxConnection,
+        dapp_id: &DeterministicId,
+        image: &[u8],
+    ) -> Result<(), Error> {
+        use dapps::dsl as d;
+
+        let favicon_id = m::DappFavicon::fetch_or_create(tx_conn.as_mut(), image)?;
+        let updated =
+            diesel::update(dapps::table.filter(d::deterministic_id.eq(dapp_id)))
+                .set(d::favicon_id.eq(&favicon_id))
+                .execute(tx_conn.as_mut())?;
+        if updated == 0 {
+            return Err(Error::User {
+                explanation: "The dapp doesn't exist.".into(),
+            });
+        }
+        m::DappFavicon::delete_orphaned(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+
+    /// The favicon images of the dapps that have one set by dapp id.
+    pub fn list_favicons(
+        conn: &mut SqliteConnection,
+        dapp_ids: &[DeterministicId],
+    ) -> Result<HashMap<DeterministicId, Vec<u8>>, Error> {
+        use dapp_favicons::dsl as df;
+        use dapps::dsl as d;
+
+        let favicons: Vec<(DeterministicId, Vec<u8>)> = dapps::table
+            .inner_join(dapp_favicons::table)
+            .filter(d::deterministic_id.eq_any(dapp_ids))
+            .select((d::deterministic_id, df::image))
+            .load(conn)?;
+
+        Ok(favicons.into_iter().collect())
+    }
+
     /// Returns the dapp id if the dapp has been added to the profile.
     pub fn fetch_id_for_profile(
         conn: &mut SqliteConnection,
@@ -134,6 +706,88 @@ impl Dapp {
     }
 }
 
+/// Higher is more relevant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SearchRelevance {
+    Substring,
+    Prefix,
+    Exact,
+}
+
+impl SearchRelevance {
+    /// Both arguments are expected to be lowercase.
+    fn new(value: &str, query: &str) -> Option<Self> {
+        if value == query {
+            Some(Self::Exact)
+        } else if value.starts_with(query) {
+            Some(Self::Prefix)
+        } else if value.contains(query) {
+            Some(Self::Substring)
+        } else {
+            None
+        }
+    }
+}
+
+/// Trim, truncate and deduplicate tags case-insensitively keeping the first spelling.
+fn sanitize_tags(tags: &[String]) -> Result<Vec<String>, Error> {
+    let mut seen: HashSet<String> = Default::default();
+    let mut results: Vec<String> = Default::default();
+    for tag in tags {
+        let tag: String = tag
+            .trim()
+            .chars()
+            .take(config::MAX_DAPP_TAG_LENGTH)
+            .collect();
+        let tag = tag.trim_end();
+        if !tag.is_empty() && seen.insert(tag.to_lowercase()) {
+            results.push(tag.to_string());
+        }
+    }
+    if results.len() > config::MAX_DAPP_TAGS {
+        return Err(Error::User {
+            explanation: format!(
+                "A dapp can have at most {} tags.",
+                config::MAX_DAPP_TAGS
+            ),
+        });
+    }
+    Ok(results)
+}
+
+/// The dapp identifier for origins without a registrable domain, eg. IP addresses or
+/// `localhost`. The identifier is `scheme://host` followed by the port only if it's not the
+/// default port of the scheme. IPv6 addresses keep their brackets and IP addresses are in
+/// canonical form, so equivalent origins map to the same identifier.
+fn origin_identifier(url: &Url) -> Result<String, Error> {
+    let host = match url.host() {
+        Some(host) => host_identifier(&host),
+        // All URLs without a host would map to the same dapp otherwise.
+        None => {
+            return Err(Error::User {
+                explanation: format!("The dapp URL '{url}' has no host."),
+            })
+        }
+    };
+    // The url crate normalizes default ports to none.
+    let identifier = match url.port() {
+        Some(port) => format!("{}://{host}:{port}", url.scheme()),
+        None => format!("{}://{host}", url.scheme()),
+    };
+    Ok(identifier)
+}
+
+fn escape_like_pattern(query: &str) -> String {
+    let mut result = String::with_capacity(query.len());
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = dapps)]
 struct DappEntity {
@@ -146,8 +800,10 @@ impl DappEntity {
         let origin = url.origin();
         let registrable_domain: Option<String> =
             public_suffix_list.registrable_domain(&origin)?.into();
-        let identifier =
-            registrable_domain.unwrap_or_else(|| origin.ascii_serialization());
+        let identifier = match registrable_domain {
+            Some(registrable_domain) => registrable_domain,
+            None => origin_identifier(&url)?,
+        };
         Ok(DappEntity {
             identifier,
             url: url.into(),
@@ -218,9 +874,41 @@ impl<'a> DeriveDeterministicId<'a, &'a str, U1> for DappEntity {
     }
 }
 
+struct DappTagEntity<'a> {
+    profile_id: &'a DeterministicId,
+    dapp_id: &'a DeterministicId,
+    /// Lowercase, because tags are case-insensitive.
+    tag: String,
+}
+
+impl<'a> DeriveDeterministicId<'a, &'a str, U3> for DappTagEntity<'a> {
+    fn entity_name(&'a self) -> EntityName {
+        EntityName::DappTag
+    }
+
+    fn unique_columns(&'a self) -> GenericArray<&'a str, U3> {
+        [
+            self.profile_id.as_ref(),
+            self.dapp_id.as_ref(),
+            self.tag.as_str(),
+        ]
+        .into()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{thread, time::Duration};
+
+    use anyhow::Result;
+    use ethers::types::H256;
+
     use super::*;
+    use crate::{
+        app_core::tests::TmpCore,
+        db::models::{self as m, LocalSettings},
+        protocols::eth,
+    };
 
     #[test]
     fn dapp_identifier() {
@@ -230,4 +918,593 @@ mod tests {
         let identifier = Dapp::dapp_identifier(url, &psl).unwrap();
         assert_eq!(identifier, "example.com");
     }
+
+    #[test]
+    fn dapp_identifier_without_registrable_domain() -> Result<()> {
+        let psl: PublicSuffixList = Default::default();
+        let test_cases = [
+            ("http://[::1]:8545", "http://[::1]:8545"),
+            ("http://[0:0:0:0:0:0:0:1]:8545/rpc", "http://[::1]:8545"),
+            ("HTTP://[::1]:8545?foo=bar", "http://[::1]:8545"),
+            ("https://[::1]:443", "https://[::1]"),
+            ("http://127.0.0.1:8080", "http://127.0.0.1:8080"),
+            (
+                "http://127.0.0.1:8080/index.html#top",
+                "http://127.0.0.1:8080",
+            ),
+            ("http://127.1:8080", "http://127.0.0.1:8080"),
+            ("http://127.0.0.1:80", "http://127.0.0.1"),
+            ("http://LOCALHOST:3000/", "http://localhost:3000"),
+        ];
+
+        for (url, expected) in test_cases {
+            let identifier = Dapp::dapp_identifier(Url::parse(url)?, &psl)?;
+            assert_eq!(identifier, expected, "url: {url}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn dapp_identifiers_in_input_order() {
+        let psl: PublicSuffixList = Default::default();
+        let urls = [
+            "https://app.uniswap.org/swap",
+            "not a url",
+            "http://127.0.0.1:8080",
+            "data:text/html,hello",
+            "https://www.example.com",
+        ];
+
+        let results = Dapp::dapp_identifiers(&urls, &psl);
+
+        assert_eq!(results.len(), urls.len());
+        assert_eq!(results[0].as_deref().ok(), Some("uniswap.org"));
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_deref().ok(), Some("http://127.0.0.1:8080"));
+        assert!(matches!(results[3], Err(Error::User { .. })));
+        assert_eq!(results[4].as_deref().ok(), Some("example.com"));
+    }
+
+    #[test]
+    fn dapp_identifier_requires_host() -> Result<()> {
+        let psl: PublicSuffixList = Default::default();
+
+        let res = Dapp::dapp_identifier(Url::parse("data:text/html,hello")?, &psl);
+        assert!(matches!(res, Err(Error::User { .. })));
+
+        Ok(())
+    }
+
+    fn search_identifiers(tmp_core: &TmpCore, query: &str) -> Result<Vec<String>> {
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let profile_id = LocalSettings::fetch_active_profile_id(&mut conn)?;
+        let dapps = Dapp::search(&mut conn, &profile_id, query, 10)?;
+        Ok(dapps.into_iter().map(|dapp| dapp.identifier).collect())
+    }
+
+    #[test]
+    fn search_prefix_match() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let res = search_identifiers(&tmp_core, "QUICK")?;
+        assert_eq!(res, vec!["quickswap.exchange".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn search_substring_match_ranked_after_prefix() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let res = search_identifiers(&tmp_core, "x")?;
+        assert_eq!(res.first().map(|s| s.as_str()), Some("xmtp.chat"));
+        assert!(res.contains(&"showtime.xyz".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn search_no_match() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let res = search_identifiers(&tmp_core, "no-such-dapp")?;
+        assert!(res.is_empty());
+        // Like wildcards are escaped
+        let res = search_identifiers(&tmp_core, "%")?;
+        assert!(res.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn search_empty_query_returns_recent() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let res = search_identifiers(&tmp_core, "  ")?;
+        assert!(!res.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn search_by_identifier() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let mut conn = tmp_core.connection_pool().connection()?;
+
+        let identifiers = |dapps: Vec<Dapp>| -> Vec<String> {
+            dapps.into_iter().map(|dapp| dapp.identifier).collect()
+        };
+        let res = Dapp::search_by_identifier(&mut conn, "SWAP.exch", 10)?;
+        assert_eq!(identifiers(res), vec!["quickswap.exchange".to_string()]);
+
+        let res = Dapp::search_by_identifier(&mut conn, ".", 1)?;
+        assert_eq!(res.len(), 1);
+
+        // Like wildcards are escaped
+        for query in ["%", "_", "quick%exchange", "\\"] {
+            let res = Dapp::search_by_identifier(&mut conn, query, 10)?;
+            assert!(res.is_empty(), "{query}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_recently_used() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let dapp_ids: Vec<DeterministicId> = tmp_core
+            .first_profile()
+            .dapps
+            .iter()
+            .take(2)
+            .map(|dapp| Ok(dapp.id.parse()?))
+            .collect::<Result<_>>()?;
+        assert_eq!(dapp_ids.len(), 2);
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let profile_id = LocalSettings::fetch_active_profile_id(&mut conn)?;
+        let sessions =
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    dapp_ids
+                        .iter()
+                        .map(|dapp_id| {
+                            let params = m::NewDappSessionParams::builder()
+                                .dapp_id(dapp_id)
+                                .profile_id(&profile_id)
+                                .build();
+                            m::LocalDappSession::create_eth_session_if_not_exists(
+                                &mut tx_conn,
+                                &params,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, Error>>()
+                })?;
+        let update = |session: &m::LocalDappSession| -> Result<()> {
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    session.clone().update_last_used_at(&mut tx_conn)
+                })?;
+            // Timestamps have millisecond resolution.
+            thread::sleep(Duration::from_millis(2));
+            Ok(())
+        };
+        let before = Dapp::fetch_optional(&mut conn, &dapp_ids[0])?.expect("dapp exists");
+
+        update(&sessions[0])?;
+        update(&sessions[1])?;
+        assert_eq!(
+            Dapp::list_recently_used(&mut conn, 2)?,
+            vec![dapp_ids[1].clone(), dapp_ids[0].clone()]
+        );
+
+        update(&sessions[0])?;
+        assert_eq!(
+            Dapp::list_recently_used(&mut conn, 1)?,
+            vec![dapp_ids[0].clone()]
+        );
+
+        // Usage is local, so the synced dapp is unchanged.
+        let after = Dapp::fetch_optional(&mut conn, &dapp_ids[0])?.expect("dapp exists");
+        assert_eq!(before, after);
+
+        Ok(())
+    }
+
+    #[test]
+    fn touch_last_used_is_throttled() -> Result<()> {
+        use local_dapp_sessions::dsl as lds;
+
+        let tmp_core = TmpCore::new()?;
+        let dapp_id: DeterministicId = tmp_core
+            .first_profile()
+            .dapps
+            .first()
+            .expect("there is a dapp")
+            .id
+            .parse()?;
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let profile_id = LocalSettings::fetch_active_profile_id(&mut conn)?;
+
+        let (touched_new, touched_old) = tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                let params = m::NewDappSessionParams::builder()
+                    .dapp_id(&dapp_id)
+                    .profile_id(&profile_id)
+                    .build();
+                let session = m::LocalDappSession::create_eth_session_if_not_exists(
+                    &mut tx_conn,
+                    &params,
+                )?;
+                let session = session.update_last_used_at(&mut tx_conn)?;
+                let touched_new =
+                    m::LocalDappSession::touch_last_used(&mut tx_conn, &session.uuid)?;
+
+                diesel::update(
+                    local_dapp_sessions::table.filter(lds::uuid.eq(&session.uuid)),
+                )
+                .set(lds::last_used_at.eq("2023-01-01T00:00:00.000Z"))
+                .execute(tx_conn.as_mut())?;
+                let touched_old =
+                    m::LocalDappSession::touch_last_used(&mut tx_conn, &session.uuid)?;
+                Ok((touched_new, touched_old))
+            })?;
+        assert!(!touched_new);
+        assert!(touched_old);
+
+        Ok(())
+    }
+
+    fn list_dapp_ids(tmp_core: &TmpCore) -> Result<Vec<DeterministicId>> {
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let profile_id = LocalSettings::fetch_active_profile_id(&mut conn)?;
+        let dapps = Dapp::list_for_profile(
+            &mut conn,
+            &profile_id,
+            None,
+            0,
+            config::UNPAGINATED_DAPP_LIST_LIMIT,
+        )?;
+        Ok(dapps
+            .into_iter()
+            .map(|dapp| dapp.deterministic_id)
+            .collect())
+    }
+
+    fn set_pinned(
+        tmp_core: &TmpCore,
+        dapp_id: &DeterministicId,
+        pinned: bool,
+    ) -> Result<()> {
+        tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                let profile_id =
+                    LocalSettings::fetch_active_profile_id(tx_conn.as_mut())?;
+                Dapp::set_pinned(&mut tx_conn, &profile_id, dapp_id, pinned)
+            })?;
+        Ok(())
+    }
+
+    #[test]
+    fn pinning_moves_dapp_to_front() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let recency_order = list_dapp_ids(&tmp_core)?;
+        assert!(recency_order.len() > 2);
+        let last = recency_order.last().cloned().unwrap();
+        let second_last = recency_order[recency_order.len() - 2].clone();
+
+        set_pinned(&tmp_core, &last, true)?;
+        set_pinned(&tmp_core, &second_last, true)?;
+        let res = list_dapp_ids(&tmp_core)?;
+        assert_eq!(res[..2], [last.clone(), second_last.clone()]);
+
+        set_pinned(&tmp_core, &last, false)?;
+        let res = list_dapp_ids(&tmp_core)?;
+        assert_eq!(res[0], second_last);
+
+        set_pinned(&tmp_core, &second_last, false)?;
+        let res = list_dapp_ids(&tmp_core)?;
+        assert_eq!(res, recency_order);
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_for_profile_pages() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let all_ids = list_dapp_ids(&tmp_core)?;
+        assert!(all_ids.len() > 2);
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let profile_id = LocalSettings::fetch_active_profile_id(&mut conn)?;
+        assert_eq!(
+            Dapp::count_for_profile(&mut conn, &profile_id, None)?,
+            all_ids.len() as i64
+        );
+
+        let mut paged_ids = Vec::new();
+        for offset in (0..all_ids.len() as u32 + 2).step_by(2) {
+            let page = Dapp::list_for_profile(&mut conn, &profile_id, None, offset, 2)?;
+            assert!(page.len() <= 2);
+            paged_ids.extend(page.into_iter().map(|dapp| dapp.deterministic_id));
+        }
+        assert_eq!(paged_ids, all_ids);
+
+        let all_dapps =
+            Dapp::list_all(&mut conn, 0, config::UNPAGINATED_DAPP_LIST_LIMIT)?;
+        let page = Dapp::list_all(&mut conn, 1, 1)?;
+        assert_eq!(page, all_dapps[1..2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_by_tag() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let dapp_ids = list_dapp_ids(&tmp_core)?;
+        assert!(dapp_ids.len() > 2);
+        let set_tags = |dapp_id: &DeterministicId, tags: &[&str]| {
+            let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    let profile_id =
+                        LocalSettings::fetch_active_profile_id(tx_conn.as_mut())?;
+                    Dapp::set_tags(&mut tx_conn, &profile_id, dapp_id, &tags)
+                })
+        };
+
+        set_tags(&dapp_ids[0], &["DeFi", " defi ", "NFT", ""])?;
+        set_tags(&dapp_ids[1], &["Social"])?;
+        set_tags(&dapp_ids[2], &["defi"])?;
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let profile_id = LocalSettings::fetch_active_profile_id(&mut conn)?;
+        let defi: Vec<DeterministicId> = Dapp::list_for_profile(
+            &mut conn,
+            &profile_id,
+            Some("DEFI"),
+            0,
+            config::UNPAGINATED_DAPP_LIST_LIMIT,
+        )?
+        .into_iter()
+        .map(|dapp| dapp.deterministic_id)
+        .collect();
+        assert_eq!(defi.len(), 2);
+        assert_eq!(
+            Dapp::count_for_profile(&mut conn, &profile_id, Some("DEFI"))?,
+            2
+        );
+        assert!(defi.contains(&dapp_ids[0]));
+        assert!(defi.contains(&dapp_ids[2]));
+        assert_eq!(
+            Dapp::list_tags_for_dapp(&mut conn, &profile_id, &dapp_ids[0])?,
+            vec!["DeFi", "NFT"]
+        );
+        let tags = Dapp::list_tags(&mut conn, &profile_id)?;
+        assert_eq!(tags.len(), 3);
+        assert!(Dapp::list_for_profile(
+            &mut conn,
+            &profile_id,
+            Some("Games"),
+            0,
+            config::UNPAGINATED_DAPP_LIST_LIMIT,
+        )?
+        .is_empty());
+        assert_eq!(
+            Dapp::count_for_profile(&mut conn, &profile_id, Some("Games"))?,
+            0
+        );
+
+        // Replaces the previous tags.
+        set_tags(&dapp_ids[1], &[])?;
+        let tags = Dapp::list_tags(&mut conn, &profile_id)?;
+        assert_eq!(tags.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn last_transacted_at_for_profile() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let profile = tmp_core.first_profile();
+        let profile_id: DeterministicId = profile.id.parse()?;
+        let dapps: Vec<(DeterministicId, m::AddressId)> = profile
+            .dapps
+            .iter()
+            .take(3)
+            .map(|dapp| Ok((dapp.id.parse()?, dapp.addresses[0].id.parse()?)))
+            .collect::<Result<_>>()?;
+        assert_eq!(dapps.len(), 3);
+        let wallet_id: m::AddressId = tmp_core.first_profile_wallet().id.parse()?;
+
+        let activity = [
+            (&dapps[0].1, "2023-01-01T00:00:00.000Z"),
+            (&dapps[1].1, "2023-02-01T00:00:00.000Z"),
+            (&dapps[0].1, "2023-03-01T00:00:00.000Z"),
+            (&dapps[0].1, "2023-01-15T00:00:00.000Z"),
+            // Wallet transactions don't count.
+            (&wallet_id, "2023-04-01T00:00:00.000Z"),
+        ];
+        tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                use transactions::dsl as t;
+
+                for (address_id, created_at) in activity {
+                    let params = m::NewEthTransactionParams::builder()
+                        .from_address_id(address_id)
+                        .chain_id(eth::ChainId::default_dapp_chain())
+                        .tx_hash(H256::random())
+                        .build();
+                    let tx_id = m::Transaction::create_eth(&mut tx_conn, &params)?;
+                    diesel::update(transactions::table.find(&tx_id))
+                        .set(t::created_at.eq(created_at))
+                        .execute(tx_conn.as_mut())?;
+                }
+                Ok(())
+            })?;
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let mut res = Dapp::list_last_transacted_at_for_profile(&mut conn, &profile_id)?;
+        assert_eq!(res.len(), 2);
+        assert_eq!(
+            res.remove(&dapps[0].0).as_deref(),
+            Some("2023-03-01T00:00:00.000Z")
+        );
+        assert_eq!(
+            res.remove(&dapps[1].0).as_deref(),
+            Some("2023-02-01T00:00:00.000Z")
+        );
+
+        let profile = tmp_core.first_profile();
+        let last_transacted_at = |dapp_id: &DeterministicId| {
+            profile
+                .dapps
+                .iter()
+                .find(|dapp| dapp.id == dapp_id.to_string())
+                .and_then(|dapp| dapp.last_transacted_at.clone())
+        };
+        assert_eq!(
+            last_transacted_at(&dapps[0].0).as_deref(),
+            Some("2023-03-01T00:00:00.000Z")
+        );
+        assert_eq!(last_transacted_at(&dapps[2].0), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn delete_dapp() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let profile = tmp_core.first_profile();
+        let added_dapp_id: DeterministicId =
+            profile.dapps.first().expect("there is a dapp").id.parse()?;
+        let url = Url::parse("https://unknown-dapp.org")?;
+        let psl = tmp_core.resources.public_suffix_list();
+
+        let (res, new_dapp_id) =
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    // Keys of the dapp are still in the profile.
+                    let res = Dapp::delete(&mut tx_conn, &added_dapp_id);
+                    let new_dapp_id = Dapp::create_if_not_exists(&mut tx_conn, url, psl)?;
+                    Ok((res, new_dapp_id))
+                })?;
+        assert!(matches!(res, Err(Error::User { .. })));
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        assert!(Dapp::fetch_optional(&mut conn, &added_dapp_id)?.is_some());
+        assert!(Dapp::fetch_optional(&mut conn, &new_dapp_id)?.is_some());
+        drop(conn);
+
+        let deleted =
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    Dapp::delete(&mut tx_conn, &new_dapp_id)
+                })?;
+        assert!(deleted);
+        let deleted =
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    Dapp::delete(&mut tx_conn, &new_dapp_id)
+                })?;
+        assert!(!deleted);
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        assert!(Dapp::fetch_optional(&mut conn, &new_dapp_id)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_favicon_dedups_images() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let profile = tmp_core.first_profile();
+        let dapp_id: DeterministicId =
+            profile.dapps.first().expect("there is a dapp").id.parse()?;
+        let url = Url::parse("https://unknown-dapp.org")?;
+        let psl = tmp_core.resources.public_suffix_list();
+        let favicon = ico(16);
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>";
+
+        let (other_dapp_id, res) =
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    let other_dapp_id =
+                        Dapp::create_if_not_exists(&mut tx_conn, url, psl)?;
+                    let favicons =
+                        Dapp::list_favicons(tx_conn.as_mut(), &[other_dapp_id.clone()])?;
+                    assert!(favicons.is_empty());
+                    Dapp::set_favicon(&mut tx_conn, &dapp_id, &favicon)?;
+                    Dapp::set_favicon(&mut tx_conn, &other_dapp_id, &favicon)?;
+                    let res = Dapp::set_favicon(&mut tx_conn, &dapp_id, svg);
+                    Ok((other_dapp_id, res))
+                })?;
+        assert!(matches!(res, Err(Error::User { .. })));
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let dapp = Dapp::fetch_optional(&mut conn, &dapp_id)?.expect("dapp exists");
+        let other_dapp =
+            Dapp::fetch_optional(&mut conn, &other_dapp_id)?.expect("dapp exists");
+        assert!(dapp.favicon_id.is_some());
+        assert_eq!(dapp.favicon_id, other_dapp.favicon_id);
+        let favicons = Dapp::list_favicons(&mut conn, &[dapp_id.clone()])?;
+        assert_eq!(favicons.get(&dapp_id), Some(&favicon));
+        assert_eq!(m::DappFavicon::list_all(&mut conn)?.len(), 1);
+
+        Ok(())
+    }
+
+    /// An ICO header with one entry of the dimension.
+    fn ico(dimension: u8) -> Vec<u8> {
+        let mut image = vec![0, 0, 1, 0, 1, 0, dimension, dimension];
+        image.resize(6 + 16, 0);
+        image
+    }
+
+    #[test]
+    fn deletes_orphaned_favicons() -> Result<()> {
+        let tmp_core = TmpCore::new()?;
+        let psl = tmp_core.resources.public_suffix_list();
+        let url = Url::parse("https://unknown-dapp.org")?;
+        let other_url = Url::parse("https://other-unknown-dapp.org")?;
+
+        let (dapp_id, other_dapp_id) =
+            tmp_core
+                .connection_pool()
+                .deferred_transaction(|mut tx_conn| {
+                    let dapp_id = Dapp::create_if_not_exists(&mut tx_conn, url, psl)?;
+                    let other_dapp_id =
+                        Dapp::create_if_not_exists(&mut tx_conn, other_url, psl)?;
+                    Dapp::set_favicon(&mut tx_conn, &dapp_id, &ico(16))?;
+                    Dapp::set_favicon(&mut tx_conn, &other_dapp_id, &ico(16))?;
+                    // Still used by the other dapp.
+                    Dapp::set_favicon(&mut tx_conn, &dapp_id, &ico(32))?;
+                    assert_eq!(m::DappFavicon::list_all(tx_conn.as_mut())?.len(), 2);
+                    // Not used by any dapp after replacement.
+                    Dapp::set_favicon(&mut tx_conn, &other_dapp_id, &ico(32))?;
+                    assert_eq!(m::DappFavicon::list_all(tx_conn.as_mut())?.len(), 1);
+                    Ok((dapp_id, other_dapp_id))
+                })?;
+
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let favicons =
+            Dapp::list_favicons(&mut conn, &[dapp_id.clone(), other_dapp_id.clone()])?;
+        assert_eq!(favicons.len(), 2);
+        assert_eq!(favicons.get(&dapp_id), Some(&ico(32)));
+
+        tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                Dapp::delete(&mut tx_conn, &dapp_id)?;
+                assert_eq!(m::DappFavicon::list_all(tx_conn.as_mut())?.len(), 1);
+                Dapp::delete(&mut tx_conn, &other_dapp_id)
+            })?;
+        let mut conn = tmp_core.connection_pool().connection()?;
+        assert!(m::DappFavicon::list_all(&mut conn)?.is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,94 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use diesel::{prelude::*, SqliteConnection};
+use generic_array::{typenum::U1, GenericArray};
+
+use crate::{
+    db::{
+        deterministic_id::{DeriveDeterministicId, DeterministicId, EntityName},
+        schema::{dapp_favicons, dapps},
+    },
+    favicon::validate_favicon,
+    utils::{blake3_hash, rfc3339_timestamp},
+    Error,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
+#[diesel(primary_key(deterministic_id))]
+pub struct DappFavicon {
+    pub deterministic_id: DeterministicId,
+    pub image_hash: Vec<u8>,
+    pub image: Vec<u8>,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+}
+
+impl DappFavicon {
+    pub fn list_all(conn: &mut SqliteConnection) -> Result<Vec<Self>, Error> {
+        Ok(dapp_favicons::table.load::<Self>(conn)?)
+    }
+
+    /// Insert a favicon unless an identical one exists and return its deterministic id.
+    /// Returns a user error if the image is not a supported favicon.
+    pub fn fetch_or_create(
+        conn: &mut SqliteConnection,
+        image: &[u8],
+    ) -> Result<DeterministicId, Error> {
+        use dapp_favicons::dsl as df;
+
+        validate_favicon(image)?;
+        let image_hash = blake3_hash(image);
+        let entity = DappFaviconEntity {
+            image_hash: image_hash.as_bytes(),
+        };
+        let deterministic_id = entity.deterministic_id()?;
+        diesel::insert_into(dapp_favicons::table)
+            .values((
+                &entity,
+                df::deterministic_id.eq(&deterministic_id),
+                df::image.eq(image),
+                df::created_at.eq(rfc3339_timestamp()),
+            ))
+            .on_conflict(df::deterministic_id)
+            .do_nothing()
+            .execute(conn)?;
+
+        Ok(deterministic_id)
+    }
+
+    /// Delete the favicons that no dapp refers to. Favicons are backed up, so they shouldn't
+    /// be kept after the dapps that used them are removed or have their favicon replaced.
+    /// Returns the number of deleted favicons.
+    pub fn delete_orphaned(conn: &mut SqliteConnection) -> Result<usize, Error> {
+        use dapp_favicons::dsl as df;
+        use dapps::dsl as d;
+
+        let used_ids = dapps::table
+            .filter(d::favicon_id.is_not_null())
+            .select(d::favicon_id);
+        let deleted = diesel::delete(
+            dapp_favicons::table.filter(df::deterministic_id.nullable().ne_all(used_ids)),
+        )
+        .execute(conn)?;
+
+        Ok(deleted)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = dapp_favicons)]
+struct DappFaviconEntity<'a> {
+    image_hash: &'a [u8],
+}
+
+impl<'a> DeriveDeterministicId<'a, &'a [u8], U1> for DappFaviconEntity<'a> {
+    fn entity_name(&'a self) -> EntityName {
+        EntityName::DappFavicon
+    }
+
+    fn unique_columns(&'a self) -> GenericArray<&'a [u8], U1> {
+        [self.image_hash].into()
+    }
+}
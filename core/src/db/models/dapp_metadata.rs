@@ -0,0 +1,141 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use diesel::{prelude::*, SqliteConnection};
+use typed_builder::TypedBuilder;
+use url::Url;
+
+use crate::{
+    config,
+    db::{schema::dapp_metadata, DeferredTxConnection, DeterministicId},
+    utils::rfc3339_timestamp,
+    Error,
+};
+
+/// Metadata reported by the dapp itself.
+/// It's untrusted, so it must not be used to identify the dapp. Use `Dapp::identifier` for
+/// that.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
+#[diesel(table_name = dapp_metadata, primary_key(dapp_id))]
+pub struct DappMetadata {
+    pub dapp_id: DeterministicId,
+    pub name: Option<String>,
+    pub icon_url: Option<String>,
+    pub description: Option<String>,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+}
+
+impl DappMetadata {
+    pub fn fetch(
+        conn: &mut SqliteConnection,
+        dapp_id: &DeterministicId,
+    ) -> Result<Option<Self>, Error> {
+        use dapp_metadata::dsl as dm;
+
+        let metadata = dapp_metadata::table
+            .filter(dm::dapp_id.eq(dapp_id))
+            .first(conn)
+            .optional()?;
+        Ok(metadata)
+    }
+
+    /// Create or replace the metadata of a dapp.
+    pub fn upsert(
+        tx_conn: &mut DeferredTxConnection,
+        dapp_id: &DeterministicId,
+        params: &DappMetadataParams,
+    ) -> Result<(), Error> {
+        use dapp_metadata::dsl as dm;
+
+        let name = sanitize_text(params.name.as_deref());
+        let icon_url = sanitize_icon_url(params.icon_url.as_deref());
+        let description = sanitize_text(params.description.as_deref());
+        let now = rfc3339_timestamp();
+
+        diesel::insert_into(dapp_metadata::table)
+            .values((
+                dm::dapp_id.eq(dapp_id),
+                dm::name.eq(&name),
+                dm::icon_url.eq(&icon_url),
+                dm::description.eq(&description),
+                dm::created_at.eq(&now),
+            ))
+            .on_conflict(dm::dapp_id)
+            .do_update()
+            .set((
+                dm::name.eq(&name),
+                dm::icon_url.eq(&icon_url),
+                dm::description.eq(&description),
+                dm::updated_at.eq(&now),
+            ))
+            .execute(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, TypedBuilder)]
+pub struct DappMetadataParams {
+    #[builder(default)]
+    pub name: Option<String>,
+    #[builder(default)]
+    pub icon_url: Option<String>,
+    #[builder(default)]
+    pub description: Option<String>,
+}
+
+/// Trim and truncate to the max length. Returns none for empty strings.
+fn sanitize_text(value: Option<&str>) -> Option<String> {
+    let value = value?.trim();
+    if value.is_empty() {
+        return None;
+    }
+    Some(
+        value
+            .chars()
+            .take(config::MAX_DAPP_METADATA_FIELD_LENGTH)
+            .collect(),
+    )
+}
+
+/// Only https urls are accepted.
+fn sanitize_icon_url(value: Option<&str>) -> Option<String> {
+    let value = value?.trim();
+    if value.len() > config::MAX_DAPP_METADATA_URL_LENGTH {
+        return None;
+    }
+    let url = Url::parse(value).ok()?;
+    if url.scheme() == "https" {
+        Some(url.to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_text() {
+        assert_eq!(sanitize_text(Some("  ")), None);
+        assert_eq!(sanitize_text(Some(" Foo ")), Some("Foo".to_string()));
+        let long = "x".repeat(config::MAX_DAPP_METADATA_FIELD_LENGTH + 1);
+        assert_eq!(
+            sanitize_text(Some(&long)).map(|s| s.chars().count()),
+            Some(config::MAX_DAPP_METADATA_FIELD_LENGTH)
+        );
+    }
+
+    #[test]
+    fn rejects_non_https_icon_url() {
+        assert_eq!(sanitize_icon_url(Some("http://example.com/icon.png")), None);
+        assert_eq!(sanitize_icon_url(Some("javascript:alert(1)")), None);
+        assert_eq!(
+            sanitize_icon_url(Some("https://example.com/icon.png")),
+            Some("https://example.com/icon.png".to_string())
+        );
+    }
+}
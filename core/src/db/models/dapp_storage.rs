@@ -0,0 +1,515 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use diesel::prelude::*;
+use generic_array::{typenum::U3, GenericArray};
+
+use crate::{
+    config,
+    db::{
+        deterministic_id::{DeriveDeterministicId, DeterministicId, EntityName},
+        models::DataEncryptionKey,
+        schema::{asymmetric_keys, dapp_storage},
+        DeferredTxConnection,
+    },
+    encryption::{
+        DataEncryptionKey as EncDek, EncryptionOutput, KeyEncryptionKey, KeyName,
+        Keychain,
+    },
+    utils::rfc3339_timestamp,
+    Error,
+};
+
+/// Key-value storage of a dapp in a profile. Values are encrypted with a key derived from the
+/// SK-DEK for the profile and bound to the profile, the dapp and the key, so a dapp can only
+/// read the values that it stored in the profile.
+#[derive(Debug)]
+pub struct DappStorage;
+
+impl DappStorage {
+    /// Store a value for a dapp in a profile, replacing the previous value of the key.
+    /// Fails if the dapp would exceed its storage quota.
+    pub fn set(
+        tx_conn: &mut DeferredTxConnection,
+        keychain: &Keychain,
+        profile_id: &DeterministicId,
+        dapp_id: &DeterministicId,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        use dapp_storage::dsl as ds;
+
+        check_key(key)?;
+        check_dapp_added(tx_conn, profile_id, dapp_id)?;
+
+        let used_bytes: usize = dapp_storage::table
+            .filter(ds::profile_id.eq(profile_id))
+            .filter(ds::dapp_id.eq(dapp_id))
+            .filter(ds::key.ne(key))
+            .select((ds::key, ds::value_size))
+            .load::<(String, i32)>(tx_conn.as_mut())?
+            .into_iter()
+            .map(|(key, value_size)| key.len() + value_size as usize)
+            .sum();
+        if used_bytes + key.len() + value.len() > config::MAX_DAPP_STORAGE_BYTES {
+            return Err(Error::User {
+                explanation: format!(
+                    "A dapp can store at most {} bytes in a profile.",
+                    config::MAX_DAPP_STORAGE_BYTES
+                ),
+            });
+        }
+        // Can't overflow, because it's less than the quota.
+        let value_size = value.len() as i32;
+
+        let (dek_id, storage_key) = fetch_storage_key(tx_conn, keychain, profile_id)?;
+        let associated_data = associated_data(profile_id, dapp_id, key);
+        let encrypted_value = storage_key
+            .encrypt_with_associated_data(value.as_bytes(), associated_data.as_bytes())?;
+
+        let entity = DappStorageEntity {
+            profile_id,
+            dapp_id,
+            key,
+        };
+        let updated_at = rfc3339_timestamp();
+        diesel::insert_into(dapp_storage::table)
+            .values((
+                ds::deterministic_id.eq(entity.deterministic_id()?),
+                ds::profile_id.eq(profile_id),
+                ds::dapp_id.eq(dapp_id),
+                ds::key.eq(key),
+                ds::dek_id.eq(&dek_id),
+                ds::encrypted_value.eq(&encrypted_value),
+                ds::value_size.eq(value_size),
+                ds::created_at.eq(&updated_at),
+            ))
+            .on_conflict((ds::profile_id, ds::dapp_id, ds::key))
+            .do_update()
+            .set((
+                ds::dek_id.eq(&dek_id),
+                ds::encrypted_value.eq(&encrypted_value),
+                ds::value_size.eq(value_size),
+                ds::updated_at.eq(&updated_at),
+            ))
+            .execute(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+
+    /// Fetch a value that a dapp stored in a profile. Returns none if the key isn't set.
+    pub fn get(
+        tx_conn: &mut DeferredTxConnection,
+        keychain: &Keychain,
+        profile_id: &DeterministicId,
+        dapp_id: &DeterministicId,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        use dapp_storage::dsl as ds;
+
+        let encrypted_value: Option<EncryptionOutput> = dapp_storage::table
+            .filter(ds::profile_id.eq(profile_id))
+            .filter(ds::dapp_id.eq(dapp_id))
+            .filter(ds::key.eq(key))
+            .select(ds::encrypted_value)
+            .first(tx_conn.as_mut())
+            .optional()?;
+        let encrypted_value = match encrypted_value {
+            Some(encrypted_value) => encrypted_value,
+            None => return Ok(None),
+        };
+
+        let (_, storage_key) = fetch_storage_key(tx_conn, keychain, profile_id)?;
+        let associated_data = associated_data(profile_id, dapp_id, key);
+        let value = storage_key
+            .decrypt_with_associated_data(&encrypted_value, associated_data.as_bytes())?;
+        let value = String::from_utf8(value.to_vec()).map_err(|_| Error::Fatal {
+            error: "Dapp storage value is not valid UTF-8".into(),
+        })?;
+
+        Ok(Some(value))
+    }
+
+    /// Delete a value that a dapp stored in a profile. It's a no-op if the key isn't set.
+    pub fn delete(
+        tx_conn: &mut DeferredTxConnection,
+        profile_id: &DeterministicId,
+        dapp_id: &DeterministicId,
+        key: &str,
+    ) -> Result<(), Error> {
+        use dapp_storage::dsl as ds;
+
+        diesel::delete(
+            dapp_storage::table
+                .filter(ds::profile_id.eq(profile_id))
+                .filter(ds::dapp_id.eq(dapp_id))
+                .filter(ds::key.eq(key)),
+        )
+        .execute(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+
+    /// Delete all values that a dapp stored in a profile. Must be called when the dapp is
+    /// removed from the profile.
+    pub fn delete_for_dapp(
+        tx_conn: &mut DeferredTxConnection,
+        profile_id: &DeterministicId,
+        dapp_id: &DeterministicId,
+    ) -> Result<(), Error> {
+        use dapp_storage::dsl as ds;
+
+        diesel::delete(
+            dapp_storage::table
+                .filter(ds::profile_id.eq(profile_id))
+                .filter(ds::dapp_id.eq(dapp_id)),
+        )
+        .execute(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+}
+
+fn check_key(key: &str) -> Result<(), Error> {
+    if key.is_empty() || key.len() > config::MAX_DAPP_STORAGE_KEY_LENGTH {
+        return Err(Error::User {
+            explanation: format!(
+                "Dapp storage keys must be between 1 and {} bytes long.",
+                config::MAX_DAPP_STORAGE_KEY_LENGTH
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn check_dapp_added(
+    tx_conn: &mut DeferredTxConnection,
+    profile_id: &DeterministicId,
+    dapp_id: &DeterministicId,
+) -> Result<(), Error> {
+    use asymmetric_keys::dsl as ak;
+
+    let key_id: Option<DeterministicId> = asymmetric_keys::table
+        .filter(ak::profile_id.eq(profile_id))
+        .filter(ak::dapp_id.eq(Some(dapp_id)))
+        .select(ak::deterministic_id)
+        .first(tx_conn.as_mut())
+        .optional()?;
+    if key_id.is_none() {
+        return Err(Error::User {
+            explanation: "The dapp hasn't been added to the profile.".into(),
+        });
+    }
+    Ok(())
+}
+
+fn fetch_storage_key(
+    tx_conn: &mut DeferredTxConnection,
+    keychain: &Keychain,
+    profile_id: &DeterministicId,
+) -> Result<(DeterministicId, EncDek), Error> {
+    let sk_kek = KeyEncryptionKey::sk_kek(keychain)?;
+    let (dek_id, sk_dek) = DataEncryptionKey::fetch_dek(
+        tx_conn.as_mut(),
+        KeyName::SkDataEncryptionKey,
+        &sk_kek,
+    )?;
+    let storage_key = sk_dek.derive_dapp_storage_key(profile_id.as_ref())?;
+    Ok((dek_id, storage_key))
+}
+
+/// Binds an encrypted value to its row, so that a value can't be moved to an other dapp or key
+/// in the database.
+fn associated_data(
+    profile_id: &DeterministicId,
+    dapp_id: &DeterministicId,
+    key: &str,
+) -> String {
+    format!("{profile_id}:{dapp_id}:{key}")
+}
+
+#[derive(Debug)]
+struct DappStorageEntity<'a> {
+    profile_id: &'a DeterministicId,
+    dapp_id: &'a DeterministicId,
+    key: &'a str,
+}
+
+impl<'a> DeriveDeterministicId<'a, &'a str, U3> for DappStorageEntity<'a> {
+    fn entity_name(&'a self) -> EntityName {
+        EntityName::DappStorage
+    }
+
+    fn unique_columns(&'a self) -> GenericArray<&'a str, U3> {
+        [self.profile_id.as_ref(), self.dapp_id.as_ref(), self.key].into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{
+        app_core::tests::TmpCore,
+        db::models::{Dapp, LocalSettings},
+    };
+
+    fn setup() -> Result<(TmpCore, DeterministicId, Vec<DeterministicId>)> {
+        let tmp_core = TmpCore::new()?;
+        let mut conn = tmp_core.connection_pool().connection()?;
+        let profile_id = LocalSettings::fetch_active_profile_id(&mut conn)?;
+        let dapp_ids = Dapp::list_for_profile(
+            &mut conn,
+            &profile_id,
+            None,
+            0,
+            config::UNPAGINATED_DAPP_LIST_LIMIT,
+        )?
+        .into_iter()
+        .map(|dapp| dapp.deterministic_id)
+        .collect();
+        Ok((tmp_core, profile_id, dapp_ids))
+    }
+
+    #[test]
+    fn dapps_cannot_read_each_others_values() -> Result<()> {
+        let (tmp_core, profile_id, dapp_ids) = setup()?;
+        let keychain = tmp_core.keychain();
+
+        tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "session",
+                    "first",
+                )?;
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[1],
+                    "session",
+                    "second",
+                )?;
+
+                let first = DappStorage::get(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "session",
+                )?;
+                assert_eq!(first.as_deref(), Some("first"));
+                let second = DappStorage::get(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[1],
+                    "session",
+                )?;
+                assert_eq!(second.as_deref(), Some("second"));
+                let third = DappStorage::get(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[2],
+                    "session",
+                )?;
+                assert_eq!(third, None);
+
+                DappStorage::delete(&mut tx_conn, &profile_id, &dapp_ids[0], "session")?;
+                let first = DappStorage::get(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "session",
+                )?;
+                assert_eq!(first, None);
+                let second = DappStorage::get(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[1],
+                    "session",
+                )?;
+                assert_eq!(second.as_deref(), Some("second"));
+
+                Ok(())
+            })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn value_moved_to_other_dapp_fails_to_decrypt() -> Result<()> {
+        use dapp_storage::dsl as ds;
+
+        let (tmp_core, profile_id, dapp_ids) = setup()?;
+        let keychain = tmp_core.keychain();
+
+        tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "a",
+                    "1",
+                )?;
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[1],
+                    "a",
+                    "2",
+                )?;
+                diesel::update(dapp_storage::table.filter(ds::dapp_id.eq(&dapp_ids[0])))
+                    .set(ds::dapp_id.eq(&dapp_ids[2]))
+                    .execute(tx_conn.as_mut())?;
+
+                let res = DappStorage::get(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[2],
+                    "a",
+                );
+                assert!(res.is_err());
+
+                Ok(())
+            })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn removal_clears_storage() -> Result<()> {
+        let (tmp_core, profile_id, dapp_ids) = setup()?;
+        let keychain = tmp_core.keychain();
+
+        tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "a",
+                    "1",
+                )?;
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "b",
+                    "2",
+                )?;
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[1],
+                    "a",
+                    "3",
+                )?;
+
+                DappStorage::delete_for_dapp(&mut tx_conn, &profile_id, &dapp_ids[0])?;
+
+                for key in ["a", "b"] {
+                    let value = DappStorage::get(
+                        &mut tx_conn,
+                        keychain,
+                        &profile_id,
+                        &dapp_ids[0],
+                        key,
+                    )?;
+                    assert_eq!(value, None);
+                }
+                let value = DappStorage::get(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[1],
+                    "a",
+                )?;
+                assert_eq!(value.as_deref(), Some("3"));
+
+                Ok(())
+            })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn enforces_quota() -> Result<()> {
+        let (tmp_core, profile_id, dapp_ids) = setup()?;
+        let keychain = tmp_core.keychain();
+        let half = "a".repeat(config::MAX_DAPP_STORAGE_BYTES / 2);
+
+        tmp_core
+            .connection_pool()
+            .deferred_transaction(|mut tx_conn| {
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "a",
+                    &half,
+                )?;
+                // Replacing a value doesn't count the previous value.
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "a",
+                    &half,
+                )?;
+                let res = DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "b",
+                    &half,
+                );
+                assert!(matches!(res, Err(Error::User { .. })));
+                // The quota is per dapp.
+                DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[1],
+                    "b",
+                    &half,
+                )?;
+
+                let res = DappStorage::set(
+                    &mut tx_conn,
+                    keychain,
+                    &profile_id,
+                    &dapp_ids[0],
+                    "",
+                    "",
+                );
+                assert!(matches!(res, Err(Error::User { .. })));
+
+                Ok(())
+            })?;
+
+        Ok(())
+    }
+}
@@ -2,10 +2,15 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use diesel::prelude::*;
+use std::str::FromStr;
+
+use diesel::{deserialize::FromSql, prelude::*, serialize::ToSql, sqlite::Sqlite};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
 use crate::{
+    config,
     db::{
         models as m,
         models::AddressId,
@@ -13,7 +18,7 @@ use crate::{
         DeferredTxConnection, DeterministicId, JsonValue,
     },
     protocols::eth,
-    utils::{new_uuid, rfc3339_timestamp},
+    utils::{new_uuid, rfc3339_timestamp, rfc3339_timestamp_seconds_ago},
     Error,
 };
 
@@ -32,6 +37,109 @@ pub struct LocalDappSession {
     pub dapp_human_identifier: String,
 
     pub chain_id: eth::ChainId,
+
+    pub trust_level: DappTrustLevel,
+
+    #[builder(default)]
+    pub spending_limit: Option<DappSpendingLimit>,
+}
+
+/// How much the user trusts a dapp on this device.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    // Diesel traits
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = diesel::sql_types::Text)]
+#[strum(serialize_all = "snake_case")]
+pub enum DappTrustLevel {
+    /// The user has to approve every transaction.
+    #[default]
+    Standard,
+    /// Transactions are approved automatically unless they're high value or flagged as risky.
+    Trusted,
+}
+
+impl FromSql<diesel::sql_types::Text, Sqlite> for DappTrustLevel {
+    fn from_sql(
+        bytes: diesel::backend::RawValue<Sqlite>,
+    ) -> diesel::deserialize::Result<Self> {
+        let s = <String as FromSql<diesel::sql_types::Text, Sqlite>>::from_sql(bytes)?;
+        Ok(Self::from_str(&s)?)
+    }
+}
+
+impl ToSql<diesel::sql_types::Text, Sqlite> for DappTrustLevel {
+    fn to_sql(
+        &self,
+        out: &mut diesel::serialize::Output<Sqlite>,
+    ) -> diesel::serialize::Result {
+        let s = self.to_string();
+        out.set_value(s);
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+/// Cumulative spending cap of a dapp over a rolling window. Transactions that would exceed
+/// the cap require approval even if the dapp is trusted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TypedBuilder)]
+pub struct DappSpendingLimit {
+    /// The length of the rolling window in seconds.
+    pub window_seconds: u64,
+    /// Native token caps per chain. Native tokens on chains without an entry aren't limited.
+    #[serde(default)]
+    #[builder(default)]
+    pub native: Vec<eth::NativeTokenAmount>,
+    /// Fungible token caps. Tokens without an entry aren't limited.
+    #[serde(default)]
+    #[builder(default)]
+    pub tokens: Vec<TokenSpendingLimit>,
+}
+
+impl DappSpendingLimit {
+    /// The cap for the native token if the token address is none or for the fungible token
+    /// otherwise. None if there is no cap.
+    pub fn cap(
+        &self,
+        chain_id: eth::ChainId,
+        token_address: Option<&eth::ChecksumAddress>,
+    ) -> Option<U256> {
+        match token_address {
+            None => self
+                .native
+                .iter()
+                .find(|limit| limit.chain_id == chain_id)
+                .map(|limit| limit.amount),
+            Some(token_address) => self
+                .tokens
+                .iter()
+                .find(|limit| {
+                    limit.chain_id == chain_id && &limit.contract_address == token_address
+                })
+                .map(|limit| limit.amount),
+        }
+    }
+
+    /// A limit without caps doesn't restrict anything.
+    pub fn is_empty(&self) -> bool {
+        self.native.is_empty() && self.tokens.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenSpendingLimit {
+    pub chain_id: eth::ChainId,
+    pub contract_address: eth::ChecksumAddress,
+    /// Amount in the lowest denomination of the token.
+    pub amount: U256,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
@@ -45,6 +153,8 @@ struct LocalDappSessionEntity {
     last_used_at: String,
     created_at: String,
     updated_at: String,
+    trust_level: DappTrustLevel,
+    spending_limit: Option<JsonValue>,
 }
 
 type AllColumns = (
@@ -54,6 +164,8 @@ type AllColumns = (
     local_dapp_sessions::last_used_at,
     local_dapp_sessions::created_at,
     local_dapp_sessions::updated_at,
+    local_dapp_sessions::trust_level,
+    local_dapp_sessions::spending_limit,
 );
 
 const ALL_COLUMNS: AllColumns = (
@@ -63,6 +175,8 @@ const ALL_COLUMNS: AllColumns = (
     local_dapp_sessions::last_used_at,
     local_dapp_sessions::created_at,
     local_dapp_sessions::updated_at,
+    local_dapp_sessions::trust_level,
+    local_dapp_sessions::spending_limit,
 );
 
 impl LocalDappSessionEntity {
@@ -246,8 +360,14 @@ impl LocalDappSession {
             uuid,
             address_id,
             dapp_id,
+            trust_level,
+            spending_limit,
             ..
         } = entity;
+        let spending_limit: Option<DappSpendingLimit> = match spending_limit {
+            Some(spending_limit) => Some(spending_limit.convert_into()?),
+            None => None,
+        };
         let session = LocalDappSession::builder()
             .uuid(uuid)
             .address_id(address_id)
@@ -256,6 +376,8 @@ impl LocalDappSession {
             .chain_id(chain_id)
             .profile_id(profile_id)
             .address(address)
+            .trust_level(trust_level)
+            .spending_limit(spending_limit)
             .build();
         Ok(session)
     }
@@ -302,6 +424,47 @@ impl LocalDappSession {
         self.update_session_address(tx_conn, &new_address_id)
     }
 
+    /// Set the trust level of the dapp on this device.
+    pub fn set_trust_level(
+        self,
+        tx_conn: &mut DeferredTxConnection,
+        trust_level: DappTrustLevel,
+    ) -> Result<Self, Error> {
+        use local_dapp_sessions::dsl as lds;
+
+        diesel::update(local_dapp_sessions::table.filter(lds::uuid.eq(&self.uuid)))
+            .set((
+                lds::trust_level.eq(trust_level),
+                lds::updated_at.eq(rfc3339_timestamp()),
+            ))
+            .execute(tx_conn.as_mut())?;
+
+        Self::fetch_session_by_id(tx_conn, &self.uuid)
+    }
+
+    /// Set the spending limit of the dapp on this device. None or a limit without caps
+    /// removes the limit.
+    pub fn set_spending_limit(
+        self,
+        tx_conn: &mut DeferredTxConnection,
+        spending_limit: Option<&DappSpendingLimit>,
+    ) -> Result<Self, Error> {
+        use local_dapp_sessions::dsl as lds;
+
+        let spending_limit = match spending_limit {
+            Some(limit) if !limit.is_empty() => Some(JsonValue::convert_from(limit)?),
+            _ => None,
+        };
+        diesel::update(local_dapp_sessions::table.filter(lds::uuid.eq(&self.uuid)))
+            .set((
+                lds::spending_limit.eq(spending_limit),
+                lds::updated_at.eq(rfc3339_timestamp()),
+            ))
+            .execute(tx_conn.as_mut())?;
+
+        Self::fetch_session_by_id(tx_conn, &self.uuid)
+    }
+
     pub fn update_last_used_at(
         self,
         tx_conn: &mut DeferredTxConnection,
@@ -314,6 +477,28 @@ impl LocalDappSession {
 
         Self::fetch_session_by_id(tx_conn, &self.uuid)
     }
+
+    /// Record that the dapp made a request. Unlike `update_last_used_at`, the DB is only
+    /// written if the last use is older than `config::DAPP_LAST_USED_RESOLUTION_SECONDS`.
+    /// Returns whether the last use was updated.
+    pub fn touch_last_used(
+        tx_conn: &mut DeferredTxConnection,
+        session_id: &str,
+    ) -> Result<bool, Error> {
+        use local_dapp_sessions::dsl as lds;
+
+        let threshold =
+            rfc3339_timestamp_seconds_ago(config::DAPP_LAST_USED_RESOLUTION_SECONDS);
+        let updated = diesel::update(
+            local_dapp_sessions::table
+                .filter(lds::uuid.eq(session_id))
+                .filter(lds::last_used_at.lt(threshold)),
+        )
+        .set(lds::last_used_at.eq(rfc3339_timestamp()))
+        .execute(tx_conn.as_mut())?;
+
+        Ok(updated > 0)
+    }
 }
 
 #[derive(Debug, Clone, TypedBuilder)]
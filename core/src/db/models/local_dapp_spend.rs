@@ -0,0 +1,156 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use diesel::prelude::*;
+use ethers::types::U256;
+
+use crate::{
+    db::{models as m, schema::local_dapp_spends, DeferredTxConnection, DeterministicId},
+    protocols::eth,
+    utils::{new_uuid, rfc3339_timestamp, rfc3339_timestamp_seconds_ago},
+    Error,
+};
+
+/// Amounts spent by dapps on this device to enforce spending limits.
+/// Not synced, because spending limits are set per device.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
+#[diesel(primary_key(uuid))]
+pub struct LocalDappSpend {
+    pub uuid: String,
+    pub session_id: String,
+    pub chain_id: DeterministicId,
+    /// None for the native token.
+    pub token_address: Option<eth::ChecksumAddress>,
+    /// Decimal amount in the lowest denomination of the token.
+    pub amount: String,
+    pub created_at: String,
+}
+
+impl LocalDappSpend {
+    /// Record an amount spent by a transaction submitted by the dapp in the session. Returns
+    /// the uuid of the record.
+    pub fn create(
+        tx_conn: &mut DeferredTxConnection,
+        session_id: &str,
+        spend: &eth::TransactionSpend,
+    ) -> Result<String, Error> {
+        use local_dapp_spends::dsl as lds;
+
+        let uuid = new_uuid();
+        let chain_id = m::Chain::fetch_or_create_eth_chain_id(tx_conn, spend.chain_id)?;
+        diesel::insert_into(local_dapp_spends::table)
+            .values((
+                lds::uuid.eq(&uuid),
+                lds::session_id.eq(session_id),
+                lds::chain_id.eq(&chain_id),
+                lds::token_address.eq(spend.token_address.as_ref()),
+                lds::amount.eq(spend.amount.to_string()),
+                lds::created_at.eq(rfc3339_timestamp()),
+            ))
+            .execute(tx_conn.as_mut())?;
+
+        Ok(uuid)
+    }
+
+    /// Reserve the spends of a transaction before submitting it. If `enforce_limit` is true,
+    /// nothing is reserved and none is returned if the spends would exceed the limit. Must be
+    /// called in an exclusive transaction, so that concurrent transactions can't exceed the
+    /// limit together. Returns the uuids of the records to release them with if the
+    /// transaction couldn't be submitted.
+    pub fn reserve(
+        tx_conn: &mut DeferredTxConnection,
+        session_id: &str,
+        spending_limit: Option<&m::DappSpendingLimit>,
+        spends: &[eth::TransactionSpend],
+        enforce_limit: bool,
+    ) -> Result<Option<Vec<String>>, Error> {
+        if enforce_limit {
+            if let Some(spending_limit) = spending_limit {
+                if Self::exceeds_limit(tx_conn, session_id, spending_limit, spends)? {
+                    return Ok(None);
+                }
+            }
+        }
+        let uuids = spends
+            .iter()
+            .map(|spend| Self::create(tx_conn, session_id, spend))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Some(uuids))
+    }
+
+    /// Delete reserved spends of a transaction that couldn't be submitted.
+    pub fn release(
+        tx_conn: &mut DeferredTxConnection,
+        uuids: &[String],
+    ) -> Result<(), Error> {
+        use local_dapp_spends::dsl as lds;
+
+        diesel::delete(local_dapp_spends::table.filter(lds::uuid.eq_any(uuids)))
+            .execute(tx_conn.as_mut())?;
+        Ok(())
+    }
+
+    /// Whether the spends would take the dapp over its spending limit in the current window.
+    pub fn exceeds_limit(
+        tx_conn: &mut DeferredTxConnection,
+        session_id: &str,
+        spending_limit: &m::DappSpendingLimit,
+        spends: &[eth::TransactionSpend],
+    ) -> Result<bool, Error> {
+        for spend in spends.iter() {
+            let cap =
+                match spending_limit.cap(spend.chain_id, spend.token_address.as_ref()) {
+                    Some(cap) => cap,
+                    None => continue,
+                };
+            let spent = Self::fetch_total_in_window(
+                tx_conn,
+                session_id,
+                spend.chain_id,
+                spend.token_address.as_ref(),
+                spending_limit.window_seconds,
+            )?;
+            match spent.checked_add(spend.amount) {
+                Some(total) if total <= cap => {}
+                _ => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    /// The total amount of the token spent by the dapp in the session on the chain within the
+    /// last `window_seconds`. The token address is none for the native token.
+    pub fn fetch_total_in_window(
+        tx_conn: &mut DeferredTxConnection,
+        session_id: &str,
+        chain_id: eth::ChainId,
+        token_address: Option<&eth::ChecksumAddress>,
+        window_seconds: u64,
+    ) -> Result<U256, Error> {
+        use local_dapp_spends::dsl as lds;
+
+        let chain_id = m::Chain::fetch_or_create_eth_chain_id(tx_conn, chain_id)?;
+        let since = rfc3339_timestamp_seconds_ago(window_seconds);
+
+        let query = local_dapp_spends::table
+            .filter(lds::session_id.eq(session_id))
+            .filter(lds::chain_id.eq(&chain_id))
+            .filter(lds::created_at.ge(&since))
+            .select(lds::amount)
+            .into_boxed();
+        let query = match token_address {
+            Some(token_address) => query.filter(lds::token_address.eq(token_address)),
+            None => query.filter(lds::token_address.is_null()),
+        };
+        let amounts: Vec<String> = query.load(tx_conn.as_mut())?;
+
+        amounts.iter().try_fold(U256::zero(), |total, amount| {
+            let amount = U256::from_dec_str(amount).map_err(|err| Error::Fatal {
+                error: format!("Invalid spend amount in DB: {err}"),
+            })?;
+            // Overflow means that the limit is definitely exceeded.
+            Ok(total.saturating_add(amount))
+        })
+    }
+}
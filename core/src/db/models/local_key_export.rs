@@ -0,0 +1,72 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use diesel::prelude::*;
+
+use crate::{
+    db::{models as m, schema::local_key_exports, DeferredTxConnection},
+    utils::{new_uuid, rfc3339_timestamp, rfc3339_timestamp_seconds_ago},
+    Error,
+};
+
+/// Private key exports on this device for auditing and rate limiting.
+/// Never contains key material.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
+#[diesel(primary_key(uuid))]
+pub struct LocalKeyExport {
+    pub uuid: String,
+    pub address_id: m::AddressId,
+    pub created_at: String,
+}
+
+impl LocalKeyExport {
+    /// Record that the private key of the address was exported.
+    pub fn create(
+        tx_conn: &mut DeferredTxConnection,
+        address_id: &m::AddressId,
+    ) -> Result<(), Error> {
+        use local_key_exports::dsl as lke;
+
+        diesel::insert_into(local_key_exports::table)
+            .values((
+                lke::uuid.eq(new_uuid()),
+                lke::address_id.eq(address_id),
+                lke::created_at.eq(rfc3339_timestamp()),
+            ))
+            .execute(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+
+    /// The number of private key exports within the last `window_seconds`.
+    pub fn count_in_window(
+        conn: &mut SqliteConnection,
+        window_seconds: u64,
+    ) -> Result<i64, Error> {
+        use local_key_exports::dsl as lke;
+
+        let since = rfc3339_timestamp_seconds_ago(window_seconds);
+        let count: i64 = local_key_exports::table
+            .filter(lke::created_at.ge(&since))
+            .count()
+            .get_result(conn)?;
+
+        Ok(count)
+    }
+
+    /// List the exports of the address, oldest first.
+    pub fn list_for_address(
+        conn: &mut SqliteConnection,
+        address_id: &m::AddressId,
+    ) -> Result<Vec<Self>, Error> {
+        use local_key_exports::dsl as lke;
+
+        let exports = local_key_exports::table
+            .filter(lke::address_id.eq(address_id))
+            .order(lke::created_at.asc())
+            .load::<Self>(conn)?;
+
+        Ok(exports)
+    }
+}
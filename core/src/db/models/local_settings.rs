@@ -3,32 +3,56 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use diesel::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
     backup::BackupVersion,
-    db::{schema::local_settings, DeferredTxConnection, DeterministicId},
+    db::{
+        schema::{local_settings, local_settings_values},
+        DeferredTxConnection, DeterministicId, ExclusiveTxConnection, JsonValue,
+    },
     encryption::KdfNonce,
     utils::rfc3339_timestamp,
     Error,
 };
 
-// numeric_expr!(local_settings::dsl::backup_version);
-
 #[derive(Debug, PartialEq, Eq, Queryable, Identifiable)]
 #[diesel(table_name = local_settings)]
 pub struct LocalSettings {
     pub id: String,
     pub profile_id: DeterministicId,
     pub backup_enabled: bool,
-    /// Monotonously increasing, but may have gaps.
-    pub backup_version: i64,
-    pub backup_completed_at: Option<String>,
     pub backup_password_updated_at: Option<String>,
     pub backup_kdf_nonce: Option<Vec<u8>>,
+    /// Unix timestamp of the most recent backup restored on this device.
+    pub last_restored_backup_timestamp: Option<i64>,
 }
 
 const SINGLETON_ID: &str = "local_settings";
 
+/// Keys of typed settings in the `local_settings_values` table. The value type of a key is
+/// determined by the accessor, eg. `LocalSettings::fetch_backup_version`.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    strum_macros::AsRefStr,
+    strum_macros::Display,
+    strum_macros::EnumString,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum SettingKey {
+    // The serializations must not be changed as they're the keys in the DB.
+    /// `i64` Monotonously increasing, but may have gaps.
+    BackupVersion,
+    /// `String` RFC 3339 timestamp of the last completed backup.
+    BackupCompletedAt,
+    #[cfg(test)]
+    TestValue,
+}
+
 impl LocalSettings {
     pub fn create(
         connection: &mut SqliteConnection,
@@ -67,32 +91,86 @@ impl LocalSettings {
         Ok(())
     }
 
-    // Returns the new version
-    pub fn increment_backup_version(
+    /// Fetch a setting value. Returns none if the setting hasn't been set.
+    pub fn get<T: DeserializeOwned>(
         connection: &mut SqliteConnection,
-    ) -> Result<(), Error> {
-        use local_settings::dsl as ls;
+        key: SettingKey,
+    ) -> Result<Option<T>, Error> {
+        use local_settings_values::dsl as lsv;
 
-        diesel::update(local_settings::table.find(&SINGLETON_ID))
-            .set(ls::backup_version.eq(ls::backup_version + 1))
+        let value: Option<JsonValue> = local_settings_values::table
+            .find(key.as_ref())
+            .select(lsv::value)
+            .first(connection)
+            .optional()?;
+
+        value.map(|value| value.convert_into()).transpose()
+    }
+
+    /// Insert or update a setting value.
+    pub fn set<T: Serialize>(
+        connection: &mut SqliteConnection,
+        key: SettingKey,
+        value: &T,
+    ) -> Result<(), Error> {
+        use local_settings_values::dsl as lsv;
+
+        let value = JsonValue::convert_from(value)?;
+        let now = rfc3339_timestamp();
+
+        diesel::insert_into(local_settings_values::table)
+            .values((
+                lsv::key.eq(key.as_ref()),
+                lsv::value.eq(&value),
+                lsv::updated_at.eq(&now),
+            ))
+            .on_conflict(lsv::key)
+            .do_update()
+            .set((lsv::value.eq(&value), lsv::updated_at.eq(&now)))
             .execute(connection)?;
 
         Ok(())
     }
 
-    pub fn fetch_backup_version(
+    /// Remove a setting value. No-op if the setting hasn't been set.
+    pub fn remove(
         connection: &mut SqliteConnection,
-    ) -> Result<BackupVersion, Error> {
-        use local_settings::dsl as ls;
+        key: SettingKey,
+    ) -> Result<(), Error> {
+        diesel::delete(local_settings_values::table.find(key.as_ref()))
+            .execute(connection)?;
+        Ok(())
+    }
 
-        let backup_version: i64 = local_settings::table
-            .find(&SINGLETON_ID)
-            .select(ls::backup_version)
-            .first(connection)?;
+    /// Increment the backup version and return the new version.
+    /// All changes that require a new backup version should go through this to keep the version
+    /// monotonically increasing. Exclusive transaction, because it reads before writing.
+    pub fn increment_backup_version(
+        tx_conn: &mut ExclusiveTxConnection,
+    ) -> Result<BackupVersion, Error> {
+        let backup_version = Self::fetch_backup_version(tx_conn.as_mut())?.next()?;
+        Self::set_backup_version(tx_conn.as_mut(), backup_version)?;
+        Ok(backup_version)
+    }
 
+    pub fn fetch_backup_version(
+        connection: &mut SqliteConnection,
+    ) -> Result<BackupVersion, Error> {
+        let backup_version: i64 =
+            Self::get(connection, SettingKey::BackupVersion)?.unwrap_or_default();
         backup_version.try_into()
     }
 
+    /// Used when restoring a backup from an other device to start a new version chain for
+    /// the current device.
+    pub fn set_backup_version(
+        connection: &mut SqliteConnection,
+        backup_version: BackupVersion,
+    ) -> Result<(), Error> {
+        let backup_version: i64 = backup_version.into();
+        Self::set(connection, SettingKey::BackupVersion, &backup_version)
+    }
+
     pub fn fetch_backup_enabled(
         connection: &mut SqliteConnection,
     ) -> Result<bool, Error> {
@@ -154,28 +232,45 @@ impl LocalSettings {
     pub fn update_backup_timestamp(
         connection: &mut SqliteConnection,
     ) -> Result<(), Error> {
-        use local_settings::dsl as ls;
-
-        diesel::update(local_settings::table.find(&SINGLETON_ID))
-            .set(ls::backup_completed_at.eq(rfc3339_timestamp()))
-            .execute(connection)?;
-
-        Ok(())
+        Self::set(
+            connection,
+            SettingKey::BackupCompletedAt,
+            &rfc3339_timestamp(),
+        )
     }
 
     pub fn fetch_backup_timestamp(
         connection: &mut SqliteConnection,
     ) -> Result<Option<String>, Error> {
+        Self::get(connection, SettingKey::BackupCompletedAt)
+    }
+
+    pub fn fetch_last_restored_backup_timestamp(
+        connection: &mut SqliteConnection,
+    ) -> Result<Option<i64>, Error> {
         use local_settings::dsl as ls;
 
-        let timestamp: Option<String> = local_settings::table
+        let timestamp: Option<i64> = local_settings::table
             .find(&SINGLETON_ID)
-            .select(ls::backup_completed_at)
+            .select(ls::last_restored_backup_timestamp)
             .first(connection)?;
 
         Ok(timestamp)
     }
 
+    pub fn set_last_restored_backup_timestamp(
+        connection: &mut SqliteConnection,
+        timestamp: i64,
+    ) -> Result<(), Error> {
+        use local_settings::dsl as ls;
+
+        diesel::update(local_settings::table.find(&SINGLETON_ID))
+            .set(ls::last_restored_backup_timestamp.eq(Some(timestamp)))
+            .execute(connection)?;
+
+        Ok(())
+    }
+
     pub fn update_backup_password_timestamp(
         connection: &mut SqliteConnection,
     ) -> Result<(), Error> {
@@ -201,9 +296,76 @@ impl LocalSettings {
         diesel::update(local_settings::table.find(&SINGLETON_ID))
             .set(ls::backup_password_updated_at.eq(&updated_at))
             .execute(tx_conn.as_mut())?;
-        diesel::update(local_settings::table.find(&SINGLETON_ID))
-            .set(ls::backup_completed_at.eq(&updated_at))
-            .execute(tx_conn.as_mut())?;
+        Self::remove(tx_conn.as_mut(), SettingKey::BackupCompletedAt)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::app_core::tests::TmpCore;
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct TestSetting {
+        name: String,
+        count: u32,
+    }
+
+    fn round_trip<T>(value: T) -> Result<()>
+    where
+        T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let tmp = TmpCore::new()?;
+        let mut conn = tmp.connection_pool().connection()?;
+        let key = SettingKey::TestValue;
+
+        assert_eq!(LocalSettings::get::<T>(&mut conn, key)?, None);
+        LocalSettings::set(&mut conn, key, &value)?;
+        assert_eq!(LocalSettings::get::<T>(&mut conn, key)?, Some(value));
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_int() -> Result<()> {
+        round_trip(i64::MAX)
+    }
+
+    #[test]
+    fn round_trips_string() -> Result<()> {
+        round_trip("2023-03-28T09:00:00Z".to_string())
+    }
+
+    #[test]
+    fn round_trips_bool() -> Result<()> {
+        round_trip(true)
+    }
+
+    #[test]
+    fn round_trips_json() -> Result<()> {
+        round_trip(TestSetting {
+            name: "foo".into(),
+            count: 42,
+        })
+    }
+
+    #[test]
+    fn set_overwrites_and_remove_clears() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let mut conn = tmp.connection_pool().connection()?;
+        let key = SettingKey::TestValue;
+
+        LocalSettings::set(&mut conn, key, &1)?;
+        LocalSettings::set(&mut conn, key, &2)?;
+        assert_eq!(LocalSettings::get::<i64>(&mut conn, key)?, Some(2));
+
+        LocalSettings::remove(&mut conn, key)?;
+        assert_eq!(LocalSettings::get::<i64>(&mut conn, key)?, None);
 
         Ok(())
     }
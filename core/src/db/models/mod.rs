@@ -10,32 +10,45 @@ mod address;
 mod asymmetric_key;
 mod chain;
 mod dapp;
+mod dapp_favicon;
+mod dapp_metadata;
+mod dapp_storage;
 mod data_encryption_key;
 mod data_migration;
 mod local_dapp_session;
+mod local_dapp_spend;
 mod local_encrypted_dek;
+mod local_key_export;
 mod local_settings;
 mod profile;
 mod profile_picture;
 mod token;
+mod transaction;
 
 #[allow(deprecated)]
 pub use account::{Account, AccountEntity, AccountParams};
 #[allow(deprecated)]
-pub use account_picture::{AccountPicture, AccountPictureEntity};
+pub use account_picture::AccountPictureEntity;
 pub use address::{
     Address, AddressEntity, AddressId, CreateEthAddressParams, ListAddressesForDappParams,
 };
 pub use asymmetric_key::{AsymmetricKey, NewAsymmetricKey};
 pub use chain::{Chain, EthChain};
 pub use dapp::Dapp;
+pub use dapp_favicon::DappFavicon;
+pub use dapp_metadata::{DappMetadata, DappMetadataParams};
+pub use dapp_storage::DappStorage;
 pub use data_encryption_key::{DataEncryptionKey, NewDataEncryptionKey};
 pub use data_migration::{DataMigration, NewDataMigration};
 pub use local_dapp_session::{
-    DappSessionParams, FetchDappSessionParams, LocalDappSession, NewDappSessionParams,
+    DappSessionParams, DappSpendingLimit, DappTrustLevel, FetchDappSessionParams,
+    LocalDappSession, NewDappSessionParams, TokenSpendingLimit,
 };
+pub use local_dapp_spend::LocalDappSpend;
 pub use local_encrypted_dek::{LocalEncryptedDek, NewLocalEncryptedDek};
-pub use local_settings::LocalSettings;
-pub use profile::{Profile, ProfileEntity, ProfileName};
+pub use local_key_export::LocalKeyExport;
+pub use local_settings::{LocalSettings, SettingKey};
+pub use profile::{Profile, ProfileEntity, ProfileName, ProfileSettings};
 pub use profile_picture::{ProfilePicture, ProfilePictureEntity};
 pub use token::Token;
+pub use transaction::{NewEthTransactionParams, Transaction};
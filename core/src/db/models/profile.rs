@@ -15,9 +15,10 @@ use crate::{
         deterministic_id::{DeriveDeterministicId, EntityName},
         models as m,
         schema::profiles,
-        DeferredTxConnection, DeterministicId,
+        DeferredTxConnection, DeterministicId, JsonValue,
     },
     encryption::Keychain,
+    fiat::FiatCurrency,
     protocols::eth,
     utils::{new_uuid, rfc3339_timestamp},
     Error,
@@ -32,23 +33,85 @@ pub struct Profile {
     pub picture_id: DeterministicId,
     pub created_at: String,
     pub updated_at: Option<String>,
+    pub user_settings: JsonValue,
+    /// Position of the profile in listings chosen by the user. Ascending.
+    pub sort_order: i32,
 }
 
 impl Profile {
+    /// List all profiles in the order chosen by the user.
     pub fn list_all(conn: &mut SqliteConnection) -> Result<Vec<Profile>, Error> {
-        Ok(profiles::table.load::<Profile>(conn)?)
+        use profiles::dsl as p;
+
+        let profiles = profiles::table
+            .order((p::sort_order.asc(), p::created_at.asc()))
+            .load::<Profile>(conn)?;
+        Ok(profiles)
+    }
+
+    pub fn fetch(
+        conn: &mut SqliteConnection,
+        deterministic_id: &DeterministicId,
+    ) -> Result<Option<Profile>, Error> {
+        use profiles::dsl as p;
+
+        let profile = profiles::table
+            .filter(p::deterministic_id.eq(deterministic_id))
+            .first(conn)
+            .optional()?;
+
+        Ok(profile)
+    }
+
+    /// Persist the order of the profiles. The ids must contain each profile exactly once.
+    pub fn reorder(
+        tx_conn: &mut DeferredTxConnection,
+        ordered_ids: &[DeterministicId],
+    ) -> Result<(), Error> {
+        use profiles::dsl as p;
+
+        let mut existing_ids: Vec<DeterministicId> = profiles::table
+            .select(p::deterministic_id)
+            .load(tx_conn.as_mut())?;
+        let mut requested_ids = ordered_ids.to_vec();
+        existing_ids.sort();
+        requested_ids.sort();
+        if existing_ids != requested_ids {
+            return Err(Error::User {
+                explanation: "The profile ids must contain each profile exactly once."
+                    .into(),
+            });
+        }
+
+        for (sort_order, profile_id) in ordered_ids.iter().enumerate() {
+            let sort_order: i32 = sort_order.try_into().map_err(|_| Error::Fatal {
+                error: "Too many profiles to reorder".into(),
+            })?;
+            diesel::update(profiles::table.filter(p::deterministic_id.eq(profile_id)))
+                .set((
+                    p::sort_order.eq(sort_order),
+                    p::updated_at.eq(rfc3339_timestamp()),
+                ))
+                .execute(tx_conn.as_mut())?;
+        }
+
+        Ok(())
     }
 
     /// Create a new profile with Ethereum protocol wallet addresses and return the profile's
-    /// deterministic id.
+    /// deterministic id. The picture is a bundled or host registered profile picture.
     pub fn create_eth_profile(
         tx_conn: &mut DeferredTxConnection,
         keychain: &Keychain,
         name: &ProfileName,
         bundled_picture_name: &str,
+        bundled_picture: &[u8],
     ) -> Result<DeterministicId, Error> {
-        let picture_id =
-            m::ProfilePicture::insert_bundled(tx_conn.as_mut(), bundled_picture_name)?;
+        let picture_id = m::ProfilePicture::insert_bundled(
+            tx_conn.as_mut(),
+            bundled_picture_name,
+            bundled_picture,
+        )?;
         let profile_id = Self::create(tx_conn.as_mut(), name, &picture_id)?;
 
         let create_params = m::CreateEthAddressParams::builder()
@@ -74,6 +137,11 @@ impl Profile {
         let entity = ProfileEntity { uuid: &uuid };
         let deterministic_id = entity.deterministic_id()?;
         let created_at = rfc3339_timestamp();
+        // New profiles are appended
+        let max_sort_order: Option<i32> = profiles::table
+            .select(diesel::dsl::max(p::sort_order))
+            .first(conn)?;
+        let sort_order = max_sort_order.map(|max| max + 1).unwrap_or_default();
 
         diesel::insert_into(profiles::table)
             .values((
@@ -82,6 +150,7 @@ impl Profile {
                 p::name.eq(name.as_ref()),
                 p::picture_id.eq(picture_id),
                 p::created_at.eq(&created_at),
+                p::sort_order.eq(sort_order),
             ))
             .execute(conn)?;
 
@@ -123,6 +192,37 @@ impl Profile {
         Ok(())
     }
 
+    pub fn fetch_user_settings(
+        conn: &mut SqliteConnection,
+        deterministic_id: &DeterministicId,
+    ) -> Result<ProfileSettings, Error> {
+        use profiles::dsl as p;
+
+        let settings: JsonValue = profiles::table
+            .filter(p::deterministic_id.eq(deterministic_id))
+            .select(p::user_settings)
+            .first(conn)?;
+        settings.convert_into()
+    }
+
+    pub fn set_user_settings(
+        conn: &mut SqliteConnection,
+        deterministic_id: &DeterministicId,
+        settings: &ProfileSettings,
+    ) -> Result<(), Error> {
+        use profiles::dsl as p;
+
+        let settings = JsonValue::convert_from(settings)?;
+        diesel::update(profiles::table.filter(p::deterministic_id.eq(deterministic_id)))
+            .set((
+                p::user_settings.eq(&settings),
+                p::updated_at.eq(rfc3339_timestamp()),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
     /// Deprecated, because UUID should be stable. Only used in data migration to update temporary
     /// uuid.
     #[deprecated]
@@ -139,6 +239,61 @@ impl Profile {
     }
 }
 
+/// User settings for a profile
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileSettings {
+    /// Transfers and transactions above these amounts require extra confirmation.
+    /// Chains without an entry use the chain's default threshold.
+    #[serde(default)]
+    pub eth_high_value_thresholds: Vec<eth::NativeTokenAmount>,
+    /// The currency to display fiat values in. None means the app default.
+    #[serde(default)]
+    pub fiat_currency: Option<FiatCurrency>,
+    /// Whether to discover new tokens of the wallet from its transfers when its tokens are
+    /// refreshed.
+    #[serde(default)]
+    pub auto_discover_tokens: bool,
+}
+
+impl ProfileSettings {
+    pub fn eth_high_value_threshold(
+        &self,
+        chain_id: eth::ChainId,
+    ) -> eth::NativeTokenAmount {
+        self.eth_high_value_thresholds
+            .iter()
+            .find(|threshold| threshold.chain_id == chain_id)
+            .cloned()
+            .unwrap_or_else(|| chain_id.default_high_value_threshold())
+    }
+
+    pub fn set_eth_high_value_threshold(&mut self, threshold: eth::NativeTokenAmount) {
+        self.eth_high_value_thresholds
+            .retain(|t| t.chain_id != threshold.chain_id);
+        self.eth_high_value_thresholds.push(threshold);
+    }
+
+    /// Whether the amount is above the high value threshold for its chain.
+    pub fn is_eth_high_value(&self, amount: &eth::NativeTokenAmount) -> bool {
+        amount.amount > self.eth_high_value_threshold(amount.chain_id).amount
+    }
+
+    /// The preferred fiat currency of the profile or the default if it's not set.
+    pub fn fiat_currency_or(&self, default: &FiatCurrency) -> FiatCurrency {
+        self.fiat_currency
+            .clone()
+            .unwrap_or_else(|| default.clone())
+    }
+
+    pub fn set_fiat_currency(&mut self, currency: FiatCurrency) {
+        self.fiat_currency = Some(currency);
+    }
+
+    pub fn set_auto_discover_tokens(&mut self, enabled: bool) {
+        self.auto_discover_tokens = enabled;
+    }
+}
+
 /// The device name that let's the user identifies the device.
 #[derive(
     Debug, Display, Clone, Eq, PartialEq, Hash, Into, AsRef, Serialize, Deserialize,
@@ -213,6 +368,42 @@ mod tests {
         assert!(matches!(res, Err(Error::User { .. })))
     }
 
+    #[test]
+    fn high_value_threshold_override() -> Result<(), Error> {
+        let chain_id = eth::ChainId::EthMainnet;
+        let mut settings = ProfileSettings::default();
+        assert_eq!(
+            settings.eth_high_value_threshold(chain_id),
+            chain_id.default_high_value_threshold()
+        );
+
+        let threshold = eth::NativeTokenAmount::new_from_decimal(chain_id, "0.5")?;
+        settings.set_eth_high_value_threshold(threshold.clone());
+        assert_eq!(settings.eth_high_value_threshold(chain_id), threshold);
+
+        let below = eth::NativeTokenAmount::new_from_decimal(chain_id, "0.5")?;
+        assert!(!settings.is_eth_high_value(&below));
+        let above = eth::NativeTokenAmount::new_from_decimal(chain_id, "0.6")?;
+        assert!(settings.is_eth_high_value(&above));
+
+        // Other chains are unaffected
+        let other_chain = eth::ChainId::PolygonMainnet;
+        assert_eq!(
+            settings.eth_high_value_threshold(other_chain),
+            other_chain.default_high_value_threshold()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_empty_settings() -> Result<(), Error> {
+        let settings: ProfileSettings =
+            JsonValue::new(serde_json::json!({})).convert_into()?;
+        assert_eq!(settings, ProfileSettings::default());
+        Ok(())
+    }
+
     #[test]
     fn profile_name_with_emoji_count() {
         // Edge case: too long when counting bytes, ok when counting chars.
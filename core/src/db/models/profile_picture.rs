@@ -6,14 +6,17 @@ use std::fmt::Debug;
 
 use diesel::{prelude::*, SqliteConnection};
 use generic_array::{typenum::U1, GenericArray};
+use url::Url;
 
 use crate::{
-    assets::load_profile_pic,
+    assets::{load_profile_pic, validate_profile_pic},
+    async_runtime as rt, config,
     db::{
         deterministic_id::{DeriveDeterministicId, EntityName},
         schema::profile_pictures,
         DeterministicId,
     },
+    http_client::{HttpClient, HttpClientError},
     utils::{blake3_hash, rfc3339_timestamp},
     Error,
 };
@@ -34,6 +37,20 @@ impl ProfilePicture {
         Ok(profile_pictures::table.load::<ProfilePicture>(conn)?)
     }
 
+    pub fn fetch(
+        conn: &mut SqliteConnection,
+        id: &DeterministicId,
+    ) -> Result<Option<ProfilePicture>, Error> {
+        use profile_pictures::dsl as pp;
+
+        let picture = profile_pictures::table
+            .filter(pp::deterministic_id.eq(id))
+            .first(conn)
+            .optional()?;
+
+        Ok(picture)
+    }
+
     pub fn list_names(conn: &mut SqliteConnection) -> Result<Vec<String>, Error> {
         use profile_pictures::dsl as pp;
 
@@ -57,17 +74,104 @@ impl ProfilePicture {
         Ok(image)
     }
 
-    /// Insert a bundled profile picture into the database and return its deterministic id.
+    /// Insert a bundled or host registered profile picture by its name into the database and
+    /// return its deterministic id.
     pub fn insert_bundled(
         conn: &mut SqliteConnection,
         image_name: &str,
+        image: &[u8],
+    ) -> Result<DeterministicId, Error> {
+        let image_hash = blake3_hash(image);
+        let entity = ProfilePictureEntity {
+            image_hash: image_hash.as_bytes(),
+        };
+        entity.create(conn, image, Some(image_name))
+    }
+
+    /// Download an image and insert it as a profile picture, eg. to use a web avatar as profile
+    /// picture. Returns the deterministic id of the picture. The image must be a valid profile
+    /// picture like registered pictures. Don't call it in a transaction, because the download
+    /// can take up to `config::HTTP_FETCH_TIMEOUT`.
+    pub fn insert_from_url(
+        conn: &mut SqliteConnection,
+        http_client: &HttpClient,
+        url: Url,
+    ) -> Result<DeterministicId, Error> {
+        let fetch = http_client.fetch_bounded(
+            url,
+            config::MAX_PROFILE_PIC_SIZE_BYTES,
+            config::HTTP_FETCH_TIMEOUT,
+        );
+        let image = rt::block_on(fetch).map_err(|err| match err {
+            HttpClientError::UnsupportedScheme => Error::User {
+                explanation: err.to_string(),
+            },
+            HttpClientError::ResponseTooLarge { max_bytes } => Error::User {
+                explanation: format!(
+                    "The image must be at most {} KiB.",
+                    max_bytes / 1024
+                ),
+            },
+            err => Error::Retriable {
+                error: format!("Failed to download profile picture: {err}"),
+            },
+        })?;
+        validate_profile_pic(&image).map_err(|_| Error::User {
+            explanation: format!(
+                "The URL must point to a PNG image of at most {px}x{px} pixels.",
+                px = config::MAX_PROFILE_PIC_DIMENSION_PX
+            ),
+        })?;
+
+        let image_hash = blake3_hash(&image);
+        let entity = ProfilePictureEntity {
+            image_hash: image_hash.as_bytes(),
+        };
+        let deterministic_id = entity.deterministic_id()?;
+        if Self::fetch(conn, &deterministic_id)?.is_some() {
+            return Ok(deterministic_id);
+        }
+        entity.create(conn, &image, None)
+    }
+
+    /// Insert a bundled profile picture or restore its image if the row exists, but its image
+    /// is invalid. Returns the deterministic id of the picture.
+    pub fn upsert_bundled(
+        conn: &mut SqliteConnection,
+        image_name: &str,
     ) -> Result<DeterministicId, Error> {
+        use profile_pictures::dsl as pp;
+
         let image = load_profile_pic(image_name)?;
         let image_hash = blake3_hash(&image);
         let entity = ProfilePictureEntity {
             image_hash: image_hash.as_bytes(),
         };
-        entity.create(conn, &image, Some(image_name))
+        let deterministic_id = entity.deterministic_id()?;
+        diesel::insert_into(profile_pictures::table)
+            .values((
+                &entity,
+                pp::deterministic_id.eq(&deterministic_id),
+                pp::image.eq(&image),
+                pp::image_name.eq(image_name),
+                pp::created_at.eq(rfc3339_timestamp()),
+            ))
+            .on_conflict(pp::deterministic_id)
+            .do_update()
+            .set((
+                pp::image.eq(&image),
+                pp::image_hash.eq(image_hash.as_bytes()),
+                pp::updated_at.eq(rfc3339_timestamp()),
+            ))
+            .execute(conn)?;
+
+        Ok(deterministic_id)
+    }
+
+    /// Whether the image matches its hash and it's a valid profile picture.
+    pub fn is_valid(&self) -> bool {
+        blake3_hash(&self.image).as_bytes().as_slice() == self.image_hash.as_slice()
+            && validate_profile_pic(&self.image).is_ok()
     }
 
     pub fn delete(
@@ -153,3 +257,73 @@ impl<'a> DeriveDeterministicId<'a, &'a [u8], U1> for ProfilePictureEntity<'a> {
         [self.image_hash].into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{app_core::tests::TmpCore, http_client::tests::serve_once};
+
+    fn serve_response(content_type: &'static str, body: Vec<u8>) -> Result<Url> {
+        serve_once(move |stream| {
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(&body);
+        })
+    }
+
+    #[test]
+    fn insert_from_url() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let image = load_profile_pic("seal-2")?;
+        let url = serve_response("image/png", image.clone())?;
+        let http_client = HttpClient::new_without_cache();
+        let mut conn = tmp.connection_pool().connection()?;
+
+        let id = ProfilePicture::insert_from_url(&mut conn, &http_client, url)?;
+
+        assert_eq!(ProfilePicture::fetch_image(&mut conn, &id)?, image);
+        Ok(())
+    }
+
+    #[test]
+    fn insert_from_url_rejects_html() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let html = b"<!DOCTYPE html><html><body>Not an image</body></html>".to_vec();
+        let url = serve_response("text/html", html)?;
+        let http_client = HttpClient::new_without_cache();
+        let mut conn = tmp.connection_pool().connection()?;
+
+        let res = ProfilePicture::insert_from_url(&mut conn, &http_client, url);
+
+        assert!(matches!(res, Err(Error::User { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn insert_from_url_rejects_oversized() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let body = vec![0u8; config::MAX_PROFILE_PIC_SIZE_BYTES + 1];
+        let url = serve_response("image/png", body)?;
+        let http_client = HttpClient::new_without_cache();
+        let mut conn = tmp.connection_pool().connection()?;
+
+        let res = ProfilePicture::insert_from_url(&mut conn, &http_client, url);
+
+        match res {
+            Err(Error::User { explanation }) => assert!(explanation.contains("KiB")),
+            _ => panic!("Expected user error"),
+        }
+        assert!(ProfilePicture::list_all(&mut conn)?
+            .iter()
+            .all(|pic| pic.image_name.is_some()));
+        Ok(())
+    }
+}
@@ -13,7 +13,7 @@ use crate::{
         deterministic_id::{DeriveDeterministicId, DeterministicId, EntityName},
         models as m,
         models::AddressId,
-        schema::{tokens, tokens_to_addresses},
+        schema::{addresses, asymmetric_keys, tokens, tokens_to_addresses},
         DeferredTxConnection,
     },
     protocols::{eth, TokenType},
@@ -62,27 +62,86 @@ impl Token {
             new_token
         });
 
-        fungible_tokens.chain(nfts).try_for_each(|token| {
-            let token = token?;
-
-            diesel::insert_into(tokens::table)
-                .values(&token)
-                .on_conflict_do_nothing()
-                .execute(tx_conn.as_mut())?;
+        fungible_tokens
+            .chain(nfts)
+            .try_for_each(|token| Self::insert_for_address(tx_conn, &token?, address_id))
+    }
 
-            let token_to_address: NewTokenToAddressEntity = TokenToAddressEntity {
-                token_id: &token.deterministic_id,
-                address_id,
+    /// Track fungible token contracts for an address on its chain.
+    /// The operation is idempotent.
+    pub fn upsert_fungible_tokens_for_eth_address(
+        tx_conn: &mut DeferredTxConnection,
+        chain_id: eth::ChainId,
+        contract_addresses: &[eth::ChecksumAddress],
+        address_id: &AddressId,
+    ) -> Result<(), Error> {
+        let chain_db_id = m::Chain::fetch_or_create_eth_chain_id(tx_conn, chain_id)?;
+        contract_addresses.iter().try_for_each(|contract_address| {
+            let token: NewTokenEntity = TokenEntity {
+                address: contract_address,
+                chain_id: &chain_db_id,
+                type_: TokenType::Fungible,
             }
             .try_into()?;
-            diesel::insert_into(tokens_to_addresses::table)
-                .values(token_to_address)
-                .on_conflict_do_nothing()
-                .execute(tx_conn.as_mut())?;
-
-            Ok(())
+            Self::insert_for_address(tx_conn, &token, address_id)
         })
     }
+
+    fn insert_for_address(
+        tx_conn: &mut DeferredTxConnection,
+        token: &NewTokenEntity,
+        address_id: &AddressId,
+    ) -> Result<(), Error> {
+        diesel::insert_into(tokens::table)
+            .values(token)
+            .on_conflict_do_nothing()
+            .execute(tx_conn.as_mut())?;
+
+        let token_to_address: NewTokenToAddressEntity = TokenToAddressEntity {
+            token_id: &token.deterministic_id,
+            address_id,
+        }
+        .try_into()?;
+        diesel::insert_into(tokens_to_addresses::table)
+            .values(token_to_address)
+            .on_conflict_do_nothing()
+            .execute(tx_conn.as_mut())?;
+
+        Ok(())
+    }
+
+    /// List the fungible tokens that are tracked for the addresses of a profile on a chain.
+    /// Returns (owner address, token contract address) pairs.
+    pub fn list_profile_fungible_tokens_for_eth_chain(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+        chain_id: eth::ChainId,
+    ) -> Result<Vec<(eth::ChecksumAddress, eth::ChecksumAddress)>, Error> {
+        use addresses::dsl as a;
+        use asymmetric_keys::dsl as ak;
+        use tokens::dsl as t;
+        use tokens_to_addresses::dsl as tta;
+
+        let chain_db_id =
+            match m::Chain::fetch_eth_chain_deterministic_id(conn, chain_id)? {
+                Some(chain_db_id) => chain_db_id,
+                None => return Ok(Default::default()),
+            };
+
+        let results = tokens_to_addresses::table
+            .inner_join(tokens::table.on(t::deterministic_id.eq(tta::token_id)))
+            .inner_join(addresses::table.on(a::deterministic_id.eq(tta::address_id)))
+            .inner_join(
+                asymmetric_keys::table.on(ak::deterministic_id.eq(a::asymmetric_key_id)),
+            )
+            .filter(ak::profile_id.eq(profile_id))
+            .filter(t::chain_id.eq(&chain_db_id))
+            .filter(t::type_.eq(TokenType::Fungible))
+            .order((a::address.asc(), t::address.asc()))
+            .select((a::address, t::address))
+            .load(conn)?;
+        Ok(results)
+    }
 }
 
 #[readonly::make]
@@ -261,6 +320,7 @@ mod tests {
             collection_name: "".to_string(),
             name: "".to_string(),
             token_id: "".to_string(),
+            standard: "ERC721".to_string(),
             image_url: None,
         }];
 
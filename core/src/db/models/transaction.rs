@@ -0,0 +1,275 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashSet;
+
+use diesel::{prelude::*, SqliteConnection};
+use ethers::types::H256;
+use generic_array::{typenum::U2, GenericArray};
+use typed_builder::TypedBuilder;
+
+use crate::{
+    db::{
+        deterministic_id::{DeriveDeterministicId, DeterministicId, EntityName},
+        models as m,
+        models::AddressId,
+        schema::{addresses, asymmetric_keys, chains, transactions},
+        ConnectionPool, DeferredTxConnection, JsonValue,
+    },
+    protocols::eth,
+    utils::rfc3339_timestamp,
+    Error,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Identifiable)]
+#[diesel(primary_key(deterministic_id))]
+pub struct Transaction {
+    pub deterministic_id: DeterministicId,
+    pub chain_id: DeterministicId,
+    pub tx_hash: String,
+    pub from_address_id: AddressId,
+    pub to_address: Option<eth::ChecksumAddress>,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+    /// Local note by the user. Never broadcast.
+    pub memo: Option<String>,
+}
+
+impl Transaction {
+    /// Record a submitted transaction as activity of the sender on the chain.
+    /// Errors are only logged, because the transaction has been submitted already.
+    pub async fn record_submitted_eth(
+        connection_pool: &ConnectionPool,
+        from_address_id: AddressId,
+        chain_id: eth::ChainId,
+        tx_hash: H256,
+        to_address: Option<eth::ChecksumAddress>,
+        memo: Option<String>,
+    ) {
+        let res = connection_pool
+            .deferred_transaction_async(move |mut tx_conn| {
+                let params = NewEthTransactionParams::builder()
+                    .from_address_id(&from_address_id)
+                    .chain_id(chain_id)
+                    .tx_hash(tx_hash)
+                    .to_address(to_address)
+                    .memo(memo)
+                    .build();
+                Self::create_eth(&mut tx_conn, &params)
+            })
+            .await;
+        if let Err(err) = res {
+            log::error!("Failed to record transaction {tx_hash:#x} due to error: {err}");
+        }
+    }
+
+    /// Record a transaction that was submitted from an address.
+    /// The operation is idempotent.
+    /// Returns the DB id of the transaction.
+    pub fn create_eth(
+        tx_conn: &mut DeferredTxConnection,
+        params: &NewEthTransactionParams,
+    ) -> Result<DeterministicId, Error> {
+        let chain_id = m::Chain::fetch_or_create_eth_chain_id(tx_conn, params.chain_id)?;
+        let tx_hash = format!("{:#x}", params.tx_hash);
+        let entity: NewTransactionEntity = TransactionEntity {
+            chain_id: &chain_id,
+            tx_hash: &tx_hash,
+            from_address_id: params.from_address_id,
+            to_address: params.to_address.as_ref(),
+            memo: params.memo.as_deref(),
+        }
+        .try_into()?;
+
+        diesel::insert_into(transactions::table)
+            .values(&entity)
+            .on_conflict_do_nothing()
+            .execute(tx_conn.as_mut())?;
+
+        Ok(entity.deterministic_id)
+    }
+
+    /// List the transactions sent from an address ordered by most recent first.
+    pub fn list_for_address(
+        conn: &mut SqliteConnection,
+        address_id: &AddressId,
+    ) -> Result<Vec<Self>, Error> {
+        use transactions::dsl as t;
+
+        let results = transactions::table
+            .filter(t::from_address_id.eq(address_id))
+            .order(t::created_at.desc())
+            .load(conn)?;
+        Ok(results)
+    }
+
+    /// Delete the local records of the transactions sent from the addresses of a profile.
+    /// Optionally only from the addresses of a dapp or only the ones recorded before an RFC3339
+    /// timestamp. Returns the number of deleted transactions.
+    pub fn delete_for_profile(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+        dapp_id: Option<&DeterministicId>,
+        created_before: Option<&str>,
+    ) -> Result<usize, Error> {
+        use addresses::dsl as a;
+        use asymmetric_keys::dsl as ak;
+        use transactions::dsl as t;
+
+        let mut address_query = addresses::table
+            .inner_join(asymmetric_keys::table)
+            .filter(ak::profile_id.eq(profile_id))
+            .select(a::deterministic_id)
+            .into_boxed();
+        if let Some(dapp_id) = dapp_id {
+            address_query = address_query.filter(ak::dapp_id.eq(dapp_id));
+        }
+        let address_ids: Vec<AddressId> = address_query.load(conn)?;
+
+        let from_addresses = t::from_address_id.eq_any(&address_ids);
+        let deleted = match created_before {
+            Some(created_before) => diesel::delete(
+                transactions::table
+                    .filter(from_addresses)
+                    .filter(t::created_at.lt(created_before)),
+            )
+            .execute(conn)?,
+            None => diesel::delete(transactions::table.filter(from_addresses))
+                .execute(conn)?,
+        };
+
+        Ok(deleted)
+    }
+
+    /// The addresses that a profile has sent transactions to on a chain.
+    pub fn list_eth_recipients_for_profile(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+        chain_id: eth::ChainId,
+    ) -> Result<HashSet<eth::ChecksumAddress>, Error> {
+        use addresses::dsl as a;
+        use asymmetric_keys::dsl as ak;
+        use transactions::dsl as t;
+
+        let chain_db_id =
+            match m::Chain::fetch_eth_chain_deterministic_id(conn, chain_id)? {
+                Some(chain_db_id) => chain_db_id,
+                None => return Ok(Default::default()),
+            };
+
+        let to_addresses: Vec<Option<eth::ChecksumAddress>> = transactions::table
+            .inner_join(addresses::table.on(a::deterministic_id.eq(t::from_address_id)))
+            .inner_join(
+                asymmetric_keys::table.on(ak::deterministic_id.eq(a::asymmetric_key_id)),
+            )
+            .filter(ak::profile_id.eq(profile_id))
+            .filter(t::chain_id.eq(&chain_db_id))
+            .select(t::to_address)
+            .distinct()
+            .load(conn)?;
+
+        Ok(to_addresses.into_iter().flatten().collect())
+    }
+
+    /// List the Ethereum chains that a profile has transactions on ordered by most recent
+    /// activity first.
+    pub fn list_active_eth_chains_for_profile(
+        conn: &mut SqliteConnection,
+        profile_id: &DeterministicId,
+    ) -> Result<Vec<eth::ChainId>, Error> {
+        use addresses::dsl as a;
+        use asymmetric_keys::dsl as ak;
+        use chains::dsl as c;
+        use transactions::dsl as t;
+
+        let protocol_data: Vec<JsonValue> = transactions::table
+            .inner_join(addresses::table.on(a::deterministic_id.eq(t::from_address_id)))
+            .inner_join(
+                asymmetric_keys::table.on(ak::deterministic_id.eq(a::asymmetric_key_id)),
+            )
+            .inner_join(chains::table.on(c::deterministic_id.eq(t::chain_id)))
+            .filter(ak::profile_id.eq(profile_id))
+            .order(t::created_at.desc())
+            .select(c::protocol_data)
+            .load(conn)?;
+
+        // Deduplicate in Rust to preserve the order of the most recent transaction per chain.
+        let mut results: Vec<eth::ChainId> = Default::default();
+        for data in protocol_data {
+            let data: eth::ProtocolData = data.convert_into()?;
+            if !results.contains(&data.chain_id) {
+                results.push(data.chain_id)
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+#[derive(Debug, TypedBuilder)]
+#[readonly::make]
+pub struct NewEthTransactionParams<'a> {
+    pub from_address_id: &'a AddressId,
+    pub chain_id: eth::ChainId,
+    pub tx_hash: H256,
+    #[builder(default)]
+    pub to_address: Option<eth::ChecksumAddress>,
+    #[builder(default)]
+    pub memo: Option<String>,
+}
+
+#[readonly::make]
+struct TransactionEntity<'a> {
+    pub chain_id: &'a DeterministicId,
+    pub tx_hash: &'a str,
+    pub from_address_id: &'a AddressId,
+    pub to_address: Option<&'a eth::ChecksumAddress>,
+    pub memo: Option<&'a str>,
+}
+
+impl<'a> DeriveDeterministicId<'a, &'a str, U2> for TransactionEntity<'a> {
+    fn entity_name(&'a self) -> EntityName {
+        EntityName::Transaction
+    }
+
+    fn unique_columns(&'a self) -> GenericArray<&'a str, U2> {
+        [self.chain_id.as_ref(), self.tx_hash].into()
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = transactions)]
+struct NewTransactionEntity<'a> {
+    deterministic_id: DeterministicId,
+    chain_id: &'a DeterministicId,
+    tx_hash: &'a str,
+    from_address_id: &'a AddressId,
+    to_address: Option<&'a eth::ChecksumAddress>,
+    memo: Option<&'a str>,
+    created_at: String,
+}
+
+impl<'a> TryFrom<TransactionEntity<'a>> for NewTransactionEntity<'a> {
+    type Error = Error;
+
+    fn try_from(value: TransactionEntity<'a>) -> Result<Self, Self::Error> {
+        let deterministic_id = value.deterministic_id()?;
+        let TransactionEntity {
+            chain_id,
+            tx_hash,
+            from_address_id,
+            to_address,
+            memo,
+        } = value;
+        Ok(Self {
+            deterministic_id,
+            chain_id,
+            tx_hash,
+            from_address_id,
+            to_address,
+            memo,
+            created_at: rfc3339_timestamp(),
+        })
+    }
+}
@@ -25,6 +25,8 @@ diesel::table! {
         dapp_id -> Nullable<Text>,
         created_at -> Text,
         updated_at -> Nullable<Text>,
+        pinned -> Bool,
+        pin_order -> Nullable<Integer>,
     }
 }
 
@@ -39,6 +41,52 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    dapp_favicons (deterministic_id) {
+        deterministic_id -> Text,
+        image_hash -> Binary,
+        image -> Binary,
+        created_at -> Text,
+        updated_at -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    dapp_metadata (dapp_id) {
+        dapp_id -> Text,
+        name -> Nullable<Text>,
+        icon_url -> Nullable<Text>,
+        description -> Nullable<Text>,
+        created_at -> Text,
+        updated_at -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    dapp_storage (deterministic_id) {
+        deterministic_id -> Text,
+        profile_id -> Text,
+        dapp_id -> Text,
+        key -> Text,
+        dek_id -> Text,
+        encrypted_value -> Binary,
+        value_size -> Integer,
+        created_at -> Text,
+        updated_at -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    dapp_tags (deterministic_id) {
+        deterministic_id -> Text,
+        profile_id -> Text,
+        dapp_id -> Text,
+        tag -> Text,
+        created_at -> Text,
+        updated_at -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     dapps (deterministic_id) {
         deterministic_id -> Text,
@@ -46,6 +94,7 @@ diesel::table! {
         url -> Text,
         created_at -> Text,
         updated_at -> Nullable<Text>,
+        favicon_id -> Nullable<Text>,
     }
 }
 
@@ -76,6 +125,19 @@ diesel::table! {
         last_used_at -> Text,
         updated_at -> Text,
         created_at -> Text,
+        trust_level -> Text,
+        spending_limit -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    local_dapp_spends (uuid) {
+        uuid -> Text,
+        session_id -> Text,
+        chain_id -> Text,
+        token_address -> Nullable<Text>,
+        amount -> Text,
+        created_at -> Text,
     }
 }
 
@@ -90,17 +152,30 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    local_key_exports (uuid) {
+        uuid -> Text,
+        address_id -> Text,
+        created_at -> Text,
+    }
+}
+
 diesel::table! {
     local_settings (id) {
         id -> Text,
         profile_id -> Text,
         backup_enabled -> Bool,
-        // !!! `BigInt` here is a manual override. Make sure to add it back if you regenerate the
-        // schema. Context: https://github.com/diesel-rs/diesel/issues/1116
-        backup_version -> BigInt,
-        backup_completed_at -> Nullable<Text>,
         backup_password_updated_at -> Nullable<Text>,
         backup_kdf_nonce -> Nullable<Binary>,
+        last_restored_backup_timestamp -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    local_settings_values (key) {
+        key -> Text,
+        value -> Text,
+        updated_at -> Text,
     }
 }
 
@@ -123,6 +198,8 @@ diesel::table! {
         picture_id -> Text,
         created_at -> Text,
         updated_at -> Nullable<Text>,
+        user_settings -> Text,
+        sort_order -> Integer,
     }
 }
 
@@ -148,32 +225,65 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    transactions (deterministic_id) {
+        deterministic_id -> Text,
+        chain_id -> Text,
+        tx_hash -> Text,
+        from_address_id -> Text,
+        to_address -> Nullable<Text>,
+        created_at -> Text,
+        updated_at -> Nullable<Text>,
+        memo -> Nullable<Text>,
+    }
+}
+
 diesel::joinable!(addresses -> asymmetric_keys (asymmetric_key_id));
 diesel::joinable!(addresses -> chains (chain_id));
 diesel::joinable!(asymmetric_keys -> dapps (dapp_id));
 diesel::joinable!(asymmetric_keys -> data_encryption_keys (dek_id));
 diesel::joinable!(asymmetric_keys -> profiles (profile_id));
+diesel::joinable!(dapp_metadata -> dapps (dapp_id));
+diesel::joinable!(dapp_storage -> dapps (dapp_id));
+diesel::joinable!(dapp_storage -> data_encryption_keys (dek_id));
+diesel::joinable!(dapp_storage -> profiles (profile_id));
+diesel::joinable!(dapp_tags -> dapps (dapp_id));
+diesel::joinable!(dapp_tags -> profiles (profile_id));
+diesel::joinable!(dapps -> dapp_favicons (favicon_id));
 diesel::joinable!(local_dapp_sessions -> addresses (address_id));
 diesel::joinable!(local_dapp_sessions -> dapps (dapp_id));
+diesel::joinable!(local_dapp_spends -> chains (chain_id));
+diesel::joinable!(local_dapp_spends -> local_dapp_sessions (session_id));
 diesel::joinable!(local_encrypted_deks -> data_encryption_keys (dek_id));
+diesel::joinable!(local_key_exports -> addresses (address_id));
 diesel::joinable!(local_settings -> profiles (profile_id));
 diesel::joinable!(profiles -> profile_pictures (picture_id));
 diesel::joinable!(tokens -> chains (chain_id));
 diesel::joinable!(tokens_to_addresses -> addresses (address_id));
 diesel::joinable!(tokens_to_addresses -> tokens (token_id));
+diesel::joinable!(transactions -> addresses (from_address_id));
+diesel::joinable!(transactions -> chains (chain_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     addresses,
     asymmetric_keys,
     chains,
+    dapp_favicons,
+    dapp_metadata,
+    dapp_storage,
+    dapp_tags,
     dapps,
     data_encryption_keys,
     data_migrations,
     local_dapp_sessions,
+    local_dapp_spends,
     local_encrypted_deks,
+    local_key_exports,
     local_settings,
+    local_settings_values,
     profile_pictures,
     profiles,
     tokens,
     tokens_to_addresses,
+    transactions,
 );
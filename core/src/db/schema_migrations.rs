@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use diesel::SqliteConnection;
+use diesel::{OptionalExtension, RunQueryDsl, SqliteConnection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
 use crate::{db::ExclusiveTxConnection, Error};
@@ -22,3 +22,23 @@ pub fn run_migrations(tx_conn: &mut ExclusiveTxConnection) -> Result<(), Error>
 
     Ok(())
 }
+
+#[derive(QueryableByName)]
+struct AppliedVersion {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    version: String,
+}
+
+/// The version of the latest applied schema migration, eg. `20230316090000`.
+/// Reads the Diesel migrations table directly, because the migration harness would create the
+/// table if it didn't exist.
+pub fn latest_applied_version(
+    conn: &mut SqliteConnection,
+) -> Result<Option<String>, Error> {
+    let applied: Option<AppliedVersion> = diesel::sql_query(
+        "SELECT version FROM __diesel_schema_migrations ORDER BY version DESC LIMIT 1",
+    )
+    .get_result(conn)
+    .optional()?;
+    Ok(applied.map(|applied| applied.version))
+}
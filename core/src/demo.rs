@@ -0,0 +1,112 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use ethers::types::H256;
+
+use crate::{
+    assets::load_profile_pic,
+    dapp_manifest,
+    db::{models as m, DeferredTxConnection, DeterministicId},
+    protocols::eth,
+    resources::CoreResourcesI,
+    rng::RngI,
+    Error,
+};
+
+/// Sample profiles by name and bundled picture name that are created in demo mode in addition
+/// to the default profile.
+const DEMO_PROFILES: &[(&str, &str)] =
+    &[("Trading", "seal-2"), ("Collectibles", "seal-4")];
+
+/// Sample dapps that are added to each profile in demo mode.
+const DEMO_DAPPS_MANIFEST: &str = r#"[
+    {"url": "https://app.uniswap.org", "name": "Uniswap"},
+    {"url": "https://opensea.io", "name": "OpenSea"},
+    {"url": "https://app.ens.domains", "name": "ENS"}
+]"#;
+
+/// Seed sample profiles and dapps for demo mode. No-op if there are profiles other than the
+/// default profile, so the sample data is only created on the first launch.
+pub fn seed_demo_data(resources: &dyn CoreResourcesI) -> Result<(), Error> {
+    let profiles = m::Profile::list_all(&mut resources.connection_pool().connection()?)?;
+    if profiles.len() > 1 {
+        return Ok(());
+    }
+
+    resources
+        .connection_pool()
+        .deferred_transaction(|mut tx_conn| {
+            for (name, picture_name) in DEMO_PROFILES {
+                let name: m::ProfileName = name.parse()?;
+                m::Profile::create_eth_profile(
+                    &mut tx_conn,
+                    resources.keychain(),
+                    &name,
+                    picture_name,
+                    &load_profile_pic(picture_name)?,
+                )?;
+            }
+            Ok(())
+        })?;
+
+    let results = dapp_manifest::import_dapps(resources, DEMO_DAPPS_MANIFEST)?;
+    let mut dapp_ids: Vec<DeterministicId> = Default::default();
+    for result in results {
+        match result.dapp_id {
+            Some(dapp_id) => dapp_ids.push(dapp_id.parse()?),
+            None => log::error!("Failed to seed demo dapp: {result:?}"),
+        }
+    }
+
+    // Importing doesn't add the dapps to profiles, so the demo connects them as if the user
+    // had approved them.
+    let profiles = m::Profile::list_all(&mut resources.connection_pool().connection()?)?;
+    resources
+        .connection_pool()
+        .deferred_transaction(|mut tx_conn| {
+            for profile in profiles.iter() {
+                for dapp_id in dapp_ids.iter() {
+                    add_dapp_to_profile(
+                        &mut tx_conn,
+                        resources,
+                        &profile.deterministic_id,
+                        dapp_id,
+                    )?;
+                }
+            }
+            Ok(())
+        })?;
+
+    Ok(())
+}
+
+fn add_dapp_to_profile(
+    tx_conn: &mut DeferredTxConnection,
+    resources: &dyn CoreResourcesI,
+    profile_id: &DeterministicId,
+    dapp_id: &DeterministicId,
+) -> Result<(), Error> {
+    let chain_id = eth::ChainId::default_dapp_chain();
+    let params = m::CreateEthAddressParams::builder()
+        .profile_id(profile_id)
+        .chain_id(chain_id)
+        .dapp_id(Some(dapp_id))
+        .rng(resources.rng())
+        .build();
+    m::Address::create_eth_key_and_address(tx_conn, resources.keychain(), &params)?;
+    let params = m::NewDappSessionParams::builder()
+        .dapp_id(dapp_id)
+        .profile_id(profile_id)
+        .chain_id(chain_id)
+        .build();
+    m::LocalDappSession::create_eth_session(tx_conn, &params)?;
+    Ok(())
+}
+
+/// Random transaction hash for transactions that aren't submitted in demo mode.
+pub fn synthetic_tx_hash(rng: &dyn RngI) -> Result<H256, Error> {
+    let mut bytes = [0u8; 32];
+    rng.try_fill_bytes(&mut bytes)?;
+    Ok(H256::from(bytes))
+}
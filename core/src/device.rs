@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use derive_more::{AsRef, Display, Into};
 use lazy_static::lazy_static;
@@ -92,19 +92,49 @@ impl FromStr for DeviceName {
     }
 }
 
-#[derive(Debug, Display, Clone, Eq, PartialEq, AsRef, Serialize, Deserialize)]
-#[serde(transparent)]
-#[repr(transparent)]
-pub struct OperatingSystem(String);
+/// The operating system that created a backup. Operating systems that this build doesn't know,
+/// eg. from a newer device, are kept as `Unknown` with the raw token, so that their backups can
+/// still be restored.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub enum OperatingSystem {
+    Android,
+    Ios,
+    Linux,
+    MacOs,
+    Windows,
+    Unknown(String),
+}
+
+impl OperatingSystem {
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Unknown(_))
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Android => "android",
+            Self::Ios => "ios",
+            Self::Linux => "linux",
+            Self::MacOs => "macos",
+            Self::Windows => "windows",
+            Self::Unknown(os) => os,
+        }
+    }
+}
 
 impl Default for OperatingSystem {
     fn default() -> Self {
-        let os = if std::env::consts::OS.is_empty() {
-            "unknown-os"
-        } else {
-            std::env::consts::OS
-        };
-        Self(os.into())
+        std::env::consts::OS
+            .parse()
+            .unwrap_or_else(|_| Self::Unknown("unknown-os".into()))
+    }
+}
+
+impl fmt::Display for OperatingSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
     }
 }
 
@@ -112,12 +142,33 @@ impl FromStr for OperatingSystem {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.is_empty() {
-            Err(Error::Retriable {
-                error: "Empty OS string".into(),
-            })
-        } else {
-            Ok(Self(s.into()))
-        }
+        let os = match s {
+            "" => {
+                return Err(Error::Retriable {
+                    error: "Empty OS string".into(),
+                })
+            }
+            "android" => Self::Android,
+            "ios" => Self::Ios,
+            "linux" => Self::Linux,
+            "macos" => Self::MacOs,
+            "windows" => Self::Windows,
+            unknown => Self::Unknown(unknown.into()),
+        };
+        Ok(os)
+    }
+}
+
+impl TryFrom<String> for OperatingSystem {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<OperatingSystem> for String {
+    fn from(value: OperatingSystem) -> Self {
+        value.to_string()
     }
 }
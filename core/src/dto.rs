@@ -2,8 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::{collections::HashSet, iter, ops::Sub, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    iter,
+    ops::Sub,
+    sync::Arc,
+};
 
+use diesel::SqliteConnection;
 use futures::StreamExt;
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -16,6 +22,7 @@ use crate::db::{models as m, ConnectionPool, DeferredTxConnection, Deterministic
 use crate::{
     async_runtime as rt, config,
     favicon::fetch_favicons,
+    fiat,
     http_client::HttpClient,
     protocols::{eth, eth::ankr, FungibleTokenType},
     resources::CoreResourcesI,
@@ -44,6 +51,36 @@ pub struct CoreDapp {
     pub selected_address_id: Option<String>,
     pub favicon: Option<Vec<u8>>,
     pub last_used: Option<String>,
+    /// Self-reported by the dapp, so it must be presented as untrusted. The
+    /// `human_identifier` is the trusted identity of the dapp.
+    #[builder(default)]
+    pub reported_metadata: Option<CoreDappMetadata>,
+    /// When the most recent transaction was submitted by the dapp in the profile.
+    #[builder(default)]
+    pub last_transacted_at: Option<String>,
+    /// Pinned dapps are listed first in the profile.
+    #[builder(default)]
+    pub pinned: bool,
+    /// User assigned tags in alphabetical order.
+    #[builder(default)]
+    pub tags: Vec<String>,
+}
+
+/// A dapp with the profiles that it has been added to, eg. for a deep link to the dapp.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoreDappDetail {
+    pub id: String,
+    pub human_identifier: String,
+    pub url: String,
+    /// In the profile order. Empty if the dapp hasn't been added to a profile.
+    pub profile_ids: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, TypedBuilder)]
+pub struct CoreDappMetadata {
+    pub name: Option<String>,
+    pub icon_url: Option<String>,
+    pub description: Option<String>,
 }
 
 #[derive(Clone, Debug, TypedBuilder)]
@@ -62,15 +99,120 @@ pub struct CoreAddress {
 pub struct CoreFungibleToken {
     pub id: String,
     pub symbol: String,
-    pub amount: Option<String>,
+    /// None if the balance hasn't been fetched.
+    pub amount: Option<eth::CurrencyAmount>,
     pub token_type: FungibleTokenType,
     pub icon: Option<Vec<u8>>,
+    /// The value of the amount in the profile's fiat currency. None if there is no price.
+    #[builder(default)]
+    pub fiat_value: Option<CoreFiatAmount>,
+}
+
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct CoreFiatAmount {
+    /// The amount formatted for display.
+    pub amount: String,
+    /// ISO 4217 currency code.
+    pub currency: String,
+    /// Whether the amount is in the fallback currency, because prices aren't available in the
+    /// profile's currency.
+    pub fallback: bool,
+}
+
+impl From<fiat::FiatValue> for CoreFiatAmount {
+    fn from(value: fiat::FiatValue) -> Self {
+        Self {
+            amount: value.amount.to_string(),
+            currency: value.currency.to_string(),
+            fallback: value.fallback,
+        }
+    }
+}
+
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct CoreTransferPreview {
+    /// Whether the transfer is above the high value threshold of the profile and requires
+    /// extra confirmation.
+    pub high_value: bool,
+    /// The amount to transfer formatted for display.
+    pub amount: String,
+    /// The high value threshold formatted for display. None if the token has no threshold.
+    pub high_value_threshold: Option<String>,
+    /// The fiat value of the amount in the profile's currency. None if there is no price.
+    #[builder(default)]
+    pub fiat_amount: Option<CoreFiatAmount>,
+    /// The fiat value of the high value threshold in the profile's currency.
+    #[builder(default)]
+    pub fiat_high_value_threshold: Option<CoreFiatAmount>,
+}
+
+/// A transaction sent from an address.
+#[derive(Clone, Debug)]
+pub struct CoreTransaction {
+    pub id: String,
+    pub chain_id: u64,
+    pub tx_hash: String,
+    pub to_checksum_address: Option<String>,
+    /// Local note by the user. Never broadcast.
+    pub memo: Option<String>,
+    /// RFC 3339 timestamp of the submission.
+    pub created_at: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct CoreQueuedTransaction {
+    pub nonce: u64,
+    pub status: eth::QueuedTransactionStatus,
+    pub tx_hash: Option<String>,
+    pub error_message: Option<String>,
+}
+
+impl From<eth::QueuedTransaction> for CoreQueuedTransaction {
+    fn from(value: eth::QueuedTransaction) -> Self {
+        let eth::QueuedTransaction {
+            nonce,
+            status,
+            tx_hash,
+            error,
+            outflow: _,
+        } = value;
+        Self {
+            nonce: nonce.low_u64(),
+            status,
+            tx_hash: tx_hash.map(|tx_hash| format!("{tx_hash:#x}")),
+            error_message: error,
+        }
+    }
+}
+
+/// The native token balance of an address on a chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoreNativeBalance {
+    /// The balance at the latest block.
+    pub confirmed: eth::CurrencyAmount,
+    /// The confirmed balance minus the value and the estimated fee of the transactions in the
+    /// queue that haven't been confirmed yet. None unless requested.
+    pub pending: Option<eth::CurrencyAmount>,
 }
 
 #[derive(Clone, Debug, TypedBuilder)]
 pub struct CoreNFT {
     pub id: String,
     pub display_name: String,
+    #[builder(default)]
+    pub collection_name: String,
+    /// Token standard, eg. `ERC721`.
+    #[builder(default)]
+    pub standard: String,
+    #[builder(default)]
+    pub image_url: Option<String>,
+}
+
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct CoreNFTPage {
+    pub nfts: Vec<CoreNFT>,
+    /// Pass to the next `list_nfts` call to get the next page. None on the last page.
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Clone, Debug, TypedBuilder)]
@@ -105,6 +247,9 @@ pub enum CoreError {
     // An error where the message can be presented to the user directly.
     #[error("{explanation}")]
     User { explanation: String },
+    /// The database file is corrupt. The user should be offered to restore from a backup.
+    #[error("Database Corrupt: '{error}'")]
+    DatabaseCorrupt { error: String },
 }
 
 #[derive(Debug)]
@@ -184,12 +329,46 @@ impl Assembler {
         tx_conn: &mut DeferredTxConnection,
         profile_id: &DeterministicId,
     ) -> Result<Vec<CoreDapp>, Error> {
-        let dapps = m::Dapp::list_for_profile(tx_conn.as_mut(), profile_id)?;
-        let urls: Vec<Url> = dapps.iter().map(|d| d.url.clone().into()).collect();
-        let favicons = fetch_favicons(self.http_client(), urls)?;
+        let dapps = m::Dapp::list_for_profile(
+            tx_conn.as_mut(),
+            profile_id,
+            None,
+            0,
+            config::UNPAGINATED_DAPP_LIST_LIMIT,
+        )?;
+        let dapp_ids: Vec<DeterministicId> =
+            dapps.iter().map(|d| d.deterministic_id.clone()).collect();
+        // Prefer the stored favicons, because they work offline, and only fetch the missing
+        // ones. Demo mode doesn't make network requests, so only stored favicons are shown.
+        let mut favicons = m::Dapp::list_favicons(tx_conn.as_mut(), &dapp_ids)?;
+        if !self.resources.is_demo() {
+            let (missing_ids, missing_urls): (Vec<DeterministicId>, Vec<Url>) = dapps
+                .iter()
+                .filter(|d| !favicons.contains_key(&d.deterministic_id))
+                .map(|d| (d.deterministic_id.clone(), d.url.clone().into()))
+                .unzip();
+            let fetched = fetch_favicons(self.http_client(), missing_urls)?;
+            let fetched: HashMap<DeterministicId, Vec<u8>> = missing_ids
+                .into_iter()
+                .zip(fetched)
+                .filter_map(|(dapp_id, icon)| Some((dapp_id, icon?)))
+                .collect();
+            favicons.extend(fetched);
+        }
+        let mut last_transacted_at =
+            m::Dapp::list_last_transacted_at_for_profile(tx_conn.as_mut(), profile_id)?;
+        let pinned_ids: HashSet<DeterministicId> =
+            m::Dapp::list_pinned_ids_for_profile(tx_conn.as_mut(), profile_id)?
+                .into_iter()
+                .collect();
         let mut results: Vec<CoreDapp> = Default::default();
-        for (dapp, icon) in dapps.into_iter().zip(favicons) {
-            let dapp = self.assemble_dapp(tx_conn, profile_id, dapp, icon)?;
+        for dapp in dapps {
+            let icon = favicons.remove(&dapp.deterministic_id);
+            let last_transacted_at = last_transacted_at.remove(&dapp.deterministic_id);
+            let pinned = pinned_ids.contains(&dapp.deterministic_id);
+            let mut dapp =
+                self.assemble_dapp(tx_conn, profile_id, dapp, icon, last_transacted_at)?;
+            dapp.pinned = pinned;
             results.push(dapp);
         }
         Ok(results)
@@ -201,6 +380,7 @@ impl Assembler {
         profile_id: &DeterministicId,
         dapp: m::Dapp,
         favicon: Option<Vec<u8>>,
+        last_transacted_at: Option<String>,
     ) -> Result<CoreDapp, Error> {
         let params = m::ListAddressesForDappParams::builder()
             .profile_id(profile_id)
@@ -222,6 +402,19 @@ impl Assembler {
             m::LocalDappSession::fetch_eth_session(tx_conn, &dapp_session_params)?;
         let selected_address_id: Option<String> =
             dapp_session.map(|s| s.address_id.into());
+        let tags = m::Dapp::list_tags_for_dapp(
+            tx_conn.as_mut(),
+            profile_id,
+            &dapp.deterministic_id,
+        )?;
+        let reported_metadata =
+            m::DappMetadata::fetch(tx_conn.as_mut(), &dapp.deterministic_id)?.map(
+                |metadata| CoreDappMetadata {
+                    name: metadata.name,
+                    icon_url: metadata.icon_url,
+                    description: metadata.description,
+                },
+            );
 
         let m::Dapp {
             deterministic_id,
@@ -239,6 +432,9 @@ impl Assembler {
             .favicon(favicon)
             // TODO move last used at from local sessions to dapp
             .last_used(None)
+            .reported_metadata(reported_metadata)
+            .last_transacted_at(last_transacted_at)
+            .tags(tags)
             .build();
         Ok(result)
     }
@@ -278,7 +474,7 @@ impl Assembler {
 
         // We send the native token without balance first to return result ASAP.
         // UI then fetches balance async.
-        let native_token = self.make_native_token(address, chain_id, None)?;
+        let native_token = self.make_native_token(address, chain_id, None, None)?;
 
         let is_wallet =
             m::Address::is_profile_wallet(tx_conn.as_mut(), &deterministic_id)?;
@@ -299,6 +495,41 @@ impl Assembler {
         Ok(result)
     }
 
+    /// List a page of the NFTs of an address on the address' chain.
+    pub fn list_nfts(
+        &self,
+        address_id: &m::AddressId,
+        query: &eth::NftQuery,
+    ) -> Result<CoreNFTPage, Error> {
+        use ankr::AnkrRpcI;
+
+        if self.resources.is_demo() {
+            return Ok(CoreNFTPage::builder()
+                .nfts(Default::default())
+                .next_cursor(None)
+                .build());
+        }
+        let mut conn = self.connection_pool().connection()?;
+        let address = m::Address::fetch_address(&mut conn, address_id)?;
+        let chain_id = m::Address::fetch_eth_chain_id(&mut conn, address_id)?;
+        // Release the connection while waiting for the API.
+        drop(conn);
+
+        let ankr_api = ankr::AnkrRpc::new()?;
+        let ankr_page = rt::block_on(ankr_api.get_nfts(
+            address,
+            chain_id,
+            query.page_size(),
+            query.cursor.clone(),
+        ))?;
+        let page = query.page(ankr_page.nfts, ankr_page.next_page_token);
+
+        Ok(CoreNFTPage::builder()
+            .nfts(self.assemble_nfts(page.nfts))
+            .next_cursor(page.next_cursor)
+            .build())
+    }
+
     /// Fetch all the tokens for an address id.
     pub fn tokens_for_address_id(
         &self,
@@ -326,6 +557,9 @@ impl Assembler {
         &self,
         address: eth::ChecksumAddress,
     ) -> Result<Vec<CoreTokens>, Error> {
+        if self.resources.is_demo() {
+            return self.assemble_native_tokens(address);
+        }
         self.assemble_tokens(address).or_else(|error| {
             log::error!("Error fetching tokens from Ankr API: '{error}'");
             // Fall back to just fetching native token
@@ -355,10 +589,16 @@ impl Assembler {
                         error: "Address id must exist since it was just fetched from DB."
                             .into(),
                     })?;
+                    let fiat_value = self.native_token_fiat_value(
+                        tx_conn.as_mut(),
+                        &address_id,
+                        &balance,
+                    )?;
                     let native_token = self.make_native_token(
                         address,
                         balance.chain_id,
-                        Some(balance.display_amount()),
+                        Some((&balance).into()),
+                        fiat_value,
                     )?;
                     Ok(CoreTokens::builder()
                         .address_id(address_id.to_string())
@@ -396,6 +636,10 @@ impl Assembler {
         address: eth::ChecksumAddress,
         chain_id: eth::ChainId,
     ) -> Result<eth::NativeTokenAmount, Error> {
+        // Demo addresses aren't funded.
+        if self.resources.is_demo() {
+            return Ok(eth::NativeTokenAmount::new(chain_id, Default::default()));
+        }
         let provider = self.rpc_manager().eth_api_provider(chain_id);
         let balance = provider.native_token_balance_async(address).await?;
         Ok(balance)
@@ -405,7 +649,8 @@ impl Assembler {
         &self,
         address: eth::ChecksumAddress,
         chain_id: eth::ChainId,
-        amount: Option<String>,
+        amount: Option<eth::CurrencyAmount>,
+        fiat_value: Option<CoreFiatAmount>,
     ) -> Result<CoreFungibleToken, Error> {
         let native_token_id = format!("eth-{chain_id}-{address}");
         let icon = Some(chain_id.native_token().icon()?);
@@ -415,10 +660,26 @@ impl Assembler {
             .amount(amount)
             .token_type(FungibleTokenType::Native)
             .icon(icon)
+            .fiat_value(fiat_value)
             .build();
         Ok(native_token)
     }
 
+    /// The value of a native token amount in the fiat currency of the address' profile.
+    fn native_token_fiat_value(
+        &self,
+        conn: &mut SqliteConnection,
+        address_id: &m::AddressId,
+        amount: &eth::NativeTokenAmount,
+    ) -> Result<Option<CoreFiatAmount>, Error> {
+        let profile_id = m::Address::fetch_profile_id(conn, address_id)?;
+        let settings = m::Profile::fetch_user_settings(conn, &profile_id)?;
+        let currency = settings.fiat_currency_or(self.resources.default_fiat_currency());
+        let value =
+            fiat::native_token_fiat_value(self.resources.price_feed(), &currency, amount);
+        Ok(value.map(Into::into))
+    }
+
     fn assemble_fungible_tokens(
         &self,
         tokens: Vec<eth::FungibleTokenBalance>,
@@ -429,7 +690,11 @@ impl Assembler {
         let logo_urls = tokens_with_logo
             .iter()
             .map(|token| token.logo.clone().expect("tokens with logo have logo"));
-        let maybe_icons = rt::block_on(self.http_client().get_bytes(logo_urls));
+        let maybe_icons = rt::block_on(
+            self.resources
+                .ipfs_gateways()
+                .get_bytes(self.http_client(), logo_urls),
+        );
         let icons_for_all = maybe_icons
             .into_iter()
             .chain(iter::repeat(None).take(tokens_no_logo.len()));
@@ -439,7 +704,7 @@ impl Assembler {
             .chain(tokens_no_logo)
             .zip(icons_for_all)
             .map(|(token, icon)| {
-                let amount = token.display_amount();
+                let amount = eth::CurrencyAmount::from(&token);
                 let eth::FungibleTokenBalance {
                     contract_address,
                     symbol,
@@ -459,6 +724,12 @@ impl Assembler {
         result
     }
 
+    /// Resolve `ipfs://` URLs to a gateway for the host. Other URLs are unchanged.
+    fn gateway_url(&self, url: Url) -> String {
+        let ipfs_gateways = self.resources.ipfs_gateways();
+        ipfs_gateways.gateway_url(url.clone()).unwrap_or(url).into()
+    }
+
     fn assemble_nfts(&self, tokens: Vec<eth::NFTBalance>) -> Vec<CoreNFT> {
         tokens
             .into_iter()
@@ -466,12 +737,21 @@ impl Assembler {
                 let eth::NFTBalance {
                     chain_id,
                     contract_address,
+                    collection_name,
                     name,
                     token_id,
+                    standard,
+                    image_url,
                     ..
                 } = token;
                 let id = format!("{chain_id}-{contract_address}-{token_id}");
-                CoreNFT::builder().id(id).display_name(name).build()
+                CoreNFT::builder()
+                    .id(id)
+                    .display_name(name)
+                    .collection_name(collection_name)
+                    .standard(standard)
+                    .image_url(image_url.map(|url| self.gateway_url(url)))
+                    .build()
             })
             .collect()
     }
@@ -509,10 +789,16 @@ impl Assembler {
                     ..
                 } = balances;
                 chains_from_api.insert(chain_id);
+                let fiat_value = self.native_token_fiat_value(
+                    tx_conn.as_mut(),
+                    &address_id,
+                    &native_token,
+                )?;
                 let native_token = self.make_native_token(
                     address,
                     chain_id,
-                    Some(native_token.display_amount()),
+                    Some((&native_token).into()),
+                    fiat_value,
                 )?;
                 let fungible_tokens = self.assemble_fungible_tokens(fungible_tokens)?;
                 let nfts = self.assemble_nfts(nfts);
@@ -535,14 +821,24 @@ impl Assembler {
                     .collect();
 
             for chain_id in existing_chains.sub(&chains_from_api) {
-                let native_token =
-                    self.make_native_token(address, chain_id, Some("0".into()))?;
                 let address_id = m::Address::fetch_or_create_id_by_address_on_chain(
                     &mut tx_conn,
                     address,
                     chain_id,
                 )?;
                 if let Some(address_id) = address_id {
+                    let balance = eth::NativeTokenAmount::zero_balance(chain_id);
+                    let fiat_value = self.native_token_fiat_value(
+                        tx_conn.as_mut(),
+                        &address_id,
+                        &balance,
+                    )?;
+                    let native_token = self.make_native_token(
+                        address,
+                        chain_id,
+                        Some((&balance).into()),
+                        fiat_value,
+                    )?;
                     results.push(
                         CoreTokens::builder()
                             .address_id(address_id.to_string())
@@ -583,6 +879,7 @@ impl From<Error> for CoreError {
             Error::Fatal { error } => CoreError::Fatal { error },
             Error::User { explanation } => CoreError::User { explanation },
             Error::Retriable { error } => CoreError::Retriable { error },
+            Error::DatabaseCorrupt { error } => CoreError::DatabaseCorrupt { error },
             Error::JsonRpc { code, message } => {
                 if JSONRPC_USER_ERROR_REGEX.is_match(&message) {
                     CoreError::User {
@@ -14,7 +14,8 @@ use crate::{
         encrypt_decrypt::{decrypt, encrypt},
         encryption_output::EncryptionOutput,
         key_material::KeyMaterial,
-        KeyName, Keychain,
+        root_backup_key::MIN_BLAKE_CONTEXT_LEN,
+        KeyName, Keychain, KeychainError,
     },
     Error,
 };
@@ -104,6 +105,57 @@ impl DataEncryptionKey {
         )?))
     }
 
+    /// Derive the key that encrypts the dapp storage of a profile from the SK-DEK, so that values
+    /// stored in one profile can't be decrypted with the key of an other profile.
+    pub fn derive_dapp_storage_key(&self, profile_id: &str) -> Result<Self, Error> {
+        // In this scope to prevent being used in other context by accident.
+        const DAPP_STORAGE_DEK_CONTEXT: &str = "\
+        org.sealvault.dapp-storage.keys.profile-dapp-storage-data-encryption-key \
+        unique suffix: 193c6ac1190fcb542f52901c9bb27c0f";
+        // Make sure nothing is missing due to multiline formatting issues.
+        assert!(DAPP_STORAGE_DEK_CONTEXT.len() >= MIN_BLAKE_CONTEXT_LEN);
+        // The key material has fixed length, so the concatenation is unambiguous.
+        let mut hasher = blake3::Hasher::new_derive_key(DAPP_STORAGE_DEK_CONTEXT);
+        hasher.update(self.expose_secret().as_ref());
+        hasher.update(profile_id.as_bytes());
+        let tag = Zeroizing::new(*hasher.finalize().as_bytes());
+        let key_material = KeyMaterial::<U32>::from_slice(tag.as_ref())?;
+        Ok(Self::new(
+            KeyName::DappStorageDataEncryptionKey,
+            key_material,
+        ))
+    }
+
+    /// Encrypt a value and bind it to the associated data. Decryption fails unless the same
+    /// associated data is provided.
+    pub fn encrypt_with_associated_data(
+        &self,
+        value: &[u8],
+        associated_data: &[u8],
+    ) -> Result<EncryptionOutput, Error> {
+        let payload = Payload {
+            msg: value,
+            aad: associated_data,
+        };
+        encrypt(payload, self)
+    }
+
+    pub fn decrypt_with_associated_data(
+        &self,
+        encryption_output: &EncryptionOutput,
+        associated_data: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let payload = Payload {
+            msg: &encryption_output.cipher_text,
+            aad: associated_data,
+        };
+        Ok(Zeroizing::new(decrypt(
+            payload,
+            self,
+            &encryption_output.nonce,
+        )?))
+    }
+
     /// Encrypt a file at a given path and write it to the out path. Assumes the file fits into
     /// memory. It'd be preferable to use streaming encryption and not read the entire file into
     /// memory but streaming AEAD is immature in Rust and naive implementations are dangerous.
@@ -174,6 +226,16 @@ impl KeyEncryptionKey {
         Self::from_keychain(keychain, KeyName::SkBackupKeyEncryptionKey)
     }
 
+    /// The SK-KEK if it's in the keychain. None on first launch.
+    pub fn sk_kek_if_exists(keychain: &Keychain) -> Result<Option<Self>, Error> {
+        let name = KeyName::SkKeyEncryptionKey;
+        match keychain.get_local(name) {
+            Ok(key) => Ok(Some(Self::new(name, key))),
+            Err(KeychainError::NotFound { .. }) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     fn from_keychain(keychain: &Keychain, name: KeyName) -> Result<Self, Error> {
         let key: EncryptionKey = keychain.get_local(name)?;
         Ok(Self::new(name, key))
@@ -255,6 +317,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn dapp_storage_key_depends_on_profile() -> Result<()> {
+        let dek = DataEncryptionKey::random(KeyName::SkDataEncryptionKey)?;
+        let key_one = dek.derive_dapp_storage_key("profile-one")?;
+        let key_two = dek.derive_dapp_storage_key("profile-two")?;
+
+        let encrypted = key_one.encrypt_with_associated_data(b"value", b"key")?;
+        assert_eq!(
+            key_one
+                .decrypt_with_associated_data(&encrypted, b"key")?
+                .as_slice(),
+            b"value"
+        );
+        assert!(key_two
+            .decrypt_with_associated_data(&encrypted, b"key")
+            .is_err());
+        assert!(key_one
+            .decrypt_with_associated_data(&encrypted, b"other-key")
+            .is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn checks_name_on_dek_decryption() -> Result<()> {
         let dek = DataEncryptionKey::random(KeyName::SkDataEncryptionKey)?;
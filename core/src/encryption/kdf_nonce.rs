@@ -7,13 +7,20 @@ use std::str::FromStr;
 use base64::Engine;
 use generic_array::{typenum::U16, GenericArray};
 
-use crate::{utils::try_random_bytes, Error};
+use crate::{
+    rng::{try_random_bytes_with, RngI, SystemRng},
+    Error,
+};
 
 pub struct KdfNonce(GenericArray<u8, U16>);
 
 impl KdfNonce {
     pub fn random() -> Result<Self, Error> {
-        let kdf_nonce: GenericArray<u8, U16> = try_random_bytes()?;
+        Self::random_with(&SystemRng)
+    }
+
+    pub fn random_with(rng: &dyn RngI) -> Result<Self, Error> {
+        let kdf_nonce: GenericArray<u8, U16> = try_random_bytes_with(rng)?;
         Ok(Self(kdf_nonce))
     }
 }
@@ -58,6 +65,7 @@ mod tests {
     use anyhow::Result;
 
     use super::*;
+    use crate::rng::tests::DeterministicRng;
 
     #[test]
     fn encode_decode() -> Result<()> {
@@ -67,4 +75,12 @@ mod tests {
         assert_eq!(nonce.0, parsed_nonce.0);
         Ok(())
     }
+
+    #[test]
+    fn reproducible_with_deterministic_rng() -> Result<()> {
+        let nonce = KdfNonce::random_with(&DeterministicRng::new(42))?;
+        let same_nonce = KdfNonce::random_with(&DeterministicRng::new(42))?;
+        assert_eq!(String::from(&nonce), String::from(&same_nonce));
+        Ok(())
+    }
 }
@@ -31,6 +31,9 @@ pub enum KeyName {
     DbBackupDataEncryptionKey,
     #[strum(serialize = "SECRET-KEY-BACKUP-KEY-ENCRYPTION-KEY")]
     SkBackupKeyEncryptionKey,
+    /// Derived from the SK-DEK for each profile. Never stored.
+    #[strum(serialize = "DAPP-STORAGE-DATA-ENCRYPTION-KEY")]
+    DappStorageDataEncryptionKey,
 }
 
 impl From<KeyName> for String {
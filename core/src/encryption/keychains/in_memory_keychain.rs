@@ -5,11 +5,14 @@
 use std::{
     collections::HashMap,
     fmt::{Debug, Formatter},
+    fs, io,
+    path::PathBuf,
     sync::{Arc, RwLock},
 };
 
 use generic_array::ArrayLength;
-use zeroize::Zeroizing;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::{
     device::DeviceIdentifier,
@@ -18,14 +21,17 @@ use crate::{
         keychains::keychain::{KeychainError, KeychainImpl},
         KeyName,
     },
+    Error,
 };
 
 type SyncedKey = (DeviceIdentifier, KeyName);
 
-/// In-memory keychain for testing.
+/// In-memory keychain for testing and development.
 pub(super) struct InMemoryKeychain {
     local_data: Arc<RwLock<HashMap<KeyName, Zeroizing<Vec<u8>>>>>,
     synced_data: Arc<RwLock<HashMap<SyncedKey, Zeroizing<Vec<u8>>>>>,
+    /// If set, the keys are written to this file on every change.
+    file_path: Option<PathBuf>,
 }
 
 impl InMemoryKeychain {
@@ -33,8 +39,120 @@ impl InMemoryKeychain {
         InMemoryKeychain {
             local_data: Arc::new(RwLock::new(HashMap::new())),
             synced_data: Arc::new(RwLock::new(HashMap::new())),
+            file_path: None,
         }
     }
+
+    /// Keychain that is loaded from and saved to a file, so that a persistent DB can be
+    /// reopened by a new process. The keys are stored in plain text, so this is only for
+    /// development.
+    pub fn new_persistent(file_path: PathBuf) -> Result<Self, Error> {
+        let contents = match fs::read(&file_path) {
+            Ok(contents) => Zeroizing::new(contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Zeroizing::new(Vec::new())
+            }
+            Err(err) => {
+                return Err(Error::Fatal {
+                    error: format!("Failed to read keychain file: {err}"),
+                })
+            }
+        };
+        let mut local_data = HashMap::new();
+        let mut synced_data = HashMap::new();
+        if !contents.is_empty() {
+            let file: KeychainFile =
+                serde_json::from_slice(&contents).map_err(|err| Error::Fatal {
+                    error: format!("Invalid keychain file: {err}"),
+                })?;
+            for entry in file.local {
+                let name = parse_key_name(&entry.name)?;
+                local_data.insert(name, decode_key(&entry.key)?);
+            }
+            for entry in file.synced {
+                let name = parse_key_name(&entry.name)?;
+                let entry_name = synced_entry_name(&entry.device_identifier, name);
+                synced_data.insert(entry_name, decode_key(&entry.key)?);
+            }
+        }
+        Ok(InMemoryKeychain {
+            local_data: Arc::new(RwLock::new(local_data)),
+            synced_data: Arc::new(RwLock::new(synced_data)),
+            file_path: Some(file_path),
+        })
+    }
+
+    /// Write the keys to the keychain file if there is one.
+    fn persist(&self) -> Result<(), KeychainError> {
+        let file_path = match self.file_path.as_ref() {
+            Some(file_path) => file_path,
+            None => return Ok(()),
+        };
+        let local = self
+            .local_data
+            .read()?
+            .iter()
+            .map(|(name, key)| KeychainFileEntry {
+                name: name.to_string(),
+                key: hex::encode(key.as_slice()),
+            })
+            .collect();
+        let synced = self
+            .synced_data
+            .read()?
+            .iter()
+            .map(|((device_identifier, name), key)| SyncedKeychainFileEntry {
+                device_identifier: device_identifier.clone(),
+                name: name.to_string(),
+                key: hex::encode(key.as_slice()),
+            })
+            .collect();
+        let contents = serde_json::to_vec(&KeychainFile { local, synced })
+            .map(Zeroizing::new)
+            .map_err(|err| Error::Fatal {
+                error: format!("Failed to serialize keychain: {err}"),
+            })?;
+        fs::write(file_path, contents.as_slice()).map_err(|err| Error::Fatal {
+            error: format!("Failed to write keychain file: {err}"),
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeychainFile {
+    local: Vec<KeychainFileEntry>,
+    synced: Vec<SyncedKeychainFileEntry>,
+}
+
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct KeychainFileEntry {
+    name: String,
+    /// Hex encoded key material.
+    key: String,
+}
+
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct SyncedKeychainFileEntry {
+    #[zeroize(skip)]
+    device_identifier: DeviceIdentifier,
+    name: String,
+    /// Hex encoded key material.
+    key: String,
+}
+
+fn parse_key_name(name: &str) -> Result<KeyName, Error> {
+    name.parse().map_err(|_| Error::Fatal {
+        error: format!("Unknown key name in keychain file: '{name}'"),
+    })
+}
+
+fn decode_key(key: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+    hex::decode(key)
+        .map(Zeroizing::new)
+        .map_err(|_| Error::Fatal {
+            error: "Invalid key in keychain file".into(),
+        })
 }
 
 impl KeychainImpl for InMemoryKeychain {
@@ -65,9 +183,11 @@ impl KeychainImpl for InMemoryKeychain {
     }
 
     fn delete_local(&self, name: KeyName) -> Result<(), KeychainError> {
-        let mut d = self.local_data.write()?;
-        let _ = d.remove(&name);
-        Ok(())
+        {
+            let mut d = self.local_data.write()?;
+            let _ = d.remove(&name);
+        }
+        self.persist()
     }
 
     fn delete_synced(
@@ -75,10 +195,12 @@ impl KeychainImpl for InMemoryKeychain {
         device_identifier: &DeviceIdentifier,
         name: KeyName,
     ) -> Result<(), KeychainError> {
-        let mut d = self.synced_data.write()?;
-        let entry_name = synced_entry_name(device_identifier, name);
-        let _ = d.remove(&entry_name);
-        Ok(())
+        {
+            let mut d = self.synced_data.write()?;
+            let entry_name = synced_entry_name(device_identifier, name);
+            let _ = d.remove(&entry_name);
+        }
+        self.persist()
     }
 
     fn upsert_local<N: ArrayLength<u8>>(
@@ -86,9 +208,11 @@ impl KeychainImpl for InMemoryKeychain {
         name: KeyName,
         key: KeyMaterial<N>,
     ) -> Result<(), KeychainError> {
-        let mut d = self.local_data.write()?;
-        d.insert(name, key_to_vec(key));
-        Ok(())
+        {
+            let mut d = self.local_data.write()?;
+            d.insert(name, key_to_vec(key));
+        }
+        self.persist()
     }
 
     fn upsert_synced<N: ArrayLength<u8>>(
@@ -97,10 +221,12 @@ impl KeychainImpl for InMemoryKeychain {
         name: KeyName,
         key: KeyMaterial<N>,
     ) -> Result<(), KeychainError> {
-        let mut d = self.synced_data.write()?;
-        let entry_name = synced_entry_name(device_identifier, name);
-        d.insert(entry_name, key_to_vec(key));
-        Ok(())
+        {
+            let mut d = self.synced_data.write()?;
+            let entry_name = synced_entry_name(device_identifier, name);
+            d.insert(entry_name, key_to_vec(key));
+        }
+        self.persist()
     }
 }
 
@@ -3,6 +3,8 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::fmt::Debug;
+#[cfg(not(target_os = "ios"))]
+use std::path::Path;
 
 use generic_array::ArrayLength;
 
@@ -84,6 +86,14 @@ impl Keychain {
         Self { keychain }
     }
 
+    /// Keychain that is saved to a file for development with a persistent DB.
+    /// The keys are stored in plain text.
+    #[cfg(not(target_os = "ios"))]
+    pub fn new_persistent(file_path: &Path) -> Result<Self, Error> {
+        let keychain = InMemoryKeychain::new_persistent(file_path.to_path_buf())?;
+        Ok(Self { keychain })
+    }
+
     /// Get a symmetric key from the keychain.
     pub(in crate::encryption) fn get_local<N: ArrayLength<u8>>(
         &self,
@@ -224,6 +234,36 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(not(target_os = "ios"))]
+    #[test]
+    fn persistent_keychain_reloads_keys() -> Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let file_path = tmp_dir.path().join("keychain.json");
+        let device_id: DeviceIdentifier = "test-device".parse()?;
+        let local_key = KeyMaterial::<U32>::random()?;
+        let local_arr: GenericArray<u8, U32> =
+            GenericArray::clone_from_slice(local_key.as_ref());
+        let synced_key = KeyMaterial::<U32>::random()?;
+        let synced_arr: GenericArray<u8, U32> =
+            GenericArray::clone_from_slice(synced_key.as_ref());
+
+        let keychain = Keychain::new_persistent(&file_path)?;
+        keychain.upsert_local(KeyName::SkKeyEncryptionKey, local_key)?;
+        keychain.upsert_synced(&device_id, KeyName::RootBackupKey, synced_key)?;
+        keychain.upsert_local(KeyName::KdfSecret, KeyMaterial::<U32>::random()?)?;
+        keychain.delete_local_if_exists(KeyName::KdfSecret)?;
+        drop(keychain);
+
+        let keychain = Keychain::new_persistent(&file_path)?;
+        let res = keychain.get_local::<U32>(KeyName::SkKeyEncryptionKey)?;
+        assert_eq!(res.as_ref(), local_arr.as_slice());
+        let res = keychain.get_synced::<U32>(&device_id, KeyName::RootBackupKey)?;
+        assert_eq!(res.as_ref(), synced_arr.as_slice());
+        let res = keychain.get_local::<U32>(KeyName::KdfSecret);
+        assert!(matches!(res, Err(KeychainError::NotFound { .. })));
+        Ok(())
+    }
+
     #[test]
     fn upsert_ok() -> Result<()> {
         let keychain = Keychain::new();
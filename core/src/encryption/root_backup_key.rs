@@ -28,7 +28,7 @@ const ITERATIONS: u32 = 1;
 const PARALLELISM: u32 = 1;
 const TAG_BYTES: usize = 32;
 
-const MIN_BLAKE_CONTEXT_LEN: usize = 100;
+pub(super) const MIN_BLAKE_CONTEXT_LEN: usize = 100;
 
 /// The root cloud backup key that is derived from the backup password.
 /// More: https://sealvault.org/dev-docs/design/backup/#key-derivation-functions
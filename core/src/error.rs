@@ -4,11 +4,11 @@
 
 use diesel::r2d2;
 use jsonrpsee::types::{
-    error::{CallError, ErrorCode as JsonrpseeErrorCode, ErrorCode},
+    error::{CallError, ErrorCode as JsonrpseeErrorCode},
     ErrorObject,
 };
 
-use crate::{protocols::eth::in_page_provider::InPageErrorCode, CoreError};
+use crate::CoreError;
 
 #[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum Error {
@@ -27,6 +27,9 @@ pub enum Error {
     /// An error where the message can be presented directly to the user.
     #[error("{explanation}")]
     User { explanation: String },
+    /// The database file is corrupt. The host should offer restoring from a backup.
+    #[error("Database Corrupt: '{error}'")]
+    DatabaseCorrupt { error: String },
 }
 
 impl Error {
@@ -44,6 +47,10 @@ impl Error {
                 log::error!("Fatal error sending token: {error:?}");
                 "An unexpected error occurred. Please restart the application and try again!".into()
             }
+            CoreError::DatabaseCorrupt { error } => {
+                log::error!("Database corrupt: {error:?}");
+                "The app data is damaged. Please restore from a backup!".into()
+            }
         }
     }
 }
@@ -54,6 +61,7 @@ impl From<CoreError> for Error {
             CoreError::Retriable { error } => Error::Retriable { error },
             CoreError::Fatal { error } => Error::Fatal { error },
             CoreError::User { explanation } => Error::User { explanation },
+            CoreError::DatabaseCorrupt { error } => Error::DatabaseCorrupt { error },
         }
     }
 }
@@ -93,6 +101,14 @@ impl From<diesel::result::Error> for Error {
     }
 }
 
+impl From<diesel::ConnectionError> for Error {
+    fn from(err: diesel::ConnectionError) -> Self {
+        Error::Fatal {
+            error: format!("Failed to connect to DB with error: {err}"),
+        }
+    }
+}
+
 impl From<base64::DecodeError> for Error {
     fn from(_: base64::DecodeError) -> Self {
         Error::Fatal {
@@ -177,16 +193,6 @@ impl From<jsonrpsee::core::Error> for Error {
     }
 }
 
-impl From<InPageErrorCode> for Error {
-    fn from(code: InPageErrorCode) -> Self {
-        let code: ErrorCode = code.into();
-        Error::JsonRpc {
-            code,
-            message: code.to_string(),
-        }
-    }
-}
-
 impl From<CallError> for Error {
     fn from(error: CallError) -> Self {
         let error: ErrorObject = error.into();
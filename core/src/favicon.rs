@@ -42,6 +42,113 @@ pub async fn fetch_favicon_async(
     Ok(first)
 }
 
+/// Make sure the image is a raster image format that favicons are served in and within the
+/// size limits by parsing its header. Vector images like SVG are rejected, because they can
+/// contain scripts.
+pub fn validate_favicon(image: &[u8]) -> Result<(), Error> {
+    if image.len() > config::MAX_FAVICON_SIZE_BYTES {
+        return Err(Error::User {
+            explanation: format!(
+                "The favicon must be at most {} KiB.",
+                config::MAX_FAVICON_SIZE_BYTES / 1024
+            ),
+        });
+    }
+    let (width, height) = image_dimensions(image).ok_or_else(|| Error::User {
+        explanation: "The favicon must be a PNG, ICO, JPEG, GIF or WebP image.".into(),
+    })?;
+    let valid_dimension = |d: u32| d > 0 && d <= config::MAX_FAVICON_DIMENSION_PX;
+    if !valid_dimension(width) || !valid_dimension(height) {
+        return Err(Error::User {
+            explanation: format!(
+                "The favicon must be at most {px}x{px} pixels.",
+                px = config::MAX_FAVICON_DIMENSION_PX
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// The width and height of a PNG, ICO, JPEG, GIF or WebP image from its header or None if
+/// the image is not in one of these formats or the header is invalid.
+fn image_dimensions(image: &[u8]) -> Option<(u32, u32)> {
+    let u16_be =
+        |i: usize| Some(u16::from_be_bytes(image.get(i..i + 2)?.try_into().ok()?));
+    let u16_le =
+        |i: usize| Some(u16::from_le_bytes(image.get(i..i + 2)?.try_into().ok()?));
+    let u32_be =
+        |i: usize| Some(u32::from_be_bytes(image.get(i..i + 4)?.try_into().ok()?));
+    let u32_le =
+        |i: usize| Some(u32::from_le_bytes(image.get(i..i + 4)?.try_into().ok()?));
+
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // The first chunk must be the IHDR chunk with 13 bytes of data that starts with the
+        // big endian width and height. See https://www.w3.org/TR/png/#11IHDR
+        if u32_be(8)? != 13 || image.get(12..16)? != b"IHDR" {
+            return None;
+        }
+        Some((u32_be(16)?, u32_be(20)?))
+    } else if image.starts_with(&[0, 0, 1, 0]) {
+        // The ICO directory is followed by the entries and a dimension of 0 means 256 pixels.
+        // Entries may differ in size, so all of them are checked.
+        let count = usize::from(u16_le(4)?);
+        let mut max_dimensions: Option<(u32, u32)> = None;
+        for i in 0..count {
+            let entry = image.get(6 + 16 * i..6 + 16 * (i + 1))?;
+            let dimension = |d: u8| if d == 0 { 256 } else { u32::from(d) };
+            let (width, height) = (dimension(entry[0]), dimension(entry[1]));
+            let (max_width, max_height) = max_dimensions.unwrap_or_default();
+            max_dimensions = Some((max_width.max(width), max_height.max(height)));
+        }
+        max_dimensions
+    } else if image.starts_with(b"GIF87a") || image.starts_with(b"GIF89a") {
+        Some((u32::from(u16_le(6)?), u32::from(u16_le(8)?)))
+    } else if image.starts_with(&[0xff, 0xd8]) {
+        // Walk the segments until the start of frame segment that contains the dimensions.
+        // See https://www.w3.org/Graphics/JPEG/itu-t81.pdf B.2.2
+        let mut i = 2;
+        loop {
+            if *image.get(i)? != 0xff {
+                return None;
+            }
+            let marker = *image.get(i + 1)?;
+            let is_start_of_frame =
+                (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker);
+            if is_start_of_frame {
+                return Some((u32::from(u16_be(i + 7)?), u32::from(u16_be(i + 5)?)));
+            }
+            i += 2 + usize::from(u16_be(i + 2)?);
+        }
+    } else if image.starts_with(b"RIFF") && image.get(8..12)? == b"WEBP" {
+        // See https://developers.google.com/speed/webp/docs/riff_container
+        match image.get(12..16)? {
+            b"VP8 " => {
+                if image.get(23..26)? != [0x9d, 0x01, 0x2a] {
+                    return None;
+                }
+                let width = u32::from(u16_le(26)? & 0x3fff);
+                let height = u32::from(u16_le(28)? & 0x3fff);
+                Some((width, height))
+            }
+            b"VP8L" => {
+                if *image.get(20)? != 0x2f {
+                    return None;
+                }
+                let bits = u32_le(21)?;
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => {
+                let width = u32_le(24)? & 0xff_ffff;
+                let height = u32_le(26)? >> 8;
+                Some((width + 1, height + 1))
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
 fn site_url_to_api_url(url: Url) -> Result<Url, Error> {
     let domain = match url.host() {
         Some(Host::Domain(domain)) => domain,
@@ -83,4 +190,70 @@ mod tests {
         assert_eq!(url, expected_url);
         Ok(())
     }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut image = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        image.extend(width.to_be_bytes());
+        image.extend(height.to_be_bytes());
+        image.extend([8, 6, 0, 0, 0]);
+        image
+    }
+
+    fn ico(dimensions: &[u8]) -> Vec<u8> {
+        let mut image = vec![0, 0, 1, 0, dimensions.len() as u8, 0];
+        for dimension in dimensions {
+            let mut entry = [0u8; 16];
+            entry[0] = *dimension;
+            entry[1] = *dimension;
+            image.extend(entry);
+        }
+        image
+    }
+
+    #[test]
+    fn validates_favicon_format() {
+        assert!(validate_favicon(&png(32, 32)).is_ok());
+        assert!(validate_favicon(&ico(&[16, 32, 0])).is_ok());
+        assert!(validate_favicon(b"GIF89a\x10\0\x10\0").is_ok());
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xc0, 0, 11, 8, 0, 16, 0, 32,
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((32, 16)));
+        assert!(validate_favicon(&jpeg).is_ok());
+        let webp_lossless = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2f\x0f\xc0\x03\0";
+        assert_eq!(image_dimensions(webp_lossless), Some((16, 16)));
+        assert!(validate_favicon(webp_lossless).is_ok());
+        let webp_extended = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0\x0f\0\0\x1f\0\0";
+        assert_eq!(image_dimensions(webp_extended), Some((16, 32)));
+
+        // Signatures without a valid header are rejected.
+        assert!(matches!(
+            validate_favicon(&[0, 0, 1, 0, 1, 0]),
+            Err(Error::User { .. })
+        ));
+        assert!(matches!(
+            validate_favicon(b"RIFF\0\0\0\0WEBPVP8 "),
+            Err(Error::User { .. })
+        ));
+        assert!(matches!(
+            validate_favicon(&png(0, 16)),
+            Err(Error::User { .. })
+        ));
+        assert!(matches!(
+            validate_favicon(&png(16, config::MAX_FAVICON_DIMENSION_PX + 1)),
+            Err(Error::User { .. })
+        ));
+
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>";
+        assert!(matches!(validate_favicon(svg), Err(Error::User { .. })));
+        assert!(matches!(validate_favicon(&[]), Err(Error::User { .. })));
+        let too_large = [0xff, 0xd8, 0xff]
+            .into_iter()
+            .chain(iter::repeat(0).take(config::MAX_FAVICON_SIZE_BYTES))
+            .collect::<Vec<u8>>();
+        assert!(matches!(
+            validate_favicon(&too_large),
+            Err(Error::User { .. })
+        ));
+    }
 }
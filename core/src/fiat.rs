@@ -0,0 +1,371 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{fmt::Debug, str::FromStr};
+
+use derive_more::{AsRef, Display};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, protocols::eth, Error};
+
+/// Active ISO 4217 currency codes.
+const ISO_4217_CODES: [&str; 155] = [
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD",
+    "BDT", "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN",
+    "BZD", "CAD", "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF",
+    "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS",
+    "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA",
+    "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MYR", "MZN", "NAD",
+    "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN",
+    "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD",
+    "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT",
+    "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VES",
+    "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+/// ISO 4217 currencies without minor units.
+const ZERO_DECIMAL_CODES: [&str; 15] = [
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "VND",
+    "VUV", "XAF", "XOF",
+];
+
+/// An ISO 4217 currency code, eg. "USD".
+#[derive(Clone, Debug, Display, PartialEq, Eq, Hash, AsRef, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct FiatCurrency(String);
+
+impl FiatCurrency {
+    /// The currency for profiles that haven't set a preference.
+    pub fn default_currency() -> Self {
+        Self(config::DEFAULT_FIAT_CURRENCY.into())
+    }
+
+    /// The currency to use if the price feed doesn't support the user's currency.
+    pub fn fallback() -> Self {
+        Self(config::FALLBACK_FIAT_CURRENCY.into())
+    }
+
+    fn decimal_places(&self) -> u32 {
+        if ZERO_DECIMAL_CODES.contains(&self.0.as_str()) {
+            0
+        } else {
+            2
+        }
+    }
+}
+
+impl FromStr for FiatCurrency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_uppercase();
+        if ISO_4217_CODES.contains(&code.as_str()) {
+            Ok(Self(code))
+        } else {
+            Err(Error::User {
+                explanation: format!("'{s}' is not a supported ISO 4217 currency code."),
+            })
+        }
+    }
+}
+
+impl TryFrom<String> for FiatCurrency {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<FiatCurrency> for String {
+    fn from(value: FiatCurrency) -> Self {
+        value.0
+    }
+}
+
+/// Lets hosts plug in a source of token prices.
+pub trait PriceFeedI: Debug + Send + Sync {
+    /// Whether the feed has prices in the currency.
+    fn supports_currency(&self, currency: &FiatCurrency) -> bool;
+
+    /// The price of one native token of the chain in the currency. None if not available.
+    fn native_token_price(
+        &self,
+        chain_id: eth::ChainId,
+        currency: &FiatCurrency,
+    ) -> Option<Decimal>;
+}
+
+/// Price feed implemented by the host, eg. with the exchange rates of a price API.
+/// Exposed through FFI as a callback interface.
+pub trait CorePriceFeedI: Send + Sync + Debug {
+    /// Whether the feed has prices in the ISO 4217 currency code.
+    fn supports_currency(&self, currency: String) -> bool;
+
+    /// The price of one native token by its symbol, eg. "ETH", in the ISO 4217 currency
+    /// code as a decimal string. None if not available.
+    fn native_token_price(
+        &self,
+        native_token_symbol: String,
+        currency: String,
+    ) -> Option<String>;
+}
+
+/// Adapts the price feed of the host to the core.
+#[derive(Debug)]
+pub struct HostPriceFeed {
+    price_feed: Box<dyn CorePriceFeedI>,
+}
+
+impl HostPriceFeed {
+    pub fn new(price_feed: Box<dyn CorePriceFeedI>) -> Self {
+        Self { price_feed }
+    }
+}
+
+impl PriceFeedI for HostPriceFeed {
+    fn supports_currency(&self, currency: &FiatCurrency) -> bool {
+        self.price_feed.supports_currency(currency.to_string())
+    }
+
+    fn native_token_price(
+        &self,
+        chain_id: eth::ChainId,
+        currency: &FiatCurrency,
+    ) -> Option<Decimal> {
+        // Test net tokens have no value.
+        if chain_id.is_test_net() {
+            return None;
+        }
+        let symbol = chain_id.native_token().symbol();
+        let price = self
+            .price_feed
+            .native_token_price(symbol.clone(), currency.to_string())?;
+        match Decimal::from_str(&price) {
+            Ok(price) => Some(price),
+            Err(err) => {
+                log::warn!(
+                    "Invalid {symbol} price '{price}' from host price feed: {err}"
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Price feed that doesn't have any prices.
+#[derive(Debug, Default)]
+pub struct DefaultPriceFeed {}
+
+impl DefaultPriceFeed {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl PriceFeedI for DefaultPriceFeed {
+    fn supports_currency(&self, _: &FiatCurrency) -> bool {
+        false
+    }
+
+    fn native_token_price(&self, _: eth::ChainId, _: &FiatCurrency) -> Option<Decimal> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FiatValue {
+    /// The value rounded to the minor unit of the currency.
+    pub amount: Decimal,
+    pub currency: FiatCurrency,
+    /// Whether the value is in the fallback currency, because the feed doesn't support the
+    /// preferred currency.
+    pub fallback: bool,
+}
+
+/// Value of a native token amount in the preferred currency or the fallback currency if the
+/// feed doesn't support the preferred one. None if there is no price.
+pub fn native_token_fiat_value(
+    price_feed: &dyn PriceFeedI,
+    preferred_currency: &FiatCurrency,
+    amount: &eth::NativeTokenAmount,
+) -> Option<FiatValue> {
+    let (currency, fallback) = if price_feed.supports_currency(preferred_currency) {
+        (preferred_currency.clone(), false)
+    } else {
+        let fallback = FiatCurrency::fallback();
+        if !price_feed.supports_currency(&fallback) {
+            return None;
+        }
+        (fallback, true)
+    };
+    let price = price_feed.native_token_price(amount.chain_id, &currency)?;
+    // Amounts that don't fit in a decimal are way above the total supply.
+    let raw_amount = i128::try_from(u128::try_from(amount.amount).ok()?).ok()?;
+    let decimals = amount.chain_id.native_token().decimals();
+    let token_amount =
+        Decimal::try_from_i128_with_scale(raw_amount, decimals.into()).ok()?;
+    let value = token_amount.checked_mul(price)?;
+    Some(FiatValue {
+        amount: value.round_dp(currency.decimal_places()),
+        currency,
+        fallback,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    /// Price feed with the same native token price on all chains.
+    #[derive(Debug)]
+    pub struct PriceFeedMock {
+        prices: HashMap<String, Decimal>,
+    }
+
+    impl PriceFeedMock {
+        pub fn new() -> Self {
+            let prices = HashMap::from([
+                ("USD".into(), Decimal::from(2000)),
+                ("EUR".into(), Decimal::from(1800)),
+            ]);
+            Self { prices }
+        }
+    }
+
+    impl PriceFeedI for PriceFeedMock {
+        fn supports_currency(&self, currency: &FiatCurrency) -> bool {
+            self.prices.contains_key(currency.as_ref())
+        }
+
+        fn native_token_price(
+            &self,
+            _: eth::ChainId,
+            currency: &FiatCurrency,
+        ) -> Option<Decimal> {
+            self.prices.get(currency.as_ref()).copied()
+        }
+    }
+
+    #[test]
+    fn parses_iso_4217_codes() -> Result<()> {
+        let currency: FiatCurrency = "eur".parse()?;
+        assert_eq!(currency.to_string(), "EUR");
+        assert!("EURO".parse::<FiatCurrency>().is_err());
+        assert!("XYZ".parse::<FiatCurrency>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn converts_in_preferred_currency() -> Result<()> {
+        let amount =
+            eth::NativeTokenAmount::new_from_decimal(eth::ChainId::EthMainnet, "0.5")?;
+        let value =
+            native_token_fiat_value(&PriceFeedMock::new(), &"EUR".parse()?, &amount)
+                .expect("has price");
+        assert_eq!(value.amount, Decimal::from(900));
+        assert_eq!(value.currency.to_string(), "EUR");
+        assert!(!value.fallback);
+        Ok(())
+    }
+
+    #[test]
+    fn falls_back_for_unsupported_currency() -> Result<()> {
+        let amount =
+            eth::NativeTokenAmount::new_from_decimal(eth::ChainId::EthMainnet, "0.5")?;
+        let value =
+            native_token_fiat_value(&PriceFeedMock::new(), &"JPY".parse()?, &amount)
+                .expect("has price");
+        assert_eq!(value.amount, Decimal::from(1000));
+        assert_eq!(value.currency, FiatCurrency::fallback());
+        assert!(value.fallback);
+        Ok(())
+    }
+
+    #[test]
+    fn converts_raw_amount() -> Result<()> {
+        let price_feed = PriceFeedMock::new();
+        let currency: FiatCurrency = "USD".parse()?;
+
+        // 1.234567890123456789 ETH
+        let amount = eth::NativeTokenAmount::new(
+            eth::ChainId::EthMainnet,
+            1_234_567_890_123_456_789_u128.into(),
+        );
+        let value =
+            native_token_fiat_value(&price_feed, &currency, &amount).expect("has price");
+        assert_eq!(value.amount, "2469.14".parse()?);
+
+        let amount = eth::NativeTokenAmount::new(eth::ChainId::EthMainnet, 1.into());
+        let value =
+            native_token_fiat_value(&price_feed, &currency, &amount).expect("has price");
+        assert!(value.amount.is_zero());
+
+        let amount = eth::NativeTokenAmount::new(
+            eth::ChainId::EthMainnet,
+            ethers::types::U256::MAX,
+        );
+        assert!(native_token_fiat_value(&price_feed, &currency, &amount).is_none());
+
+        Ok(())
+    }
+
+    /// Host price feed with a price for Ether only that returns an invalid price for Matic.
+    #[derive(Debug)]
+    pub struct CorePriceFeedMock {}
+
+    impl CorePriceFeedI for CorePriceFeedMock {
+        fn supports_currency(&self, currency: String) -> bool {
+            currency == "USD"
+        }
+
+        fn native_token_price(&self, symbol: String, _: String) -> Option<String> {
+            match symbol.as_str() {
+                "ETH" => Some("2000.5".into()),
+                "MATIC" => Some("invalid".into()),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn converts_with_host_price_feed() -> Result<()> {
+        let price_feed = HostPriceFeed::new(Box::new(CorePriceFeedMock {}));
+        let currency: FiatCurrency = "USD".parse()?;
+
+        let amount =
+            eth::NativeTokenAmount::new_from_decimal(eth::ChainId::EthMainnet, "2")?;
+        let value =
+            native_token_fiat_value(&price_feed, &currency, &amount).expect("has price");
+        assert_eq!(value.amount, "4001".parse()?);
+
+        let amount =
+            eth::NativeTokenAmount::new_from_decimal(eth::ChainId::EthGoerli, "2")?;
+        assert!(native_token_fiat_value(&price_feed, &currency, &amount).is_none());
+
+        let amount =
+            eth::NativeTokenAmount::new_from_decimal(eth::ChainId::PolygonMainnet, "2")?;
+        assert!(native_token_fiat_value(&price_feed, &currency, &amount).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn no_value_without_prices() -> Result<()> {
+        let amount =
+            eth::NativeTokenAmount::new_from_decimal(eth::ChainId::EthMainnet, "0.5")?;
+        let value =
+            native_token_fiat_value(&DefaultPriceFeed::new(), &"USD".parse()?, &amount);
+        assert!(value.is_none());
+        Ok(())
+    }
+}
@@ -2,12 +2,13 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::fmt::Debug;
+use std::{fmt::Debug, time::Duration};
 
 use futures::StreamExt;
 use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache};
-use reqwest::Client;
+use reqwest::{redirect, Client};
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use url::Url;
 
 use crate::{config, Error};
 
@@ -18,7 +19,7 @@ pub struct HttpClient {
 
 impl HttpClient {
     pub fn new(cache_dir: String) -> Self {
-        let client = ClientBuilder::new(Client::new())
+        let client = ClientBuilder::new(bounded_client())
             .with(Cache(HttpCache {
                 mode: CacheMode::Default,
                 manager: CACacheManager { path: cache_dir },
@@ -28,36 +29,83 @@ impl HttpClient {
         Self { client }
     }
 
-    #[cfg(test)]
     pub fn new_without_cache() -> Self {
-        let client = ClientBuilder::new(Client::new()).build();
+        let client = ClientBuilder::new(bounded_client()).build();
         Self { client }
     }
 
     /// Fetch the URLs and return the bodies as bytes or None if there was an error.
+    /// Responses larger than `config::MAX_HTTP_ICON_SIZE_BYTES` are errors.
     pub async fn get_bytes(
         &self,
-        urls: impl Iterator<Item = url::Url>,
+        urls: impl Iterator<Item = Url>,
     ) -> Vec<Option<Vec<u8>>> {
         let result: Vec<Option<Vec<u8>>> = futures::stream::iter(urls)
-            .map(|url| {
-                let client = &self.client;
-                async move {
-                    // TODO log errors
-                    let response = client.get(url).send().await.ok();
-                    match response {
-                        Some(response) => {
-                            response.bytes().await.ok().map(|bytes| bytes.into())
-                        }
-                        None => None,
-                    }
-                }
+            .map(|url| async move {
+                self.fetch_bounded(
+                    url,
+                    config::MAX_HTTP_ICON_SIZE_BYTES,
+                    config::HTTP_FETCH_TIMEOUT,
+                )
+                .await
+                .map_err(|err| log::debug!("Failed to fetch bytes: {err}"))
+                .ok()
             })
             .buffered(config::MAX_ASYNC_CONCURRENT_REQUESTS)
             .collect()
             .await;
         result
     }
+
+    /// Fetch an http(s) URL and return the body. Aborts if the body is larger than `max_bytes`
+    /// or if the whole fetch takes longer than `timeout`. The body is read in chunks, so at most
+    /// one chunk above the limit is read into memory.
+    /// Other schemes (eg. `ipfs://`) must be resolved to an http(s) gateway URL first.
+    pub async fn fetch_bounded(
+        &self,
+        url: Url,
+        max_bytes: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, HttpClientError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(HttpClientError::UnsupportedScheme);
+        }
+        tokio::time::timeout(timeout, self.fetch_bounded_inner(url, max_bytes))
+            .await
+            .map_err(|_| HttpClientError::Timeout)?
+    }
+
+    async fn fetch_bounded_inner(
+        &self,
+        url: Url,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, HttpClientError> {
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        if let Some(content_length) = response.content_length() {
+            if content_length > max_bytes as u64 {
+                return Err(HttpClientError::ResponseTooLarge { max_bytes });
+            }
+        }
+        // The content length header is optional, so check the actual size too.
+        let mut body: Vec<u8> = Default::default();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > max_bytes {
+                return Err(HttpClientError::ResponseTooLarge { max_bytes });
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+/// Client with connect timeout and redirect cap. The total timeout is set per request.
+pub(crate) fn bounded_client() -> Client {
+    Client::builder()
+        .connect_timeout(config::HTTP_CONNECT_TIMEOUT)
+        .redirect(redirect::Policy::limited(config::HTTP_MAX_REDIRECTS))
+        .build()
+        // Same as `Client::new()` which panics if the TLS backend can't be initialized.
+        .expect("TLS backend can be initialized")
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -73,6 +121,12 @@ pub enum HttpClientError {
     },
     #[error("'{error}'")]
     Core { error: Error },
+    #[error("Only http and https URLs can be fetched.")]
+    UnsupportedScheme,
+    #[error("The response is larger than {max_bytes} bytes.")]
+    ResponseTooLarge { max_bytes: usize },
+    #[error("The request timed out.")]
+    Timeout,
 }
 
 impl From<reqwest_middleware::Error> for HttpClientError {
@@ -104,3 +158,96 @@ impl From<tokio::task::JoinError> for HttpClientError {
         error.into()
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::async_runtime as rt;
+
+    const MAX_BYTES: usize = 1024;
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// Serve one request on a local port. The handler is called after the request was read.
+    pub fn serve_once(
+        handler: impl FnOnce(&mut dyn Write) + Send + 'static,
+    ) -> Result<Url> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                handler(&mut stream);
+            }
+        });
+        Ok(url)
+    }
+
+    #[test]
+    fn fetches_small_response() -> Result<()> {
+        let url = serve_once(|stream| {
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+            );
+        })?;
+        let client = HttpClient::new_without_cache();
+
+        let body = rt::block_on(client.fetch_bounded(url, MAX_BYTES, TIMEOUT))?;
+
+        assert_eq!(body, b"hello");
+        Ok(())
+    }
+
+    #[test]
+    fn aborts_too_large_response() -> Result<()> {
+        // No content length header, so the limit must be enforced while streaming.
+        let url = serve_once(|stream| {
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n");
+            let _ = stream.write_all(&vec![0u8; 100 * MAX_BYTES]);
+        })?;
+        let client = HttpClient::new_without_cache();
+
+        let res = rt::block_on(client.fetch_bounded(url, MAX_BYTES, TIMEOUT));
+
+        assert!(matches!(res, Err(HttpClientError::ResponseTooLarge { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn aborts_slow_response() -> Result<()> {
+        let url = serve_once(|stream| {
+            thread::sleep(4 * TIMEOUT);
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+            );
+        })?;
+        let client = HttpClient::new_without_cache();
+
+        let res = rt::block_on(client.fetch_bounded(url, MAX_BYTES, TIMEOUT));
+
+        assert!(matches!(res, Err(HttpClientError::Timeout)));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_non_http_schemes() -> Result<()> {
+        let client = HttpClient::new_without_cache();
+        for url in [
+            "file:///etc/passwd",
+            "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi",
+        ] {
+            let res =
+                rt::block_on(client.fetch_bounded(Url::parse(url)?, MAX_BYTES, TIMEOUT));
+            assert!(matches!(res, Err(HttpClientError::UnsupportedScheme)));
+        }
+        Ok(())
+    }
+}
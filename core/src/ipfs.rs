@@ -0,0 +1,336 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use futures::StreamExt;
+use url::Url;
+
+use crate::{config, http_client::HttpClient, utils::is_https_or_loopback, Error};
+
+/// The health of a configured IPFS gateway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpfsGatewayHealth {
+    pub url: String,
+    /// Unhealthy gateways are tried after the healthy ones until the retry interval passes.
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug, Clone)]
+struct IpfsGateway {
+    url: Url,
+    consecutive_failures: u32,
+    last_failure_at: Option<Instant>,
+}
+
+impl IpfsGateway {
+    fn new(url: Url) -> Self {
+        Self {
+            url,
+            consecutive_failures: 0,
+            last_failure_at: None,
+        }
+    }
+
+    fn is_healthy(&self, retry_after: Duration) -> bool {
+        match self.last_failure_at {
+            Some(last_failure_at) => last_failure_at.elapsed() >= retry_after,
+            None => true,
+        }
+    }
+
+    /// The gateway URL of an `ipfs://<cid>/<path>` URL.
+    fn resolve(&self, ipfs_url: &Url) -> Result<Url, Error> {
+        let cid = ipfs_url.host_str().ok_or_else(|| Error::Retriable {
+            error: format!("No CID in IPFS url: '{ipfs_url}'"),
+        })?;
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .map_err(|_| Error::Fatal {
+                error: "IPFS gateway url can't be a base".into(),
+            })?
+            .pop_if_empty()
+            .extend(["ipfs", cid])
+            .extend(ipfs_url.path_segments().into_iter().flatten());
+        Ok(url)
+    }
+}
+
+/// HTTP gateways to fetch `ipfs://` content from. Gateways are tried in the configured order
+/// on failure, healthy ones first, so a gateway that is down doesn't break loading metadata.
+/// The configuration is kept in memory, so the host has to set it on each start.
+#[derive(Debug)]
+pub struct IpfsGateways {
+    retry_after: Duration,
+    gateways: RwLock<Vec<IpfsGateway>>,
+}
+
+impl IpfsGateways {
+    pub fn new(urls: &[String], retry_after: Duration) -> Result<Self, Error> {
+        let gateways = Self {
+            retry_after,
+            gateways: Default::default(),
+        };
+        gateways.set_gateways(urls)?;
+        Ok(gateways)
+    }
+
+    /// Replace the gateways. Fails if there are no gateways or a gateway URL is invalid.
+    pub fn set_gateways(&self, urls: &[String]) -> Result<(), Error> {
+        if urls.is_empty() {
+            return Err(Error::User {
+                explanation: "At least one IPFS gateway must be configured.".into(),
+            });
+        }
+        let gateways = urls
+            .iter()
+            .map(|url| Ok(IpfsGateway::new(parse_gateway_url(url)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        *self.gateways.write()? = gateways;
+        Ok(())
+    }
+
+    pub fn health(&self) -> Result<Vec<IpfsGatewayHealth>, Error> {
+        let gateways = self.gateways.read()?;
+        let health = gateways
+            .iter()
+            .map(|gateway| IpfsGatewayHealth {
+                url: gateway.url.to_string(),
+                healthy: gateway.is_healthy(self.retry_after),
+                consecutive_failures: gateway.consecutive_failures,
+            })
+            .collect();
+        Ok(health)
+    }
+
+    /// The gateway URL of an `ipfs://` URL with the preferred gateway for clients that load the
+    /// content themselves. Other URLs are returned unchanged.
+    pub fn gateway_url(&self, url: Url) -> Result<Url, Error> {
+        if url.scheme() != "ipfs" {
+            return Ok(url);
+        }
+        let gateway = self
+            .gateways_in_order()?
+            .into_iter()
+            .next()
+            .expect("at least one gateway");
+        gateway.resolve(&url)
+    }
+
+    /// Fetch the URLs and return the bodies as bytes or None if there was an error.
+    /// `ipfs://` URLs are fetched through the gateways.
+    pub async fn get_bytes(
+        &self,
+        http_client: &HttpClient,
+        urls: impl Iterator<Item = Url>,
+    ) -> Vec<Option<Vec<u8>>> {
+        futures::stream::iter(urls)
+            .map(|url| async move {
+                let res = if url.scheme() == "ipfs" {
+                    self.fetch(http_client, &url, config::MAX_HTTP_ICON_SIZE_BYTES)
+                        .await
+                } else {
+                    http_client
+                        .fetch_bounded(
+                            url,
+                            config::MAX_HTTP_ICON_SIZE_BYTES,
+                            config::HTTP_FETCH_TIMEOUT,
+                        )
+                        .await
+                        .map_err(|err| Error::Retriable {
+                            error: err.to_string(),
+                        })
+                };
+                res.map_err(|err| log::debug!("Failed to fetch bytes: {err}"))
+                    .ok()
+            })
+            .buffered(config::MAX_ASYNC_CONCURRENT_REQUESTS)
+            .collect()
+            .await
+    }
+
+    /// Fetch an `ipfs://` URL from the first gateway that succeeds.
+    pub async fn fetch(
+        &self,
+        http_client: &HttpClient,
+        ipfs_url: &Url,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut last_error: Option<String> = None;
+        for gateway in self.gateways_in_order()? {
+            let url = gateway.resolve(ipfs_url)?;
+            let res = http_client
+                .fetch_bounded(url, max_bytes, config::HTTP_FETCH_TIMEOUT)
+                .await;
+            self.record_result(&gateway.url, res.is_ok())?;
+            match res {
+                Ok(body) => return Ok(body),
+                Err(err) => {
+                    log::debug!("IPFS gateway '{}' failed: {err}", gateway.url);
+                    last_error = Some(err.to_string());
+                }
+            }
+        }
+        Err(Error::Retriable {
+            error: format!(
+                "All IPFS gateways failed. Last error: {}",
+                last_error.unwrap_or_default()
+            ),
+        })
+    }
+
+    /// Healthy gateways first, then the unhealthy ones, both in the configured order.
+    fn gateways_in_order(&self) -> Result<Vec<IpfsGateway>, Error> {
+        let gateways = self.gateways.read()?;
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = gateways
+            .iter()
+            .cloned()
+            .partition(|gateway| gateway.is_healthy(self.retry_after));
+        Ok(healthy.into_iter().chain(unhealthy).collect())
+    }
+
+    fn record_result(&self, url: &Url, success: bool) -> Result<(), Error> {
+        let mut gateways = self.gateways.write()?;
+        // The gateways may have been replaced in the meantime.
+        if let Some(gateway) = gateways.iter_mut().find(|gateway| &gateway.url == url) {
+            if success {
+                gateway.consecutive_failures = 0;
+                gateway.last_failure_at = None;
+            } else {
+                gateway.consecutive_failures += 1;
+                gateway.last_failure_at = Some(Instant::now());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for IpfsGateways {
+    fn default() -> Self {
+        let urls: Vec<String> = config::DEFAULT_IPFS_GATEWAYS
+            .iter()
+            .map(|url| url.to_string())
+            .collect();
+        Self::new(&urls, config::IPFS_GATEWAY_RETRY_AFTER).expect("static is ok")
+    }
+}
+
+fn parse_gateway_url(url: &str) -> Result<Url, Error> {
+    let invalid = || Error::User {
+        explanation: format!("Invalid IPFS gateway url: '{url}'"),
+    };
+    let parsed = Url::parse(url).map_err(|_| invalid())?;
+    let is_valid = is_https_or_loopback(&parsed)
+        && !parsed.cannot_be_a_base()
+        && parsed.query().is_none()
+        && parsed.fragment().is_none();
+    if is_valid {
+        Ok(parsed)
+    } else {
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::async_runtime as rt;
+
+    const IPFS_URL: &str =
+        "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/1.json";
+
+    /// Serve one request on a local port with the status and body.
+    fn serve_once(response: &'static [u8]) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(response);
+            }
+        });
+        Ok(url)
+    }
+
+    #[test]
+    fn falls_back_to_next_gateway() -> Result<()> {
+        let failing = serve_once(
+            b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )?;
+        let working = serve_once(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        )?;
+        let gateways = IpfsGateways::new(
+            &[failing.clone(), working],
+            config::IPFS_GATEWAY_RETRY_AFTER,
+        )?;
+        let client = HttpClient::new_without_cache();
+
+        let body = rt::block_on(gateways.fetch(&client, &Url::parse(IPFS_URL)?, 1024))?;
+
+        assert_eq!(body, b"hello");
+        let health = gateways.health()?;
+        assert_eq!(health[0].url, failing);
+        assert!(!health[0].healthy);
+        assert_eq!(health[0].consecutive_failures, 1);
+        assert!(health[1].healthy);
+        // The failing gateway is tried last until the retry interval passes.
+        let gateway_url = gateways.gateway_url(Url::parse(IPFS_URL)?)?;
+        assert!(!gateway_url.as_str().starts_with(&failing));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_gateway_url() -> Result<()> {
+        let gateways = IpfsGateways::new(
+            &["https://gateway.example.com/".into()],
+            config::IPFS_GATEWAY_RETRY_AFTER,
+        )?;
+
+        let url = gateways.gateway_url(Url::parse(IPFS_URL)?)?;
+        assert_eq!(
+            url.as_str(),
+            "https://gateway.example.com/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/1.json"
+        );
+        let https_url = Url::parse("https://example.com/1.png")?;
+        assert_eq!(gateways.gateway_url(https_url.clone())?, https_url);
+
+        Ok(())
+    }
+
+    #[test]
+    fn validates_gateways() -> Result<()> {
+        let gateways = IpfsGateways::default();
+
+        assert!(gateways.set_gateways(&[]).is_err());
+        assert!(gateways
+            .set_gateways(&["http://gateway.example.com".into()])
+            .is_err());
+        assert!(gateways
+            .set_gateways(&["https://gateway.example.com?a=b".into()])
+            .is_err());
+        assert!(gateways.set_gateways(&["not a url".into()]).is_err());
+        // Failed updates keep the previous gateways.
+        assert_eq!(
+            gateways.health()?.len(),
+            config::DEFAULT_IPFS_GATEWAYS.len()
+        );
+
+        Ok(())
+    }
+}
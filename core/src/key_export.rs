@@ -0,0 +1,276 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use diesel::SqliteConnection;
+use zeroize::Zeroizing;
+
+use crate::{
+    config,
+    db::{models as m, DeterministicId},
+    resources::CoreResourcesI,
+    rng::RngI,
+    Error, UniffiCustomTypeConverter,
+};
+
+/// Presented to the user when authenticating a private key export.
+const KEY_EXPORT_AUTHENTICATION_REASON: &str = "Export the private key of the wallet";
+
+/// Hex encoded private key that is zeroized on drop.
+pub type PrivateKeyHex = Zeroizing<String>;
+
+impl UniffiCustomTypeConverter for PrivateKeyHex {
+    type Builtin = String;
+
+    fn into_custom(val: Self::Builtin) -> uniffi::Result<Self> {
+        Ok(Zeroizing::new(val))
+    }
+
+    fn from_custom(mut obj: Self) -> Self::Builtin {
+        // Move out instead of copying, so there is only one copy to leave to the host.
+        std::mem::take(&mut *obj)
+    }
+}
+
+/// Pending private key export requests by their confirmation tokens. Kept in memory only, so
+/// restarting the app cancels them.
+#[derive(Debug, Default)]
+pub struct KeyExportConfirmations {
+    pending: Mutex<HashMap<String, PendingKeyExport>>,
+}
+
+#[derive(Debug)]
+struct PendingKeyExport {
+    address_id: m::AddressId,
+    expires_at: Instant,
+}
+
+impl KeyExportConfirmations {
+    /// Issue a single use token that confirms exporting the private key of the address.
+    pub fn issue(
+        &self,
+        rng: &dyn RngI,
+        address_id: m::AddressId,
+    ) -> Result<String, Error> {
+        let mut bytes = [0u8; 16];
+        rng.try_fill_bytes(&mut bytes)?;
+        let token = hex::encode(bytes);
+
+        let now = Instant::now();
+        let export = PendingKeyExport {
+            address_id,
+            expires_at: now + config::KEY_EXPORT_CONFIRMATION_TTL,
+        };
+        let mut pending = self.pending.lock()?;
+        pending.retain(|_, export| export.expires_at > now);
+        pending.insert(token.clone(), export);
+
+        Ok(token)
+    }
+
+    /// Consume the token. Fails if the token wasn't issued for the address or it expired.
+    pub fn consume(&self, token: &str, address_id: &m::AddressId) -> Result<(), Error> {
+        let export = self.pending.lock()?.remove(token);
+        match export {
+            Some(export)
+                if &export.address_id == address_id
+                    && export.expires_at > Instant::now() =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::User {
+                explanation: "The private key export must be confirmed again.".into(),
+            }),
+        }
+    }
+}
+
+/// Issue a confirmation token for exporting the private key of an address of the profile.
+/// The host must authenticate the user first.
+pub fn request_private_key_export(
+    resources: &dyn CoreResourcesI,
+    profile_id: &DeterministicId,
+    address_id: &m::AddressId,
+) -> Result<String, Error> {
+    {
+        let mut conn = resources.connection_pool().connection()?;
+        check_address_of_profile(&mut conn, profile_id, address_id)?;
+    }
+    // Don't hold the connection while waiting for the user.
+    let is_authenticated = resources
+        .ui_callbacks()
+        .authenticate_user(KEY_EXPORT_AUTHENTICATION_REASON.into());
+    if !is_authenticated {
+        return Err(Error::User {
+            explanation: "Exporting the private key requires authentication.".into(),
+        });
+    }
+    resources
+        .key_export_confirmations()
+        .issue(resources.rng(), address_id.clone())
+}
+
+/// Export the private key of an address of the profile with a confirmation token from
+/// `request_private_key_export`. Exports are rate limited and recorded without the key.
+pub fn export_private_key(
+    resources: &dyn CoreResourcesI,
+    profile_id: &DeterministicId,
+    address_id: &m::AddressId,
+    confirmation_token: &str,
+) -> Result<PrivateKeyHex, Error> {
+    // Consume the token first, so that it can't be retried.
+    resources
+        .key_export_confirmations()
+        .consume(confirmation_token, address_id)?;
+
+    let private_key = resources.connection_pool().deferred_transaction(
+        |mut tx_conn| {
+            check_address_of_profile(tx_conn.as_mut(), profile_id, address_id)?;
+
+            let window_seconds = config::KEY_EXPORT_WINDOW.as_secs();
+            let num_exports =
+                m::LocalKeyExport::count_in_window(tx_conn.as_mut(), window_seconds)?;
+            if num_exports >= config::MAX_KEY_EXPORTS_PER_WINDOW {
+                return Err(Error::User {
+                    explanation: "Too many private key exports. Please try again later."
+                        .into(),
+                });
+            }
+
+            let signing_key = m::Address::fetch_eth_signing_key(
+                &mut tx_conn,
+                resources.keychain(),
+                address_id,
+            )?;
+            m::LocalKeyExport::create(&mut tx_conn, address_id)?;
+            Ok(signing_key.key.secret_key_hex())
+        },
+    )?;
+    log::warn!("Exported the private key of address '{address_id}'");
+
+    Ok(private_key)
+}
+
+fn check_address_of_profile(
+    conn: &mut SqliteConnection,
+    profile_id: &DeterministicId,
+    address_id: &m::AddressId,
+) -> Result<(), Error> {
+    let address_profile_id = m::Address::fetch_profile_id(conn, address_id)?;
+    if &address_profile_id != profile_id {
+        return Err(Error::User {
+            explanation: "The address doesn't belong to the profile.".into(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{app_core::tests::TmpCore, protocols::eth::ChecksumAddress, CoreError};
+
+    #[test]
+    fn export_requires_confirmation_and_is_recorded_without_key() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile_id = tmp.first_profile().id;
+        let wallet = tmp.first_profile_wallet();
+
+        let res = tmp.core.export_private_key(
+            profile_id.clone(),
+            wallet.id.clone(),
+            "not-a-token".into(),
+        );
+        assert!(matches!(res, Err(CoreError::User { .. })));
+
+        let token = tmp
+            .core
+            .request_private_key_export(profile_id.clone(), wallet.id.clone())?;
+        let private_key = tmp.core.export_private_key(
+            profile_id.clone(),
+            wallet.id.clone(),
+            token.clone(),
+        )?;
+
+        let secret_key = k256::SecretKey::from_slice(&hex::decode(&*private_key)?)?;
+        let address = ChecksumAddress::new(&secret_key.public_key())?;
+        assert_eq!(address.to_string(), wallet.checksum_address);
+
+        // Tokens are single use.
+        let res = tmp
+            .core
+            .export_private_key(profile_id, wallet.id.clone(), token);
+        assert!(matches!(res, Err(CoreError::User { .. })));
+
+        let address_id: m::AddressId = wallet.id.parse()?;
+        let mut conn = tmp.connection_pool().connection()?;
+        let exports = m::LocalKeyExport::list_for_address(&mut conn, &address_id)?;
+        assert_eq!(exports.len(), 1);
+        assert!(!format!("{exports:?}").contains(private_key.as_str()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_requires_authentication() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile_id = tmp.first_profile().id;
+        let wallet_id = tmp.first_profile_wallet().id;
+
+        tmp.deny_user_authentication();
+        let res = tmp.core.request_private_key_export(profile_id, wallet_id);
+
+        assert!(matches!(res, Err(CoreError::User { .. })));
+        assert_eq!(
+            tmp.authentication_requests(),
+            vec![KEY_EXPORT_AUTHENTICATION_REASON.to_string()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn export_is_rate_limited() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let profile_id = tmp.first_profile().id;
+        let wallet_id = tmp.first_profile_wallet().id;
+
+        let export = || {
+            let token = tmp
+                .core
+                .request_private_key_export(profile_id.clone(), wallet_id.clone())?;
+            tmp.core
+                .export_private_key(profile_id.clone(), wallet_id.clone(), token)
+        };
+        for _ in 0..config::MAX_KEY_EXPORTS_PER_WINDOW {
+            export()?;
+        }
+
+        assert!(matches!(export(), Err(CoreError::User { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_address_of_other_profile() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let wallet_id = tmp.first_profile_wallet().id;
+        tmp.core.create_profile("Other".into(), "seal-1".into())?;
+        let other_profile_id = tmp
+            .core
+            .list_profiles()?
+            .into_iter()
+            .find(|profile| profile.name == "Other")
+            .expect("profile was created")
+            .id;
+
+        let res = tmp
+            .core
+            .request_private_key_export(other_profile_id, wallet_id);
+
+        assert!(matches!(res, Err(CoreError::User { .. })));
+        Ok(())
+    }
+}
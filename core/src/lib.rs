@@ -16,50 +16,82 @@ pub mod assets;
 pub mod async_runtime;
 pub mod config;
 pub mod dto;
+pub mod platform_html;
 pub mod protocols;
 
 mod backup;
+mod dapp_manifest;
 mod db;
+mod demo;
 mod device;
 mod encryption;
 mod error;
 mod favicon;
+mod fiat;
 mod http_client;
+mod ipfs;
+mod key_export;
+mod metrics;
 mod public_suffix_list;
 mod resources;
+mod rng;
 mod signatures;
+mod storage_usage;
 mod ui_callback;
 mod utils;
+mod version_info;
 
 // Interfaces defined in SealVaultCore.udl must be exposed directly.
 pub use crate::{
     app_core::{
-        AppCore, CoreArgs, EthChangeDappChainArgs, EthTransferFungibleTokenArgs,
-        EthTransferNativeTokenArgs,
+        AppCore, ClearedHistory, CoreArgs, DappSpendingLimitArgs, EthChangeDappChainArgs,
+        EthSigningRequestArgs, EthTransferFungibleTokenArgs, EthTransferNativeTokenArgs,
+        HistoryFilter, ListNftsArgs, NativeSpendingLimitArgs, OriginAccount,
+        TokenSpendingLimitArgs,
     },
     async_runtime::{block_on, handle},
     backup::{
         find_latest_backup as core_find_latest_backup,
-        restore_backup as core_restore_backup, BackupError as CoreBackupError,
-        BackupRestoreData, BackupStorageI as CoreBackupStorageI,
+        restore_backup as core_restore_backup, BackupConflict, BackupContents,
+        BackupDiff, BackupEntityDiff, BackupError as CoreBackupError, BackupFileInfo,
+        BackupReminder, BackupReminderSeverity, BackupRestoreData,
+        BackupStorageI as CoreBackupStorageI, DeviceBackups, RestoreStatus,
     },
+    dapp_manifest::DappImportResult,
+    db::{models::DappTrustLevel, rekey_database as core_rekey_database},
     dto::{
-        CoreAddress, CoreDapp, CoreError, CoreEthChain, CoreFungibleToken, CoreNFT,
-        CoreProfile, CoreTokens,
+        CoreAddress, CoreDapp, CoreDappDetail, CoreDappMetadata, CoreError, CoreEthChain,
+        CoreFiatAmount, CoreFungibleToken, CoreNFT, CoreNFTPage, CoreNativeBalance,
+        CoreProfile, CoreQueuedTransaction, CoreTokens, CoreTransaction,
+        CoreTransferPreview,
     },
     error::Error,
+    fiat::CorePriceFeedI,
+    ipfs::IpfsGatewayHealth,
+    key_export::PrivateKeyHex,
+    metrics::{MethodCount, MetricsSnapshot},
     protocols::{
-        eth::in_page_provider::{
-            CoreInPageCallbackI, DappApprovalParams, DappKeyProvider,
-            InPageRequestContextI,
+        eth::{
+            display_currency_amount as core_display_currency_amount,
+            in_page_provider::{
+                CoreInPageCallbackI, DappApprovalParams, DappKeyProvider,
+                DappSignBatchApprovalParams, DappSignBatchItem,
+                DappTransactionApprovalParams, InPageRequestContextI, ScriptOptions,
+            },
+            Allowance, CoreTransactionInspectorI, CurrencyAmount, DecodedCall,
+            DecodedCallArg, InspectedTransaction, NftSort, QueuedTransactionStatus,
+            RpcTestResult, SignatureKind, SignedTransaction, SigningRequest,
         },
         FungibleTokenType,
     },
+    storage_usage::StorageBreakdown,
     ui_callback::{
         CoreUICallbackI, DappAllotmentTransferResult, DappSignatureResult,
         DappTransactionApproved, DappTransactionResult, TokenTransferResult,
+        TransactionWarning, VerifyingContract,
     },
     utils::uri_fixup as core_uri_fixup,
+    version_info::VersionInfo,
 };
 
 // Build FFI based on SealVaultCore.udl.
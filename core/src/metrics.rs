@@ -0,0 +1,105 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        PoisonError, RwLock,
+    },
+};
+
+/// The number of in-page requests handled for a JSON-RPC method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodCount {
+    pub method: String,
+    pub count: u64,
+}
+
+/// Aggregate counts since the core was started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Sorted by method name.
+    pub requests_by_method: Vec<MethodCount>,
+    pub approvals_granted: u64,
+    pub approvals_rejected: u64,
+    pub transfers_sent: u64,
+    pub backups_created: u64,
+}
+
+/// In-memory usage counters for product metrics. They never contain personal data and they're
+/// reset when the app restarts.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests_by_method: RwLock<HashMap<String, AtomicU64>>,
+    approvals_granted: AtomicU64,
+    approvals_rejected: AtomicU64,
+    transfers_sent: AtomicU64,
+    backups_created: AtomicU64,
+}
+
+impl Metrics {
+    /// Count a handled in-page request. Only pass supported method names, as the dapp controls
+    /// the method of unsupported requests.
+    pub fn record_request(&self, method: &str) {
+        // Only take the write lock the first time a method is seen.
+        {
+            let requests = self
+                .requests_by_method
+                .read()
+                .unwrap_or_else(PoisonError::into_inner);
+            if let Some(count) = requests.get(method) {
+                count.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        let mut requests = self
+            .requests_by_method
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        requests
+            .entry(method.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count the user's response to an approval request from a dapp.
+    pub fn record_approval(&self, granted: bool) {
+        let counter = if granted {
+            &self.approvals_granted
+        } else {
+            &self.approvals_rejected
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_transfer_sent(&self) {
+        self.transfers_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_backup_created(&self) {
+        self.backups_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut requests_by_method: Vec<MethodCount> = self
+            .requests_by_method
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(method, count)| MethodCount {
+                method: method.clone(),
+                count: count.load(Ordering::Relaxed),
+            })
+            .collect();
+        requests_by_method.sort_by(|a, b| a.method.cmp(&b.method));
+        MetricsSnapshot {
+            requests_by_method,
+            approvals_granted: self.approvals_granted.load(Ordering::Relaxed),
+            approvals_rejected: self.approvals_rejected.load(Ordering::Relaxed),
+            transfers_sent: self.transfers_sent.load(Ordering::Relaxed),
+            backups_created: self.backups_created.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Platform-conditional content in HTML templates.
+//!
+//! A section wrapped in `<!--<section>-only` and `<section>-only-->` is commented out unless
+//! the section applies to the platform the page is rendered for. Eg.:
+//!
+//! ```html
+//! <!--desktop-only
+//! <script src="/js/in-page-provider.js"></script>
+//! desktop-only-->
+//! ```
+
+/// The platform a page is rendered for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// The SealVault iOS app. It injects the in-page provider itself.
+    Iphone,
+    /// A desktop browser without the SealVault app.
+    Desktop,
+    /// An Android browser without the SealVault app.
+    Android,
+}
+
+impl Platform {
+    /// Detect the platform from a `User-Agent` header value. Unknown user agents are treated
+    /// as desktop browsers.
+    pub fn from_user_agent(user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        if user_agent.contains("iphone") {
+            Self::Iphone
+        } else if user_agent.contains("android") {
+            Self::Android
+        } else {
+            Self::Desktop
+        }
+    }
+
+    /// The sections that are rendered for the platform. `desktop` sections are meant for
+    /// browsers without the SealVault app, so they're rendered on Android as well.
+    fn sections(&self) -> &'static [&'static str] {
+        match self {
+            Self::Iphone => &["iphone"],
+            Self::Desktop => &["desktop"],
+            Self::Android => &["android", "desktop"],
+        }
+    }
+}
+
+/// Uncomment the sections of an HTML template that apply to the platform. Sections for other
+/// platforms are left commented out.
+pub fn render_platform_html(html: &str, platform: Platform) -> String {
+    platform
+        .sections()
+        .iter()
+        .fold(html.to_string(), |html, section| {
+            html.replace(&format!("<!--{section}-only"), "")
+                .replace(&format!("{section}-only-->"), "")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "<head>\
+        <!--desktop-only<script src=\"/js/in-page-provider.js\"></script>desktop-only-->\
+        <!--iphone-only<meta name=\"iphone\">iphone-only-->\
+        <!--android-only<meta name=\"android\">android-only-->\
+        </head>";
+
+    #[test]
+    fn renders_iphone() {
+        let html = render_platform_html(TEMPLATE, Platform::Iphone);
+        assert_eq!(
+            html,
+            "<head>\
+            <!--desktop-only<script src=\"/js/in-page-provider.js\"></script>desktop-only-->\
+            <meta name=\"iphone\">\
+            <!--android-only<meta name=\"android\">android-only-->\
+            </head>"
+        );
+    }
+
+    #[test]
+    fn renders_desktop() {
+        let html = render_platform_html(TEMPLATE, Platform::Desktop);
+        assert_eq!(
+            html,
+            "<head>\
+            <script src=\"/js/in-page-provider.js\"></script>\
+            <!--iphone-only<meta name=\"iphone\">iphone-only-->\
+            <!--android-only<meta name=\"android\">android-only-->\
+            </head>"
+        );
+    }
+
+    #[test]
+    fn renders_android() {
+        let html = render_platform_html(TEMPLATE, Platform::Android);
+        assert_eq!(
+            html,
+            "<head>\
+            <script src=\"/js/in-page-provider.js\"></script>\
+            <!--iphone-only<meta name=\"iphone\">iphone-only-->\
+            <meta name=\"android\">\
+            </head>"
+        );
+    }
+
+    #[test]
+    fn detects_platform_from_user_agent() {
+        let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 16_3 like Mac OS X) \
+            AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148";
+        let android = "Mozilla/5.0 (Linux; Android 13; Pixel 7) AppleWebKit/537.36 \
+            (KHTML, like Gecko) Chrome/110.0.0.0 Mobile Safari/537.36";
+        let desktop = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) \
+            AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.3 Safari/605.1.15";
+
+        assert_eq!(Platform::from_user_agent(iphone), Platform::Iphone);
+        assert_eq!(Platform::from_user_agent(android), Platform::Android);
+        assert_eq!(Platform::from_user_agent(desktop), Platform::Desktop);
+        assert_eq!(Platform::from_user_agent(""), Platform::Desktop);
+    }
+}
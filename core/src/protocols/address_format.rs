@@ -0,0 +1,90 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::{protocols::eth::ChecksumAddress, Error};
+
+/// The encoding of addresses on a chain. Code that handles addresses should dispatch on the
+/// format of the chain instead of assuming EVM addresses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, strum_macros::Display)]
+pub enum AddressFormat {
+    /// 20-byte hex addresses with EIP-55 mixed-case checksum.
+    #[strum(serialize = "EVM")]
+    EvmChecksum,
+}
+
+/// An address in the format of its chain.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChainAddress {
+    Evm(ChecksumAddress),
+}
+
+impl ChainAddress {
+    pub fn address_format(&self) -> AddressFormat {
+        match self {
+            Self::Evm(_) => AddressFormat::EvmChecksum,
+        }
+    }
+
+    /// The EVM address. Fails for addresses in other formats.
+    pub fn evm_address(&self) -> Result<ChecksumAddress, Error> {
+        match self {
+            Self::Evm(address) => Ok(*address),
+        }
+    }
+}
+
+impl AddressFormat {
+    /// Parse the address in this format. Fails if the address is in a different format.
+    pub fn parse_address(&self, address: &str) -> Result<ChainAddress, Error> {
+        match self {
+            Self::EvmChecksum => {
+                let address: ChecksumAddress =
+                    address.parse().map_err(|err| Error::User {
+                        explanation: format!("Invalid {self} address: {err}"),
+                    })?;
+                Ok(ChainAddress::Evm(address))
+            }
+        }
+    }
+
+    /// Format the address for display. Fails if the address is in a different format.
+    pub fn format_address(&self, address: &ChainAddress) -> Result<String, Error> {
+        match (self, address) {
+            (Self::EvmChecksum, ChainAddress::Evm(address)) => Ok(address.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::protocols::eth::ChainId;
+
+    #[test]
+    fn evm_format_round_trips() -> Result<()> {
+        let raw = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        for chain_id in ChainId::iter() {
+            let address = chain_id.parse_address(raw)?;
+
+            assert_eq!(address.address_format(), AddressFormat::EvmChecksum);
+            assert_eq!(chain_id.format_address(&address)?, raw);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_address_in_wrong_format() {
+        let chain_id = ChainId::EthMainnet;
+
+        // Solana address
+        let res = chain_id.parse_address("7EcDhSYGxXyscszYEp35KHN8vvw3svAuLKTzXwCFLtV");
+        assert!(matches!(res, Err(Error::User { .. })));
+        // Invalid checksum
+        let res = chain_id.parse_address("0x5aaeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        assert!(matches!(res, Err(Error::User { .. })));
+    }
+}
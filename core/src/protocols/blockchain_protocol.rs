@@ -6,6 +6,8 @@ use std::str::FromStr;
 
 use diesel::{deserialize::FromSql, serialize::ToSql, sqlite::Sqlite};
 
+use crate::signatures::EllipticCurve;
+
 #[derive(
     Clone,
     Debug,
@@ -23,6 +25,15 @@ pub enum BlockchainProtocol {
     Ethereum,
 }
 
+impl BlockchainProtocol {
+    /// The curve of the keys that can sign for the protocol.
+    pub fn elliptic_curve(&self) -> EllipticCurve {
+        match self {
+            Self::Ethereum => EllipticCurve::Secp256k1,
+        }
+    }
+}
+
 // TODO (abiro) add derive macro
 impl FromSql<diesel::sql_types::Text, Sqlite> for BlockchainProtocol {
     fn from_sql(
@@ -63,4 +74,12 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn elliptic_curve() {
+        assert_eq!(
+            BlockchainProtocol::Ethereum.elliptic_curve(),
+            EllipticCurve::Secp256k1
+        );
+    }
 }
@@ -0,0 +1,255 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashSet;
+
+use ethers::types::U256;
+
+use crate::{
+    protocols::eth::{ChecksumAddress, RpcProvider},
+    Error,
+};
+
+/// Risk added if the spender can move an unlimited amount of tokens.
+const UNLIMITED_ALLOWANCE_RISK: u32 = 2;
+/// Risk added if the profile has never sent a transaction to the spender on the chain.
+const UNKNOWN_SPENDER_RISK: u32 = 1;
+
+/// Allowances at or above this are treated as unlimited. Dapps usually approve `U256::MAX`,
+/// but some tokens (eg. UNI, COMP) store allowances as `uint96` and cap them at its max.
+fn unlimited_allowance_threshold() -> U256 {
+    (U256::one() << 96) - 1
+}
+
+/// The current allowance of a spender on a fungible token of an owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenAllowance {
+    pub owner: ChecksumAddress,
+    pub token: ChecksumAddress,
+    pub spender: ChecksumAddress,
+    /// In the lowest denomination of the token.
+    pub amount: U256,
+}
+
+impl TokenAllowance {
+    pub fn is_unlimited(&self) -> bool {
+        self.amount >= unlimited_allowance_threshold()
+    }
+}
+
+/// An allowance with its risk assessment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allowance {
+    pub owner_checksum_address: String,
+    pub token_checksum_address: String,
+    pub spender_checksum_address: String,
+    /// Decimal amount in the lowest denomination of the token.
+    pub amount: String,
+    pub unlimited: bool,
+    /// Whether the profile has sent transactions to the spender on the chain.
+    pub known_spender: bool,
+    /// Higher is riskier. Zero means limited allowance to a known spender.
+    pub risk_score: u32,
+}
+
+/// Fetch the non-zero allowances that the owner has granted on a fungible token.
+/// Returns an empty list if the owner has never approved a spender on the token.
+pub async fn fetch_token_allowances(
+    rpc_provider: &RpcProvider,
+    owner: ChecksumAddress,
+    token: ChecksumAddress,
+) -> Result<Vec<TokenAllowance>, Error> {
+    let spenders = rpc_provider
+        .fungible_token_approval_spenders_async(token, owner)
+        .await?;
+
+    let mut results: Vec<TokenAllowance> = Default::default();
+    for spender in spenders {
+        let amount = rpc_provider
+            .fungible_token_allowance_async(token, owner, spender)
+            .await?;
+        // Revoked or spent
+        if amount.is_zero() {
+            continue;
+        }
+        results.push(TokenAllowance {
+            owner,
+            token,
+            spender,
+            amount,
+        })
+    }
+    Ok(results)
+}
+
+/// Score the allowances and sort them by risk, highest first. Allowances with the same risk
+/// are sorted by amount, largest first.
+pub fn rank_allowances(
+    allowances: Vec<TokenAllowance>,
+    known_spenders: &HashSet<ChecksumAddress>,
+) -> Vec<Allowance> {
+    let mut scored: Vec<(u32, bool, TokenAllowance)> = allowances
+        .into_iter()
+        .map(|allowance| {
+            let known_spender = known_spenders.contains(&allowance.spender);
+            let mut risk_score = 0;
+            if allowance.is_unlimited() {
+                risk_score += UNLIMITED_ALLOWANCE_RISK;
+            }
+            if !known_spender {
+                risk_score += UNKNOWN_SPENDER_RISK;
+            }
+            (risk_score, known_spender, allowance)
+        })
+        .collect();
+    scored.sort_by(|(a_risk, _, a), (b_risk, _, b)| {
+        b_risk.cmp(a_risk).then_with(|| b.amount.cmp(&a.amount))
+    });
+
+    scored
+        .into_iter()
+        .map(|(risk_score, known_spender, allowance)| Allowance {
+            owner_checksum_address: allowance.owner.into(),
+            token_checksum_address: allowance.token.into(),
+            spender_checksum_address: allowance.spender.into(),
+            amount: allowance.amount.to_string(),
+            unlimited: allowance.is_unlimited(),
+            known_spender,
+            risk_score,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use ethers::{signers::Signer, types::Address};
+
+    use super::*;
+    use crate::{
+        async_runtime as rt,
+        protocols::eth::{
+            contracts::{test_util::TestContractDeployer, ERC20Contract},
+            ChainId,
+        },
+    };
+
+    fn random_address() -> ChecksumAddress {
+        Address::random().into()
+    }
+
+    #[test]
+    fn ranks_unlimited_and_unknown_spenders_first() {
+        let owner = random_address();
+        let token = random_address();
+        let known = random_address();
+        let unknown = random_address();
+        let allowance = |spender, amount| TokenAllowance {
+            owner,
+            token,
+            spender,
+            amount,
+        };
+        let allowances = vec![
+            allowance(known, U256::from(100)),
+            allowance(unknown, U256::from(100)),
+            allowance(known, U256::MAX),
+            allowance(unknown, U256::MAX),
+            allowance(known, U256::from(1000)),
+        ];
+        let known_spenders: HashSet<ChecksumAddress> = [known].into();
+
+        let ranked = rank_allowances(allowances, &known_spenders);
+
+        let summary: Vec<(String, bool, u32)> = ranked
+            .into_iter()
+            .map(|a| (a.spender_checksum_address, a.unlimited, a.risk_score))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (unknown.to_string(), true, 3),
+                (known.to_string(), true, 2),
+                (unknown.to_string(), false, 1),
+                (known.to_string(), false, 0),
+                (known.to_string(), false, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn uint96_max_is_unlimited() {
+        let allowance = TokenAllowance {
+            owner: random_address(),
+            token: random_address(),
+            spender: random_address(),
+            amount: unlimited_allowance_threshold(),
+        };
+        assert!(allowance.is_unlimited());
+        let allowance = TokenAllowance {
+            amount: unlimited_allowance_threshold() - 1,
+            ..allowance
+        };
+        assert!(!allowance.is_unlimited());
+    }
+
+    #[test]
+    fn fetches_current_allowances() -> Result<()> {
+        let chain_id = ChainId::EthMainnet;
+        let contract_deployer = TestContractDeployer::init(chain_id);
+        let token = contract_deployer.deploy_fungible_token_test_contract()?;
+        let provider = Arc::new(contract_deployer.provider());
+        let contract = ERC20Contract::new(token, provider);
+        let owner: ChecksumAddress = contract_deployer.deployer_wallet().address().into();
+
+        let limited_spender = random_address();
+        let unlimited_spender = random_address();
+        let revoked_spender = random_address();
+        let approvals = [
+            (limited_spender, U256::from(100)),
+            (unlimited_spender, U256::MAX),
+            (revoked_spender, U256::from(100)),
+            (revoked_spender, U256::zero()),
+        ];
+        for (spender, amount) in approvals {
+            // Anvil needs explicit gas, see the `sends_fungible_token` test.
+            let call = contract.approve(spender.to_address(), amount).gas(100000);
+            let pending_tx = rt::block_on(call.send())?;
+            let _receipt = rt::block_on(pending_tx)?;
+        }
+
+        let allowances = rt::block_on(fetch_token_allowances(
+            &contract_deployer.rpc_provider,
+            owner,
+            token,
+        ))?;
+        let ranked = rank_allowances(allowances, &[limited_spender].into());
+
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(
+            ranked[0].spender_checksum_address,
+            unlimited_spender.to_string()
+        );
+        assert!(ranked[0].unlimited);
+        assert!(!ranked[0].known_spender);
+        assert_eq!(
+            ranked[1].spender_checksum_address,
+            limited_spender.to_string()
+        );
+        assert_eq!(ranked[1].amount, "100");
+        assert_eq!(ranked[1].risk_score, 0);
+
+        // No approvals
+        let allowances = rt::block_on(fetch_token_allowances(
+            &contract_deployer.rpc_provider,
+            random_address(),
+            token,
+        ))?;
+        assert!(allowances.is_empty());
+
+        Ok(())
+    }
+}
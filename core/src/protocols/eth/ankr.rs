@@ -142,6 +142,43 @@ pub trait AnkrRpcI<'a> {
 
         to_token_balances(fungible_balances, nft_balances)
     }
+
+    /// Fetch a page of the non-fungible tokens of an address on a chain from Ankr Advanced API
+    /// in the order returned by the API. The page size is capped at the API maximum.
+    async fn get_nfts(
+        &'a self,
+        address: ChecksumAddress,
+        chain_id: ChainId,
+        page_size: usize,
+        page_token: Option<String>,
+    ) -> Result<AnkrNftPage, AnkrRpcError> {
+        let mut params = ObjectParams::new();
+        params.insert("blockchain", vec![AnkrBlockchain::from(chain_id)])?;
+        params.insert("walletAddress", address.to_string())?;
+        params.insert("pageSize", page_size.clamp(1, PAGE_SIZE))?;
+        params.insert("pageToken", page_token)?;
+
+        let balances: AnkrNFTBalances =
+            self.client().request("ankr_getNFTsByOwner", params).await?;
+        let AnkrNFTBalances {
+            next_page_token,
+            assets,
+            ..
+        } = balances;
+
+        Ok(AnkrNftPage {
+            nfts: assets.into_iter().map(Into::into).collect(),
+            next_page_token: normalize_next_page_token(next_page_token),
+        })
+    }
+}
+
+/// A page of non-fungible tokens from Ankr Advanced API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnkrNftPage {
+    pub nfts: Vec<NFTBalance>,
+    /// Pass to the next call to get the next page. None on the last page.
+    pub next_page_token: Option<String>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -332,9 +369,9 @@ impl From<AnkrNFTBalance> for NFTBalance {
             name,
             symbol,
             contract_address,
+            contract_type,
             token_id,
             image_url,
-            ..
         } = value;
         NFTBalance {
             chain_id: blockchain.into(),
@@ -343,13 +380,22 @@ impl From<AnkrNFTBalance> for NFTBalance {
             collection_name,
             name,
             token_id,
+            standard: contract_type,
             image_url,
         }
     }
 }
 
 #[derive(
-    Clone, Debug, Serialize, Deserialize, EnumString, EnumIter, strum_macros::Display,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    strum_macros::Display,
 )]
 #[strum(serialize_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
@@ -682,7 +728,7 @@ mod tests {
         async fn get_nfts_by_owner(
             &self,
             walletAddress: String,
-            _blockchain: Vec<AnkrBlockchain>,
+            blockchain: Vec<AnkrBlockchain>,
             _pageSize: usize,
             pageToken: Option<String>,
         ) -> RpcResult<AnkrNFTBalances> {
@@ -748,8 +794,9 @@ mod tests {
                 }])
             };
 
-            let assets: Vec<AnkrNFTBalance> =
+            let mut assets: Vec<AnkrNFTBalance> =
                 serde_json::from_value(balances).expect("correct type mapping");
+            assets.retain(|asset| blockchain.contains(&asset.blockchain));
 
             // Simulate paging once
             let next_page_token = if pageToken.is_none() {
@@ -857,6 +904,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn get_nfts() -> Result<()> {
+        let ankr = AnkrRpc::new()?;
+        let address: ChecksumAddress = TEST_ADDRESS.parse()?;
+
+        let first_page =
+            rt::block_on(ankr.get_nfts(address, ChainId::PolygonMainnet, 50, None))?;
+        assert!(first_page.nfts.is_empty());
+        assert!(first_page.next_page_token.is_some());
+
+        let second_page = rt::block_on(ankr.get_nfts(
+            address,
+            ChainId::PolygonMainnet,
+            50,
+            first_page.next_page_token,
+        ))?;
+        let standards: Vec<&str> = second_page
+            .nfts
+            .iter()
+            .map(|nft| nft.standard.as_str())
+            .collect();
+        assert_eq!(standards, vec!["ERC721", "ERC1155"]);
+        assert!(second_page
+            .nfts
+            .iter()
+            .all(|nft| nft.chain_id == ChainId::PolygonMainnet));
+        assert!(second_page.next_page_token.is_none());
+        Ok(())
+    }
+
     #[test]
     fn ankr_balance_raw_integer() -> Result<()> {
         let s = r#""941696667609996629""#;
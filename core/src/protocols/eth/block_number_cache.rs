@@ -0,0 +1,169 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::lock::Mutex as AsyncMutex;
+
+use crate::{config, protocols::eth::ChainId, Error};
+
+#[derive(Debug, Clone, Copy)]
+struct CachedBlockNumber {
+    block_number: u64,
+    fetched_at: Instant,
+}
+
+type CacheEntry = Arc<AsyncMutex<Option<CachedBlockNumber>>>;
+
+/// Caches the latest block number per chain for a short time to avoid spamming the RPC
+/// endpoint when multiple UI elements ask for it at once.
+#[derive(Debug)]
+pub struct BlockNumberCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<ChainId, CacheEntry>>,
+}
+
+impl BlockNumberCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+
+    fn entry(&self, chain_id: ChainId) -> Result<CacheEntry, Error> {
+        let mut entries = self.entries.lock()?;
+        Ok(entries.entry(chain_id).or_default().clone())
+    }
+
+    /// Return the cached block number for the chain if it's fresh, otherwise fetch it.
+    /// Concurrent callers for the same chain wait for a single fetch.
+    pub async fn get_or_fetch<F, Fut>(
+        &self,
+        chain_id: ChainId,
+        fetch: F,
+    ) -> Result<u64, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<u64, Error>>,
+    {
+        let entry = self.entry(chain_id)?;
+        let mut cached = entry.lock().await;
+        if let Some(cached) = cached.as_ref() {
+            if cached.fetched_at.elapsed() < self.ttl {
+                return Ok(cached.block_number);
+            }
+        }
+        let block_number = fetch().await?;
+        *cached = Some(CachedBlockNumber {
+            block_number,
+            fetched_at: Instant::now(),
+        });
+        Ok(block_number)
+    }
+}
+
+impl Default for BlockNumberCache {
+    fn default() -> Self {
+        Self::new(config::BLOCK_NUMBER_CACHE_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::async_runtime as rt;
+
+    fn fetch_counted(
+        calls: &AtomicUsize,
+        block_number: u64,
+    ) -> impl Future<Output = Result<u64, Error>> + '_ {
+        async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(block_number)
+        }
+    }
+
+    #[test]
+    fn fetches_once_within_ttl() -> Result<()> {
+        let cache = BlockNumberCache::new(Duration::from_secs(60));
+        let calls = AtomicUsize::new(0);
+        let chain_id = ChainId::EthMainnet;
+
+        let first =
+            rt::block_on(cache.get_or_fetch(chain_id, || fetch_counted(&calls, 1)))?;
+        let second =
+            rt::block_on(cache.get_or_fetch(chain_id, || fetch_counted(&calls, 2)))?;
+
+        assert_eq!(first, 1);
+        assert_eq!(second, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refetches_after_ttl() -> Result<()> {
+        let cache = BlockNumberCache::new(Duration::ZERO);
+        let calls = AtomicUsize::new(0);
+        let chain_id = ChainId::EthMainnet;
+
+        let _ = rt::block_on(cache.get_or_fetch(chain_id, || fetch_counted(&calls, 1)))?;
+        let second =
+            rt::block_on(cache.get_or_fetch(chain_id, || fetch_counted(&calls, 2)))?;
+
+        assert_eq!(second, 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn caches_per_chain() -> Result<()> {
+        let cache = BlockNumberCache::new(Duration::from_secs(60));
+        let calls = AtomicUsize::new(0);
+
+        let eth = rt::block_on(
+            cache.get_or_fetch(ChainId::EthMainnet, || fetch_counted(&calls, 1)),
+        )?;
+        let polygon = rt::block_on(
+            cache.get_or_fetch(ChainId::PolygonMainnet, || fetch_counted(&calls, 2)),
+        )?;
+
+        assert_eq!(eth, 1);
+        assert_eq!(polygon, 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_are_not_cached() -> Result<()> {
+        let cache = BlockNumberCache::new(Duration::from_secs(60));
+        let chain_id = ChainId::EthMainnet;
+
+        let res = rt::block_on(cache.get_or_fetch(chain_id, || async {
+            Err(Error::Retriable {
+                error: "unreachable".into(),
+            })
+        }));
+        assert!(res.is_err());
+
+        let calls = AtomicUsize::new(0);
+        let block_number =
+            rt::block_on(cache.get_or_fetch(chain_id, || fetch_counted(&calls, 3)))?;
+        assert_eq!(block_number, 3);
+
+        Ok(())
+    }
+}
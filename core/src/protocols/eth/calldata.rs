@@ -0,0 +1,234 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use ethers::{
+    abi::{Function, HumanReadableParser, Token},
+    types::I256,
+};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+
+use crate::{protocols::eth::ChecksumAddress, Error};
+
+const SELECTOR_LEN: usize = 4;
+
+/// Human readable signatures of functions that are commonly called by dapps.
+const KNOWN_FUNCTIONS: &[&str] = &[
+    // ERC-20
+    "function transfer(address to, uint256 amount)",
+    "function approve(address spender, uint256 amount)",
+    // Also ERC-721 with `tokenId` as third argument
+    "function transferFrom(address from, address to, uint256 amount)",
+    "function increaseAllowance(address spender, uint256 addedValue)",
+    "function decreaseAllowance(address spender, uint256 subtractedValue)",
+    // ERC-721 and ERC-1155
+    "function setApprovalForAll(address operator, bool approved)",
+    "function safeTransferFrom(address from, address to, uint256 tokenId)",
+    "function safeTransferFrom(address from, address to, uint256 tokenId, bytes data)",
+    "function safeTransferFrom(address from, address to, uint256 id, uint256 amount, bytes data)",
+    // Wrapped native tokens
+    "function deposit()",
+    "function withdraw(uint256 amount)",
+];
+
+lazy_static! {
+    pub static ref SELECTOR_REGISTRY: SelectorRegistry = SelectorRegistry::new();
+}
+
+/// Function signatures by their 4-byte selectors.
+#[derive(Debug, Clone)]
+pub struct SelectorRegistry {
+    functions: HashMap<[u8; SELECTOR_LEN], Function>,
+}
+
+impl SelectorRegistry {
+    /// Registry with the known functions.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            functions: Default::default(),
+        };
+        for signature in KNOWN_FUNCTIONS {
+            registry.register(signature).expect("static ok");
+        }
+        registry
+    }
+
+    /// Register a function by its human readable signature,
+    /// eg. `function transfer(address to, uint256 amount)`.
+    pub fn register(&mut self, signature: &str) -> Result<(), Error> {
+        let function = HumanReadableParser::parse_function(signature).map_err(|err| {
+            Error::Fatal {
+                error: format!("Failed to parse function signature with error: '{err}'"),
+            }
+        })?;
+        self.functions.insert(function.short_signature(), function);
+        Ok(())
+    }
+
+    pub fn lookup(&self, selector: &[u8]) -> Option<&Function> {
+        let selector: [u8; SELECTOR_LEN] = selector.try_into().ok()?;
+        self.functions.get(&selector)
+    }
+
+    /// Decode the function call from transaction data.
+    pub fn decode(&self, data: &[u8]) -> Result<DecodedCall, Error> {
+        let selector = data.get(..SELECTOR_LEN).ok_or_else(|| Error::User {
+            explanation: "Calldata must start with a 4-byte function selector.".into(),
+        })?;
+        let function = match self.lookup(selector) {
+            Some(function) => function,
+            None => {
+                return Ok(DecodedCall::Unknown {
+                    selector: format!("0x{}", hex::encode(selector)),
+                })
+            }
+        };
+
+        let tokens = function
+            .decode_input(&data[SELECTOR_LEN..])
+            .map_err(|err| Error::User {
+                explanation: format!(
+                    "Failed to decode arguments of '{}' with error: '{err}'",
+                    function.signature()
+                ),
+            })?;
+        let args = function
+            .inputs
+            .iter()
+            .zip(tokens.iter())
+            .map(|(param, token)| DecodedCallArg {
+                name: param.name.clone(),
+                param_type: param.kind.to_string(),
+                value: format_token(token),
+            })
+            .collect();
+
+        Ok(DecodedCall::Known {
+            function_name: function.name.clone(),
+            signature: function.signature(),
+            args,
+        })
+    }
+}
+
+impl Default for SelectorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A function call decoded from transaction data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodedCall {
+    Known {
+        function_name: String,
+        /// Canonical signature, eg. `transfer(address,uint256)`
+        signature: String,
+        args: Vec<DecodedCallArg>,
+    },
+    /// No signature is known for the selector.
+    Unknown {
+        /// Hex encoded with 0x prefix.
+        selector: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedCallArg {
+    pub name: String,
+    /// Solidity type, eg. `uint256`
+    pub param_type: String,
+    /// Display value. Addresses are checksummed and integers are decimal.
+    pub value: String,
+}
+
+/// The 4-byte function selector of transaction data hex encoded with 0x prefix.
+/// None if the data is shorter than a selector.
+pub fn calldata_selector(data: &[u8]) -> Option<String> {
+    data.get(..SELECTOR_LEN)
+        .map(|selector| format!("0x{}", hex::encode(selector)))
+}
+
+fn format_token(token: &Token) -> String {
+    match token {
+        Token::Address(address) => ChecksumAddress::from(*address).to_string(),
+        Token::Uint(value) => value.to_string(),
+        Token::Int(value) => I256::from_raw(*value).to_string(),
+        Token::Bool(value) => value.to_string(),
+        Token::String(value) => value.clone(),
+        Token::Bytes(value) | Token::FixedBytes(value) => {
+            format!("0x{}", hex::encode(value))
+        }
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            format!("[{}]", tokens.iter().map(format_token).join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn decodes_erc20_transfer() -> Result<()> {
+        let to = "0x8ba1f109551bD432803012645Ac136ddd64DBA72";
+        let data = hex::decode(
+            "a9059cbb\
+             0000000000000000000000008ba1f109551bd432803012645ac136ddd64dba72\
+             00000000000000000000000000000000000000000000000000000000000003e8",
+        )?;
+
+        let decoded = SELECTOR_REGISTRY.decode(&data)?;
+
+        assert_eq!(
+            decoded,
+            DecodedCall::Known {
+                function_name: "transfer".into(),
+                signature: "transfer(address,uint256)".into(),
+                args: vec![
+                    DecodedCallArg {
+                        name: "to".into(),
+                        param_type: "address".into(),
+                        value: to.into(),
+                    },
+                    DecodedCallArg {
+                        name: "amount".into(),
+                        param_type: "uint256".into(),
+                        value: "1000".into(),
+                    },
+                ],
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_selector() -> Result<()> {
+        let data = hex::decode("deadbeef00")?;
+        let decoded = SELECTOR_REGISTRY.decode(&data)?;
+        assert_eq!(
+            decoded,
+            DecodedCall::Unknown {
+                selector: "0xdeadbeef".into()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_short_calldata() {
+        assert!(SELECTOR_REGISTRY.decode(&[0xa9, 0x05]).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_arguments() -> Result<()> {
+        let data = hex::decode("a9059cbb0000")?;
+        assert!(SELECTOR_REGISTRY.decode(&data).is_err());
+        Ok(())
+    }
+}
@@ -16,8 +16,9 @@ use strum_macros::{EnumIter, EnumString};
 use url::Url;
 
 use crate::{
-    protocols::eth::{
-        chain_settings::ChainSettings, token::NativeToken, NativeTokenAmount,
+    protocols::{
+        eth::{chain_settings::ChainSettings, token::NativeToken, NativeTokenAmount},
+        AddressFormat, ChainAddress,
     },
     Error,
 };
@@ -114,6 +115,26 @@ impl ChainId {
         }
     }
 
+    pub fn address_format(&self) -> AddressFormat {
+        match *self {
+            Self::EthMainnet => AddressFormat::EvmChecksum,
+            Self::EthGoerli => AddressFormat::EvmChecksum,
+
+            Self::PolygonMainnet => AddressFormat::EvmChecksum,
+            Self::PolygonMumbai => AddressFormat::EvmChecksum,
+        }
+    }
+
+    /// Parse an address in the address format of the chain.
+    pub fn parse_address(&self, address: &str) -> Result<ChainAddress, Error> {
+        self.address_format().parse_address(address)
+    }
+
+    /// Format an address for display in the address format of the chain.
+    pub fn format_address(&self, address: &ChainAddress) -> Result<String, Error> {
+        self.address_format().format_address(address)
+    }
+
     pub fn http_rpc_endpoint(&self) -> Url {
         let raw_url = match *self {
             ChainId::EthMainnet => "https://rpc.ankr.com/eth",
@@ -150,6 +171,24 @@ impl ChainId {
         }
     }
 
+    /// Transfers and transactions above this amount require extra confirmation from the user
+    /// unless the profile overrides it.
+    pub fn default_high_value_threshold(&self) -> NativeTokenAmount {
+        let units = match *self {
+            Self::EthMainnet => parse_units("1", "ether"),
+            Self::EthGoerli => parse_units("1", "ether"),
+            Self::PolygonMainnet => parse_units("1000", "ether"),
+            Self::PolygonMumbai => parse_units("1000", "ether"),
+        }
+        .expect("unit test catches panics");
+
+        match units {
+            ParseUnits::U256(amount) => NativeTokenAmount::new(*self, amount),
+            // Threshold cannot be negative. Unit test checks this exhaustively.
+            ParseUnits::I256(_) => unreachable!(),
+        }
+    }
+
     pub fn default_user_settings(&self) -> ChainSettings {
         let default_dapp_allotment = self.default_dapp_allotment();
         ChainSettings::new(default_dapp_allotment)
@@ -236,6 +275,14 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn default_high_value_thresholds_dont_panic() {
+        for chain_id in ChainId::iter() {
+            let threshold = chain_id.default_high_value_threshold();
+            assert!(threshold.amount > U256::zero());
+        }
+    }
+
     #[test]
     fn chain_rpc_endpoints_dont_panic() {
         for chain_id in ChainId::iter() {
@@ -0,0 +1,86 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashMap, str::FromStr};
+
+use ethers::types::Address;
+use lazy_static::lazy_static;
+
+use crate::protocols::eth::ChecksumAddress;
+
+/// Well-known contracts that dapps commonly request typed data signatures for.
+/// These are deployed at the same address on all supported chains.
+const KNOWN_CONTRACTS: &[(&str, &str)] = &[
+    (
+        "0x000000000022D473030F116dDEE9F6B43aC78BA3",
+        "Uniswap Permit2",
+    ),
+    (
+        "0x00000000006c3852cbEf3e08E8dF289169EdE581",
+        "OpenSea Seaport 1.1",
+    ),
+    (
+        "0x00000000000001ad428e4906aE43D8F9852d0dD6",
+        "OpenSea Seaport 1.4",
+    ),
+    (
+        "0xDef1C0ded9bec7F1a1670819833240f027b25EfF",
+        "0x Exchange Proxy",
+    ),
+];
+
+lazy_static! {
+    pub static ref CONTRACT_REGISTRY: ContractRegistry = ContractRegistry::new();
+}
+
+/// Human readable labels of contracts by their addresses.
+#[derive(Debug, Clone)]
+pub struct ContractRegistry {
+    labels: HashMap<ChecksumAddress, String>,
+}
+
+impl ContractRegistry {
+    /// Registry with the known contracts.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            labels: Default::default(),
+        };
+        for (address, label) in KNOWN_CONTRACTS {
+            let address = Address::from_str(address).expect("static ok");
+            registry.register(address.into(), label);
+        }
+        registry
+    }
+
+    pub fn register(&mut self, address: ChecksumAddress, label: &str) {
+        self.labels.insert(address, label.into());
+    }
+
+    pub fn lookup(&self, address: &ChecksumAddress) -> Option<&str> {
+        self.labels.get(address).map(|label| label.as_str())
+    }
+}
+
+impl Default for ContractRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_known_contracts_only() {
+        let permit2: ChecksumAddress =
+            Address::from_str("0x000000000022d473030f116ddee9f6b43ac78ba3")
+                .unwrap()
+                .into();
+        assert_eq!(CONTRACT_REGISTRY.lookup(&permit2), Some("Uniswap Permit2"));
+
+        let unknown: ChecksumAddress = Address::random().into();
+        assert_eq!(CONTRACT_REGISTRY.lookup(&unknown), None);
+    }
+}
@@ -6,11 +6,14 @@ use ethers::contract::abigen;
 abigen!(
     ERC20Contract,
     r#"[
+        function allowance(address owner, address spender) external view returns (uint256)
+        function approve(address spender, uint256 amount) external returns (bool)
         function balanceOf(address account) external view returns (uint256)
         function decimals() external view returns (uint8)
         function symbol() external view returns (string memory)
         function transfer(address to, uint256 amount) external returns (bool)
         event Transfer(address indexed from, address indexed to, uint256 value)
+        event Approval(address indexed owner, address indexed spender, uint256 value)
     ]"#,
     event_derives(serde::Deserialize, serde::Serialize)
 );
@@ -23,7 +26,7 @@ pub mod test_util {
     use ethers::{
         contract::ContractFactory,
         middleware::SignerMiddleware,
-        providers::{Http, Provider},
+        providers::Provider,
         signers::{LocalWallet, Signer},
         solc::{Artifact, Project, ProjectPathsConfig},
     };
@@ -31,7 +34,8 @@ pub mod test_util {
     use crate::{
         async_runtime as rt,
         protocols::eth::{
-            AnvilRpcManager, ChainId, ChecksumAddress, RpcManagerI, RpcProvider,
+            rpc_transport::RpcTransport, AnvilRpcManager, ChainId, ChecksumAddress,
+            RpcManagerI, RpcProvider,
         },
     };
 
@@ -58,7 +62,7 @@ pub mod test_util {
             wallet.with_chain_id(self.chain_id)
         }
 
-        pub fn provider(&self) -> Provider<Http> {
+        pub fn provider(&self) -> Provider<RpcTransport> {
             self.rpc_provider.provider.clone()
         }
 
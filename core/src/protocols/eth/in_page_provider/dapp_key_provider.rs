@@ -4,10 +4,13 @@
 
 use std::{fmt::Debug, sync::Arc};
 
-use ethers::types::{Address, Bytes, TransactionRequest, H256};
+use ethers::types::{
+    transaction::eip712::TypedData, Address, Bytes, NameOrAddress, TransactionRequest,
+    H256, U256,
+};
 use jsonrpsee::{
     core::server::helpers::MethodResponse,
-    types::{error::ErrorCode, ErrorObject, Request},
+    types::{error::ErrorCode, ErrorObject, Id, Request},
 };
 use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
@@ -18,20 +21,31 @@ use url::Url;
 
 use crate::{
     async_runtime as rt, config,
-    db::{models as m, ConnectionPool, DeterministicId},
+    db::{models as m, ConnectionPool, DeferredTxConnection, DeterministicId},
+    demo,
     favicon::fetch_favicon_async,
     http_client::HttpClient,
     protocols::eth::{
-        explorer,
-        in_page_provider::in_page_request::{
-            AddEthereumChainParameter, InPageRequest, InPageRequestParams,
-            SwitchEthereumChainParameter,
+        calldata_selector, explorer,
+        in_page_provider::{
+            dapp_request_error::DappRequestError,
+            in_page_request::{
+                AddEthereumChainParameter, DomainMetadata, InPageRequest,
+                InPageRequestParams, SwitchEthereumChainParameter,
+            },
+            PendingApproval,
         },
-        ChainId, ChainSettings, ChecksumAddress, RpcManagerI, Signer, SigningKey,
+        inspect_token_approvals, transaction_spends, ChainId, ChainSettings,
+        ChecksumAddress, DecodedCall, FeeEstimate, NativeTokenAmount, RpcManagerI,
+        Signer, SigningKey, TransactionQueueKey, TransactionSpend, CONTRACT_REGISTRY,
+        SELECTOR_REGISTRY, TYPED_DATA_SCHEMA_REGISTRY,
     },
     public_suffix_list::PublicSuffixList,
     resources::CoreResourcesI,
-    ui_callback::{DappSignatureResult, DappTransactionApproved, DappTransactionResult},
+    ui_callback::{
+        DappSignatureResult, DappTransactionApproved, DappTransactionResult,
+        TransactionWarning, VerifyingContract,
+    },
     CoreError, DappAllotmentTransferResult, Error,
 };
 
@@ -41,6 +55,8 @@ pub struct DappKeyProvider {
     resources: Arc<dyn CoreResourcesI>,
     request_context: Box<dyn InPageRequestContextI>,
     url: Url,
+    /// The id of the request if the host registered it to be able to cancel it.
+    request_id: Option<String>,
 }
 
 impl DappKeyProvider {
@@ -53,9 +69,15 @@ impl DappKeyProvider {
             resources,
             request_context,
             url,
+            request_id: None,
         })
     }
 
+    pub(crate) fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
     fn connection_pool(&self) -> &ConnectionPool {
         self.resources.connection_pool()
     }
@@ -82,11 +104,20 @@ impl DappKeyProvider {
         rt::spawn(self.in_page_request_async(raw_request))
     }
 
+    /// Cancel a request registered with `PendingRequests`.
+    pub(crate) fn cancel_request(
+        self,
+        request_id: String,
+    ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        rt::spawn(self.handle_cancel_request(request_id))
+    }
+
     /// Response to a `CoreInPageCallbackI.request_dapp_approval`
     pub(crate) fn user_approved_dapp(
         self,
         dapp_approval: DappApprovalParams,
     ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        self.resources.metrics().record_approval(true);
         rt::spawn(self.handle_user_approved_dapp(dapp_approval))
     }
 
@@ -95,64 +126,132 @@ impl DappKeyProvider {
         self,
         dapp_approval: DappApprovalParams,
     ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        self.resources.metrics().record_approval(false);
         rt::spawn(self.handle_user_rejected_dapp(dapp_approval))
     }
 
+    /// Respond to a `CoreInPageCallbackI.request_dapp_transaction_approval`
+    pub(crate) fn user_approved_dapp_transaction(
+        self,
+        tx_approval: DappTransactionApprovalParams,
+    ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        self.resources.metrics().record_approval(true);
+        rt::spawn(self.handle_user_approved_dapp_transaction(tx_approval))
+    }
+
+    /// Respond to a `CoreInPageCallbackI.request_dapp_transaction_approval`
+    pub(crate) fn user_rejected_dapp_transaction(
+        self,
+        tx_approval: DappTransactionApprovalParams,
+    ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        self.resources.metrics().record_approval(false);
+        rt::spawn(self.handle_user_rejected_dapp_transaction(tx_approval))
+    }
+
+    /// Respond to a `CoreInPageCallbackI.request_dapp_sign_batch_approval`
+    pub(crate) fn user_approved_dapp_sign_batch(
+        self,
+        batch_approval: DappSignBatchApprovalParams,
+    ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        self.resources.metrics().record_approval(true);
+        rt::spawn(self.handle_user_approved_dapp_sign_batch(batch_approval))
+    }
+
+    /// Respond to a `CoreInPageCallbackI.request_dapp_sign_batch_approval`
+    pub(crate) fn user_rejected_dapp_sign_batch(
+        self,
+        batch_approval: DappSignBatchApprovalParams,
+    ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        self.resources.metrics().record_approval(false);
+        rt::spawn(self.handle_user_rejected_dapp_sign_batch(batch_approval))
+    }
+
     pub async fn in_page_request_async(self, raw_request: String) -> Result<(), Error> {
-        match self.raw_json_rpc_request(raw_request).await? {
+        match self.raw_json_rpc_request(raw_request.clone()).await? {
             None => {
-                // We're waiting for a dapp approval callback from the UI, so no response.
+                // We're waiting for an approval callback from the UI, so no response.
             }
             Some(response) => self.respond_to_request(response).await?,
         }
         Ok(())
     }
 
+    async fn handle_cancel_request(self, request_id: String) -> Result<(), Error> {
+        let raw_request = match self.resources.pending_requests().cancel(&request_id)? {
+            Some(raw_request) => raw_request,
+            None => return Ok(()),
+        };
+        let request = parse_request(&raw_request)?;
+        let err: ErrorObject = DappRequestError::RequestCancelled.into();
+        let response = MethodResponse::error(request.id, err);
+        respond_with_callbacks(self.request_context.callbacks(), response).await
+    }
+
+    /// Take the approval prompt that the user responded to and continue processing its
+    /// request. Returns false if the prompt timed out, the request was cancelled or the user
+    /// responded already, in which case the response must be ignored.
+    fn take_approval(&mut self, approval_id: u64) -> Result<bool, Error> {
+        match self
+            .resources
+            .pending_requests()
+            .take_approval(approval_id)?
+        {
+            Some(PendingApproval { request_id }) => {
+                self.request_id = request_id;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     async fn raw_json_rpc_request(
         &self,
         raw_request: String,
     ) -> Result<Option<MethodResponse>, Error> {
         let request = parse_request(&raw_request)?;
 
-        let call = json!({
-            "method": request.method,
-            "params": request.params
-        });
-        let in_page_request: Result<InPageRequest, _> = serde_json::from_value(call);
+        // Checked before parsing so that the host can disable any method.
+        if self.is_disabled_method(&request.method) {
+            let err: ErrorObject = DappRequestError::UnsupportedMethod {
+                method: request.method.to_string(),
+            }
+            .into();
+            return Ok(Some(MethodResponse::error(request.id, err)));
+        }
 
-        match in_page_request {
+        match parse_in_page_request(&request) {
             Ok(in_page_request) => {
+                // Only supported methods are counted, because the dapp controls the method.
+                self.resources.metrics().record_request(&request.method);
                 match self.dispatch(in_page_request, &raw_request).await {
                     Ok(None) => Ok(None),
-                    Ok(Some(result)) => Ok(Some(MethodResponse::response(
-                        request.id,
-                        result,
-                        config::MAX_JSONRPC_RESPONSE_SIZE_BYTES,
-                    ))),
-                    Err(Error::JsonRpc { code, message }) => {
-                        // We need to select a data type even though data is none, <String>
-                        let data: Option<String> = None;
-                        let error_object = ErrorObject::owned(code.code(), message, data);
-                        Ok(Some(MethodResponse::error(request.id, error_object)))
+                    Ok(Some(result)) => {
+                        Ok(Some(to_method_response(request.id, Ok(result))))
                     }
-                    Err(error) => Err(error),
+                    Err(err) => Ok(Some(to_method_response(request.id, Err(err)))),
                 }
             }
             Err(err) => {
                 let message = err.to_string();
                 // TODO there is probably a better way to handle this
-                let code = if message.contains("unknown variant") {
-                    InPageErrorCode::MethodNotFound
+                let err = if message.contains("unknown variant") {
+                    DappRequestError::MethodNotFound { message }
                 } else {
-                    InPageErrorCode::InvalidParams
+                    DappRequestError::InvalidParams { message }
                 };
-                let data: Option<String> = None;
-                let error_object = ErrorObject::owned(code.to_i32(), message, data);
+                let error_object: ErrorObject = err.into();
                 Ok(Some(MethodResponse::error(request.id, error_object)))
             }
         }
     }
 
+    fn is_disabled_method(&self, method: &str) -> bool {
+        self.resources
+            .disabled_methods()
+            .iter()
+            .any(|disabled| disabled == method)
+    }
+
     /// Resolve JSON-RPC method.
     async fn dispatch<'a>(
         &self,
@@ -161,10 +260,18 @@ impl DappKeyProvider {
     ) -> Result<Option<serde_json::Value>, Error> {
         let maybe_session = self.fetch_session_for_approved_dapp().await?;
         match request {
-            InPageRequest::EthAccounts(..) if maybe_session.is_none() => {
+            // EIP-1102 connection flow: prompt the user to connect the dapp. The response is
+            // sent once the user approved or rejected.
+            InPageRequest::EthRequestAccounts(..) if maybe_session.is_none() => {
                 self.request_add_new_dapp(raw_request).await?;
                 Ok(None)
             }
+            // EIP-1193: an empty list if the dapp isn't connected, never an error. Never prompts
+            // and doesn't reveal whether the dapp was added to an other profile.
+            InPageRequest::EthAccounts(..) if maybe_session.is_none() => {
+                let accounts: Vec<ChecksumAddress> = Default::default();
+                Ok(Some(to_value(accounts)?))
+            }
             // MetaMask exposes chain id and net version even if the user didn't authorize the dapp.
             InPageRequest::EthChainId(..) if maybe_session.is_none() => {
                 Ok(Some(self.eth_chain_id_unauthorized()?))
@@ -172,58 +279,121 @@ impl DappKeyProvider {
             InPageRequest::EthNetworkId(..) if maybe_session.is_none() => {
                 Ok(Some(self.net_version_unauthorized()?))
             }
+            // Doesn't depend on the session.
+            InPageRequest::Web3ClientVersion(..) => Ok(Some(self.web3_client_version()?)),
+            // Respond the same way whether the dapp is connected or not to avoid leaking the
+            // connection status.
+            InPageRequest::SendDomainMetadata(metadata) => {
+                if let Some(session) = maybe_session {
+                    self.store_dapp_metadata(session, metadata).await?;
+                }
+                Ok(Some(to_value(true)?))
+            }
             _ => match maybe_session {
                 Some(session) => {
-                    let res = self.dispatch_authorized_methods(request, session).await?;
-                    Ok(Some(res))
-                }
-                None => {
-                    let err: Error = InPageErrorCode::Unauthorized.into();
-                    Err(err)
+                    self.dispatch_authorized_methods(request, session, raw_request)
+                        .await
                 }
+                None => Err(DappRequestError::Unauthorized.into()),
             },
         }
     }
 
     /// Resolve JSON-RPC method if user has approved the dapp in the current profile.
+    /// Returns none if the request is waiting for user approval.
     async fn dispatch_authorized_methods<'a>(
         &self,
         request: InPageRequest,
         session: m::LocalDappSession,
-    ) -> Result<serde_json::Value, Error> {
+        raw_request: &str,
+    ) -> Result<Option<serde_json::Value>, Error> {
         // let params = Params::new(request.params.map(|params| params.get()));
-        match request {
-            InPageRequest::EthAccounts(..) => self.eth_request_accounts(session).await,
+        let result = match request {
+            InPageRequest::EthRequestAccounts(..) => {
+                self.eth_request_accounts(session).await
+            }
+            InPageRequest::EthAccounts(..) => self.eth_accounts(session),
             InPageRequest::EthChainId(..) => self.eth_chain_id(session),
+            InPageRequest::EthNetworkId(..) => self.net_version(session),
             InPageRequest::EthSendTransaction(tx) => {
-                self.eth_send_transaction(tx, session).await
+                // May need to wait for the user to approve the transaction.
+                return self.eth_send_transaction(tx, session, raw_request).await;
             }
             InPageRequest::PersonalSign(message, address, password) => {
                 self.personal_sign(message, address, password, session)
                     .await
             }
+            InPageRequest::EthSignTypedDataV4(address, typed_data) => {
+                // Needs to wait for the user to approve the message. It's presented as a batch of
+                // one message.
+                self.request_dapp_sign_batch_approval(
+                    address,
+                    vec![typed_data],
+                    session,
+                    raw_request,
+                )
+                .await?;
+                return Ok(None);
+            }
+            InPageRequest::SignTypedDataBatch(address, typed_data) => {
+                // Needs to wait for the user to approve the batch.
+                self.request_dapp_sign_batch_approval(
+                    address,
+                    typed_data,
+                    session,
+                    raw_request,
+                )
+                .await?;
+                return Ok(None);
+            }
             InPageRequest::WalletAddEthereumChain(param) => {
                 self.wallet_add_ethereum_chain(param, session).await
             }
             InPageRequest::WalletSwitchEthereumChain(param) => {
                 self.wallet_switch_ethereum_chain(param, session).await
             }
-            InPageRequest::Web3ClientVersion(..) => self.web3_client_version(),
             InPageRequest::Web3Sha3(payload) => self.web3_sha3(payload).await,
             request => self.proxy_method(request, session).await,
-        }
+        };
+        result.map(Some)
     }
 
+    /// Respond unless the request was cancelled or timed out.
     async fn respond_to_request(&self, response: MethodResponse) -> Result<(), Error> {
+        let pending_requests = self.resources.pending_requests();
+        if !pending_requests.complete(self.request_id.as_deref())? {
+            return Ok(());
+        }
+        respond_with_callbacks(self.request_context.callbacks(), response).await
+    }
+
+    /// Reject the request if the user doesn't respond to the approval prompt in time. Must be
+    /// called before the prompt is shown, so that the user can't respond before the request
+    /// is marked as waiting for approval. Returns the approval id for the prompt.
+    fn schedule_approval_timeout(&self, raw_request: &str) -> Result<u64, Error> {
+        let approval_id = self
+            .resources
+            .pending_requests()
+            .await_approval(self.request_id.as_deref())?;
+        let resources = self.resources.clone();
         let callbacks = self.request_context.callbacks();
-        rt::spawn_blocking(move || {
-            // Prevent reflected XSS by passing the result as hexadecimal utf-8 bytes to JS.
-            // See the security model in the developer docs for more.
-            let hex_response = hex::encode(response.result.as_bytes());
-            callbacks.respond(hex_response);
-            Ok(())
-        })
-        .await?
+        let raw_request = raw_request.to_string();
+        rt::spawn(async move {
+            tokio::time::sleep(resources.approval_timeout()).await;
+            if !resources.pending_requests().time_out(approval_id)? {
+                return Ok(());
+            }
+            let request = parse_request(&raw_request)?;
+            let err: ErrorObject = DappRequestError::ApprovalTimedOut.into();
+            let response = MethodResponse::error(request.id, err);
+            let ui_resources = resources.clone();
+            rt::spawn_blocking(move || {
+                ui_resources.ui_callbacks().approval_timed_out(raw_request);
+            })
+            .await?;
+            respond_with_callbacks(callbacks, response).await
+        });
+        Ok(approval_id)
     }
 
     /// Notify the in-page JS about an event in the background.
@@ -293,10 +463,10 @@ impl DappKeyProvider {
         if !request.allow_proxy() {
             // Must return 4200 for unsupported method for Ethereum
             // https://github.com/ethereum/EIPs/blob/master/EIPS/eip-1193.md#supported-rpc-methods
-            return Err(Error::JsonRpc {
-                code: InPageErrorCode::UnsupportedMethod.into(),
-                message: format!("This method is not supported: '{}'", request),
-            });
+            return Err(DappRequestError::UnsupportedMethod {
+                method: request.to_string(),
+            }
+            .into());
         }
 
         let provider = self.rpc_manager().eth_api_provider(session.chain_id);
@@ -322,6 +492,15 @@ impl DappKeyProvider {
         Ok(result)
     }
 
+    /// The chain id of the session as a decimal string.
+    fn net_version(
+        &self,
+        session: m::LocalDappSession,
+    ) -> Result<serde_json::Value, Error> {
+        let result = to_value(session.chain_id.network_version())?;
+        Ok(result)
+    }
+
     // Only ok to expose unauthorized if we respond to all requests with the same response,
     // otherwise it's a privacy leak.
     fn net_version_unauthorized(&self) -> Result<serde_json::Value, Error> {
@@ -349,6 +528,36 @@ impl DappKeyProvider {
         Ok(result)
     }
 
+    fn eth_accounts(
+        &self,
+        session: m::LocalDappSession,
+    ) -> Result<serde_json::Value, Error> {
+        let m::LocalDappSession { address, .. } = session;
+        to_value(vec![address])
+    }
+
+    async fn store_dapp_metadata(
+        &self,
+        session: m::LocalDappSession,
+        metadata: DomainMetadata,
+    ) -> Result<(), Error> {
+        let DomainMetadata {
+            name,
+            icon,
+            description,
+        } = metadata;
+        let params = m::DappMetadataParams::builder()
+            .name(name)
+            .icon_url(icon)
+            .description(description)
+            .build();
+        self.connection_pool()
+            .deferred_transaction_async(move |mut tx_conn| {
+                m::DappMetadata::upsert(&mut tx_conn, &session.dapp_id, &params)
+            })
+            .await
+    }
+
     async fn request_add_new_dapp<'a>(&self, raw_request: &str) -> Result<(), Error> {
         let resources = self.resources.clone();
         let (profile_id, chain_id, chain_settings) = resources
@@ -370,6 +579,7 @@ impl DappKeyProvider {
         let favicon = self.fetch_favicon().await?;
         let dapp_identifier = m::Dapp::dapp_identifier(url, self.public_suffix_list())?;
         let dapp_allotment = chain_settings.default_dapp_allotment;
+        let approval_id = self.schedule_approval_timeout(raw_request)?;
         let dapp_approval = DappApprovalParams::builder()
             .profile_id(profile_id)
             .dapp_identifier(dapp_identifier)
@@ -380,6 +590,7 @@ impl DappKeyProvider {
             .chain_display_name(chain_id.display_name())
             .chain_id(chain_id)
             .json_rpc_request(raw_request)
+            .approval_id(approval_id)
             .build();
 
         rt::spawn_blocking(move || {
@@ -391,9 +602,19 @@ impl DappKeyProvider {
     }
 
     async fn handle_user_approved_dapp(
-        self,
+        mut self,
         dapp_approval: DappApprovalParams,
     ) -> Result<(), Error> {
+        if !self.take_approval(dapp_approval.approval_id)? {
+            return Ok(());
+        }
+        if !self.is_page_on_dapp(&dapp_approval.dapp_identifier)? {
+            // Request approval for the dapp that the page is on now instead of adding it with
+            // the approval for an other dapp.
+            return self
+                .request_add_new_dapp(&dapp_approval.json_rpc_request)
+                .await;
+        }
         let request = parse_request(&dapp_approval.json_rpc_request)?;
         let session = self.add_new_dapp(dapp_approval.clone()).await?;
         let accounts = self.eth_request_accounts(session).await?;
@@ -407,11 +628,14 @@ impl DappKeyProvider {
     }
 
     async fn handle_user_rejected_dapp(
-        self,
+        mut self,
         dapp_approval: DappApprovalParams,
     ) -> Result<(), Error> {
+        if !self.take_approval(dapp_approval.approval_id)? {
+            return Ok(());
+        }
         let request = parse_request(&dapp_approval.json_rpc_request)?;
-        let err: ErrorObject = InPageErrorCode::UserRejected.into();
+        let err: ErrorObject = DappRequestError::UserRejected.into();
         let response = MethodResponse::error(request.id, err);
         self.respond_to_request(response).await?;
         Ok(())
@@ -440,6 +664,7 @@ impl DappKeyProvider {
                     .profile_id(&profile_id)
                     .chain_id(chain_id)
                     .dapp_id(Some(&dapp_id))
+                    .rng(resources.rng())
                     .build();
                 m::Address::create_eth_key_and_address(
                     &mut tx_conn,
@@ -468,9 +693,13 @@ impl DappKeyProvider {
         &self,
         session: m::LocalDappSession,
     ) -> Result<(), Error> {
+        // Demo wallets aren't funded.
+        if self.resources.is_demo() {
+            return Ok(());
+        }
         let resources = self.resources.clone();
         let session_clone = session.clone();
-        let (chain_settings, wallet_signing_key) = self
+        let (chain_settings, wallet_address_id, wallet_signing_key) = self
             .connection_pool()
             .deferred_transaction_async(move |mut tx_conn| {
                 let wallet_address_id = m::Address::fetch_eth_wallet_id(
@@ -487,13 +716,14 @@ impl DappKeyProvider {
                     resources.keychain(),
                     &wallet_address_id,
                 )?;
-                Ok((chain_settings, wallet_signing_key))
+                Ok((chain_settings, wallet_address_id, wallet_signing_key))
             })
             .await?;
         // Call blockchain API in background.
         rt::spawn(Self::make_default_dapp_allotment_transfer(
             self.resources.clone(),
             chain_settings,
+            wallet_address_id,
             wallet_signing_key,
             session_clone,
         ));
@@ -503,6 +733,7 @@ impl DappKeyProvider {
     async fn make_default_dapp_allotment_transfer(
         resources: Arc<dyn CoreResourcesI>,
         chain_settings: ChainSettings,
+        wallet_address_id: m::AddressId,
         wallet_signing_key: SigningKey,
         session: m::LocalDappSession,
     ) -> Result<(), Error> {
@@ -511,14 +742,40 @@ impl DappKeyProvider {
             .eth_api_provider(wallet_signing_key.chain_id);
         // Call fails if there are insufficient funds.
         let res = async {
-            let tx_hash = provider
-                .transfer_native_token_async(
-                    &wallet_signing_key,
-                    session.address,
-                    &chain_settings.default_dapp_allotment,
-                )
+            let tx = provider.native_token_transfer_tx(
+                &wallet_signing_key,
+                session.address,
+                &chain_settings.default_dapp_allotment,
+            )?;
+            // Queued with the wallet's transfers, so that the allotment counts towards the
+            // pending balance of the wallet.
+            let queue = resources.transaction_queue();
+            let queue_key = TransactionQueueKey {
+                profile_id: session.profile_id.clone(),
+                chain_id: wallet_signing_key.chain_id,
+                address: wallet_signing_key.address.to_address(),
+            };
+            let (nonce, tx_hash) = provider
+                .send_queued_transaction_async(queue, &queue_key, &wallet_signing_key, tx)
                 .await?;
-            provider.wait_for_confirmation_async(tx_hash).await?;
+            m::Transaction::record_submitted_eth(
+                resources.connection_pool(),
+                wallet_address_id,
+                wallet_signing_key.chain_id,
+                tx_hash,
+                Some(session.address),
+                None,
+            )
+            .await;
+            let confirmation = provider.wait_for_confirmation_async(tx_hash).await;
+            let queue_res = match confirmation.as_ref() {
+                Ok(_) => queue.mark_confirmed(&queue_key, nonce),
+                Err(err) => queue.mark_failed(&queue_key, nonce, err.to_string()),
+            };
+            if let Err(err) = queue_res {
+                log::error!("Failed to update transaction queue due to error: {err}");
+            }
+            confirmation?;
             Ok::<(), Error>(())
         }
         .await;
@@ -534,8 +791,7 @@ impl DappKeyProvider {
 
             let mut callback_result = DappAllotmentTransferResult::builder()
                 .dapp_identifier(dapp_human_identifier)
-                .amount(amount.display_amount())
-                .token_symbol(amount.chain_id.native_token().symbol())
+                .amount(&amount)
                 .chain_display_name(amount.chain_id.display_name())
                 .build();
 
@@ -572,6 +828,20 @@ impl DappKeyProvider {
         .await?
     }
 
+    /// Whether the page is on the dapp that an approval was requested for. The page may have
+    /// navigated to an other origin while the user was reviewing the request.
+    fn is_page_on_dapp(&self, dapp_identifier: &str) -> Result<bool, Error> {
+        let page_dapp_identifier =
+            m::Dapp::dapp_identifier(self.url.clone(), self.public_suffix_list())?;
+        let is_on_dapp = page_dapp_identifier == dapp_identifier;
+        if !is_on_dapp {
+            log::warn!(
+                "Page is on '{page_dapp_identifier}' instead of approved dapp '{dapp_identifier}'"
+            );
+        }
+        Ok(is_on_dapp)
+    }
+
     async fn fetch_session_for_approved_dapp(
         &self,
     ) -> Result<Option<m::LocalDappSession>, Error> {
@@ -601,6 +871,10 @@ impl DappKeyProvider {
                                 &mut tx_conn,
                                 &params,
                             )?;
+                        m::LocalDappSession::touch_last_used(
+                            &mut tx_conn,
+                            &session.uuid,
+                        )?;
                         Some(session)
                     }
                     None => None,
@@ -610,43 +884,494 @@ impl DappKeyProvider {
             .await
     }
 
+    /// Send the transaction if the dapp is trusted and the transaction is neither high value,
+    /// nor flagged as risky, nor over the dapp's spending limit. Otherwise request approval
+    /// from the user and return none.
     async fn eth_send_transaction(
+        &self,
+        tx: TransactionRequest,
+        session: m::LocalDappSession,
+        raw_request: &str,
+    ) -> Result<Option<serde_json::Value>, Error> {
+        let mut review = self.review_transaction(&tx, &session).await?;
+        if !review.requires_approval(session.trust_level) {
+            // Checked again when reserving, because a concurrent transaction from the dapp may
+            // have spent in the meantime.
+            match self
+                .reserve_spends(&session, review.spends.clone(), true)
+                .await?
+            {
+                Some(spend_ids) => {
+                    let result = self
+                        .send_approved_transaction(tx, session, review, spend_ids)
+                        .await?;
+                    return Ok(Some(result));
+                }
+                None => review.exceeds_spending_limit = true,
+            }
+        }
+        self.request_dapp_transaction_approval(&tx, session, review, raw_request)
+            .await?;
+        Ok(None)
+    }
+
+    async fn review_transaction(
+        &self,
+        tx: &TransactionRequest,
+        session: &m::LocalDappSession,
+    ) -> Result<TransactionReview, Error> {
+        let chain_id = session.chain_id;
+        let data = tx.data.as_deref().unwrap_or_default();
+        // Oversized calldata isn't decoded at all to bound the work on pathological input.
+        // Such transactions always require approval, as their spends are unknown.
+        let data_too_large = data.len() > self.resources.max_decoded_calldata_bytes();
+        let (warnings, spends) = if data_too_large {
+            (Vec::new(), Vec::new())
+        } else {
+            let mut warnings =
+                self.resources.transaction_inspector().inspect(tx, chain_id);
+            warnings.extend(inspect_token_approvals(tx));
+            (warnings, transaction_spends(tx, chain_id))
+        };
+
+        let amount = NativeTokenAmount::new(chain_id, tx.value.unwrap_or_default());
+        let high_value = self.is_high_value(&session.profile_id, &amount).await?;
+
+        let exceeds_spending_limit =
+            self.exceeds_spending_limit(session, spends.clone()).await?;
+
+        let no_code_at_target =
+            self.is_call_to_address_without_code(tx, chain_id).await?;
+        let proxy_implementation = if no_code_at_target {
+            None
+        } else {
+            self.proxy_implementation(tx, chain_id).await?
+        };
+        let fee_estimate = self.estimate_fee(tx, chain_id).await;
+
+        Ok(TransactionReview {
+            amount,
+            high_value,
+            warnings,
+            exceeds_spending_limit,
+            no_code_at_target,
+            proxy_implementation,
+            spends,
+            calldata_selector: calldata_selector(data),
+            calldata_size: data.len(),
+            data_too_large,
+            fee_estimate,
+        })
+    }
+
+    /// The fee estimate is reused when the transaction is sent. It's only needed for the
+    /// pending balance, so failing to estimate isn't an error.
+    async fn estimate_fee(
+        &self,
+        tx: &TransactionRequest,
+        chain_id: ChainId,
+    ) -> Option<FeeEstimate> {
+        if self.resources.is_demo() {
+            return None;
+        }
+        let provider = self.rpc_manager().eth_api_provider(chain_id);
+        match provider.estimate_fee_async(tx).await {
+            Ok(estimate) => Some(estimate),
+            Err(err) => {
+                log::warn!("Failed to estimate transaction fee due to error: {err:?}");
+                None
+            }
+        }
+    }
+
+    /// The current implementation if the transaction calls an EIP-1967 proxy contract. The
+    /// logic of proxies can be swapped by upgrading the implementation.
+    async fn proxy_implementation(
+        &self,
+        tx: &TransactionRequest,
+        chain_id: ChainId,
+    ) -> Result<Option<ChecksumAddress>, Error> {
+        let has_data = tx
+            .data
+            .as_ref()
+            .map(|data| !data.is_empty())
+            .unwrap_or_default();
+        let to_address = match &tx.to {
+            Some(NameOrAddress::Address(address)) if has_data => *address,
+            _ => return Ok(None),
+        };
+        if self.resources.is_demo() {
+            return Ok(None);
+        }
+        let provider = self.rpc_manager().eth_api_provider(chain_id);
+        provider.proxy_implementation_async(to_address.into()).await
+    }
+
+    /// Whether the transaction calls a contract function on an address that has no code,
+    /// because the contract was never deployed or it self-destructed. Such calls succeed
+    /// without doing anything. Value transfers without data and contract deployments are
+    /// exempt.
+    async fn is_call_to_address_without_code(
+        &self,
+        tx: &TransactionRequest,
+        chain_id: ChainId,
+    ) -> Result<bool, Error> {
+        let has_data = tx
+            .data
+            .as_ref()
+            .map(|data| !data.is_empty())
+            .unwrap_or_default();
+        let to_address = match &tx.to {
+            Some(NameOrAddress::Address(address)) if has_data => *address,
+            _ => return Ok(false),
+        };
+        if self.resources.is_demo() {
+            return Ok(false);
+        }
+        let provider = self.rpc_manager().eth_api_provider(chain_id);
+        let has_code = provider.has_code_async(to_address.into()).await?;
+        Ok(!has_code)
+    }
+
+    /// Whether the spends would take the dapp over its spending limit in the current window.
+    async fn exceeds_spending_limit(
+        &self,
+        session: &m::LocalDappSession,
+        spends: Vec<TransactionSpend>,
+    ) -> Result<bool, Error> {
+        let spending_limit = match session.spending_limit.clone() {
+            Some(spending_limit) => spending_limit,
+            None => return Ok(false),
+        };
+        let session_id = session.uuid.clone();
+        self.connection_pool()
+            .deferred_transaction_async(move |mut tx_conn| {
+                m::LocalDappSpend::exceeds_limit(
+                    &mut tx_conn,
+                    &session_id,
+                    &spending_limit,
+                    &spends,
+                )
+            })
+            .await
+    }
+
+    /// Reserve the spends of a transaction towards the dapp's spending limit before submitting
+    /// it. The limit is checked in the same DB transaction if `enforce_limit` is true and
+    /// none is returned if the spends would exceed it. Returns the ids of the reserved spends.
+    async fn reserve_spends(
+        &self,
+        session: &m::LocalDappSession,
+        spends: Vec<TransactionSpend>,
+        enforce_limit: bool,
+    ) -> Result<Option<Vec<String>>, Error> {
+        if spends.is_empty() {
+            return Ok(Some(Default::default()));
+        }
+        let session_id = session.uuid.clone();
+        let spending_limit = session.spending_limit.clone();
+        self.connection_pool()
+            .exclusive_transaction_async(move |tx_conn| {
+                let mut tx_conn: DeferredTxConnection = tx_conn.into();
+                m::LocalDappSpend::reserve(
+                    &mut tx_conn,
+                    &session_id,
+                    spending_limit.as_ref(),
+                    &spends,
+                    enforce_limit,
+                )
+            })
+            .await
+    }
+
+    /// Release the reserved spends of a transaction that couldn't be submitted.
+    async fn release_spends(&self, spend_ids: Vec<String>) {
+        if spend_ids.is_empty() {
+            return;
+        }
+        let res = self
+            .connection_pool()
+            .deferred_transaction_async(move |mut tx_conn| {
+                m::LocalDappSpend::release(&mut tx_conn, &spend_ids)
+            })
+            .await;
+        if let Err(err) = res {
+            log::error!("Failed to release dapp spends due to error: {err}");
+        }
+    }
+
+    async fn request_dapp_transaction_approval(
+        &self,
+        tx: &TransactionRequest,
+        session: m::LocalDappSession,
+        review: TransactionReview,
+        raw_request: &str,
+    ) -> Result<(), Error> {
+        let TransactionReview {
+            amount,
+            high_value,
+            warnings,
+            exceeds_spending_limit,
+            no_code_at_target,
+            proxy_implementation,
+            calldata_selector,
+            calldata_size,
+            data_too_large,
+            ..
+        } = review;
+        // Only decoded for the prompt, as transactions sent without approval don't need it.
+        let data = tx.data.as_deref().unwrap_or_default();
+        let decoded_call = if data.is_empty() || data_too_large {
+            None
+        } else {
+            // Malformed calldata is flagged by the transaction inspector, the approval can do
+            // without the decoded call.
+            SELECTOR_REGISTRY
+                .decode(data)
+                .map_err(|err| log::debug!("Failed to decode calldata: {err}"))
+                .ok()
+        };
+        let proxy_implementation_label = proxy_implementation
+            .and_then(|address| CONTRACT_REGISTRY.lookup(&address))
+            .map(Into::into);
+        let approval_id = self.schedule_approval_timeout(raw_request)?;
+        let tx_approval = DappTransactionApprovalParams::builder()
+            .profile_id(session.profile_id)
+            .dapp_identifier(session.dapp_human_identifier)
+            .chain_display_name(session.chain_id.display_name())
+            .amount(amount.display_amount())
+            .token_symbol(amount.chain_id.native_token().symbol())
+            .high_value(high_value)
+            .warnings(warnings)
+            .exceeds_spending_limit(exceeds_spending_limit)
+            .no_code_at_target(no_code_at_target)
+            .is_proxy(proxy_implementation.is_some())
+            .proxy_implementation(proxy_implementation.map(Into::into))
+            .proxy_implementation_label(proxy_implementation_label)
+            .decoded_call(decoded_call)
+            .calldata_selector(calldata_selector)
+            .calldata_size(calldata_size as u64)
+            .data_too_large(data_too_large)
+            .json_rpc_request(raw_request)
+            .approval_id(approval_id)
+            .build();
+
+        let callbacks = self.request_context.callbacks();
+        rt::spawn_blocking(move || {
+            callbacks.request_dapp_transaction_approval(tx_approval);
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn handle_user_approved_dapp_transaction(
+        mut self,
+        tx_approval: DappTransactionApprovalParams,
+    ) -> Result<(), Error> {
+        if !self.take_approval(tx_approval.approval_id)? {
+            return Ok(());
+        }
+        let request = parse_request(&tx_approval.json_rpc_request)?;
+        let result = if self.is_page_on_dapp(&tx_approval.dapp_identifier)? {
+            self.user_approved_transaction_request(&request).await
+        } else {
+            // Don't send the transaction with the session of the dapp that the page is on now.
+            Err(DappRequestError::Unauthorized.into())
+        };
+        let response = to_method_response(request.id, result);
+        self.respond_to_request(response).await?;
+        Ok(())
+    }
+
+    async fn user_approved_transaction_request(
+        &self,
+        request: &Request<'_>,
+    ) -> Result<serde_json::Value, Error> {
+        let tx = match parse_in_page_request(request) {
+            Ok(InPageRequest::EthSendTransaction(tx)) => tx,
+            _ => {
+                return Err(Error::Fatal {
+                    error: "Expected eth_sendTransaction request for approval".into(),
+                })
+            }
+        };
+        // Make sure that the dapp is still connected in the active profile.
+        let session = self
+            .fetch_session_for_approved_dapp()
+            .await?
+            .ok_or(DappRequestError::Unauthorized)?;
+        let review = self.review_transaction(&tx, &session).await?;
+        // The user approved exceeding the limit.
+        let spend_ids = self
+            .reserve_spends(&session, review.spends.clone(), false)
+            .await?
+            .unwrap_or_default();
+        self.send_approved_transaction(tx, session, review, spend_ids)
+            .await
+    }
+
+    async fn handle_user_rejected_dapp_transaction(
+        mut self,
+        tx_approval: DappTransactionApprovalParams,
+    ) -> Result<(), Error> {
+        if !self.take_approval(tx_approval.approval_id)? {
+            return Ok(());
+        }
+        let request = parse_request(&tx_approval.json_rpc_request)?;
+        let err: ErrorObject = DappRequestError::UserRejected.into();
+        let response = MethodResponse::error(request.id, err);
+        self.respond_to_request(response).await?;
+        Ok(())
+    }
+
+    /// Send a transaction whose spends were reserved. The spends are released if the
+    /// transaction couldn't be submitted.
+    async fn send_approved_transaction(
+        &self,
+        tx: TransactionRequest,
+        session: m::LocalDappSession,
+        review: TransactionReview,
+        spend_ids: Vec<String>,
+    ) -> Result<serde_json::Value, Error> {
+        let result = self.submit_transaction(tx, session, review).await;
+        if result.is_err() {
+            self.release_spends(spend_ids).await;
+        }
+        result
+    }
+
+    async fn submit_transaction(
         &self,
         mut tx: TransactionRequest,
         session: m::LocalDappSession,
+        review: TransactionReview,
     ) -> Result<serde_json::Value, Error> {
+        if self.resources.is_demo() {
+            return self.demo_transaction(session, review).await;
+        }
         let (session, signing_key) = self.fetch_eth_signing_key(session).await?;
 
-        // Remove nonce to fill with latest nonce from remote API in signer to make sure tx nonce is
-        // current. MetaMask does this too.
-        tx.nonce = None;
+        let to_address: Option<ChecksumAddress> = match tx.to {
+            Some(NameOrAddress::Address(address)) => Some(address.into()),
+            _ => None,
+        };
 
         let provider = self.rpc_manager().eth_api_provider(signing_key.chain_id);
 
+        // The nonce from the dapp is only used to replace a transaction in the queue. Otherwise
+        // the queue assigns the next nonce based on the latest nonce from the remote API to make
+        // sure that the tx nonce is current. MetaMask ignores the dapp's nonce too.
+        let queue_key = transaction_queue_key(&session);
+        let pending_nonce = provider.pending_nonce_async(session.address).await?;
+        let fee = match review.fee_estimate {
+            Some(estimate) => {
+                estimate.apply(&mut tx);
+                estimate.fee()
+            }
+            None => U256::zero(),
+        };
+        let outflow = tx.value.unwrap_or_default().saturating_add(fee);
+        let queue = self.resources.transaction_queue();
+        let nonce = queue.enqueue(&queue_key, pending_nonce, tx.nonce, outflow)?;
+        // The request task may be aborted while waiting, eg. if the user navigates away.
+        let _queue_guard = queue.guard(&queue_key, nonce);
+        tx.nonce = Some(nonce);
+        queue.wait_for_turn(&queue_key, nonce).await?;
+
         let tx_hash_fut = provider.send_transaction_async(&signing_key, tx);
 
         let resources = self.resources.clone();
-        let session = Self::approved_dapp_transaction(resources, session).await;
+        let session = Self::approved_dapp_transaction(resources, session, review).await;
 
         let tx_hash = tx_hash_fut.await;
+        // Without awaiting in between, so that an abort can't remove a sent transaction.
+        let queue_res = match tx_hash.as_ref() {
+            Ok(tx_hash) => queue.mark_submitted(&queue_key, nonce, *tx_hash),
+            Err(err) => queue.mark_failed(&queue_key, nonce, err.to_string()),
+        };
+        if let Err(err) = queue_res {
+            log::error!("Failed to update transaction queue due to error: {err}");
+        }
 
         let resources = self.resources.clone();
         let tx_hash_res = tx_hash.clone();
         // Call in background.
         rt::spawn(async move {
-            Self::dapp_transaction_result(resources, session, tx_hash_res).await;
+            Self::dapp_transaction_result(
+                resources,
+                session,
+                to_address,
+                nonce,
+                tx_hash_res,
+            )
+            .await;
         });
 
         to_value(tx_hash?)
     }
 
+    /// Report an approved transaction as successful with a synthetic tx hash without
+    /// submitting it to the network.
+    async fn demo_transaction(
+        &self,
+        session: m::LocalDappSession,
+        review: TransactionReview,
+    ) -> Result<serde_json::Value, Error> {
+        let tx_hash = demo::synthetic_tx_hash(self.resources.rng())?;
+        let resources = self.resources.clone();
+        let session = Self::approved_dapp_transaction(resources, session, review).await;
+
+        let result = DappTransactionResult::builder()
+            .dapp_identifier(session.dapp_human_identifier)
+            .chain_display_name(session.chain_id.display_name())
+            .build();
+        let resources = self.resources.clone();
+        let joined = rt::spawn_blocking(move || {
+            resources.ui_callbacks().dapp_transaction_result(result);
+        })
+        .await;
+        if joined.is_err() {
+            log::error!("Failed to join dapp_transaction_result callback future.")
+        }
+
+        to_value(tx_hash)
+    }
+
+    /// Whether the amount is above the high value threshold of the profile.
+    async fn is_high_value(
+        &self,
+        profile_id: &DeterministicId,
+        amount: &NativeTokenAmount,
+    ) -> Result<bool, Error> {
+        let profile_id = profile_id.clone();
+        let settings = self
+            .connection_pool()
+            .deferred_transaction_async(move |mut tx_conn| {
+                m::Profile::fetch_user_settings(tx_conn.as_mut(), &profile_id)
+            })
+            .await?;
+        Ok(settings.is_eth_high_value(amount))
+    }
+
     async fn approved_dapp_transaction(
         resources: Arc<dyn CoreResourcesI>,
         session: m::LocalDappSession,
+        review: TransactionReview,
     ) -> m::LocalDappSession {
+        let TransactionReview {
+            amount,
+            high_value,
+            warnings,
+            ..
+        } = review;
         let result = DappTransactionApproved::builder()
             .dapp_identifier(session.dapp_human_identifier.clone())
             .chain_display_name(session.chain_id.display_name())
+            .amount(amount.display_amount())
+            .token_symbol(amount.chain_id.native_token().symbol())
+            .high_value(high_value)
+            .warnings(warnings)
             .build();
 
         let joined = rt::spawn_blocking(move || {
@@ -668,8 +1393,23 @@ impl DappKeyProvider {
     async fn dapp_transaction_result(
         resources: Arc<dyn CoreResourcesI>,
         session: m::LocalDappSession,
+        to_address: Option<ChecksumAddress>,
+        nonce: U256,
         tx_hash_res: Result<H256, Error>,
     ) {
+        if let Ok(tx_hash) = tx_hash_res.as_ref() {
+            m::Transaction::record_submitted_eth(
+                resources.connection_pool(),
+                session.address_id.clone(),
+                session.chain_id,
+                *tx_hash,
+                to_address,
+                None,
+            )
+            .await;
+        }
+
+        let queue_key = transaction_queue_key(&session);
         let m::LocalDappSession {
             dapp_human_identifier,
             chain_id,
@@ -680,23 +1420,34 @@ impl DappKeyProvider {
             .chain_display_name(chain_id.display_name())
             .build();
 
-        let result =
-            match tx_hash_res {
-                Ok(tx_hash) => {
-                    let rpc_provider =
-                        resources.rpc_manager().eth_api_provider(session.chain_id);
-                    match rpc_provider.wait_for_confirmation_async(tx_hash).await {
-                        Ok(tx_hash) => explorer::tx_url(session.chain_id, &tx_hash)
-                            .ok()
+        let result = match tx_hash_res {
+            Ok(tx_hash) => {
+                let rpc_provider =
+                    resources.rpc_manager().eth_api_provider(session.chain_id);
+                let confirmation =
+                    rpc_provider.wait_for_confirmation_async(tx_hash).await;
+                let queue = resources.transaction_queue();
+                let queue_res = match confirmation.as_ref() {
+                    Ok(_) => queue.mark_confirmed(&queue_key, nonce),
+                    Err(err) => queue.mark_failed(&queue_key, nonce, err.to_string()),
+                };
+                if let Err(err) = queue_res {
+                    log::error!("Failed to update transaction queue due to error: {err}");
+                }
+                match confirmation {
+                    Ok(tx_hash) => {
+                        explorer::tx_url(session.chain_id, &tx_hash)
+                            .ok()
                             .map(|url| {
                                 partial_result.explorer_url = Some(url.to_string());
                                 partial_result
-                            }),
-                        Err(err) => dapp_transaction_result_error(partial_result, err),
+                            })
                     }
+                    Err(err) => dapp_transaction_result_error(partial_result, err),
                 }
-                Err(err) => dapp_transaction_result_error(partial_result, err),
-            };
+            }
+            Err(err) => dapp_transaction_result_error(partial_result, err),
+        };
 
         if let Some(result) = result {
             let joined = rt::spawn_blocking(move || {
@@ -717,10 +1468,10 @@ impl DappKeyProvider {
         session: m::LocalDappSession,
     ) -> Result<serde_json::Value, Error> {
         if session.address != address {
-            return Err(Error::JsonRpc {
-                code: InPageErrorCode::InvalidParams.into(),
+            return Err(DappRequestError::InvalidParams {
                 message: "Invalid address".into(),
-            });
+            }
+            .into());
         }
 
         let (session, signing_key) = self.fetch_eth_signing_key(session).await?;
@@ -733,23 +1484,207 @@ impl DappKeyProvider {
 
         let resources = self.resources.clone();
         // Call in background
-        rt::spawn(Self::personal_sign_callback(resources, session));
+        rt::spawn(Self::signature_callback(resources, session, None, None));
 
         Ok(signature)
     }
 
-    async fn personal_sign_callback(
+    async fn request_dapp_sign_batch_approval(
+        &self,
+        address: Address,
+        typed_data: Vec<TypedData>,
+        session: m::LocalDappSession,
+        raw_request: &str,
+    ) -> Result<(), Error> {
+        if typed_data.is_empty() || typed_data.len() > config::MAX_SIGN_BATCH_SIZE {
+            return Err(DappRequestError::InvalidParams {
+                message: format!(
+                    "A batch must have between 1 and {} messages",
+                    config::MAX_SIGN_BATCH_SIZE
+                ),
+            }
+            .into());
+        }
+        for data in typed_data.iter() {
+            check_typed_data(address, data, &session)?;
+        }
+
+        let resources = self.resources.clone();
+        let callbacks = self.request_context.callbacks();
+        let approval_id = self.schedule_approval_timeout(raw_request)?;
+        let raw_request = raw_request.to_string();
+        rt::spawn_blocking(move || {
+            let items: Vec<DappSignBatchItem> = typed_data
+                .iter()
+                .map(|data| {
+                    let verifying_contract =
+                        data.domain.verifying_contract.map(|address| {
+                            Self::verifying_contract(
+                                resources.as_ref(),
+                                &session,
+                                address.into(),
+                            )
+                        });
+                    DappSignBatchItem::builder()
+                        .primary_type(data.primary_type.clone())
+                        .domain_name(data.domain.name.clone())
+                        .domain_version(data.domain.version.clone())
+                        .verifying_contract(verifying_contract)
+                        .known_schema(known_schema(data))
+                        .build()
+                })
+                .collect();
+            let batch_approval = DappSignBatchApprovalParams::builder()
+                .profile_id(session.profile_id)
+                .dapp_identifier(session.dapp_human_identifier)
+                .chain_display_name(session.chain_id.display_name())
+                .items(items)
+                .json_rpc_request(raw_request)
+                .approval_id(approval_id)
+                .build();
+            callbacks.request_dapp_sign_batch_approval(batch_approval);
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn handle_user_approved_dapp_sign_batch(
+        mut self,
+        batch_approval: DappSignBatchApprovalParams,
+    ) -> Result<(), Error> {
+        if !self.take_approval(batch_approval.approval_id)? {
+            return Ok(());
+        }
+        let request = parse_request(&batch_approval.json_rpc_request)?;
+        let result = if self.is_page_on_dapp(&batch_approval.dapp_identifier)? {
+            self.user_approved_sign_batch_request(&request, &batch_approval.items)
+                .await
+        } else {
+            // Don't sign with the key of the dapp that the page is on now.
+            Err(DappRequestError::Unauthorized.into())
+        };
+        let response = to_method_response(request.id, result);
+        self.respond_to_request(response).await?;
+        Ok(())
+    }
+
+    /// Sign the approved messages of the batch and return the signatures in the request order
+    /// with null for the declined ones. Returns the signature for `eth_signTypedData_v4`
+    /// requests.
+    async fn user_approved_sign_batch_request(
+        &self,
+        request: &Request<'_>,
+        items: &[DappSignBatchItem],
+    ) -> Result<serde_json::Value, Error> {
+        let (address, typed_data, is_batch) = match parse_in_page_request(request) {
+            Ok(InPageRequest::SignTypedDataBatch(address, typed_data)) => {
+                (address, typed_data, true)
+            }
+            Ok(InPageRequest::EthSignTypedDataV4(address, typed_data)) => {
+                (address, vec![typed_data], false)
+            }
+            _ => {
+                return Err(Error::Fatal {
+                    error: "Expected typed data signature request for approval".into(),
+                })
+            }
+        };
+        if items.len() != typed_data.len() {
+            return Err(Error::Fatal {
+                error: "The approved items don't match the batch request".into(),
+            });
+        }
+        if !items.iter().any(|item| item.approved) {
+            return Err(DappRequestError::UserRejected.into());
+        }
+        // Make sure that the dapp is still connected in the active profile on the same chain.
+        let session = self
+            .fetch_session_for_approved_dapp()
+            .await?
+            .ok_or(DappRequestError::Unauthorized)?;
+        for data in typed_data.iter() {
+            check_typed_data(address, data, &session)?;
+        }
+
+        let approved: Vec<bool> = items.iter().map(|item| item.approved).collect();
+        let signed_items: Vec<(Option<ChecksumAddress>, Option<String>)> = typed_data
+            .iter()
+            .zip(approved.iter())
+            .filter(|(_, approved)| **approved)
+            .map(|(data, _)| {
+                let verifying_contract = data.domain.verifying_contract.map(Into::into);
+                (verifying_contract, known_schema(data))
+            })
+            .collect();
+
+        let (session, signing_key) = self.fetch_eth_signing_key(session).await?;
+        let signatures = rt::spawn_blocking(move || {
+            let signer = Signer::new(&signing_key);
+            let signatures = typed_data
+                .iter()
+                .zip(approved)
+                .map(|(data, approved)| {
+                    if approved {
+                        let signature = signer.sign_typed_data(data)?;
+                        Ok(Some(signature.to_string()))
+                    } else {
+                        Ok(None)
+                    }
+                })
+                .collect::<Result<Vec<Option<String>>, Error>>()?;
+            if is_batch {
+                to_value(signatures)
+            } else {
+                // At least one message is approved.
+                to_value(signatures.into_iter().flatten().next())
+            }
+        })
+        .await??;
+
+        for (verifying_contract, known_schema) in signed_items {
+            let resources = self.resources.clone();
+            // Call in background
+            rt::spawn(Self::signature_callback(
+                resources,
+                session.clone(),
+                verifying_contract,
+                known_schema,
+            ));
+        }
+
+        Ok(signatures)
+    }
+
+    async fn handle_user_rejected_dapp_sign_batch(
+        mut self,
+        batch_approval: DappSignBatchApprovalParams,
+    ) -> Result<(), Error> {
+        if !self.take_approval(batch_approval.approval_id)? {
+            return Ok(());
+        }
+        let request = parse_request(&batch_approval.json_rpc_request)?;
+        let err: ErrorObject = DappRequestError::UserRejected.into();
+        let response = MethodResponse::error(request.id, err);
+        self.respond_to_request(response).await?;
+        Ok(())
+    }
+
+    async fn signature_callback(
         resources: Arc<dyn CoreResourcesI>,
         session: m::LocalDappSession,
+        verifying_contract: Option<ChecksumAddress>,
+        known_schema: Option<String>,
     ) {
-        let m::LocalDappSession {
-            dapp_human_identifier,
-            ..
-        } = session;
-        let result = DappSignatureResult::builder()
-            .dapp_identifier(dapp_human_identifier)
-            .build();
         let joined = rt::spawn_blocking(move || {
+            let verifying_contract = verifying_contract.map(|address| {
+                Self::verifying_contract(resources.as_ref(), &session, address)
+            });
+            let result = DappSignatureResult::builder()
+                .dapp_identifier(session.dapp_human_identifier)
+                .verifying_contract(verifying_contract)
+                .known_schema(known_schema)
+                .build();
             resources.ui_callbacks().signed_message_for_dapp(result);
         })
         .await;
@@ -758,6 +1693,31 @@ impl DappKeyProvider {
         }
     }
 
+    /// Resolve the verifying contract of a typed data signature for display.
+    fn verifying_contract(
+        resources: &dyn CoreResourcesI,
+        session: &m::LocalDappSession,
+        address: ChecksumAddress,
+    ) -> VerifyingContract {
+        let label = CONTRACT_REGISTRY.lookup(&address).map(Into::into);
+        let known_to_dapp = resources
+            .connection_pool()
+            .connection()
+            .and_then(|mut conn| {
+                m::Transaction::list_for_address(&mut conn, &session.address_id)
+            })
+            .map(|txs| txs.iter().any(|tx| tx.to_address == Some(address)))
+            .unwrap_or_else(|err| {
+                log::error!("Failed to list dapp transactions: {err}");
+                false
+            });
+        VerifyingContract::builder()
+            .checksum_address(address)
+            .label(label)
+            .known_to_dapp(known_to_dapp)
+            .build()
+    }
+
     /// We don't support adding chains that aren't supported already, so this is a noop if the chain
     /// is already supported and an error if it isn't.
     /// It changes the current chain to the "added" one to follow MetaMask behaviour.
@@ -788,8 +1748,8 @@ impl DappKeyProvider {
         session: m::LocalDappSession,
     ) -> Result<serde_json::Value, Error> {
         // If we can parse the chain, then it's supported.
-        // MetaMask returns error code 4902 if the chain has not been added yet so that the dapp can
-        // request adding it. We don't do that, because we don't support adding arbitrary chains.
+        // Returns error code 4902 like MetaMask if the chain is not supported. Dapps may request
+        // adding it in response, but we don't support adding arbitrary chains.
         let new_chain_id: ChainId = parse_0x_chain_id(&param.chain_id)?;
 
         self.change_eth_chain(session, new_chain_id).await?;
@@ -816,7 +1776,7 @@ impl DappKeyProvider {
     }
 
     fn web3_client_version(&self) -> Result<serde_json::Value, Error> {
-        Ok("SealVault".into())
+        to_value(config::WEB3_CLIENT_VERSION)
     }
 
     async fn web3_sha3(&self, payload: Bytes) -> Result<serde_json::Value, Error> {
@@ -848,6 +1808,9 @@ impl DappKeyProvider {
     }
 
     async fn fetch_favicon(&self) -> Result<Option<Vec<u8>>, Error> {
+        if self.resources.is_demo() {
+            return Ok(None);
+        }
         let client = self.http_client();
         let favicon = fetch_favicon_async(client, self.url.clone()).await?;
         Ok(favicon)
@@ -887,14 +1850,137 @@ pub struct DappApprovalParams {
     /// The JSON-RPC request that requested adding this dapp.
     #[builder(setter(into))]
     pub json_rpc_request: String,
+    /// Identifies the prompt when the UI responds. Responses to prompts that timed out or
+    /// whose request was cancelled are ignored.
+    pub approval_id: u64,
+}
+
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct DappTransactionApprovalParams {
+    /// The profile in which the dapp is connected.
+    #[builder(setter(into))]
+    pub profile_id: String,
+    /// A human readable dapp identifier that can be presented to the user.
+    #[builder(setter(into))]
+    pub dapp_identifier: String,
+    /// The displayable name of the chain where the transaction is sent.
+    #[builder(setter(into))]
+    pub chain_display_name: String,
+    /// The native token amount sent with the transaction.
+    #[builder(setter(into))]
+    pub amount: String,
+    /// The symbol of the native token of the chain.
+    #[builder(setter(into))]
+    pub token_symbol: String,
+    /// Whether the amount is above the high value threshold of the profile.
+    pub high_value: bool,
+    /// Safety warnings about the transaction.
+    pub warnings: Vec<TransactionWarning>,
+    /// Whether the transaction would take the dapp over its spending limit.
+    #[builder(default)]
+    pub exceeds_spending_limit: bool,
+    /// Whether the transaction calls a contract function on an address without code.
+    /// The call can't execute, but the user would still pay for gas.
+    #[builder(default)]
+    pub no_code_at_target: bool,
+    /// Whether the transaction calls an EIP-1967 proxy contract. The implementation of a proxy
+    /// and thus its behavior can change.
+    #[builder(default)]
+    pub is_proxy: bool,
+    /// The checksum address of the current implementation of the proxy.
+    #[builder(default)]
+    pub proxy_implementation: Option<String>,
+    /// Human readable name of the implementation if it's a well-known contract.
+    #[builder(default)]
+    pub proxy_implementation_label: Option<String>,
+    /// The function call in the transaction data. None if there is no data, it's too large to
+    /// decode or it's malformed.
+    #[builder(default)]
+    pub decoded_call: Option<DecodedCall>,
+    /// The 4-byte function selector of the transaction data hex encoded with 0x prefix.
+    #[builder(default)]
+    pub calldata_selector: Option<String>,
+    /// The size of the transaction data in bytes.
+    #[builder(default)]
+    pub calldata_size: u64,
+    /// Whether the transaction data is too large to decode. Only the selector and the size can
+    /// be shown then.
+    #[builder(default)]
+    pub data_too_large: bool,
+    /// The JSON-RPC request that submitted the transaction.
+    #[builder(setter(into))]
+    pub json_rpc_request: String,
+    /// Identifies the prompt when the UI responds. Responses to prompts that timed out or
+    /// whose request was cancelled are ignored.
+    pub approval_id: u64,
+}
+
+/// A typed data message in a batch signature request.
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct DappSignBatchItem {
+    /// The EIP-712 primary type of the message.
+    #[builder(setter(into))]
+    pub primary_type: String,
+    /// The name from the EIP-712 domain.
+    #[builder(default)]
+    pub domain_name: Option<String>,
+    /// The version from the EIP-712 domain.
+    #[builder(default)]
+    pub domain_version: Option<String>,
+    /// The contract from the EIP-712 domain.
+    #[builder(default)]
+    pub verifying_contract: Option<VerifyingContract>,
+    /// The name of the standard if the message structure matches a well-known schema. The UI
+    /// should caution the user about custom schemas.
+    #[builder(default)]
+    pub known_schema: Option<String>,
+    /// Whether to sign the message. The UI sets it to false for messages that the user
+    /// declined.
+    #[builder(default = true)]
+    pub approved: bool,
+}
+
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct DappSignBatchApprovalParams {
+    /// The profile in which the dapp is connected.
+    #[builder(setter(into))]
+    pub profile_id: String,
+    /// A human readable dapp identifier that can be presented to the user.
+    #[builder(setter(into))]
+    pub dapp_identifier: String,
+    /// The displayable name of the chain where the signatures are valid.
+    #[builder(setter(into))]
+    pub chain_display_name: String,
+    /// The messages to sign in the request order.
+    pub items: Vec<DappSignBatchItem>,
+    /// The JSON-RPC request that submitted the batch.
+    #[builder(setter(into))]
+    pub json_rpc_request: String,
+    /// Identifies the prompt when the UI responds. Responses to prompts that timed out or
+    /// whose request was cancelled are ignored.
+    pub approval_id: u64,
 }
 
 pub trait CoreInPageCallbackI: Send + Sync + Debug {
     /// Request a dapp approval from the user through the UI.
-    /// After the user has approved the dapp for the first time, it'll be allowed to connect and
-    /// execute transactions automatically.
+    /// After the user has approved the dapp for the first time, it'll be allowed to connect.
     fn request_dapp_approval(&self, dapp_approval: DappApprovalParams);
 
+    /// Request the user to approve a transaction submitted by a connected dapp.
+    /// Trusted dapps only need approval for high value or risky transactions.
+    fn request_dapp_transaction_approval(
+        &self,
+        tx_approval: DappTransactionApprovalParams,
+    );
+
+    /// Request the user to approve signing a batch of typed data messages from a connected
+    /// dapp. The user can decline individual messages by setting `approved` to false.
+    /// `eth_signTypedData_v4` requests are a batch of one message.
+    fn request_dapp_sign_batch_approval(
+        &self,
+        batch_approval: DappSignBatchApprovalParams,
+    );
+
     /// Respond to an in-page provider request.
     fn respond(&self, response_hex: String);
 
@@ -902,6 +1988,39 @@ pub trait CoreInPageCallbackI: Send + Sync + Debug {
     fn notify(&self, event_hex: String);
 }
 
+/// The result of the safety checks on a dapp transaction.
+#[derive(Debug)]
+struct TransactionReview {
+    amount: NativeTokenAmount,
+    high_value: bool,
+    warnings: Vec<TransactionWarning>,
+    exceeds_spending_limit: bool,
+    no_code_at_target: bool,
+    /// The current implementation if the target is a proxy contract.
+    proxy_implementation: Option<ChecksumAddress>,
+    spends: Vec<TransactionSpend>,
+    calldata_selector: Option<String>,
+    calldata_size: usize,
+    /// The calldata is larger than the decoding limit.
+    data_too_large: bool,
+    fee_estimate: Option<FeeEstimate>,
+}
+
+impl TransactionReview {
+    fn requires_approval(&self, trust_level: m::DappTrustLevel) -> bool {
+        match trust_level {
+            m::DappTrustLevel::Standard => true,
+            m::DappTrustLevel::Trusted => {
+                self.high_value
+                    || !self.warnings.is_empty()
+                    || self.exceeds_spending_limit
+                    || self.no_code_at_target
+                    || self.data_too_large
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ProviderMessage {
@@ -937,6 +2056,55 @@ enum ProviderEvent {
     SealVaultConnect,
 }
 
+/// The name of the standard if the typed data matches a well-known schema.
+fn known_schema(typed_data: &TypedData) -> Option<String> {
+    TYPED_DATA_SCHEMA_REGISTRY
+        .lookup(typed_data)
+        .map(Into::into)
+}
+
+/// Check that the typed data is for the session's address and chain.
+fn check_typed_data(
+    address: Address,
+    typed_data: &TypedData,
+    session: &m::LocalDappSession,
+) -> Result<(), Error> {
+    if session.address != address {
+        return Err(DappRequestError::InvalidParams {
+            message: "Invalid address".into(),
+        }
+        .into());
+    }
+    // Prevent replaying the signature on an other chain.
+    if let Some(chain_id) = typed_data.domain.chain_id {
+        let session_chain_id: U256 = session.chain_id.into();
+        if chain_id != session_chain_id {
+            return Err(DappRequestError::InvalidParams {
+                message: format!(
+                    "Typed data chain id {chain_id} doesn't match the current chain id \
+                    {session_chain_id}"
+                ),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+async fn respond_with_callbacks(
+    callbacks: Box<dyn CoreInPageCallbackI>,
+    response: MethodResponse,
+) -> Result<(), Error> {
+    rt::spawn_blocking(move || {
+        // Prevent reflected XSS by passing the result as hexadecimal utf-8 bytes to JS.
+        // See the security model in the developer docs for more.
+        let hex_response = hex::encode(response.result.as_bytes());
+        callbacks.respond(hex_response);
+        Ok(())
+    })
+    .await?
+}
+
 fn parse_request(raw_request: &str) -> Result<Request, Error> {
     if raw_request.as_bytes().len() > config::MAX_JSONRPC_REQUEST_SIZE_BYTES {
         return Err(invalid_raw_request());
@@ -946,10 +2114,37 @@ fn parse_request(raw_request: &str) -> Result<Request, Error> {
     Ok(req)
 }
 
+fn parse_in_page_request(request: &Request) -> Result<InPageRequest, serde_json::Error> {
+    let call = json!({
+        "method": request.method,
+        "params": request.params
+    });
+    serde_json::from_value(call)
+}
+
+/// Convert the result of a method to a JSON-RPC response. Errors are mapped through
+/// `DappRequestError` so that the dapp always gets a response.
+fn to_method_response(
+    id: Id,
+    result: Result<serde_json::Value, Error>,
+) -> MethodResponse {
+    match result {
+        Ok(result) => {
+            MethodResponse::response(id, result, config::MAX_JSONRPC_RESPONSE_SIZE_BYTES)
+        }
+        Err(err) => {
+            let err: ErrorObject = DappRequestError::from(err).into();
+            MethodResponse::error(id, err)
+        }
+    }
+}
+
 fn strip_0x_hex_prefix(s: &str) -> Result<&str, Error> {
-    s.strip_prefix("0x").ok_or_else(|| Error::JsonRpc {
-        code: InPageErrorCode::InvalidParams.into(),
-        message: "Message must start with 0x".into(),
+    s.strip_prefix("0x").ok_or_else(|| {
+        DappRequestError::InvalidParams {
+            message: "Message must start with 0x".into(),
+        }
+        .into()
     })
 }
 
@@ -958,15 +2153,16 @@ fn parse_0x_chain_id(hex_chain_id: &str) -> Result<ChainId, Error> {
     let chain_id = strip_0x_hex_prefix(hex_chain_id)?;
     let chain_id =
         ethers::core::types::U64::from_str_radix(chain_id, 16).map_err(|_| {
-            Error::JsonRpc {
-                code: InPageErrorCode::InvalidParams.into(),
+            DappRequestError::InvalidParams {
                 message: "Invalid U64".into(),
             }
         })?;
-    let chain_id: ChainId = chain_id.try_into().map_err(|_| Error::JsonRpc {
-        code: InPageErrorCode::InvalidParams.into(),
-        message: "Unsupported chain id".into(),
-    })?;
+    let chain_id: ChainId =
+        chain_id
+            .try_into()
+            .map_err(|_| DappRequestError::UnrecognizedChain {
+                chain_id: hex_chain_id.into(),
+            })?;
     Ok(chain_id)
 }
 
@@ -981,6 +2177,14 @@ fn to_value(val: impl Serialize) -> Result<serde_json::Value, Error> {
     })
 }
 
+fn transaction_queue_key(session: &m::LocalDappSession) -> TransactionQueueKey {
+    TransactionQueueKey {
+        profile_id: session.profile_id.clone(),
+        chain_id: session.chain_id,
+        address: session.address.to_address(),
+    }
+}
+
 fn dapp_transaction_result_error(
     mut partial_result: DappTransactionResult,
     err: Error,
@@ -1016,10 +2220,13 @@ pub enum InPageErrorCode {
     UnsupportedMethod = 4200,
     Disconnected = 4900,
     ChainDisconnected = 4901,
+
+    // https://github.com/ethereum/EIPs/blob/master/EIPS/eip-3326.md#wallet_switchethereumchain
+    UnrecognizedChain = 4902,
 }
 
 impl InPageErrorCode {
-    fn to_i32(&self) -> i32 {
+    pub fn to_i32(&self) -> i32 {
         ToPrimitive::to_i32(self).expect("error codes fit into i32")
     }
 }
@@ -1037,13 +2244,6 @@ impl From<InPageErrorCode> for ErrorCode {
     }
 }
 
-impl From<InPageErrorCode> for ErrorObject<'static> {
-    fn from(code: InPageErrorCode) -> Self {
-        let code: ErrorCode = code.into();
-        code.into()
-    }
-}
-
 // More tests are in integrations tests in the [dev server.](tools/dev-server/static/ethereum.html)
 #[cfg(test)]
 mod tests {
@@ -1054,15 +2254,18 @@ mod tests {
 
     use super::*;
     use crate::{
-        app_core::tests::{InPageRequestContextMockArgs, TmpCore},
+        app_core::tests::{
+            CoreResourcesMock, InPageRequestContextMockArgs, TmpCore, TmpCoreDir,
+            FLAGGED_RECIPIENT,
+        },
         protocols::eth::{
             in_page_provider::{
                 in_page_request::{
                     AddEthereumChainParameter, InPageRequest, InPageRequestParams,
                 },
-                load_in_page_provider_script,
+                load_in_page_provider_script, ScriptOptions,
             },
-            ChainId,
+            ChainId, QueuedTransactionStatus,
         },
         utils::new_uuid,
     };
@@ -1070,20 +2273,32 @@ mod tests {
     impl DappKeyProvider {
         /// `call("method_name", rpc_params!["arg1", "arg2"])`
         fn test_call(self, request: InPageRequest) -> Result<()> {
+            let handle = self.test_spawn(request)?;
+            rt::block_on(handle)??;
+            Ok(())
+        }
+
+        /// Start handling the request without waiting for the result.
+        fn test_spawn(
+            self,
+            request: InPageRequest,
+        ) -> Result<tokio::task::JoinHandle<Result<(), Error>>> {
             let id = Id::Str(new_uuid().into());
             let InPageRequestParams { method, params } = request.try_into()?;
             let request = RequestSer::owned(id, method, Some(params));
             let raw_request =
                 serde_json::to_string(&request).expect("request serializes");
-            let handle = self.in_page_request(raw_request);
-            rt::block_on(handle)??;
-            Ok(())
+            Ok(self.in_page_request(raw_request))
         }
     }
 
+    const TEST_WALLET_NAME: &str = "SealVault";
+    const TEST_WALLET_ICON: &str = "data:image/svg+xml;base64,PHN2Zz48L3N2Zz4=";
+    const TEST_WALLET_RDNS: &str = "org.sealvault";
+
     fn authorize_dapp(core: &TmpCore) -> Result<String> {
         let provider = core.in_page_provider();
-        provider.test_call(InPageRequest::EthAccounts(()))?;
+        provider.test_call(InPageRequest::EthRequestAccounts(()))?;
         core.wait_for_first_in_page_response();
         let responses = core.responses();
         let response = responses.first().unwrap();
@@ -1170,7 +2385,7 @@ mod tests {
             .transfer_allotment(false)
             .build();
         let provider = core.in_page_provider_with_args(mock_args);
-        provider.test_call(InPageRequest::EthAccounts(()))?;
+        provider.test_call(InPageRequest::EthRequestAccounts(()))?;
         core.wait_for_first_in_page_response();
 
         check_dapp_authorization(&core);
@@ -1205,7 +2420,7 @@ mod tests {
             .user_approves(false)
             .build();
         let provider = core.in_page_provider_with_args(mock_args);
-        provider.test_call(InPageRequest::EthAccounts(()))?;
+        provider.test_call(InPageRequest::EthRequestAccounts(()))?;
         core.wait_for_first_in_page_response();
 
         assert!(core.dapp_approval().is_some());
@@ -1221,115 +2436,1060 @@ mod tests {
         Ok(())
     }
 
+    const OTHER_DAPP_URL: &str = "https://other.example.org";
+
     #[test]
-    fn disallows_un_approved() -> Result<()> {
+    fn dapp_approval_for_other_origin_requires_reapproval() -> Result<()> {
         let core = TmpCore::new()?;
+        let args = InPageRequestContextMockArgs::builder()
+            .user_approves(false)
+            .build();
+        core.in_page_provider_with_args(args.clone())
+            .test_call(InPageRequest::EthRequestAccounts(()))?;
+        core.wait_for_in_page_responses(1);
+        let dapp_approval = core.dapp_approval().expect("approval was requested");
+        assert_eq!(dapp_approval.dapp_identifier, "example.com");
+
+        // The page navigated to an other origin before the user approved.
+        let provider = core.in_page_provider_for_url(args, OTHER_DAPP_URL);
+        rt::block_on(provider.handle_user_approved_dapp(dapp_approval))?;
+        core.wait_for_in_page_responses(2);
+
+        let dapp_approval = core.dapp_approval().expect("approval was requested");
+        assert_eq!(dapp_approval.dapp_identifier, "example.org");
+        let dapps = core.first_profile().dapps;
+        assert!(!dapps.iter().any(
+            |dapp| ["example.com", "example.org"].contains(&&*dapp.human_identifier)
+        ));
 
-        let mock_args = InPageRequestContextMockArgs::builder()
+        Ok(())
+    }
+
+    #[test]
+    fn transaction_approval_for_other_origin_is_unauthorized() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+        let dapp_address: Address = authorize_dapp(&core)?.parse()?;
+
+        let args = InPageRequestContextMockArgs::builder()
             .user_approves(false)
             .build();
-        let provider = core.in_page_provider_with_args(mock_args);
-        provider.test_call(InPageRequest::PersonalSign(
-            "0xabcd1234".parse()?,
-            "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826".parse()?,
-            None,
-        ))?;
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+        core.in_page_provider_with_args(args.clone())
+            .test_call(InPageRequest::EthSendTransaction(tx))?;
+        core.wait_for_in_page_responses(2);
+        let tx_approval = core
+            .dapp_tx_approval_requests()
+            .pop()
+            .expect("approval was requested");
+
+        // The page navigated to an other origin before the user approved.
+        let provider = core.in_page_provider_for_url(args, OTHER_DAPP_URL);
+        rt::block_on(provider.handle_user_approved_dapp_transaction(tx_approval))?;
 
         let responses = core.responses();
-        assert!(core.dapp_approval().is_none());
-        assert_eq!(responses.len(), 1);
-        assert_eq!(core.notifications().len(), 0);
-
+        assert_eq!(responses.len(), 3);
         let unauthorized = InPageErrorCode::Unauthorized.to_i32().to_string();
-        assert!(responses[0].contains(&unauthorized));
+        assert!(responses[2].contains(&unauthorized));
+        assert!(core.dapp_tx_approvals().is_empty());
 
         Ok(())
     }
 
     #[test]
-    fn proxy_checks_allowed_methods() -> Result<()> {
+    fn request_accounts_when_already_connected() -> Result<()> {
         let core = TmpCore::new()?;
+        let address = authorize_dapp(&core)?;
 
-        let _ = authorize_dapp(&core)?;
-
-        // This request should be refused as it's an unsupported method
         let provider = core.in_page_provider();
-        provider.test_call(InPageRequest::EthSign(
-            "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826".parse()?,
-            "0xabcd1234".parse()?,
-        ))?;
+        provider.test_call(InPageRequest::EthRequestAccounts(()))?;
 
         let responses = core.responses();
-        assert!(core.dapp_approval().is_some());
         assert_eq!(responses.len(), 2);
+        let response: Response<Vec<String>> = serde_json::from_str(&responses[1])?;
+        assert_eq!(response.result, vec![address]);
+        // Emits connect again.
+        assert_eq!(core.notifications().len(), 2);
 
-        let unsupported = InPageErrorCode::UnsupportedMethod.to_i32().to_string();
-        assert!(responses[1].contains(&unsupported));
+        // Doesn't emit connect.
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthAccounts(()))?;
+        let responses = core.responses();
+        assert_eq!(responses.len(), 3);
+        let response: Response<Vec<String>> = serde_json::from_str(&responses[2])?;
+        assert_eq!(response.result, vec![address]);
+        assert_eq!(core.notifications().len(), 2);
 
         Ok(())
     }
 
     #[test]
-    fn personal_sign_callback() -> Result<()> {
+    fn eth_accounts_does_not_prompt() -> Result<()> {
         let core = TmpCore::new()?;
 
-        let address = authorize_dapp(&core)?;
-
         let provider = core.in_page_provider();
-        provider.test_call(InPageRequest::PersonalSign(
-            "0xabcd".parse()?,
-            address.parse()?,
-            None,
-        ))?;
-        // Dapp allotment transfer + personal sign approved
-        core.wait_for_ui_callbacks(2);
+        provider.test_call(InPageRequest::EthAccounts(()))?;
 
+        assert!(core.dapp_approval().is_none());
+        assert!(core.notifications().is_empty());
         let responses = core.responses();
-        assert!(core.dapp_approval().is_some());
-        assert_eq!(responses.len(), 2);
-        assert_eq!(core.dapp_signature_results().len(), 1);
+        assert_eq!(responses.len(), 1);
+        let response: Response<Vec<String>> = serde_json::from_str(&responses[0])?;
+        assert!(response.result.is_empty());
 
         Ok(())
     }
 
     #[test]
-    fn send_transactions_callback() -> Result<()> {
+    fn eth_accounts_is_empty_when_connected_in_other_profile() -> Result<()> {
         let core = TmpCore::new()?;
-        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
-
-        let dapp_address = authorize_dapp(&core)?;
-        let dapp_address: Address = dapp_address.parse().expect("checksum address");
-
-        let tx = TransactionRequest::new()
-            .to(Address::random())
-            .value(U256::one())
-            .from(dapp_address);
+        let address = authorize_dapp(&core)?;
 
         let provider = core.in_page_provider();
-        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
-        // Dapp allotment transfer + tx approved + tx succeeded
-        core.wait_for_ui_callbacks(3);
-
-        let approval_results = core.dapp_tx_approvals();
-        let tx_results = core.dapp_tx_results();
+        provider.test_call(InPageRequest::EthAccounts(()))?;
+        core.core.create_profile("other".into(), "seal-2".into())?;
+        let other_profile = core.core.list_profiles()?.pop().unwrap();
+        core.core.set_active_profile_id(other_profile.id)?;
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthAccounts(()))?;
 
-        assert_eq!(approval_results.len(), 1);
-        assert_eq!(tx_results.len(), 1);
-        assert!(tx_results[0].explorer_url.is_some());
-        assert!(tx_results[0].error_message.is_none());
+        let responses = core.responses();
+        assert_eq!(responses.len(), 3);
+        let connected: Response<Vec<String>> = serde_json::from_str(&responses[1])?;
+        assert_eq!(connected.result, vec![address]);
+        let not_connected: Response<Vec<String>> = serde_json::from_str(&responses[2])?;
+        assert!(not_connected.result.is_empty());
+        // Only the initial connection prompted.
+        assert_eq!(core.notifications().len(), 1);
 
         Ok(())
     }
 
     #[test]
-    fn send_transactions_error_callback() -> Result<()> {
+    fn net_version_matches_active_chain() -> Result<()> {
         let core = TmpCore::new()?;
+        let _ = authorize_dapp(&core)?;
 
-        let dapp_address = authorize_dapp(&core)?;
-        let dapp_address: Address = dapp_address.parse().expect("checksum address");
-
-        let tx = TransactionRequest::new()
-            .to(Address::random())
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::WalletSwitchEthereumChain(
+            SwitchEthereumChainParameter {
+                chain_id: ChainId::EthMainnet.display_hex(),
+            },
+        ))?;
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthNetworkId(()))?;
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 3);
+        let response: Response<String> = serde_json::from_str(&responses[2])?;
+        assert_eq!(response.result, ChainId::EthMainnet.network_version());
+
+        Ok(())
+    }
+
+    #[test]
+    fn web3_client_version_without_approval() -> Result<()> {
+        let core = TmpCore::new()?;
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::Web3ClientVersion(()))?;
+
+        assert!(core.dapp_approval().is_none());
+        let responses = core.responses();
+        assert_eq!(responses.len(), 1);
+        let response: Response<String> = serde_json::from_str(&responses[0])?;
+        assert_eq!(
+            response.result,
+            format!("SealVault/{}", env!("CARGO_PKG_VERSION"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn disallows_un_approved() -> Result<()> {
+        let core = TmpCore::new()?;
+
+        let mock_args = InPageRequestContextMockArgs::builder()
+            .user_approves(false)
+            .build();
+        let provider = core.in_page_provider_with_args(mock_args);
+        provider.test_call(InPageRequest::PersonalSign(
+            "0xabcd1234".parse()?,
+            "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826".parse()?,
+            None,
+        ))?;
+
+        let responses = core.responses();
+        assert!(core.dapp_approval().is_none());
+        assert_eq!(responses.len(), 1);
+        assert_eq!(core.notifications().len(), 0);
+
+        let unauthorized = InPageErrorCode::Unauthorized.to_i32().to_string();
+        assert!(responses[0].contains(&unauthorized));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_checks_allowed_methods() -> Result<()> {
+        let core = TmpCore::new()?;
+
+        let _ = authorize_dapp(&core)?;
+
+        // This request should be refused as it's an unsupported method
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSign(
+            "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826".parse()?,
+            "0xabcd1234".parse()?,
+        ))?;
+
+        let responses = core.responses();
+        assert!(core.dapp_approval().is_some());
+        assert_eq!(responses.len(), 2);
+
+        let unsupported = InPageErrorCode::UnsupportedMethod.to_i32().to_string();
+        assert!(responses[1].contains(&unsupported));
+
+        Ok(())
+    }
+
+    fn example_dapp(core: &TmpCore) -> crate::dto::CoreDapp {
+        core.first_profile()
+            .dapps
+            .into_iter()
+            .find(|dapp| dapp.human_identifier == "example.com")
+            .expect("dapp was added")
+    }
+
+    #[test]
+    fn stores_reported_dapp_metadata() -> Result<()> {
+        let core = TmpCore::new()?;
+        let _ = authorize_dapp(&core)?;
+        let dapp = example_dapp(&core);
+        assert!(dapp.reported_metadata.is_none());
+
+        let metadata = DomainMetadata {
+            name: Some("Uniswap".into()),
+            icon: Some("https://app.uniswap.org/favicon.png".into()),
+            description: Some("Swap tokens".into()),
+        };
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::SendDomainMetadata(metadata))?;
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 2);
+        assert!(!responses[1].to_lowercase().contains("error"));
+
+        let updated_dapp = example_dapp(&core);
+        // Self-reported metadata doesn't affect the identity of the dapp.
+        assert_eq!(updated_dapp.id, dapp.id);
+        assert_eq!(updated_dapp.human_identifier, dapp.human_identifier);
+        let reported = updated_dapp.reported_metadata.expect("metadata was stored");
+        assert_eq!(reported.name.as_deref(), Some("Uniswap"));
+        assert_eq!(
+            reported.icon_url.as_deref(),
+            Some("https://app.uniswap.org/favicon.png")
+        );
+        assert_eq!(reported.description.as_deref(), Some("Swap tokens"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_dapp_metadata_if_not_connected() -> Result<()> {
+        let core = TmpCore::new()?;
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::SendDomainMetadata(Default::default()))?;
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 1);
+        assert!(!responses[0].to_lowercase().contains("error"));
+        assert!(core.dapp_approval().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_disabled_method() -> Result<()> {
+        let mut resources = CoreResourcesMock::new(TmpCoreDir::new()?, false)?;
+        resources.set_disabled_methods(vec!["eth_blockNumber".into()]);
+        let core = TmpCore::with_resources(resources)?;
+        let _ = authorize_dapp(&core)?;
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthBlockNumber(()))?;
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthChainId(()))?;
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 3);
+        let rejected: serde_json::Value = serde_json::from_str(&responses[1])?;
+        assert_eq!(
+            rejected["error"]["code"],
+            InPageErrorCode::UnsupportedMethod.to_i32()
+        );
+        let dapp_approval = core.dapp_approval().expect("dapp was approved");
+        let response: Response<String> = serde_json::from_str(&responses[2])?;
+        assert_eq!(response.result, format!("{:#x}", dapp_approval.chain_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn personal_sign_callback() -> Result<()> {
+        let core = TmpCore::new()?;
+
+        let address = authorize_dapp(&core)?;
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::PersonalSign(
+            "0xabcd".parse()?,
+            address.parse()?,
+            None,
+        ))?;
+        // Dapp allotment transfer + personal sign approved
+        core.wait_for_ui_callbacks(2);
+
+        let responses = core.responses();
+        assert!(core.dapp_approval().is_some());
+        assert_eq!(responses.len(), 2);
+        assert_eq!(core.dapp_signature_results().len(), 1);
+
+        Ok(())
+    }
+
+    fn typed_data(chain_id: u64, verifying_contract: &str) -> Result<TypedData> {
+        let data = json!({
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "chainId", "type": "uint256"},
+                    {"name": "verifyingContract", "type": "address"}
+                ],
+                "Mail": [{"name": "contents", "type": "string"}]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "chainId": chain_id,
+                "verifyingContract": verifying_contract
+            },
+            "message": {"contents": "Hello, Bob!"}
+        });
+        Ok(serde_json::from_value(data)?)
+    }
+
+    #[test]
+    fn sign_typed_data_callback_resolves_verifying_contract() -> Result<()> {
+        let core = TmpCore::new()?;
+        let address = authorize_dapp(&core)?;
+        let chain_id: u64 = ChainId::default_dapp_chain().into();
+        let permit2 = "0x000000000022D473030F116dDEE9F6B43aC78BA3";
+        let unknown: ChecksumAddress = Address::random().into();
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSignTypedDataV4(
+            address.parse()?,
+            typed_data(chain_id, permit2)?,
+        ))?;
+        // Dapp allotment transfer + typed data signature
+        core.wait_for_ui_callbacks(2);
+        core.wait_for_in_page_responses(2);
+
+        // The user is asked to approve the message with the verifying contract resolved.
+        let approval = core.dapp_sign_batch_approval().expect("approval requested");
+        assert_eq!(approval.items.len(), 1);
+        let item = &approval.items[0];
+        assert_eq!(item.domain_name.as_deref(), Some("Ether Mail"));
+        let approval_contract = item.verifying_contract.clone().unwrap();
+        assert_eq!(approval_contract.label.as_deref(), Some("Uniswap Permit2"));
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSignTypedDataV4(
+            address.parse()?,
+            typed_data(chain_id, &unknown.to_string())?,
+        ))?;
+        core.wait_for_ui_callbacks(3);
+        core.wait_for_in_page_responses(3);
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 3);
+        assert!(responses
+            .iter()
+            .all(|response| !response.to_lowercase().contains("error")));
+        let _: Response<String> = serde_json::from_str(&responses[1])?;
+        let results = core.dapp_signature_results();
+        assert_eq!(results.len(), 2);
+
+        let known = results[0].verifying_contract.clone().unwrap();
+        assert_eq!(known.checksum_address, permit2);
+        assert_eq!(known.label.as_deref(), Some("Uniswap Permit2"));
+        assert!(!known.known_to_dapp);
+
+        let unknown_contract = results[1].verifying_contract.clone().unwrap();
+        assert_eq!(unknown_contract.checksum_address, unknown.to_string());
+        assert!(unknown_contract.label.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_typed_data_approval_has_known_schema() -> Result<()> {
+        let core = TmpCore::new()?;
+        let address = authorize_dapp(&core)?;
+        let chain_id: u64 = ChainId::default_dapp_chain().into();
+        let permit: TypedData = serde_json::from_value(serde_json::json!({
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "version", "type": "string"},
+                    {"name": "chainId", "type": "uint256"}
+                ],
+                "Permit": [
+                    {"name": "owner", "type": "address"},
+                    {"name": "spender", "type": "address"},
+                    {"name": "value", "type": "uint256"},
+                    {"name": "nonce", "type": "uint256"},
+                    {"name": "deadline", "type": "uint256"}
+                ]
+            },
+            "primaryType": "Permit",
+            "domain": {"name": "USD Coin", "version": "2", "chainId": chain_id},
+            "message": {
+                "owner": address,
+                "spender": ChecksumAddress::from(Address::random()).to_string(),
+                "value": "1000",
+                "nonce": 0,
+                "deadline": 1
+            }
+        }))?;
+
+        // The user doesn't respond, so nothing is signed.
+        let args = InPageRequestContextMockArgs::builder()
+            .user_responds(false)
+            .build();
+        let provider = core.in_page_provider_with_args(args);
+        provider
+            .test_call(InPageRequest::EthSignTypedDataV4(address.parse()?, permit))?;
+
+        let approval = core.dapp_sign_batch_approval().expect("approval requested");
+        let item = &approval.items[0];
+        assert_eq!(item.known_schema.as_deref(), Some("EIP-2612 Permit"));
+        assert_eq!(item.domain_version.as_deref(), Some("2"));
+        assert!(core.dapp_signature_results().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_typed_data_user_rejects() -> Result<()> {
+        let core = TmpCore::new()?;
+        let address = authorize_dapp(&core)?;
+        let chain_id: u64 = ChainId::default_dapp_chain().into();
+
+        let args = InPageRequestContextMockArgs::builder()
+            .user_approves(false)
+            .build();
+        let provider = core.in_page_provider_with_args(args);
+        provider.test_call(InPageRequest::EthSignTypedDataV4(
+            address.parse()?,
+            typed_data(chain_id, "0x000000000022D473030F116dDEE9F6B43aC78BA3")?,
+        ))?;
+        core.wait_for_in_page_responses(2);
+
+        assert!(core.dapp_sign_batch_approval().is_some());
+        let responses = core.responses();
+        let rejected = InPageErrorCode::UserRejected.to_i32().to_string();
+        assert!(responses[1].contains(&rejected));
+        assert!(core.dapp_signature_results().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_typed_data_rejects_other_chain() -> Result<()> {
+        let core = TmpCore::new()?;
+        let address = authorize_dapp(&core)?;
+        let chain_id: u64 = ChainId::default_dapp_chain().into();
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSignTypedDataV4(
+            address.parse()?,
+            typed_data(chain_id + 1, "0x000000000022D473030F116dDEE9F6B43aC78BA3")?,
+        ))?;
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 2);
+        assert!(responses[1].contains("error"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn handled_requests_are_counted() -> Result<()> {
+        let core = TmpCore::new()?;
+        authorize_dapp(&core)?;
+
+        core.in_page_provider()
+            .test_call(InPageRequest::EthChainId(()))?;
+        core.in_page_provider()
+            .test_call(InPageRequest::EthChainId(()))?;
+
+        let snapshot = core.core.metrics_snapshot();
+        let count = |method: &str| {
+            snapshot
+                .requests_by_method
+                .iter()
+                .find(|method_count| method_count.method == method)
+                .map(|method_count| method_count.count)
+        };
+        assert_eq!(count("eth_chainId"), Some(2));
+        assert_eq!(count("eth_requestAccounts"), Some(1));
+        assert_eq!(snapshot.approvals_granted, 1);
+        assert_eq!(snapshot.approvals_rejected, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sign_typed_data_batch_with_partial_approval() -> Result<()> {
+        let core = TmpCore::new()?;
+        let address = authorize_dapp(&core)?;
+        let chain_id: u64 = ChainId::default_dapp_chain().into();
+        let contracts: Vec<String> = (0..3)
+            .map(|_| ChecksumAddress::from(Address::random()).to_string())
+            .collect();
+        let typed_data = contracts
+            .iter()
+            .map(|contract| typed_data(chain_id, contract))
+            .collect::<Result<Vec<_>>>()?;
+
+        let args = InPageRequestContextMockArgs::builder()
+            .declined_sign_batch_items(vec![1])
+            .build();
+        let provider = core.in_page_provider_with_args(args);
+        provider.test_call(InPageRequest::SignTypedDataBatch(
+            address.parse()?,
+            typed_data.clone(),
+        ))?;
+        core.wait_for_in_page_responses(2);
+        let batch_approval = core.dapp_sign_batch_approval().expect("approval requested");
+        // Sign the approved messages one by one to compare.
+        for (count, index) in [(3, 0), (4, 2)] {
+            core.in_page_provider()
+                .test_call(InPageRequest::EthSignTypedDataV4(
+                    address.parse()?,
+                    typed_data[index].clone(),
+                ))?;
+            core.wait_for_in_page_responses(count);
+        }
+        // Dapp allotment transfer + two batch signatures + two single signatures
+        core.wait_for_ui_callbacks(5);
+
+        let approval_contracts: Vec<String> = batch_approval
+            .items
+            .iter()
+            .map(|item| item.verifying_contract.clone().unwrap().checksum_address)
+            .collect();
+        assert_eq!(approval_contracts, contracts);
+        assert!(batch_approval
+            .items
+            .iter()
+            .all(|item| item.primary_type == "Mail"));
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 4);
+        let batch: Response<Vec<Option<String>>> = serde_json::from_str(&responses[1])?;
+        let first: Response<String> = serde_json::from_str(&responses[2])?;
+        let third: Response<String> = serde_json::from_str(&responses[3])?;
+        assert_eq!(
+            batch.result,
+            vec![Some(first.result), None, Some(third.result)]
+        );
+        assert_eq!(core.dapp_signature_results().len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_transactions_callback() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        let approval_results = core.dapp_tx_approvals();
+        let tx_results = core.dapp_tx_results();
+
+        assert_eq!(approval_results.len(), 1);
+        assert_eq!(tx_results.len(), 1);
+        assert!(tx_results[0].explorer_url.is_some());
+        assert!(tx_results[0].error_message.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_transactions_inspector_warnings() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        let flagged: Address = FLAGGED_RECIPIENT.parse().expect("valid address");
+        let tx = TransactionRequest::new()
+            .to(flagged)
+            .value(U256::one())
+            .from(dapp_address);
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        let approval_results = core.dapp_tx_approvals();
+        assert_eq!(approval_results.len(), 1);
+        assert_eq!(approval_results[0].warnings.len(), 1);
+        assert!(approval_results[0].warnings[0]
+            .message
+            .to_lowercase()
+            .contains("malicious"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_transactions_no_inspector_warnings() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
+        core.wait_for_ui_callbacks(3);
+
+        let approval_results = core.dapp_tx_approvals();
+        assert_eq!(approval_results.len(), 1);
+        assert!(approval_results[0].warnings.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn flags_call_to_address_without_code() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        // Value transfer to an address without code is exempt.
+        let transfer = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(transfer))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        // ERC-20 `transfer` call to an address without code.
+        let call = TransactionRequest::new()
+            .to(Address::random())
+            .data(hex::decode(
+                "a9059cbb\
+                 0000000000000000000000008ba1f109551bd432803012645ac136ddd64dba72\
+                 00000000000000000000000000000000000000000000000000000000000003e8",
+            )?)
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(call))?;
+        core.wait_for_ui_callbacks(5);
+
+        let approval_requests = core.dapp_tx_approval_requests();
+        assert_eq!(approval_requests.len(), 2);
+        assert!(!approval_requests[0].no_code_at_target);
+        assert!(approval_requests[1].no_code_at_target);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_decoding_oversized_calldata() -> Result<()> {
+        let mut resources = CoreResourcesMock::new(TmpCoreDir::new()?, false)?;
+        resources.set_max_decoded_calldata_bytes(64);
+        let core = TmpCore::with_resources(resources)?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        // Unlimited ERC-20 `approve` call padded with trailing bytes beyond the limit.
+        let mut data = hex::decode(
+            "095ea7b3\
+             0000000000000000000000008ba1f109551bd432803012645ac136ddd64dba72",
+        )?;
+        data.extend([u8::MAX; 32]);
+        data.extend([0; 32]);
+        let data_len = data.len();
+        let call = TransactionRequest::new()
+            .to(Address::random())
+            .data(data)
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(call))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        let approval_requests = core.dapp_tx_approval_requests();
+        assert_eq!(approval_requests.len(), 1);
+        let request = &approval_requests[0];
+        assert!(request.data_too_large);
+        assert!(request.decoded_call.is_none());
+        // The inspectors don't decode oversized calldata either.
+        assert!(request.warnings.is_empty());
+        assert_eq!(request.calldata_selector.as_deref(), Some("0x095ea7b3"));
+        assert_eq!(request.calldata_size, data_len as u64);
+        assert_eq!(core.dapp_tx_approvals().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_transactions_high_value() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+        core.core.set_eth_high_value_threshold(
+            core.first_profile().id,
+            ChainId::default_dapp_chain().into(),
+            "0.5".into(),
+        )?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        let below = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(below))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        let above = TransactionRequest::new()
+            .to(Address::random())
+            .value(ethers::utils::parse_ether("0.6")?)
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(above))?;
+        core.wait_for_ui_callbacks(5);
+
+        let approval_results = core.dapp_tx_approvals();
+        assert_eq!(approval_results.len(), 2);
+        assert!(!approval_results[0].high_value);
+        assert!(approval_results[1].high_value);
+        assert_eq!(approval_results[1].amount, "0.6");
+
+        Ok(())
+    }
+
+    fn trust_first_dapp(core: &TmpCore) -> Result<()> {
+        let profile = core.first_profile();
+        let dapp = profile.dapps.first().expect("dapp is added");
+        core.core.set_dapp_trust(
+            profile.id.clone(),
+            dapp.id.clone(),
+            m::DappTrustLevel::Trusted,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn standard_dapp_transaction_requires_approval() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        assert_eq!(core.dapp_tx_approval_requests().len(), 1);
+        assert_eq!(core.dapp_tx_approvals().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn trusted_dapp_small_transaction_auto_approves() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+        trust_first_dapp(&core)?;
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        assert!(core.dapp_tx_approval_requests().is_empty());
+        let approval_results = core.dapp_tx_approvals();
+        assert_eq!(approval_results.len(), 1);
+        assert!(approval_results[0].warnings.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rapid_transactions_get_sequential_nonces() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+        trust_first_dapp(&core)?;
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+        let first = core
+            .in_page_provider()
+            .test_spawn(InPageRequest::EthSendTransaction(tx.clone()))?;
+        let second = core
+            .in_page_provider()
+            .test_spawn(InPageRequest::EthSendTransaction(tx))?;
+        rt::block_on(first)??;
+        rt::block_on(second)??;
+        // Dapp allotment transfer + 2 x (tx approved + tx succeeded)
+        core.wait_for_ui_callbacks(5);
+
+        let queue = core.core.transaction_queue(
+            core.first_profile().id,
+            ChainId::default_dapp_chain().into(),
+            ethers::utils::to_checksum(&dapp_address, None),
+        )?;
+        let nonces: Vec<u64> = queue.iter().map(|entry| entry.nonce).collect();
+        assert_eq!(nonces, vec![0, 1]);
+        assert!(queue
+            .iter()
+            .all(|entry| entry.status == QueuedTransactionStatus::Confirmed));
+        let tx_results = core.dapp_tx_results();
+        assert_eq!(tx_results.len(), 2);
+        assert!(tx_results
+            .iter()
+            .all(|result| result.error_message.is_none()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn trusted_dapp_unlimited_approval_requires_approval() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+        trust_first_dapp(&core)?;
+
+        // approve(address spender, uint256 amount) with the max amount
+        let spender = Address::random();
+        let mut data = hex::decode("095ea7b3")?;
+        data.extend([0u8; 12]);
+        data.extend(spender.as_bytes());
+        data.extend([u8::MAX; 32]);
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .data(data)
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        let approval_requests = core.dapp_tx_approval_requests();
+        assert_eq!(approval_requests.len(), 1);
+        assert!(!approval_requests[0].high_value);
+        assert_eq!(approval_requests[0].warnings.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn trusted_dapp_over_spending_limit_requires_approval() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+        trust_first_dapp(&core)?;
+
+        let profile = core.first_profile();
+        let dapp = profile.dapps.first().expect("dapp is added");
+        let native_limit = crate::NativeSpendingLimitArgs::builder()
+            .chain_id(ChainId::default_dapp_chain().into())
+            .amount_decimal("0.000000000000000002".into())
+            .build();
+        let args = crate::DappSpendingLimitArgs::builder()
+            .profile_id(profile.id.clone())
+            .dapp_id(dapp.id.clone())
+            .window_seconds(3600)
+            .native_limits(vec![native_limit])
+            .build();
+        core.core.set_dapp_spending_limit(args)?;
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(U256::one())
+            .from(dapp_address);
+        // Up to the limit
+        core.in_page_provider()
+            .test_call(InPageRequest::EthSendTransaction(tx.clone()))?;
+        core.in_page_provider()
+            .test_call(InPageRequest::EthSendTransaction(tx.clone()))?;
+        // Dapp allotment transfer + 2 x (tx approved + tx succeeded)
+        core.wait_for_ui_callbacks(5);
+        assert!(core.dapp_tx_approval_requests().is_empty());
+
+        // Crossing the limit
+        core.in_page_provider()
+            .test_call(InPageRequest::EthSendTransaction(tx))?;
+        core.wait_for_ui_callbacks(7);
+
+        let approval_requests = core.dapp_tx_approval_requests();
+        assert_eq!(approval_requests.len(), 1);
+        assert!(approval_requests[0].exceeds_spending_limit);
+        assert!(!approval_requests[0].high_value);
+        assert!(approval_requests[0].warnings.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reserved_spends_count_towards_limit() -> Result<()> {
+        let core = TmpCore::new()?;
+        authorize_dapp(&core)?;
+
+        let profile = core.first_profile();
+        let dapp = profile.dapps.first().expect("dapp is added");
+        let native_limit = crate::NativeSpendingLimitArgs::builder()
+            .chain_id(ChainId::default_dapp_chain().into())
+            .amount_decimal("0.000000000000000001".into())
+            .build();
+        let args = crate::DappSpendingLimitArgs::builder()
+            .profile_id(profile.id.clone())
+            .dapp_id(dapp.id.clone())
+            .window_seconds(3600)
+            .native_limits(vec![native_limit])
+            .build();
+        core.core.set_dapp_spending_limit(args)?;
+
+        let provider = core.in_page_provider();
+        let session = rt::block_on(provider.fetch_session_for_approved_dapp())?
+            .expect("dapp is connected");
+        let spends = vec![TransactionSpend {
+            chain_id: ChainId::default_dapp_chain(),
+            token_address: None,
+            amount: U256::one(),
+        }];
+
+        let reserve = |enforce_limit| -> Result<Option<Vec<String>>> {
+            let spend_ids = rt::block_on(provider.reserve_spends(
+                &session,
+                spends.clone(),
+                enforce_limit,
+            ))?;
+            Ok(spend_ids)
+        };
+        let spend_ids = reserve(true)?.expect("within limit");
+        // A concurrent transaction can't exceed the limit.
+        assert_eq!(reserve(true)?, None);
+
+        rt::block_on(provider.release_spends(spend_ids));
+        assert!(reserve(true)?.is_some());
+        // The user approved exceeding the limit.
+        assert!(reserve(false)?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn trusted_dapp_high_value_requires_approval() -> Result<()> {
+        let core = TmpCore::new()?;
+        core.fund_first_profile_wallet(ChainId::default_dapp_chain(), 10)?;
+        core.core.set_eth_high_value_threshold(
+            core.first_profile().id,
+            ChainId::default_dapp_chain().into(),
+            "0.5".into(),
+        )?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+        trust_first_dapp(&core)?;
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
+            .value(ethers::utils::parse_ether("0.6")?)
+            .from(dapp_address);
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::EthSendTransaction(tx))?;
+        // Dapp allotment transfer + tx approved + tx succeeded
+        core.wait_for_ui_callbacks(3);
+
+        let approval_requests = core.dapp_tx_approval_requests();
+        assert_eq!(approval_requests.len(), 1);
+        assert!(approval_requests[0].high_value);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_transactions_error_callback() -> Result<()> {
+        let core = TmpCore::new()?;
+
+        let dapp_address = authorize_dapp(&core)?;
+        let dapp_address: Address = dapp_address.parse().expect("checksum address");
+
+        let tx = TransactionRequest::new()
+            .to(Address::random())
             .value(U256::one())
             .from(dapp_address);
 
@@ -1383,13 +3543,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn switch_to_unrecognized_chain() -> Result<()> {
+        let core = TmpCore::new()?;
+
+        let _ = authorize_dapp(&core)?;
+
+        let provider = core.in_page_provider();
+        provider.test_call(InPageRequest::WalletSwitchEthereumChain(
+            SwitchEthereumChainParameter {
+                chain_id: "0x539".to_string(),
+            },
+        ))?;
+
+        let responses = core.responses();
+        assert_eq!(responses.len(), 2);
+        let unrecognized_chain = InPageErrorCode::UnrecognizedChain.to_i32().to_string();
+        assert!(responses[1].contains(&unrecognized_chain));
+
+        Ok(())
+    }
+
     #[test]
     fn loads_in_page_provider_with_replace() -> Result<()> {
         let rpc_provider_name = "somethingUnlikelyToBeFoundInTheSource";
         let request_handler_name = "somethingElse.unlikely.to.be.found";
 
-        let source =
-            load_in_page_provider_script(rpc_provider_name, request_handler_name)?;
+        let source = load_in_page_provider_script(
+            rpc_provider_name,
+            request_handler_name,
+            TEST_WALLET_NAME,
+            TEST_WALLET_ICON,
+            TEST_WALLET_RDNS,
+            &Default::default(),
+        )?;
 
         let network_version = ChainId::default_dapp_chain().network_version();
         let chain_id = ChainId::default_dapp_chain().display_hex();
@@ -1402,6 +3589,105 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn in_page_provider_announces_wallet_info() -> Result<()> {
+        let source = load_in_page_provider_script(
+            "sealVaultRpcProvider",
+            "sealVaultRequestHandler",
+            TEST_WALLET_NAME,
+            TEST_WALLET_ICON,
+            TEST_WALLET_RDNS,
+            &Default::default(),
+        )?;
+
+        let prefix = r#"const SEALVAULT_WALLET_INFO = ""#;
+        let start = source.find(prefix).expect("wallet info constant") + prefix.len();
+        let end = start + source[start..].find('"').expect("closing quote");
+        let wallet_info: serde_json::Value =
+            serde_json::from_slice(&hex::decode(&source[start..end])?)?;
+
+        assert_eq!(wallet_info["rdns"], TEST_WALLET_RDNS);
+        assert_eq!(wallet_info["name"], TEST_WALLET_NAME);
+        assert_eq!(wallet_info["icon"], TEST_WALLET_ICON);
+        let uuid = wallet_info["uuid"].as_str().expect("uuid is string");
+        assert_eq!(uuid::Uuid::parse_str(uuid)?.get_version_num(), 4);
+        assert!(source.contains("eip6963:announceProvider"));
+        assert!(source.contains("eip6963:requestProvider"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_wallet_info() {
+        let load = |icon, rdns| {
+            load_in_page_provider_script(
+                "a",
+                "b",
+                TEST_WALLET_NAME,
+                icon,
+                rdns,
+                &Default::default(),
+            )
+        };
+        assert!(load(TEST_WALLET_ICON, "sealvault").is_err());
+        assert!(load(TEST_WALLET_ICON, "org.seal\"vault").is_err());
+        assert!(load("https://sealvault.org/icon.png", TEST_WALLET_RDNS).is_err());
+    }
+
+    fn load_test_script(options: &ScriptOptions) -> Result<String, Error> {
+        load_in_page_provider_script(
+            "sealVaultRpcProvider",
+            "sealVaultRequestHandler",
+            TEST_WALLET_NAME,
+            TEST_WALLET_ICON,
+            TEST_WALLET_RDNS,
+            options,
+        )
+    }
+
+    #[test]
+    fn debug_in_page_provider_has_logging_hooks() -> Result<()> {
+        let options = ScriptOptions {
+            backend_url: Some("http://localhost:8080/backend".into()),
+            debug: true,
+            minify: false,
+        };
+        let debug = load_test_script(&options)?;
+        assert!(debug.contains("console.debug("));
+        assert!(debug.contains(&hex::encode("http://localhost:8080/backend")));
+
+        let prod = load_test_script(&Default::default())?;
+        assert!(!prod.contains("console.debug("));
+        assert!(!prod.contains(config::DEBUG_ONLY_START_MARKER));
+        assert!(prod.len() < debug.len());
+
+        let minified = load_test_script(&ScriptOptions {
+            minify: true,
+            ..Default::default()
+        })?;
+        assert!(!minified.contains("/**"));
+        assert!(minified.len() < prod.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn in_page_provider_validates_backend_url() {
+        let load = |backend_url: &str| {
+            let options = ScriptOptions {
+                backend_url: Some(backend_url.into()),
+                ..Default::default()
+            };
+            load_test_script(&options)
+        };
+        assert!(load("https://example.com/backend").is_ok());
+        assert!(load("http://127.0.0.1:8080/backend").is_ok());
+        assert!(load("http://example.com/backend").is_err());
+        assert!(load("javascript:alert(1)").is_err());
+        assert!(load("https://user@example.com").is_err());
+        assert!(load("not a url").is_err());
+    }
+
     #[test]
     fn error_codes_fit_into_i32() {
         let mut sum = 0;
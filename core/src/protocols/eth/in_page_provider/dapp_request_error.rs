@@ -0,0 +1,228 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use jsonrpsee::types::ErrorObject;
+
+use crate::{protocols::eth::in_page_provider::InPageErrorCode, Error};
+
+/// Errors that are returned to dapps in response to in-page requests.
+/// Convert with `to_rpc_error` to get the spec compliant JSON-RPC error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DappRequestError {
+    /// The method doesn't exist.
+    MethodNotFound { message: String },
+    /// Invalid method parameters.
+    InvalidParams { message: String },
+    /// An unexpected error occurred. Details are not disclosed to the dapp.
+    Internal,
+    /// The user rejected the request.
+    UserRejected,
+    /// The request was cancelled by the host before it was completed.
+    RequestCancelled,
+    /// The user didn't respond to the approval prompt in time.
+    ApprovalTimedOut,
+    /// The requested method and/or account has not been authorized by the user.
+    Unauthorized,
+    /// The method is valid, but the wallet doesn't support it.
+    UnsupportedMethod { method: String },
+    /// The chain id is not recognized by the wallet.
+    UnrecognizedChain { chain_id: String },
+    /// An error returned by the RPC node that is forwarded to the dapp as is.
+    Rpc { code: i32, message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonRpcError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl From<JsonRpcError> for ErrorObject<'static> {
+    fn from(error: JsonRpcError) -> Self {
+        // We need to select a data type even though data is none, <String>
+        let data: Option<String> = None;
+        ErrorObject::owned(error.code, error.message, data)
+    }
+}
+
+/// The canonical mapping of dapp-facing errors to JSON-RPC error codes and messages.
+/// Codes are from the JSON-RPC spec, EIP-1193 and EIP-3326.
+pub fn to_rpc_error(err: &DappRequestError) -> JsonRpcError {
+    let (code, message) = match err {
+        DappRequestError::Rpc { code, message } => {
+            return JsonRpcError {
+                code: *code,
+                message: message.clone(),
+            }
+        }
+        DappRequestError::MethodNotFound { message } => {
+            (InPageErrorCode::MethodNotFound, message.clone())
+        }
+        DappRequestError::InvalidParams { message } => {
+            (InPageErrorCode::InvalidParams, message.clone())
+        }
+        DappRequestError::Internal => {
+            (InPageErrorCode::InternalError, "Internal error".into())
+        }
+        DappRequestError::UserRejected => (
+            InPageErrorCode::UserRejected,
+            "The user rejected the request".into(),
+        ),
+        // EIP-1193 has no dedicated code for cancellation, and dapps handle user rejection.
+        DappRequestError::RequestCancelled => (
+            InPageErrorCode::UserRejected,
+            "The request was cancelled".into(),
+        ),
+        DappRequestError::ApprovalTimedOut => (
+            InPageErrorCode::UserRejected,
+            "The request timed out waiting for user approval".into(),
+        ),
+        DappRequestError::Unauthorized => (
+            InPageErrorCode::Unauthorized,
+            "The requested method and/or account has not been authorized by the user"
+                .into(),
+        ),
+        DappRequestError::UnsupportedMethod { method } => (
+            InPageErrorCode::UnsupportedMethod,
+            format!("This method is not supported: '{method}'"),
+        ),
+        DappRequestError::UnrecognizedChain { chain_id } => (
+            InPageErrorCode::UnrecognizedChain,
+            format!("Unrecognized chain id: '{chain_id}'"),
+        ),
+    };
+    JsonRpcError {
+        code: code.to_i32(),
+        message,
+    }
+}
+
+/// Errors from the RPC node are forwarded, while other errors are logged and reported to the
+/// dapp as internal errors.
+impl From<Error> for DappRequestError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::JsonRpc { code, message } => DappRequestError::Rpc {
+                code: code.code(),
+                message,
+            },
+            err => {
+                log::error!("Failed to process dapp request due to error: {err}");
+                DappRequestError::Internal
+            }
+        }
+    }
+}
+
+impl From<DappRequestError> for Error {
+    fn from(err: DappRequestError) -> Self {
+        let JsonRpcError { code, message } = to_rpc_error(&err);
+        Error::JsonRpc {
+            code: code.into(),
+            message,
+        }
+    }
+}
+
+impl From<DappRequestError> for ErrorObject<'static> {
+    fn from(err: DappRequestError) -> Self {
+        to_rpc_error(&err).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_to_documented_codes() {
+        let cases = [
+            (
+                DappRequestError::MethodNotFound {
+                    message: "foo".into(),
+                },
+                -32601,
+            ),
+            (
+                DappRequestError::InvalidParams {
+                    message: "foo".into(),
+                },
+                -32602,
+            ),
+            (DappRequestError::Internal, -32603),
+            (DappRequestError::UserRejected, 4001),
+            (DappRequestError::RequestCancelled, 4001),
+            (DappRequestError::ApprovalTimedOut, 4001),
+            (DappRequestError::Unauthorized, 4100),
+            (
+                DappRequestError::UnsupportedMethod {
+                    method: "eth_sign".into(),
+                },
+                4200,
+            ),
+            (
+                DappRequestError::UnrecognizedChain {
+                    chain_id: "0x539".into(),
+                },
+                4902,
+            ),
+        ];
+        for (err, expected_code) in cases {
+            let rpc_error = to_rpc_error(&err);
+            assert_eq!(rpc_error.code, expected_code);
+            assert!(!rpc_error.message.is_empty());
+
+            let error: Error = err.into();
+            match error {
+                Error::JsonRpc { code, message } => {
+                    assert_eq!(code.code(), expected_code);
+                    assert_eq!(message, rpc_error.message);
+                }
+                _ => panic!("Expected JSON-RPC error"),
+            }
+        }
+    }
+
+    #[test]
+    fn forwards_rpc_errors() {
+        let err: DappRequestError = Error::JsonRpc {
+            code: (-32000).into(),
+            message: "execution reverted".into(),
+        }
+        .into();
+        let rpc_error = to_rpc_error(&err);
+        assert_eq!(rpc_error.code, -32000);
+        assert_eq!(rpc_error.message, "execution reverted");
+    }
+
+    #[test]
+    fn hides_internal_errors() {
+        let err: DappRequestError = Error::Fatal {
+            error: "secret details".into(),
+        }
+        .into();
+        assert_eq!(err, DappRequestError::Internal);
+        let rpc_error = to_rpc_error(&err);
+        assert_eq!(rpc_error.code, -32603);
+        assert!(!rpc_error.message.contains("secret"));
+    }
+
+    #[test]
+    fn preserves_messages() {
+        let err = DappRequestError::InvalidParams {
+            message: "Invalid address".into(),
+        };
+        assert_eq!(to_rpc_error(&err).message, "Invalid address");
+
+        let err = DappRequestError::UnsupportedMethod {
+            method: "eth_sign".into(),
+        };
+        assert!(to_rpc_error(&err).message.contains("eth_sign"));
+
+        let err = DappRequestError::UnrecognizedChain {
+            chain_id: "0x539".into(),
+        };
+        assert!(to_rpc_error(&err).message.contains("0x539"));
+    }
+}
@@ -36,6 +36,14 @@ pub enum InPageRequest {
     #[serde(rename = "wallet_switchEthereumChain", with = "sequence_len_one")]
     WalletSwitchEthereumChain(SwitchEthereumChainParameter),
 
+    /// Self-reported dapp metadata sent by the in-page script on connect.
+    /// Based on MetaMask's legacy `metamask_sendDomainMetadata`.
+    #[serde(
+        rename = "sealvault_sendDomainMetadata",
+        alias = "metamask_sendDomainMetadata"
+    )]
+    SendDomainMetadata(DomainMetadata),
+
     // Ethereum RPC methods
     #[serde(rename = "web3_clientVersion", with = "empty_params")]
     Web3ClientVersion(()),
@@ -58,13 +66,15 @@ pub enum InPageRequest {
     #[serde(rename = "eth_maxPriorityFeePerGas", with = "empty_params")]
     EthMaxPriorityFeePerGas(()),
 
-    #[serde(
-        rename = "eth_accounts",
-        alias = "eth_requestAccounts",
-        with = "empty_params"
-    )]
+    /// Returns the accounts if the dapp is connected, an empty list otherwise.
+    #[serde(rename = "eth_accounts", with = "empty_params")]
     EthAccounts(()),
 
+    /// https://eips.ethereum.org/EIPS/eip-1102
+    /// Requests the user to connect the dapp if it's not connected yet.
+    #[serde(rename = "eth_requestAccounts", with = "empty_params")]
+    EthRequestAccounts(()),
+
     #[serde(rename = "eth_blockNumber", with = "empty_params")]
     EthBlockNumber(()),
 
@@ -131,6 +141,12 @@ pub enum InPageRequest {
     #[serde(rename = "eth_signTypedData_v4")]
     EthSignTypedDataV4(Address, TypedData),
 
+    /// Signs multiple [EIP-712](https://github.com/ethereum/EIPs/blob/master/EIPS/eip-712.md)
+    /// messages with a single approval. Responds with the signatures in the request order and
+    /// null for the messages that the user declined.
+    #[serde(rename = "sealvault_signTypedDataBatch")]
+    SignTypedDataBatch(Address, Vec<TypedData>),
+
     #[serde(rename = "eth_sendTransaction", with = "sequence_len_one")]
     // EthSendTransaction(Box<TransactionRequest>),
     EthSendTransaction(TransactionRequest),
@@ -330,6 +346,18 @@ impl TryFrom<InPageRequest> for InPageRequestParams {
     }
 }
 
+/// Untrusted metadata reported by the dapp.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DomainMetadata {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Icon url
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
 /// Incomplete because we only care about the chain_id param.
 /// From https://docs.metamask.io/guide/rpc-api.html#wallet-addethereumchain
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -437,6 +465,26 @@ mod tests {
         let _req = serde_json::from_value::<InPageRequest>(value).unwrap();
     }
 
+    #[test]
+    fn test_eth_request_accounts() {
+        let s = r#"{"method": "eth_requestAccounts", "params":[]}"#;
+        let value: serde_json::Value = serde_json::from_str(s).unwrap();
+        let req = serde_json::from_value::<InPageRequest>(value).unwrap();
+        assert_eq!(req, InPageRequest::EthRequestAccounts(()));
+    }
+
+    #[test]
+    fn test_metamask_send_domain_metadata() {
+        let s = r#"{"method": "metamask_sendDomainMetadata", "params":{"name": "Foo", "icon": null}}"#;
+        let value: serde_json::Value = serde_json::from_str(s).unwrap();
+        let req = serde_json::from_value::<InPageRequest>(value).unwrap();
+        let expected = DomainMetadata {
+            name: Some("Foo".into()),
+            ..Default::default()
+        };
+        assert_eq!(req, InPageRequest::SendDomainMetadata(expected));
+    }
+
     #[test]
     fn test_eth_network_id() {
         let s = r#"{"method": "eth_networkId", "params":[]}"#;
@@ -2,22 +2,78 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 mod dapp_key_provider;
+mod dapp_request_error;
 mod in_page_request;
+mod pending_requests;
 
 pub use dapp_key_provider::{
-    CoreInPageCallbackI, DappApprovalParams, DappKeyProvider, InPageErrorCode,
-    InPageRequestContextI,
+    CoreInPageCallbackI, DappApprovalParams, DappKeyProvider,
+    DappSignBatchApprovalParams, DappSignBatchItem, DappTransactionApprovalParams,
+    InPageErrorCode, InPageRequestContextI,
 };
+pub use dapp_request_error::{to_rpc_error, DappRequestError, JsonRpcError};
+pub use pending_requests::{PendingApproval, PendingRequests};
+use serde::Serialize;
+use url::Url;
 
-use crate::{assets, config, protocols::eth, Error};
+use crate::{
+    assets, config,
+    protocols::eth,
+    utils::{is_https_or_loopback, new_uuid},
+    Error,
+};
+
+/// Options to build variants of the in-page script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptOptions {
+    /// URL of an HTTP backend that the script posts requests to instead of calling the request
+    /// handler of the host. Used by the dev server.
+    pub backend_url: Option<String>,
+    /// Keep verbose client-side logging.
+    pub debug: bool,
+    /// Strip comments and indentation. Off by default, because the line-based minifier
+    /// doesn't parse JS, so it could change multi-line template literals.
+    pub minify: bool,
+}
+
+impl Default for ScriptOptions {
+    /// Production options.
+    fn default() -> Self {
+        Self {
+            backend_url: None,
+            debug: false,
+            minify: false,
+        }
+    }
+}
 
 pub fn load_in_page_provider_script(
     rpc_provider_name: &str,
     request_handler_name: &str,
+    wallet_name: &str,
+    wallet_icon: &str,
+    wallet_rdns: &str,
+    options: &ScriptOptions,
 ) -> Result<String, Error> {
     let chain_id = eth::ChainId::default_dapp_chain();
     let network_version = chain_id.network_version();
     let hex_chain_id = chain_id.display_hex();
+    let wallet_info = WalletInfo::new(wallet_name, wallet_icon, wallet_rdns)?;
+    let wallet_info = wallet_info.to_hex_json()?;
+    let backend_url = match options.backend_url.as_deref() {
+        Some(backend_url) => {
+            // The script sets the request handler as a property of `window`.
+            if request_handler_name.contains('.') {
+                return Err(Error::Fatal {
+                    error: "The request handler must not be nested with a backend url"
+                        .into(),
+                });
+            }
+            // Passed as hex to prevent injection like the wallet info.
+            hex::encode(validate_backend_url(backend_url)?.as_str())
+        }
+        None => "".into(),
+    };
     let replacements = vec![
         (config::RPC_PROVIDER_PLACEHOLDER, rpc_provider_name),
         (config::REQUEST_HANDLER_PLACEHOLDER, request_handler_name),
@@ -26,6 +82,8 @@ pub fn load_in_page_provider_script(
             config::DEFAULT_NETWORK_VERSION_PLACEHOLDER,
             &network_version,
         ),
+        (config::WALLET_INFO_PLACEHOLDER, &wallet_info),
+        (config::BACKEND_URL_PLACEHOLDER, &backend_url),
     ];
 
     let path = format!(
@@ -33,7 +91,115 @@ pub fn load_in_page_provider_script(
         config::JS_PREFIX,
         config::IN_PAGE_PROVIDER_FILE_NAME
     );
-    let text = assets::load_asset_with_replacements(&path, replacements.iter())?;
+    let mut text = assets::load_asset_with_replacements(&path, replacements.iter())?;
+    if !options.debug {
+        text = strip_debug_only(&text);
+    }
+    if options.minify {
+        text = minify(&text);
+    }
 
     Ok(text)
 }
+
+/// The backend must be served over HTTPS unless it's on the loopback interface.
+fn validate_backend_url(backend_url: &str) -> Result<Url, Error> {
+    let url = Url::parse(backend_url).map_err(|err| Error::Fatal {
+        error: format!("Invalid backend url '{backend_url}': {err}"),
+    })?;
+    if !is_https_or_loopback(&url) {
+        return Err(Error::Fatal {
+            error: format!("Backend url must be HTTPS or loopback HTTP: '{backend_url}'"),
+        });
+    }
+    Ok(url)
+}
+
+/// Remove the blocks between the debug-only markers.
+fn strip_debug_only(script: &str) -> String {
+    let mut in_debug_block = false;
+    let mut lines: Vec<&str> = Default::default();
+    for line in script.lines() {
+        match line.trim() {
+            config::DEBUG_ONLY_START_MARKER => in_debug_block = true,
+            config::DEBUG_ONLY_END_MARKER => in_debug_block = false,
+            _ if !in_debug_block => lines.push(line),
+            _ => {}
+        }
+    }
+    lines.join("\n")
+}
+
+/// Conservative minification that removes whole line comments, indentation and empty lines.
+/// Line breaks are kept, so automatic semicolon insertion works the same.
+fn minify(script: &str) -> String {
+    let mut in_block_comment = false;
+    let mut lines: Vec<&str> = Default::default();
+    for line in script.lines() {
+        let line = line.trim();
+        if in_block_comment {
+            in_block_comment = !line.ends_with("*/");
+        } else if line.starts_with("/*") {
+            in_block_comment = !line.ends_with("*/");
+        } else if !line.is_empty() && !line.starts_with("//") {
+            lines.push(line);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Wallet info announced to dapps for multi-wallet discovery.
+/// https://eips.ethereum.org/EIPS/eip-6963#provider-info
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct WalletInfo<'a> {
+    /// UUIDv4 that is unique per page load.
+    uuid: String,
+    name: &'a str,
+    /// Data URI of the wallet icon.
+    icon: &'a str,
+    /// Reverse DNS identifier of the wallet, eg. `org.sealvault`
+    rdns: &'a str,
+}
+
+impl<'a> WalletInfo<'a> {
+    fn new(name: &'a str, icon: &'a str, rdns: &'a str) -> Result<Self, Error> {
+        if name.trim().is_empty() {
+            return Err(Error::Fatal {
+                error: "Wallet name must not be empty".into(),
+            });
+        }
+        if !icon.starts_with("data:image/") {
+            return Err(Error::Fatal {
+                error: "Wallet icon must be an image data URI".into(),
+            });
+        }
+        if !is_reverse_dns(rdns) {
+            return Err(Error::Fatal {
+                error: format!("Invalid reverse DNS wallet identifier: '{rdns}'"),
+            });
+        }
+        Ok(Self {
+            uuid: new_uuid(),
+            name,
+            icon,
+            rdns,
+        })
+    }
+
+    /// The info is passed to the script as hex encoded JSON to prevent injection.
+    fn to_hex_json(&self) -> Result<String, Error> {
+        let json = serde_json::to_string(self).map_err(|err| Error::Fatal {
+            error: format!("Failed to serialize wallet info with error: '{err}'"),
+        })?;
+        Ok(hex::encode(json))
+    }
+}
+
+fn is_reverse_dns(rdns: &str) -> bool {
+    let labels: Vec<&str> = rdns.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
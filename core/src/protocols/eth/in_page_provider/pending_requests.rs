@@ -0,0 +1,215 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use tokio::task::JoinHandle;
+
+use crate::Error;
+
+/// In-page requests that the host can cancel by the request id it passed in, eg. when the user
+/// navigates away from the page, and the approval prompts that the user hasn't responded to
+/// yet. Kept in memory only.
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    inner: Mutex<PendingRequestsInner>,
+}
+
+#[derive(Debug, Default)]
+struct PendingRequestsInner {
+    /// Requests registered by the host mapped by their request id.
+    requests: HashMap<String, PendingRequest>,
+    /// Approval prompts waiting for the user to respond mapped by their approval id.
+    approvals: HashMap<u64, PendingApproval>,
+    /// Zero is never assigned, so that it can be the default on the FFI boundary.
+    last_approval_id: u64,
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    raw_request: String,
+    task: Option<JoinHandle<Result<(), Error>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingApproval {
+    /// The id of the request that the prompt is for if the host registered it.
+    pub request_id: Option<String>,
+}
+
+impl PendingRequests {
+    /// Register a request before it's processed.
+    pub fn register(&self, request_id: &str, raw_request: &str) -> Result<(), Error> {
+        let mut inner = self.inner.lock()?;
+        if inner.requests.contains_key(request_id) {
+            return Err(Error::Fatal {
+                error: format!("Duplicate in-page request id: '{request_id}'"),
+            });
+        }
+        inner.requests.insert(
+            request_id.into(),
+            PendingRequest {
+                raw_request: raw_request.into(),
+                task: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Set the task that processes the request, so that it can be aborted. The task is
+    /// detached if the request was completed in the meantime.
+    pub fn set_task(
+        &self,
+        request_id: &str,
+        task: JoinHandle<Result<(), Error>>,
+    ) -> Result<(), Error> {
+        let mut inner = self.inner.lock()?;
+        if let Some(request) = inner.requests.get_mut(request_id) {
+            request.task = Some(task);
+        }
+        Ok(())
+    }
+
+    /// Mark the request completed before responding to it. Returns false if a registered
+    /// request was cancelled or timed out, in which case it must not be responded to.
+    /// Requests that weren't registered can always be completed.
+    pub fn complete(&self, request_id: Option<&str>) -> Result<bool, Error> {
+        let request_id = match request_id {
+            Some(request_id) => request_id,
+            None => return Ok(true),
+        };
+        let mut inner = self.inner.lock()?;
+        Ok(inner.requests.remove(request_id).is_some())
+    }
+
+    /// Mark the request as waiting for the user to respond to an approval prompt. Returns the
+    /// id of the prompt that the response from the UI must carry.
+    pub fn await_approval(&self, request_id: Option<&str>) -> Result<u64, Error> {
+        let mut inner = self.inner.lock()?;
+        inner.last_approval_id += 1;
+        let approval_id = inner.last_approval_id;
+        inner.approvals.insert(
+            approval_id,
+            PendingApproval {
+                request_id: request_id.map(Into::into),
+            },
+        );
+        Ok(approval_id)
+    }
+
+    /// Take the approval prompt that the user responded to. Returns none if the prompt timed
+    /// out, its request was cancelled or the user responded to it already.
+    pub fn take_approval(
+        &self,
+        approval_id: u64,
+    ) -> Result<Option<PendingApproval>, Error> {
+        let mut inner = self.inner.lock()?;
+        Ok(inner.approvals.remove(&approval_id))
+    }
+
+    /// Time out an approval prompt that the user didn't respond to. Returns false if the user
+    /// responded or the request was cancelled. A late response from the UI is ignored.
+    pub fn time_out(&self, approval_id: u64) -> Result<bool, Error> {
+        let mut inner = self.inner.lock()?;
+        let approval = match inner.approvals.remove(&approval_id) {
+            Some(approval) => approval,
+            None => return Ok(false),
+        };
+        if let Some(request_id) = approval.request_id {
+            inner.requests.remove(&request_id);
+        }
+        Ok(true)
+    }
+
+    /// Cancel a pending request and abort its task. Returns the raw request to respond to with
+    /// an error or none if the request is unknown or it was completed already.
+    pub fn cancel(&self, request_id: &str) -> Result<Option<String>, Error> {
+        let mut inner = self.inner.lock()?;
+        let request = match inner.requests.remove(request_id) {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+        if let Some(task) = request.task {
+            task.abort();
+        }
+        // The request may be waiting for approval in the UI.
+        inner
+            .approvals
+            .retain(|_, approval| approval.request_id.as_deref() != Some(request_id));
+        Ok(Some(request.raw_request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn cancelled_request_cannot_complete() -> Result<()> {
+        let pending = PendingRequests::default();
+        pending.register("1", "raw-request")?;
+        let approval_id = pending.await_approval(Some("1"))?;
+
+        assert_eq!(pending.cancel("1")?, Some("raw-request".into()));
+        assert_eq!(pending.take_approval(approval_id)?, None);
+        assert!(!pending.complete(Some("1"))?);
+        assert_eq!(pending.cancel("1")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn completed_request_cannot_be_cancelled() -> Result<()> {
+        let pending = PendingRequests::default();
+        pending.register("1", "raw-request")?;
+
+        assert!(pending.complete(Some("1"))?);
+        assert_eq!(pending.cancel("1")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn timed_out_request_cannot_complete() -> Result<()> {
+        let pending = PendingRequests::default();
+        pending.register("1", "raw-request")?;
+        let first_prompt = pending.await_approval(Some("1"))?;
+        let second_prompt = pending.await_approval(Some("1"))?;
+
+        assert!(pending.take_approval(first_prompt)?.is_some());
+        assert!(!pending.time_out(first_prompt)?);
+        assert!(pending.time_out(second_prompt)?);
+        assert_eq!(pending.take_approval(second_prompt)?, None);
+        assert!(!pending.complete(Some("1"))?);
+        assert_eq!(pending.cancel("1")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unregistered_approval_times_out_once() -> Result<()> {
+        let pending = PendingRequests::default();
+        let approval_id = pending.await_approval(None)?;
+
+        assert_ne!(approval_id, 0);
+        assert!(pending.time_out(approval_id)?);
+        assert!(!pending.time_out(approval_id)?);
+        assert_eq!(pending.take_approval(approval_id)?, None);
+        assert!(pending.complete(None)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() -> Result<()> {
+        let pending = PendingRequests::default();
+        pending.register("1", "raw-request")?;
+
+        assert!(pending.register("1", "other-request").is_err());
+
+        Ok(())
+    }
+}
@@ -7,31 +7,66 @@ use k256::Secp256k1;
 use crate::signatures::AsymmetricKey;
 
 // Some names need to be be camel case in ankr for generated code.
+mod allowance;
 #[allow(non_snake_case)]
 pub mod ankr;
+mod block_number_cache;
+mod calldata;
 mod chain_id;
 mod chain_settings;
 mod checksum_address;
+mod contract_registry;
 mod contracts;
 pub mod explorer;
 pub mod in_page_provider;
+mod nft_listing;
 mod protocol_data;
+mod retry;
+mod rpc_check;
 mod rpc_provider;
+mod rpc_transport;
 mod signer;
 mod signing_key;
+mod signing_request;
 mod token;
+mod token_discovery;
+mod transaction_inspector;
+mod transaction_queue;
+mod typed_data_schema;
 
 pub type EthereumAsymmetricKey = AsymmetricKey<Secp256k1>;
+pub use allowance::{fetch_token_allowances, rank_allowances, Allowance, TokenAllowance};
+pub use block_number_cache::BlockNumberCache;
+pub use calldata::{
+    calldata_selector, DecodedCall, DecodedCallArg, SelectorRegistry, SELECTOR_REGISTRY,
+};
 pub use chain_id::ChainId;
 pub use chain_settings::ChainSettings;
 pub use checksum_address::ChecksumAddress;
+pub use contract_registry::{ContractRegistry, CONTRACT_REGISTRY};
+pub use nft_listing::{NftPage, NftQuery, NftSort};
 pub use protocol_data::ProtocolData;
+pub use rpc_check::{test_rpc_url, RpcTestResult};
 #[cfg(test)]
 pub use rpc_provider::anvil::AnvilRpcManager;
-pub use rpc_provider::{RpcManager, RpcManagerI, RpcProvider};
-pub use signer::Signer;
+pub use rpc_provider::{
+    parse_block_id, FeeEstimate, RpcManager, RpcManagerI, RpcProvider,
+};
+pub use signer::{verify_signature, SignatureKind, Signer};
 pub use signing_key::SigningKey;
+pub use signing_request::{SignedTransaction, SigningRequest};
 pub use token::{
-    FungibleTokenAmount, FungibleTokenBalance, NFTBalance, NativeTokenAmount,
-    TokenBalances,
+    display_currency_amount, CurrencyAmount, FungibleToken, FungibleTokenAmount,
+    FungibleTokenBalance, NFTBalance, NativeTokenAmount, TokenBalances,
+};
+pub use token_discovery::{discover_tokens, start_auto_discovery};
+pub use transaction_inspector::{
+    inspect_token_approvals, transaction_spends, CoreTransactionInspectorI,
+    DefaultTransactionInspector, HostTransactionInspector, InspectedTransaction,
+    TransactionInspectorI, TransactionSpend,
+};
+pub use transaction_queue::{
+    QueuedTransaction, QueuedTransactionGuard, QueuedTransactionStatus, TransactionQueue,
+    TransactionQueueKey,
 };
+pub use typed_data_schema::{TypedDataSchemaRegistry, TYPED_DATA_SCHEMA_REGISTRY};
@@ -0,0 +1,157 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use typed_builder::TypedBuilder;
+
+use crate::{
+    config,
+    protocols::eth::{ChecksumAddress, NFTBalance},
+};
+
+/// Sort order of NFT listings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NftSort {
+    /// The order returned by the indexer.
+    #[default]
+    Indexer,
+    /// By collection name then token name, case-insensitively.
+    CollectionName,
+}
+
+/// Filters and pagination for NFT listings. Pages are fetched from the indexer one at a time,
+/// and the filters and the sort order are applied within a page, so a filtered page may have
+/// fewer NFTs than the limit.
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct NftQuery {
+    #[builder(default)]
+    pub contract_address: Option<ChecksumAddress>,
+    /// Token standard, eg. `ERC721`. Case-insensitive.
+    #[builder(default)]
+    pub standard: Option<String>,
+    #[builder(default)]
+    pub sort: NftSort,
+    /// Opaque indexer cursor from the previous page. None for the first page.
+    #[builder(default)]
+    pub cursor: Option<String>,
+    /// Clamped between 1 and `config::MAX_NFT_PAGE_SIZE`.
+    #[builder(default = config::MAX_NFT_PAGE_SIZE)]
+    pub limit: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NftPage {
+    pub nfts: Vec<NFTBalance>,
+    /// Pass to the next query to get the next page. None if this is the last page.
+    pub next_cursor: Option<String>,
+}
+
+impl NftQuery {
+    /// The number of NFTs to fetch from the indexer.
+    pub fn page_size(&self) -> usize {
+        self.limit.clamp(1, config::MAX_NFT_PAGE_SIZE) as usize
+    }
+
+    /// Filter and sort a page of NFTs fetched from the indexer.
+    pub fn page(&self, nfts: Vec<NFTBalance>, next_cursor: Option<String>) -> NftPage {
+        let mut nfts: Vec<NFTBalance> =
+            nfts.into_iter().filter(|nft| self.matches(nft)).collect();
+        match self.sort {
+            NftSort::Indexer => (),
+            NftSort::CollectionName => nfts.sort_by_cached_key(|nft| {
+                (nft.collection_name.to_lowercase(), nft.name.to_lowercase())
+            }),
+        }
+
+        NftPage { nfts, next_cursor }
+    }
+
+    fn matches(&self, nft: &NFTBalance) -> bool {
+        let contract_matches = self
+            .contract_address
+            .map(|address| address == nft.contract_address)
+            .unwrap_or(true);
+        let standard_matches = self
+            .standard
+            .as_ref()
+            .map(|standard| standard.eq_ignore_ascii_case(&nft.standard))
+            .unwrap_or(true);
+        contract_matches && standard_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Address;
+
+    use super::*;
+    use crate::protocols::eth::ChainId;
+
+    fn mock_collection(
+        contract_address: ChecksumAddress,
+        collection_name: &str,
+        standard: &str,
+        size: usize,
+    ) -> Vec<NFTBalance> {
+        (0..size)
+            .map(|token_id| NFTBalance {
+                chain_id: ChainId::PolygonMainnet,
+                contract_address,
+                symbol: "TEST".into(),
+                collection_name: collection_name.into(),
+                name: format!("{collection_name} #{token_id:03}"),
+                token_id: token_id.to_string(),
+                standard: standard.into(),
+                image_url: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn filters_page_by_contract_address() {
+        let large: ChecksumAddress = Address::random().into();
+        let mut nfts = mock_collection(large, "Large", "ERC721", 3);
+        nfts.extend(mock_collection(
+            Address::random().into(),
+            "Other",
+            "ERC1155",
+            5,
+        ));
+
+        let query = NftQuery::builder()
+            .contract_address(Some(large))
+            .limit(50)
+            .build();
+        let page = query.page(nfts, Some("next".into()));
+
+        let token_ids: Vec<String> =
+            page.nfts.into_iter().map(|nft| nft.token_id).collect();
+        assert_eq!(token_ids, vec!["0", "1", "2"]);
+        assert_eq!(page.next_cursor.as_deref(), Some("next"));
+    }
+
+    #[test]
+    fn filters_by_standard_and_sorts_by_collection_name() {
+        let mut nfts = mock_collection(Address::random().into(), "b", "ERC1155", 2);
+        nfts.extend(mock_collection(Address::random().into(), "A", "ERC1155", 2));
+        nfts.extend(mock_collection(Address::random().into(), "c", "ERC721", 2));
+
+        let query = NftQuery::builder()
+            .standard(Some("erc1155".into()))
+            .sort(NftSort::CollectionName)
+            .build();
+        let page = query.page(nfts, None);
+
+        let names: Vec<String> = page.nfts.into_iter().map(|nft| nft.name).collect();
+        assert_eq!(names, vec!["A #000", "A #001", "b #000", "b #001"]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn clamps_page_size() {
+        let query = NftQuery::builder().limit(0).build();
+        assert_eq!(query.page_size(), 1);
+        let query = NftQuery::builder().limit(1000).build();
+        assert_eq!(query.page_size(), config::MAX_NFT_PAGE_SIZE as usize);
+    }
+}
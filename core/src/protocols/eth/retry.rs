@@ -0,0 +1,240 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{future::Future, time::Duration};
+
+use ethers::providers::ProviderError;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::config;
+
+lazy_static! {
+    // Errors that will fail the same way no matter how many times we retry.
+    static ref PERMANENT_ERROR_REGEX: Regex = Regex::new(
+        r"(?i)nonce too low|insufficient funds|underpriced|intrinsic gas|gas limit|execution reverted|invalid sender"
+    )
+    .expect("static is ok");
+    // Errors caused by a momentary issue with the node or the network.
+    static ref TRANSIENT_ERROR_REGEX: Regex = Regex::new(
+        r"(?i)time(d)? ?out|too many requests|\b429\b|rate limit|limit exceeded|temporarily unavailable|service unavailable|bad gateway|\b50[234]\b|connection (reset|refused|closed)|error sending request"
+    )
+    .expect("static is ok");
+    // The node already has the transaction in its mempool.
+    static ref ALREADY_KNOWN_REGEX: Regex =
+        Regex::new(r"(?i)already known|known transaction|already imported")
+            .expect("static is ok");
+}
+
+/// Exponential backoff parameters for retrying transient RPC errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// The backoff before the retry attempt (zero based). Doubles on each attempt up to the cap.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: config::RPC_MAX_RETRIES,
+            initial_backoff: config::RPC_INITIAL_BACKOFF,
+            max_backoff: config::RPC_MAX_BACKOFF,
+        }
+    }
+}
+
+/// Whether an RPC error is caused by a momentary issue (eg. timeout or rate limiting) and the
+/// request may succeed if retried.
+pub fn is_transient_error(error: &ProviderError) -> bool {
+    let message = error.to_string();
+    if PERMANENT_ERROR_REGEX.is_match(&message) {
+        return false;
+    }
+    match error {
+        ProviderError::HTTPError(err) => err.is_timeout() || err.is_connect(),
+        _ => TRANSIENT_ERROR_REGEX.is_match(&message),
+    }
+}
+
+/// Whether the node rejected a broadcast because it already has the transaction.
+pub fn is_already_known_error(error: &ProviderError) -> bool {
+    ALREADY_KNOWN_REGEX.is_match(&error.to_string())
+}
+
+/// Broadcast a transaction retrying on transient errors with exponential backoff.
+///
+/// A transient error doesn't mean that the node didn't accept the transaction (eg. the response
+/// may have timed out), so `is_accepted` is checked with the transaction hash before each retry
+/// to make sure we never broadcast a transaction twice.
+pub async fn broadcast_with_retry<S, SFut, C, CFut>(
+    policy: RetryPolicy,
+    mut send: S,
+    mut is_accepted: C,
+) -> Result<(), ProviderError>
+where
+    S: FnMut() -> SFut,
+    SFut: Future<Output = Result<(), ProviderError>>,
+    C: FnMut() -> CFut,
+    CFut: Future<Output = bool>,
+{
+    let mut attempt: u32 = 0;
+    loop {
+        match send().await {
+            Ok(()) => return Ok(()),
+            Err(err) if is_already_known_error(&err) => return Ok(()),
+            Err(err) => {
+                if attempt >= policy.max_retries || !is_transient_error(&err) {
+                    return Err(err);
+                }
+                log::warn!("Transient error broadcasting transaction: '{err}'");
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                if is_accepted().await {
+                    return Ok(());
+                }
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::async_runtime as rt;
+
+    fn test_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        }
+    }
+
+    fn transient_error() -> ProviderError {
+        ProviderError::CustomError("429 Too Many Requests".into())
+    }
+
+    fn permanent_error() -> ProviderError {
+        ProviderError::CustomError("nonce too low".into())
+    }
+
+    #[test]
+    fn classifies_errors() {
+        assert!(is_transient_error(&transient_error()));
+        assert!(is_transient_error(&ProviderError::CustomError(
+            "operation timed out".into()
+        )));
+        assert!(!is_transient_error(&permanent_error()));
+        assert!(!is_transient_error(&ProviderError::CustomError(
+            "insufficient funds for gas * price + value".into()
+        )));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(100), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn retries_transient_error() -> Result<()> {
+        let counter = AtomicU32::new(0);
+        let sends = &counter;
+
+        rt::block_on(broadcast_with_retry(
+            test_policy(),
+            move || async move {
+                if sends.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(transient_error())
+                } else {
+                    Ok(())
+                }
+            },
+            || async { false },
+        ))?;
+
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn does_not_retry_permanent_error() {
+        let counter = AtomicU32::new(0);
+        let sends = &counter;
+
+        let result = rt::block_on(broadcast_with_retry(
+            test_policy(),
+            move || async move {
+                sends.fetch_add(1, Ordering::SeqCst);
+                Err(permanent_error())
+            },
+            || async { false },
+        ));
+
+        assert!(result.is_err());
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn does_not_rebroadcast_accepted_transaction() -> Result<()> {
+        let counter = AtomicU32::new(0);
+        let sends = &counter;
+
+        rt::block_on(broadcast_with_retry(
+            test_policy(),
+            move || async move {
+                sends.fetch_add(1, Ordering::SeqCst);
+                Err(transient_error())
+            },
+            || async { true },
+        ))?;
+
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let counter = AtomicU32::new(0);
+        let sends = &counter;
+        let policy = test_policy();
+
+        let result = rt::block_on(broadcast_with_retry(
+            policy,
+            move || async move {
+                sends.fetch_add(1, Ordering::SeqCst);
+                Err(transient_error())
+            },
+            || async { false },
+        ));
+
+        assert!(result.is_err());
+        assert_eq!(counter.load(Ordering::SeqCst), policy.max_retries + 1);
+    }
+}
@@ -0,0 +1,204 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::{Duration, Instant};
+
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::{Address, BlockId, BlockNumber},
+};
+use url::Url;
+
+use crate::{http_client::bounded_client, Error};
+
+/// The result of checking an RPC endpoint before adding it as a custom network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcTestResult {
+    /// The chain id reported by the endpoint.
+    pub chain_id: u64,
+    /// The latest block number.
+    pub block_number: u64,
+    /// Round trip time of the `eth_chainId` request in milliseconds.
+    pub latency_ms: u64,
+    /// Whether the endpoint serves historical state.
+    pub archive: bool,
+}
+
+/// Check that the URL is a working Ethereum JSON-RPC endpoint.
+/// Fails with a user error if the endpoint doesn't respond within the timeout.
+pub async fn test_rpc_url(url: &str, timeout: Duration) -> Result<RpcTestResult, Error> {
+    let url = Url::parse(url.trim()).map_err(|err| Error::User {
+        explanation: format!("Invalid RPC URL: {err}"),
+    })?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::User {
+            explanation: "The RPC URL must be an http or https URL.".into(),
+        });
+    }
+
+    let provider = Provider::new(Http::new_with_client(url, bounded_client()));
+    tokio::time::timeout(timeout, test_rpc_provider(provider))
+        .await
+        .map_err(|_| Error::User {
+            explanation: "The RPC endpoint didn't respond in time.".into(),
+        })?
+}
+
+async fn test_rpc_provider(provider: Provider<Http>) -> Result<RpcTestResult, Error> {
+    let start = Instant::now();
+    let chain_id = provider.get_chainid().await.map_err(rpc_error)?;
+    let latency_ms = start.elapsed().as_millis().try_into().unwrap_or(u64::MAX);
+    let chain_id: u64 = chain_id.try_into().map_err(|_| Error::User {
+        explanation: format!("Invalid chain id from RPC endpoint: {chain_id}"),
+    })?;
+
+    let block_number = provider
+        .get_block_number()
+        .await
+        .map_err(rpc_error)?
+        .as_u64();
+
+    // Full nodes only keep the state of recent blocks, so querying the balance at the first
+    // block fails unless it's an archive node.
+    let historical_block =
+        BlockId::Number(BlockNumber::Number(block_number.min(1).into()));
+    let archive = provider
+        .get_balance(Address::zero(), Some(historical_block))
+        .await
+        .map_err(|err| log::debug!("Historical balance query failed: {err}"))
+        .is_ok();
+
+    Ok(RpcTestResult {
+        chain_id,
+        block_number,
+        latency_ms,
+        archive,
+    })
+}
+
+fn rpc_error(err: ethers::providers::ProviderError) -> Error {
+    log::debug!("RPC URL test failed: {err}");
+    Error::User {
+        explanation: "The RPC endpoint returned an invalid response.".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use anyhow::Result;
+    use serde_json::json;
+
+    use super::*;
+    use crate::async_runtime as rt;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Serve JSON-RPC requests on a local port with a fixed chain id and block number.
+    /// Historical balance queries fail unless `archive` is set.
+    fn serve_mock_rpc(archive: bool) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = respond(stream, archive);
+            }
+        });
+        Ok(url)
+    }
+
+    fn respond(stream: TcpStream, archive: bool) -> Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            if line == "\r\n" || line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse()?;
+                }
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body)?;
+        let request: serde_json::Value = serde_json::from_slice(&body)?;
+
+        let id = request["id"].clone();
+        let response = match request["method"].as_str() {
+            Some("eth_chainId") => json!({"jsonrpc": "2.0", "id": id, "result": "0x89"}),
+            Some("eth_blockNumber") => {
+                json!({"jsonrpc": "2.0", "id": id, "result": "0x3e8"})
+            }
+            Some("eth_getBalance") if archive => {
+                json!({"jsonrpc": "2.0", "id": id, "result": "0x0"})
+            }
+            _ => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": -32000, "message": "missing trie node"}
+            }),
+        };
+        let response = serde_json::to_vec(&response)?;
+
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+            Connection: close\r\n\r\n",
+            response.len()
+        )?;
+        stream.write_all(&response)?;
+        Ok(())
+    }
+
+    #[test]
+    fn detects_chain_and_archive_node() -> Result<()> {
+        let url = serve_mock_rpc(true)?;
+
+        let result = rt::block_on(test_rpc_url(&url, TIMEOUT))?;
+
+        assert_eq!(result.chain_id, 137);
+        assert_eq!(result.block_number, 1000);
+        assert!(result.archive);
+        Ok(())
+    }
+
+    #[test]
+    fn detects_full_node() -> Result<()> {
+        let url = serve_mock_rpc(false)?;
+
+        let result = rt::block_on(test_rpc_url(&url, TIMEOUT))?;
+
+        assert_eq!(result.chain_id, 137);
+        assert!(!result.archive);
+        Ok(())
+    }
+
+    #[test]
+    fn unresponsive_endpoint_times_out() -> Result<()> {
+        // Accepts connections, but never responds.
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+
+        let res = rt::block_on(test_rpc_url(&url, Duration::from_millis(200)));
+
+        assert!(matches!(res, Err(Error::User { .. })));
+        drop(listener);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_non_http_urls() {
+        let res = rt::block_on(test_rpc_url("ws://localhost:8545", TIMEOUT));
+        assert!(matches!(res, Err(Error::User { .. })));
+    }
+}
@@ -3,36 +3,80 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::{
+    collections::VecDeque,
     fmt::{Debug, Formatter},
     sync::Arc,
 };
 
 use ethers::{
-    core::types::{BlockNumber, TransactionRequest, H256},
-    providers::{Http, Middleware, PendingTransaction, Provider},
-    types::BlockId,
+    contract::EthEvent,
+    core::types::{BlockNumber, Bytes, TransactionRequest, H256, U256},
+    providers::{Middleware, PendingTransaction, Provider},
+    types::{BlockId, Filter, Log},
 };
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::Serialize;
 use url::Url;
 
 use crate::{
-    async_runtime as rt,
+    async_runtime as rt, config,
     protocols::eth::{
-        contracts::ERC20Contract, signer::SignerMiddleware, token::FungibleToken,
+        contracts::{ERC20Contract, TransferFilter},
+        rpc_transport::RpcTransport,
+        signer::SignerMiddleware,
+        token::FungibleToken,
         ChainId, ChecksumAddress, FungibleTokenAmount, NativeTokenAmount, SigningKey,
+        TransactionQueue, TransactionQueueKey,
     },
     Error,
 };
 
+/// The storage slot of the implementation address of EIP-1967 proxies:
+/// `bytes32(uint256(keccak256('eip1967.proxy.implementation')) - 1)`
+/// https://eips.ethereum.org/EIPS/eip-1967
+const EIP_1967_IMPLEMENTATION_SLOT: [u8; 32] = [
+    0x36, 0x08, 0x94, 0xa1, 0x3b, 0xa1, 0xa3, 0x21, 0x06, 0x67, 0xc8, 0x28, 0x49, 0x2d,
+    0xb9, 0x8d, 0xca, 0x3e, 0x20, 0x76, 0xcc, 0x37, 0x35, 0xa9, 0x20, 0xa3, 0xca, 0x50,
+    0x5d, 0x38, 0x2b, 0xbc,
+];
+
+/// The estimated gas limit and gas price of a transaction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FeeEstimate {
+    pub gas: U256,
+    pub gas_price: U256,
+}
+
+impl FeeEstimate {
+    /// The fee in the native token.
+    pub fn fee(&self) -> U256 {
+        self.gas.saturating_mul(self.gas_price)
+    }
+
+    /// Set the gas limit and the gas price of the transaction unless they're set already, so
+    /// that they aren't estimated again when the transaction is sent.
+    pub fn apply(&self, tx: &mut TransactionRequest) {
+        tx.gas.get_or_insert(self.gas);
+        tx.gas_price.get_or_insert(self.gas_price);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RpcProvider {
-    pub(super) provider: Provider<Http>,
+    pub(super) provider: Provider<RpcTransport>,
     pub(super) chain_id: ChainId,
 }
 
 impl RpcProvider {
     pub fn new(chain_id: ChainId, http_endpoint: Url) -> Self {
-        let provider = Provider::new(Http::new(http_endpoint));
+        let provider = Provider::new(RpcTransport::new(http_endpoint));
+        Self { provider, chain_id }
+    }
+
+    /// Log requests to the endpoint with secrets redacted at debug level.
+    pub fn new_with_logging(chain_id: ChainId, http_endpoint: Url) -> Self {
+        let provider = Provider::new(RpcTransport::new_with_logging(http_endpoint));
         Self { provider, chain_id }
     }
 
@@ -83,6 +127,134 @@ impl RpcProvider {
         Ok(pending_tx.tx_hash())
     }
 
+    /// Broadcast an RLP encoded transaction that was signed elsewhere.
+    pub async fn send_raw_transaction_async(
+        &self,
+        raw_transaction: Bytes,
+    ) -> Result<H256, Error> {
+        let pending_tx = self.provider.send_raw_transaction(raw_transaction).await?;
+        Ok(pending_tx.tx_hash())
+    }
+
+    /// Send a transaction through the transaction queue of the sender. The queue assigns the
+    /// nonce and the transaction is sent once the lower nonce transactions have been
+    /// submitted. The transaction is marked as submitted or failed in the queue, the caller
+    /// must mark it as confirmed.
+    /// The value and the estimated fee count towards the pending outflow of the sender.
+    /// Returns the nonce and the transaction hash.
+    pub async fn send_queued_transaction_async(
+        &self,
+        queue: &TransactionQueue,
+        key: &TransactionQueueKey,
+        signing_key: &SigningKey,
+        mut tx: TransactionRequest,
+    ) -> Result<(U256, H256), Error> {
+        let pending_nonce = self.pending_nonce_async(signing_key.address).await?;
+        // The fee estimate is only needed for the pending balance, so it's not worth failing
+        // the transaction over. The signer estimates the gas if it's not set.
+        let fee = match self.estimate_fee_async(&tx).await {
+            Ok(estimate) => {
+                estimate.apply(&mut tx);
+                estimate.fee()
+            }
+            Err(err) => {
+                log::warn!("Failed to estimate transaction fee due to error: {err:?}");
+                U256::zero()
+            }
+        };
+        let outflow = tx.value.unwrap_or_default().saturating_add(fee);
+        let nonce = queue.enqueue(key, pending_nonce, None, outflow)?;
+        // The task may be aborted while waiting.
+        let _queue_guard = queue.guard(key, nonce);
+        tx.nonce = Some(nonce);
+        queue.wait_for_turn(key, nonce).await?;
+
+        let tx_hash = self.send_transaction_async(signing_key, tx).await;
+        let queue_res = match tx_hash.as_ref() {
+            Ok(tx_hash) => queue.mark_submitted(key, nonce, *tx_hash),
+            Err(err) => queue.mark_failed(key, nonce, err.to_string()),
+        };
+        if let Err(err) = queue_res {
+            log::error!("Failed to update transaction queue due to error: {err}");
+        }
+        Ok((nonce, tx_hash?))
+    }
+
+    /// Estimate the gas limit and the gas price of a transaction. Uses the gas limit and the
+    /// gas price of the transaction if they're set.
+    pub async fn estimate_fee_async(
+        &self,
+        tx: &TransactionRequest,
+    ) -> Result<FeeEstimate, Error> {
+        let gas = match tx.gas {
+            Some(gas) => gas,
+            None => {
+                self.provider
+                    .estimate_gas(&tx.clone().into(), Some(BlockNumber::Latest.into()))
+                    .await?
+            }
+        };
+        let gas_price = match tx.gas_price {
+            Some(gas_price) => gas_price,
+            None => self.provider.get_gas_price().await?,
+        };
+        Ok(FeeEstimate { gas, gas_price })
+    }
+
+    /// The nonce for the next transaction from the address including pending transactions.
+    pub async fn pending_nonce_async(
+        &self,
+        address: ChecksumAddress,
+    ) -> Result<U256, Error> {
+        let nonce = self
+            .provider
+            .get_transaction_count(
+                address.to_address(),
+                Some(BlockNumber::Pending.into()),
+            )
+            .await?;
+        Ok(nonce)
+    }
+
+    /// Whether there is contract code deployed at the address.
+    /// Returns false for externally owned accounts and self-destructed contracts.
+    pub async fn has_code_async(&self, address: ChecksumAddress) -> Result<bool, Error> {
+        let code = self
+            .provider
+            .get_code(address.to_address(), Some(BlockNumber::Latest.into()))
+            .await?;
+        Ok(!code.is_empty())
+    }
+
+    /// The current implementation address if the contract is an EIP-1967 proxy. Returns none
+    /// if the implementation slot is empty, ie. the contract is not a proxy.
+    pub async fn proxy_implementation_async(
+        &self,
+        address: ChecksumAddress,
+    ) -> Result<Option<ChecksumAddress>, Error> {
+        let slot = H256::from(EIP_1967_IMPLEMENTATION_SLOT);
+        let value = self
+            .provider
+            .get_storage_at(address.to_address(), slot, Some(BlockNumber::Latest.into()))
+            .await?;
+        if value.is_zero() {
+            return Ok(None);
+        }
+        // The address is stored in the lower 20 bytes of the slot.
+        let implementation = ethers::types::Address::from(value);
+        Ok(Some(implementation.into()))
+    }
+
+    /// Fetch the latest block number.
+    pub fn block_number(&self) -> Result<u64, Error> {
+        rt::block_on(self.block_number_async())
+    }
+
+    pub async fn block_number_async(&self) -> Result<u64, Error> {
+        let block_number = self.provider.get_block_number().await?;
+        Ok(block_number.as_u64())
+    }
+
     fn verify_chain_ids(
         &self,
         signing_key: &SigningKey,
@@ -122,6 +294,18 @@ impl RpcProvider {
         to_address: ChecksumAddress,
         amount: &NativeTokenAmount,
     ) -> Result<H256, Error> {
+        let tx = self.native_token_transfer_tx(signing_key, to_address, amount)?;
+        let tx_hash = self.send_transaction_async(signing_key, tx).await?;
+        Ok(tx_hash)
+    }
+
+    /// Build a native token transfer transaction from the signing key's address.
+    pub fn native_token_transfer_tx(
+        &self,
+        signing_key: &SigningKey,
+        to_address: ChecksumAddress,
+        amount: &NativeTokenAmount,
+    ) -> Result<TransactionRequest, Error> {
         self.verify_chain_ids(signing_key, amount.chain_id)?;
 
         // TODO use EIP-1559 once we can get reliable `max_priority_fee_per_gas` estimates on all
@@ -130,10 +314,7 @@ impl RpcProvider {
             .to(to_address.to_address())
             .value(amount.amount)
             .from(signing_key.address.to_address());
-
-        let tx_hash = self.send_transaction_async(signing_key, tx).await?;
-
-        Ok(tx_hash)
+        Ok(tx)
     }
 
     pub fn transfer_fungible_token(
@@ -159,14 +340,30 @@ impl RpcProvider {
         amount_decimal: &str,
         contract_address: ChecksumAddress,
     ) -> Result<H256, Error> {
-        let signer = Arc::new(SignerMiddleware::new(&self.provider, signing_key));
-        let contract = ERC20Contract::new(contract_address, signer);
+        let tx = self
+            .fungible_token_transfer_tx_async(
+                signing_key,
+                to_address,
+                amount_decimal,
+                contract_address,
+            )
+            .await?;
+        let tx_hash = self.send_transaction_async(signing_key, tx).await?;
+        Ok(tx_hash)
+    }
 
-        let contract_call = contract.decimals();
-        let decimals: u8 =
-            contract_call.call().await.map_err(|err| Error::Retriable {
-                error: err.to_string(),
-            })?;
+    /// Build a fungible token transfer transaction from the signing key's address.
+    pub async fn fungible_token_transfer_tx_async(
+        &self,
+        signing_key: &SigningKey,
+        to_address: ChecksumAddress,
+        amount_decimal: &str,
+        contract_address: ChecksumAddress,
+    ) -> Result<TransactionRequest, Error> {
+        let provider = Arc::new(self.provider.clone());
+        let contract = ERC20Contract::new(contract_address, provider);
+
+        let decimals = self.fungible_token_decimals_async(contract_address).await?;
         let fungible_token =
             FungibleToken::new(self.chain_id, contract_address, decimals);
         let fungible_token_amount =
@@ -174,10 +371,11 @@ impl RpcProvider {
 
         let contract_call =
             contract.transfer(to_address.to_address(), fungible_token_amount.amount);
-        let pending_tx = contract_call.send().await.map_err(|err| Error::Retriable {
-            error: err.to_string(),
-        })?;
-        Ok(pending_tx.tx_hash())
+        let tx = TransactionRequest::new()
+            .to(contract_address.to_address())
+            .data(contract_call.calldata().unwrap_or_default())
+            .from(signing_key.address.to_address());
+        Ok(tx)
     }
 
     pub fn fungible_token_symbol(
@@ -202,6 +400,137 @@ impl RpcProvider {
         Ok(symbol)
     }
 
+    pub fn fungible_token_decimals(
+        &self,
+        contract_address: ChecksumAddress,
+    ) -> Result<u8, Error> {
+        rt::block_on(self.fungible_token_decimals_async(contract_address))
+    }
+
+    pub async fn fungible_token_decimals_async(
+        &self,
+        contract_address: ChecksumAddress,
+    ) -> Result<u8, Error> {
+        let provider = Arc::new(self.provider.clone());
+        let contract = ERC20Contract::new(contract_address, provider);
+
+        let contract_call = contract.decimals();
+        let decimals: u8 =
+            contract_call.call().await.map_err(|err| Error::Retriable {
+                error: err.to_string(),
+            })?;
+        Ok(decimals)
+    }
+
+    /// The spenders that the owner has ever approved on a fungible token contract based on
+    /// the `Approval` event logs. The approvals may have been revoked or spent since.
+    ///
+    /// RPC providers limit the block range or the number of results of `eth_getLogs`, so a
+    /// range that the provider rejects is split in half and retried down to
+    /// `config::GET_LOGS_MIN_BLOCK_RANGE` blocks. Fails after
+    /// `config::GET_LOGS_MAX_REQUESTS` requests.
+    pub async fn fungible_token_approval_spenders_async(
+        &self,
+        contract_address: ChecksumAddress,
+        owner: ChecksumAddress,
+    ) -> Result<Vec<ChecksumAddress>, Error> {
+        let provider = Arc::new(self.provider.clone());
+        let contract = ERC20Contract::new(contract_address, provider);
+        let latest = self.block_number_async().await?;
+
+        let mut results: Vec<ChecksumAddress> = Default::default();
+        // Processed in block order.
+        let mut ranges = VecDeque::from([(0, latest)]);
+        let mut requests = 0;
+        while let Some((from_block, to_block)) = ranges.pop_front() {
+            if requests == config::GET_LOGS_MAX_REQUESTS {
+                return Err(Error::Retriable {
+                    error: "Too many requests to fetch approval logs".into(),
+                });
+            }
+            requests += 1;
+            let event = contract
+                .approval_filter()
+                .topic1(H256::from(owner.to_address()))
+                .from_block(from_block)
+                .to_block(to_block);
+            let logs = match event.query().await {
+                Ok(logs) => logs,
+                Err(err) => match split_block_range(from_block, to_block) {
+                    Some((first, second)) => {
+                        ranges.push_front(second);
+                        ranges.push_front(first);
+                        continue;
+                    }
+                    None => {
+                        return Err(Error::Retriable {
+                            error: format!(
+                                "Failed to fetch approval logs in blocks \
+                                {from_block}-{to_block} with error: {err}"
+                            ),
+                        })
+                    }
+                },
+            };
+            for log in logs {
+                let spender: ChecksumAddress = log.spender.into();
+                if !results.contains(&spender) {
+                    results.push(spender)
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// The `Transfer` event logs from and to an address in a block range on any contract.
+    /// ERC-721 transfers have the same event signature, so the results may include them.
+    pub async fn transfer_logs_async(
+        &self,
+        address: ChecksumAddress,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Log>, Error> {
+        let filter = Filter::new()
+            .topic0(TransferFilter::signature())
+            .from_block(from_block)
+            .to_block(to_block);
+        let address = H256::from(address.to_address());
+        let sent = filter.clone().topic1(address);
+        let received = filter.topic2(address);
+
+        let mut logs = self.get_logs_async(&sent).await?;
+        logs.extend(self.get_logs_async(&received).await?);
+        Ok(logs)
+    }
+
+    async fn get_logs_async(&self, filter: &Filter) -> Result<Vec<Log>, Error> {
+        self.provider
+            .get_logs(filter)
+            .await
+            .map_err(|err| Error::Retriable {
+                error: err.to_string(),
+            })
+    }
+
+    /// The amount of fungible tokens that the spender may currently move on behalf of the
+    /// owner in the lowest denomination of the token.
+    pub async fn fungible_token_allowance_async(
+        &self,
+        contract_address: ChecksumAddress,
+        owner: ChecksumAddress,
+        spender: ChecksumAddress,
+    ) -> Result<U256, Error> {
+        let provider = Arc::new(self.provider.clone());
+        let contract = ERC20Contract::new(contract_address, provider);
+
+        let contract_call = contract.allowance(owner.to_address(), spender.to_address());
+        let allowance: U256 =
+            contract_call.call().await.map_err(|err| Error::Retriable {
+                error: err.to_string(),
+            })?;
+        Ok(allowance)
+    }
+
     /// Fetch the native token balance for an address.
     pub fn native_token_balance(
         &self,
@@ -214,11 +543,29 @@ impl RpcProvider {
         &self,
         address: ChecksumAddress,
     ) -> Result<NativeTokenAmount, Error> {
-        let block_id: Option<BlockId> = Some(BlockNumber::Latest.into());
+        self.native_token_balance_at_async(address, BlockNumber::Latest.into())
+            .await
+    }
+
+    /// Fetch the native token balance for an address at a block.
+    pub fn native_token_balance_at(
+        &self,
+        address: ChecksumAddress,
+        block_id: BlockId,
+    ) -> Result<NativeTokenAmount, Error> {
+        rt::block_on(self.native_token_balance_at_async(address, block_id))
+    }
+
+    pub async fn native_token_balance_at_async(
+        &self,
+        address: ChecksumAddress,
+        block_id: BlockId,
+    ) -> Result<NativeTokenAmount, Error> {
         let balance = self
             .provider
-            .get_balance(address.to_address(), block_id)
-            .await?;
+            .get_balance(address.to_address(), Some(block_id))
+            .await
+            .map_err(|err| missing_state_error(err.into()))?;
         let amount = NativeTokenAmount::new(self.chain_id, balance);
         Ok(amount)
     }
@@ -245,11 +592,18 @@ pub trait RpcManagerI: Debug + Send + Sync {
     fn eth_api_provider(&self, chain_id: ChainId) -> RpcProvider;
 }
 
-pub struct RpcManager {}
+pub struct RpcManager {
+    log_rpc: bool,
+}
 
 impl RpcManager {
     pub fn new() -> Self {
-        Self {}
+        Self { log_rpc: false }
+    }
+
+    /// Log RPC requests with secrets redacted at debug level if `log_rpc` is true.
+    pub fn new_with_logging(log_rpc: bool) -> Self {
+        Self { log_rpc }
     }
 }
 
@@ -261,14 +615,76 @@ impl Default for RpcManager {
 
 impl Debug for RpcManager {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("RpcManagerImpl").finish()
+        f.debug_struct("RpcManagerImpl")
+            .field("log_rpc", &self.log_rpc)
+            .finish()
     }
 }
 
 impl RpcManagerI for RpcManager {
     fn eth_api_provider(&self, chain_id: ChainId) -> RpcProvider {
         let http_endpoint = chain_id.http_rpc_endpoint();
-        RpcProvider::new(chain_id, http_endpoint)
+        if self.log_rpc {
+            RpcProvider::new_with_logging(chain_id, http_endpoint)
+        } else {
+            RpcProvider::new(chain_id, http_endpoint)
+        }
+    }
+}
+
+/// Parse a block tag (`latest`, `pending`, `safe`, `finalized` or `earliest`) or a block number
+/// in decimal or `0x` prefixed hex format.
+pub fn parse_block_id(block: &str) -> Result<BlockId, Error> {
+    let block_number = match block {
+        "latest" => BlockNumber::Latest,
+        "pending" => BlockNumber::Pending,
+        "safe" => BlockNumber::Safe,
+        "finalized" => BlockNumber::Finalized,
+        "earliest" => BlockNumber::Earliest,
+        number => {
+            let parsed = match number.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => number.parse::<u64>(),
+            };
+            let number = parsed.map_err(|_| Error::User {
+                explanation: format!("Invalid block tag or number: '{block}'"),
+            })?;
+            BlockNumber::Number(number.into())
+        }
+    };
+    Ok(block_number.into())
+}
+
+lazy_static! {
+    // Errors returned by common node implementations when they don't have the state for a block.
+    static ref MISSING_STATE_REGEX: Regex = Regex::new(
+        r"(?i)missing trie node|header not found|state.*not available|pruned|archive"
+    )
+    .expect("static is ok");
+}
+
+/// Split an inclusive block range in half. None if the range is already at the minimum size.
+fn split_block_range(from_block: u64, to_block: u64) -> Option<((u64, u64), (u64, u64))> {
+    if to_block.saturating_sub(from_block) < config::GET_LOGS_MIN_BLOCK_RANGE {
+        return None;
+    }
+    let middle = from_block + (to_block - from_block) / 2;
+    Some(((from_block, middle), (middle + 1, to_block)))
+}
+
+/// Non-archive nodes only keep the state for recent blocks, so queries for historical blocks may
+/// fail. Turn these errors into an explanation that can be presented to the user.
+fn missing_state_error(error: Error) -> Error {
+    match error {
+        Error::JsonRpc { message, .. } if MISSING_STATE_REGEX.is_match(&message) => {
+            Error::User {
+                explanation:
+                    "The state for the requested block is not available on this \
+                    node. Historical queries require an archive node."
+                        .into(),
+            }
+        }
+        error => error,
     }
 }
 
@@ -347,6 +763,11 @@ pub mod anvil {
             Url::parse(&endpoint).expect("valid url")
         }
 
+        /// Whether an RPC provider has been requested, ie. Anvil was started.
+        pub fn is_started(&self) -> bool {
+            self.anvil_instance.read().unwrap().is_some()
+        }
+
         pub fn wallet(&self) -> LocalWallet {
             MnemonicBuilder::<English>::default()
                 .phrase(MNEMONIC)
@@ -372,6 +793,23 @@ pub mod anvil {
                 .expect("send_transaction ok");
             rt::block_on(pending_tx).expect("pending tx ok");
         }
+
+        /// Make the contract look like an EIP-1967 proxy of the implementation.
+        pub fn set_proxy_implementation(
+            &self,
+            chain_id: ChainId,
+            proxy_address: ChecksumAddress,
+            implementation_address: ChecksumAddress,
+        ) {
+            let provider = self.eth_api_provider(chain_id);
+            let slot = H256::from(EIP_1967_IMPLEMENTATION_SLOT);
+            let value = H256::from(implementation_address.to_address());
+            let _: bool = rt::block_on(provider.provider.request(
+                "anvil_setStorageAt",
+                (proxy_address.to_address(), slot, value),
+            ))
+            .expect("anvil_setStorageAt ok");
+        }
     }
 
     impl Default for AnvilRpcManager {
@@ -429,6 +867,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn splits_block_ranges_to_minimum() {
+        assert_eq!(
+            split_block_range(0, 99_999),
+            Some(((0, 49_999), (50_000, 99_999)))
+        );
+        assert_eq!(
+            split_block_range(10, 20_000),
+            Some(((10, 10_005), (10_006, 20_000)))
+        );
+        let min_range = config::GET_LOGS_MIN_BLOCK_RANGE;
+        assert_eq!(split_block_range(5, 5 + min_range - 1), None);
+    }
+
     #[test]
     fn native_token_balance() -> Result<()> {
         let rpc_manager = AnvilRpcManager::new();
@@ -443,6 +895,78 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn native_token_balance_pending() -> Result<()> {
+        let rpc_manager = AnvilRpcManager::new();
+        let chain_id = ChainId::EthMainnet;
+        let provider = rpc_manager.eth_api_provider(chain_id);
+        let accounts = rt::block_on(provider.provider.get_accounts())?;
+
+        let block_id = parse_block_id("pending")?;
+        let balance = provider.native_token_balance_at(accounts[0].into(), block_id)?;
+        assert_eq!(balance.display_amount(), "10000");
+
+        Ok(())
+    }
+
+    #[test]
+    fn native_token_balance_at_historical_block() -> Result<()> {
+        let rpc_manager = AnvilRpcManager::new();
+        let chain_id = ChainId::EthMainnet;
+        let provider = rpc_manager.eth_api_provider(chain_id);
+
+        let address: ChecksumAddress = Address::random().into();
+        rpc_manager.send_native_token(chain_id, address, 2);
+
+        let latest =
+            provider.native_token_balance_at(address, parse_block_id("latest")?)?;
+        assert_eq!(latest.display_amount(), "2");
+        let genesis =
+            provider.native_token_balance_at(address, parse_block_id("0x0")?)?;
+        assert_eq!(genesis.display_amount(), "0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_block_ids() -> Result<()> {
+        assert_eq!(parse_block_id("latest")?, BlockNumber::Latest.into());
+        assert_eq!(parse_block_id("pending")?, BlockNumber::Pending.into());
+        assert_eq!(parse_block_id("finalized")?, BlockNumber::Finalized.into());
+        assert_eq!(
+            parse_block_id("123")?,
+            BlockNumber::Number(123.into()).into()
+        );
+        assert_eq!(
+            parse_block_id("0x7b")?,
+            BlockNumber::Number(123.into()).into()
+        );
+
+        for invalid in ["", "0x", "Latest", "-1", "0xzz", "foo"] {
+            assert!(matches!(parse_block_id(invalid), Err(Error::User { .. })));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn maps_missing_state_error() {
+        let error = Error::JsonRpc {
+            code: (-32000).into(),
+            message: "missing trie node 1a2b (path )".into(),
+        };
+        assert!(matches!(
+            missing_state_error(error),
+            Error::User { explanation } if explanation.contains("archive")
+        ));
+
+        let error = Error::JsonRpc {
+            code: (-32000).into(),
+            message: "insufficient funds".into(),
+        };
+        assert!(matches!(missing_state_error(error), Error::JsonRpc { .. }));
+    }
+
     #[test]
     fn sends_native_token() -> Result<()> {
         let chain_id = ChainId::EthMainnet;
@@ -484,6 +1008,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn has_code() -> Result<()> {
+        let chain_id = ChainId::EthMainnet;
+        let contract_deployer = TestContractDeployer::init(chain_id);
+        let contract_address = contract_deployer.deploy_fungible_token_test_contract()?;
+        let provider = &contract_deployer.rpc_provider;
+
+        assert!(rt::block_on(provider.has_code_async(contract_address))?);
+        let empty: ChecksumAddress = Address::random().into();
+        assert!(!rt::block_on(provider.has_code_async(empty))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_proxy_implementation() -> Result<()> {
+        let chain_id = ChainId::EthMainnet;
+        let contract_deployer = TestContractDeployer::init(chain_id);
+        let not_proxy = contract_deployer.deploy_fungible_token_test_contract()?;
+        let proxy = contract_deployer.deploy_fungible_token_test_contract()?;
+        let implementation: ChecksumAddress = Address::random().into();
+        contract_deployer.anvil_rpc.set_proxy_implementation(
+            chain_id,
+            proxy,
+            implementation,
+        );
+        let provider = &contract_deployer.rpc_provider;
+
+        assert_eq!(
+            rt::block_on(provider.proxy_implementation_async(not_proxy))?,
+            None
+        );
+        assert_eq!(
+            rt::block_on(provider.proxy_implementation_async(proxy))?,
+            Some(implementation)
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn sends_fungible_token() -> Result<()> {
         // Deploy ERC20 test contract on Anvil dev node
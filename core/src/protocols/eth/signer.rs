@@ -3,17 +3,20 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 // Based on: https://github.com/gakonst/ethers-rs/blob/239f559ca04b296a1b4cd1fc7588f29b125be565/ethers-middleware/src/signer.rs
-use std::{convert::From, fmt};
+use std::{convert::From, fmt, str::FromStr};
 
 use async_trait::async_trait;
 use ethers::{
-    core::types::{
-        transaction::{eip2718::TypedTransaction, eip2930::AccessListWithGasUsed},
-        Address, BlockId, Bytes, Signature as EthereumSignature, U256, U64,
+    core::{
+        types::{
+            transaction::{eip2718::TypedTransaction, eip2930::AccessListWithGasUsed},
+            Address, BlockId, Bytes, RecoveryMessage, Signature as EthereumSignature,
+            H256, U256, U64,
+        },
+        utils::keccak256,
     },
     providers::{
-        maybe, Http, Middleware, MiddlewareError, PendingTransaction, Provider,
-        ProviderError,
+        maybe, Middleware, MiddlewareError, PendingTransaction, Provider, ProviderError,
     },
     types::transaction::eip712::{Eip712, TypedData},
 };
@@ -21,7 +24,13 @@ use k256::{FieldBytes, Secp256k1};
 use sha3::{Digest, Keccak256};
 
 use crate::{
-    protocols::eth::{chain_id::ChainId, signing_key::SigningKey, EthereumAsymmetricKey},
+    protocols::eth::{
+        chain_id::ChainId,
+        retry::{broadcast_with_retry, RetryPolicy},
+        rpc_transport::RpcTransport,
+        signing_key::SigningKey,
+        EthereumAsymmetricKey,
+    },
     signatures::RecoverableSignature,
     Error,
 };
@@ -97,6 +106,18 @@ impl<'a> Signer<'a> {
         Ok(self.sign_tx(tx)?.into())
     }
 
+    /// Sign a transaction for broadcasting. Returns the RLP encoded signed transaction and its
+    /// hash.
+    pub fn sign_raw_transaction(
+        &self,
+        tx: &TypedTransaction,
+    ) -> Result<(Bytes, H256), Error> {
+        let sig: EthereumSignature = self.sign_for_on_chain(tx)?.into();
+        let signed_tx = tx.rlp_signed(&sig);
+        let tx_hash = H256::from(keccak256(&signed_tx));
+        Ok((signed_tx, tx_hash))
+    }
+
     const PERSONAL_SIGN_PREFIX: &'static str = "\x19Ethereum Signed Message:\n";
 
     fn personal_sign_message<T: AsRef<[u8]>>(data: T) -> Vec<u8> {
@@ -122,6 +143,11 @@ impl<'a> Signer<'a> {
         &self,
         typed_data: &TypedData,
     ) -> Result<OffChainEthereumSignature, Error> {
+        let payload = Self::typed_data_payload(typed_data)?;
+        Ok(self.hazmat_sign_bytes(&payload)?.into())
+    }
+
+    fn typed_data_payload(typed_data: &TypedData) -> Result<Vec<u8>, Error> {
         // Payload encoding based on https://github.com/gakonst/ethers-rs/blob/64b7f1ef1ac71fefcacc44d8ff1ddfcb8e6b5417/ethers-core/src/types/transaction/eip712.rs#L555
         // We are not using `TypedData::encode_eip712`, because it returns the hash of the payload,
         // but we need the payload itself to pass to the digest function.
@@ -143,18 +169,71 @@ impl<'a> Signer<'a> {
             payload.extend(&hash)
         }
 
-        Ok(self.hazmat_sign_bytes(&payload)?.into())
+        Ok(payload)
     }
 }
 
+/// The standard that an off-chain signature was produced with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// [EIP-191](https://eips.ethereum.org/EIPS/eip-191) `personal_sign`
+    PersonalSign,
+    /// [EIP-712](https://eips.ethereum.org/EIPS/eip-712) typed data
+    TypedData,
+}
+
+/// Verify that an off-chain signature recovers to the expected address.
+/// For personal sign, a `0x` prefixed hex message is treated as bytes and any other message as
+/// UTF-8 text like MetaMask does. For typed data, the message is the typed data JSON.
+/// Returns an error if the signature, the message or the address is malformed.
+pub fn verify_signature(
+    kind: SignatureKind,
+    message: &str,
+    signature: &str,
+    expected_address: &str,
+) -> Result<bool, Error> {
+    let expected_address =
+        Address::from_str(expected_address).map_err(|_| Error::User {
+            explanation: format!("Invalid address: '{expected_address}'"),
+        })?;
+    let signature = EthereumSignature::from_str(signature).map_err(|_| Error::User {
+        explanation: "The signature is malformed.".into(),
+    })?;
+    let payload = match kind {
+        SignatureKind::PersonalSign => {
+            let data = message
+                .strip_prefix("0x")
+                .and_then(|hex_message| hex::decode(hex_message).ok())
+                .unwrap_or_else(|| message.as_bytes().to_vec());
+            Signer::personal_sign_message(data)
+        }
+        SignatureKind::TypedData => {
+            let typed_data: TypedData =
+                serde_json::from_str(message).map_err(|_| Error::User {
+                    explanation: "The typed data is malformed.".into(),
+                })?;
+            Signer::typed_data_payload(&typed_data)?
+        }
+    };
+    let recovered = signature
+        .recover(RecoveryMessage::Hash(keccak256(payload).into()))
+        .map_err(|_| Error::User {
+            explanation: "The signature is malformed.".into(),
+        })?;
+    Ok(recovered == expected_address)
+}
+
 /// Signer middleware for ethers-rs using our key management.
 pub(super) struct SignerMiddleware<'a> {
-    provider: &'a Provider<Http>,
+    provider: &'a Provider<RpcTransport>,
     signer: Signer<'a>,
 }
 
 impl<'a> SignerMiddleware<'a> {
-    pub fn new(provider: &'a Provider<Http>, signing_key: &'a SigningKey) -> Self {
+    pub fn new(
+        provider: &'a Provider<RpcTransport>,
+        signing_key: &'a SigningKey,
+    ) -> Self {
         let signer = Signer::new(signing_key);
         Self { provider, signer }
     }
@@ -162,9 +241,9 @@ impl<'a> SignerMiddleware<'a> {
 
 #[async_trait]
 impl<'a> Middleware for SignerMiddleware<'a> {
-    type Error = SignerMiddlewareError<Provider<Http>>;
-    type Provider = Http;
-    type Inner = Provider<Http>;
+    type Error = SignerMiddlewareError<Provider<RpcTransport>>;
+    type Provider = RpcTransport;
+    type Inner = Provider<RpcTransport>;
 
     fn inner(&self) -> &Self::Inner {
         self.provider
@@ -214,13 +293,27 @@ impl<'a> Middleware for SignerMiddleware<'a> {
         // fill any missing fields
         self.fill_transaction(&mut tx, block).await?;
 
-        let sig: EthereumSignature = self.signer.sign_for_on_chain(&tx)?.into();
-        let signed_tx = tx.rlp_signed(&sig);
-
-        // Submit the raw transaction
-        let res = self.inner().send_raw_transaction(signed_tx).await?;
+        let (signed_tx, tx_hash) = self.signer.sign_raw_transaction(&tx)?;
+
+        // Submit the raw transaction retrying on transient errors. The transaction hash is used to
+        // check if the node has accepted the transaction before retrying to avoid double-broadcast.
+        let provider = self.inner();
+        let signed_tx = &signed_tx;
+        broadcast_with_retry(
+            RetryPolicy::default(),
+            move || async move {
+                provider
+                    .send_raw_transaction(signed_tx.clone())
+                    .await
+                    .map(|_| ())
+            },
+            move || async move {
+                matches!(provider.get_transaction(tx_hash).await, Ok(Some(_)))
+            },
+        )
+        .await?;
 
-        Ok(res)
+        Ok(PendingTransaction::new(tx_hash, provider))
     }
 
     async fn estimate_gas(
@@ -404,7 +497,7 @@ mod tests {
 
     use anyhow::Result;
     use ethers::core::{
-        types::{RecoveryMessage, TransactionRequest},
+        types::TransactionRequest,
         utils::{hex, keccak256},
     };
     use lazy_static::lazy_static;
@@ -624,4 +717,89 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn verifies_personal_sign_signature() -> Result<()> {
+        for case in PERSONAL_SIGN_VECTORS.iter() {
+            let kind = SignatureKind::PersonalSign;
+            assert!(verify_signature(
+                kind,
+                case.data,
+                case.signature,
+                case.address
+            )?);
+            let hex_message = format!("0x{}", hex::encode(case.data));
+            assert!(verify_signature(
+                kind,
+                &hex_message,
+                case.signature,
+                case.address
+            )?);
+
+            let tampered = format!("{}.", case.data);
+            assert!(!verify_signature(
+                kind,
+                &tampered,
+                case.signature,
+                case.address
+            )?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn verifies_typed_data_signature() -> Result<()> {
+        let key: EthereumAsymmetricKey =
+            "c85ef7d79691fe79573b1a7064c19c1a9819ebdbd1faaab1a8ec92344438aaf4".parse()?;
+        let signing_key = SigningKey::new(key, ChainId::default_dapp_chain())?;
+        let address = signing_key.address.to_string();
+        let mut data = json!({
+            "types": {
+                "EIP712Domain": [{"name": "name", "type": "string"}],
+                "Mail": [{"name": "contents", "type": "string"}]
+            },
+            "primaryType": "Mail",
+            "domain": {"name": "Ether Mail"},
+            "message": {"contents": "Hello, Bob!"}
+        });
+        let typed_data: TypedData = serde_json::from_value(data.clone())?;
+        let signature = Signer::new(&signing_key)
+            .sign_typed_data(&typed_data)?
+            .to_string();
+
+        let kind = SignatureKind::TypedData;
+        assert!(verify_signature(
+            kind,
+            &data.to_string(),
+            &signature,
+            &address
+        )?);
+
+        data["message"]["contents"] = json!("Hello, Eve!");
+        assert!(!verify_signature(
+            kind,
+            &data.to_string(),
+            &signature,
+            &address
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_signature_is_error() {
+        let case = &PERSONAL_SIGN_VECTORS[0];
+        let kind = SignatureKind::PersonalSign;
+
+        assert!(verify_signature(kind, case.data, "0xabcd", case.address).is_err());
+        assert!(verify_signature(kind, case.data, "not hex", case.address).is_err());
+        assert!(verify_signature(kind, case.data, case.signature, "0x1234").is_err());
+        assert!(verify_signature(
+            SignatureKind::TypedData,
+            "{}",
+            case.signature,
+            case.address
+        )
+        .is_err());
+    }
 }
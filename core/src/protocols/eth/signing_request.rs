@@ -0,0 +1,197 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use ethers::types::{
+    transaction::eip2718::TypedTransaction, Bytes, TransactionRequest, U256,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::DeterministicId,
+    protocols::eth::{
+        ChainId, ChecksumAddress, RpcProvider, Signer, SigningKey, TransactionQueue,
+        TransactionQueueKey,
+    },
+    Error,
+};
+
+/// Increment on breaking changes to the serialized format.
+const SIGNING_REQUEST_VERSION: u32 = 1;
+
+/// A transaction built on an online device to be signed on another one, e.g. an air-gapped
+/// device through a QR code. The request is self-contained, so signing it doesn't need network
+/// access.
+/// Exposed through FFI to UI.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningRequest {
+    pub version: u32,
+    /// The profile that the sender account belongs to.
+    pub profile_id: String,
+    pub chain_id: u64,
+    pub from_checksum_address: String,
+    /// None for contract deployments.
+    pub to_checksum_address: Option<String>,
+    /// Decimal amount in wei.
+    pub value: String,
+    /// Hex encoded with 0x prefix.
+    pub data: String,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    /// Decimal amount in wei.
+    pub gas_price: String,
+}
+
+impl SigningRequest {
+    /// Build a signing request with the gas limit and gas price fetched from the chain.
+    /// The nonce is reserved in the transaction queue, so that transactions sent from this
+    /// device don't reuse it.
+    pub async fn build(
+        rpc_provider: &RpcProvider,
+        transaction_queue: &TransactionQueue,
+        profile_id: DeterministicId,
+        from_address: ChecksumAddress,
+        to_address: Option<ChecksumAddress>,
+        value: U256,
+        data: Bytes,
+    ) -> Result<Self, Error> {
+        let chain_id = rpc_provider.chain_id;
+        let key = TransactionQueueKey {
+            profile_id,
+            chain_id,
+            address: from_address.to_address(),
+        };
+        let mut tx = TransactionRequest::new()
+            .from(from_address.to_address())
+            .value(value)
+            .data(data)
+            .chain_id(chain_id);
+        if let Some(to_address) = to_address {
+            tx = tx.to(to_address.to_address());
+        }
+
+        let pending_nonce = rpc_provider.pending_nonce_async(from_address).await?;
+        let fee_estimate = rpc_provider.estimate_fee_async(&tx).await?;
+        fee_estimate.apply(&mut tx);
+        let outflow = value.saturating_add(fee_estimate.fee());
+        let nonce = transaction_queue.reserve(&key, pending_nonce, outflow)?;
+        let tx = tx.nonce(nonce);
+
+        Self::new(key.profile_id.to_string(), chain_id, &tx)
+    }
+
+    fn new(
+        profile_id: String,
+        chain_id: ChainId,
+        tx: &TransactionRequest,
+    ) -> Result<Self, Error> {
+        let missing = |field: &str| Error::Fatal {
+            error: format!("Missing {field} in transaction for signing request"),
+        };
+        let from: ChecksumAddress = tx.from.ok_or_else(|| missing("from"))?.into();
+        let to = tx
+            .to
+            .as_ref()
+            .and_then(|to| to.as_address())
+            .map(|to| ChecksumAddress::from(*to).to_string());
+        Ok(Self {
+            version: SIGNING_REQUEST_VERSION,
+            profile_id,
+            chain_id: chain_id.into(),
+            from_checksum_address: from.to_string(),
+            to_checksum_address: to,
+            value: tx.value.unwrap_or_default().to_string(),
+            data: tx.data.clone().unwrap_or_default().to_string(),
+            nonce: tx.nonce.ok_or_else(|| missing("nonce"))?.as_u64(),
+            gas_limit: tx.gas.ok_or_else(|| missing("gas"))?.as_u64(),
+            gas_price: tx
+                .gas_price
+                .ok_or_else(|| missing("gas price"))?
+                .to_string(),
+        })
+    }
+
+    /// Serialize the request to transfer it to the signing device.
+    pub fn encode(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|err| Error::Fatal {
+            error: format!("Failed to serialize signing request with error: '{err}'"),
+        })
+    }
+
+    /// Deserialize a request from another device.
+    pub fn decode(payload: &str) -> Result<Self, Error> {
+        let request: Self = serde_json::from_str(payload).map_err(|_| Error::User {
+            explanation: "The signing request is malformed.".into(),
+        })?;
+        if request.version != SIGNING_REQUEST_VERSION {
+            return Err(Error::User {
+                explanation: format!(
+                    "Signing request version {} is not supported. Please update the app.",
+                    request.version
+                ),
+            });
+        }
+        Ok(request)
+    }
+
+    pub fn from_address(&self) -> Result<ChecksumAddress, Error> {
+        parse_address(&self.from_checksum_address)
+    }
+
+    pub fn eth_chain_id(&self) -> Result<ChainId, Error> {
+        self.chain_id.try_into()
+    }
+
+    fn transaction(&self) -> Result<TransactionRequest, Error> {
+        let mut tx = TransactionRequest::new()
+            .from(self.from_address()?.to_address())
+            .value(parse_amount(&self.value)?)
+            .data(Bytes::from_str(&self.data).map_err(|_| Error::User {
+                explanation: "The transaction data must be hex encoded.".into(),
+            })?)
+            .nonce(self.nonce)
+            .gas(self.gas_limit)
+            .gas_price(parse_amount(&self.gas_price)?)
+            .chain_id(self.eth_chain_id()?);
+        if let Some(to) = self.to_checksum_address.as_deref() {
+            tx = tx.to(parse_address(to)?.to_address());
+        }
+        Ok(tx)
+    }
+
+    /// Sign the request without network access. The signing key must be the key of the
+    /// sender account on the chain of the request.
+    pub fn sign(&self, signing_key: &SigningKey) -> Result<SignedTransaction, Error> {
+        let tx: TypedTransaction = self.transaction()?.into();
+        let (raw_transaction, tx_hash) =
+            Signer::new(signing_key).sign_raw_transaction(&tx)?;
+        Ok(SignedTransaction {
+            raw_transaction: raw_transaction.to_string(),
+            tx_hash: format!("{tx_hash:#x}"),
+        })
+    }
+}
+
+/// A signed transaction that is ready to be broadcast.
+/// Exposed through FFI to UI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedTransaction {
+    /// RLP encoded signed transaction, hex encoded with 0x prefix.
+    pub raw_transaction: String,
+    pub tx_hash: String,
+}
+
+fn parse_address(address: &str) -> Result<ChecksumAddress, Error> {
+    address.parse().map_err(|_| Error::User {
+        explanation: format!("Invalid address in signing request: '{address}'"),
+    })
+}
+
+fn parse_amount(amount: &str) -> Result<U256, Error> {
+    U256::from_dec_str(amount).map_err(|_| Error::User {
+        explanation: format!("Invalid amount in signing request: '{amount}'"),
+    })
+}
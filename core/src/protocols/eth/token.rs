@@ -127,6 +127,68 @@ impl FungibleTokenAmount {
     }
 }
 
+/// A native or fungible token amount with the context needed to display it, so that the UI
+/// doesn't have to guess the decimals and the symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurrencyAmount {
+    /// The amount in the lowest denomination of the token (eg. Wei on Ethereum) in decimal.
+    pub raw: String,
+    /// The smallest transferable fraction of the token is `10^{-decimals}`
+    pub decimals: u8,
+    pub symbol: String,
+    /// Ethereum chain id.
+    pub chain_id: u64,
+}
+
+impl CurrencyAmount {
+    pub fn new(chain_id: ChainId, amount: U256, decimals: u8, symbol: String) -> Self {
+        Self {
+            raw: amount.to_string(),
+            decimals,
+            symbol,
+            chain_id: chain_id.into(),
+        }
+    }
+
+    /// The amount denominated in the highest denomination of the token in decimal, eg. "1.5".
+    /// The raw amount is returned as is if it's not a valid decimal integer.
+    pub fn display(&self) -> String {
+        match U256::from_dec_str(&self.raw) {
+            Ok(amount) => display_amount(amount, self.decimals),
+            Err(_) => self.raw.clone(),
+        }
+    }
+}
+
+impl From<&NativeTokenAmount> for CurrencyAmount {
+    fn from(value: &NativeTokenAmount) -> Self {
+        let native_token = value.chain_id.native_token();
+        Self::new(
+            value.chain_id,
+            value.amount,
+            native_token.decimals(),
+            native_token.symbol(),
+        )
+    }
+}
+
+impl From<&FungibleTokenBalance> for CurrencyAmount {
+    fn from(value: &FungibleTokenBalance) -> Self {
+        Self::new(
+            value.chain_id,
+            value.amount,
+            value.decimals,
+            value.symbol.clone(),
+        )
+    }
+}
+
+/// Display a currency amount. Exposed through FFI, because UDL dictionaries can't have
+/// methods.
+pub fn display_currency_amount(amount: CurrencyAmount) -> String {
+    amount.display()
+}
+
 /// Based on
 /// https://github.com/gakonst/ethers-rs/blob/3681099af328610b429fd22eab5e9f68f693c60c/ethers-core/src/utils/mod.rs#L101
 fn display_amount(amount: U256, decimals: u8) -> String {
@@ -207,6 +269,8 @@ pub struct NFTBalance {
     pub collection_name: String,
     pub name: String,
     pub token_id: String,
+    /// Token standard, eg. `ERC721` or `ERC1155`.
+    pub standard: String,
     pub image_url: Option<url::Url>,
 }
 
@@ -292,6 +356,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn currency_amounts_carry_decimals_and_symbol() -> Result<()> {
+        let native = NativeTokenAmount::new_from_decimal(ChainId::PolygonMainnet, "1.5")?;
+        let native = CurrencyAmount::from(&native);
+        assert_eq!(native.raw, "1500000000000000000");
+        assert_eq!(native.decimals, 18);
+        assert_eq!(native.symbol, "MATIC");
+        assert_eq!(native.chain_id, 137);
+        assert_eq!(native.display(), "1.5");
+
+        let balance = FungibleTokenBalance {
+            chain_id: ChainId::EthMainnet,
+            contract_address: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse()?,
+            amount: U256::from(1_230_000),
+            decimals: 6,
+            symbol: "USDC".into(),
+            name: "USD Coin".into(),
+            logo: None,
+        };
+        let token = CurrencyAmount::from(&balance);
+        assert_eq!(token.raw, "1230000");
+        assert_eq!(token.decimals, 6);
+        assert_eq!(token.symbol, "USDC");
+        assert_eq!(token.chain_id, 1);
+        assert_eq!(token.display(), "1.23");
+
+        Ok(())
+    }
+
     #[test]
     fn native_to_string() {
         assert_eq!(NativeToken::Eth.to_string(), "ETH");
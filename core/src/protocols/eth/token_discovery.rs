@@ -0,0 +1,302 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::Instant,
+};
+
+use ethers::{
+    contract::EthEvent,
+    types::{Log, U256},
+};
+use lazy_static::lazy_static;
+
+use crate::{
+    config,
+    protocols::eth::{contracts::TransferFilter, ChainId, ChecksumAddress, RpcProvider},
+    Error,
+};
+
+lazy_static! {
+    // When automatic discovery was last started for an address on a chain.
+    static ref LAST_AUTO_DISCOVERY: Mutex<HashMap<(ChainId, ChecksumAddress), Instant>> =
+        Default::default();
+}
+
+/// Symbols ending in these are advertising a website, which is what spam tokens do.
+const SPAM_SYMBOL_DOMAINS: &[&str] = &[
+    ".com", ".io", ".org", ".net", ".xyz", ".app", ".finance", ".site", ".top", ".gift",
+];
+
+/// Discover fungible tokens that the address has recently sent or received from the
+/// `Transfer` event logs. Returns the token contracts that aren't known yet, most recently
+/// transferred first. Spam tokens and contracts without a symbol are skipped.
+pub async fn discover_tokens(
+    rpc_provider: &RpcProvider,
+    address: ChecksumAddress,
+    known_tokens: &HashSet<ChecksumAddress>,
+) -> Result<Vec<ChecksumAddress>, Error> {
+    let logs = fetch_recent_transfer_logs(rpc_provider, address).await?;
+
+    let mut results: Vec<ChecksumAddress> = Default::default();
+    for token in new_token_contracts(&logs, known_tokens) {
+        if results.len() >= config::TOKEN_DISCOVERY_MAX_TOKENS {
+            break;
+        }
+        match rpc_provider.fungible_token_symbol_async(token).await {
+            Ok(symbol) if is_spam_token_symbol(&symbol) => {
+                log::debug!("Skipping spam token {token} with symbol '{symbol}'")
+            }
+            Ok(_) => results.push(token),
+            // The symbol is optional in ERC-20, but tokens without one can't be displayed.
+            Err(err) => log::debug!("Skipping token {token} without symbol: {err:?}"),
+        }
+    }
+    Ok(results)
+}
+
+/// Whether automatic discovery may start for the address on the chain. Returns true at most
+/// once per `TOKEN_AUTO_DISCOVERY_INTERVAL` for an address, so that refreshing the token list
+/// doesn't start a new scan each time.
+pub fn start_auto_discovery(chain_id: ChainId, address: ChecksumAddress) -> bool {
+    let mut last_discovery = LAST_AUTO_DISCOVERY
+        .lock()
+        .expect("no panics while holding the lock");
+    let now = Instant::now();
+    match last_discovery.get(&(chain_id, address)) {
+        Some(started_at)
+            if now.duration_since(*started_at)
+                < config::TOKEN_AUTO_DISCOVERY_INTERVAL =>
+        {
+            false
+        }
+        _ => {
+            last_discovery.insert((chain_id, address), now);
+            true
+        }
+    }
+}
+
+/// Fetch the transfer logs of the address in the last `TOKEN_DISCOVERY_BLOCK_RANGE` blocks,
+/// newest first. At most `TOKEN_DISCOVERY_MAX_LOGS` logs are returned.
+async fn fetch_recent_transfer_logs(
+    rpc_provider: &RpcProvider,
+    address: ChecksumAddress,
+) -> Result<Vec<Log>, Error> {
+    let latest = rpc_provider.block_number_async().await?;
+    let earliest = latest.saturating_sub(config::TOKEN_DISCOVERY_BLOCK_RANGE - 1);
+
+    let mut logs: Vec<Log> = Default::default();
+    // Scan backwards, so that the most recent transfers are kept when the log limit is hit.
+    let mut to_block = latest;
+    loop {
+        let from_block = to_block
+            .saturating_sub(config::TOKEN_DISCOVERY_BLOCKS_PER_REQUEST - 1)
+            .max(earliest);
+        let mut chunk = rpc_provider
+            .transfer_logs_async(address, from_block, to_block)
+            .await?;
+        chunk.sort_by(|a, b| b.block_number.cmp(&a.block_number));
+        logs.extend(chunk);
+        if from_block == earliest || logs.len() >= config::TOKEN_DISCOVERY_MAX_LOGS {
+            break;
+        }
+        to_block = from_block - 1;
+    }
+    logs.truncate(config::TOKEN_DISCOVERY_MAX_LOGS);
+    Ok(logs)
+}
+
+/// The fungible token contracts in the transfer logs that aren't known yet in the order of
+/// the logs.
+/// Zero value transfers are ignored, because anyone can emit them to make a token show up in
+/// a wallet's history.
+pub fn new_token_contracts(
+    logs: &[Log],
+    known_tokens: &HashSet<ChecksumAddress>,
+) -> Vec<ChecksumAddress> {
+    let mut results: Vec<ChecksumAddress> = Default::default();
+    for log in logs {
+        if !is_fungible_token_transfer(log) {
+            continue;
+        }
+        let token: ChecksumAddress = log.address.into();
+        if !known_tokens.contains(&token) && !results.contains(&token) {
+            results.push(token)
+        }
+    }
+    results
+}
+
+/// ERC-20 `Transfer` events have 2 indexed arguments and the value as data. ERC-721
+/// transfers have the same signature, but the token id is indexed as well.
+fn is_fungible_token_transfer(log: &Log) -> bool {
+    log.topics.len() == 3
+        && log.topics[0] == TransferFilter::signature()
+        && log.data.len() == 32
+        && !U256::from_big_endian(&log.data).is_zero()
+}
+
+/// Heuristic to detect spam tokens by their symbol. Spam tokens commonly advertise a website
+/// or a message in the symbol.
+pub fn is_spam_token_symbol(symbol: &str) -> bool {
+    let lowercase = symbol.to_lowercase();
+    symbol.is_empty()
+        || symbol.chars().count() > config::MAX_TOKEN_SYMBOL_LENGTH
+        || symbol.chars().any(|c| c.is_whitespace() || c.is_control())
+        || lowercase.contains("://")
+        || lowercase.starts_with("www.")
+        || SPAM_SYMBOL_DOMAINS
+            .iter()
+            .any(|domain| lowercase.ends_with(domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use ethers::types::{Address, Bytes, H256, U64};
+
+    use super::*;
+    use crate::{
+        async_runtime as rt,
+        protocols::eth::contracts::{test_util::TestContractDeployer, ERC20Contract},
+    };
+
+    fn random_address() -> ChecksumAddress {
+        Address::random().into()
+    }
+
+    fn transfer_log(
+        token: ChecksumAddress,
+        from: ChecksumAddress,
+        to: ChecksumAddress,
+        value: u64,
+        block_number: u64,
+    ) -> Log {
+        let mut data = [0u8; 32];
+        U256::from(value).to_big_endian(&mut data);
+        Log {
+            address: token.to_address(),
+            topics: vec![
+                TransferFilter::signature(),
+                H256::from(from.to_address()),
+                H256::from(to.to_address()),
+            ],
+            data: Bytes::from(data.to_vec()),
+            block_number: Some(U64::from(block_number)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_new_fungible_token_contracts() {
+        let owner = random_address();
+        let sender = random_address();
+        let received = random_address();
+        let sent = random_address();
+        let known = random_address();
+        let poisoned = random_address();
+        let spam = random_address();
+        let nft = random_address();
+
+        let mut nft_log = transfer_log(nft, sender, owner, 0, 9);
+        nft_log.topics.push(H256::from_low_u64_be(1));
+        nft_log.data = Default::default();
+        let logs = vec![
+            transfer_log(received, sender, owner, 100, 10),
+            transfer_log(sent, owner, sender, 5, 9),
+            transfer_log(received, sender, owner, 50, 8),
+            transfer_log(known, sender, owner, 1, 8),
+            // Address poisoning
+            transfer_log(poisoned, sender, owner, 0, 7),
+            transfer_log(spam, sender, owner, 1_000_000, 7),
+            nft_log,
+        ];
+        let known_tokens = HashSet::from([known]);
+
+        let candidates = new_token_contracts(&logs, &known_tokens);
+        assert_eq!(candidates, vec![received, sent, spam]);
+    }
+
+    #[test]
+    fn discovers_transferred_tokens() -> Result<()> {
+        let chain_id = ChainId::EthMainnet;
+        let contract_deployer = TestContractDeployer::init(chain_id);
+        let provider = Arc::new(contract_deployer.provider());
+        let owner = random_address();
+
+        let mut tokens: Vec<ChecksumAddress> = Default::default();
+        for amount in [100, 200, 0] {
+            let token = contract_deployer.deploy_fungible_token_test_contract()?;
+            let contract = ERC20Contract::new(token, provider.clone());
+            // Anvil needs explicit gas, see the `sends_fungible_token` test.
+            let call = contract
+                .transfer(owner.to_address(), U256::from(amount))
+                .gas(100000);
+            let pending_tx = rt::block_on(call.send())?;
+            let _receipt = rt::block_on(pending_tx)?;
+            tokens.push(token);
+        }
+        let (received, known, poisoned) = (tokens[0], tokens[1], tokens[2]);
+
+        let discovered = rt::block_on(discover_tokens(
+            &contract_deployer.rpc_provider,
+            owner,
+            &HashSet::from([known]),
+        ))?;
+        assert_eq!(discovered, vec![received]);
+        assert!(!discovered.contains(&poisoned));
+
+        let discovered = rt::block_on(discover_tokens(
+            &contract_deployer.rpc_provider,
+            owner,
+            &Default::default(),
+        ))?;
+        // Most recent first
+        assert_eq!(discovered, vec![known, received]);
+
+        // No transfers
+        let discovered = rt::block_on(discover_tokens(
+            &contract_deployer.rpc_provider,
+            random_address(),
+            &Default::default(),
+        ))?;
+        assert!(discovered.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn throttles_auto_discovery() {
+        let address = random_address();
+
+        assert!(start_auto_discovery(ChainId::EthMainnet, address));
+        assert!(!start_auto_discovery(ChainId::EthMainnet, address));
+        assert!(start_auto_discovery(ChainId::PolygonMainnet, address));
+    }
+
+    #[test]
+    fn detects_spam_symbols() {
+        for symbol in [
+            "",
+            "Visit site",
+            "https://a.b",
+            "www.airdrop",
+            "$ claim",
+            "ETHGIFT.com",
+        ] {
+            assert!(is_spam_token_symbol(symbol), "{symbol}");
+        }
+        assert!(is_spam_token_symbol(
+            &"X".repeat(config::MAX_TOKEN_SYMBOL_LENGTH + 1)
+        ));
+        for symbol in ["USDC", "USDC.e", "WETH", "1INCH"] {
+            assert!(!is_spam_token_symbol(symbol), "{symbol}");
+        }
+    }
+}
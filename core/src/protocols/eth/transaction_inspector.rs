@@ -0,0 +1,346 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Debug;
+
+use ethers::types::{NameOrAddress, TransactionRequest, U256};
+use typed_builder::TypedBuilder;
+
+use crate::{
+    protocols::eth::{ChainId, ChecksumAddress},
+    ui_callback::TransactionWarning,
+};
+
+/// Lets hosts plug in custom safety checks for transactions (eg. flagging known-malicious
+/// contracts from a threat feed) without changing the core.
+pub trait TransactionInspectorI: Debug + Send + Sync {
+    /// Inspect a transaction before it's submitted and return warnings to present to the user.
+    fn inspect(
+        &self,
+        tx: &TransactionRequest,
+        chain_id: ChainId,
+    ) -> Vec<TransactionWarning>;
+}
+
+/// Transaction inspector that doesn't produce any warnings.
+#[derive(Debug, Default)]
+pub struct DefaultTransactionInspector {}
+
+impl DefaultTransactionInspector {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl TransactionInspectorI for DefaultTransactionInspector {
+    fn inspect(&self, _: &TransactionRequest, _: ChainId) -> Vec<TransactionWarning> {
+        Default::default()
+    }
+}
+
+/// Transaction inspector implemented by the host, eg. with the threat feed of a security
+/// vendor.
+/// Exposed through FFI as a callback interface.
+pub trait CoreTransactionInspectorI: Send + Sync + Debug {
+    /// Inspect a transaction before it's submitted and return warnings to present to the user.
+    fn inspect(&self, tx: InspectedTransaction) -> Vec<TransactionWarning>;
+}
+
+/// A transaction passed to the transaction inspector of the host.
+/// Exposed through FFI.
+#[derive(Clone, Debug, PartialEq, Eq, TypedBuilder)]
+pub struct InspectedTransaction {
+    pub chain_id: u64,
+    #[builder(default)]
+    pub from_checksum_address: Option<String>,
+    /// None for contract deployments.
+    #[builder(default)]
+    pub to_checksum_address: Option<String>,
+    /// The native token value in wei as a decimal string.
+    pub value_wei: String,
+    /// Hex encoded calldata with a `0x` prefix.
+    pub data: String,
+}
+
+impl InspectedTransaction {
+    fn new(tx: &TransactionRequest, chain_id: ChainId) -> Self {
+        let to_checksum_address = match tx.to.as_ref() {
+            Some(NameOrAddress::Address(address)) => {
+                let address: ChecksumAddress = (*address).into();
+                Some(address.to_string())
+            }
+            _ => None,
+        };
+        Self::builder()
+            .chain_id(chain_id.into())
+            .from_checksum_address(
+                tx.from.map(|from| ChecksumAddress::from(from).to_string()),
+            )
+            .to_checksum_address(to_checksum_address)
+            .value_wei(tx.value.unwrap_or_default().to_string())
+            .data(format!(
+                "0x{}",
+                hex::encode(tx.data.as_deref().unwrap_or_default())
+            ))
+            .build()
+    }
+}
+
+/// Adapts the transaction inspector of the host to the core.
+#[derive(Debug)]
+pub struct HostTransactionInspector {
+    inspector: Box<dyn CoreTransactionInspectorI>,
+}
+
+impl HostTransactionInspector {
+    pub fn new(inspector: Box<dyn CoreTransactionInspectorI>) -> Self {
+        Self { inspector }
+    }
+}
+
+impl TransactionInspectorI for HostTransactionInspector {
+    fn inspect(
+        &self,
+        tx: &TransactionRequest,
+        chain_id: ChainId,
+    ) -> Vec<TransactionWarning> {
+        self.inspector
+            .inspect(InspectedTransaction::new(tx, chain_id))
+    }
+}
+
+// `approve(address,uint256)`
+const ERC20_APPROVE_SELECTOR: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+// `transfer(address,uint256)`
+const ERC20_TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+// `transferFrom(address,address,uint256)`
+const ERC20_TRANSFER_FROM_SELECTOR: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+// `setApprovalForAll(address,bool)`
+const SET_APPROVAL_FOR_ALL_SELECTOR: [u8; 4] = [0xa2, 0x2c, 0xb4, 0x65];
+const SELECTOR_LEN: usize = 4;
+const WORD_LEN: usize = 32;
+
+/// Flag transactions that let the spender move an unlimited amount of the user's tokens.
+/// These checks always run regardless of the pluggable transaction inspector.
+pub fn inspect_token_approvals(tx: &TransactionRequest) -> Vec<TransactionWarning> {
+    let data = match tx.data.as_ref() {
+        Some(data) => data.as_ref(),
+        None => return Default::default(),
+    };
+    // The second argument of both methods.
+    let second_arg_range = SELECTOR_LEN + WORD_LEN..SELECTOR_LEN + 2 * WORD_LEN;
+    let (selector, second_arg) =
+        match (data.get(..SELECTOR_LEN), data.get(second_arg_range)) {
+            (Some(selector), Some(second_arg)) => (selector, second_arg),
+            _ => return Default::default(),
+        };
+
+    let message = if selector == ERC20_APPROVE_SELECTOR
+        && U256::from_big_endian(second_arg) == U256::MAX
+    {
+        Some("The transaction approves spending an unlimited amount of tokens")
+    } else if selector == SET_APPROVAL_FOR_ALL_SELECTOR
+        && !U256::from_big_endian(second_arg).is_zero()
+    {
+        Some("The transaction approves transferring all NFTs in the collection")
+    } else {
+        None
+    };
+
+    message
+        .map(|message| vec![TransactionWarning::builder().message(message).build()])
+        .unwrap_or_default()
+}
+
+/// An amount of a token that a transaction spends or lets a spender move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionSpend {
+    pub chain_id: ChainId,
+    /// The fungible token contract. None for the native token.
+    pub token_address: Option<ChecksumAddress>,
+    /// Amount in the lowest denomination of the token.
+    pub amount: U256,
+}
+
+/// The native token value of the transaction and the amount of ERC-20 tokens that it transfers
+/// or approves. Approvals are counted, because that's how dapps move tokens on behalf of the
+/// user. `transferFrom` is counted too, because the dapp address may move tokens that it was
+/// approved to spend by an other address.
+pub fn transaction_spends(
+    tx: &TransactionRequest,
+    chain_id: ChainId,
+) -> Vec<TransactionSpend> {
+    let mut spends: Vec<TransactionSpend> = Default::default();
+
+    let value = tx.value.unwrap_or_default();
+    if !value.is_zero() {
+        spends.push(TransactionSpend {
+            chain_id,
+            token_address: None,
+            amount: value,
+        })
+    }
+
+    let token_address = match tx.to.as_ref() {
+        Some(NameOrAddress::Address(address)) => *address,
+        _ => return spends,
+    };
+    let data = match tx.data.as_ref() {
+        Some(data) => data.as_ref(),
+        None => return spends,
+    };
+    let selector = match data.get(..SELECTOR_LEN) {
+        Some(selector) => selector,
+        None => return spends,
+    };
+    let amount_arg_index =
+        if selector == ERC20_TRANSFER_SELECTOR || selector == ERC20_APPROVE_SELECTOR {
+            1
+        } else if selector == ERC20_TRANSFER_FROM_SELECTOR {
+            2
+        } else {
+            return spends;
+        };
+    let amount_arg_start = SELECTOR_LEN + amount_arg_index * WORD_LEN;
+    if let Some(amount_arg) = data.get(amount_arg_start..amount_arg_start + WORD_LEN) {
+        spends.push(TransactionSpend {
+            chain_id,
+            token_address: Some(token_address.into()),
+            amount: U256::from_big_endian(amount_arg),
+        })
+    }
+
+    spends
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Address, Bytes};
+
+    use super::*;
+
+    fn tx_with_call(selector: [u8; 4], second_arg: U256) -> TransactionRequest {
+        let mut data = selector.to_vec();
+        data.extend([0u8; WORD_LEN - 20]);
+        data.extend(Address::repeat_byte(1).as_bytes());
+        let mut arg = [0u8; WORD_LEN];
+        second_arg.to_big_endian(&mut arg);
+        data.extend(arg);
+        TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .data(Bytes::from(data))
+    }
+
+    #[test]
+    fn flags_unlimited_erc20_approval() {
+        let tx = tx_with_call(ERC20_APPROVE_SELECTOR, U256::MAX);
+        assert_eq!(inspect_token_approvals(&tx).len(), 1);
+    }
+
+    #[test]
+    fn allows_limited_erc20_approval() {
+        let tx = tx_with_call(ERC20_APPROVE_SELECTOR, U256::from(1000));
+        assert!(inspect_token_approvals(&tx).is_empty());
+    }
+
+    #[test]
+    fn flags_set_approval_for_all() {
+        let tx = tx_with_call(SET_APPROVAL_FOR_ALL_SELECTOR, U256::one());
+        assert_eq!(inspect_token_approvals(&tx).len(), 1);
+
+        let tx = tx_with_call(SET_APPROVAL_FOR_ALL_SELECTOR, U256::zero());
+        assert!(inspect_token_approvals(&tx).is_empty());
+    }
+
+    #[test]
+    fn passes_transaction_to_host_inspector() {
+        let tx = TransactionRequest::new()
+            .from(Address::repeat_byte(2))
+            .to(Address::repeat_byte(1))
+            .value(5)
+            .data(Bytes::from(vec![0xab, 0xcd]));
+        let inspected = InspectedTransaction::new(&tx, ChainId::EthMainnet);
+        let to: ChecksumAddress = Address::repeat_byte(1).into();
+        let from: ChecksumAddress = Address::repeat_byte(2).into();
+        assert_eq!(
+            inspected,
+            InspectedTransaction::builder()
+                .chain_id(1)
+                .from_checksum_address(Some(from.to_string()))
+                .to_checksum_address(Some(to.to_string()))
+                .value_wei("5".into())
+                .data("0xabcd".into())
+                .build()
+        );
+    }
+
+    #[test]
+    fn native_and_token_spends() {
+        let chain_id = ChainId::EthMainnet;
+        let tx = TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .value(5);
+        assert_eq!(
+            transaction_spends(&tx, chain_id),
+            vec![TransactionSpend {
+                chain_id,
+                token_address: None,
+                amount: U256::from(5),
+            }]
+        );
+
+        for selector in [ERC20_TRANSFER_SELECTOR, ERC20_APPROVE_SELECTOR] {
+            let tx = tx_with_call(selector, U256::from(1000));
+            assert_eq!(
+                transaction_spends(&tx, chain_id),
+                vec![TransactionSpend {
+                    chain_id,
+                    token_address: Some(Address::repeat_byte(1).into()),
+                    amount: U256::from(1000),
+                }]
+            );
+        }
+
+        let tx = tx_with_call(SET_APPROVAL_FOR_ALL_SELECTOR, U256::one());
+        assert!(transaction_spends(&tx, chain_id).is_empty());
+    }
+
+    #[test]
+    fn transfer_from_spends() {
+        let chain_id = ChainId::EthMainnet;
+        let mut data = ERC20_TRANSFER_FROM_SELECTOR.to_vec();
+        for address in [Address::repeat_byte(2), Address::repeat_byte(3)] {
+            data.extend([0u8; WORD_LEN - 20]);
+            data.extend(address.as_bytes());
+        }
+        let mut amount = [0u8; WORD_LEN];
+        U256::from(1000).to_big_endian(&mut amount);
+        data.extend(amount);
+        let tx = TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .data(Bytes::from(data));
+
+        assert_eq!(
+            transaction_spends(&tx, chain_id),
+            vec![TransactionSpend {
+                chain_id,
+                token_address: Some(Address::repeat_byte(1).into()),
+                amount: U256::from(1000),
+            }]
+        );
+    }
+
+    #[test]
+    fn ignores_plain_transfers() {
+        let tx = TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .value(1);
+        assert!(inspect_token_approvals(&tx).is_empty());
+
+        let tx = TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .data(Bytes::from(ERC20_APPROVE_SELECTOR.to_vec()));
+        assert!(inspect_token_approvals(&tx).is_empty());
+    }
+}
@@ -0,0 +1,716 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use ethers::types::{Address, H256, U256};
+
+use crate::{config, db::DeterministicId, protocols::eth::ChainId, Error};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, strum_macros::Display)]
+pub enum QueuedTransactionStatus {
+    /// Waiting for lower nonce transactions to be submitted.
+    Queued,
+    Submitted,
+    Confirmed,
+    /// Either submission failed or the transaction failed on chain. If submission failed, the
+    /// nonce wasn't used and the transaction blocks higher nonce transactions until it's
+    /// cancelled or replaced.
+    Failed,
+    /// A transaction with the same nonce was submitted in its place.
+    Replaced,
+    /// Cleared by a nonce reset before it was confirmed. It needs to be submitted again.
+    /// Removed from the queue once the nonce has been used on chain.
+    Cleared,
+    /// The nonce is reserved for a transaction that is signed and broadcast outside the
+    /// queue, eg. on an air-gapped device. Removed from the queue once the nonce has been used
+    /// on chain.
+    Reserved,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedTransaction {
+    pub nonce: U256,
+    pub status: QueuedTransactionStatus,
+    pub tx_hash: Option<H256>,
+    pub error: Option<String>,
+    /// The native token value plus the estimated fee of the transaction.
+    pub outflow: U256,
+}
+
+impl QueuedTransaction {
+    fn new(nonce: U256, outflow: U256) -> Self {
+        Self {
+            nonce,
+            status: QueuedTransactionStatus::Queued,
+            tx_hash: None,
+            error: None,
+            outflow,
+        }
+    }
+
+    /// A failed transaction blocks the queue if it was never submitted, because its nonce is
+    /// still unused.
+    fn is_blocking(&self) -> bool {
+        self.status == QueuedTransactionStatus::Failed && self.tx_hash.is_none()
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            QueuedTransactionStatus::Confirmed
+                | QueuedTransactionStatus::Replaced
+                | QueuedTransactionStatus::Failed
+                | QueuedTransactionStatus::Cleared
+        ) && !self.is_blocking()
+    }
+
+    /// Not replaced or cleared.
+    fn is_active(&self) -> bool {
+        !matches!(
+            self.status,
+            QueuedTransactionStatus::Replaced | QueuedTransactionStatus::Cleared
+        )
+    }
+
+    fn is_replaceable(&self) -> bool {
+        self.status == QueuedTransactionStatus::Submitted || self.is_blocking()
+    }
+}
+
+/// Transactions are queued per sender address on a chain in a profile.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TransactionQueueKey {
+    pub profile_id: DeterministicId,
+    pub chain_id: ChainId,
+    pub address: Address,
+}
+
+/// Assigns nonces to transactions in the order they're added and makes sure that they're
+/// submitted in nonce order.
+#[derive(Debug, Default)]
+pub struct TransactionQueue {
+    queues: Mutex<HashMap<TransactionQueueKey, Vec<QueuedTransaction>>>,
+}
+
+enum Turn {
+    Ready,
+    Wait,
+    Blocked {
+        nonce: U256,
+    },
+    /// The transaction was cleared by a nonce reset.
+    Cleared,
+    /// The transaction isn't in the queue anymore, eg. because it was cancelled.
+    Removed,
+}
+
+impl TransactionQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn lock(
+        &self,
+    ) -> Result<MutexGuard<HashMap<TransactionQueueKey, Vec<QueuedTransaction>>>, Error>
+    {
+        Ok(self.queues.lock()?)
+    }
+
+    /// Add a transaction to the queue and return its nonce.
+    /// The nonce is the next one after the queued transactions or the pending nonce of the
+    /// address on the chain, whichever is higher.
+    /// If `replace_nonce` is the nonce of a submitted or failed transaction, the new
+    /// transaction replaces it.
+    /// The outflow is the native token value plus the estimated fee of the transaction.
+    /// Returns an error if a failed transaction blocks the queue.
+    pub fn enqueue(
+        &self,
+        key: &TransactionQueueKey,
+        pending_nonce: U256,
+        replace_nonce: Option<U256>,
+        outflow: U256,
+    ) -> Result<U256, Error> {
+        let mut queues = self.lock()?;
+        let queue = queues.entry(key.clone()).or_default();
+        prune_finished(queue, pending_nonce);
+
+        if let Some(nonce) = replace_nonce {
+            if let Some(replaced) = queue
+                .iter_mut()
+                .find(|entry| entry.nonce == nonce && entry.is_replaceable())
+            {
+                replaced.status = QueuedTransactionStatus::Replaced;
+                queue.push(QueuedTransaction::new(nonce, outflow));
+                return Ok(nonce);
+            }
+        }
+
+        let nonce = next_nonce(queue, pending_nonce)?;
+        queue.push(QueuedTransaction::new(nonce, outflow));
+
+        Ok(nonce)
+    }
+
+    /// Reserve the next nonce for a transaction that is signed and broadcast outside the
+    /// queue, so that queued transactions don't reuse it. Unlike enqueued transactions, the
+    /// reserved transaction doesn't make higher nonce transactions wait.
+    /// The outflow is the native token value plus the estimated fee of the transaction.
+    /// Returns an error if a failed transaction blocks the queue.
+    pub fn reserve(
+        &self,
+        key: &TransactionQueueKey,
+        pending_nonce: U256,
+        outflow: U256,
+    ) -> Result<U256, Error> {
+        let mut queues = self.lock()?;
+        let queue = queues.entry(key.clone()).or_default();
+        prune_finished(queue, pending_nonce);
+
+        let nonce = next_nonce(queue, pending_nonce)?;
+        let mut reserved = QueuedTransaction::new(nonce, outflow);
+        reserved.status = QueuedTransactionStatus::Reserved;
+        queue.push(reserved);
+
+        Ok(nonce)
+    }
+
+    /// Wait until all lower nonce transactions in the queue have been submitted.
+    /// If a lower nonce transaction failed to submit, the transaction is removed from the queue
+    /// and an error is returned. An error is returned as well if the transaction was cleared
+    /// by a nonce reset or removed from the queue while waiting.
+    pub async fn wait_for_turn(
+        &self,
+        key: &TransactionQueueKey,
+        nonce: U256,
+    ) -> Result<(), Error> {
+        loop {
+            match self.turn(key, nonce)? {
+                Turn::Ready => return Ok(()),
+                Turn::Wait => tokio::time::sleep(config::TX_QUEUE_POLL_INTERVAL).await,
+                Turn::Blocked {
+                    nonce: blocking_nonce,
+                } => {
+                    self.remove_queued(key, nonce)?;
+                    return Err(blocked_error(blocking_nonce));
+                }
+                Turn::Cleared => return Err(cleared_error(nonce)),
+                Turn::Removed => return Err(not_found_error(nonce)),
+            }
+        }
+    }
+
+    fn turn(&self, key: &TransactionQueueKey, nonce: U256) -> Result<Turn, Error> {
+        let queues = self.lock()?;
+        let queue = queues
+            .get(key)
+            .map(|queue| queue.as_slice())
+            .unwrap_or_default();
+        let mut entries = queue.iter().filter(|entry| entry.nonce == nonce);
+        if !entries.clone().any(|entry| entry.is_active()) {
+            // Only a reset clears transactions, so a missing transaction was removed otherwise.
+            let is_cleared =
+                entries.any(|entry| entry.status == QueuedTransactionStatus::Cleared);
+            return Ok(if is_cleared {
+                Turn::Cleared
+            } else {
+                Turn::Removed
+            });
+        }
+        let lower = queue.iter().filter(|entry| entry.nonce < nonce);
+        let mut turn = Turn::Ready;
+        for entry in lower {
+            if entry.is_blocking() {
+                return Ok(Turn::Blocked { nonce: entry.nonce });
+            } else if entry.status == QueuedTransactionStatus::Queued {
+                turn = Turn::Wait;
+            }
+        }
+        Ok(turn)
+    }
+
+    pub fn mark_submitted(
+        &self,
+        key: &TransactionQueueKey,
+        nonce: U256,
+        tx_hash: H256,
+    ) -> Result<(), Error> {
+        self.update_active(key, nonce, |entry| {
+            entry.status = QueuedTransactionStatus::Submitted;
+            entry.tx_hash = Some(tx_hash);
+        })
+    }
+
+    pub fn mark_confirmed(
+        &self,
+        key: &TransactionQueueKey,
+        nonce: U256,
+    ) -> Result<(), Error> {
+        self.update_active(key, nonce, |entry| {
+            entry.status = QueuedTransactionStatus::Confirmed;
+        })
+    }
+
+    pub fn mark_failed(
+        &self,
+        key: &TransactionQueueKey,
+        nonce: U256,
+        error: String,
+    ) -> Result<(), Error> {
+        self.update_active(key, nonce, |entry| {
+            entry.status = QueuedTransactionStatus::Failed;
+            entry.error = Some(error);
+        })
+    }
+
+    /// Remove a failed transaction that blocks the queue.
+    pub fn cancel(&self, key: &TransactionQueueKey, nonce: U256) -> Result<(), Error> {
+        let mut queues = self.lock()?;
+        let queue = queues.get_mut(key).ok_or_else(|| not_found_error(nonce))?;
+        let index = queue
+            .iter()
+            .position(|entry| entry.nonce == nonce && entry.is_blocking())
+            .ok_or_else(|| not_found_error(nonce))?;
+        queue.remove(index);
+        Ok(())
+    }
+
+    /// Clear the transactions that haven't been confirmed from the pending nonce of the address
+    /// on the chain. This unblocks the queue if the local state got out of sync with the chain,
+    /// e.g. because transactions were dropped from the mempool or sent from another wallet.
+    /// Returns the number of cleared transactions.
+    pub fn reset(
+        &self,
+        key: &TransactionQueueKey,
+        pending_nonce: U256,
+    ) -> Result<usize, Error> {
+        let mut queues = self.lock()?;
+        let queue = queues.entry(key.clone()).or_default();
+        let mut cleared = 0;
+        for entry in queue.iter_mut().filter(|entry| {
+            entry.nonce >= pending_nonce
+                && matches!(
+                    entry.status,
+                    QueuedTransactionStatus::Queued
+                        | QueuedTransactionStatus::Submitted
+                        | QueuedTransactionStatus::Failed
+                        | QueuedTransactionStatus::Reserved
+                )
+        }) {
+            entry.status = QueuedTransactionStatus::Cleared;
+            entry.error = Some(
+                "Cleared by a nonce reset. Please submit the transaction again.".into(),
+            );
+            cleared += 1;
+        }
+        prune_finished(queue, pending_nonce);
+        Ok(cleared)
+    }
+
+    /// List the transactions in the queue ordered by nonce.
+    pub fn list(
+        &self,
+        key: &TransactionQueueKey,
+    ) -> Result<Vec<QueuedTransaction>, Error> {
+        let queues = self.lock()?;
+        let mut results = queues.get(key).cloned().unwrap_or_default();
+        // Stable sort keeps replaced transactions before their replacements.
+        results.sort_by_key(|entry| entry.nonce);
+        Ok(results)
+    }
+
+    /// The sum of the outflows of the transactions that haven't been confirmed yet. Failed
+    /// and replaced transactions don't spend from the balance.
+    pub fn pending_outflow(&self, key: &TransactionQueueKey) -> Result<U256, Error> {
+        let queues = self.lock()?;
+        let outflow = queues
+            .get(key)
+            .into_iter()
+            .flatten()
+            .filter(|entry| {
+                matches!(
+                    entry.status,
+                    QueuedTransactionStatus::Queued
+                        | QueuedTransactionStatus::Submitted
+                        | QueuedTransactionStatus::Reserved
+                )
+            })
+            .fold(U256::zero(), |sum, entry| sum.saturating_add(entry.outflow));
+        Ok(outflow)
+    }
+
+    /// Update the transaction with the nonce that hasn't been replaced or cleared.
+    fn update_active<F>(
+        &self,
+        key: &TransactionQueueKey,
+        nonce: U256,
+        update: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut QueuedTransaction),
+    {
+        let mut queues = self.lock()?;
+        let entry = queues.get_mut(key).and_then(|queue| {
+            queue
+                .iter_mut()
+                .find(|entry| entry.nonce == nonce && entry.is_active())
+        });
+        match entry {
+            Some(entry) => {
+                update(entry);
+                Ok(())
+            }
+            None => Err(not_found_error(nonce)),
+        }
+    }
+
+    /// Returns a guard that removes the transaction from the queue when it's dropped before
+    /// the transaction was submitted, eg. because the task sending it was aborted.
+    pub fn guard<'a>(
+        &'a self,
+        key: &'a TransactionQueueKey,
+        nonce: U256,
+    ) -> QueuedTransactionGuard<'a> {
+        QueuedTransactionGuard {
+            queue: self,
+            key,
+            nonce,
+        }
+    }
+
+    fn remove_queued(&self, key: &TransactionQueueKey, nonce: U256) -> Result<(), Error> {
+        let mut queues = self.lock()?;
+        if let Some(queue) = queues.get_mut(key) {
+            queue.retain(|entry| {
+                !(entry.nonce == nonce && entry.status == QueuedTransactionStatus::Queued)
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Removes a transaction from the queue on drop if it's still waiting to be submitted, so that
+/// it doesn't block higher nonce transactions.
+#[derive(Debug)]
+pub struct QueuedTransactionGuard<'a> {
+    queue: &'a TransactionQueue,
+    key: &'a TransactionQueueKey,
+    nonce: U256,
+}
+
+impl<'a> Drop for QueuedTransactionGuard<'a> {
+    fn drop(&mut self) {
+        if let Err(err) = self.queue.remove_queued(self.key, self.nonce) {
+            log::error!("Failed to remove queued transaction due to error: {err}");
+        }
+    }
+}
+
+/// The next nonce after the active transactions in the queue or the pending nonce, whichever
+/// is higher. Returns an error if a failed transaction blocks the queue.
+fn next_nonce(queue: &[QueuedTransaction], pending_nonce: U256) -> Result<U256, Error> {
+    if let Some(blocking) = queue
+        .iter()
+        .find(|entry| entry.is_blocking() && entry.nonce >= pending_nonce)
+    {
+        return Err(blocked_error(blocking.nonce));
+    }
+
+    let nonce = queue
+        .iter()
+        .filter(|entry| entry.is_active())
+        .map(|entry| entry.nonce + 1)
+        .max()
+        .unwrap_or_default()
+        .max(pending_nonce);
+    Ok(nonce)
+}
+
+/// Keep the most recent finished transactions whose nonce has been used on chain. Cleared
+/// and reserved transactions are removed once their nonce has been used on chain, because
+/// the queue doesn't track them after that.
+fn prune_finished(queue: &mut Vec<QueuedTransaction>, pending_nonce: U256) {
+    queue.retain(|entry| {
+        let is_untracked = matches!(
+            entry.status,
+            QueuedTransactionStatus::Cleared | QueuedTransactionStatus::Reserved
+        );
+        !(is_untracked && entry.nonce < pending_nonce)
+    });
+    let mut finished: Vec<U256> = queue
+        .iter()
+        .filter(|entry| entry.is_finished() && entry.nonce < pending_nonce)
+        .map(|entry| entry.nonce)
+        .collect();
+    if finished.len() > config::TX_QUEUE_MAX_FINISHED {
+        finished.sort();
+        let cutoff = finished[finished.len() - config::TX_QUEUE_MAX_FINISHED];
+        queue.retain(|entry| !(entry.is_finished() && entry.nonce < cutoff));
+    }
+}
+
+fn blocked_error(nonce: U256) -> Error {
+    Error::User {
+        explanation: format!(
+            "The transaction with nonce {nonce} failed. Please cancel or replace it before submitting new transactions."
+        ),
+    }
+}
+
+fn cleared_error(nonce: U256) -> Error {
+    Error::User {
+        explanation: format!(
+            "The transaction with nonce {nonce} was cleared by a nonce reset. Please submit it again."
+        ),
+    }
+}
+
+fn not_found_error(nonce: U256) -> Error {
+    Error::User {
+        explanation: format!("No matching transaction with nonce {nonce} in the queue."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::async_runtime as rt;
+
+    fn test_key() -> TransactionQueueKey {
+        TransactionQueueKey {
+            profile_id: "A".repeat(52).parse().expect("valid deterministic id"),
+            chain_id: ChainId::EthMainnet,
+            address: Address::repeat_byte(1),
+        }
+    }
+
+    fn statuses(queue: &TransactionQueue) -> Result<Vec<QueuedTransactionStatus>> {
+        let statuses = queue
+            .list(&test_key())?
+            .into_iter()
+            .map(|entry| entry.status)
+            .collect();
+        Ok(statuses)
+    }
+
+    #[test]
+    fn assigns_sequential_nonces() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        assert_eq!(queue.enqueue(&key, 5.into(), None, U256::zero())?, 5.into());
+        // Pending nonce on chain hasn't been updated yet.
+        assert_eq!(queue.enqueue(&key, 5.into(), None, U256::zero())?, 6.into());
+        // Transaction was sent from outside the queue.
+        assert_eq!(
+            queue.enqueue(&key, 10.into(), None, U256::zero())?,
+            10.into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn waits_for_lower_nonces() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        let second = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+
+        assert!(matches!(queue.turn(&key, first)?, Turn::Ready));
+        assert!(matches!(queue.turn(&key, second)?, Turn::Wait));
+
+        queue.mark_submitted(&key, first, H256::random())?;
+        rt::block_on(queue.wait_for_turn(&key, second))?;
+        queue.mark_submitted(&key, second, H256::random())?;
+        queue.mark_confirmed(&key, first)?;
+
+        assert_eq!(
+            statuses(&queue)?,
+            vec![
+                QueuedTransactionStatus::Confirmed,
+                QueuedTransactionStatus::Submitted
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn failed_submission_blocks_until_cancelled() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        let second = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        queue.mark_failed(&key, first, "insufficient funds".into())?;
+
+        let res = rt::block_on(queue.wait_for_turn(&key, second));
+        assert!(matches!(res, Err(Error::User { .. })));
+        let res = queue.enqueue(&key, 0.into(), None, U256::zero());
+        assert!(matches!(res, Err(Error::User { .. })));
+
+        queue.cancel(&key, first)?;
+        assert_eq!(queue.enqueue(&key, 0.into(), None, U256::zero())?, first);
+        Ok(())
+    }
+
+    #[test]
+    fn replaces_failed_transaction() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        queue.mark_failed(&key, first, "underpriced".into())?;
+
+        assert_eq!(
+            queue.enqueue(&key, 0.into(), Some(first), U256::zero())?,
+            first
+        );
+        assert_eq!(
+            statuses(&queue)?,
+            vec![
+                QueuedTransactionStatus::Replaced,
+                QueuedTransactionStatus::Queued
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn guard_removes_unsubmitted_transaction() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        let second = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        {
+            let _guard = queue.guard(&key, first);
+            queue.mark_submitted(&key, first, H256::random())?;
+        }
+        {
+            let _guard = queue.guard(&key, second);
+        }
+
+        assert_eq!(statuses(&queue)?, vec![QueuedTransactionStatus::Submitted]);
+        Ok(())
+    }
+
+    #[test]
+    fn failed_on_chain_does_not_block() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        queue.mark_submitted(&key, first, H256::random())?;
+        queue.mark_failed(&key, first, "reverted".into())?;
+
+        assert_eq!(queue.enqueue(&key, 1.into(), None, U256::zero())?, 1.into());
+        Ok(())
+    }
+
+    #[test]
+    fn reset_clears_waiting_transactions() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, 1.into())?;
+        let second = queue.enqueue(&key, 0.into(), None, 2.into())?;
+        queue.mark_submitted(&key, first, H256::random())?;
+        queue.mark_confirmed(&key, first)?;
+
+        assert_eq!(queue.reset(&key, 1.into())?, 1);
+        let res = rt::block_on(queue.wait_for_turn(&key, second));
+        assert!(matches!(res, Err(Error::User { .. })));
+        assert_eq!(queue.pending_outflow(&key)?, U256::zero());
+        assert_eq!(
+            statuses(&queue)?,
+            vec![
+                QueuedTransactionStatus::Confirmed,
+                QueuedTransactionStatus::Cleared
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prunes_cleared_transactions_once_nonce_is_used() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        assert_eq!(queue.reset(&key, 0.into())?, 2);
+        // The cleared nonces are reused.
+        assert_eq!(queue.enqueue(&key, 0.into(), None, U256::zero())?, 0.into());
+        assert_eq!(queue.list(&key)?.len(), 3);
+
+        queue.enqueue(&key, 2.into(), None, U256::zero())?;
+        assert_eq!(
+            statuses(&queue)?,
+            vec![
+                QueuedTransactionStatus::Queued,
+                QueuedTransactionStatus::Queued
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn removed_transaction_is_not_reported_as_cleared() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        queue.remove_queued(&key, first)?;
+
+        assert!(matches!(queue.turn(&key, first)?, Turn::Removed));
+        let res = rt::block_on(queue.wait_for_turn(&key, first));
+        match res {
+            Err(Error::User { explanation }) => {
+                assert!(!explanation.contains("cleared"), "{explanation}")
+            }
+            res => panic!("unexpected result: {res:?}"),
+        }
+
+        let second = queue.enqueue(&key, 0.into(), None, U256::zero())?;
+        queue.reset(&key, 0.into())?;
+        assert!(matches!(queue.turn(&key, second)?, Turn::Cleared));
+        Ok(())
+    }
+
+    #[test]
+    fn reserved_nonce_is_not_reused() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let reserved = queue.reserve(&key, 0.into(), 1.into())?;
+        let next = queue.enqueue(&key, 0.into(), None, 2.into())?;
+        assert_eq!(next, reserved + 1);
+        // Reserved transactions are broadcast outside the queue, so they don't block it.
+        assert!(matches!(queue.turn(&key, next)?, Turn::Ready));
+        assert_eq!(queue.pending_outflow(&key)?, 3.into());
+
+        // The reserved nonce was used on chain.
+        queue.enqueue(&key, 1.into(), None, U256::zero())?;
+        assert!(!statuses(&queue)?.contains(&QueuedTransactionStatus::Reserved));
+        Ok(())
+    }
+
+    #[test]
+    fn pending_outflow_counts_unconfirmed() -> Result<()> {
+        let queue = TransactionQueue::new();
+        let key = test_key();
+        let first = queue.enqueue(&key, 0.into(), None, 1.into())?;
+        let second = queue.enqueue(&key, 0.into(), None, 2.into())?;
+        let third = queue.enqueue(&key, 0.into(), None, 4.into())?;
+        assert_eq!(queue.pending_outflow(&key)?, 7.into());
+
+        queue.mark_submitted(&key, first, H256::random())?;
+        queue.mark_confirmed(&key, first)?;
+        queue.mark_submitted(&key, second, H256::random())?;
+        queue.mark_failed(&key, third, "underpriced".into())?;
+        assert_eq!(queue.pending_outflow(&key)?, 2.into());
+
+        // The replacement counts instead of the replaced transaction.
+        queue.enqueue(&key, 2.into(), Some(second), 8.into())?;
+        assert_eq!(queue.pending_outflow(&key)?, 8.into());
+
+        Ok(())
+    }
+}
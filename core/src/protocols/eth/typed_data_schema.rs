@@ -0,0 +1,166 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use ethers::types::transaction::eip712::{encode_type, TypedData};
+use lazy_static::lazy_static;
+
+/// EIP-712 schemas of well-known standards by their encoded primary type.
+/// The encoded type includes the referenced struct types, so the whole structure must match.
+/// See https://eips.ethereum.org/EIPS/eip-712#definition-of-encodetype
+const KNOWN_SCHEMAS: &[(&str, &str)] = &[
+    (
+        "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)",
+        "EIP-2612 Permit",
+    ),
+    (
+        "Permit(address holder,address spender,uint256 nonce,uint256 expiry,bool allowed)",
+        "DAI Permit",
+    ),
+    (
+        "PermitSingle(PermitDetails details,address spender,uint256 sigDeadline)\
+        PermitDetails(address token,uint160 amount,uint48 expiration,uint48 nonce)",
+        "Permit2 Allowance",
+    ),
+    (
+        "PermitBatch(PermitDetails[] details,address spender,uint256 sigDeadline)\
+        PermitDetails(address token,uint160 amount,uint48 expiration,uint48 nonce)",
+        "Permit2 Batch Allowance",
+    ),
+    (
+        "PermitTransferFrom(TokenPermissions permitted,address spender,uint256 nonce,\
+        uint256 deadline)TokenPermissions(address token,uint256 amount)",
+        "Permit2 Transfer",
+    ),
+    (
+        "OrderComponents(address offerer,address zone,OfferItem[] offer,\
+        ConsiderationItem[] consideration,uint8 orderType,uint256 startTime,\
+        uint256 endTime,bytes32 zoneHash,uint256 salt,bytes32 conduitKey,uint256 counter)\
+        ConsiderationItem(uint8 itemType,address token,uint256 identifierOrCriteria,\
+        uint256 startAmount,uint256 endAmount,address recipient)\
+        OfferItem(uint8 itemType,address token,uint256 identifierOrCriteria,\
+        uint256 startAmount,uint256 endAmount)",
+        "Seaport Order",
+    ),
+];
+
+lazy_static! {
+    pub static ref TYPED_DATA_SCHEMA_REGISTRY: TypedDataSchemaRegistry =
+        TypedDataSchemaRegistry::new();
+}
+
+/// Names of well-known EIP-712 schemas by their encoded primary type. Matching a known schema
+/// means that the message is a routine payload of a standard, while an unknown schema is a
+/// custom payload that the user should review with caution.
+#[derive(Debug, Clone)]
+pub struct TypedDataSchemaRegistry {
+    names: HashMap<String, String>,
+}
+
+impl TypedDataSchemaRegistry {
+    /// Registry with the known schemas.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            names: Default::default(),
+        };
+        for (encoded_type, name) in KNOWN_SCHEMAS {
+            registry.register(encoded_type, name);
+        }
+        registry
+    }
+
+    /// Register a schema by its EIP-712 encoded primary type.
+    pub fn register(&mut self, encoded_type: &str, name: &str) {
+        self.names.insert(encoded_type.into(), name.into());
+    }
+
+    /// The name of the standard if the structure of the typed data matches a known schema.
+    pub fn lookup(&self, typed_data: &TypedData) -> Option<&str> {
+        let encoded_type = encode_type(&typed_data.primary_type, &typed_data.types)
+            .map_err(|err| log::debug!("Failed to encode typed data type: {err}"))
+            .ok()?;
+        self.names.get(&encoded_type).map(|name| name.as_str())
+    }
+}
+
+impl Default for TypedDataSchemaRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::json;
+
+    use super::*;
+
+    fn typed_data(types: serde_json::Value, primary_type: &str) -> Result<TypedData> {
+        let data = json!({
+            "types": types,
+            "primaryType": primary_type,
+            "domain": {"name": "Test", "chainId": 1},
+            "message": {}
+        });
+        Ok(serde_json::from_value(data)?)
+    }
+
+    #[test]
+    fn recognizes_standard_permit() -> Result<()> {
+        let permit = typed_data(
+            json!({
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "chainId", "type": "uint256"}
+                ],
+                "Permit": [
+                    {"name": "owner", "type": "address"},
+                    {"name": "spender", "type": "address"},
+                    {"name": "value", "type": "uint256"},
+                    {"name": "nonce", "type": "uint256"},
+                    {"name": "deadline", "type": "uint256"}
+                ]
+            }),
+            "Permit",
+        )?;
+
+        assert_eq!(
+            TYPED_DATA_SCHEMA_REGISTRY.lookup(&permit),
+            Some("EIP-2612 Permit")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn flags_custom_schema_as_unknown() -> Result<()> {
+        let custom = typed_data(
+            json!({
+                "EIP712Domain": [{"name": "name", "type": "string"}],
+                "Mail": [{"name": "contents", "type": "string"}]
+            }),
+            "Mail",
+        )?;
+        // Same name as a standard, but with an extra field.
+        let extended_permit = typed_data(
+            json!({
+                "EIP712Domain": [{"name": "name", "type": "string"}],
+                "Permit": [
+                    {"name": "owner", "type": "address"},
+                    {"name": "spender", "type": "address"},
+                    {"name": "value", "type": "uint256"},
+                    {"name": "nonce", "type": "uint256"},
+                    {"name": "deadline", "type": "uint256"},
+                    {"name": "callback", "type": "address"}
+                ]
+            }),
+            "Permit",
+        )?;
+
+        assert_eq!(TYPED_DATA_SCHEMA_REGISTRY.lookup(&custom), None);
+        assert_eq!(TYPED_DATA_SCHEMA_REGISTRY.lookup(&extended_permit), None);
+        Ok(())
+    }
+}
@@ -2,11 +2,13 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+mod address_format;
 mod blockchain_protocol;
 pub mod eth;
 mod token_type;
 
 pub use crate::protocols::{
+    address_format::{AddressFormat, ChainAddress},
     blockchain_protocol::BlockchainProtocol,
     token_type::{FungibleTokenType, TokenType},
 };
@@ -4,11 +4,13 @@
 
 use std::{
     fmt::{Debug, Formatter},
+    net::IpAddr,
     str,
     time::Duration,
 };
 
 use publicsuffix::Psl;
+use url::Url;
 
 use crate::{assets::load_asset_as_string, config, Error};
 
@@ -40,12 +42,48 @@ impl PublicSuffixList {
         origin: &url::Origin,
     ) -> Result<RegistrableDomain, Error> {
         match origin {
-            url::Origin::Tuple(_, host, _) => self.registrable_domain_for_host(host),
+            url::Origin::Tuple(_, host, _) => self.registrable_domain_for_url_host(host),
             url::Origin::Opaque(_) => Ok(RegistrableDomain::Null),
         }
     }
 
-    fn registrable_domain_for_host(
+    /// The registrable domain for a bare host, a host with a port or an origin string.
+    /// IP addresses are returned without the port as `host_identifier` formats them, which is
+    /// the host in their dapp identifier. Returns None if the host has no registrable domain,
+    /// eg. `localhost` or a public suffix.
+    pub fn registrable_domain_for_host(
+        &self,
+        host: &str,
+    ) -> Result<Option<String>, Error> {
+        let host = host.trim();
+        // Bare IPv6 addresses can't be parsed as the host of an URL without brackets.
+        let url_host: url::Host = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => url::Host::Ipv4(ip),
+            Ok(IpAddr::V6(ip)) => url::Host::Ipv6(ip),
+            Err(_) => {
+                let url = if host.contains("://") {
+                    Url::parse(host)?
+                } else {
+                    Url::parse(&format!("http://{host}"))?
+                };
+                url.host()
+                    .ok_or_else(|| Error::Retriable {
+                        error: format!("No host in '{host}'"),
+                    })?
+                    .to_owned()
+            }
+        };
+        match url_host {
+            url::Host::Domain(_) => {
+                Ok(self.registrable_domain_for_url_host(&url_host)?.into())
+            }
+            url::Host::Ipv4(_) | url::Host::Ipv6(_) => {
+                Ok(Some(host_identifier(&url_host)))
+            }
+        }
+    }
+
+    fn registrable_domain_for_url_host(
         &self,
         host: &url::Host,
     ) -> Result<RegistrableDomain, Error> {
@@ -65,6 +103,16 @@ impl PublicSuffixList {
     }
 }
 
+/// The host as it appears in identifiers. Domains are lowercased and IPv6 addresses are
+/// bracketed as in URLs, so that the same host always maps to the same string.
+pub fn host_identifier<S: AsRef<str>>(host: &url::Host<S>) -> String {
+    match host {
+        url::Host::Domain(domain) => domain.as_ref().to_lowercase(),
+        url::Host::Ipv4(ip) => ip.to_string(),
+        url::Host::Ipv6(ip) => format!("[{ip}]"),
+    }
+}
+
 impl Debug for PublicSuffixList {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("PublicSuffixList")
@@ -187,8 +235,34 @@ mod tests {
         for (host, expected) in TEST_CASES.clone().into_iter() {
             let host = url::Host::<String>::parse(host)?;
 
-            assert_eq!(psl.registrable_domain_for_host(&host)?, expected);
+            assert_eq!(psl.registrable_domain_for_url_host(&host)?, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn registrable_domain_for_host_str() -> Result<()> {
+        let psl: PublicSuffixList = Default::default();
+        let test_cases = [
+            ("sub.example.co.uk", Some("example.co.uk")),
+            ("sub.example.co.uk:8443", Some("example.co.uk")),
+            ("https://sub.example.co.uk", Some("example.co.uk")),
+            ("127.0.0.1", Some("127.0.0.1")),
+            ("127.0.0.1:8080", Some("127.0.0.1")),
+            ("::1", Some("[::1]")),
+            ("[::1]:8080", Some("[::1]")),
+            ("http://[0:0::1]:8080", Some("[::1]")),
+            ("localhost", None),
+            ("localhost:3000", None),
+            ("co.uk", None),
+        ];
+
+        for (host, expected) in test_cases {
+            let res = psl.registrable_domain_for_host(host)?;
+            assert_eq!(res.as_deref(), expected, "{host}");
         }
+        assert!(psl.registrable_domain_for_host("").is_err());
 
         Ok(())
     }
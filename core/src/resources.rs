@@ -2,18 +2,29 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use typed_builder::TypedBuilder;
 
 use crate::{
-    backup::BackupStorageI,
+    assets::ProfilePicRegistry,
+    backup::{BackupSpool, BackupStorageI, BackupsInProgress, RestoreStatusTracker},
+    config,
     db::ConnectionPool,
     device::{DeviceIdentifier, DeviceName},
     encryption::Keychain,
+    fiat::{DefaultPriceFeed, FiatCurrency, PriceFeedI},
     http_client::HttpClient,
+    ipfs::IpfsGateways,
+    key_export::KeyExportConfirmations,
+    metrics::Metrics,
     protocols::eth,
     public_suffix_list::PublicSuffixList,
+    rng::{RngI, SystemRng},
     CoreUICallbackI,
 };
 
@@ -23,11 +34,49 @@ pub trait CoreResourcesI: Debug + Send + Sync {
     fn connection_pool(&self) -> &ConnectionPool;
     fn keychain(&self) -> &Keychain;
     fn http_client(&self) -> &HttpClient;
+    /// Gateways to fetch `ipfs://` content from.
+    fn ipfs_gateways(&self) -> &IpfsGateways;
     fn rpc_manager(&self) -> &dyn eth::RpcManagerI;
     fn public_suffix_list(&self) -> &PublicSuffixList;
     fn backup_storage(&self) -> &dyn BackupStorageI;
+    /// Local storage for backups that failed to upload.
+    fn backup_spool(&self) -> &BackupSpool;
+    /// DB backups of this core that are being created.
+    fn backups_in_progress(&self) -> &BackupsInProgress;
+    /// Status of the backup restores of this core.
+    fn restore_status(&self) -> &RestoreStatusTracker;
     fn device_id(&self) -> &DeviceIdentifier;
     fn device_name(&self) -> &DeviceName;
+    fn transaction_inspector(&self) -> &dyn eth::TransactionInspectorI;
+    fn transaction_queue(&self) -> &eth::TransactionQueue;
+    fn block_number_cache(&self) -> &eth::BlockNumberCache;
+    fn pending_requests(&self) -> &eth::in_page_provider::PendingRequests;
+    /// Pending private key export requests.
+    fn key_export_confirmations(&self) -> &KeyExportConfirmations;
+    /// Profile pictures registered by the host in addition to the bundled ones.
+    fn profile_pics(&self) -> &ProfilePicRegistry;
+    /// Aggregate usage counters since start.
+    fn metrics(&self) -> &Metrics;
+    /// Random generator for secrets.
+    fn rng(&self) -> &dyn RngI;
+    fn price_feed(&self) -> &dyn PriceFeedI;
+    /// The fiat currency for profiles that haven't set a preference.
+    fn default_fiat_currency(&self) -> &FiatCurrency;
+    /// In-page JSON-RPC methods that are rejected even if they're supported.
+    fn disabled_methods(&self) -> &[String];
+    /// How long to wait for the user to respond to an approval prompt.
+    fn approval_timeout(&self) -> Duration;
+    /// Calldata larger than this is not decoded for transaction approvals.
+    fn max_decoded_calldata_bytes(&self) -> usize;
+    /// Backups of DBs larger than this are neither created nor restored.
+    fn max_backup_size_bytes(&self) -> u64;
+    /// The version of the host app.
+    fn app_version(&self) -> &str;
+    /// Demo mode seeds sample data and doesn't submit anything to the network.
+    fn is_demo(&self) -> bool;
+    /// Whether the cache dir was writable at startup. Caching is skipped if it's not.
+    fn is_cache_dir_writable(&self) -> bool;
+    fn cache_dir(&self) -> &Path;
 }
 
 // All Send + Sync. Grouped in this struct to simplify getting an Arc to all.
@@ -38,11 +87,52 @@ pub struct CoreResources {
     connection_pool: ConnectionPool,
     keychain: Keychain,
     http_client: HttpClient,
+    #[builder(default)]
+    ipfs_gateways: IpfsGateways,
     rpc_manager: Box<dyn eth::RpcManagerI>,
     public_suffix_list: PublicSuffixList,
     backup_storage: Box<dyn BackupStorageI>,
+    backup_spool: BackupSpool,
+    #[builder(default)]
+    backups_in_progress: BackupsInProgress,
+    #[builder(default)]
+    restore_status: RestoreStatusTracker,
     device_name: DeviceName,
     device_id: DeviceIdentifier,
+    #[builder(default = Box::new(eth::DefaultTransactionInspector::new()))]
+    transaction_inspector: Box<dyn eth::TransactionInspectorI>,
+    #[builder(default)]
+    transaction_queue: eth::TransactionQueue,
+    #[builder(default)]
+    block_number_cache: eth::BlockNumberCache,
+    #[builder(default)]
+    pending_requests: eth::in_page_provider::PendingRequests,
+    #[builder(default)]
+    key_export_confirmations: KeyExportConfirmations,
+    #[builder(default)]
+    profile_pics: ProfilePicRegistry,
+    #[builder(default)]
+    metrics: Metrics,
+    #[builder(default = Box::new(SystemRng))]
+    rng: Box<dyn RngI>,
+    #[builder(default = Box::new(DefaultPriceFeed::new()))]
+    price_feed: Box<dyn PriceFeedI>,
+    #[builder(default = FiatCurrency::default_currency())]
+    default_fiat_currency: FiatCurrency,
+    #[builder(default)]
+    disabled_methods: Vec<String>,
+    #[builder(default = config::DEFAULT_APPROVAL_TIMEOUT)]
+    approval_timeout: Duration,
+    #[builder(default = config::DEFAULT_MAX_DECODED_CALLDATA_BYTES)]
+    max_decoded_calldata_bytes: usize,
+    #[builder(default = config::DEFAULT_MAX_BACKUP_SIZE_BYTES)]
+    max_backup_size_bytes: u64,
+    app_version: String,
+    #[builder(default)]
+    demo: bool,
+    #[builder(default = true)]
+    cache_dir_writable: bool,
+    cache_dir: PathBuf,
 }
 
 impl CoreResourcesI for CoreResources {
@@ -62,6 +152,10 @@ impl CoreResourcesI for CoreResources {
         &self.http_client
     }
 
+    fn ipfs_gateways(&self) -> &IpfsGateways {
+        &self.ipfs_gateways
+    }
+
     fn rpc_manager(&self) -> &dyn eth::RpcManagerI {
         &*self.rpc_manager
     }
@@ -74,6 +168,18 @@ impl CoreResourcesI for CoreResources {
         &*self.backup_storage
     }
 
+    fn backup_spool(&self) -> &BackupSpool {
+        &self.backup_spool
+    }
+
+    fn backups_in_progress(&self) -> &BackupsInProgress {
+        &self.backups_in_progress
+    }
+
+    fn restore_status(&self) -> &RestoreStatusTracker {
+        &self.restore_status
+    }
+
     fn device_id(&self) -> &DeviceIdentifier {
         &self.device_id
     }
@@ -81,4 +187,76 @@ impl CoreResourcesI for CoreResources {
     fn device_name(&self) -> &DeviceName {
         &self.device_name
     }
+
+    fn transaction_inspector(&self) -> &dyn eth::TransactionInspectorI {
+        &*self.transaction_inspector
+    }
+
+    fn transaction_queue(&self) -> &eth::TransactionQueue {
+        &self.transaction_queue
+    }
+
+    fn block_number_cache(&self) -> &eth::BlockNumberCache {
+        &self.block_number_cache
+    }
+
+    fn pending_requests(&self) -> &eth::in_page_provider::PendingRequests {
+        &self.pending_requests
+    }
+
+    fn key_export_confirmations(&self) -> &KeyExportConfirmations {
+        &self.key_export_confirmations
+    }
+
+    fn profile_pics(&self) -> &ProfilePicRegistry {
+        &self.profile_pics
+    }
+
+    fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    fn rng(&self) -> &dyn RngI {
+        &*self.rng
+    }
+
+    fn price_feed(&self) -> &dyn PriceFeedI {
+        &*self.price_feed
+    }
+
+    fn default_fiat_currency(&self) -> &FiatCurrency {
+        &self.default_fiat_currency
+    }
+
+    fn disabled_methods(&self) -> &[String] {
+        &self.disabled_methods
+    }
+
+    fn approval_timeout(&self) -> Duration {
+        self.approval_timeout
+    }
+
+    fn max_decoded_calldata_bytes(&self) -> usize {
+        self.max_decoded_calldata_bytes
+    }
+
+    fn max_backup_size_bytes(&self) -> u64 {
+        self.max_backup_size_bytes
+    }
+
+    fn app_version(&self) -> &str {
+        &self.app_version
+    }
+
+    fn is_demo(&self) -> bool {
+        self.demo
+    }
+
+    fn is_cache_dir_writable(&self) -> bool {
+        self.cache_dir_writable
+    }
+
+    fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
 }
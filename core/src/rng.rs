@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Debug;
+
+use generic_array::{ArrayLength, GenericArray};
+use rand::{thread_rng, CryptoRng, RngCore};
+
+use crate::Error;
+
+/// Source of randomness for secrets. Lets tests inject a deterministic generator.
+pub trait RngI: Debug + Send + Sync {
+    /// Fails if the underlying generator fails.
+    fn try_fill_bytes(&self, buffer: &mut [u8]) -> Result<(), Error>;
+}
+
+/// The thread local CSPRNG seeded by the OS.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemRng;
+
+impl RngI for SystemRng {
+    fn try_fill_bytes(&self, buffer: &mut [u8]) -> Result<(), Error> {
+        thread_rng()
+            .try_fill_bytes(buffer)
+            .map_err(|err| Error::Fatal {
+                error: format!(
+                    "Failed to generate random bytes with error code: {:?}",
+                    err.code()
+                ),
+            })
+    }
+}
+
+/// Generate random bytes for a generic array with the provided generator.
+pub fn try_random_bytes_with<N: ArrayLength<u8>>(
+    rng: &dyn RngI,
+) -> Result<GenericArray<u8, N>, Error> {
+    let mut buffer: GenericArray<u8, N> = Default::default();
+    rng.try_fill_bytes(buffer.as_mut_slice())?;
+    Ok(buffer)
+}
+
+/// Adapter to use an `RngI` where the `rand` traits are expected, eg. key generation.
+pub struct RngAdapter<'a>(pub &'a dyn RngI);
+
+impl<'a> RngCore for RngAdapter<'a> {
+    fn next_u32(&mut self) -> u32 {
+        let mut buffer = [0u8; 4];
+        self.fill_bytes(&mut buffer);
+        u32::from_le_bytes(buffer)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buffer = [0u8; 8];
+        self.fill_bytes(&mut buffer);
+        u64::from_le_bytes(buffer)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        // Panicking on failure is the documented behaviour of `RngCore::fill_bytes`.
+        self.0
+            .try_fill_bytes(dest)
+            .expect("random generator doesn't fail")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest).map_err(rand::Error::new)
+    }
+}
+
+// The adapted generators are either the system CSPRNG or test only.
+impl<'a> CryptoRng for RngAdapter<'a> {}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Mutex;
+
+    use anyhow::Result;
+    use generic_array::typenum::U16;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// Seedable generator for reproducible tests. Must never be used in production.
+    #[derive(Debug)]
+    pub struct DeterministicRng {
+        rng: Mutex<StdRng>,
+    }
+
+    impl DeterministicRng {
+        pub fn new(seed: u64) -> Self {
+            Self {
+                rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            }
+        }
+    }
+
+    impl RngI for DeterministicRng {
+        fn try_fill_bytes(&self, buffer: &mut [u8]) -> Result<(), Error> {
+            let mut rng = self.rng.lock()?;
+            rng.fill_bytes(buffer);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn deterministic_rng_is_reproducible() -> Result<()> {
+        let a: GenericArray<u8, U16> = try_random_bytes_with(&DeterministicRng::new(1))?;
+        let b: GenericArray<u8, U16> = try_random_bytes_with(&DeterministicRng::new(1))?;
+        let c: GenericArray<u8, U16> = try_random_bytes_with(&DeterministicRng::new(2))?;
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        Ok(())
+    }
+
+    #[test]
+    fn system_rng_is_not_default() -> Result<()> {
+        let bytes: GenericArray<u8, U16> = try_random_bytes_with(&SystemRng)?;
+        assert_ne!(bytes, GenericArray::<u8, U16>::default());
+        Ok(())
+    }
+}
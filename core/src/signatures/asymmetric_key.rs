@@ -12,10 +12,11 @@ use k256::elliptic_curve::{CurveArithmetic, PublicKey};
 // Must depend on k256 instead of elliptic_curve, because there are dependency resolution conflicts
 // when specifying elliptic_curve as dependency directly.
 use k256::pkcs8::{AssociatedOid, EncodePublicKey};
-use rand::thread_rng;
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::{
     encryption::{DataEncryptionKey, EncryptionOutput},
+    rng::{RngAdapter, RngI, SystemRng},
     signatures::elliptic_curve::EllipticCurve,
     Error,
 };
@@ -54,12 +55,16 @@ where
     }
 
     pub fn random() -> Result<AsymmetricKey<C>, Error> {
+        Self::random_with(&SystemRng)
+    }
+
+    pub fn random_with(rng: &dyn RngI) -> Result<AsymmetricKey<C>, Error> {
         // TODO the compiler could allocate `SecretKey` on the stack before moving it into the `Box`
         // which would leave a copy on the stack that won't be zeroized. Box::pin wouldn't help
         // here, because because the `SecretKey` would be still created on the stack first before
         // moving it in the box.
         // Also, we should be using the fallible RNG interface.
-        let mut csprng = thread_rng();
+        let mut csprng = RngAdapter(rng);
         let secret_key = Box::new(SecretKey::random(&mut csprng));
         Self::new(secret_key)
     }
@@ -80,6 +85,14 @@ where
         Self::new(secret_key)
     }
 
+    /// Hex encoded secret key. Only for exporting the key on explicit request of the user.
+    pub fn secret_key_hex(&self) -> Zeroizing<String> {
+        let mut bytes = self.secret_key.to_bytes();
+        let secret_hex = Zeroizing::new(hex::encode(bytes.as_slice()));
+        bytes.as_mut_slice().zeroize();
+        secret_hex
+    }
+
     pub fn to_encrypted_der(
         &self,
         key: &DataEncryptionKey,
@@ -109,7 +122,7 @@ mod tests {
     use k256::Secp256k1;
 
     use super::*;
-    use crate::encryption::KeyName;
+    use crate::{encryption::KeyName, rng::tests::DeterministicRng};
 
     #[test]
     fn encrypt_decrypt() -> Result<()> {
@@ -123,4 +136,14 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn reproducible_with_deterministic_rng() -> Result<()> {
+        let kp: AsymmetricKey<Secp256k1> =
+            AsymmetricKey::random_with(&DeterministicRng::new(42))?;
+        let same_kp: AsymmetricKey<Secp256k1> =
+            AsymmetricKey::random_with(&DeterministicRng::new(42))?;
+        assert_eq!(kp, same_kp);
+        Ok(())
+    }
 }
@@ -67,6 +67,6 @@ mod tests {
 
     #[test]
     fn to_string() {
-        assert_eq!(EllipticCurve::Secp256k1.to_string(), "Secp256k1")
+        assert_eq!(EllipticCurve::Secp256k1.to_string(), "Secp256k1");
     }
 }
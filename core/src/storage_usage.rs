@@ -0,0 +1,146 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{fs, path::Path};
+
+use crate::{
+    db::{db_size_bytes, table_sizes_bytes},
+    resources::CoreResourcesI,
+    Error,
+};
+
+const PROFILE_PICTURE_TABLES: &[&str] = &["profile_pictures"];
+const TRANSACTION_TABLES: &[&str] = &["transactions"];
+const DAPP_AND_KEY_TABLES: &[&str] = &[
+    "addresses",
+    "asymmetric_keys",
+    "dapp_favicons",
+    "dapp_metadata",
+    "dapp_storage",
+    "dapp_tags",
+    "dapps",
+    "data_encryption_keys",
+    "local_dapp_sessions",
+    "local_dapp_spends",
+    "local_encrypted_deks",
+];
+
+/// Storage usage in bytes by category. Exposed through FFI to UI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageBreakdown {
+    pub profile_pictures: u64,
+    pub transactions: u64,
+    pub dapps_and_keys: u64,
+    /// The rest of the DB including settings, tokens and free pages.
+    pub other: u64,
+    /// The HTTP cache and pending backups in the cache dir.
+    pub cache: u64,
+}
+
+impl StorageBreakdown {
+    /// The size of the DB which is what goes into a backup.
+    pub fn db_total(&self) -> u64 {
+        self.profile_pictures + self.transactions + self.dapps_and_keys + self.other
+    }
+}
+
+pub fn storage_breakdown(
+    resources: &dyn CoreResourcesI,
+) -> Result<StorageBreakdown, Error> {
+    let mut conn = resources.connection_pool().connection()?;
+    let db_size = db_size_bytes(&mut conn)?;
+    let table_sizes = table_sizes_bytes(&mut conn)?;
+
+    let category_size = |tables: &[&str]| -> u64 {
+        tables
+            .iter()
+            .filter_map(|table| table_sizes.get(*table))
+            .sum()
+    };
+    let profile_pictures = category_size(PROFILE_PICTURE_TABLES);
+    let transactions = category_size(TRANSACTION_TABLES);
+    let dapps_and_keys = category_size(DAPP_AND_KEY_TABLES);
+    let other = db_size.saturating_sub(profile_pictures + transactions + dapps_and_keys);
+
+    Ok(StorageBreakdown {
+        profile_pictures,
+        transactions,
+        dapps_and_keys,
+        other,
+        cache: dir_size_bytes(resources.cache_dir()),
+    })
+}
+
+/// The total size of the files in the directory recursively. Doesn't follow symlinks and
+/// skips entries that can't be read, since the cache can change while it's walked.
+fn dir_size_bytes(path: &Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!("Failed to read dir '{}': {err}", path.display());
+            return 0;
+        }
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = fs::symlink_metadata(entry.path()).ok()?;
+            if metadata.is_dir() {
+                Some(dir_size_bytes(&entry.path()))
+            } else if metadata.is_file() {
+                Some(metadata.len())
+            } else {
+                None
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{app_core::tests::TmpCore, assets::load_profile_pic};
+
+    #[test]
+    fn large_picture_increases_pictures_category() -> Result<()> {
+        let tmp = TmpCore::new()?;
+        let initial = tmp.core.storage_breakdown()?;
+
+        let padding_bytes = 200_000;
+        let mut picture = load_profile_pic("seal-3")?;
+        picture.extend(vec![1u8; padding_bytes]);
+        let picture_name = "storage-breakdown-test".to_string();
+        tmp.core.register_profile_pictures(HashMap::from([(
+            picture_name.clone(),
+            picture,
+        )]))?;
+        tmp.core.create_profile("foo".into(), picture_name)?;
+
+        let breakdown = tmp.core.storage_breakdown()?;
+        assert!(
+            breakdown.profile_pictures >= initial.profile_pictures + padding_bytes as u64
+        );
+        assert!(breakdown.db_total() >= breakdown.profile_pictures);
+
+        Ok(())
+    }
+
+    #[test]
+    fn counts_cache_files() -> Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let nested = tmp_dir.path().join("nested");
+        fs::create_dir(&nested)?;
+        fs::write(tmp_dir.path().join("a"), [0u8; 10])?;
+        fs::write(nested.join("b"), [0u8; 20])?;
+
+        assert_eq!(dir_size_bytes(tmp_dir.path()), 30);
+        assert_eq!(dir_size_bytes(&tmp_dir.path().join("missing")), 0);
+
+        Ok(())
+    }
+}
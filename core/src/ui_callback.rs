@@ -6,6 +6,8 @@ use std::fmt::Debug;
 
 use typed_builder::TypedBuilder;
 
+use crate::protocols::eth::CurrencyAmount;
+
 pub trait CoreUICallbackI: Send + Sync + Debug {
     /// When a token transfer initiated by the user was sent to the blockchain API.
     fn sent_token_transfer(&self, result: TokenTransferResult);
@@ -19,16 +21,20 @@ pub trait CoreUICallbackI: Send + Sync + Debug {
     fn approved_dapp_transaction(&self, result: DappTransactionApproved);
     /// The result of a transaction that was auto-approved by the in-page provider.
     fn dapp_transaction_result(&self, result: DappTransactionResult);
+    /// The user didn't respond in time to the approval prompt for the JSON-RPC request and the
+    /// request was rejected. The host should dismiss the prompt.
+    fn approval_timed_out(&self, json_rpc_request: String);
+    /// Ask the user to authenticate with biometrics or the device passcode before a sensitive
+    /// operation. The reason is presented to the user. Blocks until the user responds and
+    /// returns whether the user authenticated.
+    fn authenticate_user(&self, reason: String) -> bool;
 }
 
 #[derive(Clone, Debug, TypedBuilder)]
 pub struct TokenTransferResult {
-    /// The amount that was transferred.
-    #[builder(setter(into))]
-    pub amount: String,
-    /// The symbol of the token that was transferred.
+    /// The amount and the token that was transferred.
     #[builder(setter(into))]
-    pub token_symbol: String,
+    pub amount: CurrencyAmount,
     /// The displayable name of the chain where the token was transferred.
     #[builder(setter(into))]
     pub chain_display_name: String,
@@ -49,12 +55,9 @@ pub struct DappAllotmentTransferResult {
     /// A human readable dapp identifier that can be presented to the user.
     #[builder(setter(into))]
     pub dapp_identifier: String,
-    /// The amount that was transferred.
+    /// The amount and the token that was transferred.
     #[builder(setter(into))]
-    pub amount: String,
-    /// The symbol of the token that was transferred.
-    #[builder(setter(into))]
-    pub token_symbol: String,
+    pub amount: CurrencyAmount,
     /// The displayable name of the chain where the token was transferred.
     #[builder(setter(into))]
     pub chain_display_name: String,
@@ -69,6 +72,29 @@ pub struct DappSignatureResult {
     /// A human readable dapp identifier that can be presented to the user.
     #[builder(setter(into))]
     pub dapp_identifier: String,
+    /// The contract from the EIP-712 domain if it's a typed data signature with a verifying
+    /// contract.
+    #[builder(default)]
+    pub verifying_contract: Option<VerifyingContract>,
+    /// The name of the standard if it's a typed data signature that matches a well-known
+    /// schema, eg. "EIP-2612 Permit". None for custom schemas and other signatures.
+    /// The user is informed about the schema before signing by `DappSignBatchItem`.
+    #[builder(default)]
+    pub known_schema: Option<String>,
+}
+
+/// The contract that verifies a typed data signature.
+#[derive(Clone, Debug, PartialEq, Eq, TypedBuilder)]
+pub struct VerifyingContract {
+    #[builder(setter(into))]
+    pub checksum_address: String,
+    /// Human readable name if it's a well-known contract. The UI should show the address with a
+    /// caution if there is no label and the contract isn't known to the dapp.
+    #[builder(default)]
+    pub label: Option<String>,
+    /// Whether the dapp has sent transactions to the contract before.
+    #[builder(default)]
+    pub known_to_dapp: bool,
 }
 
 #[derive(Clone, Debug, TypedBuilder)]
@@ -79,6 +105,25 @@ pub struct DappTransactionApproved {
     /// The displayable name of the chain where the transaction was approved.
     #[builder(setter(into))]
     pub chain_display_name: String,
+    /// The native token amount sent with the transaction.
+    #[builder(setter(into))]
+    pub amount: String,
+    /// The symbol of the native token of the chain.
+    #[builder(setter(into))]
+    pub token_symbol: String,
+    /// Whether the amount is above the high value threshold of the profile.
+    #[builder(default)]
+    pub high_value: bool,
+    /// Safety warnings about the transaction from the transaction inspector.
+    #[builder(default)]
+    pub warnings: Vec<TransactionWarning>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, TypedBuilder)]
+pub struct TransactionWarning {
+    /// A warning message that can be presented to the user.
+    #[builder(setter(into))]
+    pub message: String,
 }
 
 #[derive(Clone, Debug, TypedBuilder)]
@@ -2,10 +2,15 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::{collections::HashSet, path::Path, time::SystemTime};
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
 
 use chacha20poly1305::aead::generic_array::{ArrayLength, GenericArray};
-use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use chrono::{DateTime, FixedOffset, SecondsFormat, TimeZone, Utc};
 use email_address::EmailAddress;
 use lazy_static::lazy_static;
 use rand::{thread_rng, RngCore};
@@ -23,6 +28,27 @@ pub fn rfc3339_timestamp() -> String {
     dt.to_rfc3339_opts(SecondsFormat::Millis, true)
 }
 
+/// Create an RFC339 timestamp for the given number of seconds before now.
+/// The result can be compared as string with timestamps from `rfc3339_timestamp`.
+pub fn rfc3339_timestamp_seconds_ago(seconds: u64) -> String {
+    let then = SystemTime::now()
+        .checked_sub(Duration::from_secs(seconds))
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let dt: DateTime<Utc> = then.into();
+    dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Create an RFC3339 timestamp in the same format as `rfc3339_timestamp` from a unix timestamp.
+pub fn rfc3339_timestamp_from_unix(seconds: i64) -> Result<String, Error> {
+    let dt = Utc
+        .timestamp_opt(seconds, 0)
+        .single()
+        .ok_or_else(|| Error::User {
+            explanation: format!("Invalid unix timestamp: {seconds}"),
+        })?;
+    Ok(dt.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
 pub fn parse_rfc3339_timestamp(s: &str) -> Result<DateTime<FixedOffset>, Error> {
     DateTime::parse_from_rfc3339(s).map_err(|err| Error::Retriable {
         error: err.to_string(),
@@ -49,6 +75,26 @@ pub fn tmp_file() -> Result<NamedTempFile, Error> {
     })
 }
 
+/// Whether files can be created in the directory. Creates the directory if it doesn't exist.
+pub fn is_dir_writable(dir: &Path) -> bool {
+    fs::create_dir_all(dir)
+        .and_then(|_| NamedTempFile::new_in(dir))
+        .map_err(|err| log::warn!("Directory is not writable: '{err}'"))
+        .is_ok()
+}
+
+/// Whether the URL is HTTPS or HTTP on the loopback interface without credentials.
+pub fn is_https_or_loopback(url: &Url) -> bool {
+    let is_loopback = match url.host() {
+        Some(url::Host::Domain(domain)) => domain == "localhost",
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    };
+    let is_secure = url.scheme() == "https" || (url.scheme() == "http" && is_loopback);
+    is_secure && url.has_host() && url.username().is_empty() && url.password().is_none()
+}
+
 /// Generate a v4 UUID.
 pub fn new_uuid() -> String {
     Uuid::new_v4().to_string()
@@ -0,0 +1,93 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    backup::BackupScheme,
+    db::{models as m, schema_migrations},
+    resources::CoreResourcesI,
+    Error,
+};
+
+/// Versions of the app, the database and the backup for diagnostics and bug reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    /// The version of the core library.
+    pub crate_version: String,
+    /// The latest applied schema migration, eg. `20230316090000`.
+    pub schema_migration_version: String,
+    /// The latest applied data migration, eg. `v3`.
+    pub data_migration_version: Option<String>,
+    /// The oldest backup scheme that can be restored.
+    pub min_backup_scheme: String,
+    /// The backup scheme of new backups.
+    pub max_backup_scheme: String,
+    /// The backup version of the device.
+    pub backup_version: i64,
+    /// Whether the core runs in demo mode with sample data.
+    pub demo: bool,
+    /// Whether the cache dir was writable at startup. Nothing is cached if it's not.
+    pub cache_dir_writable: bool,
+}
+
+/// Collect the version info. Only reads from the database.
+pub fn version_info(resources: &dyn CoreResourcesI) -> Result<VersionInfo, Error> {
+    let mut conn = resources.connection_pool().connection()?;
+
+    let schema_migration_version = schema_migrations::latest_applied_version(&mut conn)?
+        .ok_or_else(|| Error::Fatal {
+            error: "No schema migrations were applied".into(),
+        })?;
+    let data_migration_version = m::DataMigration::list_versions_sorted(&mut conn)?
+        .pop()
+        .map(Into::into);
+    let backup_version = m::LocalSettings::fetch_backup_version(&mut conn)?;
+
+    let mut schemes = BackupScheme::iter();
+    let min_backup_scheme = schemes.next().expect("there is a backup scheme");
+    let max_backup_scheme = schemes.last().unwrap_or(min_backup_scheme);
+
+    Ok(VersionInfo {
+        crate_version: env!("CARGO_PKG_VERSION").into(),
+        schema_migration_version,
+        data_migration_version,
+        min_backup_scheme: min_backup_scheme.to_string(),
+        max_backup_scheme: max_backup_scheme.to_string(),
+        backup_version: backup_version.into(),
+        demo: resources.is_demo(),
+        cache_dir_writable: resources.is_cache_dir_writable(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use diesel_migrations::MigrationSource;
+
+    use super::*;
+    use crate::app_core::tests::TmpCore;
+
+    #[test]
+    fn schema_migration_version_matches_applied_migrations() -> Result<()> {
+        let core = TmpCore::new()?;
+        let migrations = MigrationSource::<diesel::sqlite::Sqlite>::migrations(
+            &schema_migrations::MIGRATIONS,
+        )
+        .expect("embedded migrations are valid");
+        let latest = migrations
+            .iter()
+            .map(|migration| migration.name().version().to_string())
+            .max()
+            .expect("there are migrations");
+
+        let info = core.core.version_info()?;
+
+        assert_eq!(info.schema_migration_version, latest);
+        assert_eq!(info.data_migration_version, core.data_migration_version()?);
+        assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.min_backup_scheme, "v1");
+        Ok(())
+    }
+}
@@ -80,11 +80,11 @@ struct AppTabNavigationInner: View {
                 return
             }
             if let errorMessage = res.errorMessage {
-                let title = "Failed to transfer \(res.amount) \(res.tokenSymbol) to \(res.displayTo())"
+                let title = "Failed to transfer \(res.amount.display()) \(res.amount.symbol) to \(res.displayTo())"
                 let detail = "Error on \(res.chainDisplayName): \(errorMessage)"
                 bannerModel.bannerData = BannerData(title: title, detail: detail, type: .error)
             } else {
-                let title = "Sent \(res.amount) \(res.tokenSymbol) to \(res.displayTo())"
+                let title = "Sent \(res.amount.display()) \(res.amount.symbol) to \(res.displayTo())"
                 let details = "On \(res.chainDisplayName)"
                 bannerModel.bannerData = BannerData(title: title, detail: details, type: .success)
             }
@@ -94,11 +94,11 @@ struct AppTabNavigationInner: View {
                 return
             }
             if let errorMessage = res.errorMessage {
-                let title = "Failed to transfer \(res.amount) \(res.tokenSymbol) to \(res.displayTo())"
+                let title = "Failed to transfer \(res.amount.display()) \(res.amount.symbol) to \(res.displayTo())"
                 let detail = "Error on \(res.chainDisplayName): \(errorMessage)"
                 bannerModel.bannerData = BannerData(title: title, detail: detail, type: .error)
             } else {
-                let title = "Confirmed \(res.amount) \(res.tokenSymbol) to \(res.displayTo())"
+                let title = "Confirmed \(res.amount.display()) \(res.amount.symbol) to \(res.displayTo())"
                 let details = "On \(res.chainDisplayName)"
                 bannerModel.bannerData = BannerData(title: title, detail: details, type: .success)
             }
@@ -108,11 +108,11 @@ struct AppTabNavigationInner: View {
                 return
             }
             if let errorMessage = res.errorMessage {
-                let title = "Failed to transfer \(res.amount) \(res.tokenSymbol) to \(res.dappIdentifier) address"
+                let title = "Failed to transfer \(res.amount.display()) \(res.amount.symbol) to \(res.dappIdentifier) address"
                 let detail = "Error on \(res.chainDisplayName): \(errorMessage)"
                 bannerModel.bannerData = BannerData(title: title, detail: detail, type: .error)
             } else {
-                let title = "Confirmed \(res.amount) \(res.tokenSymbol) to \(res.dappIdentifier) address"
+                let title = "Confirmed \(res.amount.display()) \(res.amount.symbol) to \(res.dappIdentifier) address"
                 let details = "On \(res.chainDisplayName)"
                 bannerModel.bannerData = BannerData(title: title, detail: details, type: .success)
             }
@@ -122,8 +122,16 @@ struct AppTabNavigationInner: View {
                 return
             }
             let title = "Approved signature for \(res.dappIdentifier)"
-            let detail = "Automatic approval is safe because it has its own address."
-            bannerModel.bannerData = BannerData(title: title, detail: detail, type: .success)
+            if let contract = res.verifyingContract, contract.label == nil, !contract.knownToDapp {
+                let detail = "Signed for unknown contract \(contract.checksumAddress). Be cautious."
+                bannerModel.bannerData = BannerData(title: title, detail: detail, type: .warning)
+            } else if let schema = res.knownSchema {
+                let detail = "Signed a standard \(schema) message."
+                bannerModel.bannerData = BannerData(title: title, detail: detail, type: .success)
+            } else {
+                let detail = "Automatic approval is safe because it has its own address."
+                bannerModel.bannerData = BannerData(title: title, detail: detail, type: .success)
+            }
         }
         .onChange(of: callbackModel.dappTransactionApproved) { val in
             guard let res = val else {
@@ -221,7 +229,7 @@ struct AppTabNavigation_Previews: PreviewProvider {
         let callbackTokenSent = CallbackModel()
         DispatchQueue.main.asyncAfter(deadline: .now() + .seconds(2)) {
             callbackTokenSent.tokenTransferSent = TokenTransferResult(
-                amount: "0.1", tokenSymbol: "MATIC", chainDisplayName: "Polygon PoS",
+                amount: .previewMatic, chainDisplayName: "Polygon PoS",
                 toDisplayName: "Default Profile Wallet", explorerUrl: nil, errorMessage: nil
             )
         }
@@ -229,7 +237,7 @@ struct AppTabNavigation_Previews: PreviewProvider {
         let callbackTokenTransferError = CallbackModel()
         DispatchQueue.main.asyncAfter(deadline: .now() + .seconds(2)) {
             callbackTokenTransferError.tokenTransferSent = TokenTransferResult(
-                amount: "0.1", tokenSymbol: "MATIC", chainDisplayName: "Polygon PoS",
+                amount: .previewMatic, chainDisplayName: "Polygon PoS",
                 toDisplayName: "Default Profile Wallet", explorerUrl: nil, errorMessage: "insufficient funds"
             )
         }
@@ -237,7 +245,7 @@ struct AppTabNavigation_Previews: PreviewProvider {
         let callbackTokenTransferConfirmed = CallbackModel()
         DispatchQueue.main.asyncAfter(deadline: .now() + .seconds(2)) {
             callbackTokenTransferConfirmed.tokenTransferResult = TokenTransferResult(
-                amount: "0.1", tokenSymbol: "MATIC", chainDisplayName: "Polygon PoS",
+                amount: .previewMatic, chainDisplayName: "Polygon PoS",
                 toDisplayName: "Default Profile Wallet", explorerUrl: explorerUrl, errorMessage: nil
             )
         }
@@ -245,7 +253,7 @@ struct AppTabNavigation_Previews: PreviewProvider {
         let callbackDappAllotmentSuccess = CallbackModel()
         DispatchQueue.main.asyncAfter(deadline: .now() + .seconds(2)) {
             callbackDappAllotmentSuccess.dappAllotmentResult = DappAllotmentTransferResult(
-                dappIdentifier: "example.com", amount: "0.1", tokenSymbol: "MATIC",
+                dappIdentifier: "example.com", amount: .previewMatic,
                 chainDisplayName: "Polygon PoS", errorMessage: nil
             )
         }
@@ -253,7 +261,7 @@ struct AppTabNavigation_Previews: PreviewProvider {
         let callbackDappAllotmentError = CallbackModel()
         DispatchQueue.main.asyncAfter(deadline: .now() + .seconds(2)) {
             callbackDappAllotmentError.dappAllotmentResult = DappAllotmentTransferResult(
-                dappIdentifier: "example.com", amount: "0.1", tokenSymbol: "MATIC",
+                dappIdentifier: "example.com", amount: .previewMatic,
                 chainDisplayName: "Polygon PoS", errorMessage: "insufficient funds"
             )
         }
@@ -261,14 +269,15 @@ struct AppTabNavigation_Previews: PreviewProvider {
         let callbackSignedMessage = CallbackModel()
         DispatchQueue.main.asyncAfter(deadline: .now() + .seconds(2)) {
             callbackSignedMessage.dappSignatureResult = DappSignatureResult(
-                dappIdentifier: "example.com"
+                dappIdentifier: "example.com", verifyingContract: nil, knownSchema: nil
             )
         }
 
         let callbackSentTransaction = CallbackModel()
         DispatchQueue.main.asyncAfter(deadline: .now() + .seconds(2)) {
             callbackSentTransaction.dappTransactionApproved = DappTransactionApproved(
-                dappIdentifier: "example.com", chainDisplayName: "Polygon PoS"
+                dappIdentifier: "example.com", chainDisplayName: "Polygon PoS", amount: "0.1",
+                tokenSymbol: "MATIC", highValue: false, warnings: []
             )
         }
 
@@ -18,6 +18,10 @@ class BrowserModel: ObservableObject {
     @Published var goForward: Bool = false
     @Published var dappApprovalRequest: DappApprovalRequest?
     @Published var dappApprovalPresented = false
+    @Published var dappTransactionApprovalRequest: DappTransactionApprovalRequest?
+    @Published var dappTransactionApprovalPresented = false
+    @Published var dappSignBatchApprovalRequest: DappSignBatchApprovalRequest?
+    @Published var dappSignBatchApprovalPresented = false
     @Published var loadingProgress: Double = 0.0
     @Published var isAddressBarFocused = false
 
@@ -86,6 +90,18 @@ class BrowserModel: ObservableObject {
             self.dappApprovalPresented = false
         }
     }
+
+    @MainActor
+    func setDappTransactionApproval(_ request: DappTransactionApprovalRequest?) {
+        self.dappTransactionApprovalRequest = request
+        self.dappTransactionApprovalPresented = request != nil
+    }
+
+    @MainActor
+    func setDappSignBatchApproval(_ request: DappSignBatchApprovalRequest?) {
+        self.dappSignBatchApprovalRequest = request
+        self.dappSignBatchApprovalPresented = request != nil
+    }
 }
 
 struct BrowserView: View {
@@ -99,6 +115,34 @@ struct BrowserView: View {
                     .presentationDetents([.medium])
                     .background(.ultraThinMaterial)
             }
+            .alert(
+                "Approve Transaction",
+                isPresented: $browserModel.dappTransactionApprovalPresented,
+                presenting: browserModel.dappTransactionApprovalRequest
+            ) { request in
+                Button("Approve") {
+                    request.approve()
+                }
+                Button("Reject", role: .cancel) {
+                    request.reject()
+                }
+            } message: { request in
+                Text(request.message)
+            }
+            .alert(
+                "Approve Signatures",
+                isPresented: $browserModel.dappSignBatchApprovalPresented,
+                presenting: browserModel.dappSignBatchApprovalRequest
+            ) { request in
+                Button("Approve") {
+                    request.approve()
+                }
+                Button("Reject", role: .cancel) {
+                    request.reject()
+                }
+            } message: { request in
+                Text(request.message)
+            }
     }
 }
 
@@ -41,6 +41,109 @@ class DappApprovalRequest: Identifiable, ObservableObject {
 
 }
 
+class DappTransactionApprovalRequest: Identifiable, ObservableObject {
+    // It's important to have a unique id per request
+    let id = UUID()
+    let context: InPageRequestContext
+    var params: DappTransactionApprovalParams
+
+    init(context: InPageRequestContext, params: DappTransactionApprovalParams) {
+        self.context = context
+        self.params = params
+    }
+
+    var message: String {
+        var lines = ["\(params.dappIdentifier) wants to send \(params.amount) \(params.tokenSymbol) on \(params.chainDisplayName)."]
+        if params.highValue {
+            lines.append("This is a high value transaction.")
+        }
+        if params.noCodeAtTarget {
+            lines.append("The recipient is not a contract, so the call won't do anything, but you'll still pay the fee.")
+        }
+        if params.isProxy, let implementation = params.proxyImplementation {
+            let name = params.proxyImplementationLabel ?? implementation
+            lines.append("The contract is a proxy of \(name). Its logic can be changed by its owner.")
+        }
+        if let call = callSummary {
+            lines.append(call)
+        }
+        lines.append(contentsOf: params.warnings.map { $0.message })
+        return lines.joined(separator: "\n")
+    }
+
+    var callSummary: String? {
+        switch params.decodedCall {
+        case let .known(_, signature, args):
+            let argLines = args.map { "\($0.name): \($0.value)" }
+            return (["Calls \(signature)"] + argLines).joined(separator: "\n")
+        case let .unknown(selector):
+            return "Calls unknown function \(selector)."
+        case .none:
+            guard let selector = params.calldataSelector else {
+                return nil
+            }
+            if params.dataTooLarge {
+                return "Calls function \(selector) with \(params.calldataSize) bytes of data that is too large to show."
+            }
+            return "Calls function \(selector)."
+        }
+    }
+
+    func approve() {
+        do {
+            try context.core.userApprovedDappTransaction(context: context, params: self.params)
+        } catch {
+            print("userApprovedDappTransaction threw: \(error)")
+        }
+    }
+
+    func reject() {
+        do {
+            try context.core.userRejectedDappTransaction(context: context, params: self.params)
+        } catch {
+            print("userRejectedDappTransaction threw: \(error)")
+        }
+    }
+}
+
+class DappSignBatchApprovalRequest: Identifiable, ObservableObject {
+    // It's important to have a unique id per request
+    let id = UUID()
+    let context: InPageRequestContext
+    var params: DappSignBatchApprovalParams
+
+    init(context: InPageRequestContext, params: DappSignBatchApprovalParams) {
+        self.context = context
+        self.params = params
+    }
+
+    var message: String {
+        var lines = ["\(params.dappIdentifier) wants to sign \(params.items.count) messages on \(params.chainDisplayName):"]
+        for item in params.items {
+            let contract = item.verifyingContract.map { $0.label ?? $0.checksumAddress }
+            let schema = item.knownSchema ?? "Custom message"
+            lines.append([item.domainName, schema, contract].compactMap { $0 }.joined(separator: " - "))
+        }
+        return lines.joined(separator: "\n")
+    }
+
+    func approve() {
+        do {
+            try context.core.userApprovedDappSignBatch(context: context, params: self.params)
+        } catch {
+            print("userApprovedDappSignBatch threw: \(error)")
+        }
+    }
+
+    func reject() {
+        do {
+            try context.core.userRejectedDappSignBatch(context: context, params: self.params)
+        } catch {
+            print("userRejectedDappSignBatch threw: \(error)")
+        }
+    }
+}
+
 struct DappApproval: View {
     @EnvironmentObject private var viewModel: GlobalModel
     @Environment(\.dismiss) var dismiss
@@ -11,4 +11,6 @@ class CallbackModel: ObservableObject {
     @Published var dappSignatureResult: DappSignatureResult?
     @Published var dappTransactionApproved: DappTransactionApproved?
     @Published var dappTransactionResult: DappTransactionResult?
+    /// The JSON-RPC request of the last approval prompt that timed out.
+    @Published var approvalTimedOut: String?
 }
@@ -46,4 +46,82 @@ class CoreUICallback: CoreUiCallbackI {
             self.model.dappTransactionResult = result
         }
     }
+
+    func approvalTimedOut(jsonRpcRequest: String) {
+        DispatchQueue.main.async {
+            self.model.approvalTimedOut = jsonRpcRequest
+        }
+    }
+
+    // The core calls this from a background thread.
+    func authenticateUser(reason: String) -> Bool {
+        authenticateBlocking(reason: reason)
+    }
+}
+
+// Hook for third-party transaction checks, eg. a security vendor's threat feed.
+// The core flags token approvals itself, so there is nothing to add by default.
+class CoreTransactionInspector: CoreTransactionInspectorI {
+    func inspect(tx _: InspectedTransaction) -> [TransactionWarning] {
+        []
+    }
+}
+
+// Native token prices from the Coinbase exchange rates API.
+// The core asks for prices synchronously, so cached rates are returned and they're refreshed in the
+// background when they're stale.
+class CorePriceFeed: CorePriceFeedI {
+    private static let refreshInterval: TimeInterval = 5 * 60
+
+    private let lock = NSLock()
+    // Rates by native token symbol and currency code.
+    private var rates: [String: [String: String]] = [:]
+    private var refreshedAt: [String: Date] = [:]
+
+    func supportsCurrency(currency _: String) -> Bool {
+        // The API has rates for all the currencies that the core supports.
+        true
+    }
+
+    func nativeTokenPrice(nativeTokenSymbol: String, currency: String) -> String? {
+        lock.lock()
+        let price = rates[nativeTokenSymbol]?[currency]
+        let isStale = refreshedAt[nativeTokenSymbol].map {
+            Date().timeIntervalSince($0) > Self.refreshInterval
+        } ?? true
+        if isStale {
+            // Set before the request finishes to avoid concurrent refreshes.
+            refreshedAt[nativeTokenSymbol] = Date()
+        }
+        lock.unlock()
+
+        if isStale {
+            refresh(nativeTokenSymbol)
+        }
+        return price
+    }
+
+    private func refresh(_ symbol: String) {
+        var components = URLComponents(string: "https://api.coinbase.com/v2/exchange-rates")!
+        components.queryItems = [URLQueryItem(name: "currency", value: symbol)]
+        URLSession.shared.dataTask(with: components.url!) { data, _, error in
+            guard let data = data,
+                  let response = try? JSONDecoder().decode(ExchangeRatesResponse.self, from: data)
+            else {
+                print("Failed to fetch \(symbol) exchange rates: \(String(describing: error))")
+                return
+            }
+            self.lock.lock()
+            self.rates[symbol] = response.data.rates
+            self.lock.unlock()
+        }.resume()
+    }
+}
+
+private struct ExchangeRatesResponse: Decodable {
+    struct ExchangeRates: Decodable {
+        let rates: [String: String]
+    }
+
+    let data: ExchangeRates
 }
@@ -3,6 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 import Foundation
+import Network
 import SwiftUI
 
 @MainActor
@@ -18,6 +19,7 @@ class GlobalModel: ObservableObject {
     @Published var backupEnabled: Bool = true
 
     private var backgroundTaskID: UIBackgroundTaskIdentifier?
+    private let pathMonitor = NWPathMonitor()
 
     var activeProfile: Profile? {
         return profileList.first(where: { acc in acc.id == activeProfileId })
@@ -40,10 +42,15 @@ class GlobalModel: ObservableObject {
     static func coreArgs() -> CoreArgs {
         CoreArgs(
             deviceId: deviceId(), deviceName: deviceName(), cacheDir: LocalFiles.cacheDir(),
-            dbFilePath: LocalFiles.ensureDbFilePath()
+            dbFilePath: LocalFiles.ensureDbFilePath(), appVersion: appVersion()
         )
     }
 
+    private static func appVersion() -> String {
+        // Set by the build from the marketing version of the app.
+        Bundle.main.object(forInfoDictionaryKey: "CFBundleShortVersionString") as! String
+    }
+
     private static func deviceName() -> String {
         UIDevice.current.name
     }
@@ -63,7 +70,8 @@ class GlobalModel: ObservableObject {
         var core: AppCoreProtocol
         do {
             core = try AppCore(
-                args: coreArgs, backupStorage: CoreBackupStorage(), uiCallback: CoreUICallback(callbackModel)
+                args: coreArgs, backupStorage: CoreBackupStorage(), uiCallback: CoreUICallback(callbackModel),
+                transactionInspector: CoreTransactionInspector(), priceFeed: CorePriceFeed()
             )
         } catch {
             print("Failed to create core: \(error)")
@@ -99,6 +107,29 @@ class GlobalModel: ObservableObject {
 
 // MARK: - App Core
 extension GlobalModel {
+    /// Upload backups that failed to upload, eg. because the network was down, when the network becomes
+    /// available. The monitor reports the current state on start, so it also flushes at startup.
+    func flushPendingBackupsOnConnectivity() {
+        pathMonitor.pathUpdateHandler = { [weak self] path in
+            if path.status == .satisfied {
+                self?.flushPendingBackups()
+            }
+        }
+        pathMonitor.start(queue: DispatchQueue.global(qos: .background))
+    }
+
+    nonisolated func flushPendingBackups() {
+        DispatchQueue.global(qos: .background).async {
+            do {
+                try self.core.flushPendingBackups()
+            } catch CoreBackupError.BackupDisabled(message: _) {
+                // Nothing to upload
+            } catch {
+                print("Error for core flushPendingBackups: \(error)")
+            }
+        }
+    }
+
     func onBackground() {
         DispatchQueue.global(qos: .background).async {
             // Request the task assertion and save the ID.
@@ -186,25 +217,28 @@ extension GlobalModel {
     }
 
     func enableBackup() async -> BannerData? {
-        let success = await dispatchBackground(.userInteractive) {
+        let banner: BannerData? = await dispatchBackground(.userInteractive) {
             do {
                 try self.core.enableBackup()
-                return true
+                return nil
+            } catch CoreBackupError.BackupSpooled(message: _) {
+                // Backup is enabled, but the first backup couldn't be uploaded yet.
+                return BannerData(
+                    title: "Backup pending",
+                    detail: "The backup will be uploaded to iCloud when it's reachable.",
+                    type: .warning
+                )
             } catch {
                 print("Error enabling backup: \(error)")
-                return false
+                return BannerData(
+                    title: "Error enabling backup",
+                    detail: "Make sure iCloud is enabled and try to restart the app.",
+                    type: .error
+                )
             }
         }
-        if success {
-            self.backupEnabled = await self.fetchBackupEnabled()
-            return nil
-        } else {
-            return BannerData(
-                title: "Error enabling backup",
-                detail: "Make sure iCloud is enabled and try to restart the app.",
-                type: .error
-            )
-        }
+        self.backupEnabled = await self.fetchBackupEnabled()
+        return banner
     }
 
     func disableBackup() async -> BannerData? {
@@ -409,6 +443,14 @@ class PreviewAppCore: AppCoreProtocol {
         [try! self.tokensForAddressId(addressId: checksumAddress)]
     }
 
+    func listNfts(args _: ListNftsArgs) throws -> CoreNftPage {
+        CoreNftPage(nfts: [Self.toCoreNFT(NFT.example())], nextCursor: nil)
+    }
+
+    func clearHistory(profileId _: String, filter _: HistoryFilter) throws -> ClearedHistory {
+        ClearedHistory(transactions: 0)
+    }
+
     func tokensForAddressId(addressId: String) throws -> CoreTokens {
         CoreTokens(
             addressId: addressId,
@@ -457,7 +499,7 @@ class PreviewAppCore: AppCoreProtocol {
         return CoreFungibleToken(
             id: token.id,
             symbol: token.symbol,
-            amount: token.amount,
+            amount: token.amount.map { CurrencyAmount.fromDisplay($0, symbol: token.symbol) },
             tokenType: FungibleTokenType.native,
             icon: icon
         )
@@ -515,10 +557,32 @@ class PreviewAppCore: AppCoreProtocol {
         backupEnabledToggle = false
     }
 
+    func flushPendingBackups() throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func resendBackup() throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func diffBackups(
+        firstBackupFileName _: String, secondBackupFileName _: String, password _: String
+    ) throws -> BackupDiff {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
     func isBackupEnabled() throws -> Bool {
         self.backupEnabledToggle
     }
 
+    func optimizeDatabase() throws -> UInt64 {
+        0
+    }
+
+    func restoreStatus() throws -> RestoreStatus {
+        .notStarted
+    }
+
     func lastUploadedBackup() throws -> Int64? {
         Thread.sleep(forTimeInterval: 0.5)
         return Int64(Date().timeIntervalSince1970)
@@ -528,6 +592,32 @@ class PreviewAppCore: AppCoreProtocol {
         "AAA1-BBB2-CCC3-DDD4"
     }
 
+    func requestPrivateKeyExport(profileId _: String, addressId _: String) throws -> String {
+        "preview-token"
+    }
+
+    func exportPrivateKey(profileId _: String, addressId _: String, confirmationToken _: String) throws -> String {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func storageBreakdown() throws -> StorageBreakdown {
+        StorageBreakdown(profilePictures: 0, transactions: 0, dappsAndKeys: 0, other: 0, cache: 0)
+    }
+
+    func checkDatabaseIntegrity() throws {}
+
+    func setIpfsGateways(urls _: [String]) throws {}
+
+    func ipfsGatewayHealth() throws -> [IpfsGatewayHealth] {
+        []
+    }
+
+    func metricsSnapshot() -> MetricsSnapshot {
+        MetricsSnapshot(
+            requestsByMethod: [], approvalsGranted: 0, approvalsRejected: 0, transfersSent: 0, backupsCreated: 0
+        )
+    }
+
     func fetchFavicon(rawUrl: String) throws -> [UInt8]? {
         nil
     }
@@ -551,6 +641,34 @@ class PreviewAppCore: AppCoreProtocol {
         throw CoreError.Fatal(message: "not implemented")
     }
 
+    func listTransactions(addressId _: String) throws -> [CoreTransaction] {
+        []
+    }
+
+    func importDapps(manifestJson _: String) throws -> [DappImportResult] {
+        []
+    }
+
+    func scanAllowances(profileId _: String, chainId _: UInt64) throws -> [Allowance] {
+        []
+    }
+
+    func discoverTokens(profileId _: String, chainId _: UInt64) throws -> [String] {
+        []
+    }
+
+    func setAutoDiscoverTokens(profileId _: String, enabled _: Bool) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func testRpcUrl(url _: String) throws -> RpcTestResult {
+        RpcTestResult(chainId: 1, blockNumber: 16_000_000, latencyMs: 100, archive: false)
+    }
+
+    func versionInfo() throws -> VersionInfo {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
     func listProfiles() throws -> [CoreProfile] {
         let wallets = [
             Address.ethereumWallet(),
@@ -606,6 +724,44 @@ class PreviewAppCore: AppCoreProtocol {
         throw CoreError.Fatal(message: "not implemented")
     }
 
+    func setProfilePictureFromUrl(profileId _: String, url _: String) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func reorderProfiles(orderedIds: [String]) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func accountsForOrigin(url _: String) throws -> [OriginAccount] {
+        []
+    }
+
+    func resolveDappDeeplink(idOrUrl _: String) throws -> CoreDappDetail? {
+        nil
+    }
+
+    func setDappTags(profileId _: String, dappId _: String, tags _: [String]) throws {}
+
+    func listDappTags(profileId _: String) throws -> [String] {
+        ["DeFi", "NFT"]
+    }
+
+    func dappStorageSet(profileId _: String, dappId _: String, key _: String, value _: String) throws {}
+
+    func dappStorageGet(profileId _: String, dappId _: String, key _: String) throws -> String? {
+        nil
+    }
+
+    func dappStorageDelete(profileId _: String, dappId _: String, key _: String) throws {}
+
+    func dappStorageClear(profileId _: String, dappId _: String) throws {}
+
+    func setDappPinned(profileId _: String, dappId _: String, pinned _: Bool) throws {}
+
+    func reorderPinnedDapps(profileId _: String, orderedDappIds _: [String]) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
     func randomBundledProfilePicture() throws -> String? {
         "seal-9"
     }
@@ -623,14 +779,19 @@ class PreviewAppCore: AppCoreProtocol {
         throw CoreError.Fatal(message: "not implemented")
     }
 
-    func getInPageScript(rpcProviderName _: String, requestHandlerName _: String) throws -> String {
+    func getInPageScript(
+        rpcProviderName _: String, requestHandlerName _: String, walletName _: String, walletIcon _: String,
+        walletRdns _: String, options _: ScriptOptions
+    ) throws -> String {
         throw CoreError.Fatal(message: "not implemented")
     }
 
-    func inPageRequest(context _: InPageRequestContextI, rawRequest _: String) throws {
+    func inPageRequest(context _: InPageRequestContextI, rawRequest _: String, requestId _: String?) throws {
         throw CoreError.Fatal(message: "not implemented")
     }
 
+    func cancelRequest(context _: InPageRequestContextI, requestId _: String) throws {}
+
     func userApprovedDapp(context: InPageRequestContextI, params: DappApprovalParams) throws {
         throw CoreError.Fatal(message: "not implemented")
     }
@@ -639,10 +800,46 @@ class PreviewAppCore: AppCoreProtocol {
         throw CoreError.Fatal(message: "not implemented")
     }
 
+    func userApprovedDappTransaction(context _: InPageRequestContextI, params _: DappTransactionApprovalParams) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func userRejectedDappTransaction(context _: InPageRequestContextI, params _: DappTransactionApprovalParams) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func userApprovedDappSignBatch(context _: InPageRequestContextI, params _: DappSignBatchApprovalParams) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func userRejectedDappSignBatch(context _: InPageRequestContextI, params _: DappSignBatchApprovalParams) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func setDappTrust(profileId _: String, dappId _: String, level _: DappTrustLevel) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func setDappSpendingLimit(args _: DappSpendingLimitArgs) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
     func addEthChain(chainId: UInt64, addressId: String) throws {
         throw CoreError.Fatal(message: "not implemented")
     }
 
+    func transactionQueue(profileId _: String, chainId _: UInt64, checksumAddress _: String) throws -> [CoreQueuedTransaction] {
+        []
+    }
+
+    func cancelQueuedTransaction(profileId _: String, chainId _: UInt64, checksumAddress _: String, nonce _: UInt64) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func resetNonce(profileId _: String, chainId _: UInt64, checksumAddress _: String) throws -> UInt64 {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
     func ethChangeDappChain(args: EthChangeDappChainArgs) throws {
         throw CoreError.Fatal(message: "not implemented")
     }
@@ -660,6 +857,94 @@ class PreviewAppCore: AppCoreProtocol {
         let res = try! listProfiles().first!.dapps.map {$0.id}.prefix(Int(limit))
         return [String](res)
     }
+
+    func backupReminderStatus() throws -> BackupReminder {
+        BackupReminder(
+            severity: .fine, hasPendingChanges: false, hasUnsavedKeyMaterial: false, secondsSinceLastUpload: 60
+        )
+    }
+
+    func estimateBackupSize() throws -> UInt64 {
+        1024 * 1024
+    }
+
+    func listBackupsGrouped() -> [DeviceBackups] {
+        []
+    }
+
+    func detectBackupConflicts() -> [BackupConflict] {
+        []
+    }
+
+    func activeChainsForProfile(profileId _: String) throws -> [UInt64] {
+        [137]
+    }
+
+    func registerProfilePictures(pictures _: [String: [UInt8]]) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func listAvailableProfilePictures() throws -> [String] {
+        []
+    }
+
+    func ethNativeTokenTransferPreview(args: EthTransferNativeTokenArgs) throws -> CoreTransferPreview {
+        CoreTransferPreview(highValue: false, amount: args.amountDecimal, highValueThreshold: "1")
+    }
+
+    func ethFungibleTokenTransferPreview(args: EthTransferFungibleTokenArgs) throws -> CoreTransferPreview {
+        CoreTransferPreview(highValue: false, amount: args.amountDecimal, highValueThreshold: nil)
+    }
+
+    func setEthHighValueThreshold(profileId _: String, chainId _: UInt64, amountDecimal _: String) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func setFiatCurrency(profileId _: String, currencyCode _: String) throws {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func currentBlockNumber(chainId _: UInt64) throws -> UInt64 {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func decodeCalldata(data _: String) throws -> DecodedCall {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func verifySignature(message _: String, signature _: String, expectedAddress _: String, kind _: SignatureKind) throws -> Bool {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func buildSigningRequest(profileId _: String, chainId _: UInt64, tx _: EthSigningRequestArgs) throws -> SigningRequest {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func signRequest(request _: SigningRequest) throws -> SignedTransaction {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func encodeSigningRequest(request _: SigningRequest) throws -> String {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func decodeSigningRequest(payload _: String) throws -> SigningRequest {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func broadcastSignedTransaction(chainId _: UInt64, rawTransaction _: String) throws -> String {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func balanceAt(chainId _: UInt64, checksumAddress _: String, block _: String) throws -> String {
+        throw CoreError.Fatal(message: "not implemented")
+    }
+
+    func nativeTokenBalance(
+        profileId _: String, chainId _: UInt64, checksumAddress _: String, includePending _: Bool
+    ) throws -> CoreNativeBalance {
+        throw CoreError.Fatal(message: "not implemented")
+    }
 }
 
 extension GlobalModel {
@@ -12,9 +12,11 @@ class Profile: Identifiable, ObservableObject {
     @Published var picture: UIImage
     @Published var wallets: MultichainAddress
     @Published var dapps: [String: Dapp]
+    // The order of the dapps as returned by the core, pinned dapps first.
+    @Published var dappIds: [String]
 
     var dappList: [Dapp] {
-        self.dapps.values.sorted(by: { $0.displayName < $1.displayName })
+        self.dappIds.compactMap { self.dapps[$0] }
     }
 
     var allAddresses: [Address] {
@@ -31,6 +33,7 @@ class Profile: Identifiable, ObservableObject {
         self.picture = picture
         self.wallets = MultichainAddress(core, wallets)
         self.dapps = Dictionary(uniqueKeysWithValues: dapps.map { ($0.id, $0) })
+        self.dappIds = dapps.map { $0.id }
     }
 
     static func fromCore(_ core: AppCoreProtocol, _ profile: CoreProfile) -> Self {
@@ -74,6 +77,7 @@ class Profile: Identifiable, ObservableObject {
                 self.dapps[dapp.id] = dapp
             }
         }
+        self.dappIds = coreDapps.map { $0.id }
     }
 
     func isAddressSelectedForAdapp(addressId: String) -> Bool {
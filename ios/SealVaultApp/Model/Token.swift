@@ -26,7 +26,7 @@ class Token: Identifiable, ObservableObject {
             id: token.id,
             symbol: token.symbol,
             icon: Self.convertIcon(token.icon),
-            amount: token.amount,
+            amount: token.amount?.display(),
             nativeToken: token.tokenType == FungibleTokenType.native
         )
     }
@@ -46,8 +46,8 @@ class Token: Identifiable, ObservableObject {
         self.icon = Self.convertIcon(token.icon)
         // Tokens are listed without amounts first when fetching profiles.
         // Don't unset amount if we have fetched the amount for this token already.
-        if token.amount != nil {
-            self.amount = token.amount
+        if let amount = token.amount {
+            self.amount = amount.display()
         }
         switch token.tokenType {
         case .native:
@@ -80,9 +80,28 @@ extension Token {
     }
 }
 
+extension CurrencyAmount {
+    func display() -> String {
+        coreDisplayCurrencyAmount(amount: self)
+    }
+}
+
 // MARK: - preview
 
 #if DEBUG
+    extension CurrencyAmount {
+        static let previewMatic = CurrencyAmount(
+            raw: "100000000000000000", decimals: 18, symbol: "MATIC", chainId: 137
+        )
+
+        /// Convert a decimal display amount like "45.51" to a currency amount.
+        static func fromDisplay(_ amount: String, symbol: String) -> CurrencyAmount {
+            let parts = amount.split(separator: ".", maxSplits: 1)
+            let decimals = parts.count == 2 ? parts[1].count : 0
+            return CurrencyAmount(raw: parts.joined(), decimals: UInt8(decimals), symbol: symbol, chainId: 1)
+        }
+    }
+
     extension Token {
         static func eth(_ address: String) -> Token {
             let symbol = "ETH"
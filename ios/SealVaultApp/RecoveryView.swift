@@ -111,7 +111,7 @@ struct RecoveryViewInner: View {
                     self.setProcessing(true)
                     var success = false
                     do {
-                        try coreRestoreBackup(
+                        _ = try coreRestoreBackup(
                             coreArgs: GlobalModel.coreArgs(),
                             backupStorage: CoreBackupStorage(),
                             backupFileName: restoreData.backupFileName,
@@ -128,6 +128,11 @@ struct RecoveryViewInner: View {
                             detail: """
 Please make sure the you have iCloud Keychain sync enabled and try again a few minutes later.
 """)
+                    } catch CoreBackupError.RollbackDetected(message: _) {
+                        self.setErrorBanner(
+                            title: "Outdated backup",
+                            detail: "A more recent backup has already been restored on this device."
+                        )
                     } catch {
                         self.setErrorBanner(
                             title: "Unexpected error restoring the backup",
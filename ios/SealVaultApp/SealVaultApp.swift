@@ -30,6 +30,7 @@ struct AppInner: View {
             .environmentObject(model)
             .environmentObject(bannerModel)
             .task {
+                model.flushPendingBackupsOnConnectivity()
                 await model.refreshProfiles()
 
                 #if DEBUG
@@ -331,15 +331,53 @@ struct TransferButton: View {
 
     @ObservedObject var state: TransferState
     @EnvironmentObject private var bannerModel: BannerModel
+    @State private var highValuePreview: CoreTransferPreview?
+    @State private var presentHighValueConfirmation = false
 
-    func makeTransfer() async -> Bool {
+    var highValueMessage: String {
+        guard let preview = highValuePreview else {
+            return ""
+        }
+        let threshold = preview.highValueThreshold ?? ""
+        return "Sending \(preview.amount) \(state.token.symbol) is above your high value threshold of " +
+            "\(threshold) \(state.token.symbol)."
+    }
+
+    /// Returns the preview if the transfer is high value and the user must confirm it.
+    func fetchHighValuePreview() async -> CoreTransferPreview? {
+        await dispatchBackground(.userInteractive) {
+            guard state.token.nativeToken, let toAddress = state.toChecksumAddress else {
+                return nil
+            }
+            let args = EthTransferNativeTokenArgs(
+                fromAddressId: state.fromAddress.id, toChecksumAddress: toAddress, amountDecimal: state.amount
+            )
+            do {
+                let preview = try core.ethNativeTokenTransferPreview(args: args)
+                return preview.highValue ? preview : nil
+            } catch {
+                // The transfer reports the error to the user
+                print("Error previewing transfer: \(error)")
+                return nil
+            }
+        }
+    }
+
+    func submit(confirmedHighValue: Bool) async {
+        _ = await makeTransfer(confirmedHighValue: confirmedHighValue)
+        // Reset amount so that user doesn't submit twice by accident
+        state.amount = ""
+        state.processing = false
+    }
+
+    func makeTransfer(confirmedHighValue: Bool) async -> Bool {
         await dispatchBackground(.userInteractive) {
             do {
                 if let toAddress = state.toChecksumAddress {
                     if state.token.nativeToken {
                         let args = EthTransferNativeTokenArgs(
                             fromAddressId: state.fromAddress.id, toChecksumAddress: toAddress,
-                            amountDecimal: state.amount
+                            amountDecimal: state.amount, confirmedHighValue: confirmedHighValue
                         )
                         try core.ethTransferNativeToken(args: args)
                     } else {
@@ -388,10 +426,13 @@ struct TransferButton: View {
             }
             state.processing = true
             Task {
-                let success = await makeTransfer()
-                // Reset amount so that user doesn't submit twice by accident
-                state.amount = ""
-                state.processing = false
+                if let preview = await fetchHighValuePreview() {
+                    highValuePreview = preview
+                    presentHighValueConfirmation = true
+                    state.processing = false
+                    return
+                }
+                await submit(confirmedHighValue: false)
             }
         }, label: {
             if state.processing {
@@ -411,6 +452,17 @@ struct TransferButton: View {
         .disabled(state.buttonDisabled)
         .foregroundColor(Color.white)
         .cornerRadius(cornerRadius)
+        .confirmationDialog(
+            highValueMessage, isPresented: $presentHighValueConfirmation, titleVisibility: .visible
+        ) {
+            Button("Send", role: .destructive, action: {
+                state.processing = true
+                Task {
+                    await submit(confirmedHighValue: true)
+                }
+            })
+            Button("Cancel", role: .cancel, action: {})
+        }
     }
 }
 
@@ -30,3 +30,25 @@ func authenticate(reason: String) async throws {
 
     }
 }
+
+// Blocks the calling thread until the user responds, so it must not be called on the main thread.
+func authenticateBlocking(reason: String) -> Bool {
+    let context = LAContext()
+    var error: NSError?
+    guard context.canEvaluatePolicy(.deviceOwnerAuthentication, error: &error) else {
+        print("Authentication unavailable")
+        return false
+    }
+
+    let semaphore = DispatchSemaphore(value: 0)
+    var isAuthenticated = false
+    context.evaluatePolicy(.deviceOwnerAuthentication, localizedReason: reason) { success, error in
+        if !success {
+            print("Authentication error: \(String(describing: error))")
+        }
+        isAuthenticated = success
+        semaphore.signal()
+    }
+    semaphore.wait()
+    return isAuthenticated
+}
@@ -108,16 +108,18 @@ public struct WebViewRepresentable: UIViewRepresentable {
     }
 
     public func makeCoordinator() -> Coordinator {
-        Coordinator(self.model)
+        Coordinator(self.model, self.scriptHandler)
 
     }
 
     public final class Coordinator: NSObject {
         var model: BrowserModel
+        var scriptHandler: WebViewScriptHandler
         var observer: NSKeyValueObservation?
 
-        init(_ model: BrowserModel) {
+        init(_ model: BrowserModel, _ scriptHandler: WebViewScriptHandler) {
             self.model = model
+            self.scriptHandler = scriptHandler
         }
 
        deinit {
@@ -161,6 +163,9 @@ final class WebViewScriptHandler: NSObject, WKScriptMessageHandler {
     // TODO: rethink hierarchy to avoid weak reference
     weak var webView: WKWebView?
 
+    // Requests that haven't been responded to by their request id. Only accessed on the main thread.
+    private var pendingRequests: [String: InPageRequestContext] = [:]
+
     init(core: AppCoreProtocol, stateModel: BrowserModel) {
         self.core = core
         self.stateModel = stateModel
@@ -174,9 +179,24 @@ final class WebViewScriptHandler: NSObject, WKScriptMessageHandler {
         super.init()
     }
 
+    // EIP-6963 wallet info announced to dapps
+    static let walletName = "SealVault"
+    static let walletRdns = "org.sealvault"
+    static let walletIcon: String = {
+        // App icons can't be loaded by name, so the icon is a separate image asset.
+        guard let data = UIImage(named: "wallet-icon")?.pngData() else {
+            // Empty SVG as the icon must be a data URI
+            return "data:image/svg+xml;base64,PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciLz4="
+        }
+        return "data:image/png;base64,\(data.base64EncodedString())"
+    }()
+
     func getInPageScript() -> String? {
         do {
-            return try core.getInPageScript(rpcProviderName: rpcProviderName, requestHandlerName: handlerKey)
+            return try core.getInPageScript(
+                rpcProviderName: rpcProviderName, requestHandlerName: handlerKey, walletName: Self.walletName,
+                walletIcon: Self.walletIcon, walletRdns: Self.walletRdns, options: ScriptOptions()
+            )
         } catch {
             print("Error getting in page script: \(error)")
             return nil
@@ -191,16 +211,18 @@ final class WebViewScriptHandler: NSObject, WKScriptMessageHandler {
         }
         if message.name == handlerName {
             guard let context = InPageRequestContext.build(
-                core, stateModel, message, rpcProviderName: rpcProviderName
+                core, stateModel, message, rpcProviderName: rpcProviderName, scriptHandler: self
             ) else {
                 return
             }
             guard let messageBody = message.body as? String else {
                 return
             }
+            let requestId = context.requestId
+            self.pendingRequests[requestId] = context
             self.serialQueue.async { [weak self] in
                 do {
-                    try self?.core.inPageRequest(context: context, rawRequest: messageBody)
+                    try self?.core.inPageRequest(context: context, rawRequest: messageBody, requestId: requestId)
                 } catch {
                     // If an error is thrown here, it's caused by Swift, eg. passing an invalid url
                     print("Error: \(error)")
@@ -210,6 +232,23 @@ final class WebViewScriptHandler: NSObject, WKScriptMessageHandler {
             print("unknown message recipient: \(message.name)")
         }
     }
+
+    func requestCompleted(requestId: String) {
+        self.pendingRequests.removeValue(forKey: requestId)
+    }
+
+    // Cancel the requests of the page that are waiting for a response, eg. when the user navigates away.
+    func cancelPendingRequests() {
+        let requests = self.pendingRequests
+        self.pendingRequests = [:]
+        for (requestId, context) in requests {
+            do {
+                try self.core.cancelRequest(context: context, requestId: requestId)
+            } catch {
+                print("Error cancelling in-page request: \(error)")
+            }
+        }
+    }
 }
 
 class InPageRequestContext: InPageRequestContextI {
@@ -218,19 +257,24 @@ class InPageRequestContext: InPageRequestContextI {
     var message: WKScriptMessage
     var webViewUrl: URL
     var rpcProviderName: String
+    // Lets the script handler cancel the request
+    let requestId: String = UUID().uuidString
+    weak var scriptHandler: WebViewScriptHandler?
 
     init(_ core: AppCoreProtocol, _ stateModel: BrowserModel, _ message: WKScriptMessage,
-         _ webViewUrl: URL, rpcProviderName: String
+         _ webViewUrl: URL, rpcProviderName: String, scriptHandler: WebViewScriptHandler? = nil
     ) {
         self.core = core
         self.stateModel = stateModel
         self.message = message
         self.webViewUrl = webViewUrl
         self.rpcProviderName = rpcProviderName
+        self.scriptHandler = scriptHandler
     }
 
     static func build(
-        _ core: AppCoreProtocol, _ stateModel: BrowserModel, _ message: WKScriptMessage, rpcProviderName: String
+        _ core: AppCoreProtocol, _ stateModel: BrowserModel, _ message: WKScriptMessage, rpcProviderName: String,
+        scriptHandler: WebViewScriptHandler? = nil
     ) -> InPageRequestContext? {
         guard let webView = message.webView else {
             return nil
@@ -238,7 +282,9 @@ class InPageRequestContext: InPageRequestContextI {
         guard let url = webView.url else {
             return nil
         }
-        return InPageRequestContext(core, stateModel, message, url, rpcProviderName: rpcProviderName)
+        return InPageRequestContext(
+            core, stateModel, message, url, rpcProviderName: rpcProviderName, scriptHandler: scriptHandler
+        )
     }
 
     func pageUrl() -> String {
@@ -266,8 +312,23 @@ class CoreInPageCallback: CoreInPageCallbackI {
         }
     }
 
+    func requestDappTransactionApproval(txApproval: DappTransactionApprovalParams) {
+        DispatchQueue.main.async {
+            let request = DappTransactionApprovalRequest(context: self.context, params: txApproval)
+            self.context.stateModel.setDappTransactionApproval(request)
+        }
+    }
+
+    func requestDappSignBatchApproval(batchApproval: DappSignBatchApprovalParams) {
+        DispatchQueue.main.async {
+            let request = DappSignBatchApprovalRequest(context: self.context, params: batchApproval)
+            self.context.stateModel.setDappSignBatchApproval(request)
+        }
+    }
+
     func respond(responseHex: String) {
         DispatchQueue.main.async {
+            self.context.scriptHandler?.requestCompleted(requestId: self.context.requestId)
             // Must capture self to prevent the callback object from being GCed before this has a chance to run
             guard let webView = self.context.message.webView else {
                 print("Returning early from notify: webview has been GCed")
@@ -294,6 +355,8 @@ class CoreInPageCallback: CoreInPageCallbackI {
 // TODO: implement all WKNavigationDelegate methods
 extension WebViewRepresentable.Coordinator: WKNavigationDelegate {
     public func webView(_ webView: WKWebView, didStartProvisionalNavigation _: WKNavigation!) {
+        // Requests waiting for approval from the previous page can't be responded to anymore
+        self.scriptHandler.cancelPendingRequests()
         self.model.loading = true
         self.model.setRawUrl(webView.url)
     }
@@ -65,7 +65,8 @@ def clean_rust():
 
 
 def build_rust(configuration, targets):
-    args = ["cargo", "build"]
+    # SQLCipher for database encryption uses CommonCrypto on iOS
+    args = ["cargo", "build", "--features", "sqlcipher"]
     if configuration != "debug":
         args.append("--release")
     for t in targets:
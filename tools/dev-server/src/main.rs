@@ -2,77 +2,282 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::sync::Arc;
+use std::{
+    convert::Infallible,
+    env,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
     body::{boxed, BoxBody},
     extract::State,
-    http::{header, HeaderMap, Request, Response, StatusCode, Uri},
-    response::IntoResponse,
+    http::{header, HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
     routing::{get, post},
-    Router,
+    Json, Router,
 };
 use dotenv::dotenv;
+use futures::Stream;
 use hyper::Body;
-use sealvault_tools_lib::{InPageRequestContextMock, ToolAppCore};
+use sealvault_tools_lib::{
+    InPageEvent, InPageRequestContextMock, ToolAppCore, UICallbackEvent,
+    DEFAULT_DB_FILE_PATH, DEFAULT_DEVICE_ID,
+};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast::error::RecvError, watch};
 use tower::ServiceExt;
-use tower_http::{services::ServeDir, trace::TraceLayer};
-use uniffi_sealvault_core::async_runtime;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    services::ServeDir,
+    trace::TraceLayer,
+};
+use uniffi_sealvault_core::{
+    async_runtime,
+    platform_html::{render_platform_html, Platform},
+    ScriptOptions,
+};
+
+const DEFAULT_STATIC_FOLDER: &str = "./static";
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+const ADDRESS_ENV_VAR: &str = "SEALVAULT_DEV_ADDR";
+/// Read if `SEALVAULT_DEV_ADDR` isn't set for existing setups.
+const LEGACY_ADDRESS_ENV_VAR: &str = "SEALVAULT_DEV_ADDRESS";
+const DB_FILE_PATH_ENV_VAR: &str = "SEALVAULT_DEV_DB";
+const STATIC_FOLDER_ENV_VAR: &str = "SEALVAULT_DEV_STATIC";
+const DEVICE_ID_ENV_VAR: &str = "SEALVAULT_DEV_DEVICE_ID";
+/// Comma separated list of origins, eg. `http://localhost:3000,http://127.0.0.1:3000`
+const CORS_ORIGINS_ENV_VAR: &str = "SEALVAULT_DEV_CORS_ORIGINS";
+/// The dev server doesn't approve requests, so requests that need approval time out.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Wait this long for notifications that follow a response, eg. `accountsChanged`.
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_millis(500);
 
-const STATIC_FOLDER: &str = "./static";
-const ADDRESS: &str = "127.0.0.1:8080";
+/// The number of backend requests being processed.
+static IN_FLIGHT_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a backend request as in flight until dropped.
+struct InFlightRequest;
+
+impl InFlightRequest {
+    fn start() -> Self {
+        IN_FLIGHT_REQUESTS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        IN_FLIGHT_REQUESTS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 /// SealVault Dev Server
 ///
 /// Serves the static directory at `http://localhost:8080/` and proxies requests to the backend
 /// at http://localhost:8080/backend
 ///
+/// UI callbacks of the core are streamed as Server-Sent Events at
+/// http://localhost:8080/events
+///
+/// The address, the DB file path, the static directory and the device id can be set with
+/// environment variables. Frontends served from other origins can call the backend if their
+/// origins are listed in the `SEALVAULT_DEV_CORS_ORIGINS` environment variable. See the read me
+/// for details.
 fn main() -> Result<()> {
     dotenv().ok();
 
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    let app_core = Arc::new(ToolAppCore::new()?);
+    let config = Arc::new(DevServerConfig::from_env()?);
+    let app_core = Arc::new(
+        ToolAppCore::new_with(&config.device_id, &config.db_file_path)
+            .context("Failed to create app core")?,
+    );
 
-    async_runtime::block_on(run_server(app_core));
-    Ok(())
+    async_runtime::block_on(run_server(config, app_core))
 }
 
-async fn run_server(app_core: Arc<ToolAppCore>) {
-    let app = Router::new()
+#[derive(Debug, Clone)]
+struct DevServerConfig {
+    address: SocketAddr,
+    cors_origins: Vec<HeaderValue>,
+    db_file_path: String,
+    static_folder: PathBuf,
+    device_id: String,
+}
+
+impl DevServerConfig {
+    fn from_env() -> Result<Self> {
+        let address = env::var(ADDRESS_ENV_VAR)
+            .or_else(|_| env::var(LEGACY_ADDRESS_ENV_VAR))
+            .unwrap_or_else(|_| DEFAULT_ADDRESS.into());
+        let address: SocketAddr = address
+            .parse()
+            .with_context(|| format!("Invalid {ADDRESS_ENV_VAR}: '{address}'"))?;
+
+        let cors_origins = env::var(CORS_ORIGINS_ENV_VAR).unwrap_or_default();
+        let cors_origins = cors_origins
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/'))
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .with_context(|| format!("Invalid origin in {CORS_ORIGINS_ENV_VAR}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let db_file_path = env::var(DB_FILE_PATH_ENV_VAR)
+            .unwrap_or_else(|_| DEFAULT_DB_FILE_PATH.into());
+        let static_folder = env::var(STATIC_FOLDER_ENV_VAR)
+            .unwrap_or_else(|_| DEFAULT_STATIC_FOLDER.into())
+            .into();
+        let device_id =
+            env::var(DEVICE_ID_ENV_VAR).unwrap_or_else(|_| DEFAULT_DEVICE_ID.into());
+
+        Ok(Self {
+            address,
+            cors_origins,
+            db_file_path,
+            static_folder,
+            device_id,
+        })
+    }
+
+    fn is_same_origin(&self, origin: &str) -> bool {
+        let port = self.address.port();
+        origin == format!("http://{}", self.address)
+            || (self.address.ip().is_loopback()
+                && origin == format!("http://localhost:{port}"))
+    }
+
+    fn is_allowed_origin(&self, origin: &HeaderValue) -> bool {
+        let same_origin = origin
+            .to_str()
+            .map(|origin| self.is_same_origin(origin))
+            .unwrap_or_default();
+        same_origin || self.cors_origins.contains(origin)
+    }
+
+    fn cors_layer(&self) -> CorsLayer {
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(self.cors_origins.clone()))
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([header::CONTENT_TYPE])
+    }
+}
+
+async fn run_server(
+    config: Arc<DevServerConfig>,
+    app_core: Arc<ToolAppCore>,
+) -> Result<()> {
+    // Tells the event streams to end, otherwise they'd block graceful shutdown.
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+
+    let backend_routes = Router::new()
         .route("/backend", post(backend))
-        .route("/js/in-page-provider.js", get(in_page_provider))
-        .fallback(static_handler)
+        .route(
+            "/events",
+            get(move |state| ui_events(state, shutdown_receiver.clone())),
+        )
+        .route_layer(middleware::from_fn_with_state(config.clone(), check_origin))
+        // The CORS layer answers preflight requests before they're routed.
+        .layer(config.cors_layer());
+
+    let app = Router::new()
+        .merge(backend_routes)
+        .route(
+            "/js/in-page-provider.js",
+            get({
+                let config = config.clone();
+                move |state| in_page_provider(state, config.clone())
+            }),
+        )
+        .fallback({
+            let config = config.clone();
+            move |uri, headers| static_handler(uri, headers, config.clone())
+        })
         .layer(TraceLayer::new_for_http())
         .with_state(app_core);
 
-    axum::Server::bind(&ADDRESS.parse().expect("valid address"))
+    log::info!("Listening on http://{}", config.address);
+    let drained = Arc::new(AtomicUsize::new(0));
+    axum::Server::try_bind(&config.address)
+        .with_context(|| format!("Failed to bind {}", config.address))?
         .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal(drained.clone(), shutdown_sender))
         .await
-        .expect("server starts");
+        .context("Server error")?;
+    // The app core is dropped with the router, which lets SQLite checkpoint the WAL into a
+    // persistent DB file.
+    log::info!(
+        "Shut down after draining {} backend requests",
+        drained.load(Ordering::SeqCst)
+    );
+    Ok(())
+}
+
+/// Resolves on Ctrl-C. The server stops accepting connections then and waits for in-flight
+/// requests to finish. The number of in-flight requests is stored in `drained`. Event streams
+/// are ended through `shutdown`.
+async fn shutdown_signal(drained: Arc<AtomicUsize>, shutdown: watch::Sender<bool>) {
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        log::error!("Failed to listen for Ctrl-C, graceful shutdown is disabled: {err}");
+        std::future::pending::<()>().await;
+    }
+    let in_flight = IN_FLIGHT_REQUESTS.load(Ordering::SeqCst);
+    drained.store(in_flight, Ordering::SeqCst);
+    // Fails if there are no event streams.
+    let _ = shutdown.send(true);
+    log::info!("Shutting down, waiting for {in_flight} backend requests to finish");
+}
+
+/// Reject cross-origin requests from origins that aren't allowed. Requests without an `Origin`
+/// header aren't from browsers, so they're let through.
+async fn check_origin<B>(
+    State(config): State<Arc<DevServerConfig>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<axum::response::Response, StatusCode> {
+    match req.headers().get(header::ORIGIN) {
+        Some(origin) if !config.is_allowed_origin(origin) => {
+            log::warn!("Rejected request from disallowed origin: {origin:?}");
+            Err(StatusCode::FORBIDDEN)
+        }
+        _ => Ok(next.run(req).await),
+    }
 }
 
 // Based on https://benw.is/posts/serving-static-files-with-axum
 async fn static_handler(
     uri: Uri,
     headers: HeaderMap,
+    config: Arc<DevServerConfig>,
 ) -> Result<Response<BoxBody>, (StatusCode, String)> {
     dbg!(&uri);
-    let res = get_static_file(uri.clone()).await?;
+    let res = get_static_file(uri.clone(), &config.static_folder).await?;
 
     let content_type = get_header_value(res.headers(), "Content-Type");
     if content_type.to_lowercase().contains("html") {
         let bytes = hyper::body::to_bytes(res.into_body())
             .await
             .expect("can consume body");
-        let mut body_str =
+        let body_str =
             String::from_utf8(bytes.to_vec()).expect("body bytes is valid utf-8");
-        let user_agent = get_header_value(&headers, "User-Agent").to_lowercase();
-        if !user_agent.contains("iphone") {
-            body_str = body_str.replace("<!--desktop-only", "");
-            body_str = body_str.replace("desktop-only-->", "");
-        };
+        let platform =
+            Platform::from_user_agent(&get_header_value(&headers, "User-Agent"));
+        let body_str = render_platform_html(&body_str, platform);
         let html_response = Response::builder()
             .status(200)
             .header("Content-Type", "text/html")
@@ -84,10 +289,13 @@ async fn static_handler(
     }
 }
 
-async fn get_static_file(uri: Uri) -> Result<Response<BoxBody>, (StatusCode, String)> {
+async fn get_static_file(
+    uri: Uri,
+    static_folder: &Path,
+) -> Result<Response<BoxBody>, (StatusCode, String)> {
     let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
 
-    match ServeDir::new(STATIC_FOLDER).oneshot(req).await {
+    match ServeDir::new(static_folder).oneshot(req).await {
         Ok(res) => Ok(res.map(boxed)),
         Err(err) => {
             log::error!("Error serving directory: {err}");
@@ -99,13 +307,28 @@ async fn get_static_file(uri: Uri) -> Result<Response<BoxBody>, (StatusCode, Str
     }
 }
 
-async fn in_page_provider(State(app_core): State<Arc<ToolAppCore>>) -> impl IntoResponse {
+async fn in_page_provider(
+    State(app_core): State<Arc<ToolAppCore>>,
+    config: Arc<DevServerConfig>,
+) -> impl IntoResponse {
     const SEALVAULT_RPC_PROVIDER: &str = "sealVaultRpcProvider";
     const SEALVAULT_REQUEST_HANDLER: &str = "sealVaultRequestHandler";
+    const WALLET_NAME: &str = "SealVault Dev";
+    // Empty SVG, the dev server doesn't need a real icon.
+    const WALLET_ICON: &str = "data:image/svg+xml;base64,PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciLz4=";
+    const WALLET_RDNS: &str = "org.sealvault.dev";
 
     let in_page_script = app_core.core.get_in_page_script(
         SEALVAULT_RPC_PROVIDER.into(),
         SEALVAULT_REQUEST_HANDLER.into(),
+        WALLET_NAME.into(),
+        WALLET_ICON.into(),
+        WALLET_RDNS.into(),
+        ScriptOptions {
+            backend_url: Some(format!("http://{}/backend", config.address)),
+            debug: true,
+            minify: false,
+        },
     );
 
     match in_page_script {
@@ -125,30 +348,116 @@ async fn in_page_provider(State(app_core): State<Arc<ToolAppCore>>) -> impl Into
     }
 }
 
+/// Process an in-page request and return the events that the core sent to the in-page
+/// provider for it. A single event is returned as an object and multiple events, eg. a response
+/// and a notification, as an array. Events are serialized as
+/// `{"kind": "response" | "notification", "payload": <JSON-RPC message>}`.
 async fn backend(
     State(app_core): State<Arc<ToolAppCore>>,
     headers: HeaderMap,
     req_body: String,
-) -> impl IntoResponse {
+) -> Result<Json<Value>, StatusCode> {
+    let _in_flight = InFlightRequest::start();
     let referer = get_header_value(&headers, "Referer");
 
-    // TODO support respond and notify
-    let in_page_request_context = Box::new(InPageRequestContextMock::new(&referer));
+    let (sender, receiver) = mpsc::channel();
+    let in_page_request_context =
+        Box::new(InPageRequestContextMock::with_events(&referer, sender));
     let result = tokio::task::spawn_blocking(move || {
         app_core
             .core
-            .in_page_request(in_page_request_context, req_body)
+            .in_page_request(in_page_request_context, req_body, None)
     })
     .await
     .expect("thread can be joined");
 
-    match result {
-        Ok(_) => StatusCode::OK,
-        Err(err) => {
-            log::error!("Error processing in page request: {err}");
-            StatusCode::INTERNAL_SERVER_ERROR
+    if let Err(err) = result {
+        log::error!("Error processing in page request: {err}");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let events = tokio::task::spawn_blocking(move || collect_events(receiver))
+        .await
+        .expect("thread can be joined");
+
+    let mut events: Vec<Value> = events.into_iter().map(event_to_json).collect();
+    match events.len() {
+        0 => {
+            log::warn!("No response to in page request in {RESPONSE_TIMEOUT:?}");
+            Err(StatusCode::GATEWAY_TIMEOUT)
+        }
+        1 => Ok(Json(events.remove(0))),
+        _ => Ok(Json(Value::Array(events))),
+    }
+}
+
+/// Wait for the response to a request and collect the notifications that were sent before it
+/// or shortly after it. Returns early if the core dropped the request context.
+fn collect_events(receiver: Receiver<InPageEvent>) -> Vec<InPageEvent> {
+    let mut events = Vec::new();
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(event) => {
+                let is_response = matches!(event, InPageEvent::Response(_));
+                events.push(event);
+                if is_response {
+                    break;
+                }
+            }
+            Err(_) => return events,
         }
     }
+    while let Ok(event) = receiver.recv_timeout(NOTIFICATION_TIMEOUT) {
+        events.push(event);
+    }
+    events
+}
+
+fn event_to_json(event: InPageEvent) -> Value {
+    let (kind, payload) = match event {
+        InPageEvent::Response(payload) => ("response", payload),
+        InPageEvent::Notification(payload) => ("notification", payload),
+    };
+    // Payloads are JSON-RPC messages, but pass them through as is if they're not valid JSON.
+    let payload = serde_json::from_str(&payload).unwrap_or(Value::String(payload));
+    json!({ "kind": kind, "payload": payload })
+}
+
+/// Stream the UI callback invocations of the core as Server-Sent Events. Each event is
+/// serialized as `{"type": <callback name>, "payload": <callback argument>}`.
+async fn ui_events(
+    State(app_core): State<Arc<ToolAppCore>>,
+    shutdown: watch::Receiver<bool>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = app_core.ui_events.subscribe();
+    let stream = futures::stream::unfold(
+        (receiver, shutdown),
+        |(mut receiver, mut shutdown)| async move {
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => return None,
+                    event = receiver.recv() => match event {
+                        Ok(event) => {
+                            let event = ui_event_to_sse(event);
+                            return Some((Ok(event), (receiver, shutdown)));
+                        }
+                        Err(RecvError::Lagged(skipped)) => {
+                            log::warn!("Event stream lagged, skipped {skipped} UI events")
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        },
+    );
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn ui_event_to_sse(event: UICallbackEvent) -> Event {
+    let UICallbackEvent { kind, payload } = event;
+    Event::default().data(json!({ "type": kind, "payload": payload }).to_string())
 }
 
 fn get_header_value(headers: &HeaderMap, name: &str) -> String {
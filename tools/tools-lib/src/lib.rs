@@ -2,78 +2,210 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::fs;
+use std::{
+    fs,
+    sync::{mpsc::Sender, Arc, Mutex},
+};
 
 use anyhow::Result;
+use serde_json::{json, Value};
 use tempfile::{tempdir, TempDir};
+use tokio::sync::broadcast;
 use uniffi_sealvault_core::{
-    AppCore, CoreArgs, CoreBackupStorageI, CoreInPageCallbackI, CoreUICallbackI,
-    DappAllotmentTransferResult, DappApprovalParams, DappSignatureResult,
-    DappTransactionApproved, DappTransactionResult, InPageRequestContextI,
-    TokenTransferResult,
+    AppCore, CoreArgs, CoreBackupStorageI, CoreInPageCallbackI, CorePriceFeedI,
+    CoreTransactionInspectorI, CoreUICallbackI, CurrencyAmount,
+    DappAllotmentTransferResult, DappApprovalParams, DappSignBatchApprovalParams,
+    DappSignatureResult, DappTransactionApprovalParams, DappTransactionApproved,
+    DappTransactionResult, InPageRequestContextI, InspectedTransaction,
+    TokenTransferResult, TransactionWarning,
 };
 
+/// The device id of tool app cores unless it's overridden.
+pub const DEFAULT_DEVICE_ID: &str = "dev-tools";
+/// Tool app cores use an in-memory DB unless it's overridden.
+pub const DEFAULT_DB_FILE_PATH: &str = ":memory:";
+/// UI callback events are dropped for subscribers that lag behind by more than this.
+const UI_EVENTS_CAPACITY: usize = 100;
+
 pub struct ToolAppCore {
     // It isn't accessed, but it must held on for the lifetime of the struct, as the directory is
     // deleted on drop.
     #[allow(unused)]
     work_dir: TempDir,
     pub core: AppCore,
+    /// The UI callback invocations of the core. Subscribe to receive them.
+    pub ui_events: broadcast::Sender<UICallbackEvent>,
 }
 
 impl ToolAppCore {
     pub fn new() -> Result<Self> {
+        Self::new_with(DEFAULT_DEVICE_ID, DEFAULT_DB_FILE_PATH)
+    }
+
+    /// App core with a custom device id and DB file path, eg. to run multiple instances side by
+    /// side or to inspect the DB.
+    pub fn new_with(device_id: &str, db_file_path: &str) -> Result<Self> {
         let work_dir = tempdir()?;
         let cache_dir = work_dir.path().join("cache");
         fs::create_dir_all(cache_dir.as_path())?;
         let backend_args = CoreArgs {
-            device_id: "dev-tools".into(),
+            device_id: device_id.into(),
             device_name: "dev-tools-device-id".into(),
             cache_dir: cache_dir.to_str().expect("utf-8 path").into(),
-            db_file_path: ":memory:".into(),
+            db_file_path: db_file_path.into(),
+            default_fiat_currency: "USD".into(),
+            log_rpc: false,
+            disabled_methods: None,
+            approval_timeout_seconds: None,
+            max_decoded_calldata_bytes: None,
+            max_backup_size_bytes: None,
+            app_version: env!("CARGO_PKG_VERSION").into(),
+            db_encryption_key: None,
+            demo: false,
         };
+        let (ui_events, _) = broadcast::channel(UI_EVENTS_CAPACITY);
         let core = AppCore::new(
             backend_args,
             Box::new(CoreBackupStorageMock::new()),
-            Box::new(CoreUICallBackMock::new()),
+            Box::new(CoreUICallBackMock::with_events(ui_events.clone())),
+            Box::new(CoreTransactionInspectorMock::new()),
+            Box::new(CorePriceFeedMock::new()),
         )?;
-        Ok(Self { work_dir, core })
+        Ok(Self {
+            work_dir,
+            core,
+            ui_events,
+        })
     }
 }
 
+/// A UI callback invocation from the core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UICallbackEvent {
+    /// The name of the callback, eg. `token_transfer_result`.
+    pub kind: &'static str,
+    /// The argument of the callback.
+    pub payload: Value,
+}
+
 #[derive(Debug, Default)]
-pub struct CoreUICallBackMock {}
+pub struct CoreUICallBackMock {
+    events: Option<broadcast::Sender<UICallbackEvent>>,
+}
 
 impl CoreUICallBackMock {
     pub fn new() -> Self {
-        Self {}
+        Self { events: None }
+    }
+
+    /// Forward the callback invocations to the channel.
+    pub fn with_events(events: broadcast::Sender<UICallbackEvent>) -> Self {
+        Self {
+            events: Some(events),
+        }
+    }
+
+    fn send_event(&self, kind: &'static str, payload: Value) {
+        log::debug!("UI callback {kind}: {payload}");
+        if let Some(events) = self.events.as_ref() {
+            // Fails if there are no subscribers, which is fine.
+            let _ = events.send(UICallbackEvent { kind, payload });
+        }
     }
 }
 
 impl CoreUICallbackI for CoreUICallBackMock {
     fn sent_token_transfer(&self, result: TokenTransferResult) {
-        log::debug!("Sent token transfer: {:?}", result)
+        self.send_event("sent_token_transfer", token_transfer_json(&result))
     }
 
     fn token_transfer_result(&self, result: TokenTransferResult) {
-        log::debug!("Token transfer result: {:?}", result)
+        self.send_event("token_transfer_result", token_transfer_json(&result))
     }
 
     fn dapp_allotment_transfer_result(&self, result: DappAllotmentTransferResult) {
-        log::debug!("Dapp allotment transfer result: {:?}", result)
+        let payload = json!({
+            "dapp_identifier": result.dapp_identifier,
+            "amount": currency_amount_json(&result.amount),
+            "chain_display_name": result.chain_display_name,
+            "error_message": result.error_message,
+        });
+        self.send_event("dapp_allotment_transfer_result", payload)
     }
 
     fn signed_message_for_dapp(&self, result: DappSignatureResult) {
-        log::debug!("Dapp signature result: {:?}", result)
+        let verifying_contract = result.verifying_contract.map(|contract| {
+            json!({
+                "checksum_address": contract.checksum_address,
+                "label": contract.label,
+                "known_to_dapp": contract.known_to_dapp,
+            })
+        });
+        let payload = json!({
+            "dapp_identifier": result.dapp_identifier,
+            "verifying_contract": verifying_contract,
+            "known_schema": result.known_schema,
+        });
+        self.send_event("signed_message_for_dapp", payload)
     }
 
     fn approved_dapp_transaction(&self, result: DappTransactionApproved) {
-        log::debug!("Sent transactions for dapp result: {:?}", result)
+        let warnings: Vec<String> = result
+            .warnings
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect();
+        let payload = json!({
+            "dapp_identifier": result.dapp_identifier,
+            "chain_display_name": result.chain_display_name,
+            "amount": result.amount,
+            "token_symbol": result.token_symbol,
+            "high_value": result.high_value,
+            "warnings": warnings,
+        });
+        self.send_event("approved_dapp_transaction", payload)
     }
 
     fn dapp_transaction_result(&self, result: DappTransactionResult) {
-        log::debug!("Dapp transaction result: {:?}", result)
+        let payload = json!({
+            "dapp_identifier": result.dapp_identifier,
+            "chain_display_name": result.chain_display_name,
+            "explorer_url": result.explorer_url,
+            "error_message": result.error_message,
+        });
+        self.send_event("dapp_transaction_result", payload)
+    }
+
+    fn approval_timed_out(&self, json_rpc_request: String) {
+        let payload = json!({ "json_rpc_request": json_rpc_request });
+        self.send_event("approval_timed_out", payload)
     }
+
+    /// Tool cores have no user to authenticate, so it always succeeds.
+    fn authenticate_user(&self, reason: String) -> bool {
+        self.send_event("authenticate_user", json!({ "reason": reason }));
+        true
+    }
+}
+
+fn token_transfer_json(result: &TokenTransferResult) -> Value {
+    json!({
+        "amount": currency_amount_json(&result.amount),
+        "chain_display_name": result.chain_display_name,
+        "to_display_name": result.to_display_name,
+        "explorer_url": result.explorer_url,
+        "error_message": result.error_message,
+    })
+}
+
+fn currency_amount_json(amount: &CurrencyAmount) -> Value {
+    json!({
+        "raw": amount.raw,
+        "decimals": amount.decimals,
+        "symbol": amount.symbol,
+        "chain_id": amount.chain_id,
+        "display": amount.display(),
+    })
 }
 
 #[derive(Debug)]
@@ -89,6 +221,14 @@ impl InPageRequestContextMock {
             callbacks: Box::new(CoreInPageCallbackMock::new()),
         }
     }
+
+    /// Send the decoded responses and notifications for the page to the channel.
+    pub fn with_events(page_url: &str, events: Sender<InPageEvent>) -> Self {
+        Self {
+            page_url: page_url.into(),
+            callbacks: Box::new(CoreInPageCallbackMock::with_events(events)),
+        }
+    }
 }
 
 impl InPageRequestContextI for InPageRequestContextMock {
@@ -101,14 +241,42 @@ impl InPageRequestContextI for InPageRequestContextMock {
     }
 }
 
+/// A decoded message from the core to the in-page provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InPageEvent {
+    /// JSON-RPC response to a request.
+    Response(String),
+    /// JSON-RPC notification, eg. `accountsChanged`.
+    Notification(String),
+}
+
 #[derive(Debug, Clone)]
-pub struct CoreInPageCallbackMock {}
+pub struct CoreInPageCallbackMock {
+    // The sender is behind a mutex, because callbacks must be `Sync`.
+    events: Option<Arc<Mutex<Sender<InPageEvent>>>>,
+}
 
 impl CoreInPageCallbackMock {
     // We don't want to create the mock by accident with `Default::default`.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        Self {}
+        Self { events: None }
+    }
+
+    pub fn with_events(events: Sender<InPageEvent>) -> Self {
+        Self {
+            events: Some(Arc::new(Mutex::new(events))),
+        }
+    }
+
+    fn send_event(&self, event: InPageEvent) {
+        if let Some(events) = self.events.as_ref() {
+            let res = events.lock().expect("not poisoned").send(event);
+            // The receiver is dropped if the caller stopped waiting for events.
+            if let Err(err) = res {
+                log::debug!("Dropped in-page event: {:?}", err.0);
+            }
+        }
     }
 }
 
@@ -117,16 +285,26 @@ impl CoreInPageCallbackI for CoreInPageCallbackMock {
         log::debug!("Request dapp approval: {params:?}")
     }
 
+    fn request_dapp_transaction_approval(&self, params: DappTransactionApprovalParams) {
+        log::debug!("Request dapp transaction approval: {params:?}")
+    }
+
+    fn request_dapp_sign_batch_approval(&self, params: DappSignBatchApprovalParams) {
+        log::debug!("Request dapp sign batch approval: {params:?}")
+    }
+
     fn respond(&self, response_hex: String) {
         let response = hex::decode(response_hex).expect("valid hex");
         let response = String::from_utf8_lossy(&response);
         log::debug!("In-page callback response: '{response}'");
+        self.send_event(InPageEvent::Response(response.into()));
     }
 
     fn notify(&self, message_hex: String) {
         let event = hex::decode(message_hex).expect("valid hex");
         let event = String::from_utf8_lossy(&event);
         log::debug!("In-page callback notification: '{event}'");
+        self.send_event(InPageEvent::Notification(event.into()));
     }
 }
 
@@ -166,3 +344,41 @@ impl CoreBackupStorageI for CoreBackupStorageMock {
         false
     }
 }
+
+/// Transaction inspector that doesn't produce any warnings.
+#[derive(Debug, Clone)]
+pub struct CoreTransactionInspectorMock {}
+
+impl CoreTransactionInspectorMock {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl CoreTransactionInspectorI for CoreTransactionInspectorMock {
+    fn inspect(&self, _: InspectedTransaction) -> Vec<TransactionWarning> {
+        Default::default()
+    }
+}
+
+/// Price feed that doesn't have any prices.
+#[derive(Debug, Clone)]
+pub struct CorePriceFeedMock {}
+
+impl CorePriceFeedMock {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl CorePriceFeedI for CorePriceFeedMock {
+    fn supports_currency(&self, _: String) -> bool {
+        false
+    }
+
+    fn native_token_price(&self, _: String, _: String) -> Option<String> {
+        None
+    }
+}